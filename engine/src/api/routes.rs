@@ -18,8 +18,11 @@ use actix_files;
 use actix_multipart::Multipart;
 use actix_web::{
     delete, get,
-    http::header::{ContentDisposition, DispositionType},
-    patch, post, put, web, HttpRequest, HttpResponse, Responder,
+    http::{
+        header::{ContentDisposition, DispositionParam, DispositionType},
+        Method,
+    },
+    patch, post, put, route, web, HttpRequest, HttpResponse, Responder,
 };
 use actix_web_grants::{authorities::AuthDetails, proc_macro::protect};
 
@@ -28,10 +31,12 @@ use argon2::{
     Argon2, PasswordHasher,
 };
 use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use futures_util::StreamExt;
 use log::*;
 use path_clean::PathClean;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use sqlx::{Pool, Sqlite};
 use tokio::{fs, sync::Mutex};
 
@@ -40,33 +45,65 @@ use crate::{
     db::{
         handles,
         models::Role,
-        models::{Channel, TextPreset, User, UserMeta},
+        models::{
+            AlertRule, Channel, ConfigPreset, HistoryFilter, MediaEntry, Tag, TextPreset, User,
+            UserMeta,
+        },
+        GLOBAL_SETTINGS,
     },
     player::{
-        controller::ChannelController,
+        controller::{ChannelController, ChannelManager, ProcessUnit},
+        input::{
+            emergency::{end_emergency, start_emergency},
+            program::{switch_program, Bus, SwitchRequest},
+        },
+        output::{DelayStatus, OutputRequest, ReplayRequest},
         utils::{
-            get_data_map, get_date_range, import::import_file, sec_to_time, time_to_sec,
-            JsonPlaylist,
+            channel_state::ChannelAction,
+            freeze::{freeze_frame, FreezeRequest},
+            get_data_map, get_date_range,
+            import::import_file,
+            media_audit::audit_channel,
+            sec_to_time, time_to_sec, JsonPlaylist,
         },
     },
     utils::{
         advanced_config::AdvancedConfig,
+        alerts, archive,
+        as_run::AsRunReport,
         channels::{create_channel, delete_channel},
         config::{get_config, PlayoutConfig, Template},
-        control::{control_state, send_message, ControlParams, Process, ProcessCtl},
+        config_schema,
+        control::{
+            button_command, control_state, remote_control, send_message, ControlParams, Process,
+            ProcessCtl,
+        },
         errors::ServiceError,
+        event_history::{self, EventKind, MAX_EVENTS},
         files::{
             browser, create_directory, norm_abs_path, remove_file_or_folder, rename_file, upload,
             MoveObject, PathObject,
         },
-        logging::MailQueue,
+        log_search::{search_logs, LogQuery},
+        logging::{MailQueue, Target},
+        media_metadata::{apply_metadata, import_metadata},
         naive_date_time_from_str,
-        playlist::{delete_playlist, generate_playlist, read_playlist, write_playlist},
-        public_path, read_log_file, system, TextFilter,
+        play_history::HISTORY_TIME_FORMAT,
+        playlist::{
+            delete_playlist, generate_playlist, playlist_history, read_playlist, write_playlist,
+        },
+        public_path, public_server, read_log_file, secrets,
+        share::{self, ShareQuery, ShareRequest},
+        system,
+        uptime::monthly_report,
+        TextFilter,
     },
     vec_strings,
 };
 
+const MAX_PREFERENCES: usize = 100;
+const MAX_PREFERENCE_SIZE: usize = 16 * 1024;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DateObj {
     #[serde(default)]
@@ -94,6 +131,12 @@ pub struct ImportObj {
     date: String,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TemplateObj {
+    #[serde(default)]
+    template: Option<i32>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ProgramObj {
     #[serde(default = "time_after", deserialize_with = "naive_date_time_from_str")]
@@ -120,6 +163,88 @@ fn time_before() -> NaiveDateTime {
         .naive_local()
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct TagsObj {
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    all: bool,
+}
+
+/// Tag names are stored comma separated in queries, so a comma is not allowed.
+fn valid_tag(name: &str) -> Result<String, ServiceError> {
+    let name = name.trim();
+
+    if name.is_empty() || name.contains(',') || name.contains(';') {
+        return Err(ServiceError::BadRequest(format!(
+            "Invalid tag name: \"{name}\""
+        )));
+    }
+
+    Ok(name.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryObj {
+    start: Option<String>,
+    end: Option<String>,
+    at: Option<String>,
+    search: Option<String>,
+    #[serde(default = "default_page")]
+    page: u32,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventObj {
+    #[serde(default)]
+    since: i64,
+    #[serde(default = "default_event_limit")]
+    limit: u32,
+}
+
+fn default_event_limit() -> u32 {
+    100
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AsRunObj {
+    start: Option<String>,
+    end: Option<String>,
+    #[serde(default)]
+    format: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportObj {
+    month: Option<String>,
+    #[serde(default)]
+    format: String,
+}
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_limit() -> u32 {
+    50
+}
+
+/// Parse time from query, in the same formats as the program range.
+fn history_time(time: &Option<String>) -> Result<Option<String>, ServiceError> {
+    time.as_ref()
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            naive_date_time_from_str(Value::String(t.clone()))
+                .map(|d| d.format(HISTORY_TIME_FORMAT).to_string())
+                .map_err(|e| ServiceError::BadRequest(format!("Invalid time {t}: {e}")))
+        })
+        .transpose()
+}
+
 #[derive(Debug, Serialize)]
 struct ProgramItem {
     source: String,
@@ -202,6 +327,124 @@ async fn get_user(
     }
 }
 
+fn preferences_map(rows: Vec<(String, String)>) -> Map<String, Value> {
+    rows.into_iter()
+        .map(|(key, value)| {
+            let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            (key, value)
+        })
+        .collect()
+}
+
+/// **Get Preferences**
+///
+/// Settings from the current user, like default channel or UI settings, as key-value object.
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/api/preferences' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/preferences")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role"
+)]
+async fn get_preferences(
+    pool: web::Data<Pool<Sqlite>>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let rows = handles::select_preferences(&pool, user.id).await?;
+
+    Ok(web::Json(preferences_map(rows)))
+}
+
+/// **Update Preferences**
+///
+/// Given keys are added or replaced, keys with `null` are removed. Values can be any JSON.
+/// Keys can have up to 64 letters, numbers, `_`, `-` and `.`; a user can have up to 100 keys
+/// and a value can not be bigger than 16 KB.
+///
+/// ```BASH
+/// curl -X PUT 'http://127.0.0.1:8787/api/preferences' -H 'Content-Type: application/json' \
+/// -d '{ "default_channel": 2, "ui.theme": "dark", "notify.errors": null }' \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[put("/preferences")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role"
+)]
+async fn update_preferences(
+    pool: web::Data<Pool<Sqlite>>,
+    data: web::Json<Map<String, Value>>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let key_regex = Regex::new(r"^[A-Za-z0-9_.-]{1,64}$").unwrap();
+    let mut keys: Vec<String> = handles::select_preferences(&pool, user.id)
+        .await?
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    let mut preferences = vec![];
+
+    for (key, value) in data.into_inner() {
+        if !key_regex.is_match(&key) {
+            return Err(ServiceError::BadRequest(format!(
+                "Invalid preference key: {key}"
+            )));
+        }
+
+        if value.is_null() {
+            keys.retain(|k| *k != key);
+            preferences.push((key, None));
+        } else {
+            let value = serde_json::to_string(&value)?;
+
+            if value.len() > MAX_PREFERENCE_SIZE {
+                return Err(ServiceError::BadRequest(format!(
+                    "Preference {key} is too big"
+                )));
+            }
+
+            if !keys.contains(&key) {
+                keys.push(key.clone());
+            }
+
+            preferences.push((key, Some(value)));
+        }
+    }
+
+    if keys.len() > MAX_PREFERENCES {
+        return Err(ServiceError::BadRequest(format!(
+            "Only {MAX_PREFERENCES} preferences are allowed"
+        )));
+    }
+
+    handles::update_preferences(&pool, user.id, &preferences).await?;
+    let rows = handles::select_preferences(&pool, user.id).await?;
+
+    Ok(web::Json(preferences_map(rows)))
+}
+
+/// **Delete Preference**
+///
+/// ```BASH
+/// curl -X DELETE 'http://127.0.0.1:8787/api/preferences/ui.theme' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[delete("/preferences/{key}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role"
+)]
+async fn delete_preference(
+    pool: web::Data<Pool<Sqlite>>,
+    key: web::Path<String>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    handles::update_preferences(&pool, user.id, &[(key.into_inner(), None)]).await?;
+
+    Ok("Delete preference Success")
+}
+
 /// **Get User by ID**
 ///
 /// ```BASH
@@ -461,11 +704,15 @@ async fn patch_channel(
 /// -d '{ "name": "Channel 2", "preview_url": "http://localhost/live/channel2.m3u8", "extra_extensions": "jpg,jpeg,png" }' \
 /// -H "Authorization: Bearer <TOKEN>"
 /// ```
+///
+/// With the optional query `?template=1` the configuration is copied from channel 1,
+/// paths from the template channel get replaced by the paths of the new channel.
 #[post("/channel/")]
 #[protect("Role::GlobalAdmin", ty = "Role")]
 async fn add_channel(
     pool: web::Data<Pool<Sqlite>>,
     data: web::Json<Channel>,
+    obj: web::Query<TemplateObj>,
     controllers: web::Data<Mutex<ChannelController>>,
     queue: web::Data<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
 ) -> Result<impl Responder, ServiceError> {
@@ -474,6 +721,7 @@ async fn add_channel(
         controllers.into_inner(),
         queue.into_inner(),
         data.into_inner(),
+        obj.template,
     )
     .await
     {
@@ -516,18 +764,13 @@ async fn remove_channel(
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
 async fn get_advanced_config(
+    pool: web::Data<Pool<Sqlite>>,
     id: web::Path<i32>,
-    controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
 ) -> Result<impl Responder, ServiceError> {
-    let manager = controllers
-        .lock()
-        .await
-        .get(*id)
-        .await
-        .ok_or_else(|| ServiceError::BadRequest(format!("Channel ({id}) not exists!")))?;
-    let config = manager.config.lock().await.advanced.clone();
+    // read from database, the running config has the output override already applied
+    let config = AdvancedConfig::new(handles::select_advanced_configuration(&pool, *id).await?);
 
     Ok(web::Json(config))
 }
@@ -560,6 +803,7 @@ async fn update_advanced_config(
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
 
+    data.validate()?;
     handles::update_advanced_configuration(&pool, *id, data.clone()).await?;
     let new_config = get_config(&pool, *id).await?;
 
@@ -568,6 +812,22 @@ async fn update_advanced_config(
     Ok(web::Json("Update success"))
 }
 
+/// **Get Config Schema**
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/config/schema -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// Response is a JSON Schema from the playout config, fields with `"x-restart": true` restart decoder and encoder of a running channel.
+#[get("/config/schema")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role"
+)]
+async fn get_config_schema() -> Result<impl Responder, ServiceError> {
+    Ok(web::Json(config_schema::playout_config_schema()))
+}
+
 /// **Get Config**
 ///
 /// ```BASH
@@ -593,7 +853,8 @@ async fn get_playout_config(
         .get(*id)
         .await
         .ok_or_else(|| ServiceError::BadRequest(format!("Channel ({id}) not exists!")))?;
-    let config = manager.config.lock().await.clone();
+    let mut config = manager.config.lock().await.clone();
+    config.redact();
 
     Ok(web::Json(config))
 }
@@ -604,6 +865,11 @@ async fn get_playout_config(
 /// curl -X PUT http://127.0.0.1:8787/api/playout/config/1 -H "Content-Type: application/json" \
 /// -d { <CONFIG DATA> } -H 'Authorization: Bearer <TOKEN>'
 /// ```
+///
+/// Changes are applied to the running channel immediately, `applied` in the response tells how:
+/// `live` when the running filters got the new text style, font or volume, `reload` when the current clip is played again,
+/// `restart` when decoder and encoder are started again for encoder changes, and `saved` when the channel doesn't run.
+/// `warnings` lists settings which don't work well together, like stream copy with a logo, the config is saved anyway.
 #[allow(clippy::too_many_arguments)]
 #[put("/playout/config/{id}")]
 #[protect(
@@ -628,10 +894,23 @@ async fn update_playout_config(
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
     let p = manager.channel.lock().await.storage.clone();
     let storage = Path::new(&p);
-    let config_id = manager.config.lock().await.general.id;
+    let stored = manager.config.lock().await.clone();
+    let config_id = stored.general.id;
+
+    data.restore_secrets(&stored);
+
+    if !role.has_authority(&Role::GlobalAdmin) {
+        let locked = handles::select_locked_fields(&pool, *id).await?;
+        data.keep_locked(&stored, &locked)?;
+    }
+
+    data.check_weekday_overrides()?;
 
     let (_, _, logo) = norm_abs_path(storage, &data.processing.logo)?;
-    let (_, _, filler) = norm_abs_path(storage, &data.storage.filler)?;
+    let (_, _, filler) = norm_abs_path(
+        stored.channel.filler_root(data.storage.shared_filler),
+        &data.storage.filler,
+    )?;
     let (_, _, font) = norm_abs_path(storage, &data.text.font)?;
 
     data.processing.logo = logo;
@@ -647,7 +926,7 @@ async fn update_playout_config(
 
         if queue_lock.id == *id {
             if queue_lock.config.recipient != new_config.mail.recipient {
-                queue_lock.clear_raw();
+                queue_lock.clear_sent();
             }
 
             queue_lock.update(new_config.mail.clone());
@@ -655,9 +934,230 @@ async fn update_playout_config(
         }
     }
 
-    manager.update_config(new_config).await;
+    let warnings = new_config.warnings();
+    let applied = manager.update_config(new_config).await;
 
-    Ok(web::Json("Update success"))
+    Ok(web::Json(
+        json!({ "applied": applied, "warnings": warnings }),
+    ))
+}
+
+/// **Get Locked Config Fields**
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/playout/config/1/locks -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// Response is a list of config fields, which only global admins can change.
+#[get("/playout/config/{id}/locks")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_config_locks(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let locked = handles::select_locked_fields(&pool, *id).await?;
+
+    Ok(web::Json(locked))
+}
+
+/// **Update Locked Config Fields**
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/playout/config/1/locks -H "Content-Type: application/json" \
+/// -d '["output.mode", "output.output_param", "logging"]' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// Fields are written as `section.field`, or only `section` to lock the whole section.
+/// Changes from other users on locked fields are ignored, an empty list removes all locks.
+#[put("/playout/config/{id}/locks")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+async fn update_config_locks(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<Vec<String>>,
+) -> Result<impl Responder, ServiceError> {
+    let schema = config_schema::playout_config_schema();
+
+    for field in data.iter() {
+        let (section, key) = match field.split_once('.') {
+            Some((section, key)) => (section, Some(key)),
+            None => (field.as_str(), None),
+        };
+        let properties = &schema["properties"][section];
+
+        if !properties.is_object() || key.is_some_and(|k| !properties["properties"][k].is_object())
+        {
+            return Err(ServiceError::BadRequest(format!(
+                "Unknown config field: {field}"
+            )));
+        }
+    }
+
+    handles::update_locked_fields(&pool, *id, &data).await?;
+
+    Ok("Update locked fields success")
+}
+
+/// #### Config Presets
+///
+/// Config presets are named sets of processing and output parameters, like *YouTube 1080p*,
+/// which can be applied to any channel.
+///
+/// **Get all Config Presets**
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/config/presets -H 'Content-Type: application/json' \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/config/presets")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+async fn get_config_presets(pool: web::Data<Pool<Sqlite>>) -> Result<impl Responder, ServiceError> {
+    match handles::select_config_presets(&pool).await {
+        Ok(mut presets) => {
+            for preset in &mut presets {
+                preset.output_param = secrets::redact(&preset.output_param);
+            }
+
+            Ok(web::Json(presets))
+        }
+        Err(e) => {
+            error!("{e}");
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// **Add Config Preset**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/config/presets -H 'Content-Type: application/json' \
+/// -d '{ "name": "YouTube 1080p", "processing_width": 1920, "processing_height": 1080, "processing_aspect": 1.778, "processing_fps": 25, "processing_audio_channels": 2, "output_mode": "stream", "output_param": "<PARAMETERS>" }' \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/config/presets")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+async fn add_config_preset(
+    pool: web::Data<Pool<Sqlite>>,
+    data: web::Json<ConfigPreset>,
+) -> Result<impl Responder, ServiceError> {
+    if handles::select_config_preset(&pool, &data.name)
+        .await
+        .is_ok()
+    {
+        return Err(ServiceError::Conflict(format!(
+            "Config preset \"{}\" already exists!",
+            data.name
+        )));
+    }
+
+    data.mode()?;
+
+    handles::insert_config_preset(&pool, data.into_inner()).await?;
+
+    Ok("Add config preset Success")
+}
+
+/// **Update Config Preset**
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/config/presets/1 -H 'Content-Type: application/json' \
+/// -d '{ <PRESET DATA> }' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[put("/config/presets/{id}")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+async fn update_config_preset(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<ConfigPreset>,
+) -> Result<impl Responder, ServiceError> {
+    let mut preset = data.into_inner();
+    preset.mode()?;
+
+    if let Some(stored) = handles::select_config_presets(&pool)
+        .await?
+        .into_iter()
+        .find(|p| p.id == *id)
+    {
+        preset.output_param = secrets::restore(&preset.output_param, &stored.output_param);
+    }
+
+    handles::update_config_preset(&pool, *id, preset).await?;
+
+    Ok("Update config preset Success")
+}
+
+/// **Delete Config Preset**
+///
+/// ```BASH
+/// curl -X DELETE http://127.0.0.1:8787/api/config/presets/1 -H 'Content-Type: application/json' \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[delete("/config/presets/{id}")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+async fn delete_config_preset(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+) -> Result<impl Responder, ServiceError> {
+    handles::delete_config_preset(&pool, *id).await?;
+
+    Ok("Delete config preset Success")
+}
+
+/// **Apply Config Preset to Channel**
+///
+/// ```BASH
+/// curl -X PUT 'http://127.0.0.1:8787/api/playout/config/1/preset/YouTube%201080p' \
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// Response is the same as from **Update Config**.
+#[put("/playout/config/{id}/preset/{name}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin"),
+    ty = "Role",
+    expr = "user.channels.contains(&path.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn apply_config_preset(
+    pool: web::Data<Pool<Sqlite>>,
+    path: web::Path<(i32, String)>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let (id, name) = path.into_inner();
+    let manager = controllers
+        .lock()
+        .await
+        .get(id)
+        .await
+        .ok_or_else(|| ServiceError::BadRequest(format!("Channel ({id}) not exists!")))?;
+    let preset = handles::select_config_preset(&pool, &name)
+        .await
+        .map_err(|_| ServiceError::BadRequest(format!("Config preset \"{name}\" not found!")))?;
+    let stored = manager.config.lock().await.clone();
+    let mut config = stored.clone();
+
+    preset.apply(&mut config)?;
+
+    if !role.has_authority(&Role::GlobalAdmin) {
+        let locked = handles::select_locked_fields(&pool, id).await?;
+        config.keep_locked(&stored, &locked)?;
+    }
+
+    handles::update_configuration(&pool, stored.general.id, config).await?;
+    let new_config = get_config(&pool, id).await?;
+    let warnings = new_config.warnings();
+    let applied = manager.update_config(new_config).await;
+
+    Ok(web::Json(
+        json!({ "applied": applied, "warnings": warnings }),
+    ))
 }
 
 /// #### Text Presets
@@ -778,21 +1278,314 @@ async fn delete_preset(
     Err(ServiceError::InternalServerError)
 }
 
-/// ### ffplayout controlling
+/// #### Media Tags
 ///
-/// here we communicate with the engine for:
-/// - jump to last or next clip
-/// - reset playlist state
-/// - get infos about current, next, last clip
-/// - send text to the engine, for overlaying it (as lower third etc.)
+/// Tags can be added to media files, to filter them in playlist templates and in folder mode.
+/// Paths are relative to the channel storage.
 ///
-/// **Send Text to ffplayout**
+/// **Get all Tags**
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/control/1/text/ \
+/// curl -X GET http://127.0.0.1:8787/api/tags/1 -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/tags/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_tags(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let tags = handles::select_tags(&pool, *id).await?;
+
+    Ok(web::Json(tags))
+}
+
+/// **Add new Tag**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/tags/1/ -H 'Content-Type: application/json' \
+/// -d '{ "name": "news" }' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/tags/{id}/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn add_tag(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<Tag>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let name = valid_tag(&data.name)?;
+
+    match handles::insert_tag(&pool, *id, &name).await {
+        Ok(tag) => Ok(web::Json(tag)),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(ServiceError::Conflict(
+            format!("Tag \"{name}\" already exists!"),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// **Rename Tag**
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/tags/1/1 -H 'Content-Type: application/json' \
+/// -d '{ "name": "sports" }' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[put("/tags/{channel}/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&path.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn update_tag(
+    pool: web::Data<Pool<Sqlite>>,
+    path: web::Path<(i32, i32)>,
+    data: web::Json<Tag>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let (channel, id) = path.into_inner();
+    let name = valid_tag(&data.name)?;
+
+    match handles::update_tag(&pool, channel, id, &name).await {
+        Ok(result) if result.rows_affected() > 0 => Ok("Update Success"),
+        Ok(_) => Err(ServiceError::NoContent("Tag not found".to_string())),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(ServiceError::Conflict(
+            format!("Tag \"{name}\" already exists!"),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// **Delete Tag**
+///
+/// The tag is also removed from all media files.
+///
+/// ```BASH
+/// curl -X DELETE http://127.0.0.1:8787/api/tags/1/1 -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[delete("/tags/{channel}/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&path.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn delete_tag(
+    pool: web::Data<Pool<Sqlite>>,
+    path: web::Path<(i32, i32)>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let (channel, id) = path.into_inner();
+
+    handles::delete_tag(&pool, channel, id).await?;
+
+    Ok("Delete tag Success")
+}
+
+/// **Get Tags from Media File**
+///
+/// ```BASH
+/// curl -X GET "http://127.0.0.1:8787/api/media/1/tags?path=folder/clip.mp4" \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/media/{id}/tags")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_media_tags(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<TagsObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let storage = manager.config.lock().await.channel.storage.clone();
+    let (_, _, path) = norm_abs_path(&storage, &obj.path)?;
+
+    let media = handles::select_media(&pool, *id, &path)
+        .await?
+        .unwrap_or(MediaEntry {
+            path,
+            ..Default::default()
+        });
+
+    Ok(web::Json(media))
+}
+
+/// **Set Tags from Media File**
+///
+/// Replace all tags from the file, missing tags are created. An empty list removes all tags.
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/media/1/tags -H 'Content-Type: application/json' \
+/// -d '{ "path": "folder/clip.mp4", "tags": ["news", "sports"] }' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[put("/media/{id}/tags")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn update_media_tags(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<MediaEntry>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let storage = manager.config.lock().await.channel.storage.clone();
+    let (abs_path, _, path) = norm_abs_path(&storage, &data.path)?;
+
+    if !abs_path.is_file() {
+        return Err(ServiceError::BadRequest("File not exists!".to_string()));
+    }
+
+    let mut tags = data
+        .tags
+        .iter()
+        .map(|t| valid_tag(t))
+        .collect::<Result<Vec<_>, _>>()?;
+    tags.sort();
+    tags.dedup();
+
+    handles::update_media_tags(&pool, *id, &path, &tags).await?;
+
+    let media = handles::select_media(&pool, *id, &path)
+        .await?
+        .unwrap_or(MediaEntry {
+            path,
+            ..Default::default()
+        });
+
+    Ok(web::Json(media))
+}
+
+/// **Import Media Metadata**
+///
+/// Import title, category and custom fields for the media files from a CSV file. The rows are
+/// matched by the `filename` or `house_id` column, all columns besides `title` and `category`
+/// are stored as custom fields. Empty cells keep the stored values.
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/media/1/metadata -H 'Content-Type: text/csv' \
+/// --data-binary @metadata.csv -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// **Response:**
+///
+/// ```JSON
+/// { "rows": 120, "imported": 118, "skipped": [[14, "no file for \"AD1002\""]] }
+/// ```
+#[put("/media/{id}/metadata")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn import_media_metadata(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: String,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+    let report = import_metadata(&pool, &config, &data).await?;
+
+    Ok(web::Json(report))
+}
+
+/// **Search Media by Tags**
+///
+/// Tags are comma separated, files with one of the tags are returned.
+/// With `all=true` only files which have all tags. Without tags all tagged files are returned.
+///
+/// ```BASH
+/// curl -X GET "http://127.0.0.1:8787/api/media/1?tags=news,sports&all=true" \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/media/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn search_media(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<TagsObj>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let tags: Vec<String> = obj
+        .tags
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let media = handles::select_tagged_media(&pool, *id, &tags, obj.all).await?;
+
+    Ok(web::Json(media))
+}
+
+/// ### ffplayout controlling
+///
+/// here we communicate with the engine for:
+/// - jump to last or next clip
+/// - reset playlist state
+/// - get infos about current, next, last clip
+/// - send text to the engine, for overlaying it (as lower third etc.)
+///
+/// **Send Text to ffplayout**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/control/1/text/ \
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>' \
 /// -d '{"text": "Hello from ffplayout", "x": "(w-text_w)/2", "y": "(h-text_h)/2", fontsize": "24", "line_spacing": "4", "fontcolor": "#ffffff", "box": "1", "boxcolor": "#000000", "boxborderw": "4", "alpha": "1.0"}'
 /// ```
+///
+/// With `text.layers`, like `lower_third,ticker`, the channel has more text layers next to the default one, `layer` selects it. `font` is a font file relative to the storage. `animation_in` and `animation_out` are `fade`, `slide_left`, `slide_right`, `slide_top` or `slide_bottom`, slides come in from the edge and go out to it, `duration` is the length of the animation in seconds (default 0.5). After `expire` seconds the text goes out again. A new text for a layer stops its running animation.
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/control/1/text/ \
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>' \
+/// -d '{"text": "Jane Doe - Reporter", "layer": "lower_third", "font": "fonts/Inter.ttf", "x": "80", "y": "h-160", "fontsize": "42", "animation_in": "slide_left", "animation_out": "fade", "duration": 0.6, "expire": 8}'
+/// ```
 #[post("/control/{id}/text/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
@@ -850,6 +1643,13 @@ pub async fn control_playout(
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
 
+    manager
+        .state
+        .lock()
+        .await
+        .state
+        .check(ChannelAction::Playout)?;
+
     if manager.is_processing.load(Ordering::SeqCst) {
         return Err(ServiceError::Conflict(
             "A command is already being processed, please wait".to_string(),
@@ -915,28 +1715,39 @@ pub async fn media_current(
     Ok(web::Json(media_map))
 }
 
-/// #### ffplayout Process Control
+/// **Get Encoder Stats**
 ///
-/// Control ffplayout process, like:
-/// - start
-/// - stop
-/// - restart
-/// - status
+/// Progress from the encoder, updated every half second. `updated` is `null` until the encoder
+/// reported the first time, in desktop mode there are no stats.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/control/1/process/
+/// curl -X GET http://127.0.0.1:8787/api/control/1/encoder/stats
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
-/// -d '{"command": "start"}'
 /// ```
-#[post("/control/{id}/process/")]
+///
+/// **Response:**
+///
+/// ```JSON
+///     {
+///       "frame": 90125,
+///       "fps": 25.0,
+///       "bitrate": 4512.3,
+///       "total_size": 2033401856,
+///       "out_time": 3605.0,
+///       "speed": 1.0,
+///       "dup_frames": 0,
+///       "drop_frames": 12,
+///       "updated": "2024-05-20T06:00:01.123456+02:00"
+///     }
+/// ```
+#[get("/control/{id}/encoder/stats")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn process_control(
+pub async fn encoder_stats(
     id: web::Path<i32>,
-    proc: web::Json<Process>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -947,67 +1758,28 @@ pub async fn process_control(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    manager.list_init.store(true, Ordering::SeqCst);
-
-    if manager.is_processing.load(Ordering::SeqCst) {
-        return Err(ServiceError::Conflict(
-            "A command is already being processed, please wait".to_string(),
-        ));
-    }
-
-    manager.is_processing.store(true, Ordering::SeqCst);
-
-    match proc.into_inner().command {
-        ProcessCtl::Status => {
-            manager.is_processing.store(false, Ordering::SeqCst);
-
-            if manager.is_alive.load(Ordering::SeqCst) {
-                return Ok(web::Json("active"));
-            }
-            return Ok(web::Json("not running"));
-        }
-        ProcessCtl::Start => {
-            if !manager.is_alive.load(Ordering::SeqCst) {
-                manager.channel.lock().await.active = true;
-                manager.start().await?;
-            }
-        }
-        ProcessCtl::Stop => {
-            manager.channel.lock().await.active = false;
-            manager.stop_all(true).await?;
-        }
-        ProcessCtl::Restart => {
-            manager.stop_all(false).await?;
-            tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
-
-            if !manager.is_alive.load(Ordering::SeqCst) {
-                manager.start().await?;
-            }
-        }
-    }
-
-    manager.is_processing.store(false, Ordering::SeqCst);
+    let stats = manager.encoder_stats.lock().await.clone();
 
-    Ok(web::Json("Success"))
+    Ok(web::Json(stats))
 }
 
-/// #### ffplayout Playlist Operations
+/// **Get Output Health**
 ///
-/// **Get playlist**
+/// Connection from the encoder to the streaming target, with the availability in percent
+/// over the last 24 hours and the latest disconnects.
 ///
 /// ```BASH
-/// curl -X GET http://127.0.0.1:8787/api/playlist/1?date=2022-06-20
+/// curl -X GET http://127.0.0.1:8787/api/control/1/output/health
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[get("/playlist/{id}")]
+#[get("/control/{id}/output/health")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn get_playlist(
+pub async fn output_health(
     id: web::Path<i32>,
-    obj: web::Query<DateObj>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1018,30 +1790,28 @@ pub async fn get_playlist(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
+    let report = manager.output_health.lock().await.report(Local::now());
 
-    match read_playlist(&config, obj.date.clone()).await {
-        Ok(playlist) => Ok(web::Json(playlist)),
-        Err(e) => Err(e),
-    }
+    Ok(web::Json(report))
 }
 
-/// **Save playlist**
+/// **Get Process Supervision**
+///
+/// Starts, failed starts in a row and the backoff for the decoder, encoder and ingest processes,
+/// with the channel restarts from the restart policy.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/playlist/1/
+/// curl -X GET http://127.0.0.1:8787/api/control/1/processes
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
-/// --data "{<JSON playlist data>}"
 /// ```
-#[post("/playlist/{id}/")]
+#[get("/control/{id}/processes")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn save_playlist(
+pub async fn process_status(
     id: web::Path<i32>,
-    data: web::Json<JsonPlaylist>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1052,148 +1822,188 @@ pub async fn save_playlist(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
+    let status = manager.supervisor.lock().await.clone();
 
-    match write_playlist(&config, data.into_inner()).await {
-        Ok(res) => Ok(web::Json(res)),
-        Err(e) => Err(e),
-    }
+    Ok(web::Json(status))
 }
 
-/// **Generate Playlist**
+/// **Get Channel State**
 ///
-/// A new playlist will be generated and response.
+/// State of the channel, the one before and since when it is set. The states are `stopped`,
+/// `starting`, `on_air_playlist`, `on_air_live`, `fallback`, `stopping` and `failed`.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/playlist/1/generate/2022-06-20
+/// curl -X GET http://127.0.0.1:8787/api/control/1/state
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
-/// /// --data '{ "paths": [<list of paths>] }' # <- data is optional
 /// ```
+#[get("/control/{id}/state")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn channel_status(
+    id: web::Path<i32>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let state = manager.state.lock().await.clone();
+
+    Ok(web::Json(state))
+}
+
+/// **Get Outputs**
+///
+/// Renditions from the output config, if they are stopped by the operator and if their
+/// encoder runs.
 ///
-/// Or with template:
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/playlist/1/generate/2023-00-05
+/// curl -X GET http://127.0.0.1:8787/api/control/1/outputs
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
-/// --data '{"template": {"sources": [\
-///            {"start": "00:00:00", "duration": "10:00:00", "shuffle": true, "paths": ["path/1", "path/2"]}, \
-///            {"start": "10:00:00", "duration": "14:00:00", "shuffle": false, "paths": ["path/3", "path/4"]}]}}'
 /// ```
-#[post("/playlist/{id}/generate/{date}")]
+#[get("/control/{id}/outputs")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
-    expr = "user.channels.contains(&params.0) || role.has_authority(&Role::GlobalAdmin)"
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn gen_playlist(
-    params: web::Path<(i32, String)>,
-    data: Option<web::Json<PathsObj>>,
+pub async fn output_status(
+    id: web::Path<i32>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
 ) -> Result<impl Responder, ServiceError> {
-    let manager = controllers.lock().await.get(params.0).await.unwrap();
-    manager.config.lock().await.general.generate = Some(vec![params.1.clone()]);
-    let storage = manager.config.lock().await.channel.storage.clone();
-
-    if let Some(obj) = data {
-        if let Some(paths) = &obj.paths {
-            let mut path_list = vec![];
-
-            for path in paths {
-                let (p, _, _) = norm_abs_path(&storage, path)?;
-
-                path_list.push(p);
-            }
-
-            manager.config.lock().await.storage.paths = path_list;
-        }
-
-        manager
-            .config
-            .lock()
-            .await
-            .general
-            .template
-            .clone_from(&obj.template);
-    }
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+    let status = manager.outputs.status(&config).await;
 
-    match generate_playlist(manager).await {
-        Ok(playlist) => Ok(web::Json(playlist)),
-        Err(e) => Err(e),
-    }
+    Ok(web::Json(status))
 }
 
-/// **Delete Playlist**
+/// **Control Output**
+///
+/// Start or stop one rendition, the main output and the other renditions go on.
 ///
 /// ```BASH
-/// curl -X DELETE http://127.0.0.1:8787/api/playlist/1/2022-06-20
+/// curl -X POST http://127.0.0.1:8787/api/control/1/outputs/public/
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// -d '{"command": "stop"}'
 /// ```
-#[delete("/playlist/{id}/{date}")]
+#[post("/control/{id}/outputs/{name}/")]
 #[protect(
-    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    any("Role::GlobalAdmin", "Role::ChannelAdmin"),
     ty = "Role",
-    expr = "user.channels.contains(&params.0) || role.has_authority(&Role::GlobalAdmin)"
+    expr = "user.channels.contains(&path.0) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn del_playlist(
-    params: web::Path<(i32, String)>,
+pub async fn output_control(
+    path: web::Path<(i32, String)>,
+    data: web::Json<OutputRequest>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
 ) -> Result<impl Responder, ServiceError> {
-    let manager = controllers.lock().await.get(params.0).await.unwrap();
+    let (id, name) = path.into_inner();
+    let manager = controllers
+        .lock()
+        .await
+        .get(id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
     let config = manager.config.lock().await.clone();
 
-    match delete_playlist(&config, &params.1).await {
-        Ok(m) => Ok(web::Json(m)),
-        Err(e) => Err(e),
+    if !config.output.renditions.iter().any(|r| r.name == name) {
+        return Err(ServiceError::BadRequest(format!(
+            "Output \"{name}\" not found, the main output runs with the channel"
+        )));
     }
+
+    let enabled = match data.command {
+        ProcessCtl::Status => !manager.outputs.is_stopped(&name),
+        ProcessCtl::Start | ProcessCtl::Stop => {
+            let enabled = data.command == ProcessCtl::Start;
+
+            if manager.outputs.set(&name, enabled) {
+                event_history::push(
+                    id,
+                    EventKind::Output,
+                    format!("Output {name} {}", data.command),
+                );
+            }
+
+            enabled
+        }
+        ProcessCtl::Restart => {
+            return Err(ServiceError::BadRequest(
+                "Outputs can only be started or stopped".to_string(),
+            ))
+        }
+    };
+
+    Ok(web::Json(if enabled { "active" } else { "stopped" }))
 }
 
-/// ### Log file
+/// **Get Delay Buffer**
 ///
-/// **Read Log File**
+/// Configured delay, the seconds in the buffer and if the filler plays after a dump.
 ///
 /// ```BASH
-/// curl -X GET http://127.0.0.1:8787/api/log/1?date=2022-06-20
+/// curl -X GET http://127.0.0.1:8787/api/control/1/delay
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[get("/log/{id}")]
+#[get("/control/{id}/delay")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn get_log(
+pub async fn delay_status(
     id: web::Path<i32>,
-    // controllers: web::Data<Mutex<ChannelController>>,
-    log: web::Query<DateObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
 ) -> Result<impl Responder, ServiceError> {
-    // let manager = controllers.lock().await.get(*id).await.ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    // let timezone = manager.config.lock().await.channel.timezone;
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let delay = manager.delay.lock().await.clone();
 
-    read_log_file(&id, &log.date).await
+    match delay {
+        Some(delay) => Ok(web::Json(delay.status().await?)),
+        None => Ok(web::Json(DelayStatus::default())),
+    }
 }
 
-/// ### File Operations
+/// **Dump Delay Buffer**
 ///
-/// **Get File/Folder List**
+/// Drop everything in the delay buffer, the filler plays until the program is back with the full delay.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/file/1/browse/ -H 'Content-Type: application/json'
-/// -d '{ "source": "/" }' -H 'Authorization: Bearer <TOKEN>'
+/// curl -X POST http://127.0.0.1:8787/api/control/1/delay/dump/
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[post("/file/{id}/browse/")]
+#[post("/control/{id}/delay/dump/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn file_browser(
+pub async fn delay_dump(
     id: web::Path<i32>,
-    data: web::Json<PathObject>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1204,60 +2014,79 @@ pub async fn file_browser(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let channel = manager.channel.lock().await.clone();
-    let config = manager.config.lock().await.clone();
+    let delay = manager
+        .delay
+        .lock()
+        .await
+        .clone()
+        .ok_or(ServiceError::Conflict(
+            "Delay buffer is not running".to_string(),
+        ))?;
+    let dropped = delay.dump().await?;
 
-    match browser(&config, &channel, &data.into_inner()).await {
-        Ok(obj) => Ok(web::Json(obj)),
-        Err(e) => Err(e),
-    }
+    Ok(web::Json(json!({ "dropped": dropped })))
 }
 
-/// **Create Folder**
+/// **Instant Replay**
+///
+/// Replay a window from the recorded program, `from` is the start in seconds before now.
+/// `speed` is optional, below 1.0 the replay runs in slow motion without sound.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/file/1/create-folder/ -H 'Content-Type: application/json'
-/// -d '{"source": "<FOLDER PATH>"}' -H 'Authorization: Bearer <TOKEN>'
+/// curl -X POST http://127.0.0.1:8787/api/control/1/replay/
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// -d '{"from": 30, "duration": 10, "speed": 0.5}'
 /// ```
-#[post("/file/{id}/create-folder/")]
+#[post("/control/{id}/replay/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn add_dir(
+pub async fn instant_replay(
     id: web::Path<i32>,
-    data: web::Json<PathObject>,
+    data: web::Json<ReplayRequest>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
-) -> Result<HttpResponse, ServiceError> {
+) -> Result<impl Responder, ServiceError> {
     let manager = controllers
         .lock()
         .await
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
+    let replay = manager
+        .replay
+        .lock()
+        .await
+        .clone()
+        .ok_or(ServiceError::Conflict(
+            "Program is not recorded for replays".to_string(),
+        ))?;
+    let info = replay.start(&manager, &data.into_inner()).await?;
 
-    create_directory(&config, &data.into_inner()).await
+    Ok(web::Json(info))
 }
 
-/// **Rename File**
+/// **Freeze Frame**
+///
+/// Hold the last frame with silent audio for `duration` seconds, then go on with the schedule.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/file/1/rename/ -H 'Content-Type: application/json'
-/// -d '{"source": "<SOURCE>", "target": "<TARGET>"}' -H 'Authorization: Bearer <TOKEN>'
+/// curl -X POST http://127.0.0.1:8787/api/control/1/freeze/
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// -d '{"duration": 10}'
 /// ```
-#[post("/file/{id}/rename/")]
+#[post("/control/{id}/freeze/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn move_rename(
+pub async fn freeze_hold(
     id: web::Path<i32>,
-    data: web::Json<MoveObject>,
+    data: web::Json<FreezeRequest>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1268,29 +2097,27 @@ pub async fn move_rename(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
+    let info = freeze_frame(&manager, &data.into_inner()).await?;
 
-    match rename_file(&config, &data.into_inner()).await {
-        Ok(obj) => Ok(web::Json(obj)),
-        Err(e) => Err(e),
-    }
+    Ok(web::Json(info))
 }
 
-/// **Remove File/Folder**
+/// **Emergency Slate**
+///
+/// Switch at once to the emergency slate from `storage.emergency_slate`, it plays until the end call.
 ///
 /// ```BASH
-/// curl -X POST http://127.0.0.1:8787/api/file/1/remove/ -H 'Content-Type: application/json'
-/// -d '{"source": "<SOURCE>"}' -H 'Authorization: Bearer <TOKEN>'
+/// curl -X POST http://127.0.0.1:8787/api/control/1/emergency/
+/// -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[post("/file/{id}/remove/")]
+#[post("/control/{id}/emergency/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn remove(
+pub async fn emergency_start(
     id: web::Path<i32>,
-    data: web::Json<PathObject>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1301,203 +2128,158 @@ pub async fn remove(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
-    let recursive = data.recursive;
 
-    match remove_file_or_folder(&config, &data.into_inner().source, recursive).await {
-        Ok(obj) => Ok(web::Json(obj)),
-        Err(e) => Err(e),
-    }
+    manager
+        .state
+        .lock()
+        .await
+        .state
+        .check(ChannelAction::Emergency)?;
+    start_emergency(&manager).await?;
+
+    Ok(web::Json("Emergency slate on air"))
 }
 
-/// **Upload File**
+/// **End Emergency Slate**
+///
+/// Go back to the schedule, a playlist starts again on the current time.
 ///
 /// ```BASH
-/// curl -X PUT http://127.0.0.1:8787/api/file/1/upload/ -H 'Authorization: Bearer <TOKEN>'
-/// -F "file=@file.mp4"
+/// curl -X POST http://127.0.0.1:8787/api/control/1/emergency/end/
+/// -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[allow(clippy::too_many_arguments)]
-#[put("/file/{id}/upload/")]
+#[post("/control/{id}/emergency/end/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-async fn save_file(
+pub async fn emergency_end(
     id: web::Path<i32>,
-    req: HttpRequest,
-    payload: Multipart,
-    obj: web::Query<FileObj>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
-) -> Result<HttpResponse, ServiceError> {
+) -> Result<impl Responder, ServiceError> {
     let manager = controllers
         .lock()
         .await
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
 
-    let size: u64 = req
-        .headers()
-        .get("content-length")
-        .and_then(|cl| cl.to_str().ok())
-        .and_then(|cls| cls.parse().ok())
-        .unwrap_or(0);
+    end_emergency(&manager).await?;
 
-    upload(&config, size, payload, &obj.path, false).await
+    Ok(web::Json("Back to schedule"))
 }
 
-/// **Get File**
+/// **Program Switch**
 ///
-/// Can be used for preview video files
+/// Source on air: `a` is the program from playlist or folder, `b` the secondary source from `ingest.secondary_source`.
 ///
 /// ```BASH
-/// curl -X GET http://127.0.0.1:8787/file/1/path/to/file.mp4
+/// curl -X GET http://127.0.0.1:8787/api/control/1/program
+/// -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[get("/file/{id}/{filename:.*}")]
-async fn get_file(
-    req: HttpRequest,
+///
+/// **Response:**
+///
+/// ```JSON
+/// {
+///     "on_air": "a"
+/// }
+/// ```
+#[get("/control/{id}/program")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn program_status(
+    id: web::Path<i32>,
     controllers: web::Data<Mutex<ChannelController>>,
-) -> Result<actix_files::NamedFile, ServiceError> {
-    let id: i32 = req.match_info().query("id").parse()?;
-    let manager = controllers.lock().await.get(id).await.unwrap();
-    let config = manager.config.lock().await;
-    let storage = config.channel.storage.clone();
-    let file_path = req.match_info().query("filename");
-    let (path, _, _) = norm_abs_path(&storage, file_path)?;
-    let file = actix_files::NamedFile::open(path)?;
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
 
-    Ok(file
-        .use_last_modified(true)
-        .set_content_disposition(ContentDisposition {
-            disposition: DispositionType::Attachment,
-            parameters: vec![],
-        }))
+    let status = manager.program.lock().await.clone();
+
+    Ok(web::Json(status))
 }
 
-/// **Get Public**
+/// **Switch Program Source**
 ///
-/// Can be used for HLS Playlist and other static files in public folder
+/// Switch between program (`a`) and secondary source (`b`), with a `cut` or a `fade`.
+/// The crossfade has a `duration` in seconds, default is one second.
 ///
 /// ```BASH
-/// curl -X GET http://127.0.0.1:8787/1/live/stream.m3u8
+/// curl -X POST http://127.0.0.1:8787/api/control/1/program/ -H 'Content-Type: application/json'
+/// -d '{ "source": "b", "transition": "fade", "duration": 2.0 }' -H 'Authorization: Bearer <TOKEN>'
 /// ```
-#[get("/{id}/{public:live|preview|public}/{file_stem:.*}")]
-async fn get_public(
-    path: web::Path<(i32, String, String)>,
-    controllers: web::Data<Mutex<ChannelController>>,
-) -> Result<actix_files::NamedFile, ServiceError> {
-    let (id, public, file_stem) = path.into_inner();
-
-    let absolute_path = if file_stem.ends_with(".ts")
-        || file_stem.ends_with(".m3u8")
-        || file_stem.ends_with(".vtt")
-    {
-        let manager = controllers.lock().await.get(id).await.unwrap();
-        let config = manager.config.lock().await;
-        config.channel.public.join(public)
-    } else {
-        public_path()
-    }
-    .clean();
-
-    let path = absolute_path.join(file_stem.as_str());
-    let file = actix_files::NamedFile::open(path)?;
-
-    Ok(file
-        .use_last_modified(true)
-        .set_content_disposition(ContentDisposition {
-            disposition: DispositionType::Attachment,
-            parameters: vec![],
-        }))
-}
-
-/// **Import playlist**
-///
-/// Import text/m3u file and convert it to a playlist
-/// lines with leading "#" will be ignore
-///
-/// ```BASH
-/// curl -X PUT http://127.0.0.1:8787/api/file/1/import/ -H 'Authorization: Bearer <TOKEN>'
-/// -F "file=@list.m3u"
-/// ```
-#[allow(clippy::too_many_arguments)]
-#[put("/file/{id}/import/")]
+#[post("/control/{id}/program/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-async fn import_playlist(
+pub async fn program_switch(
     id: web::Path<i32>,
-    req: HttpRequest,
-    payload: Multipart,
-    obj: web::Query<ImportObj>,
+    data: web::Json<SwitchRequest>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
-) -> Result<HttpResponse, ServiceError> {
+) -> Result<impl Responder, ServiceError> {
     let manager = controllers
         .lock()
         .await
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let channel_name = manager.channel.lock().await.name.clone();
-    let config = manager.config.lock().await.clone();
-    let file = obj.file.file_name().unwrap_or_default();
-    let path = env::temp_dir().join(file);
-    let path_clone = path.clone();
-    let size: u64 = req
-        .headers()
-        .get("content-length")
-        .and_then(|cl| cl.to_str().ok())
-        .and_then(|cls| cls.parse().ok())
-        .unwrap_or(0);
-
-    upload(&config, size, payload, &path, true).await?;
 
-    let response = import_file(&config, &obj.date, Some(channel_name), &path_clone).await?;
+    // back to the program is always possible, also for a stopped channel
+    if data.source == Bus::B {
+        manager
+            .state
+            .lock()
+            .await
+            .state
+            .check(ChannelAction::Switch)?;
+    }
 
-    fs::remove_file(path).await?;
+    let status = switch_program(&manager, &data.into_inner()).await?;
 
-    Ok(HttpResponse::Ok().body(response))
+    Ok(web::Json(status))
 }
 
-/// **Program info**
-///
-/// Get program infos about given date, or current day
-///
-/// Examples:
+/// #### ffplayout Process Control
 ///
-/// * get program from current day
-/// ```BASH
-/// curl -X GET http://127.0.0.1:8787/api/program/1/ -H 'Authorization: Bearer <TOKEN>'
-/// ```
+/// Control ffplayout process, like:
+/// - start
+/// - stop
+/// - restart
+/// - status
 ///
-/// * get a program range between two dates
 /// ```BASH
-/// curl -X GET http://127.0.0.1:8787/api/program/1/?start_after=2022-11-13T12:00:00&start_before=2022-11-20T11:59:59 \
-/// -H 'Authorization: Bearer <TOKEN>'
+/// curl -X POST http://127.0.0.1:8787/api/control/1/process/
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// -d '{"command": "start"}'
 /// ```
 ///
-/// * get program from give day
-/// ```BASH
-/// curl -X GET http://127.0.0.1:8787/api/program/1/?start_after=2022-11-13T10:00:00 \
-/// -H 'Authorization: Bearer <TOKEN>'
-/// ```
-#[get("/program/{id}/")]
+/// With `"drain": <seconds>` a stop waits until the current clip is finished.
+#[post("/control/{id}/process/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-async fn get_program(
+pub async fn process_control(
     id: web::Path<i32>,
-    obj: web::Query<ProgramObj>,
+    proc: web::Json<Process>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1508,103 +2290,116 @@ async fn get_program(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
-    let id = config.general.channel_id;
-    let start_sec = config.playlist.start_sec.unwrap();
-    let mut days = 0;
-    let mut program = vec![];
-    let after = obj.start_after;
-    let mut before = obj.start_before;
-
-    if after > before {
-        before = chrono::Local
-            .with_ymd_and_hms(after.year(), after.month(), after.day(), 23, 59, 59)
-            .unwrap()
-            .naive_local();
+    let proc = proc.into_inner();
+    let state = manager.state.lock().await.state;
+
+    match proc.command {
+        ProcessCtl::Status => (),
+        ProcessCtl::Start => state.check(ChannelAction::Start)?,
+        ProcessCtl::Stop => state.check(ChannelAction::Stop)?,
+        ProcessCtl::Restart => state.check(ChannelAction::Restart)?,
     }
 
-    if start_sec
-        > time_to_sec(
-            &after.format("%H:%M:%S").to_string(),
-            &config.channel.timezone,
-        )
-    {
-        days = 1;
+    manager.list_init.store(true, Ordering::SeqCst);
+
+    if manager.is_processing.load(Ordering::SeqCst) {
+        return Err(ServiceError::Conflict(
+            "A command is already being processed, please wait".to_string(),
+        ));
     }
 
-    let date_range = get_date_range(
-        id,
-        &vec_strings![
-            (after - TimeDelta::try_days(days).unwrap_or_default()).format("%Y-%m-%d"),
-            "-",
-            before.format("%Y-%m-%d")
-        ],
-    );
+    manager.is_processing.store(true, Ordering::SeqCst);
 
-    for date in date_range {
-        let mut naive = NaiveDateTime::parse_from_str(
-            &format!("{date} {}", sec_to_time(start_sec)),
-            "%Y-%m-%d %H:%M:%S%.3f",
-        )
-        .unwrap();
+    match proc.command {
+        ProcessCtl::Status => {
+            manager.is_processing.store(false, Ordering::SeqCst);
 
-        let playlist = match read_playlist(&config, date.clone()).await {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Error in Playlist from {date}: {e}");
-                continue;
+            if manager.is_alive.load(Ordering::SeqCst) {
+                return Ok(web::Json("active"));
             }
-        };
-
-        for item in playlist.program {
-            let start: DateTime<Local> = Local.from_local_datetime(&naive).unwrap();
+            return Ok(web::Json("not running"));
+        }
+        ProcessCtl::Start => {
+            if !manager.is_alive.load(Ordering::SeqCst) {
+                manager.channel.lock().await.active = true;
+                manager.start().await?;
+            }
+        }
+        ProcessCtl::Stop => {
+            manager.channel.lock().await.active = false;
 
-            let source = match Regex::new(&config.text.regex)
-                .ok()
-                .and_then(|r| r.captures(&item.source))
-            {
-                Some(t) => t[1].to_string(),
-                None => item.source,
-            };
+            if let Some(limit) = proc.drain {
+                manager.drain(tokio::time::Duration::from_secs(limit)).await;
+            }
 
-            let p_item = ProgramItem {
-                source,
-                start: start.format("%Y-%m-%d %H:%M:%S%.3f%:z").to_string(),
-                title: item.title,
-                r#in: item.seek,
-                out: item.out,
-                duration: item.duration,
-                category: item.category,
-            };
+            manager.stop_all(true).await?;
+        }
+        ProcessCtl::Restart => {
+            manager.stop_all(false).await?;
+            tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
 
-            if naive >= after && naive <= before {
-                program.push(p_item);
+            if !manager.is_alive.load(Ordering::SeqCst) {
+                manager.start().await?;
             }
-
-            naive += TimeDelta::try_milliseconds(((item.out - item.seek) * 1000.0) as i64)
-                .unwrap_or_default();
         }
     }
 
-    Ok(web::Json(program))
+    manager.is_processing.store(false, Ordering::SeqCst);
+
+    Ok(web::Json("Success"))
 }
 
-/// ### System Statistics
+/// #### ffplayout Playlist Operations
+///
+/// **Get playlist**
 ///
-/// Get statistics about CPU, Ram, Disk, etc. usage.
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/playlist/1?date=2022-06-20
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/playlist/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn get_playlist(
+    id: web::Path<i32>,
+    obj: web::Query<DateObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let store = manager.playlist_store().await;
+
+    match read_playlist(&store, obj.date.clone()).await {
+        Ok(playlist) => Ok(web::Json(playlist)),
+        Err(e) => Err(e),
+    }
+}
+
+/// **Save playlist**
 ///
 /// ```BASH
-/// curl -X GET http://127.0.0.1:8787/api/system/1
+/// curl -X POST http://127.0.0.1:8787/api/playlist/1/
 /// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// --data "{<JSON playlist data>}"
 /// ```
-#[get("/system/{id}")]
+#[post("/playlist/{id}/")]
 #[protect(
     any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
     ty = "Role",
     expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
 )]
-pub async fn get_system_stat(
+pub async fn save_playlist(
     id: web::Path<i32>,
+    data: web::Json<JsonPlaylist>,
     controllers: web::Data<Mutex<ChannelController>>,
     role: AuthDetails<Role>,
     user: web::ReqData<UserMeta>,
@@ -1615,9 +2410,1491 @@ pub async fn get_system_stat(
         .get(*id)
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
-    let config = manager.config.lock().await.clone();
+    let store = manager.playlist_store().await;
 
-    let stat = web::block(move || system::stat(&config)).await?;
+    match write_playlist(&store, data.into_inner()).await {
+        Ok(res) => Ok(web::Json(res)),
+        Err(e) => Err(e),
+    }
+}
 
-    Ok(web::Json(stat))
+/// **Playlist History**
+///
+/// Older versions from a playlist, newest first. Only available when playlists are stored in the database.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/playlist/1/history?date=2022-06-20
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/playlist/{id}/history")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn get_playlist_history(
+    id: web::Path<i32>,
+    obj: web::Query<DateObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let store = manager.playlist_store().await;
+    let history: Vec<_> = playlist_history(&store, &obj.date)
+        .await?
+        .into_iter()
+        .map(|p| {
+            json!({
+                "version": p.version,
+                "modified": p.modified,
+                "channel": p.channel,
+                "date": p.date,
+                "program": p.program,
+            })
+        })
+        .collect();
+
+    Ok(web::Json(history))
+}
+
+/// **Media Audit**
+///
+/// Result from the last check of the playlists from today and the next 14 days, with the clips
+/// which are missing, can't be probed or don't fit the channel. The check runs every 6 hours.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/playlist/1/audit -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/playlist/{id}/audit")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn get_media_audit(
+    id: web::Path<i32>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let audit = manager.media_audit.lock().await.clone();
+
+    Ok(web::Json(audit))
+}
+
+/// **Run Media Audit**
+///
+/// Check the playlists now, for example after missing files are uploaded. Response is the new result.
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/playlist/1/audit -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/playlist/{id}/audit")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn run_media_audit(
+    id: web::Path<i32>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+
+    audit_channel(&manager).await;
+    let audit = manager.media_audit.lock().await.clone();
+
+    Ok(web::Json(audit))
+}
+
+/// **Generate Playlist**
+///
+/// A new playlist will be generated and response.
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/playlist/1/generate/2022-06-20
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// /// --data '{ "paths": [<list of paths>] }' # <- data is optional
+/// ```
+///
+/// Or with template:
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/playlist/1/generate/2023-00-05
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// --data '{"template": {"sources": [\
+///            {"start": "00:00:00", "duration": "10:00:00", "shuffle": true, "paths": ["path/1", "path/2"]}, \
+///            {"start": "10:00:00", "duration": "14:00:00", "shuffle": false, "paths": ["path/3", "path/4"]}]}}'
+/// ```
+#[post("/playlist/{id}/generate/{date}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&params.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn gen_playlist(
+    params: web::Path<(i32, String)>,
+    data: Option<web::Json<PathsObj>>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers.lock().await.get(params.0).await.unwrap();
+    manager.config.lock().await.general.generate = Some(vec![params.1.clone()]);
+    let storage = manager.config.lock().await.channel.storage.clone();
+
+    if let Some(obj) = data {
+        if let Some(paths) = &obj.paths {
+            let mut path_list = vec![];
+
+            for path in paths {
+                let (p, _, _) = norm_abs_path(&storage, path)?;
+
+                path_list.push(p);
+            }
+
+            manager.config.lock().await.storage.paths = path_list;
+        }
+
+        manager
+            .config
+            .lock()
+            .await
+            .general
+            .template
+            .clone_from(&obj.template);
+    }
+
+    match generate_playlist(manager).await {
+        Ok(playlist) => Ok(web::Json(playlist)),
+        Err(e) => Err(e),
+    }
+}
+
+/// **Delete Playlist**
+///
+/// ```BASH
+/// curl -X DELETE http://127.0.0.1:8787/api/playlist/1/2022-06-20
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[delete("/playlist/{id}/{date}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&params.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn del_playlist(
+    params: web::Path<(i32, String)>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(params.0)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let store = manager.playlist_store().await;
+
+    match delete_playlist(&store, &params.1).await {
+        Ok(m) => Ok(web::Json(m)),
+        Err(e) => Err(e),
+    }
+}
+
+/// ### Log file
+///
+/// **Read Log File**
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/log/1?date=2022-06-20
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/log/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn get_log(
+    id: web::Path<i32>,
+    // controllers: web::Data<Mutex<ChannelController>>,
+    log: web::Query<DateObj>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    // let manager = controllers.lock().await.get(*id).await.ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    // let timezone = manager.config.lock().await.channel.timezone;
+
+    read_log_file(&id, &log.date).await
+}
+
+/// **Search Log Files**
+///
+/// Search the current and the rotated log files from a channel. `to`, `level`, `text` and `limit` are optional,
+/// compressed files are listed in `skipped`.
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/api/log/1/search?from=2024-05-18&to=2024-05-20&level=warn&text=connection'
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/log/{id}/search")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn search_log(
+    id: web::Path<i32>,
+    query: web::Query<LogQuery>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let result = search_logs(*id, &query).await?;
+
+    Ok(web::Json(result))
+}
+
+/// ### File Operations
+///
+/// **Get File/Folder List**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/file/1/browse/ -H 'Content-Type: application/json'
+/// -d '{ "source": "/" }' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/file/{id}/browse/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn file_browser(
+    id: web::Path<i32>,
+    data: web::Json<PathObject>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let channel = manager.channel.lock().await.clone();
+    let config = manager.config.lock().await.clone();
+
+    match browser(&config, &channel, &data.into_inner()).await {
+        Ok(obj) => Ok(web::Json(obj)),
+        Err(e) => Err(e),
+    }
+}
+
+/// **Create Folder**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/file/1/create-folder/ -H 'Content-Type: application/json'
+/// -d '{"source": "<FOLDER PATH>"}' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/file/{id}/create-folder/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn add_dir(
+    id: web::Path<i32>,
+    data: web::Json<PathObject>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<HttpResponse, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+
+    create_directory(&config, &data.into_inner()).await
+}
+
+/// **Rename File**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/file/1/rename/ -H 'Content-Type: application/json'
+/// -d '{"source": "<SOURCE>", "target": "<TARGET>"}' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/file/{id}/rename/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn move_rename(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<MoveObject>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+    let (source_path, _, source) = norm_abs_path(&config.channel.storage, &data.source)?;
+    let (target_path, _, mut target) = norm_abs_path(&config.channel.storage, &data.target)?;
+
+    // moved into a folder, the same as in rename_file
+    if target_path.is_dir() && source_path.parent() != Some(&target_path) {
+        if let Some(name) = source_path.file_name() {
+            target = Path::new(&target).join(name).to_string_lossy().to_string();
+        }
+    }
+
+    let obj = rename_file(&config, &data.into_inner()).await?;
+
+    handles::move_media(&pool, *id, &source, &target).await?;
+
+    Ok(web::Json(obj))
+}
+
+/// **Remove File/Folder**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/file/1/remove/ -H 'Content-Type: application/json'
+/// -d '{"source": "<SOURCE>"}' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/file/{id}/remove/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn remove(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<PathObject>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+    let recursive = data.recursive;
+    let (_, _, path) = norm_abs_path(&config.channel.storage, &data.source)?;
+
+    let obj = remove_file_or_folder(&config, &data.into_inner().source, recursive).await?;
+
+    handles::delete_media(&pool, *id, &path).await?;
+
+    Ok(web::Json(obj))
+}
+
+/// **Upload File**
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/file/1/upload/ -H 'Authorization: Bearer <TOKEN>'
+/// -F "file=@file.mp4"
+/// ```
+#[allow(clippy::too_many_arguments)]
+#[put("/file/{id}/upload/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn save_file(
+    id: web::Path<i32>,
+    req: HttpRequest,
+    payload: Multipart,
+    obj: web::Query<FileObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<HttpResponse, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+
+    let size: u64 = req
+        .headers()
+        .get("content-length")
+        .and_then(|cl| cl.to_str().ok())
+        .and_then(|cls| cls.parse().ok())
+        .unwrap_or(0);
+
+    upload(&config, size, payload, &obj.path, false).await
+}
+
+/// **Get File**
+///
+/// Can be used for preview video files
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/file/1/path/to/file.mp4
+/// ```
+#[get("/file/{id}/{filename:.*}")]
+async fn get_file(
+    req: HttpRequest,
+    controllers: web::Data<Mutex<ChannelController>>,
+) -> Result<actix_files::NamedFile, ServiceError> {
+    let id: i32 = req.match_info().query("id").parse()?;
+    let manager = controllers.lock().await.get(id).await.unwrap();
+    let config = manager.config.lock().await;
+    let storage = config.channel.storage.clone();
+    let file_path = req.match_info().query("filename");
+    let (path, _, _) = norm_abs_path(&storage, file_path)?;
+    let file = actix_files::NamedFile::open(path)?;
+
+    Ok(file
+        .use_last_modified(true)
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![],
+        }))
+}
+
+/// **Share File**
+///
+/// Create a link, which opens the file without login until it expires.
+/// `lifetime` are seconds, the default is one day, the limit 30 days.
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/file/1/share/ -H 'Content-Type: application/json'
+/// -d '{"source": "<SOURCE>", "lifetime": 3600}' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/file/{id}/share/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn share_file(
+    id: web::Path<i32>,
+    data: web::Json<ShareRequest>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let storage = manager.config.lock().await.channel.storage.clone();
+    let (path, _, source) = norm_abs_path(&storage, &data.source)?;
+    let lifetime = data.lifetime.unwrap_or(share::DEFAULT_LIFETIME);
+
+    if !path.is_file() {
+        return Err(ServiceError::BadRequest(format!(
+            "File {} not found",
+            data.source
+        )));
+    }
+
+    if lifetime <= 0 || lifetime > share::MAX_LIFETIME {
+        return Err(ServiceError::BadRequest(format!(
+            "Lifetime must be between 1 and {} seconds",
+            share::MAX_LIFETIME
+        )));
+    }
+
+    let secret = GLOBAL_SETTINGS
+        .get()
+        .and_then(|g| g.secret.clone())
+        .ok_or(ServiceError::InternalServerError)?;
+    let link = share::share_link(&secret, *id, &source, Utc::now().timestamp() + lifetime);
+
+    info!(target: Target::file_mail(), channel = *id;
+        "User <yellow>{}</> shares <b><magenta>{source}</></b> for <yellow>{lifetime}</> seconds",
+        user.id
+    );
+
+    Ok(web::Json(link))
+}
+
+/// **Get Shared File**
+///
+/// Opens the file from a share link, without login. With `download=true` the browser
+/// saves the file, otherwise it shows it.
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/share/1/path/to/file.mp4?expires=<EXPIRES>&signature=<SIGNATURE>'
+/// ```
+#[get("/share/{id}/{filename:.*}")]
+async fn get_shared_file(
+    path: web::Path<(i32, String)>,
+    query: web::Query<ShareQuery>,
+    controllers: web::Data<Mutex<ChannelController>>,
+) -> Result<actix_files::NamedFile, ServiceError> {
+    let (id, filename) = path.into_inner();
+    let manager = controllers
+        .lock()
+        .await
+        .get(id)
+        .await
+        .ok_or(ServiceError::Forbidden(
+            "Share link is not valid".to_string(),
+        ))?;
+    let storage = manager.config.lock().await.channel.storage.clone();
+    let (path, _, source) = norm_abs_path(&storage, &filename)?;
+    let secret = GLOBAL_SETTINGS
+        .get()
+        .and_then(|g| g.secret.clone())
+        .ok_or(ServiceError::InternalServerError)?;
+
+    share::verify(&secret, id, &source, &query, Utc::now().timestamp())?;
+
+    let file = actix_files::NamedFile::open(path)?;
+    let disposition = if query.download {
+        DispositionType::Attachment
+    } else {
+        DispositionType::Inline
+    };
+
+    Ok(file
+        .use_last_modified(true)
+        .set_content_disposition(ContentDisposition {
+            disposition,
+            parameters: vec![],
+        }))
+}
+
+/// **Get Public**
+///
+/// Can be used for HLS Playlist and other static files in public folder.
+/// CORS, Cache-Control, range requests and gzip come from `output.public_server`.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/1/live/stream.m3u8
+/// ```
+#[route(
+    "/{id}/{public:live|preview|public}/{file_stem:.*}",
+    method = "GET",
+    method = "HEAD",
+    method = "OPTIONS"
+)]
+async fn get_public(
+    req: HttpRequest,
+    path: web::Path<(i32, String, String)>,
+    controllers: web::Data<Mutex<ChannelController>>,
+) -> Result<HttpResponse, ServiceError> {
+    let (id, public, file_stem) = path.into_inner();
+    let manager = controllers
+        .lock()
+        .await
+        .get(id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+    let settings = &config.output.public_server;
+
+    if req.method() == Method::OPTIONS {
+        let mut response = HttpResponse::NoContent().finish();
+        public_server::add_cors(settings, &req, response.headers_mut());
+
+        return Ok(response);
+    }
+
+    let absolute_path = if file_stem.ends_with(".ts")
+        || file_stem.ends_with(".m4s")
+        || file_stem.ends_with(".m3u8")
+        || file_stem.ends_with(".vtt")
+    {
+        config.channel.public.join(public)
+    } else {
+        public_path()
+    }
+    .clean();
+
+    let path = absolute_path.join(file_stem.as_str()).clean();
+
+    if !path.starts_with(&absolute_path) {
+        return Err(ServiceError::Forbidden(
+            "Path is outside the public folder".to_string(),
+        ));
+    }
+
+    public_server::response(settings, &req, &path).await
+}
+
+/// **Import playlist**
+///
+/// Import text/m3u file and convert it to a playlist
+/// lines with leading "#" will be ignore
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/file/1/import/ -H 'Authorization: Bearer <TOKEN>'
+/// -F "file=@list.m3u"
+/// ```
+#[allow(clippy::too_many_arguments)]
+#[put("/file/{id}/import/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn import_playlist(
+    id: web::Path<i32>,
+    req: HttpRequest,
+    payload: Multipart,
+    obj: web::Query<ImportObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<HttpResponse, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let channel_name = manager.channel.lock().await.name.clone();
+    let config = manager.config.lock().await.clone();
+    let file = obj.file.file_name().unwrap_or_default();
+    let path = env::temp_dir().join(file);
+    let path_clone = path.clone();
+    let size: u64 = req
+        .headers()
+        .get("content-length")
+        .and_then(|cl| cl.to_str().ok())
+        .and_then(|cls| cls.parse().ok())
+        .unwrap_or(0);
+
+    upload(&config, size, payload, &path, true).await?;
+
+    let store = manager.playlist_store().await;
+    let response = import_file(&config, &store, &obj.date, Some(channel_name), &path_clone).await;
+
+    fs::remove_file(path).await?;
+
+    Ok(HttpResponse::Ok().body(response?))
+}
+
+/// **Program info**
+///
+/// Get program infos about given date, or current day
+///
+/// Examples:
+///
+/// * get program from current day
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/program/1/ -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// * get a program range between two dates
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/program/1/?start_after=2022-11-13T12:00:00&start_before=2022-11-20T11:59:59 \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// * get program from give day
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/program/1/?start_after=2022-11-13T10:00:00 \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/program/{id}/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_program(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<ProgramObj>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+    let store = manager.playlist_store().await;
+    let id = config.general.channel_id;
+    let start_sec = config.playlist.start_sec.unwrap();
+    let mut days = 0;
+    let mut program = vec![];
+    let after = obj.start_after;
+    let mut before = obj.start_before;
+
+    if after > before {
+        before = chrono::Local
+            .with_ymd_and_hms(after.year(), after.month(), after.day(), 23, 59, 59)
+            .unwrap()
+            .naive_local();
+    }
+
+    if start_sec
+        > time_to_sec(
+            &after.format("%H:%M:%S").to_string(),
+            &config.channel.timezone,
+        )
+    {
+        days = 1;
+    }
+
+    let date_range = get_date_range(
+        id,
+        &vec_strings![
+            (after - TimeDelta::try_days(days).unwrap_or_default()).format("%Y-%m-%d"),
+            "-",
+            before.format("%Y-%m-%d")
+        ],
+    );
+
+    for date in date_range {
+        let mut naive = NaiveDateTime::parse_from_str(
+            &format!("{date} {}", sec_to_time(start_sec)),
+            "%Y-%m-%d %H:%M:%S%.3f",
+        )
+        .unwrap();
+
+        let playlist = match read_playlist(&store, date.clone()).await {
+            Ok(p) => p,
+            Err(e) => {
+                error!("Error in Playlist from {date}: {e}");
+                continue;
+            }
+        };
+
+        for mut item in playlist.program {
+            let start: DateTime<Local> = Local.from_local_datetime(&naive).unwrap();
+
+            apply_metadata(&pool, &config, &mut item).await;
+
+            let source = match Regex::new(&config.text.regex)
+                .ok()
+                .and_then(|r| r.captures(&item.source))
+            {
+                Some(t) => t[1].to_string(),
+                None => item.source,
+            };
+
+            let p_item = ProgramItem {
+                source,
+                start: start.format("%Y-%m-%d %H:%M:%S%.3f%:z").to_string(),
+                title: item.title,
+                r#in: item.seek,
+                out: item.out,
+                duration: item.duration,
+                category: item.category,
+            };
+
+            if naive >= after && naive <= before {
+                program.push(p_item);
+            }
+
+            naive += TimeDelta::try_milliseconds(((item.out - item.seek) * 1000.0) as i64)
+                .unwrap_or_default();
+        }
+    }
+
+    Ok(web::Json(program))
+}
+
+/// **Play History**
+///
+/// Clips which were played out, newest first. Entries are kept for the number of days
+/// which is set in the global `play_history_days`.
+///
+/// All parameters are optional:
+///
+/// * `start`, `end`: time range in which the clips started
+/// * `at`: the clip which was running at that time
+/// * `search`: part of the source or title
+/// * `page`, `limit`: pagination, default is page 1 with 50 entries, maximum is 500
+///
+/// ```BASH
+/// curl -X GET "http://127.0.0.1:8787/api/history/1?at=2024-10-15T14:32:00" \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// **Response:**
+///
+/// ```JSON
+/// {
+///     "total": 1,
+///     "page": 1,
+///     "limit": 50,
+///     "items": [
+///         {
+///             "id": 1,
+///             "channel_id": 1,
+///             "started": "2024-10-15 14:30:12.040",
+///             "ended": "2024-10-15 14:34:42.040",
+///             "source": "/opt/tv-media/clip.mp4",
+///             "title": null,
+///             "category": "",
+///             "in": 0.0,
+///             "out": 270.0,
+///             "duration": 270.0
+///         }
+///     ]
+/// }
+/// ```
+#[get("/history/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_play_history(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<HistoryObj>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let filter = HistoryFilter {
+        start: history_time(&obj.start)?,
+        end: history_time(&obj.end)?,
+        at: history_time(&obj.at)?,
+        search: obj.search.clone().filter(|s| !s.is_empty()),
+    };
+    let page = obj.page.max(1);
+    let limit = obj.limit.clamp(1, 500);
+
+    let total = handles::count_play_history(&pool, *id, &filter).await?;
+    let items =
+        handles::select_play_history(&pool, *id, &filter, limit, (page - 1).saturating_mul(limit))
+            .await?;
+
+    Ok(web::Json(json!({
+        "total": total,
+        "page": page,
+        "limit": limit,
+        "items": items,
+    })))
+}
+
+/// **Event History**
+///
+/// State changes, output and ingest connections and errors from a channel, oldest first.
+/// With `since` only events after this id are returned, so a frontend can load what it missed.
+/// `limit` is the number of newest events, default is 100, maximum is 1000.
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/api/event/1?since=120'
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// **Response:**
+///
+/// ```JSON
+/// [
+///     {
+///         "id": 121,
+///         "channel_id": 1,
+///         "time": "2024-10-15 14:30:12.040",
+///         "kind": "ingest",
+///         "message": "Live ingest connected"
+///     }
+/// ]
+/// ```
+#[get("/event/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_event_history(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<EventObj>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let limit = obj.limit.clamp(1, MAX_EVENTS as u32);
+    let events = handles::select_history_events(&pool, *id, obj.since, limit).await?;
+
+    Ok(web::Json(events))
+}
+
+/// #### Alert Rules
+///
+/// A rule alarms when its `metric` meets the `condition` (`>`, `>=`, `<`, `<=`, `=`, `!=`) against the `threshold`
+/// for `duration` seconds. Metrics are `running`, `ingest`, `connected`, `drift`, `availability`, `disconnects`,
+/// `fps`, `speed` and `audit_issues`. The `notifier` is `log`, `mail`, `telegram`, `slack` or `discord`.
+///
+/// **Get all Alert Rules**
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/alerts/1 -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/alerts/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_alert_rules(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let rules = handles::select_alert_rules(&pool, *id).await?;
+
+    Ok(web::Json(rules))
+}
+
+/// **Add new Alert Rule**
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/alerts/1/ -H 'Content-Type: application/json' \
+/// -d '{ "name": "Encoder too slow", "metric": "speed", "condition": "<", "threshold": 0.95, "duration": 60, "notifier": "telegram" }' \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[post("/alerts/{id}/")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn add_alert_rule(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    data: web::Json<AlertRule>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let mut rule = data.into_inner();
+    rule.channel_id = *id;
+    alerts::validate(&mut rule)?;
+
+    let rule = handles::insert_alert_rule(&pool, &rule).await?;
+
+    Ok(web::Json(rule))
+}
+
+/// **Update Alert Rule**
+///
+/// ```BASH
+/// curl -X PUT http://127.0.0.1:8787/api/alerts/1/1 -H 'Content-Type: application/json' \
+/// -d '{ "name": "Output is unstable", "metric": "disconnects", "condition": ">=", "threshold": 5, "enabled": false }' \
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[put("/alerts/{channel}/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin"),
+    ty = "Role",
+    expr = "user.channels.contains(&path.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn update_alert_rule(
+    pool: web::Data<Pool<Sqlite>>,
+    path: web::Path<(i32, i32)>,
+    data: web::Json<AlertRule>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let (channel, id) = path.into_inner();
+    let mut rule = data.into_inner();
+    rule.channel_id = channel;
+    rule.id = id;
+    alerts::validate(&mut rule)?;
+
+    match handles::update_alert_rule(&pool, &rule).await? {
+        result if result.rows_affected() > 0 => Ok("Update Success"),
+        _ => Err(ServiceError::NoContent("Alert rule not found".to_string())),
+    }
+}
+
+/// **Delete Alert Rule**
+///
+/// ```BASH
+/// curl -X DELETE http://127.0.0.1:8787/api/alerts/1/1 -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[delete("/alerts/{channel}/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin"),
+    ty = "Role",
+    expr = "user.channels.contains(&path.0) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn delete_alert_rule(
+    pool: web::Data<Pool<Sqlite>>,
+    path: web::Path<(i32, i32)>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let (channel, id) = path.into_inner();
+
+    handles::delete_alert_rule(&pool, channel, id).await?;
+
+    Ok("Delete alert rule Success")
+}
+
+/// **Export As-Run Report**
+///
+/// All clips which started between `start` and `end`, with the airtime per program,
+/// as CSV (default) or PDF file. `end` is exclusive, so a full day ends with the next date.
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/api/history/1/export?start=2024-10-01&end=2024-11-01&format=pdf'
+/// -H 'Authorization: Bearer <TOKEN>' -o as-run.pdf
+/// ```
+#[get("/history/{id}/export")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn export_as_run(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<AsRunObj>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<HttpResponse, ServiceError> {
+    let (Some(start), Some(end)) = (history_time(&obj.start)?, history_time(&obj.end)?) else {
+        return Err(ServiceError::BadRequest(
+            "Start and end are required".to_string(),
+        ));
+    };
+
+    let channel = handles::select_channel(&pool, &id).await?;
+    let items = handles::select_as_run(&pool, *id, &start, &end).await?;
+    let file_name = format!("as-run_{}_{}", *id, start.get(..10).unwrap_or_default());
+    let report = AsRunReport::new(channel.name, start, end, items);
+
+    let (content_type, extension, body) = match obj.format.as_str() {
+        "" | "csv" => (
+            "text/csv; charset=utf-8",
+            "csv",
+            report.to_csv().into_bytes(),
+        ),
+        "pdf" => ("application/pdf", "pdf", report.to_pdf()),
+        format => {
+            return Err(ServiceError::BadRequest(format!(
+                "Unknown report format \"{format}\", use csv or pdf"
+            )))
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "{file_name}.{extension}"
+            ))],
+        })
+        .body(body))
+}
+
+/// **Availability Report**
+///
+/// On-air time in percent and all outages with their cause, for one month. Without `month`
+/// the current month is used, `format=csv` returns the report as CSV file.
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/api/report/1/availability?month=2024-10&format=csv'
+/// -H 'Authorization: Bearer <TOKEN>'
+/// ```
+///
+/// **Response:**
+///
+/// ```JSON
+/// {
+///     "channel_id": 1,
+///     "month": "2024-10",
+///     "start": "2024-10-01 00:00:00.000",
+///     "end": "2024-10-15 14:30:12.040",
+///     "on_air": 1261612.04,
+///     "off_air": 120.0,
+///     "availability": 99.99,
+///     "outages": [
+///         {
+///             "start": "2024-10-12 03:10:02.310",
+///             "end": "2024-10-12 03:12:02.310",
+///             "duration": 120.0,
+///             "cause": "Connection refused"
+///         }
+///     ]
+/// }
+/// ```
+#[get("/report/{id}/availability")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+async fn get_availability_report(
+    pool: web::Data<Pool<Sqlite>>,
+    id: web::Path<i32>,
+    obj: web::Query<ReportObj>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<HttpResponse, ServiceError> {
+    let month = obj
+        .month
+        .clone()
+        .unwrap_or_else(|| Local::now().format("%Y-%m").to_string());
+    let report = monthly_report(&pool, *id, &month).await?;
+
+    match obj.format.as_str() {
+        "" | "json" => Ok(HttpResponse::Ok().json(report)),
+        "csv" => Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!(
+                    "availability_{}_{month}.csv",
+                    *id
+                ))],
+            })
+            .body(report.to_csv())),
+        format => Err(ServiceError::BadRequest(format!(
+            "Unknown report format \"{format}\", use json or csv"
+        ))),
+    }
+}
+
+/// ### System Statistics
+///
+/// Get statistics about CPU, Ram, Disk, etc. usage, and the state of the playout clock.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/system/1
+/// -H 'Content-Type: application/json' -H 'Authorization: Bearer <TOKEN>'
+/// ```
+#[get("/system/{id}")]
+#[protect(
+    any("Role::GlobalAdmin", "Role::ChannelAdmin", "Role::User"),
+    ty = "Role",
+    expr = "user.channels.contains(&*id) || role.has_authority(&Role::GlobalAdmin)"
+)]
+pub async fn get_system_stat(
+    id: web::Path<i32>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    role: AuthDetails<Role>,
+    user: web::ReqData<UserMeta>,
+) -> Result<impl Responder, ServiceError> {
+    let manager = controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+    let config = manager.config.lock().await.clone();
+
+    let stat = web::block(move || system::stat(&config)).await?;
+
+    Ok(web::Json(stat))
+}
+
+/// #### System Export/Import
+///
+/// Export database and playlists from all channels to a single archive.
+///
+/// ```BASH
+/// curl -X GET http://127.0.0.1:8787/api/archive/export -H 'Authorization: Bearer <TOKEN>' \
+/// -o ffplayout_export.json.gz
+/// ```
+#[get("/archive/export")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+pub async fn export_archive(pool: web::Data<Pool<Sqlite>>) -> Result<HttpResponse, ServiceError> {
+    let data = archive::export(&pool).await?;
+    let file_name = format!(
+        "ffplayout_export_{}.json.gz",
+        Local::now().format("%Y-%m-%d_%H%M%S")
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .body(data))
+}
+
+/// **Import Archive**
+///
+/// The archive gets validated and is imported on next start of ffplayout,
+/// because the import replaces the whole database.
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/api/archive/import -H 'Authorization: Bearer <TOKEN>' \
+/// --data-binary @ffplayout_export.json.gz
+/// ```
+#[post("/archive/import")]
+#[protect("Role::GlobalAdmin", ty = "Role")]
+pub async fn import_archive(mut payload: web::Payload) -> Result<impl Responder, ServiceError> {
+    let mut data = web::BytesMut::new();
+
+    while let Some(chunk) = payload.next().await {
+        data.extend_from_slice(&chunk.map_err(|e| e.to_string())?);
+
+        if data.len() as u64 > archive::MAX_SIZE {
+            return Err(ServiceError::BadRequest("Archive is too big".to_string()));
+        }
+    }
+
+    archive::stage_import(&data).await?;
+
+    Ok("Import staged, restart ffplayout to apply it")
+}
+
+/// #### RTMP Callbacks
+///
+/// For setups where nginx-rtmp gets the live stream, instead of the ingest server. The stream key from
+/// `ingest.stream_key` must be the stream name, or the `key` argument from the publish URL. The callbacks
+/// need no token, nginx must reach them over a trusted network.
+///
+/// ```NGINX
+/// on_publish http://127.0.0.1:8787/rtmp/on_publish;
+/// on_publish_done http://127.0.0.1:8787/rtmp/on_publish_done;
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct RtmpCallback {
+    #[serde(default)]
+    pub call: String,
+    #[serde(default)]
+    pub name: String,
+    pub key: Option<String>,
+    #[serde(default)]
+    pub addr: String,
+}
+
+/// Channel with ingest enabled, which has the stream key from the callback.
+async fn rtmp_channel(
+    controllers: &Mutex<ChannelController>,
+    data: &RtmpCallback,
+) -> Option<(i32, ChannelManager)> {
+    let managers = controllers.lock().await.channels.clone();
+
+    for manager in managers {
+        let config = manager.config.lock().await;
+        let key = &config.ingest.stream_key;
+
+        if config.ingest.enable
+            && (secrets::token_matches(key, &data.name)
+                || data
+                    .key
+                    .as_ref()
+                    .is_some_and(|given| secrets::token_matches(key, given)))
+        {
+            let id = config.general.channel_id;
+            drop(config);
+
+            return Some((id, manager));
+        }
+    }
+
+    None
+}
+
+/// **Publish**
+///
+/// Answers `200` for a valid stream key and starts pulling the stream, otherwise `403`, so nginx drops the publisher.
+#[post("/on_publish")]
+pub async fn rtmp_publish(
+    controllers: web::Data<Mutex<ChannelController>>,
+    data: web::Form<RtmpCallback>,
+) -> HttpResponse {
+    let Some((id, manager)) = rtmp_channel(&controllers, &data).await else {
+        warn!(
+            "RTMP publish with unknown stream key from <b><magenta>{}</></b>",
+            data.addr
+        );
+
+        return HttpResponse::Forbidden().finish();
+    };
+
+    if !manager.ingest_published.swap(true, Ordering::SeqCst) {
+        info!(target: Target::file_mail(), channel = id; "RTMP stream published from <b><magenta>{}</></b>", data.addr);
+        event_history::push(id, EventKind::Ingest, "RTMP stream published");
+    }
+
+    HttpResponse::Ok().finish()
+}
+
+/// **Publish Done**
+///
+/// Stops pulling the stream, the playlist continues.
+#[post("/on_publish_done")]
+pub async fn rtmp_publish_done(
+    controllers: web::Data<Mutex<ChannelController>>,
+    data: web::Form<RtmpCallback>,
+) -> HttpResponse {
+    let Some((id, manager)) = rtmp_channel(&controllers, &data).await else {
+        return HttpResponse::Forbidden().finish();
+    };
+
+    if manager.ingest_published.swap(false, Ordering::SeqCst) {
+        info!(target: Target::file_mail(), channel = id; "RTMP stream ended");
+        event_history::push(id, EventKind::Ingest, "RTMP stream ended");
+
+        if let Err(e) = manager.stop(ProcessUnit::Ingest).await {
+            error!(target: Target::file_mail(), channel = id; "{e}");
+        }
+    }
+
+    HttpResponse::Ok().finish()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ButtonQuery {
+    token: Option<String>,
+}
+
+/// Channel for a button request, the token comes from the query or the `X-Control-Token` header.
+async fn button_channel(
+    controllers: &Mutex<ChannelController>,
+    req: &HttpRequest,
+    query: &ButtonQuery,
+    id: i32,
+) -> Result<ChannelManager, ServiceError> {
+    let Some(manager) = controllers.lock().await.get(id).await else {
+        return Err(ServiceError::BadRequest(format!("Channel {id} not found")));
+    };
+    let token = manager.config.lock().await.general.control_token.clone();
+    let given = query
+        .token
+        .clone()
+        .or_else(|| {
+            req.headers()
+                .get("X-Control-Token")
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+
+    if token.is_empty() {
+        return Err(ServiceError::Forbidden(
+            "Button endpoints are disabled".to_string(),
+        ));
+    }
+
+    if !secrets::token_matches(&token, &given) {
+        return Err(ServiceError::Unauthorized(
+            "Wrong control token".to_string(),
+        ));
+    }
+
+    Ok(manager)
+}
+
+async fn button_state(id: i32, manager: &ChannelManager, action: &str) -> Value {
+    let state = manager.state.lock().await.state;
+
+    json!({
+        "channel": id,
+        "action": action,
+        "state": state.to_string(),
+        "emergency": manager.emergency.load(Ordering::SeqCst),
+    })
+}
+
+/// #### Buttons
+///
+/// Single purpose endpoints for Stream Deck, Bitfocus Companion and other button panels, which can't
+/// log in. They work with `GET` and `POST` and need the `general.control_token` from the channel
+/// config, as `token` in the query or in the `X-Control-Token` header. Without a token in the
+/// config the endpoints are disabled.
+///
+/// **Button Action**
+///
+/// Actions are `next`, `back`, `reset`, `start`, `stop`, `slate` (emergency slate on and off) and
+/// `status`.
+///
+/// ```BASH
+/// curl http://127.0.0.1:8787/button/1/next?token=<CONTROL TOKEN>
+/// ```
+///
+/// **Response:**
+///
+/// ```JSON
+/// { "channel": 1, "action": "next", "state": "playing", "emergency": false }
+/// ```
+#[route("/{id}/{action}", method = "GET", method = "POST")]
+pub async fn button_action(
+    pool: web::Data<Pool<Sqlite>>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    req: HttpRequest,
+    path: web::Path<(i32, String)>,
+    query: web::Query<ButtonQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let (id, action) = path.into_inner();
+    let manager = button_channel(&controllers, &req, &query, id).await?;
+    let command = button_command(&action)?;
+
+    info!(target: Target::file(), channel = id; "Button: <yellow>{action}</>");
+
+    remote_control(&pool, &manager, command).await?;
+
+    Ok(web::Json(button_state(id, &manager, &action).await))
+}
+
+/// **Button Preset**
+///
+/// Send the text preset with this name, like a lower third.
+///
+/// ```BASH
+/// curl -X POST http://127.0.0.1:8787/button/1/preset/<PRESET NAME> -H 'X-Control-Token: <CONTROL TOKEN>'
+/// ```
+#[route("/{id}/preset/{name}", method = "GET", method = "POST")]
+pub async fn button_preset(
+    pool: web::Data<Pool<Sqlite>>,
+    controllers: web::Data<Mutex<ChannelController>>,
+    req: HttpRequest,
+    path: web::Path<(i32, String)>,
+    query: web::Query<ButtonQuery>,
+) -> Result<impl Responder, ServiceError> {
+    let (id, name) = path.into_inner();
+    let manager = button_channel(&controllers, &req, &query, id).await?;
+    let Some(preset) = handles::select_presets(&pool, id)
+        .await?
+        .into_iter()
+        .find(|p| p.name == name)
+    else {
+        return Err(ServiceError::BadRequest(format!("Preset {name} not found")));
+    };
+
+    info!(target: Target::file(), channel = id; "Button: preset <yellow>{name}</>");
+
+    send_message(manager.clone(), TextFilter::from(preset)).await?;
+
+    Ok(web::Json(button_state(id, &manager, "preset").await))
 }
@@ -1,18 +1,116 @@
+use std::sync::OnceLock;
+
 use actix_web::web;
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, PasswordHasher,
 };
+use log::*;
 use rand::{distributions::Alphanumeric, Rng};
 use sqlx::{sqlite::SqliteQueryResult, Pool, Row, Sqlite};
 
 use super::models::{AdvancedConfiguration, Configuration};
-use crate::db::models::{Channel, GlobalSettings, Role, TextPreset, User};
+use crate::db::models::{
+    AlertRule, Channel, ChannelEvent, ConfigPreset, GlobalSettings, HistoryEvent, HistoryFilter,
+    MediaEntry, MediaScan, PlayHistory, PlaylistRecord, PlaylistWrite, Role, Tag, TextPreset, User,
+};
 use crate::utils::{
-    advanced_config::AdvancedConfig, config::PlayoutConfig, errors::ServiceError,
+    advanced_config::AdvancedConfig,
+    config::PlayoutConfig,
+    errors::ServiceError,
     is_running_in_container,
+    secrets::{self, SecretKey},
 };
 
+static SECRET_KEY: OnceLock<SecretKey> = OnceLock::new();
+
+async fn secret_key(conn: &Pool<Sqlite>) -> Option<&'static SecretKey> {
+    if let Some(key) = SECRET_KEY.get() {
+        return Some(key);
+    }
+
+    let secret: Option<String> = sqlx::query_scalar("SELECT secret FROM global WHERE id = 1")
+        .fetch_one(conn)
+        .await
+        .ok()?;
+
+    secret.map(|s| SECRET_KEY.get_or_init(|| SecretKey::new(&s)))
+}
+
+/// Encrypt value for storing in the database, without key the value stays plain.
+async fn encrypt(conn: &Pool<Sqlite>, value: &str) -> String {
+    match secret_key(conn).await.map(|k| k.encrypt(value)) {
+        Some(Ok(v)) => v,
+        Some(Err(e)) => {
+            error!("Encrypt secret: {e}");
+            value.to_string()
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Decrypt stored value. When this fails the value is left empty,
+/// so the encrypted text never ends up in a command line.
+async fn decrypt(conn: &Pool<Sqlite>, value: &str) -> String {
+    if !secrets::is_encrypted(value) {
+        return value.to_string();
+    }
+
+    match secret_key(conn).await.map(|k| k.decrypt(value)) {
+        Some(Ok(v)) => v,
+        Some(Err(e)) => {
+            error!("Decrypt secret: {e}, the value is left empty");
+            String::new()
+        }
+        None => {
+            error!("Decrypt secret: global secret is missing, the value is left empty");
+            String::new()
+        }
+    }
+}
+
+/// Encrypt secrets which are still stored as plain text.
+async fn encrypt_secrets(conn: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    let global = select_global(conn).await?;
+
+    sqlx::query("UPDATE global SET smtp_password = $1 WHERE id = 1")
+        .bind(encrypt(conn, &global.smtp_password).await)
+        .execute(conn)
+        .await?;
+
+    for config in sqlx::query_as::<_, Configuration>("SELECT * FROM configurations")
+        .fetch_all(conn)
+        .await?
+    {
+        sqlx::query(
+            "UPDATE configurations SET output_param = $2, ingest_param = $3, mail_notifiers = $4, output_renditions = $5, ingest_stream_key = $6, general_control_token = $7, ingest_srt_passphrase = $8 WHERE id = $1",
+        )
+        .bind(config.id)
+        .bind(encrypt(conn, &config.output_param).await)
+        .bind(encrypt(conn, &config.ingest_param).await)
+        .bind(encrypt(conn, &config.mail_notifiers).await)
+        .bind(encrypt(conn, &config.output_renditions).await)
+        .bind(encrypt(conn, &config.ingest_stream_key).await)
+        .bind(encrypt(conn, &config.general_control_token).await)
+        .bind(encrypt(conn, &config.ingest_srt_passphrase).await)
+        .execute(conn)
+        .await?;
+    }
+
+    for preset in sqlx::query_as::<_, ConfigPreset>("SELECT * FROM config_presets")
+        .fetch_all(conn)
+        .await?
+    {
+        sqlx::query("UPDATE config_presets SET output_param = $2 WHERE id = $1")
+            .bind(preset.id)
+            .bind(encrypt(conn, &preset.output_param).await)
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
 pub async fn db_migrate(conn: &Pool<Sqlite>) -> Result<(), Box<dyn std::error::Error>> {
     sqlx::migrate!("../migrations").run(conn).await?;
 
@@ -39,14 +137,20 @@ pub async fn db_migrate(conn: &Pool<Sqlite>) -> Result<(), Box<dyn std::error::E
             .await?;
     }
 
+    encrypt_secrets(conn).await?;
+
     Ok(())
 }
 
 pub async fn select_global(conn: &Pool<Sqlite>) -> Result<GlobalSettings, sqlx::Error> {
     const QUERY: &str =
-        "SELECT id, secret, logs, playlists, public, storage, shared, smtp_server, smtp_user, smtp_password, smtp_starttls, smtp_port FROM global WHERE id = 1";
+        "SELECT id, secret, logs, playlists, public, storage, shared, smtp_server, smtp_user, smtp_password, smtp_starttls, smtp_port,
+            backup_enable, backup_path, backup_interval, backup_keep, backup_media_manifest, backup_upload, play_history_days, filler_pool, sentry_dsn, clock_source FROM global WHERE id = 1";
+
+    let mut global: GlobalSettings = sqlx::query_as(QUERY).fetch_one(conn).await?;
+    global.smtp_password = decrypt(conn, &global.smtp_password).await;
 
-    sqlx::query_as(QUERY).fetch_one(conn).await
+    Ok(global)
 }
 
 pub async fn update_global(
@@ -54,7 +158,9 @@ pub async fn update_global(
     global: GlobalSettings,
 ) -> Result<SqliteQueryResult, sqlx::Error> {
     const QUERY: &str = "UPDATE global SET logs = $2, playlists = $3, public = $4, storage = $5,
-            smtp_server = $6, smtp_user = $7, smtp_password = $8, smtp_starttls = $9, smtp_port = $10  WHERE id = 1";
+            smtp_server = $6, smtp_user = $7, smtp_password = $8, smtp_starttls = $9, smtp_port = $10,
+            backup_enable = $11, backup_path = $12, backup_interval = $13, backup_keep = $14, backup_media_manifest = $15,
+            backup_upload = $16, play_history_days = $17, filler_pool = $18, sentry_dsn = $19, clock_source = $20 WHERE id = 1";
 
     sqlx::query(QUERY)
         .bind(global.id)
@@ -64,9 +170,19 @@ pub async fn update_global(
         .bind(global.storage)
         .bind(global.smtp_server)
         .bind(global.smtp_user)
-        .bind(global.smtp_password)
+        .bind(encrypt(conn, &global.smtp_password).await)
         .bind(global.smtp_starttls)
         .bind(global.smtp_port)
+        .bind(global.backup_enable)
+        .bind(global.backup_path)
+        .bind(global.backup_interval)
+        .bind(global.backup_keep)
+        .bind(global.backup_media_manifest)
+        .bind(global.backup_upload)
+        .bind(global.play_history_days)
+        .bind(global.filler_pool)
+        .bind(global.sentry_dsn)
+        .bind(global.clock_source)
         .execute(conn)
         .await
 }
@@ -149,6 +265,22 @@ pub async fn update_stat(
     q.bind(time_shift).execute(conn).await
 }
 
+pub async fn update_resume(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    point: &str,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "UPDATE channels SET resume_point = $2 WHERE id = $1";
+
+    sqlx::query(QUERY).bind(id).bind(point).execute(conn).await
+}
+
+pub async fn select_resume(conn: &Pool<Sqlite>, id: i32) -> Result<Option<String>, sqlx::Error> {
+    const QUERY: &str = "SELECT resume_point FROM channels WHERE id = $1";
+
+    sqlx::query_scalar(QUERY).bind(id).fetch_one(conn).await
+}
+
 pub async fn update_player(
     conn: &Pool<Sqlite>,
     id: i32,
@@ -198,7 +330,16 @@ pub async fn select_configuration(
 ) -> Result<Configuration, sqlx::Error> {
     const QUERY: &str = "SELECT * FROM configurations WHERE channel_id = $1";
 
-    sqlx::query_as(QUERY).bind(channel).fetch_one(conn).await
+    let mut config: Configuration = sqlx::query_as(QUERY).bind(channel).fetch_one(conn).await?;
+    config.output_param = decrypt(conn, &config.output_param).await;
+    config.ingest_param = decrypt(conn, &config.ingest_param).await;
+    config.mail_notifiers = decrypt(conn, &config.mail_notifiers).await;
+    config.output_renditions = decrypt(conn, &config.output_renditions).await;
+    config.ingest_stream_key = decrypt(conn, &config.ingest_stream_key).await;
+    config.general_control_token = decrypt(conn, &config.general_control_token).await;
+    config.ingest_srt_passphrase = decrypt(conn, &config.ingest_srt_passphrase).await;
+
+    Ok(config)
 }
 
 pub async fn insert_configuration(
@@ -210,7 +351,7 @@ pub async fn insert_configuration(
 
     sqlx::query(QUERY)
         .bind(channel_id)
-        .bind(output_param)
+        .bind(encrypt(conn, output_param).await)
         .execute(conn)
         .await
 }
@@ -220,7 +361,7 @@ pub async fn update_configuration(
     id: i32,
     config: PlayoutConfig,
 ) -> Result<SqliteQueryResult, sqlx::Error> {
-    const QUERY: &str = "UPDATE configurations SET general_stop_threshold = $2, mail_subject = $3, mail_recipient = $4, mail_level = $5, mail_interval = $6, logging_ffmpeg_level = $7, logging_ingest_level = $8, logging_detect_silence = $9, logging_ignore = $10, processing_mode = $11, processing_audio_only = $12, processing_copy_audio = $13, processing_copy_video = $14, processing_width = $15, processing_height = $16, processing_aspect = $17, processing_fps = $18, processing_add_logo = $19, processing_logo = $20, processing_logo_scale = $21, processing_logo_opacity = $22, processing_logo_position = $23, processing_audio_tracks = $24, processing_audio_track_index = $25, processing_audio_channels = $26, processing_volume = $27, processing_filter = $28, processing_vtt_enable = $29, processing_vtt_dummy = $30, ingest_enable = $31, ingest_param = $32, ingest_filter = $33, playlist_day_start = $34, playlist_length = $35, playlist_infinit = $36, storage_filler = $37, storage_extensions = $38, storage_shuffle = $39, text_add = $40, text_from_filename = $41, text_font = $42, text_style = $43, text_regex = $44, task_enable = $45, task_path = $46, output_mode = $47, output_param = $48 WHERE id = $1";
+    const QUERY: &str = "UPDATE configurations SET general_stop_threshold = $2, mail_subject = $3, mail_recipient = $4, mail_level = $5, mail_interval = $6, logging_ffmpeg_level = $7, logging_ingest_level = $8, logging_detect_silence = $9, logging_ignore = $10, processing_mode = $11, processing_audio_only = $12, processing_copy_audio = $13, processing_copy_video = $14, processing_width = $15, processing_height = $16, processing_aspect = $17, processing_fps = $18, processing_add_logo = $19, processing_logo = $20, processing_logo_scale = $21, processing_logo_opacity = $22, processing_logo_position = $23, processing_audio_tracks = $24, processing_audio_track_index = $25, processing_audio_channels = $26, processing_volume = $27, processing_filter = $28, processing_vtt_enable = $29, processing_vtt_dummy = $30, ingest_enable = $31, ingest_param = $32, ingest_filter = $33, playlist_day_start = $34, playlist_length = $35, playlist_infinit = $36, storage_filler = $37, storage_extensions = $38, storage_shuffle = $39, text_add = $40, text_from_filename = $41, text_font = $42, text_style = $43, text_regex = $44, task_enable = $45, task_path = $46, output_mode = $47, output_param = $48, playlist_weekday_overrides = $49, playlist_backend = $50, storage_tags = $51, storage_shared_filler = $52, playlist_dst_forward = $53, playlist_dst_backward = $54, mail_notifiers = $55, mail_digest = $56, mail_dedup_window = $57, mail_class_levels = $58, general_watchdog = $59, general_drift_alarm = $60, general_restart_limit = $61, general_restart_delay = $62, general_restart_max_delay = $63, general_restart_slate = $64, playlist_check_days = $65, playlist_check_hours = $66, general_disk_alarm = $67, general_disk_cleanup = $68, processing_gapless = $69, processing_persistent_decoder = $70, output_renditions = $71, output_delay = $72, output_delay_path = $73, output_replay = $74, output_replay_path = $75, storage_emergency_slate = $76, storage_emergency_audio = $77, playlist_catch_up = $78, playlist_catch_up_rate = $79, processing_preload = $80, processing_visualization = $81, playlist_stream_reconnect = $82, playlist_stream_retries = $83, playlist_stream_fallback = $84, processing_seek_mode = $85, ingest_secondary_source = $86, ingest_stream_key = $87, ingest_scte35 = $88, text_layers = $89, general_control_token = $90, playlist_vast_url = $91, general_tally = $92, storage_house_format = $93, storage_coming_up = $94, storage_coming_up_count = $95, output_public_server = $96, general_plugins = $97, ingest_srt_passphrase = $98, ingest_srt_latency = $99, ingest_srt_streamid = $100 WHERE id = $1";
 
     sqlx::query(QUERY)
         .bind(id)
@@ -254,7 +395,7 @@ pub async fn update_configuration(
         .bind(config.processing.vtt_enable)
         .bind(config.processing.vtt_dummy)
         .bind(config.ingest.enable)
-        .bind(config.ingest.input_param)
+        .bind(encrypt(conn, &config.ingest.input_param).await)
         .bind(config.ingest.custom_filter)
         .bind(config.playlist.day_start)
         .bind(config.playlist.length)
@@ -270,7 +411,109 @@ pub async fn update_configuration(
         .bind(config.task.enable)
         .bind(config.task.path.to_string_lossy().to_string())
         .bind(config.output.mode.to_string())
-        .bind(config.output.output_param)
+        .bind(encrypt(conn, &config.output.output_param).await)
+        .bind(serde_json::to_string(&config.playlist.weekday_overrides).unwrap_or_default())
+        .bind(config.playlist.backend.to_string())
+        .bind(config.storage.tags.join(";"))
+        .bind(config.storage.shared_filler)
+        .bind(config.playlist.dst_forward.to_string())
+        .bind(config.playlist.dst_backward.to_string())
+        .bind(
+            encrypt(
+                conn,
+                &serde_json::to_string(&config.mail.notifiers).unwrap_or_default(),
+            )
+            .await,
+        )
+        .bind(config.mail.digest)
+        .bind(config.mail.dedup_window)
+        .bind(serde_json::to_string(&config.mail.class_levels).unwrap_or_default())
+        .bind(config.general.watchdog)
+        .bind(config.general.drift_alarm)
+        .bind(config.general.restart_limit)
+        .bind(config.general.restart_delay)
+        .bind(config.general.restart_max_delay)
+        .bind(config.general.restart_slate)
+        .bind(config.playlist.check_days)
+        .bind(config.playlist.check_hours)
+        .bind(config.general.disk_alarm)
+        .bind(config.general.disk_cleanup)
+        .bind(config.processing.gapless)
+        .bind(config.processing.persistent_decoder)
+        .bind(
+            encrypt(
+                conn,
+                &serde_json::to_string(&config.output.renditions).unwrap_or_default(),
+            )
+            .await,
+        )
+        .bind(config.output.delay)
+        .bind(config.output.delay_path)
+        .bind(config.output.replay)
+        .bind(config.output.replay_path)
+        .bind(config.storage.emergency_slate)
+        .bind(config.storage.emergency_audio)
+        .bind(config.playlist.catch_up.to_string())
+        .bind(config.playlist.catch_up_rate)
+        .bind(config.processing.preload)
+        .bind(config.processing.visualization.to_string())
+        .bind(config.playlist.stream_reconnect)
+        .bind(config.playlist.stream_retries)
+        .bind(config.playlist.stream_fallback)
+        .bind(config.processing.seek_mode.to_string())
+        .bind(config.ingest.secondary_source)
+        .bind(encrypt(conn, &config.ingest.stream_key).await)
+        .bind(config.ingest.scte35)
+        .bind(config.text.layers)
+        .bind(encrypt(conn, &config.general.control_token).await)
+        .bind(config.playlist.vast_url)
+        .bind(config.general.tally)
+        .bind(
+            serde_json::to_string(&config.storage.house_format)
+                .unwrap_or_else(|_| "{}".to_string()),
+        )
+        .bind(config.storage.coming_up)
+        .bind(config.storage.coming_up_count)
+        .bind(
+            serde_json::to_string(&config.output.public_server)
+                .unwrap_or_else(|_| "{}".to_string()),
+        )
+        .bind(config.general.plugins)
+        .bind(encrypt(conn, &config.ingest.srt_passphrase).await)
+        .bind(config.ingest.srt_latency)
+        .bind(config.ingest.srt_streamid)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_locked_fields(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<String>, sqlx::Error> {
+    const QUERY: &str = "SELECT locked_fields FROM configurations WHERE channel_id = $1";
+
+    let fields: String = sqlx::query_scalar(QUERY)
+        .bind(channel_id)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(fields
+        .split(';')
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect())
+}
+
+pub async fn update_locked_fields(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    fields: &[String],
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "UPDATE configurations SET locked_fields = $2 WHERE channel_id = $1";
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(fields.join(";"))
         .execute(conn)
         .await
 }
@@ -289,7 +532,7 @@ pub async fn update_advanced_configuration(
     channel_id: i32,
     config: AdvancedConfig,
 ) -> Result<SqliteQueryResult, sqlx::Error> {
-    const QUERY: &str = "UPDATE advanced_configurations SET decoder_input_param = $2, decoder_output_param = $3, encoder_input_param = $4, ingest_input_param = $5, filter_deinterlace = $6, filter_pad_scale_w = $7, filter_pad_scale_h = $8, filter_pad_video = $9, filter_fps = $10, filter_scale = $11, filter_set_dar = $12, filter_fade_in = $13, filter_fade_out = $14, filter_logo = $15, filter_overlay_logo_scale = $16, filter_overlay_logo_fade_in = $17, filter_overlay_logo_fade_out = $18, filter_overlay_logo = $19, filter_tpad = $20, filter_drawtext_from_file = $21, filter_drawtext_from_zmq = $22, filter_aevalsrc = $23, filter_afade_in = $24, filter_afade_out = $25, filter_apad = $26, filter_volume = $27, filter_split = $28 WHERE channel_id = $1";
+    const QUERY: &str = "UPDATE advanced_configurations SET decoder_input_param = $2, decoder_output_param = $3, encoder_input_param = $4, ingest_input_param = $5, filter_deinterlace = $6, filter_pad_scale_w = $7, filter_pad_scale_h = $8, filter_pad_video = $9, filter_fps = $10, filter_scale = $11, filter_set_dar = $12, filter_fade_in = $13, filter_fade_out = $14, filter_logo = $15, filter_overlay_logo_scale = $16, filter_overlay_logo_fade_in = $17, filter_overlay_logo_fade_out = $18, filter_overlay_logo = $19, filter_tpad = $20, filter_drawtext_from_file = $21, filter_drawtext_from_zmq = $22, filter_aevalsrc = $23, filter_afade_in = $24, filter_afade_out = $25, filter_apad = $26, filter_volume = $27, filter_split = $28, output_overrides = $29, process_nice = $30, process_cpu_affinity = $31, process_io_priority = $32, supervision_min_runtime = $33, supervision_max_failures = $34, supervision_backoff = $35, supervision_max_backoff = $36, supervision_fatal_errors = $37 WHERE channel_id = $1";
 
     sqlx::query(QUERY)
         .bind(channel_id)
@@ -320,6 +563,15 @@ pub async fn update_advanced_configuration(
         .bind(config.filter.apad)
         .bind(config.filter.volume)
         .bind(config.filter.split)
+        .bind(serde_json::to_string(&config.output_overrides).unwrap_or_default())
+        .bind(config.process.nice)
+        .bind(config.process.cpu_affinity)
+        .bind(config.process.io_priority)
+        .bind(config.supervision.min_runtime)
+        .bind(config.supervision.max_failures)
+        .bind(config.supervision.backoff)
+        .bind(config.supervision.max_backoff)
+        .bind(config.supervision.fatal_errors)
         .execute(conn)
         .await
 }
@@ -462,6 +714,48 @@ pub async fn insert_user_channel(
     Ok(())
 }
 
+pub async fn hash_password(password: String) -> Result<String, ServiceError> {
+    web::block(move || {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|p| p.to_string())
+    })
+    .await?
+    .map_err(|e| ServiceError::Conflict(e.to_string()))
+}
+
+/// Update the given values from the user, `None` keeps the stored value.
+pub async fn update_user_login(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    mail: Option<String>,
+    password_hash: Option<String>,
+    role_id: Option<i32>,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str =
+        "UPDATE user SET mail = COALESCE($2, mail), password = COALESCE($3, password),
+        role_id = COALESCE($4, role_id) WHERE id = $1";
+
+    sqlx::query(QUERY)
+        .bind(id)
+        .bind(mail)
+        .bind(password_hash)
+        .bind(role_id)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_role_id(conn: &Pool<Sqlite>, name: &str) -> Result<Option<i32>, sqlx::Error> {
+    const QUERY: &str = "SELECT id FROM roles WHERE name = $1";
+
+    sqlx::query_scalar(QUERY)
+        .bind(name)
+        .fetch_optional(conn)
+        .await
+}
+
 pub async fn delete_user(conn: &Pool<Sqlite>, id: i32) -> Result<SqliteQueryResult, sqlx::Error> {
     const QUERY: &str = "DELETE FROM user WHERE id = $1;";
 
@@ -546,3 +840,864 @@ pub async fn delete_preset(
 
     sqlx::query(QUERY).bind(id).execute(conn).await
 }
+
+pub async fn select_config_presets(conn: &Pool<Sqlite>) -> Result<Vec<ConfigPreset>, sqlx::Error> {
+    const QUERY: &str = "SELECT * FROM config_presets ORDER BY name";
+
+    let mut presets: Vec<ConfigPreset> = sqlx::query_as(QUERY).fetch_all(conn).await?;
+
+    for preset in &mut presets {
+        preset.output_param = decrypt(conn, &preset.output_param).await;
+    }
+
+    Ok(presets)
+}
+
+pub async fn select_config_preset(
+    conn: &Pool<Sqlite>,
+    name: &str,
+) -> Result<ConfigPreset, sqlx::Error> {
+    const QUERY: &str = "SELECT * FROM config_presets WHERE name = $1";
+
+    let mut preset: ConfigPreset = sqlx::query_as(QUERY).bind(name).fetch_one(conn).await?;
+    preset.output_param = decrypt(conn, &preset.output_param).await;
+
+    Ok(preset)
+}
+
+pub async fn insert_config_preset(
+    conn: &Pool<Sqlite>,
+    preset: ConfigPreset,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str =
+        "INSERT INTO config_presets (name, processing_width, processing_height, processing_aspect, processing_fps,
+            processing_audio_channels, output_mode, output_param) VALUES($1, $2, $3, $4, $5, $6, $7, $8)";
+
+    sqlx::query(QUERY)
+        .bind(preset.name)
+        .bind(preset.processing_width)
+        .bind(preset.processing_height)
+        .bind(preset.processing_aspect)
+        .bind(preset.processing_fps)
+        .bind(preset.processing_audio_channels)
+        .bind(preset.output_mode)
+        .bind(encrypt(conn, &preset.output_param).await)
+        .execute(conn)
+        .await
+}
+
+pub async fn update_config_preset(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    preset: ConfigPreset,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str =
+        "UPDATE config_presets SET name = $2, processing_width = $3, processing_height = $4, processing_aspect = $5,
+            processing_fps = $6, processing_audio_channels = $7, output_mode = $8, output_param = $9 WHERE id = $1";
+
+    sqlx::query(QUERY)
+        .bind(id)
+        .bind(preset.name)
+        .bind(preset.processing_width)
+        .bind(preset.processing_height)
+        .bind(preset.processing_aspect)
+        .bind(preset.processing_fps)
+        .bind(preset.processing_audio_channels)
+        .bind(preset.output_mode)
+        .bind(encrypt(conn, &preset.output_param).await)
+        .execute(conn)
+        .await
+}
+
+pub async fn delete_config_preset(
+    conn: &Pool<Sqlite>,
+    id: i32,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "DELETE FROM config_presets WHERE id = $1";
+
+    sqlx::query(QUERY).bind(id).execute(conn).await
+}
+
+pub async fn select_playlist(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    date: &str,
+) -> Result<Option<PlaylistRecord>, sqlx::Error> {
+    const QUERY: &str = "SELECT * FROM playlists WHERE channel_id = $1 AND date = $2";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(date)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn select_playlist_modified(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    date: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    const QUERY: &str = "SELECT modified FROM playlists WHERE channel_id = $1 AND date = $2";
+
+    sqlx::query_scalar(QUERY)
+        .bind(channel_id)
+        .bind(date)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn select_playlist_dates(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    from: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    const QUERY: &str =
+        "SELECT date FROM playlists WHERE channel_id = $1 AND date >= $2 ORDER BY date ASC";
+
+    sqlx::query_scalar(QUERY)
+        .bind(channel_id)
+        .bind(from)
+        .fetch_all(conn)
+        .await
+}
+
+pub async fn select_playlist_history(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    date: &str,
+) -> Result<Vec<PlaylistRecord>, sqlx::Error> {
+    const QUERY: &str =
+        "SELECT id, channel_id, date, name, program, version, modified FROM playlist_history
+        WHERE channel_id = $1 AND date = $2 ORDER BY id DESC";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(date)
+        .fetch_all(conn)
+        .await
+}
+
+/// Insert or update a playlist in one transaction, the replaced version is moved to the history.
+///
+/// When `expected` is set, the playlist is only updated when the stored version is the same.
+/// A playlist which was inserted by an other save in the meantime gets updated, without an
+/// expected version.
+pub async fn upsert_playlist(
+    conn: &Pool<Sqlite>,
+    playlist: &PlaylistRecord,
+    expected: Option<i64>,
+) -> Result<PlaylistWrite, sqlx::Error> {
+    const SELECT: &str = "SELECT id, version FROM playlists WHERE channel_id = $1 AND date = $2";
+    const VERSION: &str = "SELECT version FROM playlists WHERE channel_id = $1 AND date = $2";
+    const HISTORY: &str =
+        "INSERT INTO playlist_history (playlist_id, channel_id, date, version, name, program, modified)
+        SELECT id, channel_id, date, version, name, program, modified FROM playlists WHERE channel_id = $1 AND date = $2";
+    const UPDATE: &str =
+        "UPDATE playlists SET name = $3, program = $4, version = version + 1, modified = $5
+        WHERE id = $1 AND version = $2";
+    const INSERT: &str =
+        "INSERT INTO playlists (channel_id, date, name, program, modified) VALUES($1, $2, $3, $4, $5)
+        ON CONFLICT (channel_id, date) DO NOTHING";
+
+    let mut tx = conn.begin().await?;
+    let existing: Option<(i32, i64)> = sqlx::query_as(SELECT)
+        .bind(playlist.channel_id)
+        .bind(&playlist.date)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let result = match existing {
+        // only a new playlist can be saved without version
+        Some((_, version)) if expected.is_none() => {
+            return Ok(PlaylistWrite::Unversioned(version));
+        }
+        Some((_, version)) if expected != Some(version) => {
+            return Ok(PlaylistWrite::Outdated(version));
+        }
+        Some((id, version)) => {
+            sqlx::query(HISTORY)
+                .bind(playlist.channel_id)
+                .bind(&playlist.date)
+                .execute(&mut *tx)
+                .await?;
+
+            let updated = sqlx::query(UPDATE)
+                .bind(id)
+                .bind(version)
+                .bind(&playlist.name)
+                .bind(&playlist.program)
+                .bind(&playlist.modified)
+                .execute(&mut *tx)
+                .await?;
+
+            // an other save was faster, the transaction gets rolled back on drop
+            if updated.rows_affected() == 0 {
+                return Ok(PlaylistWrite::Outdated(version + 1));
+            }
+
+            PlaylistWrite::Updated
+        }
+        None => {
+            let inserted = sqlx::query(INSERT)
+                .bind(playlist.channel_id)
+                .bind(&playlist.date)
+                .bind(&playlist.name)
+                .bind(&playlist.program)
+                .bind(&playlist.modified)
+                .execute(&mut *tx)
+                .await?;
+
+            // an other save was faster
+            if inserted.rows_affected() == 0 {
+                let version: i64 = sqlx::query_scalar(VERSION)
+                    .bind(playlist.channel_id)
+                    .bind(&playlist.date)
+                    .fetch_one(&mut *tx)
+                    .await?;
+
+                return Ok(PlaylistWrite::Outdated(version));
+            }
+
+            PlaylistWrite::Inserted
+        }
+    };
+
+    tx.commit().await?;
+
+    Ok(result)
+}
+
+pub async fn delete_playlist(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    date: &str,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "DELETE FROM playlists WHERE channel_id = $1 AND date = $2";
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(date)
+        .execute(conn)
+        .await
+}
+
+pub async fn insert_play_history(
+    conn: &Pool<Sqlite>,
+    item: &PlayHistory,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "INSERT INTO play_history (channel_id, started, ended, source, title, category, seek, out, duration)
+        VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9)";
+
+    sqlx::query(QUERY)
+        .bind(item.channel_id)
+        .bind(&item.started)
+        .bind(&item.ended)
+        .bind(&item.source)
+        .bind(&item.title)
+        .bind(&item.category)
+        .bind(item.seek)
+        .bind(item.out)
+        .bind(item.duration)
+        .execute(conn)
+        .await
+}
+
+pub async fn update_play_history_end(
+    conn: &Pool<Sqlite>,
+    id: i64,
+    ended: &str,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "UPDATE play_history SET ended = $2 WHERE id = $1";
+
+    sqlx::query(QUERY).bind(id).bind(ended).execute(conn).await
+}
+
+/// Conditions for the play history, unset filters match everything.
+///
+/// `at` matches the clip which was running at that time.
+const HISTORY_FILTER: &str = "channel_id = $1 AND ($2 IS NULL OR started >= $2) AND ($3 IS NULL OR started <= $3)
+    AND ($4 IS NULL OR (started <= $4 AND ended > $4)) AND ($5 IS NULL OR source LIKE $5 OR title LIKE $5)";
+
+pub async fn select_play_history(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    filter: &HistoryFilter,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<PlayHistory>, sqlx::Error> {
+    let query = format!(
+        "SELECT * FROM play_history WHERE {HISTORY_FILTER} ORDER BY started DESC LIMIT $6 OFFSET $7"
+    );
+    let search = filter.search.as_ref().map(|s| format!("%{s}%"));
+
+    sqlx::query_as(&query)
+        .bind(channel_id)
+        .bind(&filter.start)
+        .bind(&filter.end)
+        .bind(&filter.at)
+        .bind(search)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(conn)
+        .await
+}
+
+/// All clips which started between `start` and `end`, oldest first.
+pub async fn select_as_run(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    start: &str,
+    end: &str,
+) -> Result<Vec<PlayHistory>, sqlx::Error> {
+    const QUERY: &str =
+        "SELECT * FROM play_history WHERE channel_id = $1 AND started >= $2 AND started < $3
+        ORDER BY started ASC";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(conn)
+        .await
+}
+
+pub async fn count_play_history(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    filter: &HistoryFilter,
+) -> Result<i64, sqlx::Error> {
+    let query = format!("SELECT COUNT(*) FROM play_history WHERE {HISTORY_FILTER}");
+    let search = filter.search.as_ref().map(|s| format!("%{s}%"));
+
+    sqlx::query_scalar(&query)
+        .bind(channel_id)
+        .bind(&filter.start)
+        .bind(&filter.end)
+        .bind(&filter.at)
+        .bind(search)
+        .fetch_one(conn)
+        .await
+}
+
+pub async fn delete_play_history(
+    conn: &Pool<Sqlite>,
+    before: &str,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "DELETE FROM play_history WHERE started < $1";
+
+    sqlx::query(QUERY).bind(before).execute(conn).await
+}
+
+pub async fn insert_channel_event(
+    conn: &Pool<Sqlite>,
+    event: &ChannelEvent,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str =
+        "INSERT INTO channel_events (channel_id, time, on_air, cause) VALUES($1, $2, $3, $4)";
+
+    sqlx::query(QUERY)
+        .bind(event.channel_id)
+        .bind(&event.time)
+        .bind(event.on_air)
+        .bind(&event.cause)
+        .execute(conn)
+        .await
+}
+
+/// Events between `start` and `end`, oldest first.
+pub async fn select_channel_events(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    start: &str,
+    end: &str,
+) -> Result<Vec<ChannelEvent>, sqlx::Error> {
+    const QUERY: &str =
+        "SELECT * FROM channel_events WHERE channel_id = $1 AND time >= $2 AND time < $3
+        ORDER BY time ASC, id ASC";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(conn)
+        .await
+}
+
+/// Last event before `time`, to know the state at that time.
+pub async fn select_last_channel_event(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    time: &str,
+) -> Result<Option<ChannelEvent>, sqlx::Error> {
+    const QUERY: &str = "SELECT * FROM channel_events WHERE channel_id = $1 AND time < $2
+        ORDER BY time DESC, id DESC LIMIT 1";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(time)
+        .fetch_optional(conn)
+        .await
+}
+
+/// Save event and remove the oldest ones, which are over the limit.
+pub async fn insert_history_event(
+    conn: &Pool<Sqlite>,
+    event: &HistoryEvent,
+    keep: i64,
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO event_history (channel_id, time, kind, message) VALUES($1, $2, $3, $4)",
+    )
+    .bind(event.channel_id)
+    .bind(&event.time)
+    .bind(&event.kind)
+    .bind(&event.message)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "DELETE FROM event_history WHERE channel_id = $1 AND id <= (SELECT id FROM event_history
+            WHERE channel_id = $1 ORDER BY id DESC LIMIT 1 OFFSET $2)",
+    )
+    .bind(event.channel_id)
+    .bind(keep)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Newest events after `since`, oldest first.
+pub async fn select_history_events(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    since: i64,
+    limit: u32,
+) -> Result<Vec<HistoryEvent>, sqlx::Error> {
+    const QUERY: &str =
+        "SELECT * FROM (SELECT * FROM event_history WHERE channel_id = $1 AND id > $2
+        ORDER BY id DESC LIMIT $3) ORDER BY id ASC";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(conn)
+        .await
+}
+
+pub async fn select_alert_rules(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<AlertRule>, sqlx::Error> {
+    const QUERY: &str = "SELECT * FROM alert_rules WHERE channel_id = $1 ORDER BY id";
+
+    sqlx::query_as(QUERY).bind(channel_id).fetch_all(conn).await
+}
+
+/// Enabled rules from all channels, for the monitor.
+pub async fn select_enabled_alert_rules(
+    conn: &Pool<Sqlite>,
+) -> Result<Vec<AlertRule>, sqlx::Error> {
+    const QUERY: &str = "SELECT * FROM alert_rules WHERE enabled = 1";
+
+    sqlx::query_as(QUERY).fetch_all(conn).await
+}
+
+pub async fn insert_alert_rule(
+    conn: &Pool<Sqlite>,
+    rule: &AlertRule,
+) -> Result<AlertRule, sqlx::Error> {
+    const QUERY: &str = "INSERT INTO alert_rules (channel_id, name, metric, condition, threshold, duration, notifier, enabled)
+        VALUES($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *";
+
+    sqlx::query_as(QUERY)
+        .bind(rule.channel_id)
+        .bind(&rule.name)
+        .bind(&rule.metric)
+        .bind(&rule.condition)
+        .bind(rule.threshold)
+        .bind(rule.duration)
+        .bind(&rule.notifier)
+        .bind(rule.enabled)
+        .fetch_one(conn)
+        .await
+}
+
+pub async fn update_alert_rule(
+    conn: &Pool<Sqlite>,
+    rule: &AlertRule,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "UPDATE alert_rules SET name = $3, metric = $4, condition = $5, threshold = $6, duration = $7,
+        notifier = $8, enabled = $9 WHERE channel_id = $1 AND id = $2";
+
+    sqlx::query(QUERY)
+        .bind(rule.channel_id)
+        .bind(rule.id)
+        .bind(&rule.name)
+        .bind(&rule.metric)
+        .bind(&rule.condition)
+        .bind(rule.threshold)
+        .bind(rule.duration)
+        .bind(&rule.notifier)
+        .bind(rule.enabled)
+        .execute(conn)
+        .await
+}
+
+pub async fn delete_alert_rule(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    id: i32,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "DELETE FROM alert_rules WHERE channel_id = $1 AND id = $2";
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(id)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_tags(conn: &Pool<Sqlite>, channel_id: i32) -> Result<Vec<Tag>, sqlx::Error> {
+    const QUERY: &str = "SELECT t.id, t.channel_id, t.name, COUNT(mt.media_id) AS media FROM tags t
+        LEFT JOIN media_tags mt ON mt.tag_id = t.id WHERE t.channel_id = $1 GROUP BY t.id ORDER BY t.name";
+
+    sqlx::query_as(QUERY).bind(channel_id).fetch_all(conn).await
+}
+
+pub async fn insert_tag(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    name: &str,
+) -> Result<Tag, sqlx::Error> {
+    const QUERY: &str =
+        "INSERT INTO tags (channel_id, name) VALUES($1, $2) RETURNING id, channel_id, name";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(name)
+        .fetch_one(conn)
+        .await
+}
+
+pub async fn update_tag(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    id: i32,
+    name: &str,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "UPDATE tags SET name = $3 WHERE channel_id = $1 AND id = $2";
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(id)
+        .bind(name)
+        .execute(conn)
+        .await
+}
+
+pub async fn delete_tag(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    id: i32,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "DELETE FROM tags WHERE channel_id = $1 AND id = $2";
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(id)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_media(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    path: &str,
+) -> Result<Option<MediaEntry>, sqlx::Error> {
+    const QUERY: &str = "SELECT m.id, m.path, m.house_id, m.title, m.category, m.metadata,
+        group_concat(t.name, ',') AS tags FROM media m LEFT JOIN media_tags mt ON mt.media_id = m.id LEFT JOIN tags t ON t.id = mt.tag_id
+        WHERE m.channel_id = $1 AND m.path = $2 GROUP BY m.id";
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(path)
+        .fetch_optional(conn)
+        .await
+}
+
+/// Replace the tags from a media file in one transaction, missing tags are created.
+///
+/// Without tags and without metadata the media entry is removed.
+pub async fn update_media_tags(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    path: &str,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    if tags.is_empty() {
+        sqlx::query(
+            "DELETE FROM media_tags WHERE media_id IN
+                (SELECT id FROM media WHERE channel_id = $1 AND path = $2)",
+        )
+        .bind(channel_id)
+        .bind(path)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "DELETE FROM media WHERE channel_id = $1 AND path = $2 AND house_id = ''
+                AND title = '' AND category = '' AND metadata = '{}'",
+        )
+        .bind(channel_id)
+        .bind(path)
+        .execute(&mut *tx)
+        .await?;
+    } else {
+        let media_id: i32 = sqlx::query_scalar(
+            "INSERT INTO media (channel_id, path) VALUES($1, $2)
+                ON CONFLICT(channel_id, path) DO UPDATE SET path = excluded.path RETURNING id",
+        )
+        .bind(channel_id)
+        .bind(path)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM media_tags WHERE media_id = $1")
+            .bind(media_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for tag in tags {
+            sqlx::query(
+                "INSERT INTO tags (channel_id, name) VALUES($1, $2) ON CONFLICT DO NOTHING",
+            )
+            .bind(channel_id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "INSERT OR IGNORE INTO media_tags (media_id, tag_id)
+                    SELECT $1, id FROM tags WHERE channel_id = $2 AND name = $3",
+            )
+            .bind(media_id)
+            .bind(channel_id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    tx.commit().await
+}
+
+/// Media files which have one of the tags, or all of them when `match_all` is set.
+///
+/// Without tags all tagged media files are returned.
+pub async fn select_tagged_media(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    tags: &[String],
+    match_all: bool,
+) -> Result<Vec<MediaEntry>, sqlx::Error> {
+    const QUERY: &str = "SELECT m.id, m.path, m.house_id, m.title, m.category, m.metadata,
+        group_concat(t.name, ',') AS tags FROM media m INNER JOIN media_tags mt ON mt.media_id = m.id INNER JOIN tags t ON t.id = mt.tag_id
+        WHERE m.channel_id = $1 GROUP BY m.id
+        HAVING SUM(t.name IN (SELECT value FROM json_each($2))) >= $3 ORDER BY m.path";
+
+    let names = serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string());
+    let min_matches = if match_all {
+        tags.len()
+    } else {
+        tags.len().min(1)
+    } as i64;
+
+    sqlx::query_as(QUERY)
+        .bind(channel_id)
+        .bind(names)
+        .bind(min_matches)
+        .fetch_all(conn)
+        .await
+}
+
+/// Media files with a house ID.
+pub async fn select_house_ids(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    const QUERY: &str =
+        "SELECT house_id, path FROM media WHERE channel_id = $1 AND house_id != '' ORDER BY path";
+
+    sqlx::query_as(QUERY).bind(channel_id).fetch_all(conn).await
+}
+
+/// Store house ID, title, category and metadata from a media file, the tags are not touched.
+pub async fn insert_or_update_media_metadata(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    entry: &MediaEntry,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "INSERT INTO media (channel_id, path, house_id, title, category, metadata)
+        VALUES($1, $2, $3, $4, $5, $6)
+        ON CONFLICT(channel_id, path) DO UPDATE SET house_id = excluded.house_id,
+        title = excluded.title, category = excluded.category, metadata = excluded.metadata";
+    let metadata = serde_json::to_string(&entry.metadata).unwrap_or_else(|_| "{}".to_string());
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(&entry.path)
+        .bind(&entry.house_id)
+        .bind(&entry.title)
+        .bind(&entry.category)
+        .bind(metadata)
+        .execute(conn)
+        .await
+}
+
+/// Conditions for a media file, or all files in the folder.
+const MEDIA_PATH: &str =
+    "channel_id = $1 AND (path = $2 OR substr(path, 1, length($2) + 1) = $2 || '/')";
+
+/// Tags and metadata follow a moved file or folder, old entries on the target are replaced.
+pub async fn move_media(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    source: &str,
+    target: &str,
+) -> Result<(), sqlx::Error> {
+    if source == target {
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+
+    sqlx::query(&format!("DELETE FROM media WHERE {MEDIA_PATH}"))
+        .bind(channel_id)
+        .bind(target)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(&format!(
+        "UPDATE media SET path = $3 || substr(path, length($2) + 1) WHERE {MEDIA_PATH}"
+    ))
+    .bind(channel_id)
+    .bind(source)
+    .bind(target)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Remove tags and metadata from a deleted file or folder.
+pub async fn delete_media(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    path: &str,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    sqlx::query(&format!("DELETE FROM media WHERE {MEDIA_PATH}"))
+        .bind(channel_id)
+        .bind(path)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_media_scans(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<MediaScan>, sqlx::Error> {
+    const QUERY: &str = "SELECT path, size, modified, duration, probe, error FROM media_scan
+        WHERE channel_id = $1 ORDER BY path";
+
+    sqlx::query_as(QUERY).bind(channel_id).fetch_all(conn).await
+}
+
+pub async fn insert_or_update_media_scan(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    scan: &MediaScan,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str = "INSERT INTO media_scan (channel_id, path, size, modified, duration, probe, error)
+        VALUES($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT(channel_id, path) DO UPDATE SET size = excluded.size, modified = excluded.modified,
+        duration = excluded.duration, probe = excluded.probe, error = excluded.error,
+        scanned = CURRENT_TIMESTAMP";
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(&scan.path)
+        .bind(scan.size)
+        .bind(scan.modified)
+        .bind(scan.duration)
+        .bind(&scan.probe)
+        .bind(&scan.error)
+        .execute(conn)
+        .await
+}
+
+/// Remove scan entries from files which are not in the storage anymore.
+pub async fn delete_media_scans(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    paths: &[String],
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    const QUERY: &str =
+        "DELETE FROM media_scan WHERE channel_id = $1 AND path IN (SELECT value FROM json_each($2))";
+
+    let paths = serde_json::to_string(paths).unwrap_or_else(|_| "[]".to_string());
+
+    sqlx::query(QUERY)
+        .bind(channel_id)
+        .bind(paths)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_preferences(
+    conn: &Pool<Sqlite>,
+    user_id: i32,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    const QUERY: &str = "SELECT key, value FROM user_preferences WHERE user_id = $1 ORDER BY key";
+
+    sqlx::query_as(QUERY).bind(user_id).fetch_all(conn).await
+}
+
+/// Save preferences in one transaction, entries without value are removed.
+pub async fn update_preferences(
+    conn: &Pool<Sqlite>,
+    user_id: i32,
+    preferences: &[(String, Option<String>)],
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    for (key, value) in preferences {
+        match value {
+            Some(value) => {
+                sqlx::query(
+                    "INSERT INTO user_preferences (user_id, key, value) VALUES($1, $2, $3)
+                        ON CONFLICT(user_id, key) DO UPDATE SET value = excluded.value",
+                )
+                .bind(user_id)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM user_preferences WHERE user_id = $1 AND key = $2")
+                    .bind(user_id)
+                    .bind(key)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+    }
+
+    tx.commit().await
+}
@@ -1,14 +1,17 @@
 use std::{
     borrow::Cow,
     io,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{LazyLock, OnceLock},
 };
 
+use chrono::Local;
 use faccess::PathExt;
 use inquire::Confirm;
 use log::*;
-use sqlx::{migrate::MigrateDatabase, Pool, Sqlite, SqlitePool};
+use sqlx::{migrate::MigrateDatabase, sqlite::SqliteConnectOptions, Pool, Row, Sqlite, SqlitePool};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tokio::fs;
 
 pub mod handles;
 pub mod models;
@@ -63,6 +66,60 @@ pub static DB_PATH: LazyLock<Result<Cow<'static, Path>, io::Error>> = LazyLock::
 });
 
 pub static GLOBAL_SETTINGS: OnceLock<GlobalSettings> = OnceLock::new();
+
+/// File with the process id from the running ffplayout, next to the database.
+pub fn pid_path(db_path: &Path) -> PathBuf {
+    db_path.with_extension("pid")
+}
+
+/// Mark the database as in use by this process.
+///
+/// Only for the long running modes, the pid file from another running ffplayout is not replaced.
+pub async fn write_pid() -> io::Result<()> {
+    let db_path = DB_PATH
+        .as_ref()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    if let Some(pid) = running_instance(db_path) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("ffplayout with process id {pid} uses the database already"),
+        ));
+    }
+
+    fs::write(pid_path(db_path), std::process::id().to_string()).await
+}
+
+/// Remove the pid file, when it belongs to this process.
+pub async fn remove_pid() {
+    if let Ok(db_path) = DB_PATH.as_ref() {
+        let path = pid_path(db_path);
+
+        if fs::read_to_string(&path)
+            .await
+            .is_ok_and(|pid| pid.trim() == std::process::id().to_string())
+        {
+            let _ = fs::remove_file(path).await;
+        }
+    }
+}
+
+/// Process id from another ffplayout, which uses the database.
+pub fn running_instance(db_path: &Path) -> Option<u32> {
+    let pid = std::fs::read_to_string(pid_path(db_path))
+        .ok()?
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|pid| *pid != std::process::id())?;
+    let mut sys = System::new();
+
+    sys.refresh_processes(ProcessesToUpdate::Some(&[Pid::from_u32(pid)]), true);
+
+    sys.process(Pid::from_u32(pid))
+        .is_some_and(|p| p.name().to_string_lossy().contains("ffplayout"))
+        .then_some(pid)
+}
 pub async fn db_pool() -> Result<Pool<Sqlite>, Box<dyn std::error::Error + Send + Sync>> {
     let db_path = DB_PATH.as_ref()?;
     let db_path = db_path.to_string_lossy();
@@ -91,6 +148,149 @@ pub async fn db_drop() {
     };
 }
 
+/// Write a consistent snapshot from the database to the given file.
+///
+/// The WAL gets checkpointed first, so the snapshot contains all committed transactions.
+pub async fn db_backup(conn: &Pool<Sqlite>, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if path.exists() {
+        return Err(format!("Backup file {} already exists!", path.display()).into());
+    }
+
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(conn)
+        .await?;
+    sqlx::query("VACUUM INTO $1")
+        .bind(path.to_string_lossy().to_string())
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Replace the current database with a backup file.
+///
+/// The backup must pass the integrity check and can not be newer than the migrations this binary knows.
+pub async fn db_restore(
+    conn: &Pool<Sqlite>,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = DB_PATH.as_ref()?;
+
+    db_restore_to(conn, path, db_path).await
+}
+
+/// Replace the database file `db_path` with a backup file, see [`db_restore`].
+///
+/// No other ffplayout can use the database, the WAL and shared memory files from the old
+/// database are removed.
+pub async fn db_restore_to(
+    conn: &Pool<Sqlite>,
+    path: &Path,
+    db_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(pid) = running_instance(db_path) {
+        return Err(format!("ffplayout runs with pid {pid}, stop it before the restore!").into());
+    }
+
+    if !path.is_file() {
+        return Err(format!("Backup file {} not found!", path.display()).into());
+    }
+
+    let backup =
+        SqlitePool::connect_with(SqliteConnectOptions::new().filename(path).read_only(true))
+            .await?;
+
+    let integrity: String = sqlx::query("PRAGMA integrity_check")
+        .fetch_one(&backup)
+        .await?
+        .try_get(0)?;
+
+    if integrity != "ok" {
+        backup.close().await;
+        return Err(format!("Backup is corrupt: {integrity}").into());
+    }
+
+    let backup_version: Option<i64> =
+        sqlx::query("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&backup)
+            .await?
+            .try_get(0)?;
+
+    backup.close().await;
+
+    let backup_version = backup_version.ok_or("Backup contains no migration history!")?;
+    let current_version = sqlx::migrate!("../migrations")
+        .migrations
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default();
+
+    if backup_version > current_version {
+        return Err(format!(
+            "Backup has database version {backup_version}, but this ffplayout only supports up to {current_version}!"
+        )
+        .into());
+    }
+
+    conn.close().await;
+
+    for suffix in ["-wal", "-shm"] {
+        let mut file = db_path.as_os_str().to_owned();
+        file.push(suffix);
+        let file = PathBuf::from(file);
+
+        if file.is_file() {
+            fs::remove_file(file).await?;
+        }
+    }
+
+    fs::copy(path, db_path).await?;
+
+    Ok(())
+}
+
+/// Highest applied migration, `None` for a new database.
+pub async fn db_version(conn: &Pool<Sqlite>) -> Option<i64> {
+    sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+        .fetch_one(conn)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Migrations from this binary, which are not applied to the database yet, with version and description.
+pub async fn pending_migrations(conn: &Pool<Sqlite>) -> Vec<(i64, String)> {
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(conn)
+            .await
+            .unwrap_or_default();
+
+    sqlx::migrate!("../migrations")
+        .migrations
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .map(|m| (m.version, m.description.to_string()))
+        .collect()
+}
+
+/// Snapshot next to the database, before the migrations change it.
+pub async fn db_pre_migration_backup(
+    conn: &Pool<Sqlite>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let version = db_version(conn).await.unwrap_or_default();
+    let db_path = DB_PATH.as_ref()?;
+    let path = db_path.with_file_name(format!(
+        "ffplayout_pre_migration_{version}_{}.db",
+        Local::now().format("%Y-%m-%d_%H%M%S")
+    ));
+
+    db_backup(conn, &path).await?;
+
+    Ok(path)
+}
+
 pub async fn init_globales(conn: &Pool<Sqlite>) -> Result<(), Box<dyn std::error::Error>> {
     let config = GlobalSettings::new(conn).await;
     GLOBAL_SETTINGS
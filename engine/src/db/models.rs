@@ -1,4 +1,4 @@
-use std::{error::Error, fmt, str::FromStr};
+use std::{collections::BTreeMap, error::Error, fmt, str::FromStr};
 
 use chrono_tz::Tz;
 use regex::Regex;
@@ -9,7 +9,10 @@ use serde::{
 use sqlx::{sqlite::SqliteRow, FromRow, Pool, Row, Sqlite};
 
 use crate::db::handles;
-use crate::utils::config::PlayoutConfig;
+use crate::utils::{
+    config::{OutputMode, PlayoutConfig},
+    errors::ServiceError,
+};
 
 #[derive(Clone, Default, Debug, Deserialize, Serialize, sqlx::FromRow)]
 pub struct GlobalSettings {
@@ -25,6 +28,16 @@ pub struct GlobalSettings {
     pub smtp_password: String,
     pub smtp_starttls: bool,
     pub smtp_port: u16,
+    pub backup_enable: bool,
+    pub backup_path: String,
+    pub backup_interval: u32,
+    pub backup_keep: u32,
+    pub backup_media_manifest: bool,
+    pub backup_upload: String,
+    pub play_history_days: u32,
+    pub filler_pool: String,
+    pub sentry_dsn: String,
+    pub clock_source: String,
 }
 
 impl GlobalSettings {
@@ -46,6 +59,16 @@ impl GlobalSettings {
                 smtp_password: String::new(),
                 smtp_starttls: false,
                 smtp_port: 465,
+                backup_enable: false,
+                backup_path: String::new(),
+                backup_interval: 24,
+                backup_keep: 7,
+                backup_media_manifest: false,
+                backup_upload: String::new(),
+                play_history_days: 30,
+                filler_pool: String::new(),
+                sentry_dsn: String::new(),
+                clock_source: String::new(),
             },
         }
     }
@@ -239,6 +262,227 @@ pub struct TextPreset {
     pub alpha: String,
 }
 
+/// Named set of processing and output parameters, which can be applied to any channel.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct ConfigPreset {
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub name: String,
+    pub processing_width: i64,
+    pub processing_height: i64,
+    pub processing_aspect: f64,
+    pub processing_fps: f64,
+    pub processing_audio_channels: u8,
+    pub output_mode: String,
+    pub output_param: String,
+}
+
+impl ConfigPreset {
+    pub fn from(name: &str, config: &PlayoutConfig) -> Self {
+        Self {
+            id: 0,
+            name: name.to_string(),
+            processing_width: config.processing.width,
+            processing_height: config.processing.height,
+            processing_aspect: config.processing.aspect,
+            processing_fps: config.processing.fps,
+            processing_audio_channels: config.processing.audio_channels,
+            output_mode: config.output.mode.to_string(),
+            output_param: config.output.output_param.clone(),
+        }
+    }
+
+    /// Output mode from the preset, an unknown mode is a bad request.
+    pub fn mode(&self) -> Result<OutputMode, ServiceError> {
+        self.output_mode.parse().map_err(|e| {
+            ServiceError::BadRequest(format!(
+                "Config preset \"{}\" has invalid output mode \"{}\": {e}",
+                self.name, self.output_mode
+            ))
+        })
+    }
+
+    pub fn apply(&self, config: &mut PlayoutConfig) -> Result<(), ServiceError> {
+        let mode = self.mode()?;
+
+        config.processing.width = self.processing_width;
+        config.processing.height = self.processing_height;
+        config.processing.aspect = self.processing_aspect;
+        config.processing.fps = self.processing_fps;
+        config.processing.audio_channels = self.processing_audio_channels;
+        config.output.mode = mode;
+        config.output.output_param.clone_from(&self.output_param);
+
+        Ok(())
+    }
+}
+
+/// Playlist from one day, when the playlists are stored in the database.
+///
+/// Entries from the history have the same shape, with the version they had before they got replaced.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct PlaylistRecord {
+    pub id: i32,
+    pub channel_id: i32,
+    pub date: String,
+    pub name: String,
+    pub program: String,
+    pub version: i64,
+    pub modified: String,
+}
+
+/// Result from saving a playlist to the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistWrite {
+    Inserted,
+    Updated,
+    /// The playlist was changed in the meantime, holds the current version.
+    Outdated(i64),
+    /// The playlist exists, but the save has no version, holds the current version.
+    Unversioned(i64),
+}
+
+/// Clip which was played out, `started` and `ended` are local times.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct PlayHistory {
+    pub id: i32,
+    pub channel_id: i32,
+    pub started: String,
+    pub ended: String,
+    pub source: String,
+    pub title: Option<String>,
+    pub category: String,
+    #[serde(rename = "in")]
+    pub seek: f64,
+    pub out: f64,
+    pub duration: f64,
+}
+
+/// Channel went on or off air, `time` is local time in the history format.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct ChannelEvent {
+    pub id: i32,
+    pub channel_id: i32,
+    pub time: String,
+    pub on_air: bool,
+    pub cause: String,
+}
+
+/// Event from the channel history, `kind` is state, output, ingest, error or alert.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct HistoryEvent {
+    pub id: i64,
+    pub channel_id: i32,
+    pub time: String,
+    pub kind: String,
+    pub message: String,
+}
+
+/// Alert rule from a channel, see [`crate::utils::alerts`] for metrics, conditions and notifiers.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct AlertRule {
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    #[serde(skip_deserializing)]
+    pub channel_id: i32,
+    pub name: String,
+    pub metric: String,
+    pub condition: String,
+    pub threshold: f64,
+    /// Seconds, which the condition must hold before the alert goes out.
+    #[serde(default)]
+    pub duration: i64,
+    #[serde(default)]
+    pub notifier: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Tag from a channel, `media` is the number of tagged files.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct Tag {
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    #[serde(skip_deserializing)]
+    pub channel_id: i32,
+    pub name: String,
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub media: i64,
+}
+
+/// Media file with its tags and metadata, the path is relative to the channel storage.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct MediaEntry {
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub path: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub house_id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub category: String,
+    /// Custom fields from the metadata import.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl FromRow<'_, SqliteRow> for MediaEntry {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id").unwrap_or_default(),
+            path: row.try_get("path").unwrap_or_default(),
+            tags: row
+                .try_get::<String, &str>("tags")
+                .unwrap_or_default()
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect(),
+            house_id: row.try_get("house_id").unwrap_or_default(),
+            title: row.try_get("title").unwrap_or_default(),
+            category: row.try_get("category").unwrap_or_default(),
+            metadata: row
+                .try_get::<String, &str>("metadata")
+                .ok()
+                .and_then(|m| serde_json::from_str(&m).ok())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Probe result from the media scan, the path is relative to the channel storage.
+///
+/// `probe` holds the ffprobe result as JSON, `error` is set when the file was not readable.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct MediaScan {
+    pub path: String,
+    pub size: i64,
+    /// Modification time in seconds since epoch.
+    pub modified: i64,
+    pub duration: f64,
+    pub probe: String,
+    pub error: String,
+}
+
+/// Filter for the play history, empty fields are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct HistoryFilter {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub at: Option<String>,
+    pub search: Option<String>,
+}
+
 /// Deserialize number or string
 pub fn deserialize_number_or_string<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
@@ -280,11 +524,55 @@ pub struct Configuration {
     pub id: i32,
     pub channel_id: i32,
     pub general_stop_threshold: f64,
+    #[serde(default)]
+    pub general_watchdog: i64,
+    #[serde(default)]
+    pub general_drift_alarm: f64,
+    #[serde(default)]
+    pub general_restart_limit: i64,
+    #[serde(default)]
+    pub general_restart_delay: i64,
+    #[serde(default)]
+    pub general_restart_max_delay: i64,
+    #[serde(default)]
+    pub general_restart_slate: i64,
+    #[serde(default)]
+    pub general_disk_alarm: i64,
+    #[serde(default)]
+    pub general_disk_cleanup: i64,
+    #[serde(default)]
+    pub general_control_token: String,
+    #[serde(default)]
+    pub general_tally: String,
+    #[serde(default)]
+    pub storage_house_format: String,
+    #[serde(default)]
+    pub storage_coming_up: String,
+    #[serde(default)]
+    pub storage_coming_up_count: i64,
+    #[serde(default)]
+    pub output_public_server: String,
+    #[serde(default)]
+    pub general_plugins: String,
+    #[serde(default)]
+    pub ingest_srt_passphrase: String,
+    #[serde(default)]
+    pub ingest_srt_latency: i64,
+    #[serde(default)]
+    pub ingest_srt_streamid: String,
 
     pub mail_subject: String,
     pub mail_recipient: String,
     pub mail_level: String,
     pub mail_interval: i64,
+    #[serde(default)]
+    pub mail_notifiers: String,
+    #[serde(default)]
+    pub mail_digest: i64,
+    #[serde(default)]
+    pub mail_dedup_window: i64,
+    #[serde(default)]
+    pub mail_class_levels: String,
 
     pub logging_ffmpeg_level: String,
     pub logging_ingest_level: String,
@@ -318,31 +606,91 @@ pub struct Configuration {
     pub processing_vtt_enable: bool,
     #[serde(default)]
     pub processing_vtt_dummy: Option<String>,
+    #[serde(default)]
+    pub processing_gapless: bool,
+    #[serde(default)]
+    pub processing_persistent_decoder: bool,
+    #[serde(default)]
+    pub processing_preload: bool,
+    #[serde(default)]
+    pub processing_visualization: String,
+    #[serde(default)]
+    pub processing_seek_mode: String,
 
     pub ingest_enable: bool,
     pub ingest_param: String,
     #[serde(default)]
     pub ingest_filter: String,
+    #[serde(default)]
+    pub ingest_secondary_source: String,
+    #[serde(default)]
+    pub ingest_stream_key: String,
+    #[serde(default)]
+    pub ingest_scte35: bool,
 
     pub playlist_day_start: String,
     pub playlist_length: String,
     pub playlist_infinit: bool,
+    #[serde(default)]
+    pub playlist_weekday_overrides: String,
+    #[serde(default)]
+    pub playlist_backend: String,
+    #[serde(default)]
+    pub playlist_dst_forward: String,
+    #[serde(default)]
+    pub playlist_dst_backward: String,
+    #[serde(default)]
+    pub playlist_catch_up: String,
+    #[serde(default)]
+    pub playlist_catch_up_rate: f64,
+    #[serde(default)]
+    pub playlist_stream_reconnect: i64,
+    #[serde(default)]
+    pub playlist_stream_retries: i64,
+    #[serde(default)]
+    pub playlist_stream_fallback: f64,
+    #[serde(default)]
+    pub playlist_check_days: i64,
+    #[serde(default)]
+    pub playlist_check_hours: i64,
+    #[serde(default)]
+    pub playlist_vast_url: String,
 
     pub storage_filler: String,
     pub storage_extensions: String,
     pub storage_shuffle: bool,
+    #[serde(default)]
+    pub storage_tags: String,
+    #[serde(default)]
+    pub storage_shared_filler: bool,
 
     pub text_add: bool,
     pub text_from_filename: bool,
     pub text_font: String,
     pub text_style: String,
     pub text_regex: String,
+    #[serde(default)]
+    pub text_layers: String,
 
     pub task_enable: bool,
     pub task_path: String,
 
     pub output_mode: String,
     pub output_param: String,
+    #[serde(default)]
+    pub output_renditions: String,
+    #[serde(default)]
+    pub output_delay: f64,
+    #[serde(default)]
+    pub output_delay_path: String,
+    #[serde(default)]
+    pub output_replay: f64,
+    #[serde(default)]
+    pub output_replay_path: String,
+    #[serde(default)]
+    pub storage_emergency_slate: String,
+    #[serde(default)]
+    pub storage_emergency_audio: String,
 }
 
 impl Configuration {
@@ -351,10 +699,34 @@ impl Configuration {
             id,
             channel_id,
             general_stop_threshold: config.general.stop_threshold,
+            general_watchdog: config.general.watchdog,
+            general_drift_alarm: config.general.drift_alarm,
+            general_restart_limit: config.general.restart_limit,
+            general_restart_delay: config.general.restart_delay,
+            general_restart_max_delay: config.general.restart_max_delay,
+            general_restart_slate: config.general.restart_slate,
+            general_disk_alarm: config.general.disk_alarm,
+            general_disk_cleanup: config.general.disk_cleanup,
+            general_control_token: config.general.control_token,
+            general_tally: config.general.tally,
+            general_plugins: config.general.plugins,
+            ingest_srt_passphrase: config.ingest.srt_passphrase,
+            ingest_srt_latency: config.ingest.srt_latency,
+            ingest_srt_streamid: config.ingest.srt_streamid,
+            storage_house_format: serde_json::to_string(&config.storage.house_format)
+                .unwrap_or_else(|_| "{}".to_string()),
+            storage_coming_up: config.storage.coming_up,
+            storage_coming_up_count: config.storage.coming_up_count,
+            output_public_server: serde_json::to_string(&config.output.public_server)
+                .unwrap_or_else(|_| "{}".to_string()),
             mail_subject: config.mail.subject,
             mail_recipient: config.mail.recipient,
             mail_level: config.mail.mail_level.to_string(),
             mail_interval: config.mail.interval,
+            mail_notifiers: serde_json::to_string(&config.mail.notifiers).unwrap_or_default(),
+            mail_digest: config.mail.digest,
+            mail_dedup_window: config.mail.dedup_window,
+            mail_class_levels: serde_json::to_string(&config.mail.class_levels).unwrap_or_default(),
             logging_ffmpeg_level: config.logging.ffmpeg_level,
             logging_ingest_level: config.logging.ingest_level,
             logging_detect_silence: config.logging.detect_silence,
@@ -379,24 +751,55 @@ impl Configuration {
             processing_filter: config.processing.custom_filter,
             processing_vtt_enable: config.processing.vtt_enable,
             processing_vtt_dummy: config.processing.vtt_dummy,
+            processing_gapless: config.processing.gapless,
+            processing_persistent_decoder: config.processing.persistent_decoder,
+            processing_preload: config.processing.preload,
+            processing_visualization: config.processing.visualization.to_string(),
+            processing_seek_mode: config.processing.seek_mode.to_string(),
             ingest_enable: config.ingest.enable,
             ingest_param: config.ingest.input_param,
             ingest_filter: config.ingest.custom_filter,
+            ingest_secondary_source: config.ingest.secondary_source,
+            ingest_stream_key: config.ingest.stream_key,
+            ingest_scte35: config.ingest.scte35,
             playlist_day_start: config.playlist.day_start,
             playlist_length: config.playlist.length,
             playlist_infinit: config.playlist.infinit,
+            playlist_weekday_overrides: serde_json::to_string(&config.playlist.weekday_overrides)
+                .unwrap_or_default(),
+            playlist_backend: config.playlist.backend.to_string(),
+            playlist_dst_forward: config.playlist.dst_forward.to_string(),
+            playlist_dst_backward: config.playlist.dst_backward.to_string(),
+            playlist_catch_up: config.playlist.catch_up.to_string(),
+            playlist_catch_up_rate: config.playlist.catch_up_rate,
+            playlist_stream_reconnect: config.playlist.stream_reconnect,
+            playlist_stream_retries: config.playlist.stream_retries,
+            playlist_stream_fallback: config.playlist.stream_fallback,
+            playlist_check_days: config.playlist.check_days,
+            playlist_check_hours: config.playlist.check_hours,
+            playlist_vast_url: config.playlist.vast_url,
             storage_filler: config.storage.filler,
             storage_extensions: config.storage.extensions.join(";"),
             storage_shuffle: config.storage.shuffle,
+            storage_tags: config.storage.tags.join(";"),
+            storage_shared_filler: config.storage.shared_filler,
             text_add: config.text.add_text,
             text_font: config.text.font,
             text_from_filename: config.text.text_from_filename,
             text_style: config.text.style,
             text_regex: config.text.regex,
+            text_layers: config.text.layers,
             task_enable: config.task.enable,
             task_path: config.task.path.to_string_lossy().to_string(),
             output_mode: config.output.mode.to_string(),
             output_param: config.output.output_param,
+            output_renditions: serde_json::to_string(&config.output.renditions).unwrap_or_default(),
+            output_delay: config.output.delay,
+            output_delay_path: config.output.delay_path,
+            output_replay: config.output.replay,
+            output_replay_path: config.output.replay_path,
+            storage_emergency_slate: config.storage.emergency_slate,
+            storage_emergency_audio: config.storage.emergency_audio,
         }
     }
 }
@@ -444,4 +847,13 @@ pub struct AdvancedConfiguration {
     pub filter_apad: Option<String>,
     pub filter_volume: Option<String>,
     pub filter_split: Option<String>,
+    pub output_overrides: String,
+    pub process_nice: Option<i32>,
+    pub process_cpu_affinity: Option<String>,
+    pub process_io_priority: Option<String>,
+    pub supervision_min_runtime: Option<i64>,
+    pub supervision_max_failures: Option<i64>,
+    pub supervision_backoff: Option<i64>,
+    pub supervision_max_backoff: Option<i64>,
+    pub supervision_fatal_errors: Option<String>,
 }
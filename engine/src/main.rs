@@ -4,9 +4,13 @@ use std::{
     process::exit,
     sync::{atomic::AtomicBool, Arc},
     thread,
+    time::Duration,
 };
 
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::{
+    middleware::{from_fn, Logger},
+    web, App, HttpServer,
+};
 use actix_web_httpauth::middleware::HttpAuthentication;
 
 #[cfg(any(debug_assertions, not(feature = "embed_frontend")))]
@@ -15,23 +19,35 @@ use actix_files::Files;
 #[cfg(all(not(debug_assertions), feature = "embed_frontend"))]
 use actix_web_static_files::ResourceFiles;
 
+use futures_util::future::join_all;
 use log::*;
-use tokio::{fs::File, io::AsyncReadExt, sync::Mutex};
+use tokio::sync::Mutex;
 
 use ffplayout::{
     api::routes::*,
-    db::{db_drop, db_pool, handles, init_globales},
+    db::{db_drop, db_pool, handles, init_globales, remove_pid, write_pid, GLOBAL_SETTINGS},
     player::{
         controller::{ChannelController, ChannelManager},
-        utils::{get_date, is_remote, json_validate::validate_playlist, JsonPlaylist},
+        utils::{
+            get_date, json_validate::validate_playlist, media_audit, playlist_store::PlaylistStore,
+        },
     },
     sse::{broadcast::Broadcaster, routes::*, SseAuthState},
     utils::{
+        alerts, amcp, archive,
         args_parse::run_args,
+        backup, benchmark,
+        clock::{self, ClockSource},
         config::get_config,
+        config_check, config_diff, disk_monitor, doctor, dry_run,
+        error_report::{self, report_errors},
+        event_history,
         logging::{init_logging, MailQueue},
+        media_scan, osc, output_test, play_history,
         playlist::generate_playlist,
+        reload, schedule_check, systemd,
         time_machine::set_mock_time,
+        tui,
     },
     validator, ARGS,
 };
@@ -50,27 +66,76 @@ fn thread_counter() -> usize {
     (available_threads / 2).max(2)
 }
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
+fn main() -> std::io::Result<()> {
+    // the sockets from systemd are taken before the runtime starts its threads
+    let listen_fds = systemd::listen_fds();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(listen_fds))
+}
+
+async fn run(listen_fds: Option<i32>) -> std::io::Result<()> {
     let mail_queues = Arc::new(Mutex::new(vec![]));
 
-    let pool = db_pool().await.map_err(io::Error::other)?;
+    // runs before the database is opened, it can be the problem
+    if ARGS.doctor {
+        exit(doctor::run().await);
+    }
+
+    let mut pool = db_pool().await.map_err(io::Error::other)?;
+
+    match archive::apply_pending(&pool).await {
+        Ok(true) => println!("Import staged system archive done..."),
+        Ok(false) => {}
+        Err(e) => eprintln!("Import staged system archive: {e}"),
+    }
+
+    // the database restore closes the pool, also when the import fails afterwards
+    if pool.is_closed() {
+        pool = db_pool().await.map_err(io::Error::other)?;
+    }
 
     if let Err(c) = run_args(&pool).await {
         exit(c);
     }
 
+    // server and foreground playout keep the database open, a restore is refused while they run
+    let long_running = ARGS.listen.is_some() || ARGS.foreground || ARGS.tui;
+
+    if long_running {
+        write_pid().await?;
+    }
+
     set_mock_time(&ARGS.fake_time)?;
 
     init_globales(&pool)
         .await
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
     // LoggerHandle should be kept alive until the end
-    let _logger = init_logging(mail_queues.clone());
+    let logger = init_logging(mail_queues.clone());
+
+    if let Some(global) = GLOBAL_SETTINGS.get() {
+        error_report::init(global);
+    }
 
     let channel_controllers = Arc::new(Mutex::new(ChannelController::new()));
 
     if let Some(conn) = &ARGS.listen {
+        event_history::init(pool.clone());
+
+        // the playout clock should follow its reference, before the channels start
+        if let Some(global) = GLOBAL_SETTINGS.get() {
+            match global.clock_source.parse::<ClockSource>() {
+                Ok(source) => {
+                    tokio::spawn(clock::run(source));
+                }
+                Err(e) => error!("{e}, playout uses the system clock"),
+            }
+        }
+
         let channels = handles::select_related_channels(&pool, None)
             .await
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
@@ -93,6 +158,41 @@ async fn main() -> std::io::Result<()> {
             }
         }
 
+        tokio::spawn(backup::run(pool.clone()));
+
+        if let Some(global) = GLOBAL_SETTINGS.get().filter(|g| g.play_history_days > 0) {
+            tokio::spawn(play_history::run(pool.clone(), global.clone()));
+        }
+
+        tokio::spawn(alerts::run(pool.clone(), channel_controllers.clone()));
+        tokio::spawn(schedule_check::run(channel_controllers.clone()));
+        tokio::spawn(media_audit::run(channel_controllers.clone()));
+        tokio::spawn(disk_monitor::run(channel_controllers.clone()));
+
+        if let Some(addr) = &ARGS.amcp {
+            tokio::spawn(amcp::run(
+                addr.clone(),
+                pool.clone(),
+                channel_controllers.clone(),
+            ));
+        }
+
+        if let Some(addr) = &ARGS.osc {
+            tokio::spawn(osc::run(
+                addr.clone(),
+                pool.clone(),
+                channel_controllers.clone(),
+            ));
+        }
+
+        #[cfg(unix)]
+        tokio::spawn(reload::run(
+            pool.clone(),
+            channel_controllers.clone(),
+            mail_queues.clone(),
+            logger.as_ref().ok().cloned(),
+        ));
+
         let ip_port = conn.split(':').collect::<Vec<&str>>();
         let addr = ip_port[0];
         let port = ip_port
@@ -111,12 +211,10 @@ async fn main() -> std::io::Result<()> {
         });
         let broadcast_data = Broadcaster::create();
 
-        info!("Running ffplayout API, listen on http://{conn}");
-
         let db_clone = pool.clone();
 
         // no 'allow origin' here, give it to the reverse proxy
-        HttpServer::new(move || {
+        let server = HttpServer::new(move || {
             let auth = HttpAuthentication::bearer(validator);
             let db_pool = web::Data::new(db_clone.clone());
             // Customize logging format to get IP though proxies.
@@ -130,23 +228,43 @@ async fn main() -> std::io::Result<()> {
                 .app_data(auth_state.clone())
                 .app_data(web::Data::from(Arc::clone(&broadcast_data)))
                 .wrap(logger)
+                .wrap(from_fn(report_errors))
                 .service(web::scope("/auth").service(login).service(refresh))
                 .service(
                     web::scope("/api")
                         .wrap(auth)
                         .service(add_user)
                         .service(get_user)
+                        .service(get_preferences)
+                        .service(update_preferences)
+                        .service(delete_preference)
                         .service(get_by_name)
                         .service(get_users)
                         .service(remove_user)
                         .service(get_advanced_config)
                         .service(update_advanced_config)
+                        .service(get_config_schema)
                         .service(get_playout_config)
                         .service(update_playout_config)
+                        .service(get_config_locks)
+                        .service(update_config_locks)
+                        .service(get_config_presets)
+                        .service(add_config_preset)
+                        .service(update_config_preset)
+                        .service(delete_config_preset)
+                        .service(apply_config_preset)
                         .service(add_preset)
                         .service(get_presets)
                         .service(update_preset)
                         .service(delete_preset)
+                        .service(get_tags)
+                        .service(add_tag)
+                        .service(update_tag)
+                        .service(delete_tag)
+                        .service(get_media_tags)
+                        .service(update_media_tags)
+                        .service(search_media)
+                        .service(import_media_metadata)
                         .service(get_channel)
                         .service(get_all_channels)
                         .service(patch_channel)
@@ -156,28 +274,69 @@ async fn main() -> std::io::Result<()> {
                         .service(send_text_message)
                         .service(control_playout)
                         .service(media_current)
+                        .service(encoder_stats)
+                        .service(output_health)
+                        .service(process_status)
+                        .service(channel_status)
+                        .service(output_status)
+                        .service(output_control)
+                        .service(delay_status)
+                        .service(delay_dump)
+                        .service(instant_replay)
+                        .service(freeze_hold)
+                        .service(emergency_start)
+                        .service(emergency_end)
+                        .service(program_status)
+                        .service(program_switch)
                         .service(process_control)
                         .service(get_playlist)
                         .service(save_playlist)
+                        .service(get_playlist_history)
+                        .service(get_media_audit)
+                        .service(run_media_audit)
                         .service(gen_playlist)
                         .service(del_playlist)
                         .service(get_log)
+                        .service(search_log)
                         .service(file_browser)
                         .service(add_dir)
                         .service(move_rename)
                         .service(remove)
                         .service(save_file)
                         .service(import_playlist)
+                        .service(share_file)
                         .service(get_program)
+                        .service(get_play_history)
+                        .service(get_event_history)
+                        .service(get_alert_rules)
+                        .service(add_alert_rule)
+                        .service(update_alert_rule)
+                        .service(delete_alert_rule)
+                        .service(export_as_run)
+                        .service(get_availability_report)
                         .service(get_system_stat)
+                        .service(export_archive)
+                        .service(import_archive)
                         .service(generate_uuid),
                 )
+                .service(
+                    web::scope("/rtmp")
+                        .service(rtmp_publish)
+                        .service(rtmp_publish_done),
+                )
+                .service(
+                    web::scope("/button")
+                        .service(button_preset)
+                        .service(button_action),
+                )
                 .service(
                     web::scope("/data")
                         .service(validate_uuid)
-                        .service(event_stream),
+                        .service(event_stream)
+                        .service(log_stream),
                 )
                 .service(get_file)
+                .service(get_shared_file)
                 .service(get_public);
 
             #[cfg(all(not(debug_assertions), feature = "embed_frontend"))]
@@ -196,12 +355,44 @@ async fn main() -> std::io::Result<()> {
 
             web_app
         })
-        .bind((addr, port))?
-        .workers(thread_counter())
-        .run()
-        .await?;
+        .workers(thread_counter());
+
+        let server = match systemd::listener(listen_fds) {
+            Some(listener) => {
+                info!("Running ffplayout API, listen on socket from systemd");
+                server.listen(listener)?
+            }
+            None => {
+                info!("Running ffplayout API, listen on http://{conn}");
+                server.bind((addr, port))?
+            }
+        };
+
+        // channels are running and the API is bound, now the service counts as started
+        systemd::notify("READY=1");
+
+        if let Some(interval) = systemd::watchdog_interval() {
+            tokio::spawn(systemd::watchdog(interval));
+        }
+
+        server.run().await?;
+        systemd::notify("STOPPING=1");
     } else if ARGS.drop_db {
         db_drop().await;
+    } else if ARGS.validate_all {
+        exit(schedule_check::validate_all(&pool).await);
+    } else if let Some(seconds) = ARGS.benchmark {
+        exit(benchmark::run(&pool, seconds).await);
+    } else if let Some(seconds) = ARGS.test_output {
+        exit(output_test::run(&pool, seconds).await);
+    } else if ARGS.dry_run {
+        exit(dry_run::run(&pool).await);
+    } else if let Some(path) = &ARGS.diff_config {
+        exit(config_diff::run(&pool, path).await);
+    } else if ARGS.check_config {
+        exit(config_check::run(&pool).await);
+    } else if ARGS.scan_media {
+        exit(media_scan::run(&pool).await);
     } else {
         let channel = ARGS.channel.clone().unwrap_or_else(|| vec![1]);
 
@@ -218,7 +409,7 @@ async fn main() -> std::io::Result<()> {
                 .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
             let manager = ChannelManager::new(Some(pool.clone()), channel.clone(), config.clone());
 
-            if ARGS.foreground {
+            if ARGS.foreground || ARGS.tui {
                 if ARGS.channel.is_none() {
                     error!(
                         "Foreground mode needs at least 1 channel, run with `--channel (1 2 ...)`"
@@ -230,6 +421,10 @@ async fn main() -> std::io::Result<()> {
                 channel_controllers.lock().await.add(manager.clone());
                 mail_queues.lock().await.push(m_queue.clone());
 
+                if ARGS.tui && index == 0 {
+                    tokio::spawn(tui::run(channel_controllers.clone()));
+                }
+
                 manager
                     .foreground_start(index)
                     .await
@@ -241,35 +436,26 @@ async fn main() -> std::io::Result<()> {
                     exit(1);
                 };
             } else if ARGS.validate {
-                let mut playlist_path = config.channel.playlists.clone();
                 let start_sec = config.playlist.start_sec.unwrap();
                 let date = get_date(false, start_sec, false, &config.channel.timezone);
-
-                if playlist_path.is_dir() || is_remote(&playlist_path.to_string_lossy()) {
-                    let d: Vec<&str> = date.split('-').collect();
-                    playlist_path = playlist_path
-                        .join(d[0])
-                        .join(d[1])
-                        .join(date.clone())
-                        .with_extension("json");
-                }
-
-                let mut f = File::options()
-                    .read(true)
-                    .write(false)
-                    .open(&playlist_path)
-                    .await?;
-
-                let mut contents = String::new();
-                f.read_to_string(&mut contents).await?;
-
-                let playlist: JsonPlaylist = serde_json::from_str(&contents)?;
+                let store = manager.playlist_store().await;
+
+                let playlist = match store.read(&date).await {
+                    Ok(Some(p)) => p,
+                    Ok(None) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("Playlist {} not exists!", store.location(&date)),
+                        ));
+                    }
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+                };
 
                 validate_playlist(
                     config,
                     Arc::new(Mutex::new(Vec::new())),
                     playlist,
-                    Arc::new(AtomicBool::new(false)),
+                    Arc::new(AtomicBool::new(true)),
                 )
                 .await;
             } else if !ARGS.init {
@@ -278,8 +464,18 @@ async fn main() -> std::io::Result<()> {
         }
     }
 
-    for channel_ctl in &channel_controllers.lock().await.channels {
+    let channels = channel_controllers.lock().await.channels.clone();
+
+    for channel_ctl in &channels {
         channel_ctl.channel.lock().await.active = false;
+    }
+
+    if let Some(limit) = ARGS.drain {
+        // planned shutdown, all channels finish their clip at the same time
+        join_all(channels.iter().map(|c| c.drain(Duration::from_secs(limit)))).await;
+    }
+
+    for channel_ctl in &channels {
         channel_ctl
             .stop_all(false)
             .await
@@ -288,5 +484,9 @@ async fn main() -> std::io::Result<()> {
 
     pool.close().await;
 
+    if long_running {
+        remove_pid().await;
+    }
+
     Ok(())
 }
@@ -1,33 +1,57 @@
 use std::{
-    cmp, fmt,
+    collections::HashMap,
+    fmt,
     path::Path,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use async_walkdir::{Filtering, WalkDir};
+use chrono::Local;
 use log::*;
 use m3u8_rs::Playlist;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::{Pool, Sqlite};
 use tokio::{
     fs,
     io::{self, AsyncReadExt},
     process::{Child, ChildStdout},
     sync::Mutex,
-    time::{sleep, Duration, Instant},
+    time::{sleep, Instant},
 };
 use tokio_stream::StreamExt;
 
 use crate::player::{
-    output::{player, write_hls},
-    utils::{folder::fill_filler_list, Media},
+    filter::{v_drawtext::zmq_text, volume_unit},
+    input::program::ProgramSwitch,
+    output::{player, write_hls, DelayControl, OutputSwitch, ReplayControl},
+    utils::{
+        channel_state::{ChannelState, StateTracker},
+        drift::ScheduleDrift,
+        folder::fill_filler_list,
+        health::{output_lost, OutputHealth},
+        media_audit::MediaAudit,
+        playlist_store::PlaylistStorage,
+        progress::EncoderStats,
+        restart::{RestartAction, RestartState},
+        supervisor::Supervisor,
+        vast::VastAd,
+        Media,
+    },
 };
 use crate::utils::{
     config::{OutputMode::*, PlayoutConfig},
+    control::filter_command,
     errors::ServiceError,
+    event_history::{self, EventKind},
+    play_history, plugins,
+    tally::{self, Tally},
+    text_layers::{layer_names, layer_text, DEFAULT_LAYER},
+    uptime,
 };
 use crate::ARGS;
 use crate::{
@@ -58,6 +82,21 @@ impl fmt::Display for ProcessUnit {
 
 use ProcessUnit::*;
 
+/// How a config change reached the running channel.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigApply {
+    /// Channel is not running, or the change doesn't affect it.
+    #[default]
+    Saved,
+    /// Running filters got the new values, without interruption.
+    Live,
+    /// Current clip is played again with the new settings.
+    Reload,
+    /// Decoder and encoder are started again, at the current clip.
+    Restart,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct ChannelManager {
     pub db_pool: Option<Pool<Sqlite>>,
@@ -78,6 +117,38 @@ pub struct ChannelManager {
     pub filler_list: Arc<Mutex<Vec<Media>>>,
     pub current_index: Arc<AtomicUsize>,
     pub filler_index: Arc<AtomicUsize>,
+    pub encoder_stats: Arc<Mutex<EncoderStats>>,
+    pub output_health: Arc<Mutex<OutputHealth>>,
+    pub drift: Arc<Mutex<ScheduleDrift>>,
+    /// Play the slate instead of the program, after too many failures.
+    pub slate: Arc<AtomicBool>,
+    pub media_audit: Arc<Mutex<MediaAudit>>,
+    /// Control for the delay buffer, while it runs.
+    pub delay: Arc<Mutex<Option<DelayControl>>>,
+    /// Control for the instant replay, while the program is recorded.
+    pub replay: Arc<Mutex<Option<ReplayControl>>>,
+    /// Freeze frame, which plays before the next clip.
+    pub freeze: Arc<Mutex<Option<Media>>>,
+    /// Play the emergency slate instead of the program, until the operator ends it.
+    pub emergency: Arc<AtomicBool>,
+    /// Planned stop, the player ends after the current clip.
+    pub draining: Arc<AtomicBool>,
+    /// Starts and failures of the processes.
+    pub supervisor: Arc<Mutex<Supervisor>>,
+    /// Channel state for the API, changed only on valid transitions.
+    pub state: Arc<Mutex<StateTracker>>,
+    /// Renditions, which are stopped by the operator.
+    pub outputs: OutputSwitch,
+    /// A/B switch between program and secondary source.
+    pub program: Arc<Mutex<ProgramSwitch>>,
+    /// Stream on the external RTMP server is published, set from the publish callbacks.
+    pub ingest_published: Arc<AtomicBool>,
+    /// Last message number per text layer, a new message stops the animation from the old one.
+    pub text_layers: Arc<Mutex<HashMap<String, u64>>>,
+    /// Tracking from the ads in the list, by their begin in milliseconds.
+    pub ad_tracking: Arc<Mutex<HashMap<i64, VastAd>>>,
+    /// Tally which was sent last.
+    pub tally: Arc<Mutex<Tally>>,
 }
 
 impl ChannelManager {
@@ -88,6 +159,7 @@ impl ChannelManager {
             channel: Arc::new(Mutex::new(channel)),
             config: Arc::new(Mutex::new(config)),
             list_init: Arc::new(AtomicBool::new(true)),
+            filter_chain: Some(Arc::new(Mutex::new(vec![]))),
             current_media: Arc::new(Mutex::new(None)),
             current_list: Arc::new(Mutex::new(vec![Media::default()])),
             filler_list: Arc::new(Mutex::new(vec![])),
@@ -109,9 +181,192 @@ impl ChannelManager {
         channel.timezone.clone_from(&other.timezone);
     }
 
-    pub async fn update_config(&self, new_config: PlayoutConfig) {
+    /// Change the channel state, invalid transitions are ignored.
+    pub async fn set_state(&self, next: ChannelState) {
+        let id = self.channel.lock().await.id;
+        let mut tracker = self.state.lock().await;
+        let current = tracker.state;
+
+        if !tracker.set(next, Local::now()) {
+            debug!(target: Target::file_mail(), channel = id; "Ignore state change from <yellow>{current}</> to <yellow>{next}</>");
+        } else if current != next {
+            event_history::push(id, EventKind::State, format!("State: {current} -> {next}"));
+        }
+
+        drop(tracker);
+        tally::update(self);
+        plugins::event(self, "state", json!({ "state": next.to_string() }));
+    }
+
+    /// State from what is on air right now: live ingest, a fallback or the program.
+    pub async fn on_air(&self) {
+        let fallback = self.emergency.load(Ordering::SeqCst)
+            || self.slate.load(Ordering::SeqCst)
+            || self.current_media.lock().await.as_ref().is_some_and(|m| {
+                matches!(
+                    m.category.as_str(),
+                    "emergency" | "slate" | "freeze" | "filler"
+                )
+            });
+
+        let secondary = self
+            .current_media
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|m| m.category == "secondary");
+
+        let state = if self.ingest_is_alive.load(Ordering::SeqCst) || secondary {
+            ChannelState::OnAirLive
+        } else if fallback {
+            ChannelState::Fallback
+        } else {
+            ChannelState::OnAirPlaylist
+        };
+
+        self.set_state(state).await;
+    }
+
+    /// Playlist backend from the current config.
+    pub async fn playlist_store(&self) -> PlaylistStorage {
+        PlaylistStorage::new(&self.config.lock().await, self.db_pool.clone())
+    }
+
+    /// Apply a new config to the running channel.
+    ///
+    /// Text style, font and volume are sent to the running filters over zmq, when the text overlay uses it.
+    /// Other changes which only affect the decoder (logo, filters, logging, filler, tasks)
+    /// are applied by reloading the current clip, ingest changes restart only the idle ingest server.
+    /// A running live stream is not interrupted, it gets the new settings on the next connection.
+    /// Changes to the encoder, or a failed filter command, restart decoder and encoder at the current clip.
+    pub async fn update_config(&self, mut new_config: PlayoutConfig) -> ConfigApply {
         let mut config = self.config.lock().await;
-        *config = new_config;
+        let reload = Reload::new(&config, &new_config);
+
+        if !reload.full {
+            // running filters are still bound to the old sockets
+            new_config
+                .text
+                .zmq_stream_socket
+                .clone_from(&config.text.zmq_stream_socket);
+            new_config
+                .text
+                .zmq_server_socket
+                .clone_from(&config.text.zmq_server_socket);
+        }
+
+        let id = new_config.general.channel_id;
+
+        if reload.full && self.is_alive.load(Ordering::SeqCst) {
+            info!(target: Target::file_mail(), channel = id; "Config changed, restart decoder and encoder");
+
+            // hold the old config until the processes are stopped, so no clip is built with
+            // the new format while the old encoder runs
+            let applied = self.restart_output().await;
+            *config = new_config;
+
+            return applied;
+        }
+
+        *config = new_config.clone();
+        drop(config);
+
+        if !self.is_alive.load(Ordering::SeqCst) {
+            return ConfigApply::Saved;
+        }
+
+        let mut applied = ConfigApply::Saved;
+
+        if reload.text || reload.volume {
+            match self.live_update(&new_config, &reload).await {
+                Ok(()) => {
+                    info!(target: Target::file_mail(), channel = id; "Config changed, running filters are updated");
+
+                    applied = ConfigApply::Live;
+                }
+                Err(e) => {
+                    warn!(target: Target::file_mail(), channel = id; "{e}, restart decoder and encoder");
+
+                    return self.restart_output().await;
+                }
+            }
+        }
+
+        if reload.filler {
+            fill_filler_list(&new_config, Some(self.filler_list.clone())).await;
+            self.filler_index.store(0, Ordering::SeqCst);
+        }
+
+        if reload.ingest && !self.ingest_is_alive.load(Ordering::SeqCst) {
+            info!(target: Target::file_mail(), channel = id; "Config changed, restart ingest server");
+
+            if let Err(e) = self.stop(Ingest).await {
+                error!(target: Target::file_mail(), channel = id; "{e}");
+            }
+        }
+
+        if reload.decoder && !self.ingest_is_alive.load(Ordering::SeqCst) {
+            info!(target: Target::file_mail(), channel = id; "Config changed, reload current clip");
+
+            self.list_init.store(true, Ordering::SeqCst);
+
+            if let Err(e) = self.stop(Decoder).await {
+                error!(target: Target::file_mail(), channel = id; "{e}");
+            }
+
+            applied = ConfigApply::Reload;
+        }
+
+        applied
+    }
+
+    /// Send the new text defaults and volume to the filters.
+    async fn live_update(
+        &self,
+        config: &PlayoutConfig,
+        reload: &Reload,
+    ) -> Result<(), ServiceError> {
+        if reload.text {
+            let chain = match &self.filter_chain {
+                Some(chain) => chain.lock().await.clone(),
+                None => vec![],
+            };
+            let mut layers = vec![DEFAULT_LAYER.to_string()];
+            layers.append(&mut layer_names(&config.text.layers));
+
+            for layer in layers {
+                let message = layer_text(&chain, &layer).unwrap_or_else(|| "text=''".to_string());
+
+                filter_command(
+                    self,
+                    &format!("drawtext@{layer} reinit {}", zmq_text(config, &message)),
+                )
+                .await?;
+            }
+        }
+
+        if reload.volume {
+            filter_command(
+                self,
+                &format!("volume@volume volume {}", config.processing.volume),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stop the processes, the channel loop starts them again with the new config.
+    async fn restart_output(&self) -> ConfigApply {
+        let id = self.channel.lock().await.id;
+
+        event_history::push(id, EventKind::State, "Output restarted for config change");
+
+        if let Err(e) = self.stop_all(false).await {
+            error!(target: Target::file_mail(), channel = id; "{e}");
+        }
+
+        ConfigApply::Restart
     }
 
     pub async fn start(&self) -> Result<(), ServiceError> {
@@ -124,35 +379,92 @@ impl ChannelManager {
         let channel_id = self.channel.lock().await.id;
 
         handles::update_player(&pool_clone, channel_id, true).await?;
+        event_history::push(channel_id, EventKind::State, "Channel started");
+        self.draining.store(false, Ordering::SeqCst);
+        self.set_state(ChannelState::Starting).await;
 
         tokio::spawn(async move {
-            const MAX_DELAY: Duration = Duration::from_secs(180);
-            let mut elapsed = Duration::from_secs(5);
-            let mut retry_delay = Duration::from_millis(500);
+            let mut restart = RestartState::default();
 
             while self_clone.channel.lock().await.active {
                 self_clone.is_alive.store(true, Ordering::SeqCst);
                 self_clone.list_init.store(true, Ordering::SeqCst);
+                self_clone.set_state(ChannelState::Starting).await;
 
                 let timer = Instant::now();
-
-                if let Err(e) = run_channel(self_clone.clone()).await {
-                    if let Err(e) = self_clone.stop_all(false).await {
-                        error!(target: Target::all(), channel = channel_id; "Failed to stop channel <yellow>{channel_id}</>: {e}");
-                        break;
+                let result = run_channel(self_clone.clone()).await;
+
+                self_clone
+                    .supervisor
+                    .lock()
+                    .await
+                    .encoder
+                    .stopped(Local::now());
+
+                match result {
+                    // slate time is over, try the program again
+                    Ok(_) => {
+                        if self_clone.slate.swap(false, Ordering::SeqCst) {
+                            event_history::push(
+                                channel_id,
+                                EventKind::State,
+                                "Slate is over, play the program again",
+                            );
+                        }
                     }
+                    Err(e) => {
+                        output_lost(&self_clone, &e.to_string()).await;
 
-                    if timer.elapsed() < elapsed {
-                        elapsed += retry_delay;
-                        retry_delay = cmp::min(retry_delay * 2, MAX_DELAY);
-                    } else {
-                        elapsed = Duration::from_secs(5);
-                        retry_delay = Duration::from_secs(1);
-                    }
+                        if let Err(e) = self_clone.stop_all(false).await {
+                            error!(target: Target::all(), channel = channel_id; "Failed to stop channel <yellow>{channel_id}</>: {e}");
+                            break;
+                        }
+
+                        let general = self_clone.config.lock().await.general.clone();
+
+                        let action = restart.failed(
+                            &general,
+                            Local::now().timestamp(),
+                            timer.elapsed().as_secs_f64(),
+                        );
+
+                        {
+                            let mut supervisor = self_clone.supervisor.lock().await;
+                            supervisor.channel_restarts = restart.restarts();
+                            supervisor.channel_failures = restart.failures;
+                        }
+
+                        match action {
+                            RestartAction::Retry(delay) => {
+                                error!(target: Target::all(), channel = channel_id; "Run channel <yellow>{channel_id}</> failed: {e} | retry in <yellow>{}</> seconds", delay.as_secs());
+
+                                sleep(delay).await;
+                            }
+                            RestartAction::Slate(delay) => {
+                                if !self_clone.slate.swap(true, Ordering::SeqCst) {
+                                    error!(target: Target::all(), channel = channel_id; "Channel <yellow>{channel_id}</> failed <yellow>{}</> times in a row: {e} | play slate in <yellow>{}</> seconds", restart.failures, delay.as_secs());
+                                } else {
+                                    error!(target: Target::all(), channel = channel_id; "Run slate failed: {e} | retry in <yellow>{}</> seconds", delay.as_secs());
+                                }
+
+                                sleep(delay).await;
+                            }
+                            RestartAction::Stop => {
+                                error!(target: Target::all(), channel = channel_id; "Channel <yellow>{channel_id}</> restarted <yellow>{}</> times in the last hour, stop it: {e}", restart.restarts());
+
+                                self_clone.channel.lock().await.active = false;
+                                self_clone.slate.store(false, Ordering::SeqCst);
 
-                    error!(target: Target::all(), channel = channel_id; "Run channel <yellow>{channel_id}</> failed: {e} | retry in <yellow>{}</> seconds", retry_delay.as_secs());
+                                if let Err(e) = self_clone.stop_all(true).await {
+                                    error!(target: Target::all(), channel = channel_id; "Failed to stop channel <yellow>{channel_id}</>: {e}");
+                                }
 
-                    sleep(retry_delay).await;
+                                self_clone.set_state(ChannelState::Failed).await;
+
+                                break;
+                            }
+                        }
+                    }
                 }
             }
 
@@ -169,6 +481,7 @@ impl ChannelManager {
 
         self.is_alive.store(true, Ordering::SeqCst);
         self.list_init.store(true, Ordering::SeqCst);
+        self.set_state(ChannelState::Starting).await;
 
         let pool_clone = self.db_pool.clone().unwrap();
         let self_clone = self.clone();
@@ -252,12 +565,46 @@ impl ChannelManager {
     }
 
     /// No matter what is running, terminate them all.
+    /// Let the current clip finish, then the player ends. The limit is for long clips and a live
+    /// ingest. The channel should be deactivated before, and stopped afterwards.
+    pub async fn drain(&self, limit: Duration) {
+        let channel_id = self.channel.lock().await.id;
+
+        if !self.is_alive.load(Ordering::SeqCst) {
+            return;
+        }
+
+        info!(target: Target::all(), channel = channel_id; "Stop channel <yellow>{channel_id}</> after the current clip");
+        self.draining.store(true, Ordering::SeqCst);
+        self.set_state(ChannelState::Stopping).await;
+
+        let deadline = Instant::now() + limit;
+
+        while self.is_alive.load(Ordering::SeqCst) {
+            if Instant::now() >= deadline {
+                warn!(target: Target::all(), channel = channel_id; "Clip is not finished after <yellow>{}</> seconds, stop channel now", limit.as_secs());
+                break;
+            }
+
+            sleep(Duration::from_millis(200)).await;
+        }
+
+        // the encoder writes the rest from its input
+        if let Err(e) = self.wait(Encoder).await {
+            error!(target: Target::all(), channel = channel_id; "{e}");
+        }
+
+        self.draining.store(false, Ordering::SeqCst);
+    }
+
     pub async fn stop_all(&self, permanent: bool) -> Result<(), ServiceError> {
         let channel_id = self.channel.lock().await.id;
 
         if permanent {
             let pool = self.db_pool.clone().unwrap();
 
+            self.set_state(ChannelState::Stopping).await;
+
             if self.is_alive.load(Ordering::SeqCst) {
                 debug!(target: Target::all(), channel = channel_id; "Deactivate playout and stop all child processes from channel: <yellow>{channel_id}</>");
             }
@@ -265,6 +612,17 @@ impl ChannelManager {
             if let Err(e) = handles::update_player(&pool, channel_id, false).await {
                 error!(target: Target::all(), channel = channel_id; "Player status cannot be written: {e}");
             };
+
+            event_history::push(channel_id, EventKind::State, "Channel stopped");
+
+            if self.output_health.lock().await.stopped() {
+                tokio::spawn(uptime::record(
+                    pool,
+                    channel_id,
+                    false,
+                    "stopped".to_string(),
+                ));
+            }
         } else {
             debug!(target: Target::all(), channel = channel_id; "Stop all child processes from channel: <yellow>{channel_id}</>");
         }
@@ -272,6 +630,11 @@ impl ChannelManager {
         self.is_alive.store(false, Ordering::SeqCst);
         self.ingest_is_alive.store(false, Ordering::SeqCst);
 
+        if let Some(pool) = self.db_pool.clone() {
+            let timezone = self.channel.lock().await.timezone;
+            tokio::spawn(play_history::finish(pool, channel_id, timezone));
+        }
+
         for unit in [Decoder, Encoder, Ingest] {
             if let Err(e) = self.stop(unit).await {
                 if !e.to_string().contains("exited process") {
@@ -280,10 +643,91 @@ impl ChannelManager {
             }
         }
 
+        if permanent {
+            self.set_state(ChannelState::Stopped).await;
+        }
+
         Ok(())
     }
 }
 
+/// Which parts of a running channel are affected by a config change.
+#[derive(Debug, Default)]
+pub struct Reload {
+    /// Encoder format changed, decoder and encoder restart.
+    pub full: bool,
+    pub decoder: bool,
+    pub ingest: bool,
+    pub filler: bool,
+    /// Text style or font changed, the drawtext filter with zmq gets them.
+    pub text: bool,
+    /// Volume changed, the volume filter next to zmq gets it.
+    pub volume: bool,
+}
+
+impl Reload {
+    pub fn new(old: &PlayoutConfig, new: &PlayoutConfig) -> Self {
+        fn changed<T: Serialize>(a: &T, b: &T) -> bool {
+            serde_json::to_value(a).ok() != serde_json::to_value(b).ok()
+        }
+
+        let (o_proc, n_proc) = (&old.processing, &new.processing);
+
+        // the encoder input/output format depends on these values
+        let full = changed(&old.channel, &new.channel)
+            || changed(&old.advanced, &new.advanced)
+            || changed(&old.output, &new.output)
+            || changed(&old.playlist, &new.playlist)
+            || o_proc.mode != n_proc.mode
+            || o_proc.audio_only != n_proc.audio_only
+            || o_proc.visualization != n_proc.visualization
+            || o_proc.copy_audio != n_proc.copy_audio
+            || o_proc.copy_video != n_proc.copy_video
+            || o_proc.width != n_proc.width
+            || o_proc.height != n_proc.height
+            || o_proc.aspect != n_proc.aspect
+            || o_proc.fps != n_proc.fps
+            || o_proc.audio_tracks != n_proc.audio_tracks
+            || o_proc.audio_channels != n_proc.audio_channels
+            || o_proc.vtt_enable != n_proc.vtt_enable
+            || old.ingest.enable != new.ingest.enable
+            || old.text.add_text != new.text.add_text
+            || old.text.text_from_filename != new.text.text_from_filename;
+
+        let filler = changed(&old.storage, &new.storage);
+        // without video there is no drawtext filter, which could get the changes
+        let text = old.text.zmq_stream_socket.is_some()
+            && !o_proc.audio_only
+            && !(old.output.mode == HLS && o_proc.copy_video)
+            && changed(&old.text, &new.text);
+        let volume = volume_unit(old).is_some()
+            && volume_unit(old) == volume_unit(new)
+            && o_proc.volume != n_proc.volume;
+
+        // values which go to the running filters need no reload
+        let mut n_compare = n_proc.clone();
+
+        if volume {
+            n_compare.volume = o_proc.volume;
+        }
+
+        Self {
+            full,
+            decoder: filler
+                || changed(o_proc, &n_compare)
+                || (!text && changed(&old.text, &new.text))
+                || changed(&old.logging, &new.logging)
+                || old.general.stop_threshold != new.general.stop_threshold,
+            ingest: changed(&old.ingest, &new.ingest)
+                || changed(&old.processing, &new.processing)
+                || old.logging.ingest_level != new.logging.ingest_level,
+            filler,
+            text,
+            volume,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct ChannelController {
     pub channels: Vec<ChannelManager>,
@@ -343,6 +787,10 @@ async fn run_channel(manager: ChannelManager) -> Result<(), ServiceError> {
 
     debug!(target: Target::all(), channel = channel_id; "Start ffplayout v{VERSION}, channel: <yellow>{channel_id}</>");
 
+    for warning in config.warnings() {
+        warn!(target: Target::file_mail(), channel = channel_id; "{warning}");
+    }
+
     // Fill filler list, can also be a single file.
     // INFO: Was running in a thread, but when it runs in a tokio task and
     // after start a filler is needed, the first one will be ignored because the list is not filled.
@@ -360,6 +808,12 @@ async fn run_channel(manager: ChannelManager) -> Result<(), ServiceError> {
 }
 
 pub async fn drain_hls_path(path: &Path) -> io::Result<()> {
+    cleanup_hls_path(path, Duration::ZERO).await.map(|_| ())
+}
+
+/// Delete segments which are in no playlist and older than `min_age`,
+/// returns the number of deleted files and their size.
+pub async fn cleanup_hls_path(path: &Path, min_age: Duration) -> io::Result<(usize, u64)> {
     let m3u8_files = find_m3u8_files(path).await?;
     let mut pl_segments = vec![];
 
@@ -375,7 +829,7 @@ pub async fn drain_hls_path(path: &Path) -> io::Result<()> {
         };
     }
 
-    delete_old_segments(path, &pl_segments).await
+    delete_old_segments(path, &pl_segments, min_age).await
 }
 
 /// Recursively searches for all files with the .m3u8 extension in the specified path.
@@ -410,7 +864,11 @@ async fn find_m3u8_files(path: &Path) -> io::Result<Vec<String>> {
 async fn delete_old_segments<P: AsRef<Path> + Clone + std::fmt::Debug>(
     path: P,
     pl_segments: &[String],
-) -> io::Result<()> {
+    min_age: Duration,
+) -> io::Result<(usize, u64)> {
+    let mut deleted = 0;
+    let mut size = 0;
+
     let mut entries = WalkDir::new(path).filter(move |entry| async move {
         if entry.path().is_file()
             && entry
@@ -429,8 +887,21 @@ async fn delete_old_segments<P: AsRef<Path> + Clone + std::fmt::Debug>(
             Some(Ok(entry)) => {
                 let filename = entry.file_name().to_string_lossy().to_string();
 
-                if !pl_segments.contains(&filename) {
+                if pl_segments.contains(&filename) {
+                    continue;
+                }
+
+                let metadata = entry.metadata().await?;
+                let age = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|m| m.elapsed().ok())
+                    .unwrap_or_default();
+
+                if age >= min_age {
                     fs::remove_file(entry.path()).await?;
+                    deleted += 1;
+                    size += metadata.len();
                 }
             }
             Some(Err(e)) => {
@@ -441,5 +912,5 @@ async fn delete_old_segments<P: AsRef<Path> + Clone + std::fmt::Debug>(
         }
     }
 
-    Ok(())
+    Ok((deleted, size))
 }
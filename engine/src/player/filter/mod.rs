@@ -8,11 +8,11 @@ mod custom;
 pub mod v_drawtext;
 
 use crate::player::{
-    controller::ProcessUnit::*,
-    utils::{custom_format, fps_calc, is_close, probe::VideoStream, Media},
+    controller::{ProcessUnit, ProcessUnit::*},
+    utils::{custom_format, fps_calc, has_audio_input, is_close, probe::VideoStream, Media},
 };
 use crate::utils::{
-    config::{OutputMode::*, PlayoutConfig},
+    config::{OutputMode::*, PlayoutConfig, Visualization},
     logging::Target,
 };
 use crate::vec_strings;
@@ -34,6 +34,9 @@ impl fmt::Display for FilterType {
 
 use FilterType::*;
 
+/// Audio sample rate from the decoder output.
+const SAMPLE_RATE: i64 = 48000;
+
 #[derive(Debug, Clone)]
 pub struct Filters {
     pub audio_chain: String,
@@ -100,7 +103,11 @@ impl Filters {
 
             chain.push_str(&selector);
 
-            if filter.starts_with("aevalsrc") || filter.starts_with("movie") {
+            // sources and filters with their own input link
+            if filter.starts_with("aevalsrc")
+                || filter.starts_with("movie")
+                || filter.starts_with('[')
+            {
                 chain.push_str(&format!("{sep}{filter}"));
             } else {
                 chain.push_str(&format!(
@@ -474,7 +481,7 @@ fn add_audio(node: &Media, chain: &mut Filters, nr: i32, config: &PlayoutConfig)
 }
 
 fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32, config: &PlayoutConfig) {
-    if !Path::new(&node.audio).is_file() {
+    if !has_audio_input(node) {
         if let Some(audio_duration) = node
             .probe
             .as_ref()
@@ -494,7 +501,43 @@ fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32, config: &Playout
     }
 }
 
-fn audio_volume(chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
+/// Process with the zmq filter, which can change the volume while it runs.
+/// In HLS mode this is the decoder, in stream and null mode the encoder.
+/// `None` when the volume is fixed in the decoder.
+pub fn volume_unit(config: &PlayoutConfig) -> Option<ProcessUnit> {
+    if config.text.zmq_stream_socket.is_none()
+        || config.processing.audio_only
+        || config.processing.copy_audio
+        || config.processing.audio_track_index != -1
+        || config.advanced.filter.volume.is_some()
+    {
+        return None;
+    }
+
+    match config.output.mode {
+        HLS if !config.processing.copy_video => Some(Decoder),
+        Stream | Null
+            if config.output.output_count <= 1 && config.output.output_filter.is_none() =>
+        {
+            Some(Encoder)
+        }
+        _ => None,
+    }
+}
+
+fn audio_volume(chain: &mut Filters, config: &PlayoutConfig, nr: i32, unit: ProcessUnit) {
+    match volume_unit(config) {
+        // the encoder sets the volume
+        Some(Encoder) if unit != Encoder => return,
+        Some(_) => {
+            let volume = format!("volume@volume={}", config.processing.volume);
+
+            chain.add_filter(&volume, nr, Audio);
+            return;
+        }
+        None => {}
+    }
+
     if config.processing.volume != 1.0 {
         let volume = match config.advanced.filter.volume.clone() {
             Some(volume) => custom_format(&volume, &[config.processing.volume]),
@@ -505,6 +548,68 @@ fn audio_volume(chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
     }
 }
 
+/// Frames and audio samples from a clip, which starts at `begin` and plays `length` seconds.
+/// Both are counted from the start of the day, so rounding differences don't add up from clip to clip.
+pub fn gapless_length(begin: f64, length: f64, fps: f64) -> (i64, i64) {
+    if fps <= 0.0 {
+        return (0, 0);
+    }
+
+    let frame = |sec: f64| (sec * fps).round() as i64;
+    let sample = |frame: i64| (frame as f64 * SAMPLE_RATE as f64 / fps).round() as i64;
+    let start = frame(begin);
+    let end = frame(begin + length).max(start + 1);
+
+    (end - start, sample(end) - sample(start))
+}
+
+/// End the clip exactly at the frame, or audio sample, where the next one begins.
+/// Missing frames clone the last one, missing audio is filled with silence.
+fn gapless(
+    node: &Media,
+    chain: &mut Filters,
+    nr: i32,
+    filter_type: FilterType,
+    config: &PlayoutConfig,
+) {
+    let (frames, samples) = gapless_length(
+        node.begin.unwrap_or_default(),
+        node.out - node.seek,
+        config.processing.fps,
+    );
+
+    if frames < 1 {
+        return;
+    }
+
+    let filter = match filter_type {
+        Audio => format!("aresample={SAMPLE_RATE},apad,atrim=end_sample={samples}"),
+        Video => format!("tpad=stop=-1:stop_mode=clone,trim=end_frame={frames}"),
+    };
+
+    chain.add_filter(&filter, nr, filter_type);
+}
+
+/// Play faster, the clip ends earlier and the playout catches up with the schedule.
+fn speed(
+    node: &Media,
+    chain: &mut Filters,
+    nr: i32,
+    filter_type: FilterType,
+    config: &PlayoutConfig,
+) {
+    let Some(speed) = node.speed.filter(|s| *s > 1.0) else {
+        return;
+    };
+
+    let filter = match filter_type {
+        Audio => format!("atempo={speed:.4}"),
+        Video => format!("setpts=PTS/{speed:.4},fps={}", config.processing.fps),
+    };
+
+    chain.add_filter(&filter, nr, filter_type);
+}
+
 fn aspect_calc(aspect_string: &Option<String>, config: &PlayoutConfig) -> f64 {
     let mut source_aspect = config.processing.aspect;
 
@@ -547,6 +652,31 @@ pub fn split_filter(
     }
 }
 
+/// Video from the first audio track, for radio channels which stream to video platforms.
+fn visualization(chain: &mut Filters, config: &PlayoutConfig) {
+    if !config.processing.audio_only
+        || config.output.mode != Stream
+        || config.output.output_filter.is_some()
+    {
+        return;
+    }
+
+    let size = format!("{}x{}", config.processing.width, config.processing.height);
+    let fps = config.processing.fps;
+
+    let filter = match config.processing.visualization {
+        Visualization::Off => return,
+        Visualization::Waves => {
+            format!("[0:a:0]showwaves=s={size}:mode=cline:rate={fps},format=yuv420p")
+        }
+        Visualization::Spectrum => {
+            format!("[0:a:0]showspectrum=s={size}:slide=scroll,fps={fps},format=yuv420p")
+        }
+    };
+
+    chain.add_filter(&filter, 0, Video);
+}
+
 /// Process output filter chain and add new filters to existing ones.
 fn process_output_filters(config: &PlayoutConfig, chain: &mut Filters, custom_filter: &str) {
     let filter =
@@ -591,6 +721,39 @@ fn custom(filter: &str, chain: &mut Filters, nr: i32, filter_type: FilterType) {
     }
 }
 
+/// Filter chain for a decoder, which reads many clips after another.
+/// Every clip is fitted into the frame and gets the channel filters,
+/// per clip filters are not possible here.
+pub fn concat_chains(config: &PlayoutConfig) -> Filters {
+    let mut filters = Filters::new(config.clone(), 0);
+    let (proc_vf, proc_af) =
+        custom::filter_node(config.general.channel_id, &config.processing.custom_filter);
+
+    if !config.processing.audio_only && !config.processing.copy_video {
+        let (width, height) = (config.processing.width, config.processing.height);
+
+        filters.add_filter(
+            &format!("scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1"),
+            0,
+            Video,
+        );
+        fps(0.0, &mut filters, config);
+        overlay(&mut Media::default(), &mut filters, config);
+        custom(&proc_vf, &mut filters, 0, Video);
+    }
+
+    if !config.processing.copy_audio {
+        for i in 0..config.processing.audio_tracks {
+            // fill gaps between the clips with silence
+            filters.add_filter(&format!("aresample={SAMPLE_RATE}:async=1"), i, Audio);
+            audio_volume(&mut filters, config, i, Decoder);
+            custom(&proc_af, &mut filters, i, Audio);
+        }
+    }
+
+    filters
+}
+
 pub async fn filter_chains(
     config: &PlayoutConfig,
     node: &mut Media,
@@ -607,6 +770,14 @@ pub async fn filter_chains(
             add_text(node, &mut filters, config, filter_chain).await;
         }
 
+        if volume_unit(config) == Some(Encoder) {
+            for i in 0..config.processing.audio_tracks {
+                audio_volume(&mut filters, config, i, Encoder);
+            }
+        }
+
+        visualization(&mut filters, config);
+
         if let Some(f) = config.output.output_filter.clone() {
             process_output_filters(config, &mut filters, &f);
         } else if config.output.output_count > 1 && !config.processing.audio_only {
@@ -618,13 +789,18 @@ pub async fn filter_chains(
 
     if !config.processing.audio_only && !config.processing.copy_video {
         if let Some(probe) = node.probe.as_ref() {
-            if Path::new(&node.audio).is_file() {
+            if has_audio_input(node) {
                 filters.audio_position = 1;
             }
 
             if let Some(v_stream) = &probe.video.first() {
                 let aspect = aspect_calc(&v_stream.aspect_ratio, config);
-                let frame_per_sec = fps_calc(&v_stream.frame_rate, 1.0);
+                // slides come with the times from the list, the fps filter makes a steady rate
+                let frame_per_sec = if Path::new(&node.source).is_dir() {
+                    0.0
+                } else {
+                    fps_calc(&v_stream.frame_rate, 1.0)
+                };
 
                 deinterlace(&v_stream.field_order, &mut filters, config);
                 pad(aspect, &mut filters, v_stream, config);
@@ -657,9 +833,19 @@ pub async fn filter_chains(
 
     let (list_vf, list_af) = custom::filter_node(config.general.channel_id, &node.custom_filter);
 
+    let gapless_mode = config.processing.gapless && node.unit == Decoder;
+
     if !config.processing.copy_video {
         custom(&proc_vf, &mut filters, 0, Video);
         custom(&list_vf, &mut filters, 0, Video);
+
+        if gapless_mode && !config.processing.audio_only {
+            gapless(node, &mut filters, 0, Video, config);
+        }
+
+        if !config.processing.audio_only {
+            speed(node, &mut filters, 0, Video, config);
+        }
     }
 
     let mut audio_indexes = vec![];
@@ -679,7 +865,7 @@ pub async fn filter_chains(
                 .as_ref()
                 .and_then(|p| p.audio.get(i as usize))
                 .is_some()
-                || Path::new(&node.audio).is_file()
+                || has_audio_input(node)
             {
                 extend_audio(node, &mut filters, i, config);
             } else if node.unit == Decoder && !node.source.contains("color=c=") {
@@ -696,10 +882,16 @@ pub async fn filter_chains(
             filters.add_filter("anull", i, Audio);
 
             fade(node, &mut filters, i, Audio, config);
-            audio_volume(&mut filters, config, i);
+            audio_volume(&mut filters, config, i, node.unit);
 
             custom(&proc_af, &mut filters, i, Audio);
             custom(&list_af, &mut filters, i, Audio);
+
+            if gapless_mode {
+                gapless(node, &mut filters, i, Audio, config);
+            }
+
+            speed(node, &mut filters, i, Audio, config);
         }
     } else if config.processing.audio_track_index > -1 {
         error!(target: Target::file_mail(), channel = config.general.channel_id; "Setting 'audio_track_index' other than '-1' is not allowed in audio copy mode!");
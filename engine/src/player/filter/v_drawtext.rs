@@ -7,7 +7,10 @@ use crate::player::{
     controller::ProcessUnit::*,
     utils::{custom_format, Media},
 };
-use crate::utils::config::PlayoutConfig;
+use crate::utils::{
+    config::PlayoutConfig,
+    text_layers::{layer_names, layer_text, DEFAULT_LAYER},
+};
 
 pub async fn filter_node(
     config: &PlayoutConfig,
@@ -50,22 +53,45 @@ pub async fn filter_node(
             None => format!("drawtext=text='{escaped_text}':{}{font}", config.text.style),
         };
     } else if let Some(socket) = zmq_socket {
-        let mut filter_cmd = format!("text=''{font}");
-
-        if let Some(chain) = filter_chain {
-            if let Some(link) = chain.lock().await.iter().find(|&l| l.contains("text")) {
-                filter_cmd = link.to_string();
-            }
-        }
+        let chain = match filter_chain {
+            Some(chain) => chain.lock().await.clone(),
+            None => vec![],
+        };
+        let message = layer_text(&chain, DEFAULT_LAYER).unwrap_or_else(|| "text=''".to_string());
+        let filter_cmd = zmq_text(config, &message);
 
         filter = match config.advanced.filter.drawtext_from_zmq.clone() {
             Some(drawtext) => custom_format(&drawtext, &[&socket.replace(':', "\\:"), &filter_cmd]),
             None => format!(
-                "zmq=b=tcp\\\\://'{}',drawtext@dyntext={filter_cmd}",
+                "zmq=b=tcp\\\\://'{}',drawtext@{DEFAULT_LAYER}={filter_cmd}",
                 socket.replace(':', "\\:")
             ),
         };
+
+        // more layers get their commands from the same zmq filter
+        for layer in layer_names(&config.text.layers) {
+            let message = layer_text(&chain, &layer).unwrap_or_else(|| "text=''".to_string());
+
+            filter.push_str(&format!(",drawtext@{layer}={}", zmq_text(config, &message)));
+        }
     }
 
     filter
 }
+
+/// Options for the drawtext filter with zmq, the message overrides the default style and font.
+pub fn zmq_text(config: &PlayoutConfig, message: &str) -> String {
+    let mut options = vec![];
+
+    if !config.text.style.is_empty() {
+        options.push(config.text.style.clone());
+    }
+
+    if Path::new(&config.text.font_path).is_file() {
+        options.push(format!("fontfile='{}'", config.text.font_path));
+    }
+
+    options.push(message.to_string());
+
+    options.join(":")
+}
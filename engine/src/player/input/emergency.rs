@@ -0,0 +1,169 @@
+/// Emergency Slate
+///
+/// Play the configured image or clip, with an optional audio loop, instead of the program, until
+/// the operator goes back to the schedule. The switch ends the running clip and a live ingest, so
+/// the slate is on air at once. Afterwards a playlist starts again on the current time.
+use std::sync::atomic::Ordering;
+
+use log::*;
+
+use crate::player::{
+    controller::{ChannelManager, ProcessUnit::*},
+    input::playlist::gen_source,
+    utils::{time_in_seconds, Media},
+};
+use crate::utils::{
+    config::{PlayoutConfig, IMAGE_FORMAT},
+    errors::ServiceError,
+    event_history::{self, EventKind},
+    files::norm_abs_path,
+    logging::Target,
+};
+use crate::vec_strings;
+
+/// Length of one slate clip, the slate plays clip by clip as long as it is on.
+const EMERGENCY_CLIP: f64 = 60.0;
+
+fn is_image(source: &str) -> bool {
+    source
+        .rsplit_once('.')
+        .is_some_and(|(_, e)| IMAGE_FORMAT.contains(&e.to_lowercase().as_str()))
+}
+
+/// Slate and audio as absolute paths, the audio is empty when it is not set.
+fn slate_paths(config: &PlayoutConfig) -> Result<(String, String), ServiceError> {
+    let storage = &config.channel.storage;
+
+    if config.storage.emergency_slate.is_empty() {
+        return Err(ServiceError::Conflict(
+            "No emergency slate is configured".to_string(),
+        ));
+    }
+
+    let (slate, _, _) = norm_abs_path(storage, &config.storage.emergency_slate)?;
+
+    if !slate.is_file() {
+        return Err(ServiceError::Conflict(format!(
+            "Emergency slate not exists: {}",
+            config.storage.emergency_slate
+        )));
+    }
+
+    let audio = if config.storage.emergency_audio.is_empty() {
+        String::new()
+    } else {
+        let (audio, _, _) = norm_abs_path(storage, &config.storage.emergency_audio)?;
+
+        if !audio.is_file() {
+            return Err(ServiceError::Conflict(format!(
+                "Emergency audio not exists: {}",
+                config.storage.emergency_audio
+            )));
+        }
+
+        audio.to_string_lossy().to_string()
+    };
+
+    Ok((slate.to_string_lossy().to_string(), audio))
+}
+
+/// Loop the slate and the audio until the clip length is reached.
+pub fn emergency_cmd(node: &Media) -> Vec<String> {
+    let mut cmd = if is_image(&node.source) {
+        vec_strings!["-loop", "1", "-i", node.source]
+    } else {
+        vec_strings!["-stream_loop", "-1", "-i", node.source]
+    };
+
+    if !node.audio.is_empty() {
+        cmd.append(&mut vec_strings!["-stream_loop", "-1", "-i", node.audio]);
+    }
+
+    cmd.append(&mut vec_strings!["-t", node.out]);
+
+    cmd
+}
+
+/// Next slate clip, the filler plays when the slate is gone.
+pub async fn emergency_node(manager: &ChannelManager) -> Media {
+    let config = manager.config.lock().await.clone();
+    let begin = Some(time_in_seconds(&config.channel.timezone));
+
+    let (slate, audio) = match slate_paths(&config) {
+        Ok(paths) => paths,
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = config.general.channel_id; "{e}");
+
+            let mut node = Media::new(0, "", false).await;
+            node.out = EMERGENCY_CLIP;
+            node.duration = EMERGENCY_CLIP;
+            node.category = "emergency".to_string();
+            node.begin = begin;
+
+            return gen_source(&config, node, manager, 0).await;
+        }
+    };
+
+    let mut node = Media::new(0, &slate, true).await;
+
+    if is_image(&slate) || node.duration <= 0.0 {
+        node.duration = EMERGENCY_CLIP;
+    }
+
+    node.out = EMERGENCY_CLIP;
+    node.audio = audio;
+    node.category = "emergency".to_string();
+    node.begin = begin;
+    node.cmd = Some(emergency_cmd(&node));
+    node.add_filter(&config, &manager.filter_chain).await;
+
+    node
+}
+
+/// Put the slate on air, the running clip and a live ingest are stopped.
+pub async fn start_emergency(manager: &ChannelManager) -> Result<(), ServiceError> {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+
+    slate_paths(&config)?;
+
+    if manager.emergency.swap(true, Ordering::SeqCst) {
+        return Err(ServiceError::Conflict(
+            "Emergency slate is already on air".to_string(),
+        ));
+    }
+
+    // an open crossfade would play after the slate
+    manager.program.lock().await.clear();
+
+    warn!(target: Target::file_mail(), channel = id; "Switch to emergency slate");
+    event_history::push(id, EventKind::State, "Emergency slate on air");
+
+    // the ingest server waits until the slate is off
+    if let Err(e) = manager.stop(Ingest).await {
+        error!(target: Target::file_mail(), channel = id; "{e}");
+    }
+
+    manager.stop(Decoder).await
+}
+
+/// Go back to the schedule, a playlist starts again on the current time.
+pub async fn end_emergency(manager: &ChannelManager) -> Result<(), ServiceError> {
+    let id = manager.config.lock().await.general.channel_id;
+
+    if !manager.emergency.swap(false, Ordering::SeqCst) {
+        return Err(ServiceError::Conflict(
+            "Emergency slate is not on air".to_string(),
+        ));
+    }
+
+    info!(target: Target::file_mail(), channel = id; "Switch from emergency slate back to schedule");
+    event_history::push(
+        id,
+        EventKind::State,
+        "Emergency slate off, back to schedule",
+    );
+
+    manager.list_init.store(true, Ordering::SeqCst);
+    manager.stop(Decoder).await
+}
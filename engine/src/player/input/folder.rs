@@ -57,8 +57,10 @@ pub async fn watchman(
                                 | Modify(ModifyKind::Name(RenameMode::To)) => {
                                     let new_path = &event.paths[0];
 
+                                    // new files have no tags yet
                                     if new_path.is_file()
                                         && include_file_extension(&config, new_path)
+                                        && config.storage.tags.is_empty()
                                     {
                                         let index = sources.lock().await.len();
                                         let media =
@@ -98,7 +100,9 @@ pub async fn watchman(
                                                 .await;
                                         media_list[index] = media;
                                         info!(target: Target::file_mail(), channel = id; "Move file: <b><magenta>{old_path:?}</></b> to <b><magenta>{new_path:?}</></b>");
-                                    } else if include_file_extension(&config, new_path) {
+                                    } else if include_file_extension(&config, new_path)
+                                        && config.storage.tags.is_empty()
+                                    {
                                         let index = media_list.len();
                                         let media =
                                             Media::new(index, &new_path.to_string_lossy(), false)
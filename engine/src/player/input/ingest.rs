@@ -7,14 +7,20 @@ use tokio::{
 };
 
 use crate::utils::{
-    config::{PlayoutConfig, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS},
+    config::{PlayoutConfig, FFMPEG_IGNORE_ERRORS},
     logging::{log_line, Target},
 };
 use crate::vec_strings;
 use crate::{
     player::{
         controller::{ChannelManager, ProcessUnit::*},
-        utils::{is_free_tcp_port, valid_stream, Media},
+        input::{program::secondary_on_air, srt},
+        utils::{
+            channel_state::ChannelState,
+            is_free_tcp_port, scte35,
+            supervisor::{self, is_fatal},
+            valid_stream, Media,
+        },
     },
     utils::{errors::ServiceError, logging::fmt_cmd},
 };
@@ -27,6 +33,7 @@ async fn server_monitor(
     channel_mgr: ChannelManager,
 ) -> Result<(), ServiceError> {
     let mut is_running = false;
+    let policy = channel_mgr.config.lock().await.advanced.supervision.clone();
 
     let mut lines = buffer.lines();
     while let Ok(Some(line)) = lines.next_line().await {
@@ -49,15 +56,14 @@ async fn server_monitor(
             break;
         } else if !is_running {
             channel_mgr.ingest_is_alive.store(true, Ordering::SeqCst);
+            channel_mgr.on_air().await;
 
             is_running = true;
         }
 
-        if FFMPEG_UNRECOVERABLE_ERRORS
-            .iter()
-            .any(|i| line.contains(*i))
-        {
+        if is_fatal(&policy, &line) {
             error!(target: Target::file_mail(), channel = id; "Hit unrecoverable error!");
+            channel_mgr.set_state(ChannelState::Failed).await;
             channel_mgr.channel.lock().await.active = false;
             channel_mgr.stop_all(false).await?;
         }
@@ -66,27 +72,24 @@ async fn server_monitor(
     Ok(())
 }
 
-/// ffmpeg Ingest Server
-///
-/// Start ffmpeg in listen mode, and wait for input.
-pub async fn ingest_server(
-    config: PlayoutConfig,
-    channel_mgr: ChannelManager,
-) -> Result<(), ServiceError> {
-    let id = config.general.channel_id;
+/// Build the ffmpeg command for the ingest server from the current config.
+/// With the SRT relay, ffmpeg listens on the local port.
+async fn server_cmd(config: &PlayoutConfig, local_port: Option<u16>) -> Vec<String> {
     let mut server_cmd = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
-    let stream_input = config.ingest.input_cmd.clone().unwrap();
+    let stream_input = srt::input_cmd(
+        &config.ingest,
+        &config.ingest.input_cmd.clone().unwrap(),
+        local_port,
+    );
     let mut dummy_media = Media::new(0, "Live Stream", false).await;
     dummy_media.unit = Ingest;
-    dummy_media.add_filter(&config, &None).await;
-    let is_alive = channel_mgr.is_alive.clone();
-    let ingest_is_alive = channel_mgr.ingest_is_alive.clone();
+    dummy_media.add_filter(config, &None).await;
     let vtt_dummy = config
         .channel
         .storage
         .join(config.processing.vtt_dummy.clone().unwrap_or_default());
 
-    if let Some(ingest_input_cmd) = config.advanced.ingest.input_cmd {
+    if let Some(ingest_input_cmd) = &config.advanced.ingest.input_cmd {
         server_cmd.append(&mut ingest_input_cmd.clone());
     }
 
@@ -101,20 +104,39 @@ pub async fn ingest_server(
         server_cmd.append(&mut filter.map());
     }
 
+    if config.ingest.scte35 {
+        server_cmd.append(&mut scte35::passthrough_args());
+    }
+
     if config.processing.vtt_enable && vtt_dummy.is_file() {
         server_cmd.append(&mut vec_strings!("-map", "1:s"));
     }
 
-    if let Some(mut cmd) = config.processing.cmd {
-        server_cmd.append(&mut cmd);
+    if let Some(cmd) = &config.processing.cmd {
+        server_cmd.append(&mut cmd.clone());
     }
 
-    debug!(target: Target::file_mail(), channel = id;
-        "Server CMD: <bright-blue>ffmpeg {}</>",
-        fmt_cmd(&server_cmd)
-    );
+    server_cmd
+}
 
-    if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
+/// ffmpeg Ingest Server
+///
+/// Start ffmpeg in listen mode, and wait for input.
+/// The command is build new on every restart, so config changes are applied on the next connection.
+pub async fn ingest_server(
+    config: PlayoutConfig,
+    channel_mgr: ChannelManager,
+) -> Result<(), ServiceError> {
+    let id = config.general.channel_id;
+    let stream_input = config.ingest.input_cmd.clone().unwrap();
+    let is_alive = channel_mgr.is_alive.clone();
+    let ingest_is_alive = channel_mgr.ingest_is_alive.clone();
+    // with a stream key, an external RTMP server gets the stream and ffmpeg pulls it from there
+    let external = !config.ingest.stream_key.is_empty();
+
+    if external {
+        info!(target: Target::file_mail(), channel = id; "Start ingest, wait for publish callback");
+    } else if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
         let mut attempts = 0;
 
         while attempts < 5 {
@@ -133,12 +155,43 @@ pub async fn ingest_server(
         }
     };
 
+    let relay = if external {
+        None
+    } else {
+        srt::relay_addr(&config.ingest, &stream_input).await
+    };
+    let local_port = relay.as_ref().map(|(_, port)| port.clone());
+
+    if let Some((addr, port)) = relay {
+        info!(target: Target::file_mail(), channel = id; "Check SRT stream ids, ffmpeg listens on port <b><magenta>{}</></b>", port.get());
+        tokio::spawn(srt::relay(channel_mgr.clone(), addr, port));
+    }
+
     while is_alive.load(Ordering::SeqCst) {
+        if channel_mgr.emergency.load(Ordering::SeqCst) || secondary_on_air(&channel_mgr).await {
+            // no live ingest, while the emergency slate or the secondary source is on air
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            continue;
+        }
+
+        if external && !channel_mgr.ingest_published.load(Ordering::SeqCst) {
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            continue;
+        }
+
+        let config = channel_mgr.config.lock().await.clone();
+        let server_cmd = server_cmd(&config, local_port.as_ref().map(|p| p.get())).await;
+
+        debug!(target: Target::file_mail(), channel = id;
+            "Server CMD: <bright-blue>ffmpeg {}</>",
+            fmt_cmd(&server_cmd)
+        );
+
         let proc_ctl = channel_mgr.clone();
         let level = config.logging.ingest_level.clone();
         let ignore = config.logging.ignore_lines.clone();
         let mut server_proc = match Command::new("ffmpeg")
-            .args(server_cmd.clone())
+            .args(server_cmd)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .kill_on_drop(true)
@@ -155,14 +208,39 @@ pub async fn ingest_server(
 
         *channel_mgr.ingest_stdout.lock().await = Some(ingest_stdout);
         *channel_mgr.ingest.lock().await = Some(server_proc);
+        supervisor::started(&channel_mgr, Ingest).await;
 
         server_monitor(id, level, ignore, server_err, proc_ctl).await?;
         ingest_is_alive.store(false, Ordering::SeqCst);
+        channel_mgr.on_air().await;
 
         if let Err(e) = channel_mgr.wait(Ingest).await {
             error!(target: Target::file_mail(), channel = id; "{e}");
         }
 
+        if !is_alive.load(Ordering::SeqCst) {
+            supervisor::stopped(&channel_mgr, Ingest).await;
+            break;
+        }
+
+        if external && !channel_mgr.ingest_published.load(Ordering::SeqCst) {
+            // the stream ended on the external server, no failure
+            continue;
+        }
+
+        if !supervisor::respawn(&channel_mgr, Ingest).await {
+            error!(target: Target::file_mail(), channel = id; "Ingest server failed too often in a row, terminate channel!");
+            channel_mgr.set_state(ChannelState::Failed).await;
+            channel_mgr.channel.lock().await.active = false;
+            channel_mgr.stop_all(false).await?;
+            break;
+        }
+
+        if let Some(port) = &local_port {
+            // the port can be taken in between, then ffmpeg fails to listen there
+            debug!(target: Target::file_mail(), channel = id; "ffmpeg listens on port <b><magenta>{}</></b> after the restart", port.renew().await);
+        }
+
         trace!("Restart ingest server");
     }
 
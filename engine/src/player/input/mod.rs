@@ -1,12 +1,19 @@
+use std::sync::atomic::Ordering;
+
 use log::*;
 
+pub mod emergency;
 pub mod folder;
 pub mod ingest;
 pub mod playlist;
+pub mod program;
+pub mod slate;
+pub mod srt;
 
 pub use folder::watchman;
 pub use ingest::ingest_server;
 pub use playlist::CurrentProgram;
+pub use slate::SlateSource;
 
 use crate::player::{
     controller::ChannelManager,
@@ -17,6 +24,7 @@ use crate::utils::{config::ProcessMode::*, logging::Target};
 pub enum SourceIterator {
     Folder(FolderSource),
     Playlist(CurrentProgram),
+    Slate(SlateSource),
 }
 
 impl async_iterator::Iterator for SourceIterator {
@@ -26,6 +34,7 @@ impl async_iterator::Iterator for SourceIterator {
         match self {
             SourceIterator::Folder(folder_source) => folder_source.next().await,
             SourceIterator::Playlist(program) => program.next().await,
+            SourceIterator::Slate(slate) => slate.next().await,
         }
     }
 }
@@ -37,6 +46,12 @@ pub async fn source_generator(manager: ChannelManager) -> SourceIterator {
     let is_alive = manager.is_alive.clone();
     let current_list = manager.current_list.clone();
 
+    if manager.slate.load(Ordering::SeqCst) {
+        warn!(target: Target::file_mail(), channel = id; "Playout in slate mode");
+
+        return SourceIterator::Slate(SlateSource::new(manager));
+    }
+
     match config.processing.mode {
         Folder => {
             info!(target: Target::file_mail(), channel = id; "Playout in folder mode");
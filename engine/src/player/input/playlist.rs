@@ -6,6 +6,7 @@ use std::{
     },
 };
 
+use chrono::{Datelike, NaiveDate, Weekday};
 use log::*;
 use tokio::sync::Mutex;
 
@@ -13,16 +14,30 @@ use crate::db::handles;
 use crate::player::{
     controller::ChannelManager,
     utils::{
-        gen_dummy, get_delta, is_close, is_remote,
+        catch_up::catch_up,
+        coming_up,
+        drift::update_drift,
+        dst_change,
+        folder::fill_filler_list,
+        gen_dummy, get_date, get_delta, is_close, is_remote,
         json_serializer::{read_json, set_defaults},
         loop_filler, loop_image, modified_time,
+        playlist_store::{PlaylistStorage, PlaylistStore},
+        preload::prepare_next,
         probe::MediaProbe,
-        seek_and_length, time_in_seconds, JsonPlaylist, Media,
+        resume::{self, ResumePoint},
+        seek_and_length,
+        slideshow::{slide_images, slideshow_cmd},
+        time_in_seconds,
+        vast::AD_BREAK,
+        JsonPlaylist, Media,
     },
 };
 use crate::utils::{
-    config::{PlayoutConfig, IMAGE_FORMAT},
+    config::{CatchUp, DstBackward, DstForward, PlayoutConfig, IMAGE_FORMAT},
     logging::Target,
+    media_metadata::apply_metadata,
+    plugins,
 };
 
 /// Struct for current playlist.
@@ -33,13 +48,16 @@ pub struct CurrentProgram {
     id: i32,
     config: PlayoutConfig,
     manager: ChannelManager,
+    store: PlaylistStorage,
     start_sec: f64,
     length_sec: f64,
+    weekday: Option<Weekday>,
     json_playlist: JsonPlaylist,
     current_node: Media,
     is_alive: Arc<AtomicBool>,
     last_json_path: Option<String>,
     last_node_ad: bool,
+    resume: Option<ResumePoint>,
 }
 
 /// Prepare a playlist iterator.
@@ -47,13 +65,17 @@ impl CurrentProgram {
     pub async fn new(manager: ChannelManager) -> Self {
         let config = manager.config.lock().await.clone();
         let is_alive = manager.is_alive.clone();
+        let store = PlaylistStorage::new(&config, manager.db_pool.clone());
+        let resume = resume::load(&manager).await;
 
         Self {
             id: config.general.channel_id,
             config: config.clone(),
             manager,
+            store,
             start_sec: config.playlist.start_sec.unwrap(),
             length_sec: config.playlist.length_sec.unwrap(),
+            weekday: None,
             json_playlist: JsonPlaylist::new(
                 "1970-01-01".to_string(),
                 config.playlist.start_sec.unwrap(),
@@ -62,6 +84,7 @@ impl CurrentProgram {
             is_alive,
             last_json_path: None,
             last_node_ad: false,
+            resume,
         }
     }
 
@@ -72,9 +95,17 @@ impl CurrentProgram {
         let mut reload = false;
 
         if let Some(path) = self.json_playlist.path.clone() {
-            if (Path::new(&path).is_file() || is_remote(&path))
-                && self.json_playlist.modified != modified_time(&path).await
-            {
+            let changed = match &self.store {
+                PlaylistStorage::Database(store) => {
+                    self.json_playlist.modified != store.modified(&self.json_playlist.date).await
+                }
+                PlaylistStorage::File(_) => {
+                    (Path::new(&path).is_file() || is_remote(&path))
+                        && self.json_playlist.modified != modified_time(&path).await
+                }
+            };
+
+            if changed {
                 info!(target: Target::file_mail(), channel = self.id; "Reload playlist <b><magenta>{path}</></b>");
                 self.manager.list_init.store(true, Ordering::SeqCst);
                 get_current = true;
@@ -85,8 +116,11 @@ impl CurrentProgram {
         }
 
         if get_current {
+            self.resolve_weekday(seek, false).await;
+
             self.json_playlist = read_json(
                 &mut self.config,
+                &self.store,
                 self.manager.current_list.clone(),
                 self.json_playlist.path.clone(),
                 self.is_alive.clone(),
@@ -176,8 +210,11 @@ impl CurrentProgram {
             trace!("get next day");
             next = true;
 
+            self.resolve_weekday(false, true).await;
+
             self.json_playlist = read_json(
                 &mut self.config,
+                &self.store,
                 self.manager.current_list.clone(),
                 None,
                 self.is_alive.clone(),
@@ -206,6 +243,52 @@ impl CurrentProgram {
         next
     }
 
+    // Apply the weekday overrides from config, for the playlist which get loaded next.
+    async fn resolve_weekday(&mut self, seek: bool, get_next: bool) {
+        let base = self.manager.config.lock().await.clone();
+        let mut config = base.clone();
+        let mut weekday = None;
+
+        if !base.playlist.weekday_overrides.is_empty() {
+            // an override can move the day start, so the date is checked again with the new start
+            for _ in 0..2 {
+                let date = get_date(
+                    seek,
+                    config.playlist.start_sec.unwrap(),
+                    get_next,
+                    &config.channel.timezone,
+                );
+                let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .ok()
+                    .map(|d| d.weekday());
+
+                if day == weekday {
+                    break;
+                }
+
+                weekday = day;
+
+                if let Some(day) = day {
+                    config = base.for_weekday(day);
+                }
+            }
+        }
+
+        if weekday != self.weekday && config.playlist.day_start != self.config.playlist.day_start {
+            info!(target: Target::file_mail(), channel = self.id; "Day start on <yellow>{}</>: <yellow>{}</>", weekday.map(|d| d.to_string()).unwrap_or_default(), config.playlist.day_start);
+        }
+
+        if config.storage.filler != self.config.storage.filler {
+            info!(target: Target::file_mail(), channel = self.id; "Use filler: <b><magenta>{}</></b>", config.storage.filler);
+            fill_filler_list(&config, Some(self.manager.filler_list.clone())).await;
+        }
+
+        self.start_sec = config.playlist.start_sec.unwrap();
+        self.length_sec = config.playlist.length_sec.unwrap();
+        self.weekday = weekday;
+        self.config = config;
+    }
+
     async fn set_status(&mut self, date: String) {
         if self.manager.channel.lock().await.last_date != Some(date.clone())
             && self.manager.channel.lock().await.time_shift != 0.0
@@ -221,6 +304,15 @@ impl CurrentProgram {
             .last_date
             .clone_from(&Some(date.clone()));
         self.manager.channel.lock().await.time_shift = 0.0;
+
+        let change = dst_change(&date, self.start_sec, &self.config.channel.timezone);
+
+        if change > 0.0 {
+            info!(target: Target::file_mail(), channel = self.id; "Clocks go forward on playlist from {date}, policy: <yellow>{}</>", self.config.playlist.dst_forward);
+        } else if change < 0.0 {
+            info!(target: Target::file_mail(), channel = self.id; "Clocks go back on playlist from {date}, policy: <yellow>{}</>", self.config.playlist.dst_backward);
+        }
+
         let db_pool = self.manager.db_pool.clone().unwrap();
 
         if let Err(e) =
@@ -294,10 +386,29 @@ impl CurrentProgram {
 
         if !self.manager.list_init.load(Ordering::SeqCst) {
             let time_sec = self.get_current_time();
-            let index = self.manager.current_index.load(Ordering::SeqCst);
+            let shift = self.manager.channel.lock().await.time_shift;
+            let date = self.manager.current_date.lock().await.clone();
+            let mut index = self.manager.current_index.load(Ordering::SeqCst);
             let nodes = self.manager.current_list.lock().await;
             let last_index = nodes.len() - 1;
 
+            // only the first init after start resumes, later ones follow the schedule
+            let resume_seek = self.resume.take().and_then(|point| {
+                let seek = point.seek_for(
+                    nodes.get(point.index)?,
+                    &date,
+                    resume::timestamp(),
+                    time_sec + shift,
+                    self.config.general.stop_threshold,
+                )?;
+
+                index = point.index;
+
+                Some(seek)
+            });
+
+            self.manager.current_index.store(index, Ordering::SeqCst);
+
             // de-instance node to preserve original values in list
             let mut node_clone = nodes[index].clone();
 
@@ -306,8 +417,14 @@ impl CurrentProgram {
 
             trace!("Clip from init: {}", node_clone.source);
 
-            node_clone.seek += time_sec
-                - (node_clone.begin.unwrap() - self.manager.channel.lock().await.time_shift);
+            match resume_seek {
+                Some(seek) => {
+                    info!(target: Target::file_mail(), channel = self.id; "Resume <b><magenta>{}</></b> at <yellow>{seek:.3}</>", node_clone.source);
+
+                    node_clone.seek = seek;
+                }
+                None => node_clone.seek += time_sec - (node_clone.begin.unwrap() - shift),
+            }
 
             self.last_next_ad(&mut node_clone).await;
 
@@ -390,6 +507,14 @@ impl async_iterator::Iterator for CurrentProgram {
     type Item = Media;
 
     async fn next(&mut self) -> Option<Self::Item> {
+        // decoder settings come from the running config, start and length are only set when a
+        // playlist gets loaded, a changed playlist config restarts the output with a new program
+        let config = self.manager.config.lock().await.clone();
+        self.config = match self.weekday {
+            // overrides are resolved once, when the playlist for the day gets loaded
+            Some(day) => config.keep_weekday(&self.config, day),
+            None => config,
+        };
         self.last_json_path.clone_from(&self.json_playlist.path);
         self.last_node_ad = self.current_node.last_ad;
         self.check_for_playlist(self.manager.list_init.load(Ordering::SeqCst))
@@ -489,6 +614,13 @@ impl async_iterator::Iterator for CurrentProgram {
             self.manager.current_index.store(1, Ordering::SeqCst);
         }
 
+        if !self.manager.list_init.load(Ordering::SeqCst) {
+            tokio::spawn(prepare_next(
+                self.manager.clone(),
+                self.manager.current_index.load(Ordering::SeqCst),
+            ));
+        }
+
         Some(self.current_node.clone())
     }
 }
@@ -498,7 +630,7 @@ impl async_iterator::Iterator for CurrentProgram {
 /// - check begin and length from clip
 /// - return clip only if we are in 24 hours time range
 async fn timed_source(
-    node: Media,
+    mut node: Media,
     config: &PlayoutConfig,
     last: bool,
     manager: &ChannelManager,
@@ -523,7 +655,7 @@ async fn timed_source(
     );
 
     if config.playlist.length.contains(':') {
-        if Some(current_date) == last_date && time_shift != 0.0 {
+        if last_date.as_ref() == Some(&current_date) && time_shift != 0.0 {
             shifted_delta = delta - time_shift;
 
             debug!(target: Target::file_mail(), channel = id; "Delta: <yellow>{shifted_delta:.3}</>, shifted: <yellow>{delta:.3}</>");
@@ -531,25 +663,50 @@ async fn timed_source(
             debug!(target: Target::file_mail(), channel = id; "Delta: <yellow>{shifted_delta:.3}</>");
         }
 
+        update_drift(manager, config, shifted_delta).await;
+
         if config.general.stop_threshold > 0.0
             && shifted_delta.abs() > config.general.stop_threshold
         {
+            let change = dst_change(
+                &current_date,
+                config.playlist.start_sec.unwrap(),
+                &config.channel.timezone,
+            );
+
             // Handle summer/winter time changes.
-            // It only checks if the time change is one hour backwards or forwards.
-            // If this is enough, or if a real change needs to be checked, it needs to show production usage.
-            if is_close(shifted_delta.abs(), 3600.0, config.general.stop_threshold) {
+            // When the playlist day has a time change, the configured policy is applied.
+            // Without a detected change, a delta of one hour backwards or forwards is still treated as time change.
+            if change != 0.0 && is_close(shifted_delta, -change, config.general.stop_threshold) {
+                if change > 0.0 && config.playlist.dst_forward == DstForward::Trim {
+                    warn!(target: Target::file_mail(), channel = id; "Clocks went forward, skip <yellow>{:.3}</> seconds from playlist.", -shifted_delta);
+
+                    return dst_trim(node, config, shifted_delta, manager, last_index).await;
+                } else if change < 0.0 && config.playlist.dst_backward == DstBackward::Filler {
+                    warn!(target: Target::file_mail(), channel = id; "Clocks went back, fill <yellow>{shifted_delta:.3}</> seconds.");
+
+                    return dst_filler(node, config, shifted_delta, manager, last_index).await;
+                }
+
+                warn!(target: Target::file_mail(), channel = id; "Clocks changed, apply time shift: <yellow>{shifted_delta:.3}</> seconds.");
+
+                apply_time_shift(manager, id, time_shift + shifted_delta).await;
+            } else if is_close(shifted_delta.abs(), 3600.0, config.general.stop_threshold) {
                 warn!(
                     "A time change seemed to have occurred, apply time shift: <yellow>{shifted_delta:.3}</> seconds."
                 );
 
-                let db_pool = manager.db_pool.clone().unwrap();
-                manager.channel.lock().await.time_shift = time_shift + shifted_delta;
+                apply_time_shift(manager, id, time_shift + shifted_delta).await;
+            } else if shifted_delta < 0.0 && config.playlist.catch_up == CatchUp::Skip {
+                warn!(target: Target::file_mail(), channel = id; "Playout is <yellow>{:.3}</> seconds behind, skip to the current clip.", -shifted_delta);
 
-                if let Err(e) =
-                    handles::update_stat(&db_pool, id, None, time_shift + shifted_delta).await
-                {
-                    error!(target: Target::file_mail(), channel = id; "Unable to write status: {e}");
-                };
+                manager.list_init.store(true, Ordering::SeqCst);
+
+                return new_node;
+            } else if shifted_delta < 0.0
+                && matches!(config.playlist.catch_up, CatchUp::Trim | CatchUp::Speed)
+            {
+                debug!(target: Target::file_mail(), channel = id; "Playout is <yellow>{:.3}</> seconds behind, catch up with: {}", -shifted_delta, config.playlist.catch_up);
             } else if manager.is_alive.load(Ordering::SeqCst) {
                 error!(target: Target::file_mail(), channel = id; "Clip begin out of sync for <yellow>{delta:.3}</> seconds.");
 
@@ -567,6 +724,15 @@ async fn timed_source(
     {
         // when we are in the 24 hour range, get the clip
         new_node.skip = false;
+
+        if config.playlist.length.contains(':') {
+            let gained = catch_up(&mut node, config, -shifted_delta);
+
+            if gained > 0.0 {
+                info!(target: Target::file_mail(), channel = id; "Catch up <yellow>{gained:.3}</> of <yellow>{:.3}</> seconds delay", -shifted_delta);
+            }
+        }
+
         new_node = gen_source(config, node, manager, last_index).await;
     } else if total_delta <= 0.0 {
         info!(target: Target::file_mail(), channel = id; "Begin is over play time, skip: {}", node.source);
@@ -577,6 +743,75 @@ async fn timed_source(
     new_node
 }
 
+async fn apply_time_shift(manager: &ChannelManager, id: i32, time_shift: f64) {
+    let db_pool = manager.db_pool.clone().unwrap();
+    manager.channel.lock().await.time_shift = time_shift;
+
+    if let Err(e) = handles::update_stat(&db_pool, id, None, time_shift).await {
+        error!(target: Target::file_mail(), channel = id; "Unable to write status: {e}");
+    };
+}
+
+/// Clocks went forward: continue with the clip which should run now, clips in between are skipped.
+async fn dst_trim(
+    node: Media,
+    config: &PlayoutConfig,
+    delta: f64,
+    manager: &ChannelManager,
+    last_index: usize,
+) -> Media {
+    let now = node.begin.unwrap_or_default() - delta;
+    let current_list = manager.current_list.lock().await;
+    let index = node.index.unwrap_or_default();
+
+    let Some(mut current) = current_list
+        .iter()
+        .skip(index)
+        .find(|n| n.begin.unwrap_or_default() + n.out - n.seek > now)
+        .cloned()
+    else {
+        // the rest of the playlist is over, so the list end handles the remaining time
+        drop(current_list);
+        manager.current_index.store(last_index, Ordering::SeqCst);
+
+        let mut new_node = node;
+        new_node.skip = true;
+        new_node.cmd = None;
+
+        return new_node;
+    };
+
+    drop(current_list);
+
+    let current_index = current.index.unwrap_or(index);
+    manager.current_index.store(current_index, Ordering::SeqCst);
+
+    current.seek += now - current.begin.unwrap_or_default();
+    current.begin = Some(now);
+
+    gen_source(config, current, manager, last_index).await
+}
+
+/// Clocks went back: play filler until the clip begin is reached and repeat the clip afterwards.
+async fn dst_filler(
+    node: Media,
+    config: &PlayoutConfig,
+    delta: f64,
+    manager: &ChannelManager,
+    last_index: usize,
+) -> Media {
+    let mut media = Media::new(node.index.unwrap_or_default(), "", false).await;
+    media.begin = Some(node.begin.unwrap_or_default() - delta);
+    media.duration = delta;
+    media.out = delta;
+    media.last_ad = node.last_ad;
+
+    // the iterator goes to the next index, so the clip gets picked up again after the filler
+    manager.current_index.fetch_sub(1, Ordering::SeqCst);
+
+    gen_source(config, media, manager, last_index).await
+}
+
 async fn duplicate_for_seek_and_loop(node: &mut Media, current_list: &Arc<Mutex<Vec<Media>>>) {
     let mut nodes = current_list.lock().await;
     let index = node.index.unwrap_or_default();
@@ -643,6 +878,10 @@ pub async fn gen_source(
 
     trace!("Clip new length: {duration}, duration: {}", node.duration);
 
+    if let Some(pool) = &manager.db_pool {
+        apply_metadata(pool, config, &mut node).await;
+    }
+
     if node.probe.is_none() && !node.source.is_empty() {
         if let Err(e) = node.add_probe(true).await {
             trace!("{e:?}");
@@ -651,9 +890,46 @@ pub async fn gen_source(
         trace!("Node has a probe...");
     }
 
+    let slides = slide_images(&node.source);
+
+    if node.probe.is_none() && !slides.is_empty() {
+        // the first image stands for the folder
+        node.probe = MediaProbe::new(slides[0].as_path()).await.ok();
+    }
+
+    if node.probe.is_none() && !node.backup_source.is_empty() {
+        warn!(target: Target::file_mail(), channel = config.general.channel_id;
+            "Source not usable: <b><magenta>{}</></b>, play backup: <b><magenta>{}</></b>",
+            node.source,
+            node.backup_source
+        );
+
+        node.source = std::mem::take(&mut node.backup_source);
+
+        if let Err(e) = node.add_probe(true).await {
+            trace!("{e:?}");
+        };
+    }
+
     // separate if condition, because of node.add_probe() in last condition
     if node.probe.is_some() {
-        if node
+        if !slides.is_empty() {
+            node.cmd = match slideshow_cmd(config, &node, &slides) {
+                Ok(cmd) => Some(cmd),
+                Err(e) => {
+                    error!(target: Target::file_mail(), channel = config.general.channel_id;
+                        "Slideshow list for <b><magenta>{}</></b> failed: {e}", node.source
+                    );
+
+                    let image = Media {
+                        source: slides[0].to_string_lossy().to_string(),
+                        ..node.clone()
+                    };
+
+                    Some(loop_image(config, &image))
+                }
+            };
+        } else if node
             .source
             .rsplit_once('.')
             .map(|(_, e)| e.to_lowercase())
@@ -673,127 +949,178 @@ pub async fn gen_source(
         trace!("clip index: {node_index} | last index: {last_index}");
 
         // Last index is the index from the last item from the node list.
-        if node_index < last_index {
+        // ad breaks without source play the filler, when no ads came
+        let empty_break =
+            node.source.is_empty() && [AD_BREAK, "filler"].contains(&node.category.as_str());
+
+        if node_index < last_index && !empty_break {
             error!(target: Target::file_mail(), channel = config.general.channel_id; "Source not found: <b><magenta>{}</></b>", node.source);
         }
 
-        let fillers = manager.filler_list.lock().await;
+        add_filler(config, &mut node, manager, duration).await;
+    }
 
-        // Set list_init to true, to stay in sync.
-        manager.list_init.store(true, Ordering::SeqCst);
+    plugins::clip_filter(config, &mut node).await;
+    node.add_filter(config, &manager.filter_chain.clone()).await;
 
-        if config.storage.filler_path.is_dir() && !fillers.is_empty() {
-            let mut index = manager.filler_index.fetch_add(1, Ordering::SeqCst);
+    trace!(
+        "return gen_source: {}, seek: {}, out: {}",
+        node.source,
+        node.seek,
+        node.out,
+    );
 
-            if index > fillers.len() - 1 {
-                index = 0;
-            }
+    node
+}
 
-            let mut filler_media = fillers[index].clone();
+/// Fill the time from a missing clip with a coming up card, the next filler, the filler file or a dummy.
+async fn add_filler(
+    config: &PlayoutConfig,
+    node: &mut Media,
+    manager: &ChannelManager,
+    duration: f64,
+) {
+    let card = coming_up::card(config, manager, node).await;
+    let fillers = manager.filler_list.lock().await;
+
+    // Set list_init to true, to stay in sync.
+    manager.list_init.store(true, Ordering::SeqCst);
+
+    if let Some((source, probe)) = card {
+        node.source = source;
+        node.audio.clear();
+        node.seek = 0.0;
+        node.out = duration;
+        node.duration = duration;
+        node.probe = Some(probe);
+        node.cmd = Some(loop_image(config, node));
+    } else if config.storage.filler_path.is_dir() && !fillers.is_empty() {
+        let mut index = manager.filler_index.fetch_add(1, Ordering::SeqCst);
+
+        if index > fillers.len() - 1 {
+            index = 0;
+        }
 
-            trace!("take filler: {}", filler_media.source);
+        let mut filler_media = fillers[index].clone();
 
-            if index == fillers.len() - 1 {
-                // reset index for next round
-                manager.filler_index.store(0, Ordering::SeqCst);
-            }
+        trace!("take filler: {}", filler_media.source);
 
-            if filler_media.probe.is_none() {
-                if let Err(e) = filler_media.add_probe(false).await {
-                    error!(target: Target::file_mail(), channel = config.general.channel_id; "{e:?}");
-                };
-            }
+        if index == fillers.len() - 1 {
+            // reset index for next round
+            manager.filler_index.store(0, Ordering::SeqCst);
+        }
 
-            if filler_media.duration > duration {
-                filler_media.out = duration;
-            }
+        if filler_media.probe.is_none() {
+            if let Err(e) = filler_media.add_probe(false).await {
+                error!(target: Target::file_mail(), channel = config.general.channel_id; "{e:?}");
+            };
+        }
 
-            node.source = filler_media.source;
-            node.seek = 0.0;
-            node.out = filler_media.out;
-            node.duration = filler_media.duration;
-            node.cmd = Some(loop_filler(config, &node));
-            node.probe = filler_media.probe;
-        } else {
-            match MediaProbe::new(&config.storage.filler_path).await {
-                Ok(probe) => {
-                    if config
+        if filler_media.duration > duration {
+            filler_media.out = duration;
+        }
+
+        node.source = filler_media.source;
+        node.seek = 0.0;
+        node.out = filler_media.out;
+        node.duration = filler_media.duration;
+        node.cmd = Some(loop_filler(config, &node));
+        node.probe = filler_media.probe;
+    } else {
+        match MediaProbe::new(&config.storage.filler_path).await {
+            Ok(probe) => {
+                if config
+                    .storage
+                    .filler_path
+                    .to_string_lossy()
+                    .to_string()
+                    .rsplit_once('.')
+                    .map(|(_, e)| e.to_lowercase())
+                    .filter(|c| IMAGE_FORMAT.contains(&c.as_str()))
+                    .is_some()
+                {
+                    node.source = config
                         .storage
                         .filler_path
+                        .clone()
                         .to_string_lossy()
-                        .to_string()
-                        .rsplit_once('.')
-                        .map(|(_, e)| e.to_lowercase())
-                        .filter(|c| IMAGE_FORMAT.contains(&c.as_str()))
-                        .is_some()
-                    {
-                        node.source = config
-                            .storage
-                            .filler_path
-                            .clone()
-                            .to_string_lossy()
-                            .to_string();
-                        node.cmd = Some(loop_image(config, &node));
-                        node.probe = Some(probe);
-                    } else if let Some(filler_duration) = probe.clone().format.duration {
-                        // Create placeholder from config filler.
-                        let filler_out = filler_duration.min(duration);
-
-                        node.source = config
-                            .storage
-                            .filler_path
-                            .clone()
-                            .to_string_lossy()
-                            .to_string();
-                        node.seek = 0.0;
-                        node.out = filler_out;
-                        node.duration = filler_duration;
-                        node.cmd = Some(loop_filler(config, &node));
-                        node.probe = Some(probe);
-                    } else {
-                        // Create colored placeholder.
-                        let (source, cmd) = gen_dummy(config, duration);
-                        node.source = source;
-                        node.cmd = Some(cmd);
-                    }
-                }
-                Err(e) => {
-                    // Create colored placeholder.
-                    error!(target: Target::file_mail(), channel = config.general.channel_id; "Filler error: {e}");
-
-                    let mut dummy_duration = 60.0;
-
-                    if dummy_duration > duration {
-                        dummy_duration = duration;
-                    }
-
-                    let (source, cmd) = gen_dummy(config, dummy_duration);
+                        .to_string();
+                    node.cmd = Some(loop_image(config, &node));
+                    node.probe = Some(probe);
+                } else if let Some(filler_duration) = probe.clone().format.duration {
+                    // Create placeholder from config filler.
+                    let filler_out = filler_duration.min(duration);
+
+                    node.source = config
+                        .storage
+                        .filler_path
+                        .clone()
+                        .to_string_lossy()
+                        .to_string();
                     node.seek = 0.0;
-                    node.out = dummy_duration;
-                    node.duration = dummy_duration;
+                    node.out = filler_out;
+                    node.duration = filler_duration;
+                    node.cmd = Some(loop_filler(config, &node));
+                    node.probe = Some(probe);
+                } else {
+                    // Create colored placeholder.
+                    let (source, cmd) = gen_dummy(config, duration);
                     node.source = source;
                     node.cmd = Some(cmd);
                 }
             }
-        }
+            Err(e) => {
+                // Create colored placeholder.
+                error!(target: Target::file_mail(), channel = config.general.channel_id; "Filler error: {e}");
 
-        warn!(
-            target: Target::file_mail(), channel = config.general.channel_id;
-            "Generate filler with <yellow>{:.2}</> seconds length!",
-            node.out
-        );
+                let mut dummy_duration = 60.0;
+
+                if dummy_duration > duration {
+                    dummy_duration = duration;
+                }
+
+                let (source, cmd) = gen_dummy(config, dummy_duration);
+                node.seek = 0.0;
+                node.out = dummy_duration;
+                node.duration = dummy_duration;
+                node.source = source;
+                node.cmd = Some(cmd);
+            }
+        }
     }
 
-    node.add_filter(config, &manager.filter_chain.clone()).await;
+    node.category = "filler".to_string();
 
-    trace!(
-        "return gen_source: {}, seek: {}, out: {}",
-        node.source,
-        node.seek,
-        node.out,
+    warn!(
+        target: Target::file_mail(), channel = config.general.channel_id;
+        "Generate filler with <yellow>{:.2}</> seconds length!",
+        node.out
     );
+}
 
-    node
+/// Filler for the rest of a stream entry, which ended before its time.
+pub async fn stream_fallback(
+    config: &PlayoutConfig,
+    node: &Media,
+    manager: &ChannelManager,
+    remaining: f64,
+) -> Media {
+    let mut filler = Media {
+        seek: 0.0,
+        out: remaining,
+        duration: remaining,
+        probe: None,
+        cmd: None,
+        filter: None,
+        ..node.clone()
+    };
+
+    add_filler(config, &mut filler, manager, remaining).await;
+    filler
+        .add_filter(config, &manager.filter_chain.clone())
+        .await;
+
+    filler
 }
 
 /// Handle init clip, but this clip can be the last one in playlist,
@@ -0,0 +1,353 @@
+/// Program Switch
+///
+/// A/B switch between the program (A), from the playlist or folder, and the secondary source (B)
+/// from `ingest.secondary_source`, like a studio stream or the output from another channel. The
+/// operator switches over the API, with a cut or a crossfade. While B is on air the ingest server
+/// waits, and when A comes back, the playlist starts again on the current time.
+use std::{path::Path, sync::atomic::Ordering};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{
+    controller::{ChannelManager, ProcessUnit::*},
+    filter::{FilterType::*, Filters},
+    utils::{
+        is_remote, probe::MediaProbe, seek_and_length, stream_options, time_in_seconds, Media,
+    },
+};
+use crate::utils::{
+    config::PlayoutConfig,
+    errors::ServiceError,
+    event_history::{self, EventKind},
+    logging::Target,
+};
+use crate::vec_strings;
+
+/// Length of one clip from the secondary source, it plays clip by clip as long as it is on air.
+const SECONDARY_CLIP: f64 = 3600.0;
+
+/// Crossfade length, when the request has none.
+const FADE_DURATION: f64 = 1.0;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bus {
+    /// Program from the playlist or folder.
+    #[default]
+    A,
+    /// Secondary source.
+    B,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    #[default]
+    Cut,
+    Fade,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SwitchRequest {
+    pub source: Bus,
+    #[serde(default)]
+    pub transition: Transition,
+    /// Crossfade length in seconds.
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ProgramSwitch {
+    pub on_air: Bus,
+    /// Crossfade seconds for the switch, which the player has not done yet.
+    #[serde(skip)]
+    fade: Option<f64>,
+    /// Seconds from the secondary source, which were in the crossfade already.
+    #[serde(skip)]
+    played: f64,
+    /// Program clip, which goes on after the crossfade back from the secondary source.
+    #[serde(skip)]
+    follow: Option<Media>,
+}
+
+impl ProgramSwitch {
+    /// Forget the open crossfade, like for the emergency slate.
+    pub fn clear(&mut self) {
+        self.fade = None;
+        self.follow = None;
+    }
+}
+
+/// The ingest server waits, while the secondary source is on air.
+pub async fn secondary_on_air(manager: &ChannelManager) -> bool {
+    manager.program.lock().await.on_air == Bus::B
+}
+
+/// Position in the clip, which is on air right now.
+fn position(node: &Media, config: &PlayoutConfig, shift: f64) -> f64 {
+    let played = node
+        .begin
+        .map(|begin| time_in_seconds(&config.channel.timezone) - (begin - shift))
+        .unwrap_or_default();
+
+    node.seek + played.max(0.0)
+}
+
+/// Input for one side of the crossfade, remote streams are played from where they are.
+fn fade_input(config: &PlayoutConfig, source: &str, seek: f64, duration: f64) -> Vec<String> {
+    let mut cmd = stream_options(config, source);
+
+    if seek > 0.0 && !is_remote(source) {
+        cmd.append(&mut vec_strings!["-ss", format!("{seek:.3}")]);
+    }
+
+    cmd.append(&mut vec_strings!["-t", duration, "-i", source]);
+
+    cmd
+}
+
+/// Mix both inputs, the first one fades out while the second one fades in. Both are fitted to
+/// the channel format first, logo and text from the channel filters are not in the mix.
+pub fn crossfade_filter(config: &PlayoutConfig, duration: f64) -> Filters {
+    let mut filters = Filters::new(config.clone(), 0);
+    let proc = &config.processing;
+
+    if !proc.audio_only {
+        let fit = format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={fps},format=yuv420p,settb=AVTB",
+            w = proc.width,
+            h = proc.height,
+            fps = proc.fps
+        );
+
+        filters.add_filter(
+            &format!("[0:v]{fit}[fade_a];[1:v]{fit}[fade_b];[fade_a][fade_b]xfade=transition=fade:duration={duration}:offset=0"),
+            0,
+            Video,
+        );
+    }
+
+    filters.add_filter(
+        &format!("[0:a:0]aresample=48000[afade_a];[1:a:0]aresample=48000[afade_b];[afade_a][afade_b]acrossfade=d={duration}"),
+        0,
+        Audio,
+    );
+
+    filters
+}
+
+/// Crossfade from one source to the other, `None` when a side can't be mixed, then it is a cut.
+pub fn crossfade_node(
+    config: &PlayoutConfig,
+    from: &Media,
+    from_seek: f64,
+    to: &Media,
+    to_seek: f64,
+    duration: f64,
+) -> Option<Media> {
+    let proc = &config.processing;
+    let mixable = |node: &Media| {
+        (is_remote(&node.source) || Path::new(&node.source).is_file())
+            && node.audio.is_empty()
+            && node
+                .probe
+                .as_ref()
+                .is_some_and(|p| !p.audio.is_empty() && (proc.audio_only || !p.video.is_empty()))
+    };
+
+    if proc.copy_audio || proc.copy_video || proc.audio_tracks > 1 || !mixable(from) || !mixable(to)
+    {
+        return None;
+    }
+
+    let mut cmd = fade_input(config, &from.source, from_seek, duration);
+    cmd.append(&mut fade_input(config, &to.source, to_seek, duration));
+
+    let mut node = to.clone();
+    node.seek = 0.0;
+    node.out = duration;
+    node.duration = duration;
+    node.category = "transition".to_string();
+    node.cmd = Some(cmd);
+    node.filter = Some(crossfade_filter(config, duration));
+    node.speed = None;
+
+    Some(node)
+}
+
+/// Next clip from the secondary source, files loop.
+async fn secondary_node(manager: &ChannelManager, config: &PlayoutConfig, seek: f64) -> Media {
+    let source = config.ingest.secondary_source.clone();
+    let mut node = Media::new(0, &source, true).await;
+    let mut cmd = stream_options(config, &source);
+
+    if !is_remote(&source) {
+        if seek > 0.0 {
+            cmd.append(&mut vec_strings!["-ss", format!("{seek:.3}")]);
+        }
+
+        cmd.append(&mut vec_strings!["-stream_loop", "-1"]);
+    }
+
+    cmd.append(&mut vec_strings!["-i", source, "-t", SECONDARY_CLIP]);
+
+    node.out = SECONDARY_CLIP;
+    node.duration = SECONDARY_CLIP;
+    node.category = "secondary".to_string();
+    node.begin = Some(time_in_seconds(&config.channel.timezone));
+    node.cmd = Some(cmd);
+    node.add_filter(config, &manager.filter_chain).await;
+
+    node
+}
+
+/// Node from the program switch: the clip after the crossfade back to the program, or the
+/// secondary source, while it is on air. `None` when the program goes on like planned.
+pub async fn program_node(manager: &ChannelManager) -> Option<Media> {
+    let mut switch = manager.program.lock().await;
+
+    if let Some(node) = switch.follow.take() {
+        return Some(node);
+    }
+
+    if switch.on_air == Bus::A {
+        return None;
+    }
+
+    let fade = switch.fade.take();
+    let seek = std::mem::take(&mut switch.played);
+    drop(switch);
+
+    let config = manager.config.lock().await.clone();
+    let node = secondary_node(manager, &config, seek).await;
+
+    if let Some(duration) = fade {
+        let current = manager.current_media.lock().await.clone();
+        let shift = manager.channel.lock().await.time_shift;
+
+        if let Some(mut fade_node) = current.and_then(|current| {
+            crossfade_node(
+                &config,
+                &current,
+                position(&current, &config, shift),
+                &node,
+                0.0,
+                duration,
+            )
+        }) {
+            fade_node.begin = node.begin;
+            manager.program.lock().await.played = duration;
+
+            return Some(fade_node);
+        }
+    }
+
+    Some(node)
+}
+
+/// First program clip after the switch back, with the crossfade from the secondary source
+/// before it, when one is requested.
+pub async fn program_return(manager: &ChannelManager, node: Media) -> Media {
+    let Some(duration) = manager.program.lock().await.fade.take() else {
+        return node;
+    };
+
+    let config = manager.config.lock().await.clone();
+    let shift = manager.channel.lock().await.time_shift;
+    let Some(current) = manager.current_media.lock().await.clone() else {
+        return node;
+    };
+
+    if node.seek + duration >= node.out {
+        return node;
+    }
+
+    let Some(mut fade_node) = crossfade_node(
+        &config,
+        &current,
+        position(&current, &config, shift),
+        &node,
+        node.seek,
+        duration,
+    ) else {
+        return node;
+    };
+
+    let mut next = node;
+    next.seek += duration;
+    next.begin = next.begin.map(|begin| begin + duration);
+    next.cmd = Some(seek_and_length(&config, &mut next));
+    next.add_filter(&config, &manager.filter_chain).await;
+
+    fade_node.begin = next.begin.map(|begin| begin - duration);
+    manager.program.lock().await.follow = Some(next);
+
+    fade_node
+}
+
+/// Switch between program and secondary source, the running clip or a live ingest are stopped.
+pub async fn switch_program(
+    manager: &ChannelManager,
+    request: &SwitchRequest,
+) -> Result<ProgramSwitch, ServiceError> {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+    let source = config.ingest.secondary_source.as_str();
+
+    if source.is_empty() {
+        return Err(ServiceError::Conflict(
+            "No secondary source is configured".to_string(),
+        ));
+    }
+
+    if manager.program.lock().await.on_air == request.source {
+        return Err(ServiceError::Conflict(format!(
+            "Source {:?} is already on air",
+            request.source
+        )));
+    }
+
+    if request.source == Bus::B {
+        MediaProbe::new(source).await.map_err(|e| {
+            ServiceError::Conflict(format!("Secondary source is not available: {e}"))
+        })?;
+    }
+
+    let status = {
+        let mut switch = manager.program.lock().await;
+
+        switch.on_air = request.source;
+        switch.played = 0.0;
+        switch.follow = None;
+        switch.fade = match request.transition {
+            Transition::Cut => None,
+            Transition::Fade => Some(request.duration.unwrap_or(FADE_DURATION).clamp(0.1, 10.0)),
+        };
+
+        switch.clone()
+    };
+
+    match request.source {
+        Bus::A => {
+            info!(target: Target::file_mail(), channel = id; "Switch from secondary source back to program");
+            event_history::push(id, EventKind::State, "Program on air");
+
+            manager.list_init.store(true, Ordering::SeqCst);
+        }
+        Bus::B => {
+            info!(target: Target::file_mail(), channel = id; "Switch to secondary source: <b><magenta>{source}</></b>");
+            event_history::push(id, EventKind::State, "Secondary source on air");
+
+            // the ingest server waits until the program is back
+            if let Err(e) = manager.stop(Ingest).await {
+                error!(target: Target::file_mail(), channel = id; "{e}");
+            }
+        }
+    }
+
+    manager.stop(Decoder).await?;
+
+    Ok(status)
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::player::{
+    controller::ChannelManager,
+    input::playlist::gen_source,
+    utils::{time_in_seconds, Media},
+};
+
+/// Play the slate this long, then try the normal source again.
+const SLATE_TIME: Duration = Duration::from_secs(600);
+const SLATE_CLIP: f64 = 60.0;
+
+/// Filler clips, or a color clip when no filler is set, for a channel which failed too often.
+pub struct SlateSource {
+    manager: ChannelManager,
+    until: Instant,
+}
+
+impl SlateSource {
+    pub fn new(manager: ChannelManager) -> Self {
+        Self {
+            manager,
+            until: Instant::now() + SLATE_TIME,
+        }
+    }
+}
+
+impl async_iterator::Iterator for SlateSource {
+    type Item = Media;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        if Instant::now() >= self.until {
+            return None;
+        }
+
+        let config = self.manager.config.lock().await.clone();
+        let mut node = Media::new(0, "", false).await;
+        node.out = SLATE_CLIP;
+        node.duration = SLATE_CLIP;
+        node.category = "slate".to_string();
+        node.begin = Some(time_in_seconds(&config.channel.timezone));
+
+        Some(gen_source(&config, node, &self.manager, 0).await)
+    }
+}
@@ -0,0 +1,337 @@
+/// SRT Ingest
+///
+/// With an `srt://` address in the ingest input parameters, ffmpeg listens for SRT callers, like
+/// remote studios. Passphrase and latency come from the ingest config, so they don't need to be
+/// in the parameters.
+///
+/// ffmpeg can't check the stream id from a caller, so with `ingest.srt_streamid` a relay listens
+/// on the address instead. It reads the stream id from the handshake and forwards only callers
+/// with an allowed id to ffmpeg, which listens on a local port.
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU16, Ordering},
+        Arc, LazyLock,
+    },
+    time::{Duration, Instant},
+};
+
+use log::*;
+use regex::Regex;
+use tokio::{net::UdpSocket, task::JoinHandle, time::timeout};
+
+use crate::player::controller::ChannelManager;
+use crate::utils::{config::Ingest, logging::Target};
+
+/// Handshake type from the conclusion, which has the extensions.
+const CONCLUSION: u32 = 0xFFFF_FFFF;
+
+/// Handshake extension with the stream id.
+const EXT_SID: u16 = 5;
+
+/// A caller is removed, when ffmpeg sends nothing for this time.
+const IDLE: Duration = Duration::from_secs(10);
+
+static SRT_HOST: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^srt://[^/?]*").unwrap());
+static SRT_ADDR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^srt://([^/?]+)").unwrap());
+
+#[derive(Debug, PartialEq)]
+pub enum Handshake {
+    /// Induction, or an other handshake before the conclusion.
+    Setup,
+    /// Conclusion, with the stream id from the caller.
+    Conclusion(Option<String>),
+}
+
+pub fn is_srt(url: &str) -> bool {
+    url.trim().to_lowercase().starts_with("srt://")
+}
+
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+/// Listener address for ffmpeg, with the options from the config. With a local port, ffmpeg
+/// listens there, behind the relay.
+pub fn listener_url(url: &str, ingest: &Ingest, local_port: Option<u16>) -> String {
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut params: Vec<String> = query
+        .split('&')
+        .filter(|p| {
+            let key = p.split('=').next().unwrap_or_default();
+
+            !p.is_empty()
+                && key != "mode"
+                && !(key == "latency" && ingest.srt_latency > 0)
+                && !(key == "passphrase" && !ingest.srt_passphrase.is_empty())
+        })
+        .map(str::to_string)
+        .collect();
+
+    params.push("mode=listener".to_string());
+
+    if ingest.srt_latency > 0 {
+        // ffmpeg takes microseconds
+        params.push(format!("latency={}", ingest.srt_latency * 1000));
+    }
+
+    if !ingest.srt_passphrase.is_empty() {
+        params.push(format!("passphrase={}", encode(&ingest.srt_passphrase)));
+    }
+
+    let base = match local_port {
+        Some(port) => SRT_HOST
+            .replace(base, format!("srt://127.0.0.1:{port}").as_str())
+            .to_string(),
+        None => base.to_string(),
+    };
+
+    format!("{base}?{}", params.join("&"))
+}
+
+/// Input parameters with the SRT options.
+pub fn input_cmd(ingest: &Ingest, cmd: &[String], local_port: Option<u16>) -> Vec<String> {
+    cmd.iter()
+        .map(|p| {
+            if is_srt(p) {
+                listener_url(p, ingest, local_port)
+            } else {
+                p.clone()
+            }
+        })
+        .collect()
+}
+
+/// Host and port from the address.
+pub fn socket_addr(url: &str) -> Option<String> {
+    SRT_ADDR
+        .captures(url.trim())
+        .and_then(|c| c.get(1))
+        .map(|a| a.as_str().to_string())
+}
+
+/// The stream id is allowed, empty patterns allow all.
+pub fn stream_id_matches(patterns: &str, id: &str) -> bool {
+    let mut patterns = patterns
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .peekable();
+
+    if patterns.peek().is_none() {
+        return true;
+    }
+
+    patterns.any(|p| match p.strip_suffix('*') {
+        Some(prefix) => id.starts_with(prefix),
+        None => p == id,
+    })
+}
+
+/// Handshake from the packet, `None` when it is no handshake.
+pub fn handshake(packet: &[u8]) -> Option<Handshake> {
+    // control packet, type 0 is the handshake, with 16 bytes header and 48 bytes handshake
+    if packet.len() < 64 || packet[0] & 0x80 == 0 || packet[0] & 0x7F != 0 || packet[1] != 0 {
+        return None;
+    }
+
+    let kind = u32::from_be_bytes(packet[36..40].try_into().ok()?);
+
+    if kind != CONCLUSION {
+        return Some(Handshake::Setup);
+    }
+
+    let mut pos = 64;
+
+    while pos + 4 <= packet.len() {
+        let ext = u16::from_be_bytes([packet[pos], packet[pos + 1]]);
+        let len = u16::from_be_bytes([packet[pos + 2], packet[pos + 3]]) as usize * 4;
+        let content = packet.get(pos + 4..pos + 4 + len)?;
+
+        if ext == EXT_SID {
+            // the stream id is stored in blocks of 4 bytes, each in reversed order
+            let id: Vec<u8> = content
+                .chunks(4)
+                .flat_map(|c| c.iter().rev().copied())
+                .collect();
+
+            return Some(Handshake::Conclusion(Some(
+                String::from_utf8_lossy(&id)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )));
+        }
+
+        pos += 4 + len;
+    }
+
+    Some(Handshake::Conclusion(None))
+}
+
+/// Free local port for ffmpeg, behind the relay.
+///
+/// The port is only free at the check, an other process can take it before ffmpeg binds it.
+/// Then ffmpeg fails and gets a new port with the restart, see [`LocalPort::renew`].
+pub async fn local_port() -> Option<u16> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.ok()?;
+
+    socket.local_addr().ok().map(|a| a.port())
+}
+
+/// Local port from ffmpeg behind the relay, shared with the relay.
+#[derive(Clone, Debug)]
+pub struct LocalPort(Arc<AtomicU16>);
+
+impl LocalPort {
+    pub fn new(port: u16) -> Self {
+        Self(Arc::new(AtomicU16::new(port)))
+    }
+
+    pub fn get(&self) -> u16 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Take a new free port for the restart of ffmpeg, the old one can be in use by now.
+    pub async fn renew(&self) -> u16 {
+        if let Some(port) = local_port().await {
+            self.0.store(port, Ordering::SeqCst);
+        }
+
+        self.get()
+    }
+}
+
+struct Caller {
+    local: Arc<UdpSocket>,
+    accepted: bool,
+    task: JoinHandle<()>,
+}
+
+async fn connect(
+    public: Arc<UdpSocket>,
+    peer: SocketAddr,
+    local_port: &LocalPort,
+) -> std::io::Result<Caller> {
+    let local = Arc::new(UdpSocket::bind("127.0.0.1:0").await?);
+    local.connect(("127.0.0.1", local_port.get())).await?;
+
+    let back = local.clone();
+    let task = tokio::spawn(async move {
+        let mut buf = vec![0; 2048];
+
+        while let Ok(Ok(len)) = timeout(IDLE, back.recv(&mut buf)).await {
+            if public.send_to(&buf[..len], peer).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(Caller {
+        local,
+        accepted: false,
+        task,
+    })
+}
+
+/// Relay from the public address to ffmpeg, which lets only callers with an allowed stream id
+/// through. Runs until the channel stops.
+pub async fn relay(manager: ChannelManager, addr: String, local_port: LocalPort) {
+    let id = manager.config.lock().await.general.channel_id;
+    let public = match UdpSocket::bind(&addr).await {
+        Ok(socket) => Arc::new(socket),
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = id; "SRT relay can't listen on <b><magenta>{addr}</></b>: {e}");
+            return;
+        }
+    };
+    let mut callers: HashMap<SocketAddr, Caller> = HashMap::new();
+    let mut rejected: HashMap<SocketAddr, Instant> = HashMap::new();
+    let mut buf = vec![0; 2048];
+
+    while manager.is_alive.load(Ordering::SeqCst) {
+        let Ok(Ok((len, peer))) = timeout(Duration::from_secs(1), public.recv_from(&mut buf)).await
+        else {
+            callers.retain(|_, c| !c.task.is_finished());
+            rejected.retain(|_, t| t.elapsed() < IDLE);
+            continue;
+        };
+        let packet = &buf[..len];
+        let shake = handshake(packet);
+        let conclusion = matches!(shake, Some(Handshake::Conclusion(_)));
+
+        if callers.get(&peer).is_some_and(|c| c.task.is_finished()) {
+            callers.remove(&peer);
+        }
+
+        match shake {
+            Some(Handshake::Conclusion(stream_id)) => {
+                let allowed = manager.config.lock().await.ingest.srt_streamid.clone();
+                let stream_id = stream_id.unwrap_or_default();
+
+                if !stream_id_matches(&allowed, &stream_id) {
+                    if !rejected.contains_key(&peer) {
+                        warn!(target: Target::file_mail(), channel = id; "Unexpected SRT stream id <b><magenta>{stream_id}</></b> from {peer}");
+                    }
+
+                    if let Some(caller) = callers.remove(&peer) {
+                        caller.task.abort();
+                    }
+
+                    rejected.insert(peer, Instant::now());
+                    continue;
+                }
+
+                rejected.remove(&peer);
+            }
+            Some(Handshake::Setup) => {}
+            None if callers.get(&peer).is_some_and(|c| c.accepted) => {}
+            // no data before the conclusion
+            None => continue,
+        }
+
+        if !callers.contains_key(&peer) {
+            match connect(public.clone(), peer, &local_port).await {
+                Ok(caller) => {
+                    callers.insert(peer, caller);
+                }
+                Err(e) => {
+                    error!(target: Target::file_mail(), channel = id; "SRT relay: {e}");
+                    continue;
+                }
+            }
+        }
+
+        if let Some(caller) = callers.get_mut(&peer) {
+            caller.accepted |= conclusion;
+            let _ = caller.local.send(packet).await;
+        }
+    }
+
+    for caller in callers.values() {
+        caller.task.abort();
+    }
+}
+
+/// Public address and local port for the relay, when the ingest is SRT with allowed stream ids.
+pub async fn relay_addr(ingest: &Ingest, cmd: &[String]) -> Option<(String, LocalPort)> {
+    if ingest.srt_streamid.trim().is_empty() {
+        return None;
+    }
+
+    let addr = cmd
+        .iter()
+        .find(|p| is_srt(p))
+        .and_then(|u| socket_addr(u))?;
+
+    Some((addr, LocalPort::new(local_port().await?)))
+}
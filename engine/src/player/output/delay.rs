@@ -0,0 +1,624 @@
+/// Delay Buffer
+///
+/// Hold the decoded feed for `output.delay` seconds, before it goes to the encoder and the
+/// renditions, so content can be dropped before it is on air. The chunks are kept in memory,
+/// or in segment files under `output.delay_path`, for long delays with high bitrates.
+/// A dump drops everything in the buffer and plays the filler for the delay time,
+/// after it the program goes on, with the same delay as before.
+use std::{
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use log::*;
+use serde::Serialize;
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::{sleep_until, Duration, Instant},
+};
+
+use super::{encoder_stalled, ladder::Ladder, watch};
+use crate::player::{
+    controller::{ChannelManager, ProcessUnit::*},
+    input::playlist::gen_source,
+    utils::{stderr_reader, time_in_seconds, Media},
+};
+use crate::utils::{
+    config::PlayoutConfig,
+    errors::ServiceError,
+    event_history::{self, EventKind},
+    logging::{fmt_cmd, Target},
+};
+use crate::vec_strings;
+
+/// Bytes in one segment file, before the next one starts.
+const SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+/// Chunks on the way into the buffer, when the buffer is behind the decoder waits.
+const QUEUE_SIZE: usize = 64;
+
+enum Request {
+    Status(oneshot::Sender<DelayStatus>),
+    Dump(oneshot::Sender<f64>),
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DelayStatus {
+    /// Configured delay in seconds.
+    pub delay: f64,
+    /// Seconds in the buffer, less than the delay after start and after a dump.
+    pub buffered: f64,
+    /// Filler plays, after a dump.
+    pub dumping: bool,
+}
+
+/// Handle for the API, it exists as long as the delay buffer runs.
+#[derive(Clone, Debug)]
+pub struct DelayControl {
+    requests: mpsc::Sender<Request>,
+}
+
+impl DelayControl {
+    pub async fn status(&self) -> Result<DelayStatus, ServiceError> {
+        let (tx, rx) = oneshot::channel();
+        self.requests
+            .send(Request::Status(tx))
+            .await
+            .map_err(|_| ServiceError::Conflict("Delay buffer is stopped".to_string()))?;
+
+        rx.await
+            .map_err(|_| ServiceError::Conflict("Delay buffer is stopped".to_string()))
+    }
+
+    /// Drop the buffer and play the filler, returns the dropped seconds.
+    pub async fn dump(&self) -> Result<f64, ServiceError> {
+        let (tx, rx) = oneshot::channel();
+        self.requests
+            .send(Request::Dump(tx))
+            .await
+            .map_err(|_| ServiceError::Conflict("Delay buffer is stopped".to_string()))?;
+
+        rx.await
+            .map_err(|_| ServiceError::Conflict("Delay buffer is stopped".to_string()))
+    }
+}
+
+struct Segment {
+    path: PathBuf,
+    chunks: usize,
+    reader: Option<BufReader<File>>,
+}
+
+/// Chunks in segment files, the oldest file is removed when all its chunks are read.
+struct Segments {
+    dir: PathBuf,
+    count: usize,
+    list: VecDeque<Segment>,
+    writer: Option<(File, u64)>,
+}
+
+impl Segments {
+    async fn new(dir: PathBuf) -> io::Result<Self> {
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir).await?;
+        }
+
+        fs::create_dir_all(&dir).await?;
+
+        Ok(Self {
+            dir,
+            count: 0,
+            list: VecDeque::new(),
+            writer: None,
+        })
+    }
+
+    async fn push(&mut self, data: &[u8]) -> io::Result<()> {
+        if self
+            .writer
+            .as_ref()
+            .map_or(true, |(_, size)| *size >= SEGMENT_SIZE)
+        {
+            self.count += 1;
+            let path = self.dir.join(format!("{}.ts", self.count));
+
+            self.writer = Some((File::create(&path).await?, 0));
+            self.list.push_back(Segment {
+                path,
+                chunks: 0,
+                reader: None,
+            });
+        }
+
+        if let (Some((writer, size)), Some(segment)) = (self.writer.as_mut(), self.list.back_mut())
+        {
+            writer.write_all(data).await?;
+            writer.flush().await?;
+
+            *size += data.len() as u64;
+            segment.chunks += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn pop(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        // segments which are read completely, the one which is written now stays
+        while self.list.len() > 1 && self.list.front().is_some_and(|s| s.chunks == 0) {
+            if let Some(done) = self.list.pop_front() {
+                fs::remove_file(done.path).await?;
+            }
+        }
+
+        let Some(segment) = self.list.front_mut() else {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        };
+
+        if segment.reader.is_none() {
+            segment.reader = Some(BufReader::new(File::open(&segment.path).await?));
+        }
+
+        let mut data = vec![0u8; len];
+
+        if let Some(reader) = segment.reader.as_mut() {
+            reader.read_exact(&mut data).await?;
+        }
+
+        segment.chunks -= 1;
+
+        Ok(data)
+    }
+
+    async fn clear(&mut self) -> io::Result<()> {
+        self.writer = None;
+
+        for segment in self.list.drain(..) {
+            fs::remove_file(segment.path).await?;
+        }
+
+        Ok(())
+    }
+}
+
+enum Store {
+    Memory(VecDeque<Vec<u8>>),
+    Disk(Segments),
+}
+
+/// Chunks with their arrival time.
+struct Buffer {
+    times: VecDeque<(Instant, usize)>,
+    store: Store,
+}
+
+impl Buffer {
+    async fn new(config: &PlayoutConfig) -> io::Result<Self> {
+        let store = if config.output.delay_path.is_empty() {
+            Store::Memory(VecDeque::new())
+        } else {
+            let dir = Path::new(&config.output.delay_path)
+                .join(format!("ffplayout_delay_{}", config.general.channel_id));
+
+            Store::Disk(Segments::new(dir).await?)
+        };
+
+        Ok(Self {
+            times: VecDeque::new(),
+            store,
+        })
+    }
+
+    fn oldest(&self) -> Option<Instant> {
+        self.times.front().map(|(time, _)| *time)
+    }
+
+    async fn push(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.times.push_back((Instant::now(), data.len()));
+
+        match &mut self.store {
+            Store::Memory(chunks) => chunks.push_back(data),
+            Store::Disk(segments) => segments.push(&data).await?,
+        }
+
+        Ok(())
+    }
+
+    async fn pop(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some((_, len)) = self.times.pop_front() else {
+            return Ok(None);
+        };
+
+        match &mut self.store {
+            Store::Memory(chunks) => Ok(chunks.pop_front()),
+            Store::Disk(segments) => segments.pop(len).await.map(Some),
+        }
+    }
+
+    /// Drop all chunks, returns the seconds which were in the buffer.
+    async fn clear(&mut self) -> io::Result<f64> {
+        let seconds = self
+            .oldest()
+            .map_or(0.0, |time| time.elapsed().as_secs_f64());
+
+        self.times.clear();
+
+        match &mut self.store {
+            Store::Memory(chunks) => chunks.clear(),
+            Store::Disk(segments) => segments.clear().await?,
+        }
+
+        Ok(seconds)
+    }
+
+    async fn remove(&self) {
+        if let Store::Disk(segments) = &self.store {
+            let _ = fs::remove_dir_all(&segments.dir).await;
+        }
+    }
+}
+
+/// Decoder for the filler, with the same output format as the clips.
+fn filler_cmd(config: &PlayoutConfig, node: &Media, log_format: &str) -> Vec<String> {
+    let mut cmd = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
+
+    if let Some(input_cmd) = &config.advanced.decoder.input_cmd {
+        cmd.append(&mut input_cmd.clone());
+    }
+
+    cmd.append(&mut node.cmd.clone().unwrap_or_default());
+
+    if let Some(mut filter) = node.filter.clone() {
+        cmd.append(&mut filter.cmd());
+        cmd.append(&mut filter.map());
+    }
+
+    if let Some(processing_cmd) = &config.processing.cmd {
+        cmd.extend_from_slice(processing_cmd);
+    }
+
+    cmd
+}
+
+struct Filler {
+    proc: Child,
+    stdout: ChildStdout,
+    log_task: JoinHandle<Result<(), ServiceError>>,
+}
+
+impl Filler {
+    async fn start(manager: &ChannelManager, seconds: f64) -> Result<Self, ServiceError> {
+        let config = manager.config.lock().await.clone();
+        let log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+
+        let mut node = Media::new(0, "", false).await;
+        node.out = seconds;
+        node.duration = seconds;
+        node.category = "filler".to_string();
+        node.begin = Some(time_in_seconds(&config.channel.timezone));
+
+        let node = gen_source(&config, node, manager, 0).await;
+        let cmd = filler_cmd(&config, &node, &log_format);
+
+        debug!(target: Target::file_mail(), channel = config.general.channel_id;
+            "Delay filler CMD: <bright-blue>ffmpeg {}</>",
+            fmt_cmd(&cmd)
+        );
+
+        let mut proc = Command::new("ffmpeg")
+            .args(cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdout = proc.stdout.take().unwrap();
+        let log_task = tokio::spawn(stderr_reader(
+            BufReader::new(proc.stderr.take().unwrap()),
+            config.logging.ignore_lines.clone(),
+            Decoder,
+            manager.clone(),
+        ));
+
+        Ok(Self {
+            proc,
+            stdout,
+            log_task,
+        })
+    }
+
+    async fn finish(mut self) {
+        let _ = self.proc.wait().await;
+        let _ = self.log_task.await;
+    }
+}
+
+/// Read from the filler, waits forever without one.
+async fn read_filler(filler: &mut Option<Filler>, chunk: &mut [u8]) -> io::Result<usize> {
+    match filler {
+        Some(filler) => filler.stdout.read(chunk).await,
+        None => std::future::pending().await,
+    }
+}
+
+pub struct Delay {
+    sender: mpsc::Sender<Vec<u8>>,
+    task: JoinHandle<Result<(), ServiceError>>,
+    manager: ChannelManager,
+}
+
+impl Delay {
+    /// Start the buffer, from now on it feeds the encoder and the renditions.
+    pub async fn start(
+        manager: &ChannelManager,
+        config: &PlayoutConfig,
+        writer: BufWriter<ChildStdin>,
+        ladder: Option<Ladder>,
+    ) -> Result<Self, ServiceError> {
+        let buffer = Buffer::new(config).await?;
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        let (req_sender, requests) = mpsc::channel(8);
+
+        *manager.delay.lock().await = Some(DelayControl {
+            requests: req_sender,
+        });
+
+        info!(target: Target::file_mail(), channel = config.general.channel_id;
+            "Delay output for <yellow>{}</> seconds", config.output.delay
+        );
+
+        let task = tokio::spawn(run(
+            manager.clone(),
+            Duration::from_secs_f64(config.output.delay),
+            buffer,
+            writer,
+            ladder,
+            receiver,
+            requests,
+        ));
+
+        Ok(Self {
+            sender,
+            task,
+            manager: manager.clone(),
+        })
+    }
+
+    /// Put a chunk into the buffer, waits while the queue is full and fails when the buffer
+    /// is stopped.
+    pub async fn push(&self, data: &[u8]) -> Result<(), ServiceError> {
+        self.sender
+            .send(data.to_vec())
+            .await
+            .map_err(|_| ServiceError::ServiceUnavailable("Delay buffer is stopped".to_string()))
+    }
+
+    /// Stop the buffer, the content which is not on air is dropped.
+    pub async fn stop(self) {
+        *self.manager.delay.lock().await = None;
+        drop(self.sender);
+
+        let _ = self.task.await;
+    }
+}
+
+async fn send(
+    id: i32,
+    writer: &mut BufWriter<ChildStdin>,
+    ladder: Option<&Ladder>,
+    data: &[u8],
+    watchdog: Duration,
+) -> Result<(), ServiceError> {
+    if let Some(ladder) = ladder {
+        ladder.send(data);
+    }
+
+    match watch(watchdog, writer.write_all(data)).await {
+        Some(result) => Ok(result?),
+        None => Err(encoder_stalled(id, watchdog)),
+    }
+}
+
+async fn run(
+    manager: ChannelManager,
+    delay: Duration,
+    mut buffer: Buffer,
+    mut writer: BufWriter<ChildStdin>,
+    ladder: Option<Ladder>,
+    mut receiver: mpsc::Receiver<Vec<u8>>,
+    mut requests: mpsc::Receiver<Request>,
+) -> Result<(), ServiceError> {
+    let id = manager.channel.lock().await.id;
+    let watchdog = Duration::from_secs(manager.config.lock().await.general.watchdog.max(0) as u64);
+    let mut filler: Option<Filler> = None;
+    let mut chunk = vec![0u8; 64 * 1024];
+
+    let result = loop {
+        let due = buffer.oldest().map(|time| time + delay);
+
+        tokio::select! {
+            data = receiver.recv() => {
+                match data {
+                    Some(data) => {
+                        if let Err(e) = buffer.push(data).await {
+                            break Err(e.into());
+                        }
+                    }
+                    None => break Ok(()),
+                }
+            }
+            Some(request) = requests.recv() => {
+                match request {
+                    Request::Status(reply) => {
+                        let _ = reply.send(DelayStatus {
+                            delay: delay.as_secs_f64(),
+                            buffered: buffer.oldest().map_or(0.0, |time| time.elapsed().as_secs_f64()),
+                            dumping: filler.is_some(),
+                        });
+                    }
+                    Request::Dump(reply) => {
+                        let dropped = match buffer.clear().await {
+                            Ok(seconds) => seconds,
+                            Err(e) => break Err(e.into()),
+                        };
+
+                        warn!(target: Target::file_mail(), channel = id;
+                            "Dump <yellow>{dropped:.1}</> seconds from the delay buffer, play filler"
+                        );
+                        event_history::push(id, EventKind::Output, format!("Delay buffer dumped, {dropped:.1} seconds dropped"));
+
+                        // a running filler is replaced, so the new program comes after the full delay
+                        if let Some(old) = filler.take() {
+                            drop(old.stdout);
+                            old.finish().await;
+                        }
+
+                        match Filler::start(&manager, delay.as_secs_f64()).await {
+                            Ok(new) => filler = Some(new),
+                            Err(e) => {
+                                error!(target: Target::file_mail(), channel = id; "Start filler failed: {e}");
+                            }
+                        }
+
+                        let _ = reply.send(dropped);
+                    }
+                }
+            }
+            num = read_filler(&mut filler, &mut chunk) => {
+                match num {
+                    Ok(num) if num > 0 => {
+                        if let Err(e) = send(id, &mut writer, ladder.as_ref(), &chunk[..num], watchdog).await {
+                            break Err(e);
+                        }
+                    }
+                    result => {
+                        if let Err(e) = result {
+                            error!(target: Target::file_mail(), channel = id; "Read filler failed: {e}");
+                        }
+
+                        if let Some(done) = filler.take() {
+                            done.finish().await;
+                        }
+
+                        info!(target: Target::file_mail(), channel = id; "Filler after dump is over, back to the program");
+                    }
+                }
+            }
+            _ = sleep_until(due.unwrap_or_else(Instant::now)), if filler.is_none() && due.is_some() => {
+                match buffer.pop().await {
+                    Ok(Some(data)) => {
+                        if let Err(e) = send(id, &mut writer, ladder.as_ref(), &data, watchdog).await {
+                            break Err(e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => break Err(e.into()),
+                }
+            }
+        }
+    };
+
+    if let Some(filler) = filler.take() {
+        drop(filler.stdout);
+        filler.finish().await;
+    }
+
+    buffer.remove().await;
+
+    if let Some(ladder) = ladder {
+        ladder.stop().await;
+    }
+
+    *manager.delay.lock().await = None;
+
+    if let Err(e) = &result {
+        error!(target: Target::file_mail(), channel = id; "Delay buffer failed: {e}");
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ffplayout_delay_test_{name}"))
+    }
+
+    #[tokio::test]
+    async fn segments_push_pop() {
+        let dir = test_dir("push_pop");
+        let mut segments = Segments::new(dir.clone()).await.unwrap();
+
+        segments.push(b"first").await.unwrap();
+        segments.push(b"second").await.unwrap();
+
+        assert_eq!(segments.list.len(), 1);
+        assert_eq!(segments.pop(5).await.unwrap(), b"first");
+        assert_eq!(segments.pop(6).await.unwrap(), b"second");
+        assert!(segments.pop(1).await.is_err());
+
+        // a full segment gets the next file, the read one is removed on the next pop
+        segments.writer.as_mut().unwrap().1 = SEGMENT_SIZE;
+        segments.push(b"third").await.unwrap();
+
+        assert_eq!(segments.list.len(), 2);
+        assert_eq!(segments.pop(5).await.unwrap(), b"third");
+        assert_eq!(segments.list.len(), 1);
+        assert!(!dir.join("1.ts").exists());
+        assert!(dir.join("2.ts").exists());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn segments_clear() {
+        let dir = test_dir("clear");
+        let mut segments = Segments::new(dir.clone()).await.unwrap();
+
+        segments.push(b"chunk").await.unwrap();
+        segments.clear().await.unwrap();
+
+        assert!(segments.list.is_empty());
+        assert!(!dir.join("1.ts").exists());
+
+        // writing goes on in a new file
+        segments.push(b"after").await.unwrap();
+        assert_eq!(segments.pop(5).await.unwrap(), b"after");
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn buffer_clear() {
+        for store in [
+            Store::Memory(VecDeque::new()),
+            Store::Disk(Segments::new(test_dir("buffer")).await.unwrap()),
+        ] {
+            let mut buffer = Buffer {
+                times: VecDeque::new(),
+                store,
+            };
+
+            assert_eq!(buffer.clear().await.unwrap(), 0.0);
+
+            buffer.push(b"one".to_vec()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            buffer.push(b"two".to_vec()).await.unwrap();
+
+            assert!(buffer.clear().await.unwrap() >= 0.02);
+            assert!(buffer.oldest().is_none());
+            assert!(buffer.pop().await.unwrap().is_none());
+
+            buffer.push(b"three".to_vec()).await.unwrap();
+            assert_eq!(buffer.pop().await.unwrap().unwrap(), b"three");
+
+            buffer.remove().await;
+        }
+    }
+}
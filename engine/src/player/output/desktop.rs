@@ -8,6 +8,7 @@ use crate::utils::errors::ServiceError;
 use crate::utils::{
     config::PlayoutConfig,
     logging::{fmt_cmd, Target},
+    priority::set_priority,
 };
 use crate::vec_strings;
 
@@ -15,6 +16,27 @@ use crate::vec_strings;
 ///
 /// Instead of streaming, we run a ffplay instance and play on desktop.
 pub async fn output(config: &PlayoutConfig, log_format: &str) -> Result<Child, ServiceError> {
+    let enc_cmd = output_cmd(config, log_format).await;
+
+    debug!(target: Target::file_mail(), channel = config.general.channel_id;
+        "Encoder CMD: <bright-blue>ffplay {}</>",
+        fmt_cmd(&enc_cmd)
+    );
+
+    let mut command = Command::new("ffplay");
+    command
+        .args(enc_cmd)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped());
+    set_priority(&mut command, &config.advanced.process);
+
+    let child = command.spawn()?;
+
+    Ok(child)
+}
+
+/// ffplay command, which reads the decoded clips from stdin.
+pub async fn output_cmd(config: &PlayoutConfig, log_format: &str) -> Vec<String> {
     let mut enc_filter: Vec<String> = vec![];
     let mut enc_cmd = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
 
@@ -68,16 +90,5 @@ pub async fn output(config: &PlayoutConfig, log_format: &str) -> Result<Child, S
 
     enc_cmd.append(&mut enc_filter);
 
-    debug!(target: Target::file_mail(), channel = config.general.channel_id;
-        "Encoder CMD: <bright-blue>ffplay {}</>",
-        fmt_cmd(&enc_cmd)
-    );
-
-    let child = Command::new("ffplay")
-        .args(enc_cmd)
-        .stdin(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    Ok(child)
+    enc_cmd
 }
@@ -17,32 +17,101 @@ out:
 
 */
 
-use std::{process::Stdio, sync::atomic::Ordering, time::SystemTime};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::Ordering,
+};
 
 use async_iterator::Iterator;
 use log::*;
+use m3u8_rs::Playlist;
 use tokio::{
+    fs,
     io::{AsyncBufReadExt, BufReader},
     process::Command,
 };
 
-use crate::utils::{logging::log_line, task_runner};
+use crate::utils::{
+    config::PlayoutConfig, logging::log_line, play_history, plugins, priority::set_priority,
+    task_runner,
+};
 use crate::vec_strings;
 use crate::{
     player::{
         controller::{ChannelManager, ProcessUnit::*},
-        input::source_generator,
+        input::{
+            emergency::emergency_node,
+            program::{program_node, program_return, secondary_on_air},
+            source_generator, srt,
+        },
         utils::{
-            get_delta, is_free_tcp_port, prepare_output_cmd, sec_to_time, stderr_reader,
-            valid_stream, Media,
+            channel_state::ChannelState, get_delta, is_free_tcp_port, prepare_output_cmd, resume,
+            scte35, sec_to_time, stderr_reader, supervisor, valid_stream, vast, Media,
         },
     },
+    sse,
     utils::{
         errors::ServiceError,
+        event_history::{self, EventKind},
         logging::{fmt_cmd, Target},
     },
 };
 
+/// Flags which keep the playlist going over the ffmpeg processes: every process appends to the
+/// list, with a discontinuity, and doesn't end it.
+const CONTINUITY_FLAGS: [&str; 2] = ["append_list", "omit_endlist"];
+
+/// Add the continuity flags to the HLS output, and with `start` the number for the first
+/// segment, when the playlist was lost. The playlist is the last argument.
+pub fn hls_continuity(cmd: &mut Vec<String>, start: Option<u64>) {
+    if !cmd.windows(2).any(|w| w[0] == "-f" && w[1] == "hls") {
+        return;
+    }
+
+    let mut args = vec![];
+
+    match cmd.iter().position(|c| c == "-hls_flags") {
+        Some(i) if i + 1 < cmd.len() => {
+            for flag in CONTINUITY_FLAGS {
+                if !cmd[i + 1].split('+').any(|f| f == flag) {
+                    cmd[i + 1].push_str(&format!("+{flag}"));
+                }
+            }
+
+            if start.is_some() && !cmd[i + 1].split('+').any(|f| f == "discont_start") {
+                cmd[i + 1].push_str("+discont_start");
+            }
+        }
+        _ => {
+            let mut flags = CONTINUITY_FLAGS.join("+");
+
+            if start.is_some() {
+                flags.push_str("+discont_start");
+            }
+
+            args.append(&mut vec_strings!["-hls_flags", flags]);
+        }
+    }
+
+    if let Some(number) = start {
+        args.append(&mut vec_strings!["-start_number", number]);
+    }
+
+    let at = cmd.len().saturating_sub(1);
+    cmd.splice(at..at, args);
+}
+
+/// Sequence number for the next segment from the playlist, `None` when the list is missing.
+async fn next_sequence(playlist: &Path) -> Option<u64> {
+    let bytes = fs::read(playlist).await.ok()?;
+
+    match m3u8_rs::parse_playlist_res(&bytes) {
+        Ok(Playlist::MediaPlaylist(pl)) => Some(pl.media_sequence + pl.segments.len() as u64),
+        _ => None,
+    }
+}
+
 /// Ingest Server for HLS
 async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceError> {
     let config = manager.config.lock().await.clone();
@@ -50,7 +119,7 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
     let playlist_init = manager.list_init.clone();
     let chain = manager.filter_chain.clone();
     let stream_input = config.ingest.input_cmd.clone().unwrap();
-    let mut error_count = 0;
+    let relay = srt::relay_addr(&config.ingest, &stream_input).await;
     let mut server_prefix = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
     let mut dummy_media = Media::new(0, "Live Stream", false).await;
 
@@ -60,11 +129,16 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
     let is_alive = manager.is_alive.clone();
     let ingest_is_alive = manager.ingest_is_alive.clone();
 
+    let local_port = relay.as_ref().map(|(_, port)| port.clone());
+    let mut server_args = vec![];
+
     if let Some(ingest_input_cmd) = &config.advanced.ingest.input_cmd {
         server_prefix.append(&mut ingest_input_cmd.clone());
     }
 
-    server_prefix.append(&mut stream_input.clone());
+    if config.ingest.scte35 {
+        server_args.append(&mut scte35::passthrough_args());
+    }
 
     if config.processing.vtt_enable {
         let vtt_dummy = config
@@ -73,11 +147,21 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
             .join(config.processing.vtt_dummy.clone().unwrap_or_default());
 
         if vtt_dummy.is_file() {
-            server_prefix.append(&mut vec_strings!["-i", vtt_dummy.to_string_lossy()]);
+            server_args.append(&mut vec_strings!["-i", vtt_dummy.to_string_lossy()]);
         }
     }
 
-    let server_cmd = prepare_output_cmd(&config, server_prefix.clone(), &dummy_media.filter);
+    // with the SRT relay the local port changes on restarts, so the command is built every time
+    let build_cmd = |port: Option<u16>| {
+        let mut prefix = server_prefix.clone();
+        prefix.append(&mut srt::input_cmd(&config.ingest, &stream_input, port));
+        prefix.append(&mut server_args.clone());
+
+        let mut cmd = prepare_output_cmd(&config, prefix, &dummy_media.filter);
+        hls_continuity(&mut cmd, None);
+
+        cmd
+    };
     let mut is_running;
 
     if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
@@ -89,16 +173,31 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
         }
     };
 
-    debug!(target: Target::file_mail(), channel = id;
-        "Server CMD: <bright-blue>ffmpeg {}</>",
-        fmt_cmd(&server_cmd)
-    );
+    if let Some((addr, port)) = relay {
+        tokio::spawn(srt::relay(manager.clone(), addr, port));
+    }
 
     loop {
-        let timer = SystemTime::now();
+        if manager.emergency.load(Ordering::SeqCst) || secondary_on_air(&manager).await {
+            if !is_alive.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // no live ingest, while the emergency slate or the secondary source is on air
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            continue;
+        }
+
+        let server_cmd = build_cmd(local_port.as_ref().map(|p| p.get()));
+
+        debug!(target: Target::file_mail(), channel = id;
+            "Server CMD: <bright-blue>ffmpeg {}</>",
+            fmt_cmd(&server_cmd)
+        );
+
         let proc_ctl = manager.clone();
         let mut server_proc = match Command::new("ffmpeg")
-            .args(server_cmd.clone())
+            .args(server_cmd)
             .stderr(Stdio::piped())
             .spawn()
         {
@@ -111,6 +210,7 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
 
         let server_err = BufReader::new(server_proc.stderr.take().unwrap());
         *manager.ingest.lock().await = Some(server_proc);
+        supervisor::started(&manager, Ingest).await;
         is_running = false;
 
         let mut lines = server_err.lines();
@@ -128,8 +228,10 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
                 ingest_is_alive.store(true, Ordering::SeqCst);
                 playlist_init.store(true, Ordering::SeqCst);
                 is_running = true;
+                manager.on_air().await;
 
                 info!(target: Target::file_mail(), channel = id; "Switch from {} to live ingest", config.processing.mode);
+                event_history::push(id, EventKind::Ingest, "Live ingest connected");
 
                 if let Err(e) = manager.stop(Decoder).await {
                     error!(target: Target::file_mail(), channel = id; "{e}");
@@ -145,47 +247,76 @@ async fn ingest_to_hls_server(manager: ChannelManager) -> Result<(), ServiceErro
 
         if ingest_is_alive.load(Ordering::SeqCst) {
             info!(target: Target::file_mail(), channel = id; "Switch from live ingest to {}", config.processing.mode);
+            event_history::push(id, EventKind::Ingest, "Live ingest disconnected");
         }
 
         ingest_is_alive.store(false, Ordering::SeqCst);
+        manager.on_air().await;
 
         if let Err(e) = manager.wait(Ingest).await {
             error!(target: Target::file_mail(), channel = id; "{e}");
         }
 
         if !is_alive.load(Ordering::SeqCst) {
+            supervisor::stopped(&manager, Ingest).await;
             break;
         }
 
-        if let Ok(elapsed) = timer.elapsed() {
-            if elapsed.as_millis() < 300 {
-                error_count += 1;
+        if !supervisor::respawn(&manager, Ingest).await {
+            error!(target: Target::file_mail(), channel = id; "Reach fatal error count in ingest, terminate channel!");
+            manager.set_state(ChannelState::Failed).await;
+            manager.channel.lock().await.active = false;
+            manager.stop_all(false).await?;
+            break;
+        }
 
-                if error_count > 10 {
-                    error!(target: Target::file_mail(), channel = id; "Reach fatal error count in ingest, terminate channel!");
-                    manager.channel.lock().await.active = false;
-                    manager.stop_all(false).await?;
-                    break;
-                }
-            } else {
-                error_count = 0;
-            }
+        if let Some(port) = &local_port {
+            // the port can be taken in between, then ffmpeg fails to listen there
+            debug!(target: Target::file_mail(), channel = id; "ffmpeg listens on port <b><magenta>{}</></b> after the restart", port.renew().await);
         }
     }
 
     Ok(())
 }
 
+/// HLS writer command for the clip, `cmd` is the input part from the clip.
+pub fn writer_cmd(
+    config: &PlayoutConfig,
+    log_format: &str,
+    node: &Media,
+    mut cmd: Vec<String>,
+    read_rate: f64,
+) -> Vec<String> {
+    let mut dec_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-progress",
+        "pipe:2",
+        "-v",
+        log_format
+    ];
+
+    if let Some(decoder_input_cmd) = &config.advanced.decoder.input_cmd {
+        dec_prefix.append(&mut decoder_input_cmd.clone());
+    }
+
+    dec_prefix.append(&mut vec_strings!["-readrate", read_rate]);
+    dec_prefix.append(&mut cmd);
+
+    prepare_output_cmd(config, dec_prefix, &node.filter)
+}
+
 /// HLS Writer
 ///
 /// Write with single ffmpeg instance directly to a HLS playlist.
+/// Each process appends to the playlist, when the list was lost in between, the segment
+/// numbers go on from the last known one, so players don't see a reset media sequence.
 pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
     let config = manager.config.lock().await.clone();
     let id = config.general.channel_id;
     let current_media = manager.current_media.clone();
     let is_alive = manager.is_alive.clone();
-
-    let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+    let playlist_init = manager.list_init.clone();
 
     let channel_mgr_2 = manager.clone();
     let ingest_is_alive = manager.ingest_is_alive.clone();
@@ -195,21 +326,64 @@ pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
     // spawn a thread for ffmpeg ingest server and create a channel for package sending
     if config.ingest.enable {
         tokio::spawn(ingest_to_hls_server(channel_mgr_2));
+
+        // the playlist from the output, a name with variant pattern can't be read
+        let playlist = config
+            .output
+            .output_cmd
+            .as_ref()
+            .and_then(|cmd| cmd.last())
+            .filter(|p| p.ends_with(".m3u8") && !p.contains('%'));
+
+        if let (true, Some(playlist)) = (config.ingest.scte35, playlist) {
+            tokio::spawn(scte35::tag_segments(
+                manager.clone(),
+                PathBuf::from(playlist),
+            ));
+        }
     }
 
-    let mut error_count = 0;
+    // next segment number, to continue it when the playlist was lost
+    let mut sequence: Option<u64> = None;
 
     let mut get_source = get_source.await;
-    while let Some(node) = get_source.next().await {
+    loop {
+        if manager.draining.load(Ordering::SeqCst) {
+            info!(target: Target::file_mail(), channel = id; "Clip is finished, stop playout");
+            break;
+        }
+
+        let node = if manager.emergency.load(Ordering::SeqCst) {
+            emergency_node(&manager).await
+        } else {
+            match manager.freeze.lock().await.take() {
+                Some(node) => {
+                    // after the hold, the playlist starts again on the current time
+                    playlist_init.store(true, Ordering::SeqCst);
+
+                    node
+                }
+                None => match program_node(&manager).await {
+                    Some(node) => node,
+                    None => match get_source.next().await {
+                        Some(node) => program_return(&manager, node).await,
+                        None => break,
+                    },
+                },
+            }
+        };
+
         *current_media.lock().await = Some(node.clone());
-        let ignore = config.logging.ignore_lines.clone();
-        let timer = SystemTime::now();
+        manager.on_air().await;
 
+        let config = manager.config.lock().await.clone();
+        let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+        let ignore = config.logging.ignore_lines.clone();
         if !is_alive.load(Ordering::SeqCst) {
             break;
         }
 
-        let mut cmd = match &node.cmd {
+        let cmd = match &node.cmd {
             Some(cmd) => cmd.clone(),
             None => break,
         };
@@ -224,6 +398,20 @@ pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
             node.source
         );
 
+        if let Some(pool) = manager.db_pool.clone() {
+            tokio::spawn(play_history::record(
+                pool,
+                id,
+                config.channel.timezone,
+                node.clone(),
+            ));
+        }
+
+        tokio::spawn(resume::record(manager.clone(), node.clone()));
+        tokio::spawn(vast::track(manager.clone(), node.clone()));
+        plugins::event(&manager, "clip_started", plugins::clip_json(&node));
+        sse::clip_started(&manager, &node).await;
+
         if config.task.enable {
             if config.task.path.is_file() {
                 let channel_mgr_3 = manager.clone();
@@ -237,12 +425,6 @@ pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
             }
         }
 
-        let mut dec_prefix = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
-
-        if let Some(decoder_input_cmd) = &config.advanced.decoder.input_cmd {
-            dec_prefix.append(&mut decoder_input_cmd.clone());
-        }
-
         let mut read_rate = 1.0;
 
         if let Some(begin) = &node.begin {
@@ -259,21 +441,37 @@ pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
             }
         }
 
-        dec_prefix.append(&mut vec_strings!["-readrate", read_rate]);
+        let mut dec_cmd = writer_cmd(&config, &ff_log_format, &node, cmd, read_rate);
+
+        // the playlist from the output, a name with variant pattern can't be read
+        let playlist = dec_cmd
+            .last()
+            .filter(|p| p.ends_with(".m3u8") && !p.contains('%'))
+            .map(PathBuf::from);
+        let mut start = None;
+
+        if let (Some(path), Some(expected)) = (&playlist, sequence) {
+            if next_sequence(path).await.unwrap_or_default() < expected {
+                warn!(target: Target::file_mail(), channel = id;
+                    "HLS playlist was reset, continue with segment <yellow>{expected}</>"
+                );
+
+                start = Some(expected);
+            }
+        }
 
-        dec_prefix.append(&mut cmd);
-        let dec_cmd = prepare_output_cmd(&config, dec_prefix, &node.filter);
+        hls_continuity(&mut dec_cmd, start);
 
         debug!(target: Target::file_mail(), channel = id;
             "HLS writer CMD: <bright-blue>ffmpeg {}</>",
             fmt_cmd(&dec_cmd)
         );
 
-        let mut dec_proc = match Command::new("ffmpeg")
-            .args(dec_cmd)
-            .stderr(Stdio::piped())
-            .spawn()
-        {
+        let mut command = Command::new("ffmpeg");
+        command.args(dec_cmd).stderr(Stdio::piped());
+        set_priority(&mut command, &config.advanced.process);
+
+        let mut dec_proc = match command.spawn() {
             Ok(proc) => proc,
             Err(e) => {
                 error!(target: Target::file_mail(), channel = id; "couldn't spawn ffmpeg process: {e}");
@@ -283,6 +481,7 @@ pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
 
         let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
         *manager.decoder.lock().await = Some(dec_proc);
+        supervisor::started(&manager, Decoder).await;
 
         if let Err(e) = stderr_reader(dec_err, ignore, Decoder, manager.clone()).await {
             error!(target: Target::file_mail(), channel = id; "{e:?}");
@@ -292,21 +491,17 @@ pub async fn write_hls(manager: ChannelManager) -> Result<(), ServiceError> {
             error!(target: Target::file_mail(), channel = id; "{e}");
         }
 
+        if let Some(path) = &playlist {
+            sequence = sequence.max(next_sequence(path).await);
+        }
+
         while ingest_is_alive.load(Ordering::SeqCst) {
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
         }
 
-        if let Ok(elapsed) = timer.elapsed() {
-            if elapsed.as_millis() < 300 {
-                error_count += 1;
-
-                if error_count > 10 {
-                    error!(target: Target::file_mail(), channel = id; "Reach fatal error count, terminate channel!");
-                    break;
-                }
-            } else {
-                error_count = 0;
-            }
+        if !supervisor::respawn(&manager, Decoder).await {
+            error!(target: Target::file_mail(), channel = id; "Reach fatal error count, terminate channel!");
+            break;
         }
     }
 
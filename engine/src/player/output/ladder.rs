@@ -0,0 +1,397 @@
+/// Encoder Ladder
+///
+/// Run one more encoder for every rendition from the output config. All chunks which go to the main
+/// encoder are also sent into a broadcast buffer, every rendition reads them with its own receiver.
+/// So a clip is decoded only once, also when it goes out in different bitrates or to more destinations.
+/// Each rendition can be stopped and started again over the API, while the channel runs.
+use std::{collections::HashSet, process::Stdio, sync::Arc};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use shlex::split;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{ChildStderr, Command},
+    sync::{
+        broadcast::{self, error::RecvError},
+        watch, Mutex,
+    },
+    task::JoinHandle,
+    time::{sleep, timeout, Duration},
+};
+
+use crate::utils::{
+    advanced_config::ProcessConfig,
+    config::{PlayoutConfig, Rendition, FFMPEG_IGNORE_ERRORS},
+    control::ProcessCtl,
+    expand_env,
+    logging::{fmt_cmd, Target},
+    priority::set_priority,
+};
+use crate::vec_strings;
+
+/// Chunks in the buffer, a rendition which falls further behind loses data.
+const BUFFER_CHUNKS: usize = 256;
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+/// Time for the encoders to finish, after the feed is closed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Chunk = Arc<Vec<u8>>;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OutputRequest {
+    pub command: ProcessCtl,
+}
+
+#[derive(Debug, Default, Serialize, Clone, PartialEq, Eq)]
+pub struct OutputStatus {
+    pub name: String,
+    /// Not stopped by the operator.
+    pub enabled: bool,
+    /// Encoder is running right now.
+    pub running: bool,
+}
+
+/// Renditions which the operator stopped, they stay stopped until they are started again,
+/// also over restarts of the channel.
+#[derive(Clone, Debug)]
+pub struct OutputSwitch {
+    stopped: Arc<watch::Sender<HashSet<String>>>,
+    running: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Default for OutputSwitch {
+    fn default() -> Self {
+        Self {
+            stopped: Arc::new(watch::channel(HashSet::new()).0),
+            running: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+}
+
+impl OutputSwitch {
+    pub fn is_stopped(&self, name: &str) -> bool {
+        self.stopped.borrow().contains(name)
+    }
+
+    /// Start or stop the rendition, returns `false` when it is in this state already.
+    pub fn set(&self, name: &str, enabled: bool) -> bool {
+        self.stopped.send_if_modified(|stopped| {
+            if enabled {
+                stopped.remove(name)
+            } else {
+                stopped.insert(name.to_string())
+            }
+        })
+    }
+
+    pub async fn status(&self, config: &PlayoutConfig) -> Vec<OutputStatus> {
+        let running = self.running.lock().await;
+
+        config
+            .output
+            .renditions
+            .iter()
+            .map(|r| OutputStatus {
+                name: r.name.clone(),
+                enabled: !self.is_stopped(&r.name),
+                running: running.contains(&r.name),
+            })
+            .collect()
+    }
+
+    async fn running(&self, name: &str, running: bool) {
+        let mut list = self.running.lock().await;
+
+        if running {
+            list.insert(name.to_string());
+        } else {
+            list.remove(name);
+        }
+    }
+}
+
+/// Why the encoder ended.
+enum End {
+    /// Feed is closed, the channel stops.
+    Closed,
+    /// Stopped by the operator.
+    Stopped,
+    /// Encoder ended by itself.
+    Failed,
+}
+
+/// Encoder command for the rendition, it reads the same format as the main encoder.
+pub fn rendition_cmd(
+    config: &PlayoutConfig,
+    rendition: &Rendition,
+    log_format: &str,
+) -> Option<Vec<String>> {
+    let mut cmd = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
+
+    if let Some(input_cmd) = &config.advanced.encoder.input_cmd {
+        cmd.append(&mut input_cmd.clone());
+    }
+
+    cmd.append(&mut vec_strings!["-i", "pipe:0"]);
+    cmd.append(&mut split(&expand_env(&rendition.output_param))?);
+
+    Some(cmd)
+}
+
+pub struct Ladder {
+    sender: broadcast::Sender<Chunk>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Ladder {
+    /// Start the renditions, `None` when there are none.
+    pub fn start(config: &PlayoutConfig, log_format: &str, switch: &OutputSwitch) -> Option<Self> {
+        let id = config.general.channel_id;
+
+        if config.output.renditions.is_empty() {
+            return None;
+        }
+
+        let (sender, _) = broadcast::channel(BUFFER_CHUNKS);
+        let mut tasks = vec![];
+
+        for rendition in &config.output.renditions {
+            let Some(cmd) = rendition_cmd(config, rendition, log_format) else {
+                error!(target: Target::file_mail(), channel = id;
+                    "Output parameters from rendition <yellow>{}</> are invalid",
+                    rendition.name
+                );
+                continue;
+            };
+
+            debug!(target: Target::file_mail(), channel = id;
+                "Rendition <yellow>{}</> CMD: <bright-blue>ffmpeg {}</>",
+                rendition.name,
+                fmt_cmd(&cmd)
+            );
+
+            tasks.push(tokio::spawn(run(
+                id,
+                rendition.name.clone(),
+                cmd,
+                config.logging.ignore_lines.clone(),
+                config.advanced.process.clone(),
+                switch.clone(),
+                sender.subscribe(),
+            )));
+        }
+
+        Some(Self { sender, tasks })
+    }
+
+    /// Send chunk to all renditions, this never waits for them.
+    pub fn send(&self, data: &[u8]) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(Arc::new(data.to_vec()));
+        }
+    }
+
+    /// Close the feed and wait until the encoders are finished.
+    pub async fn stop(self) {
+        drop(self.sender);
+
+        for mut task in self.tasks {
+            if timeout(STOP_TIMEOUT, &mut task).await.is_err() {
+                task.abort();
+            }
+        }
+    }
+}
+
+async fn log_reader(id: i32, name: String, stderr: ChildStderr, ignore: Vec<String>) {
+    let mut lines = BufReader::new(stderr).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if FFMPEG_IGNORE_ERRORS.iter().any(|i| line.contains(*i))
+            || ignore.iter().any(|i| line.contains(i))
+        {
+            continue;
+        }
+
+        if line.contains("[warning]") {
+            warn!(target: Target::file_mail(), channel = id;
+                "<bright black>[Rendition {name}]</> {}",
+                line.replace("[warning] ", "")
+            );
+        } else if line.contains("[error]") || line.contains("[fatal]") {
+            error!(target: Target::file_mail(), channel = id;
+                "<bright black>[Rendition {name}]</> {}",
+                line.replace("[error] ", "").replace("[fatal] ", "")
+            );
+        } else {
+            info!(target: Target::file_mail(), channel = id;
+                "<bright black>[Rendition {name}]</> {}",
+                line.replace("[info] ", "")
+            );
+        }
+    }
+}
+
+/// Wait until the rendition is started again, returns `false` when the feed is closed before.
+async fn paused(
+    name: &str,
+    control: &mut watch::Receiver<HashSet<String>>,
+    receiver: &mut broadcast::Receiver<Chunk>,
+) -> bool {
+    loop {
+        tokio::select! {
+            changed = control.changed() => {
+                if changed.is_err() || !control.borrow_and_update().contains(name) {
+                    return true;
+                }
+            }
+            chunk = receiver.recv() => {
+                if let Err(RecvError::Closed) = chunk {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Run the encoder until the feed is closed, or the operator stops it.
+async fn encode(
+    id: i32,
+    name: &str,
+    cmd: &[String],
+    ignore: &[String],
+    process: &ProcessConfig,
+    control: &mut watch::Receiver<HashSet<String>>,
+    receiver: &mut broadcast::Receiver<Chunk>,
+) -> std::io::Result<End> {
+    // start the encoder first with data, after a restart the feed could be closed already
+    let mut next = loop {
+        tokio::select! {
+            chunk = receiver.recv() => match chunk {
+                Ok(chunk) => break Some(chunk),
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return Ok(End::Closed),
+            },
+            Ok(()) = control.changed() => {
+                if control.borrow_and_update().contains(name) {
+                    return Ok(End::Stopped);
+                }
+            }
+        }
+    };
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(cmd)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    set_priority(&mut command, process);
+
+    let mut proc = command.spawn()?;
+
+    let mut stdin = proc.stdin.take().unwrap();
+    let log_task = tokio::spawn(log_reader(
+        id,
+        name.to_string(),
+        proc.stderr.take().unwrap(),
+        ignore.to_vec(),
+    ));
+
+    let end = loop {
+        if let Some(chunk) = next.take() {
+            if stdin.write_all(&chunk).await.is_err() {
+                break End::Failed;
+            }
+        }
+
+        tokio::select! {
+            chunk = receiver.recv() => match chunk {
+                Ok(chunk) => next = Some(chunk),
+                Err(RecvError::Lagged(num)) => {
+                    warn!(target: Target::file_mail(), channel = id;
+                        "Rendition <yellow>{name}</> is too slow, skipped <yellow>{num}</> chunks"
+                    );
+                }
+                Err(RecvError::Closed) => break End::Closed,
+            },
+            Ok(()) = control.changed() => {
+                if control.borrow_and_update().contains(name) {
+                    break End::Stopped;
+                }
+            }
+        }
+    };
+
+    // without input the encoder finishes
+    drop(stdin);
+    proc.wait().await?;
+    let _ = log_task.await;
+
+    Ok(end)
+}
+
+/// Keep the rendition running, as long as the player sends data and it is not stopped.
+async fn run(
+    id: i32,
+    name: String,
+    cmd: Vec<String>,
+    ignore: Vec<String>,
+    process: ProcessConfig,
+    switch: OutputSwitch,
+    mut receiver: broadcast::Receiver<Chunk>,
+) {
+    let mut control = switch.stopped.subscribe();
+
+    loop {
+        if switch.is_stopped(&name) {
+            if !paused(&name, &mut control, &mut receiver).await {
+                break;
+            }
+
+            info!(target: Target::file_mail(), channel = id; "Start rendition <yellow>{name}</>");
+            receiver = receiver.resubscribe();
+        }
+
+        switch.running(&name, true).await;
+
+        let result = encode(
+            id,
+            &name,
+            &cmd,
+            &ignore,
+            &process,
+            &mut control,
+            &mut receiver,
+        )
+        .await;
+
+        switch.running(&name, false).await;
+
+        match result {
+            Ok(End::Closed) => break,
+            Ok(End::Stopped) => {
+                info!(target: Target::file_mail(), channel = id; "Rendition <yellow>{name}</> is stopped");
+                continue;
+            }
+            Ok(End::Failed) => {
+                error!(target: Target::file_mail(), channel = id;
+                    "Rendition <yellow>{name}</> stopped, restart in <yellow>{}</> seconds",
+                    RESTART_DELAY.as_secs()
+                );
+            }
+            Err(e) => {
+                error!(target: Target::file_mail(), channel = id;
+                    "Rendition <yellow>{name}</> failed: {e}, restart in <yellow>{}</> seconds",
+                    RESTART_DELAY.as_secs()
+                );
+            }
+        }
+
+        sleep(RESTART_DELAY).await;
+
+        // skip the data which came in the meantime, the encoder starts with the current feed
+        receiver = receiver.resubscribe();
+    }
+}
@@ -1,42 +1,370 @@
-use std::{process::Stdio, sync::atomic::Ordering};
+use std::{
+    future::Future,
+    io,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use async_iterator::Iterator;
 use log::*;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
-    process::Command,
-    time::{sleep, Duration},
+    process::{ChildStdin, ChildStdout, Command},
+    task::JoinHandle,
+    time::{sleep, timeout, Duration, Instant},
 };
 
+mod delay;
 mod desktop;
 mod hls;
+mod ladder;
 mod null;
+mod replay;
+#[cfg(target_os = "linux")]
+mod splice;
 mod stream;
 
-pub use hls::write_hls;
+pub use delay::{DelayControl, DelayStatus};
+pub use hls::{hls_continuity, write_hls, writer_cmd};
+pub use ladder::{rendition_cmd, OutputRequest, OutputStatus, OutputSwitch};
+pub use replay::{replay_cmd, replay_window, ReplayControl, ReplayInfo, ReplayRequest};
+
+use delay::Delay;
+use ladder::Ladder;
+use replay::Recorder;
 
 use crate::player::{
     controller::{ChannelManager, ProcessUnit::*},
-    input::{ingest_server, source_generator},
-    utils::{sec_to_time, stderr_reader},
+    filter::{concat_chains, Filters},
+    input::{
+        emergency::emergency_node,
+        ingest_server,
+        playlist::stream_fallback,
+        program::{program_node, program_return},
+        source_generator,
+    },
+    utils::{
+        concat::{chainable, ConcatChain, MAX_CHAIN_CLIPS},
+        health::output_connected,
+        is_remote,
+        progress::EncoderStats,
+        resume,
+        scte35::{self, TsScanner},
+        sec_to_time, stderr_reader, supervisor, vast, Media,
+    },
 };
+use crate::sse;
 use crate::utils::{
-    config::OutputMode::*,
+    config::{OutputMode::*, PlayoutConfig},
     errors::ServiceError,
+    event_history::{self, EventKind},
     logging::{fmt_cmd, Target},
+    play_history, plugins,
+    priority::set_priority,
     task_runner,
 };
 use crate::vec_strings;
 
+/// Wait for the pipe, `None` when nothing moved in the watchdog time.
+async fn watch<F: Future>(limit: Duration, future: F) -> Option<F::Output> {
+    if limit.is_zero() {
+        Some(future.await)
+    } else {
+        timeout(limit, future).await.ok()
+    }
+}
+
+/// What happened, when data was passed from decoder to encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Moved {
+    Data,
+    End,
+    Ingest,
+    DecoderStalled,
+    EncoderStalled,
+}
+
+/// Where the decoded data goes, straight to the encoder or over the delay buffer.
+enum Feed {
+    Direct {
+        writer: BufWriter<ChildStdin>,
+        ladder: Option<Ladder>,
+    },
+    Delayed(Delay),
+}
+
+impl Feed {
+    /// Encoder pipe, when the data can be spliced into it.
+    #[cfg(target_os = "linux")]
+    fn splice_writer(&mut self) -> Option<&mut BufWriter<ChildStdin>> {
+        match self {
+            Self::Direct {
+                writer,
+                ladder: None,
+            } => Some(writer),
+            _ => None,
+        }
+    }
+
+    fn is_delayed(&self) -> bool {
+        matches!(self, Self::Delayed(_))
+    }
+
+    /// Send the chunk to the renditions, or into the delay buffer, which can make us wait.
+    /// Returns `true` when nothing is left to write to the encoder.
+    /// When it is cancelled, the chunk is not in the delay buffer.
+    async fn hand_over(&self, data: &[u8]) -> Result<bool, ServiceError> {
+        match self {
+            Self::Direct { ladder, .. } => {
+                if let Some(ladder) = ladder {
+                    ladder.send(data);
+                }
+
+                Ok(false)
+            }
+            Self::Delayed(delay) => {
+                delay.push(data).await?;
+
+                Ok(true)
+            }
+        }
+    }
+
+    /// Write the whole chunk, `false` when the encoder takes nothing in the watchdog time.
+    async fn write_all(&mut self, data: &[u8], watchdog: Duration) -> Result<bool, ServiceError> {
+        match watch(watchdog, self.hand_over(data)).await.transpose()? {
+            Some(true) => return Ok(true),
+            Some(false) => {}
+            None => return Ok(false),
+        }
+
+        match self {
+            Self::Direct { writer, .. } => Ok(watch(watchdog, writer.write_all(data))
+                .await
+                .transpose()?
+                .is_some()),
+            Self::Delayed(_) => Ok(true),
+        }
+    }
+
+    async fn stop(self) {
+        match self {
+            Self::Direct { mut writer, ladder } => {
+                // the last chunk goes to the encoder, before its input is closed
+                let _ = timeout(Duration::from_secs(1), writer.flush()).await;
+
+                if let Some(ladder) = ladder {
+                    ladder.stop().await;
+                }
+            }
+            Self::Delayed(delay) => delay.stop().await,
+        }
+    }
+}
+
+/// Running decoder, with the task which reads its log.
+struct DecoderPipe {
+    stdout: ChildStdout,
+    log_task: JoinHandle<Result<(), ServiceError>>,
+    #[cfg(target_os = "linux")]
+    splice: Option<splice::Splice>,
+    /// Bytes from the decoder in the buffer, and how many of them are written.
+    filled: usize,
+    written: usize,
+    /// The bytes in the buffer went to the renditions or the delay buffer.
+    handed: bool,
+}
+
+impl DecoderPipe {
+    /// Pass decoder data to the encoder. On Linux the pipes are spliced in a blocking thread,
+    /// which returns when the decoder ends, otherwise one chunk is copied.
+    /// With renditions every chunk also goes to the ladder, with a delay into the buffer
+    /// and with the replay into the recording, so the data is never spliced.
+    /// Both ways can be cancelled, without losing data.
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    async fn pass(
+        &mut self,
+        feed: &mut Feed,
+        buffer: &mut [u8],
+        watchdog: Duration,
+        ingest: &Arc<AtomicBool>,
+        recorder: Option<&Recorder>,
+    ) -> Result<Moved, ServiceError> {
+        #[cfg(target_os = "linux")]
+        if let (None, Some(enc_writer)) = (recorder, feed.splice_writer()) {
+            use std::os::fd::AsFd;
+
+            if self.splice.is_none() {
+                // data from the ingest could be in the buffer
+                enc_writer.flush().await?;
+
+                let decoder = self.stdout.as_fd().try_clone_to_owned()?;
+                let encoder = enc_writer.get_ref().as_fd().try_clone_to_owned()?;
+                let ingest = ingest.clone();
+
+                self.splice = Some(splice::Splice::start(decoder, encoder, ingest, watchdog)?);
+            }
+
+            if let Some(splice) = self.splice.as_mut() {
+                let moved = splice.wait().await;
+                self.splice = None;
+
+                return Ok(moved?);
+            }
+        }
+
+        // single reads and writes, so a chunk is not lost when the select takes the other branch
+        if self.written == self.filled {
+            let Some(num) = watch(watchdog, self.stdout.read(buffer)).await else {
+                return Ok(Moved::DecoderStalled);
+            };
+
+            let num = num?;
+
+            if num == 0 {
+                return Ok(Moved::End);
+            }
+
+            if let Some(recorder) = recorder {
+                recorder.record(&buffer[..num]);
+            }
+
+            self.filled = num;
+            self.written = 0;
+            self.handed = false;
+        }
+
+        if !self.handed {
+            let Some(done) = watch(watchdog, feed.hand_over(&buffer[..self.filled]))
+                .await
+                .transpose()?
+            else {
+                return Ok(Moved::EncoderStalled);
+            };
+
+            if done {
+                self.written = self.filled;
+            }
+
+            self.handed = true;
+        }
+
+        let Feed::Direct {
+            writer: enc_writer, ..
+        } = feed
+        else {
+            return Ok(Moved::Data);
+        };
+
+        while self.written < self.filled {
+            let Some(num) = watch(
+                watchdog,
+                enc_writer.write(&buffer[self.written..self.filled]),
+            )
+            .await
+            .transpose()?
+            else {
+                return Ok(Moved::EncoderStalled);
+            };
+
+            if num == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+            }
+
+            self.written += num;
+        }
+
+        Ok(Moved::Data)
+    }
+}
+
+fn encoder_stalled(id: i32, watchdog: Duration) -> ServiceError {
+    error!(target: Target::file_mail(), channel = id;
+        "Encoder takes no data for <yellow>{}</> seconds, restart channel",
+        watchdog.as_secs()
+    );
+
+    ServiceError::ServiceUnavailable("Encoder is stalled".to_string())
+}
+
+/// Decoder command for the clip input `cmd` with its filter, the decoder writes to stdout.
+/// With `realtime` the input is read in real time.
+pub fn decoder_cmd(
+    config: &PlayoutConfig,
+    log_format: &str,
+    mut cmd: Vec<String>,
+    filter: Option<Filters>,
+    realtime: bool,
+) -> Vec<String> {
+    let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
+
+    if let Some(decoder_input_cmd) = &config.advanced.decoder.input_cmd {
+        dec_cmd.append(&mut decoder_input_cmd.clone());
+    }
+
+    if realtime {
+        dec_cmd.push("-re".to_string());
+    }
+
+    dec_cmd.append(&mut cmd);
+
+    if let Some(mut filter) = filter {
+        dec_cmd.append(&mut filter.cmd());
+        dec_cmd.append(&mut filter.map());
+    }
+
+    if config.processing.vtt_enable && dec_cmd.iter().any(|s| s.ends_with(".vtt")) {
+        let i = dec_cmd
+            .iter()
+            .filter(|&n| n == "-i")
+            .count()
+            .saturating_sub(1);
+
+        dec_cmd.append(&mut vec_strings!("-map", format!("{i}:s"), "-c:s", "copy"));
+    }
+
+    if let Some(cmd) = &config.processing.cmd {
+        dec_cmd.extend_from_slice(cmd);
+    }
+
+    dec_cmd
+}
+
+/// Program and command from the encoder, in HLS mode the decoder writes the output and there
+/// is no encoder.
+pub async fn encoder_cmd(
+    config: &PlayoutConfig,
+    log_format: &str,
+) -> Option<(&'static str, Vec<String>)> {
+    match config.output.mode {
+        Desktop => Some(("ffplay", desktop::output_cmd(config, log_format).await)),
+        Null => Some(("ffmpeg", null::output_cmd(config, log_format).await)),
+        Stream => Some(("ffmpeg", stream::output_cmd(config, log_format).await)),
+        HLS => None,
+    }
+}
+
 /// Player
 ///
 /// Here we create the input file loop, from playlist, or folder source.
 /// Then we read the stdout from the reader ffmpeg instance
-/// and write it to the stdin from the streamer ffmpeg instance.
+/// and write it to the stdin from the streamer ffmpeg instance,
+/// on Linux the pipes are spliced without copying the data.
+/// Renditions get the same data over a broadcast buffer, for their own encoders.
+/// With a delay, the data goes over the delay buffer to the encoders.
+/// The last minutes are recorded for instant replays, which cut into the output.
 /// If it is configured we also fire up a ffmpeg ingest server instance,
 /// for getting live feeds.
 /// When a live ingest arrive, it stops the current playing and switch to the live source.
 /// When ingest stops, it switch back to playlist/folder mode.
+/// With a persistent decoder, clips are fed to the running decoder,
+/// as long as they can be read by the concat demuxer.
+/// A watchdog restarts the decoder, or the whole channel when the encoder hangs,
+/// if no data moved between them for the configured seconds.
+/// When a stream entry ends before its time, filler plays the rest.
 pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
     let config = manager.config.lock().await.clone();
     let id = config.general.channel_id;
@@ -48,6 +376,8 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
     let ingest_is_alive = manager.ingest_is_alive.clone();
     let mut buffer = vec![0u8; 64 * 1024];
     let mut live_on = false;
+    // cues from the live ingest
+    let mut cue_scanner = config.ingest.scte35.then(TsScanner::new);
 
     // get source iterator
     let mut node_sources = source_generator(manager.clone()).await;
@@ -60,15 +390,34 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
         _ => panic!("Output mode doesn't exists!"),
     };
 
-    let mut enc_writer = BufWriter::new(enc_proc.stdin.take().unwrap());
+    *manager.encoder_stats.lock().await = EncoderStats::default();
+    output_connected(&manager).await;
+
+    let enc_writer = BufWriter::new(enc_proc.stdin.take().unwrap());
     let enc_err = BufReader::new(enc_proc.stderr.take().unwrap());
 
     *manager.encoder.lock().await = Some(enc_proc);
+    supervisor::started(&manager, Encoder).await;
     let enc_p_ctl = manager.clone();
 
     // spawn a task to log ffmpeg output error messages
     let error_encoder_task = tokio::spawn(stderr_reader(enc_err, ignore_enc, Encoder, enc_p_ctl));
 
+    // encoders for the renditions, they get the same data as the main encoder
+    let ladder = Ladder::start(&config, &ff_log_format, &manager.outputs);
+
+    let mut feed = if config.output.delay > 0.0 {
+        Feed::Delayed(Delay::start(&manager, &config, enc_writer, ladder).await?)
+    } else {
+        Feed::Direct {
+            writer: enc_writer,
+            ladder,
+        }
+    };
+
+    // rolling recording for instant replays
+    let recorder = Recorder::start(&manager, &config).await;
+
     let channel_mgr_2 = manager.clone();
 
     // spawn a task for ffmpeg ingest server and create a channel for package sending
@@ -78,9 +427,102 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
         None
     };
 
-    while let Some(node) = node_sources.next().await {
-        *manager.current_media.lock().await = Some(node.clone());
-        let ignore_dec = config.logging.ignore_lines.clone();
+    let mut chain: Option<ConcatChain> = None;
+    let mut decoder: Option<DecoderPipe> = None;
+    // clip which waits until the decoder from the chain is finished
+    let mut pending: Option<Media> = None;
+    // stream entry on the decoder, with its start, and the filler when it ended too early
+    let mut stream: Option<(Media, Instant)> = None;
+    let mut fallback: Option<Media> = None;
+
+    'sources: loop {
+        if manager.draining.load(Ordering::SeqCst) {
+            // the persistent decoder gets no next clip, the rest of the current one goes out
+            if let Some(running) = chain.take() {
+                let watchdog =
+                    Duration::from_secs(manager.config.lock().await.general.watchdog.max(0) as u64);
+
+                running.close().await;
+
+                if let Some(dec) = decoder.as_mut() {
+                    while let Moved::Data = dec
+                        .pass(
+                            &mut feed,
+                            &mut buffer,
+                            watchdog,
+                            &ingest_is_alive,
+                            recorder.as_ref(),
+                        )
+                        .await?
+                    {}
+                }
+            }
+
+            if let Some(dec) = decoder.take() {
+                drop(dec.stdout);
+
+                manager.wait(Decoder).await?;
+                dec.log_task.await??;
+            }
+
+            info!(target: Target::file_mail(), channel = id; "Clip is finished, stop playout");
+
+            is_alive.store(false, Ordering::SeqCst);
+
+            if let Err(e) = manager.stop(Ingest).await {
+                debug!(target: Target::file_mail(), channel = id; "{e}");
+            }
+
+            break;
+        }
+
+        let (node, fresh) = if manager.emergency.load(Ordering::SeqCst) {
+            pending = None;
+            fallback = None;
+
+            (emergency_node(&manager).await, true)
+        } else {
+            match manager.freeze.lock().await.take() {
+                Some(node) => {
+                    // after the hold, the playlist starts again on the current time
+                    pending = None;
+                    fallback = None;
+                    playlist_init.store(true, Ordering::SeqCst);
+
+                    (node, true)
+                }
+                None => match program_node(&manager).await {
+                    Some(node) => {
+                        pending = None;
+                        fallback = None;
+
+                        (node, true)
+                    }
+                    None => match fallback.take() {
+                        Some(node) => (node, true),
+                        None => match pending.take() {
+                            Some(node) => (node, false),
+                            None => match node_sources.next().await {
+                                Some(node) => (program_return(&manager, node).await, true),
+                                None => break,
+                            },
+                        },
+                    },
+                },
+            }
+        };
+
+        if fresh {
+            *manager.current_media.lock().await = Some(node.clone());
+        }
+
+        manager.on_air().await;
+
+        // get config changes which can be applied without restarting the encoder
+        let config = manager.config.lock().await.clone();
+        let ff_log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+        let mut ignore_dec = config.logging.ignore_lines.clone();
+        let watchdog = Duration::from_secs(config.general.watchdog.max(0) as u64);
 
         if !is_alive.load(Ordering::SeqCst) {
             debug!(target: Target::file_mail(), channel = id; "Playout is stopped, break out from source loop");
@@ -89,8 +531,8 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
 
         trace!("Decoder CMD: {:?}", node.cmd);
 
-        let mut cmd = match node.cmd {
-            Some(cmd) => cmd,
+        let mut cmd = match &node.cmd {
+            Some(cmd) => cmd.clone(),
             None => break,
         };
 
@@ -101,93 +543,160 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
             continue;
         }
 
-        let c_index = if cfg!(debug_assertions) {
-            format!(
-                " ({}/{})",
-                node.index.unwrap() + 1,
-                manager.current_list.lock().await.len()
-            )
-        } else {
-            String::new()
-        };
-
-        info!(target: Target::file_mail(), channel = id;
-            "Play for <yellow>{}</>{c_index}: <b><magenta>{}  {}</></b>",
-            sec_to_time(node.out - node.seek),
-            node.source,
-            node.audio
-        );
-
-        if config.task.enable {
-            if config.task.path.is_file() {
-                let channel_mgr_3 = manager.clone();
-
-                tokio::spawn(task_runner::run(channel_mgr_3));
+        if fresh {
+            let c_index = if cfg!(debug_assertions) {
+                format!(
+                    " ({}/{})",
+                    node.index.unwrap() + 1,
+                    manager.current_list.lock().await.len()
+                )
             } else {
-                error!(target: Target::file_mail(), channel = id;
-                    "<bright-blue>{:?}</> executable not exists!",
-                    config.task.path
-                );
+                String::new()
+            };
+
+            info!(target: Target::file_mail(), channel = id;
+                "Play for <yellow>{}</>{c_index}: <b><magenta>{}  {}</></b>",
+                sec_to_time(node.out - node.seek),
+                node.source,
+                node.audio
+            );
+
+            if let Some(pool) = manager.db_pool.clone() {
+                tokio::spawn(play_history::record(
+                    pool,
+                    id,
+                    config.channel.timezone,
+                    node.clone(),
+                ));
             }
-        }
 
-        let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
-
-        if let Some(decoder_input_cmd) = &config.advanced.decoder.input_cmd {
-            dec_cmd.append(&mut decoder_input_cmd.clone());
-        }
-
-        dec_cmd.append(&mut cmd);
-
-        if let Some(mut filter) = node.filter {
-            dec_cmd.append(&mut filter.cmd());
-            dec_cmd.append(&mut filter.map());
+            tokio::spawn(resume::record(manager.clone(), node.clone()));
+            tokio::spawn(vast::track(manager.clone(), node.clone()));
+            plugins::event(&manager, "clip_started", plugins::clip_json(&node));
+            sse::clip_started(&manager, &node).await;
+
+            if config.task.enable {
+                if config.task.path.is_file() {
+                    let channel_mgr_3 = manager.clone();
+
+                    tokio::spawn(task_runner::run(channel_mgr_3));
+                } else {
+                    error!(target: Target::file_mail(), channel = id;
+                        "<bright-blue>{:?}</> executable not exists!",
+                        config.task.path
+                    );
+                }
+            }
         }
 
-        if config.processing.vtt_enable && dec_cmd.iter().any(|s| s.ends_with(".vtt")) {
-            let i = dec_cmd
-                .iter()
-                .filter(|&n| n == "-i")
-                .count()
-                .saturating_sub(1);
+        let chain_next = config.processing.persistent_decoder && chainable(&node, &config);
 
-            dec_cmd.append(&mut vec_strings!("-map", format!("{i}:s"), "-c:s", "copy"));
-        }
+        if let Some(mut running) = chain.take() {
+            if chain_next && running.clips < MAX_CHAIN_CLIPS {
+                match running.feed(&node) {
+                    Ok(()) => chain = Some(running),
+                    Err(e) => {
+                        error!(target: Target::file_mail(), channel = id; "Send clip to decoder failed: {e}");
 
-        if let Some(cmd) = &config.processing.cmd {
-            dec_cmd.extend_from_slice(cmd);
+                        running.close().await;
+                        pending = Some(node.clone());
+                    }
+                }
+            } else {
+                // the decoder finishes the last clip, then this clip gets its own decoder
+                running.close().await;
+                pending = Some(node.clone());
+            }
         }
 
-        debug!(target: Target::file_mail(), channel = id;
-            "Decoder CMD: <bright-blue>ffmpeg {}</>",
-            fmt_cmd(&dec_cmd)
-        );
-
-        // create ffmpeg decoder instance, for reading the input files
-        let mut dec_proc = Command::new("ffmpeg")
-            .args(dec_cmd)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
-
-        let mut decoder_stdout = dec_proc.stdout.take().unwrap();
-        let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
-
-        *manager.clone().decoder.lock().await = Some(dec_proc);
-        let channel_mgr_c = manager.clone();
+        if decoder.is_none() {
+            let mut filter = node.filter.clone();
+
+            if chain_next {
+                match ConcatChain::start(id, &node).await {
+                    Ok((running, list)) => {
+                        cmd = vec_strings![
+                            "-f",
+                            "concat",
+                            "-safe",
+                            "0",
+                            "-i",
+                            list.to_string_lossy()
+                        ];
+                        filter = Some(concat_chains(&config));
+
+                        // the empty list, which ends the chain, is reported as demuxing error
+                        ignore_dec.push(running.dir.to_string_lossy().to_string());
+                        ignore_dec.push("Error during demuxing".to_string());
+
+                        chain = Some(running);
+                    }
+                    Err(e) => {
+                        error!(target: Target::file_mail(), channel = id; "Start persistent decoder failed: {e}");
+                    }
+                }
+            }
 
-        let error_decoder_task =
-            tokio::spawn(stderr_reader(dec_err, ignore_dec, Decoder, channel_mgr_c));
+            // without the delay buffer, the encoder holds the decoder in real time
+            let dec_cmd = decoder_cmd(&config, &ff_log_format, cmd, filter, feed.is_delayed());
+
+            debug!(target: Target::file_mail(), channel = id;
+                "Decoder CMD: <bright-blue>ffmpeg {}</>",
+                fmt_cmd(&dec_cmd)
+            );
+
+            // create ffmpeg decoder instance, for reading the input files
+            let mut command = Command::new("ffmpeg");
+            command
+                .args(dec_cmd)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            set_priority(&mut command, &config.advanced.process);
+
+            let mut dec_proc = command.spawn()?;
+
+            let decoder_stdout = dec_proc.stdout.take().unwrap();
+            let dec_err = BufReader::new(dec_proc.stderr.take().unwrap());
+
+            *manager.clone().decoder.lock().await = Some(dec_proc);
+            supervisor::started(&manager, Decoder).await;
+            let channel_mgr_c = manager.clone();
+
+            let error_decoder_task =
+                tokio::spawn(stderr_reader(dec_err, ignore_dec, Decoder, channel_mgr_c));
+
+            decoder = Some(DecoderPipe {
+                stdout: decoder_stdout,
+                log_task: error_decoder_task,
+                #[cfg(target_os = "linux")]
+                splice: None,
+                filled: 0,
+                written: 0,
+                handed: true,
+            });
+
+            stream = (config.playlist.stream_fallback > 0.0 && is_remote(&node.source))
+                .then(|| (node.clone(), Instant::now()));
+        }
 
         loop {
+            if let Some(recorder) = &recorder {
+                recorder.play(&mut feed, watchdog).await?;
+            }
+
             if ingest_is_alive.load(Ordering::SeqCst) {
                 // read from ingest server instance
                 if !live_on {
                     info!(target: Target::file_mail(), channel = id; "Switch from {} to live ingest", config.processing.mode);
+                    event_history::push(id, EventKind::Ingest, "Live ingest connected");
 
                     manager.stop(Decoder).await?;
                     live_on = true;
                     playlist_init.store(true, Ordering::SeqCst);
+
+                    if cue_scanner.is_some() {
+                        cue_scanner = Some(TsScanner::new());
+                    }
                 }
 
                 let mut ingest_stdout_guard = manager.ingest_stdout.lock().await;
@@ -198,31 +707,114 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
                         break;
                     }
 
-                    enc_writer.write_all(&buffer[..num]).await?;
+                    if let Some(recorder) = &recorder {
+                        recorder.record(&buffer[..num]);
+                    }
+
+                    if let Some(scanner) = cue_scanner.as_mut() {
+                        for cue in scanner.push(&buffer[..num]) {
+                            scte35::report(id, &cue);
+                        }
+                    }
+
+                    if !feed.write_all(&buffer[..num], watchdog).await? {
+                        return Err(encoder_stalled(id, watchdog));
+                    }
                 }
             } else {
                 // read from decoder instance
                 if live_on {
                     info!(target: Target::file_mail(), channel = id; "Switch from live ingest to {}", config.processing.mode);
+                    event_history::push(id, EventKind::Ingest, "Live ingest disconnected");
 
                     live_on = false;
                     break;
                 }
 
-                let num = decoder_stdout.read(&mut buffer[..]).await?;
-
-                if num == 0 {
+                let Some(dec) = decoder.as_mut() else {
                     break;
+                };
+
+                tokio::select! {
+                    moved = dec.pass(&mut feed, &mut buffer, watchdog, &ingest_is_alive, recorder.as_ref()) => {
+                        match moved? {
+                            Moved::Data | Moved::Ingest => {}
+                            Moved::End => break,
+                            Moved::DecoderStalled => {
+                                error!(target: Target::file_mail(), channel = id;
+                                    "Decoder sends no data for <yellow>{}</> seconds, restart it",
+                                    watchdog.as_secs()
+                                );
+
+                                manager.stop(Decoder).await?;
+                                break;
+                            }
+                            Moved::EncoderStalled => return Err(encoder_stalled(id, watchdog)),
+                        }
+                    }
+                    result = async {
+                        match chain.as_mut() {
+                            Some(running) => running.requested().await,
+                            None => std::future::pending::<io::Result<()>>().await,
+                        }
+                    } => {
+                        match result {
+                            // the decoder is through the clip and waits for the next one
+                            Ok(()) => continue 'sources,
+                            Err(e) => {
+                                error!(target: Target::file_mail(), channel = id; "Persistent decoder link failed: {e}");
+
+                                if let Some(running) = chain.take() {
+                                    running.close().await;
+                                }
+                            }
+                        }
+                    }
                 }
-
-                enc_writer.write_all(&buffer[..num]).await?;
             }
         }
 
-        drop(decoder_stdout);
+        if let Some(running) = chain.take() {
+            running.close().await;
+        }
 
-        manager.wait(Decoder).await?;
-        error_decoder_task.await??;
+        if let Some(dec) = decoder.take() {
+            drop(dec.stdout);
+
+            manager.wait(Decoder).await?;
+            dec.log_task.await??;
+
+            // a killed decoder has no exit code, then the clip was stopped on purpose
+            let exited = manager
+                .decoder
+                .lock()
+                .await
+                .as_mut()
+                .and_then(|p| p.try_wait().ok().flatten())
+                .is_some_and(|s| s.code().is_some());
+
+            if !exited {
+                supervisor::stopped(&manager, Decoder).await;
+            } else if !supervisor::respawn(&manager, Decoder).await {
+                return Err(ServiceError::ServiceUnavailable(
+                    "Decoder failed too often in a row".to_string(),
+                ));
+            }
+
+            if let Some((node, started)) = stream.take().filter(|_| exited) {
+                let length = (node.out - node.seek) / node.speed.unwrap_or(1.0);
+                let remaining = length - started.elapsed().as_secs_f64();
+
+                if remaining > 1.0 && is_alive.load(Ordering::SeqCst) {
+                    warn!(target: Target::file_mail(), channel = id;
+                        "Stream <b><magenta>{}</></b> ended <yellow>{remaining:.2}</> seconds too early, play filler",
+                        node.source
+                    );
+
+                    fallback = Some(stream_fallback(&config, &node, &manager, remaining).await);
+                }
+            }
+        }
     }
 
     trace!("Out of source loop");
@@ -233,7 +825,23 @@ pub async fn player(manager: ChannelManager) -> Result<(), ServiceError> {
         ingest.await??;
     }
 
+    if let Some(recorder) = recorder {
+        recorder.stop().await;
+    }
+
+    feed.stop().await;
+
+    if manager.draining.load(Ordering::SeqCst) {
+        // with the closed input, the encoder finishes
+        manager.wait(Encoder).await?;
+    }
+
     manager.stop_all(false).await?;
+
+    if let Some(running) = chain.take() {
+        running.close().await;
+    }
+
     error_encoder_task.await??;
 
     Ok(())
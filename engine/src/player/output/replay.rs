@@ -0,0 +1,463 @@
+/// Instant Replay
+///
+/// Record the last `output.replay` seconds of the program feed, in segment files of one second.
+/// A replay plays a window from this recording, optionally slowed down, then the output goes back
+/// to live. A live ingest is dropped while the replay is on air, a decoder from the playlist waits.
+use std::{
+    collections::VecDeque,
+    ops::RangeInclusive,
+    path::PathBuf,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdout, Command},
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use super::{encoder_stalled, Feed};
+use crate::player::{
+    controller::{ChannelManager, ProcessUnit::*},
+    utils::stderr_reader,
+};
+use crate::utils::{
+    config::PlayoutConfig,
+    errors::ServiceError,
+    event_history::{self, EventKind},
+    logging::{fmt_cmd, Target},
+};
+use crate::vec_strings;
+
+const SEGMENT_TIME: Duration = Duration::from_secs(1);
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// Window from the recording, `from` is the start in seconds before now.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplayRequest {
+    pub from: f64,
+    pub duration: f64,
+    /// Playback speed, from 0.1 to 1.0, slow motion is without sound.
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayInfo {
+    /// Real start of the window in seconds before now, on the segment before the requested start.
+    pub from: f64,
+    /// Seconds on air.
+    pub duration: f64,
+}
+
+struct Segment {
+    start: Instant,
+    path: PathBuf,
+}
+
+/// Segments for the window, `ages` are the seconds since the segment starts, the oldest first.
+pub fn replay_window(ages: &[f64], from: f64, duration: f64) -> Option<RangeInclusive<usize>> {
+    let first = ages.iter().rposition(|age| *age >= from)?;
+    let last = ages.iter().rposition(|age| *age > from - duration)?;
+
+    Some(first..=last.max(first))
+}
+
+/// Decoder for the replay, with the same output format as the clips.
+pub fn replay_cmd(
+    config: &PlayoutConfig,
+    inputs: &[PathBuf],
+    duration: f64,
+    speed: f64,
+    log_format: &str,
+) -> Vec<String> {
+    let input = inputs
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("|");
+    let mut audio_filter = "asetpts=N/SR/TB".to_string();
+
+    if speed < 1.0 {
+        audio_filter.push_str(",volume=0");
+    }
+
+    // the input runs with the speed, so the output comes in real time
+    let mut cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        log_format,
+        "-readrate",
+        speed,
+        "-i",
+        format!("concat:{input}"),
+        "-map",
+        "0:v:0",
+        "-map",
+        "0:a?",
+        "-vf",
+        format!("setpts=N/({}*{speed})/TB", config.processing.fps),
+        "-af",
+        format!("{audio_filter},apad"),
+        "-t",
+        format!("{:.3}", duration / speed)
+    ];
+
+    if let Some(processing_cmd) = &config.processing.cmd {
+        cmd.extend_from_slice(processing_cmd);
+    }
+
+    cmd
+}
+
+/// Handle for the API, it exists as long as the recorder runs.
+#[derive(Clone, Default)]
+pub struct ReplayControl {
+    segments: Arc<Mutex<VecDeque<Segment>>>,
+    pending: Arc<Mutex<Option<ReplayProc>>>,
+    active: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for ReplayControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ReplayControl")
+            .field("active", &self.active)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReplayControl {
+    /// Segment from the recording, `skip` counts back from the newest one.
+    pub async fn last_segment(&self, skip: usize) -> Option<PathBuf> {
+        let segments = self.segments.lock().await;
+
+        segments
+            .len()
+            .checked_sub(skip + 1)
+            .and_then(|i| segments.get(i))
+            .map(|s| s.path.clone())
+    }
+
+    /// Start the decoder for the window, the player puts it on air with the next chunk.
+    pub async fn start(
+        &self,
+        manager: &ChannelManager,
+        request: &ReplayRequest,
+    ) -> Result<ReplayInfo, ServiceError> {
+        let config = manager.config.lock().await.clone();
+        let id = config.general.channel_id;
+
+        if config.processing.audio_only
+            || config.processing.copy_video
+            || config.processing.copy_audio
+        {
+            return Err(ServiceError::BadRequest(
+                "Replay needs decoded video and audio".to_string(),
+            ));
+        }
+
+        if request.duration <= 0.0 || request.duration > request.from {
+            return Err(ServiceError::BadRequest(
+                "Duration must be greater than 0 and end before now".to_string(),
+            ));
+        }
+
+        if !(0.1..=1.0).contains(&request.speed) {
+            return Err(ServiceError::BadRequest(
+                "Speed must be between 0.1 and 1.0".to_string(),
+            ));
+        }
+
+        if self.active.swap(true, Ordering::SeqCst) {
+            return Err(ServiceError::Conflict(
+                "Replay is already on air".to_string(),
+            ));
+        }
+
+        let (inputs, from) = {
+            let segments = self.segments.lock().await;
+            let ages = segments
+                .iter()
+                .map(|s| s.start.elapsed().as_secs_f64())
+                .collect::<Vec<f64>>();
+
+            match replay_window(&ages, request.from, request.duration) {
+                Some(range) => (
+                    segments
+                        .range(range.clone())
+                        .map(|s| s.path.clone())
+                        .collect::<Vec<PathBuf>>(),
+                    ages[*range.start()],
+                ),
+                None => {
+                    self.active.store(false, Ordering::SeqCst);
+
+                    return Err(ServiceError::BadRequest(format!(
+                        "Window is not in the recording, which has {:.0} seconds",
+                        ages.first().copied().unwrap_or_default()
+                    )));
+                }
+            }
+        };
+
+        let duration = request.duration + from - request.from;
+        let log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+        let cmd = replay_cmd(&config, &inputs, duration, request.speed, &log_format);
+
+        debug!(target: Target::file_mail(), channel = id;
+            "Replay CMD: <bright-blue>ffmpeg {}</>",
+            fmt_cmd(&cmd)
+        );
+
+        let mut proc = match Command::new("ffmpeg")
+            .args(cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(proc) => proc,
+            Err(e) => {
+                self.active.store(false, Ordering::SeqCst);
+
+                return Err(e.into());
+            }
+        };
+
+        let stdout = proc.stdout.take().unwrap();
+        let log_task = tokio::spawn(stderr_reader(
+            BufReader::new(proc.stderr.take().unwrap()),
+            config.logging.ignore_lines.clone(),
+            Decoder,
+            manager.clone(),
+        ));
+
+        *self.pending.lock().await = Some(ReplayProc {
+            proc,
+            stdout,
+            log_task,
+        });
+
+        let info = ReplayInfo {
+            from,
+            duration: duration / request.speed,
+        };
+
+        info!(target: Target::file_mail(), channel = id;
+            "Replay <yellow>{:.1}</> seconds from <yellow>{from:.1}</> seconds ago, with speed <yellow>{}</>",
+            duration,
+            request.speed
+        );
+        event_history::push(
+            id,
+            EventKind::Output,
+            format!("Replay {duration:.1} seconds from {from:.1} seconds ago"),
+        );
+
+        Ok(info)
+    }
+}
+
+struct ReplayProc {
+    proc: Child,
+    stdout: ChildStdout,
+    log_task: JoinHandle<Result<(), ServiceError>>,
+}
+
+/// Record the feed into segment files and remove the old ones.
+async fn record(
+    id: i32,
+    dir: PathBuf,
+    keep: Duration,
+    segments: Arc<Mutex<VecDeque<Segment>>>,
+    mut receiver: mpsc::UnboundedReceiver<Vec<u8>>,
+) {
+    let mut count = 0;
+    let mut writer: Option<(File, Instant)> = None;
+
+    while let Some(data) = receiver.recv().await {
+        let rotate = match &writer {
+            Some((_, start)) => start.elapsed() >= SEGMENT_TIME,
+            None => true,
+        };
+
+        if rotate {
+            count += 1;
+            let path = dir.join(format!("{count}.ts"));
+            let start = Instant::now();
+
+            match File::create(&path).await {
+                Ok(file) => writer = Some((file, start)),
+                Err(e) => {
+                    error!(target: Target::file_mail(), channel = id; "Create replay segment failed: {e}");
+                    writer = None;
+                    continue;
+                }
+            }
+
+            let mut segments = segments.lock().await;
+            segments.push_back(Segment { start, path });
+
+            // keep one more segment, for the start of the oldest window
+            while segments.get(1).is_some_and(|s| s.start.elapsed() > keep) {
+                if let Some(old) = segments.pop_front() {
+                    let _ = fs::remove_file(old.path).await;
+                }
+            }
+        }
+
+        if let Some((file, _)) = writer.as_mut() {
+            if let Err(e) = file.write_all(&data).await {
+                error!(target: Target::file_mail(), channel = id; "Write replay segment failed: {e}");
+                writer = None;
+            } else {
+                let _ = file.flush().await;
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dir).await;
+}
+
+pub struct Recorder {
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+    control: ReplayControl,
+    task: JoinHandle<()>,
+    manager: ChannelManager,
+}
+
+impl Recorder {
+    /// Start recording, `None` when the replay is disabled.
+    pub async fn start(manager: &ChannelManager, config: &PlayoutConfig) -> Option<Self> {
+        let id = config.general.channel_id;
+
+        if config.output.replay <= 0.0 {
+            return None;
+        }
+
+        let root = if config.output.replay_path.is_empty() {
+            std::env::temp_dir()
+        } else {
+            PathBuf::from(&config.output.replay_path)
+        };
+        let dir = root.join(format!("ffplayout_replay_{id}"));
+
+        if dir.is_dir() {
+            let _ = fs::remove_dir_all(&dir).await;
+        }
+
+        if let Err(e) = fs::create_dir_all(&dir).await {
+            error!(target: Target::file_mail(), channel = id; "Create replay folder failed: {e}");
+            return None;
+        }
+
+        let control = ReplayControl::default();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let task = tokio::spawn(record(
+            id,
+            dir,
+            Duration::from_secs_f64(config.output.replay),
+            control.segments.clone(),
+            receiver,
+        ));
+
+        *manager.replay.lock().await = Some(control.clone());
+
+        Some(Self {
+            sender,
+            control,
+            task,
+            manager: manager.clone(),
+        })
+    }
+
+    pub fn record(&self, data: &[u8]) {
+        let _ = self.sender.send(data.to_vec());
+    }
+
+    /// Replay which waits for the player.
+    async fn take_pending(&self) -> Option<ReplayProc> {
+        self.control.pending.lock().await.take()
+    }
+
+    /// Put a waiting replay on air, returns when it is over.
+    pub(super) async fn play(
+        &self,
+        feed: &mut Feed,
+        watchdog: Duration,
+    ) -> Result<(), ServiceError> {
+        let Some(mut replay) = self.take_pending().await else {
+            return Ok(());
+        };
+
+        let id = self.manager.channel.lock().await.id;
+        let ingest_is_alive = self.manager.ingest_is_alive.clone();
+        let mut chunk = vec![0u8; 64 * 1024];
+        let mut live = vec![0u8; 64 * 1024];
+        let mut ingest_open = true;
+
+        let result = loop {
+            tokio::select! {
+                num = replay.stdout.read(&mut chunk) => {
+                    match num {
+                        Ok(0) => break Ok(()),
+                        Ok(num) => match feed.write_all(&chunk[..num], watchdog).await {
+                            Ok(true) => {}
+                            Ok(false) => break Err(encoder_stalled(id, watchdog)),
+                            Err(e) => break Err(e),
+                        },
+                        Err(e) => break Err(e.into()),
+                    }
+                }
+                // live ingest goes on, it is only recorded
+                num = async {
+                    let mut guard = self.manager.ingest_stdout.lock().await;
+
+                    match guard.as_mut() {
+                        Some(stdout) => stdout.read(&mut live).await,
+                        None => std::future::pending().await,
+                    }
+                }, if ingest_open && ingest_is_alive.load(Ordering::SeqCst) => {
+                    match num {
+                        Ok(num) if num > 0 => self.record(&live[..num]),
+                        _ => ingest_open = false,
+                    }
+                }
+            }
+        };
+
+        drop(replay.stdout);
+        let _ = replay.proc.wait().await;
+        let _ = replay.log_task.await;
+
+        self.control.active.store(false, Ordering::SeqCst);
+
+        info!(target: Target::file_mail(), channel = id; "Replay is over, back to live");
+
+        result
+    }
+
+    pub async fn stop(self) {
+        *self.manager.replay.lock().await = None;
+
+        if let Some(mut replay) = self.take_pending().await {
+            let _ = replay.proc.kill().await;
+        }
+
+        drop(self.sender);
+        let _ = self.task.await;
+    }
+}
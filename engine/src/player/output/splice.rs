@@ -0,0 +1,227 @@
+/// Splice
+///
+/// Move the data from the decoder to the encoder pipe inside the kernel, without copying it
+/// through a buffer in the engine. Runs in its own thread, as long as the decoder sends data.
+/// The thread holds copies of both pipes, so it is stopped and joined when the splice is dropped.
+use std::{
+    io,
+    os::fd::{AsFd, AsRawFd, OwnedFd},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, splice, FcntlArg, SpliceFFlags},
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+};
+use tokio::sync::oneshot;
+
+use super::Moved;
+
+/// Bytes per splice call and size of the pipe buffers,
+/// the kernel limits the pipe size for normal users to 1 MiB by default.
+const SPLICE_SIZE: usize = 1024 * 1024;
+/// Milliseconds between checks for live ingest.
+const POLL_STEP: u16 = 100;
+
+/// Wait until the pipe is ready, `false` when the time is over.
+fn ready(fd: &OwnedFd, flags: PollFlags) -> io::Result<bool> {
+    let mut fds = [PollFd::new(fd.as_fd(), flags)];
+
+    match poll(&mut fds, PollTimeout::from(POLL_STEP)) {
+        Ok(0) | Err(Errno::EINTR) => Ok(false),
+        Ok(_) => Ok(fds[0].revents().is_some_and(|r| !r.is_empty())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Move data until the decoder ends, a live ingest starts, one side is stalled or `stop` is set.
+fn splice_pipe(
+    decoder: OwnedFd,
+    encoder: OwnedFd,
+    ingest: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    watchdog: Duration,
+) -> io::Result<Moved> {
+    // bigger pipes need less wake ups, when the size is not allowed the default stays
+    for fd in [&decoder, &encoder] {
+        let _ = fcntl(fd.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(SPLICE_SIZE as i32));
+    }
+
+    let mut moved = Instant::now();
+    let stalled = |since: Instant| !watchdog.is_zero() && since.elapsed() >= watchdog;
+
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            // nobody waits for the result anymore
+            return Ok(Moved::End);
+        }
+
+        if ingest.load(Ordering::SeqCst) {
+            return Ok(Moved::Ingest);
+        }
+
+        if !ready(&decoder, PollFlags::POLLIN)? {
+            if stalled(moved) {
+                return Ok(Moved::DecoderStalled);
+            }
+
+            continue;
+        }
+
+        if !ready(&encoder, PollFlags::POLLOUT)? {
+            if stalled(moved) {
+                return Ok(Moved::EncoderStalled);
+            }
+
+            continue;
+        }
+
+        match splice(
+            &decoder,
+            None,
+            &encoder,
+            None,
+            SPLICE_SIZE,
+            SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_NONBLOCK,
+        ) {
+            Ok(0) => return Ok(Moved::End),
+            Ok(_) => moved = Instant::now(),
+            Err(Errno::EAGAIN | Errno::EINTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Splice thread, with its result.
+pub struct Splice {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+    result: oneshot::Receiver<io::Result<Moved>>,
+}
+
+impl Splice {
+    /// Start moving the data from the decoder to the encoder pipe.
+    pub fn start(
+        decoder: OwnedFd,
+        encoder: OwnedFd,
+        ingest: Arc<AtomicBool>,
+        watchdog: Duration,
+    ) -> io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, result) = oneshot::channel();
+        let thread_stop = stop.clone();
+
+        let thread = thread::Builder::new()
+            .name("splice".to_string())
+            .spawn(move || {
+                let _ = tx.send(splice_pipe(decoder, encoder, ingest, thread_stop, watchdog));
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+            result,
+        })
+    }
+
+    /// Wait until the thread returns, can be cancelled and called again.
+    pub async fn wait(&mut self) -> io::Result<Moved> {
+        (&mut self.result)
+            .await
+            .map_err(|_| io::Error::other("Splice thread is gone"))?
+    }
+}
+
+impl Drop for Splice {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        // returns after the next poll step at the latest, then both pipe copies are closed
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use nix::unistd::pipe;
+
+    use super::*;
+
+    fn pipes() -> (File, OwnedFd, OwnedFd, File) {
+        let (dec_read, dec_write) = pipe().unwrap();
+        let (enc_read, enc_write) = pipe().unwrap();
+
+        (
+            File::from(dec_write),
+            dec_read,
+            enc_write,
+            File::from(enc_read),
+        )
+    }
+
+    #[tokio::test]
+    async fn splice_moves_data() {
+        let (mut decoder, dec_read, enc_write, mut encoder) = pipes();
+        let ingest = Arc::new(AtomicBool::new(false));
+        let mut splice = Splice::start(dec_read, enc_write, ingest, Duration::ZERO).unwrap();
+
+        decoder.write_all(b"decoded data").unwrap();
+        drop(decoder);
+
+        assert_eq!(splice.wait().await.unwrap(), Moved::End);
+
+        drop(splice);
+
+        let mut data = vec![];
+        io::Read::read_to_end(&mut encoder, &mut data).unwrap();
+
+        assert_eq!(data, b"decoded data");
+    }
+
+    #[tokio::test]
+    async fn splice_ingest_and_stall() {
+        let (_decoder, dec_read, enc_write, _encoder) = pipes();
+        let ingest = Arc::new(AtomicBool::new(false));
+        let mut splice = Splice::start(
+            dec_read,
+            enc_write,
+            ingest.clone(),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+
+        assert_eq!(splice.wait().await.unwrap(), Moved::DecoderStalled);
+
+        let (_decoder, dec_read, enc_write, _encoder) = pipes();
+        let mut splice =
+            Splice::start(dec_read, enc_write, ingest.clone(), Duration::ZERO).unwrap();
+        ingest.store(true, Ordering::SeqCst);
+
+        assert_eq!(splice.wait().await.unwrap(), Moved::Ingest);
+    }
+
+    #[test]
+    fn splice_drop_closes_encoder() {
+        let (_decoder, dec_read, enc_write, mut encoder) = pipes();
+        let ingest = Arc::new(AtomicBool::new(false));
+        let splice = Splice::start(dec_read, enc_write, ingest, Duration::ZERO).unwrap();
+
+        // the decoder is still open, only the stop ends the thread
+        drop(splice);
+
+        let mut data = vec![];
+        io::Read::read_to_end(&mut encoder, &mut data).unwrap();
+
+        assert!(data.is_empty());
+    }
+}
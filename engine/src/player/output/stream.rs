@@ -6,6 +6,7 @@ use tokio::process::{Child, Command};
 use crate::utils::{
     config::PlayoutConfig,
     logging::{fmt_cmd, Target},
+    priority::set_priority,
 };
 use crate::vec_strings;
 use crate::{
@@ -21,7 +22,35 @@ use crate::{
 /// Prepare the ffmpeg command for streaming output
 pub async fn output(config: &PlayoutConfig, log_format: &str) -> Result<Child, ServiceError> {
     let id = config.general.channel_id;
-    let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", log_format];
+    let enc_cmd = output_cmd(config, log_format).await;
+
+    debug!(target: Target::file_mail(), channel = id;
+        "Encoder CMD: <bright-blue>ffmpeg {}</>",
+        fmt_cmd(&enc_cmd)
+    );
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(enc_cmd)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped());
+    set_priority(&mut command, &config.advanced.process);
+
+    let child = command.spawn()?;
+
+    Ok(child)
+}
+
+/// Encoder command, which reads the decoded clips from stdin.
+pub async fn output_cmd(config: &PlayoutConfig, log_format: &str) -> Vec<String> {
+    let mut enc_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-progress",
+        "pipe:2",
+        "-v",
+        log_format
+    ];
     let mut media = Media {
         unit: Encoder,
         ..Default::default()
@@ -34,18 +63,5 @@ pub async fn output(config: &PlayoutConfig, log_format: &str) -> Result<Child, S
 
     enc_prefix.append(&mut vec_strings!["-re", "-i", "pipe:0"]);
 
-    let enc_cmd = prepare_output_cmd(config, enc_prefix, &media.filter);
-
-    debug!(target: Target::file_mail(), channel = id;
-        "Encoder CMD: <bright-blue>ffmpeg {}</>",
-        fmt_cmd(&enc_cmd)
-    );
-
-    let child = Command::new("ffmpeg")
-        .args(enc_cmd)
-        .stdin(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    Ok(child)
+    prepare_output_cmd(config, enc_prefix, &media.filter)
 }
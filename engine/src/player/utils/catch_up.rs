@@ -0,0 +1,51 @@
+/// Catch Up
+///
+/// When the playout is behind the schedule, after a long live ingest or an outage, the
+/// `playlist.catch_up` strategy decides how it gets back in sync: restart or skip to the current
+/// clip, when it is more than the stop threshold behind, or trim or speed up the next clips,
+/// by at most `catch_up_rate` percent each, until the delay is gone.
+use crate::player::utils::Media;
+use crate::utils::config::{CatchUp, PlayoutConfig};
+
+/// Shorter clips are played like planned.
+const MIN_LENGTH: f64 = 2.0;
+
+/// Smaller delays are not worth a change.
+pub const MIN_LAG: f64 = 0.5;
+
+/// Trim or speed up the node, `lag` are the seconds behind the schedule.
+/// Returns the seconds the clip ends earlier.
+pub fn catch_up(node: &mut Media, config: &PlayoutConfig, lag: f64) -> f64 {
+    let length = node.out - node.seek;
+    let rate = config.playlist.catch_up_rate.clamp(0.0, 20.0) / 100.0;
+
+    if lag < MIN_LAG || length < MIN_LENGTH || rate <= 0.0 {
+        return 0.0;
+    }
+
+    // without decoding the speed can't change
+    let speed = config.playlist.catch_up == CatchUp::Speed
+        && !config.processing.copy_audio
+        && !config.processing.copy_video;
+
+    match config.playlist.catch_up {
+        CatchUp::Trim | CatchUp::Speed if !speed => {
+            let cut = lag.min(length * rate);
+            node.out -= cut;
+
+            cut
+        }
+        CatchUp::Speed => {
+            let factor = if lag < length {
+                (length / (length - lag)).min(1.0 + rate)
+            } else {
+                1.0 + rate
+            };
+
+            node.speed = Some(factor);
+
+            length - length / factor
+        }
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,121 @@
+/// Channel State
+///
+/// One state for the whole channel, instead of the single flags from the processes. The state
+/// only changes on valid transitions, a stopping channel doesn't go on air again, and operator
+/// commands are checked against it, before they run. Every change goes to the event history.
+use std::fmt;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::utils::errors::ServiceError;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelState {
+    #[default]
+    Stopped,
+    Starting,
+    OnAirPlaylist,
+    OnAirLive,
+    /// Filler, slate, freeze frame or emergency slate instead of the program.
+    Fallback,
+    Stopping,
+    /// Stopped by the restart or supervision policy.
+    Failed,
+}
+
+use ChannelState::*;
+
+impl fmt::Display for ChannelState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Stopped => write!(f, "stopped"),
+            Self::Starting => write!(f, "starting"),
+            Self::OnAirPlaylist => write!(f, "on_air_playlist"),
+            Self::OnAirLive => write!(f, "on_air_live"),
+            Self::Fallback => write!(f, "fallback"),
+            Self::Stopping => write!(f, "stopping"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Operator commands, which need a matching state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelAction {
+    Start,
+    Stop,
+    Restart,
+    /// Next, back and reset in the playlist.
+    Playout,
+    Emergency,
+    /// Program switch between A and B.
+    Switch,
+}
+
+impl ChannelState {
+    pub fn is_running(self) -> bool {
+        matches!(self, Starting | OnAirPlaylist | OnAirLive | Fallback)
+    }
+
+    /// Failed is possible from everywhere, the other states follow the start and stop order.
+    pub fn can_become(self, next: Self) -> bool {
+        if self == next || next == Failed {
+            return true;
+        }
+
+        match self {
+            Stopped => next == Starting,
+            Starting | OnAirPlaylist | OnAirLive | Fallback => next != Stopped,
+            Stopping => next == Stopped,
+            Failed => matches!(next, Starting | Stopping),
+        }
+    }
+
+    pub fn allows(self, action: ChannelAction) -> bool {
+        match action {
+            ChannelAction::Start => matches!(self, Stopped | Failed),
+            ChannelAction::Stop => self.is_running() || self == Failed,
+            ChannelAction::Restart => self.is_running() || self == Failed,
+            ChannelAction::Playout => matches!(self, OnAirPlaylist | Fallback),
+            ChannelAction::Emergency | ChannelAction::Switch => {
+                matches!(self, OnAirPlaylist | OnAirLive | Fallback)
+            }
+        }
+    }
+
+    pub fn check(self, action: ChannelAction) -> Result<(), ServiceError> {
+        if self.allows(action) {
+            return Ok(());
+        }
+
+        Err(ServiceError::Conflict(format!(
+            "{action:?} is not possible, channel is {self}"
+        )))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StateTracker {
+    pub state: ChannelState,
+    pub previous: ChannelState,
+    pub since: Option<DateTime<Local>>,
+}
+
+impl StateTracker {
+    /// Change to the next state, returns `false` when the transition is not valid.
+    pub fn set(&mut self, next: ChannelState, now: DateTime<Local>) -> bool {
+        if !self.state.can_become(next) {
+            return false;
+        }
+
+        if self.state != next {
+            self.previous = self.state;
+            self.state = next;
+            self.since = Some(now);
+        }
+
+        true
+    }
+}
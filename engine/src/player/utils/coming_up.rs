@@ -0,0 +1,237 @@
+/// Coming Up Cards
+///
+/// When the playout needs a filler for a gap, like a missing clip, it can show the next programs
+/// instead: the template image from `storage.coming_up` gets the begin times and titles from the
+/// next `storage.coming_up_count` clips in the list. The card is rendered with ffmpeg to an image,
+/// which is kept while the list has the same programs, and loops for the length of the gap.
+///
+/// Fillers, ads and ad breaks are not programs. When no program comes anymore, like at the end of
+/// the playlist, the normal filler plays.
+use std::{
+    env,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use log::*;
+use tokio::{fs, process::Command};
+
+use crate::player::{
+    controller::ChannelManager,
+    utils::{
+        probe::MediaProbe,
+        vast::{ADVERTISEMENT, AD_BREAK},
+        Media,
+    },
+};
+use crate::utils::{
+    config::PlayoutConfig, errors::ProcessError, files::norm_abs_path, logging::Target,
+};
+use crate::vec_strings;
+
+/// Cards are removed from the cache after this time.
+const CACHE_AGE: Duration = Duration::from_secs(86400);
+
+/// Clock time from the begin, playlists can go over midnight.
+pub fn clock_time(begin: f64) -> String {
+    let minutes = (begin / 60.0).floor() as i64;
+
+    format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+}
+
+/// Title from the clip, or the file name.
+fn program_title(node: &Media) -> String {
+    match node.title.as_ref().filter(|t| !t.trim().is_empty()) {
+        Some(title) => title.trim().to_string(),
+        None => Path::new(&node.source)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| node.source.clone()),
+    }
+}
+
+/// Begin time and title from the next programs after the list index.
+pub fn next_programs(list: &[Media], index: usize, count: usize) -> Vec<(String, String)> {
+    list.iter()
+        .skip(index + 1)
+        .filter(|n| {
+            !n.source.is_empty()
+                && !["filler", ADVERTISEMENT, AD_BREAK].contains(&n.category.as_str())
+        })
+        .take(count)
+        .map(|n| (clock_time(n.begin.unwrap_or_default()), program_title(n)))
+        .collect()
+}
+
+/// Text on the card, one line per program.
+pub fn card_text(programs: &[(String, String)]) -> String {
+    let mut lines = vec!["Coming up".to_string(), String::new()];
+
+    lines.extend(
+        programs
+            .iter()
+            .map(|(time, title)| format!("{time}  {title}")),
+    );
+
+    lines.join("\n")
+}
+
+fn escape(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .replace('\'', "'\\''")
+        .replace(':', "\\:")
+}
+
+/// ffmpeg arguments, which render the template with the text to the card image.
+pub fn render_cmd(
+    config: &PlayoutConfig,
+    template: &Path,
+    text_file: &Path,
+    card: &Path,
+) -> Vec<String> {
+    let font = if Path::new(&config.text.font_path).is_file() {
+        format!(":fontfile='{}'", escape(Path::new(&config.text.font_path)))
+    } else {
+        String::new()
+    };
+    let (width, height) = (config.processing.width, config.processing.height);
+
+    vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "error",
+        "-y",
+        "-i",
+        template.to_string_lossy(),
+        "-vf",
+        format!(
+            "scale={width}:{height},drawtext=textfile='{}'{font}:fontcolor=white:fontsize=h/18:line_spacing=h/60:x=w/10:y=h/4",
+            escape(text_file)
+        ),
+        "-frames:v",
+        "1",
+        card.to_string_lossy()
+    ]
+}
+
+async fn prune_cache(dir: &Path) {
+    let Ok(mut entries) = fs::read_dir(dir).await else {
+        return;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let old = entry
+            .metadata()
+            .await
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > CACHE_AGE);
+
+        if old {
+            let _ = fs::remove_file(entry.path()).await;
+        }
+    }
+}
+
+async fn render(
+    config: &PlayoutConfig,
+    template: &Path,
+    text: &str,
+) -> Result<PathBuf, ProcessError> {
+    let dir = env::temp_dir().join(format!("ffplayout_coming_up_{}", config.general.channel_id));
+    let mut hasher = DefaultHasher::new();
+
+    (
+        template,
+        text,
+        config.processing.width,
+        config.processing.height,
+        &config.text.font_path,
+    )
+        .hash(&mut hasher);
+
+    let name = format!("{:016x}", hasher.finish());
+    let card = dir.join(format!("{name}.png"));
+
+    if card.is_file() {
+        return Ok(card);
+    }
+
+    fs::create_dir_all(&dir).await?;
+    prune_cache(&dir).await;
+
+    let text_file = dir.join(format!("{name}.txt"));
+    fs::write(&text_file, text).await?;
+
+    let output = Command::new("ffmpeg")
+        .args(render_cmd(config, template, &text_file, &card))
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    let _ = fs::remove_file(&text_file).await;
+
+    if !output.status.success() || !card.is_file() {
+        return Err(ProcessError::Custom(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(card)
+}
+
+/// Coming up card for the gap at the position of the node, when a template is set and programs
+/// come after the gap.
+pub async fn card(
+    config: &PlayoutConfig,
+    manager: &ChannelManager,
+    node: &Media,
+) -> Option<(String, MediaProbe)> {
+    let id = config.general.channel_id;
+
+    if config.storage.coming_up.is_empty() || config.processing.audio_only {
+        return None;
+    }
+
+    let template = match norm_abs_path(&config.channel.storage, &config.storage.coming_up) {
+        Ok((path, _, _)) if path.is_file() => path,
+        _ => {
+            error!(target: Target::file_mail(), channel = id;
+                "Coming up template not exists: <b><magenta>{}</></b>",
+                config.storage.coming_up
+            );
+            return None;
+        }
+    };
+
+    let programs = next_programs(
+        &manager.current_list.lock().await,
+        node.index.unwrap_or_default(),
+        config.storage.coming_up_count.max(1) as usize,
+    );
+
+    if programs.is_empty() {
+        return None;
+    }
+
+    let card = match render(config, &template, &card_text(&programs)).await {
+        Ok(card) => card,
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = id; "Coming up card failed: {e}");
+            return None;
+        }
+    };
+
+    match MediaProbe::new(card.as_path()).await {
+        Ok(probe) => Some((card.to_string_lossy().to_string(), probe)),
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = id; "Coming up card failed: {e}");
+            None
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/// Concat Chain
+///
+/// Feed many clips to one long running decoder. The decoder reads a list in the ffconcat format,
+/// which contains the clip and, as last entry, a named pipe. When the decoder is through the clip,
+/// it opens the pipe and waits, until the list with the next clip is written into it.
+/// So the clips join without a new process and with continuous timestamps.
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use tokio::task::JoinHandle;
+
+use crate::player::{
+    controller::ProcessUnit::*,
+    utils::{is_remote, Media},
+};
+use crate::utils::config::{PlayoutConfig, IMAGE_FORMAT};
+
+/// Every clip opens one nested concat demuxer in the decoder,
+/// after this number of clips the decoder starts fresh.
+pub const MAX_CHAIN_CLIPS: usize = 100;
+
+/// Clip can be read by the concat demuxer, without own input options.
+pub fn chainable(node: &Media, config: &PlayoutConfig) -> bool {
+    let Some(probe) = node.probe.as_ref() else {
+        return false;
+    };
+    let is_image = node
+        .source
+        .rsplit_once('.')
+        .is_some_and(|(_, e)| IMAGE_FORMAT.contains(&e.to_lowercase().as_str()));
+    let single_input = node.cmd.as_ref().is_some_and(|cmd| {
+        cmd.iter().filter(|c| *c == "-i").count() == 1
+            && !cmd
+                .iter()
+                .any(|c| c == "-ss" || c == "-stream_loop" || c == "-loop" || c == "-f")
+    });
+
+    node.unit == Decoder
+        && single_input
+        && !is_image
+        && !is_remote(&node.source)
+        && node.audio.is_empty()
+        && Path::new(&node.source).is_file()
+        && (config.processing.audio_only || !probe.video.is_empty())
+        && probe.audio.len() >= config.processing.audio_tracks.max(1) as usize
+}
+
+/// Escape path for a quoted ffconcat string.
+pub(crate) fn quote(path: &str) -> String {
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
+
+/// List with the clip and the link to the next list.
+pub fn list_content(node: &Media, link: &Path) -> String {
+    let length = node.out - node.seek;
+    let mut list = format!("ffconcat version 1.0\nfile {}\n", quote(&node.source));
+
+    if node.duration > node.out {
+        list.push_str(&format!("outpoint {length}\n"));
+    }
+
+    list.push_str(&format!("duration {length}\n"));
+    list.push_str(&format!("file {}\n", quote(&link.to_string_lossy())));
+    // the link is a nested list, which also contains absolute paths
+    list.push_str("option safe 0\n");
+
+    list
+}
+
+fn make_link(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        nix::unistd::mkfifo(path, nix::sys::stat::Mode::S_IRWXU).map_err(io::Error::from)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+/// Open the link for reading, to release the writer which waits for a reader.
+fn release_link(path: &Path) -> io::Result<File> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(nix::libc::O_NONBLOCK)
+            .open(path)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+#[derive(Debug)]
+pub struct ConcatChain {
+    pub dir: PathBuf,
+    pub clips: usize,
+    link: PathBuf,
+    opener: Option<JoinHandle<io::Result<File>>>,
+    writer: Option<File>,
+}
+
+impl ConcatChain {
+    /// Create a new chain, the returned list is the decoder input.
+    pub async fn start(id: i32, node: &Media) -> io::Result<(Self, PathBuf)> {
+        let dir = std::env::temp_dir().join(format!("ffplayout_concat_{id}"));
+
+        if dir.is_dir() {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
+
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let list = dir.join("0.ffconcat");
+        let mut chain = Self {
+            dir,
+            clips: 0,
+            link: PathBuf::new(),
+            opener: None,
+            writer: None,
+        };
+
+        let link = chain.next_link()?;
+        tokio::fs::write(&list, list_content(node, &link)).await?;
+        chain.clips = 1;
+
+        Ok((chain, list))
+    }
+
+    fn next_link(&mut self) -> io::Result<PathBuf> {
+        let link = self.dir.join(format!("{}.link", self.clips + 1));
+        let path = link.clone();
+
+        make_link(&link)?;
+
+        self.link = link.clone();
+        self.opener = Some(tokio::task::spawn_blocking(move || {
+            OpenOptions::new().write(true).open(path)
+        }));
+
+        Ok(link)
+    }
+
+    /// Wait until the decoder is through the current clip and opens the link.
+    pub async fn requested(&mut self) -> io::Result<()> {
+        let Some(opener) = self.opener.as_mut() else {
+            return std::future::pending().await;
+        };
+
+        let result = opener.await;
+        self.opener = None;
+        self.writer = Some(result.map_err(io::Error::other)??);
+
+        Ok(())
+    }
+
+    /// Send the next clip to the decoder, the link to the following list exists before.
+    pub fn feed(&mut self, node: &Media) -> io::Result<()> {
+        let Some(mut writer) = self.writer.take() else {
+            return Err(io::Error::other("Decoder waits not for the next clip"));
+        };
+
+        let link = self.next_link()?;
+        writer.write_all(list_content(node, &link).as_bytes())?;
+        self.clips += 1;
+
+        Ok(())
+    }
+
+    /// End the chain, an empty list lets the decoder finish after the current clip.
+    pub async fn close(mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.write_all(b"ffconcat version 1.0\n");
+        }
+
+        if let Some(opener) = self.opener.take() {
+            let reader = release_link(&self.link);
+            let _ = opener.await;
+            drop(reader);
+        }
+
+        let _ = tokio::fs::remove_dir_all(&self.dir).await;
+    }
+}
@@ -0,0 +1,46 @@
+/// Schedule Drift
+///
+/// Difference between the wall clock and the start time from the playlist, measured on every clip
+/// change in playlist mode. A slow encoder or a hanging source shows up here first,
+/// long before the clip is out of sync for more than the stop threshold.
+use chrono::{DateTime, Local};
+use log::*;
+use serde::Serialize;
+
+use crate::player::controller::ChannelManager;
+use crate::utils::{config::PlayoutConfig, logging::Target};
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ScheduleDrift {
+    /// Seconds the playout is behind the schedule, negative when it is ahead.
+    pub seconds: f64,
+    /// Drift is larger than the alarm threshold.
+    pub alarm: bool,
+    pub updated: Option<DateTime<Local>>,
+}
+
+/// Store the drift from the clip delta and alarm, when it crosses the threshold.
+pub async fn update_drift(manager: &ChannelManager, config: &PlayoutConfig, delta: f64) {
+    let id = config.general.channel_id;
+    let threshold = config.general.drift_alarm;
+    let mut drift = manager.drift.lock().await;
+
+    drift.seconds = (-delta * 1000.0).round() / 1000.0;
+    drift.updated = Some(Local::now());
+
+    let alarm = threshold > 0.0 && drift.seconds.abs() > threshold;
+
+    if alarm && !drift.alarm {
+        error!(target: Target::file_mail(), channel = id;
+            "Playout drifts <yellow>{:.3}</> seconds from the schedule",
+            drift.seconds
+        );
+    } else if !alarm && drift.alarm {
+        info!(target: Target::file_mail(), channel = id;
+            "Playout is back in sync with the schedule, drift: <yellow>{:.3}</> seconds",
+            drift.seconds
+        );
+    }
+
+    drift.alarm = alarm;
+}
@@ -1,18 +1,65 @@
-use std::sync::{atomic::Ordering, Arc};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc},
+};
 
 use async_walkdir::{Filtering, WalkDir};
 use lexical_sort::natural_lexical_cmp;
 use log::*;
 use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use sqlx::{Pool, Sqlite};
 use tokio::sync::Mutex;
 use tokio_stream::StreamExt;
 
+use crate::db::handles;
 use crate::player::{
     controller::ChannelManager,
     utils::{include_file_extension, time_in_seconds, Media, PlayoutConfig},
 };
 use crate::utils::logging::Target;
 
+/// Files which have one of the tags, `None` when there is nothing to filter.
+///
+/// Tags are stored with paths relative to the storage, so they are joined here to absolute paths.
+pub async fn tagged_files(
+    config: &PlayoutConfig,
+    pool: Option<&Pool<Sqlite>>,
+    tags: &[String],
+) -> Option<HashSet<PathBuf>> {
+    let id = config.general.channel_id;
+
+    if tags.is_empty() {
+        return None;
+    }
+
+    let Some(pool) = pool else {
+        warn!(target: Target::file_mail(), channel = id; "No database connection, ignore tags");
+        return None;
+    };
+
+    match handles::select_tagged_media(pool, id, tags, false).await {
+        Ok(media) => Some(
+            media
+                .into_iter()
+                .map(|m| config.channel.storage.join(m.path))
+                .collect(),
+        ),
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = id; "Read tagged files failed: {e}");
+            Some(HashSet::new())
+        }
+    }
+}
+
+/// Random generator for shuffle, with a seed it gives the same order on every run.
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
 /// Folder Sources
 ///
 /// Like playlist source, we create here a folder list for iterate over it.
@@ -20,6 +67,7 @@ use crate::utils::logging::Target;
 pub struct FolderSource {
     manager: ChannelManager,
     current_node: Media,
+    rng: StdRng,
 }
 
 impl FolderSource {
@@ -41,6 +89,8 @@ impl FolderSource {
             );
         }
 
+        let tagged = tagged_files(config, manager.db_pool.as_ref(), &config.storage.tags).await;
+
         for path in &path_list {
             if !path.is_dir() {
                 error!(target: Target::file_mail(), channel = id; "Path not exists: <b><magenta>{path:?}</></b>");
@@ -61,6 +111,10 @@ impl FolderSource {
             loop {
                 match entries.next().await {
                     Some(Ok(entry)) => {
+                        if tagged.as_ref().is_some_and(|t| !t.contains(&entry.path())) {
+                            continue;
+                        }
+
                         let media = Media::new(0, &entry.path().to_string_lossy(), false).await;
                         media_list.push(media);
                     }
@@ -80,9 +134,10 @@ impl FolderSource {
             );
         }
 
+        let mut rng = seeded_rng(config.general.seed);
+
         if config.storage.shuffle {
             info!(target: Target::file_mail(), channel = id; "Shuffle files");
-            let mut rng = StdRng::from_entropy();
             media_list.shuffle(&mut rng);
         } else {
             media_list.sort_by(|d1, d2| d1.source.cmp(&d2.source));
@@ -99,23 +154,25 @@ impl FolderSource {
         Self {
             manager,
             current_node: Media::default(),
+            rng,
         }
     }
 
     pub async fn from_list(manager: &ChannelManager, list: Vec<Media>) -> Self {
+        let seed = manager.config.lock().await.general.seed;
         *manager.current_list.lock().await = list;
 
         Self {
             manager: manager.clone(),
             current_node: Media::default(),
+            rng: seeded_rng(seed),
         }
     }
 
     async fn shuffle(&mut self) {
-        let mut rng = StdRng::from_entropy();
         let mut nodes = self.manager.current_list.lock().await;
 
-        nodes.shuffle(&mut rng);
+        nodes.shuffle(&mut self.rng);
 
         for (index, item) in nodes.iter_mut().enumerate() {
             item.index = Some(index);
@@ -230,7 +287,7 @@ pub async fn fill_filler_list(
         }
 
         if config.storage.shuffle {
-            let mut rng = StdRng::from_entropy();
+            let mut rng = seeded_rng(config.general.seed);
 
             filler_list.shuffle(&mut rng);
         } else {
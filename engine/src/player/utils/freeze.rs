@@ -0,0 +1,193 @@
+/// Freeze Frame
+///
+/// Hold the last frame from the program, with silent audio, for some seconds. The frame is taken
+/// from the current clip at the played position, or from the replay recording when the clip is
+/// not a file. The hold runs like a normal clip, so HLS and stream outputs stay continuous.
+/// Afterwards a playlist starts again on the current time, a folder goes on with the next clip.
+use std::{path::PathBuf, process::Stdio, sync::atomic::Ordering};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::player::{
+    controller::{ChannelManager, ProcessUnit::*},
+    input::playlist::gen_source,
+    utils::{is_remote, played_since, time_in_seconds, Media},
+};
+use crate::utils::{errors::ServiceError, logging::Target};
+use crate::vec_strings;
+
+/// Longest hold, a longer break is better done with the playlist.
+pub const MAX_FREEZE: f64 = 600.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FreezeRequest {
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FreezeInfo {
+    /// Position in the clip, where the frame is taken from.
+    pub position: f64,
+    pub duration: f64,
+}
+
+/// Played position in the clip, `begin` is the start in seconds of the day.
+pub fn frame_position(node: &Media, now: f64, shift: f64) -> f64 {
+    let played = node
+        .begin
+        .map_or(0.0, |begin| played_since(begin - shift, now));
+
+    (node.seek + played).clamp(node.seek, (node.out - 0.1).max(node.seek))
+}
+
+/// Write one frame, `position` is the seek in the input, negative values seek from the end.
+pub fn grab_cmd(source: &str, position: f64, target: &str) -> Vec<String> {
+    let seek = if position < 0.0 { "-sseof" } else { "-ss" };
+
+    vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "error",
+        seek,
+        format!("{position:.3}"),
+        "-i",
+        source,
+        "-map",
+        "0:v:0",
+        "-frames:v",
+        "1",
+        "-update",
+        "1",
+        "-y",
+        target
+    ]
+}
+
+async fn grab(cmd: Vec<String>) -> Result<(), ServiceError> {
+    let output = Command::new("ffmpeg")
+        .args(cmd)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(ServiceError::Conflict(format!(
+            "Grab frame failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Take the frame, put the hold before the next clip and end the current one.
+pub async fn freeze_frame(
+    manager: &ChannelManager,
+    request: &FreezeRequest,
+) -> Result<FreezeInfo, ServiceError> {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+
+    if !(1.0..=MAX_FREEZE).contains(&request.duration) {
+        return Err(ServiceError::BadRequest(format!(
+            "Duration must be between 1 and {MAX_FREEZE} seconds"
+        )));
+    }
+
+    if !manager.is_alive.load(Ordering::SeqCst) {
+        return Err(ServiceError::Conflict("Playout is not running".to_string()));
+    }
+
+    if manager.ingest_is_alive.load(Ordering::SeqCst) {
+        return Err(ServiceError::Conflict(
+            "Live ingest is on air, freeze is not possible".to_string(),
+        ));
+    }
+
+    if config.processing.audio_only {
+        return Err(ServiceError::Conflict(
+            "Audio only channel has no frame to hold".to_string(),
+        ));
+    }
+
+    if manager.freeze.lock().await.is_some() {
+        return Err(ServiceError::Conflict(
+            "Freeze is already pending".to_string(),
+        ));
+    }
+
+    let current = manager.current_media.lock().await.clone();
+    let shift = manager.channel.lock().await.time_shift;
+    let target = std::env::temp_dir().join(format!("ffplayout_freeze_{id}.png"));
+    let target_str = target.to_string_lossy().to_string();
+    let mut position = 0.0;
+
+    let source = match current {
+        Some(node)
+            if PathBuf::from(&node.source).is_file()
+                || (is_remote(&node.source) && !node.source.contains(".m3u8")) =>
+        {
+            position = frame_position(&node, time_in_seconds(&config.channel.timezone), shift);
+            Some(node.source)
+        }
+        _ => None,
+    };
+
+    let cmd = match source {
+        Some(source) => grab_cmd(&source, position, &target_str),
+        None => {
+            let replay = manager.replay.lock().await.clone();
+
+            match replay {
+                // the newest segment is still written
+                Some(replay) => match replay.last_segment(1).await {
+                    Some(segment) => grab_cmd(&segment.to_string_lossy(), -0.1, &target_str),
+                    None => {
+                        return Err(ServiceError::Conflict(
+                            "Recording has no segment yet".to_string(),
+                        ))
+                    }
+                },
+                None => {
+                    return Err(ServiceError::Conflict(
+                        "Current clip is not a file and the program is not recorded".to_string(),
+                    ))
+                }
+            }
+        }
+    };
+
+    grab(cmd).await?;
+
+    let mut node = Media::new(0, &target_str, true).await;
+    node.out = request.duration;
+    node.duration = request.duration;
+    node.category = "freeze".to_string();
+    node.begin = Some(time_in_seconds(&config.channel.timezone));
+
+    let node = gen_source(&config, node, manager, 0).await;
+
+    if node.probe.is_none() {
+        return Err(ServiceError::Conflict(
+            "Grabbed frame is not readable".to_string(),
+        ));
+    }
+
+    *manager.freeze.lock().await = Some(node);
+
+    info!(target: Target::file_mail(), channel = id;
+        "Freeze frame for <yellow>{:.1}</> seconds",
+        request.duration
+    );
+
+    manager.stop(Decoder).await?;
+
+    Ok(FreezeInfo {
+        position,
+        duration: request.duration,
+    })
+}
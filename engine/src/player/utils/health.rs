@@ -0,0 +1,202 @@
+/// Output Health
+///
+/// Track when the encoder loses the connection to the streaming target and when it comes back,
+/// to compute the availability over the last 24 hours. Restarts in a loop are otherwise only
+/// visible in the log file.
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local, TimeDelta};
+use log::*;
+use serde::Serialize;
+
+use crate::player::{controller::ChannelManager, utils::sec_to_time};
+use crate::utils::{
+    event_history::{self, EventKind},
+    logging::Target,
+    uptime,
+};
+
+/// ffmpeg messages, which mean that the output is gone.
+pub const OUTPUT_ERRORS: [&str; 7] = [
+    "Broken pipe",
+    "Connection refused",
+    "Connection reset by peer",
+    "Connection timed out",
+    "End of file",
+    "Error writing trailer",
+    "Input/output error",
+];
+
+const MAX_EVENTS: usize = 100;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OutputEvent {
+    pub time: DateTime<Local>,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OutputReport {
+    pub connected: bool,
+    pub since: Option<DateTime<Local>>,
+    /// Uptime in percent, over the last 24 hours or since the first start.
+    pub availability: f64,
+    pub disconnects_hour: usize,
+    pub disconnects_day: usize,
+    pub events: Vec<OutputEvent>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OutputHealth {
+    started: Option<DateTime<Local>>,
+    connected: Option<DateTime<Local>>,
+    down_since: Option<DateTime<Local>>,
+    outages: VecDeque<(DateTime<Local>, DateTime<Local>)>,
+    events: VecDeque<OutputEvent>,
+}
+
+impl OutputHealth {
+    fn window() -> TimeDelta {
+        TimeDelta::hours(24)
+    }
+
+    /// Encoder is running, returns the downtime in seconds when it was disconnected before.
+    pub fn connected(&mut self, now: DateTime<Local>) -> Option<f64> {
+        self.started.get_or_insert(now);
+        self.connected = Some(now);
+
+        let downtime = self.down_since.take().map(|since| {
+            self.outages.push_back((since, now));
+            (now - since).num_milliseconds() as f64 / 1000.0
+        });
+
+        self.prune(now);
+
+        downtime
+    }
+
+    /// Connection is lost, returns `false` when it was already lost.
+    pub fn disconnected(&mut self, now: DateTime<Local>, reason: &str) -> bool {
+        if self.connected.take().is_none() {
+            return false;
+        }
+
+        self.down_since = Some(now);
+        self.events.push_back(OutputEvent {
+            time: now,
+            reason: reason.to_string(),
+        });
+
+        if self.events.len() > MAX_EVENTS {
+            self.events.pop_front();
+        }
+
+        true
+    }
+
+    /// Stopped on purpose, this is no outage. Returns `false` when it was not connected.
+    pub fn stopped(&mut self) -> bool {
+        self.down_since = None;
+        self.connected.take().is_some()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.is_some()
+    }
+
+    pub fn disconnects(&self, now: DateTime<Local>, period: TimeDelta) -> usize {
+        self.events.iter().filter(|e| now - e.time < period).count()
+    }
+
+    pub fn availability(&self, now: DateTime<Local>) -> f64 {
+        let Some(started) = self.started else {
+            return 100.0;
+        };
+
+        let window_start = started.max(now - Self::window());
+        let total = (now - window_start).num_milliseconds();
+
+        if total <= 0 {
+            return 100.0;
+        }
+
+        let current = self.down_since.map(|since| (since, now));
+        let down: i64 = self
+            .outages
+            .iter()
+            .chain(current.iter())
+            .map(|(start, end)| (*end.min(&now) - *start.max(&window_start)).num_milliseconds())
+            .filter(|ms| *ms > 0)
+            .sum();
+
+        100.0 - down as f64 / total as f64 * 100.0
+    }
+
+    pub fn report(&self, now: DateTime<Local>) -> OutputReport {
+        OutputReport {
+            connected: self.connected.is_some(),
+            since: self.connected.or(self.down_since),
+            availability: (self.availability(now) * 100.0).round() / 100.0,
+            disconnects_hour: self.disconnects(now, TimeDelta::hours(1)),
+            disconnects_day: self.disconnects(now, Self::window()),
+            events: self.events.iter().rev().take(20).cloned().collect(),
+        }
+    }
+
+    fn prune(&mut self, now: DateTime<Local>) {
+        let window_start = now - Self::window();
+
+        self.outages.retain(|(_, end)| *end > window_start);
+        self.events.retain(|e| e.time > window_start);
+    }
+}
+
+/// Encoder is started, log how long the output was gone.
+pub async fn output_connected(manager: &ChannelManager) {
+    let id = manager.channel.lock().await.id;
+    let now = Local::now();
+    let mut health = manager.output_health.lock().await;
+    let was_connected = health.is_connected();
+    let downtime = health.connected(now);
+
+    if let Some(downtime) = downtime {
+        warn!(target: Target::file_mail(), channel = id;
+            "Output is back after <yellow>{}</>, availability: <yellow>{:.2}%</>",
+            sec_to_time(downtime),
+            health.availability(now)
+        );
+    }
+
+    if was_connected {
+        return;
+    }
+
+    let cause = if downtime.is_some() {
+        "reconnected"
+    } else {
+        "started"
+    };
+
+    event_history::push(id, EventKind::Output, format!("Output {cause}"));
+
+    if let Some(pool) = manager.db_pool.clone() {
+        tokio::spawn(uptime::record(pool, id, true, cause.to_string()));
+    }
+}
+
+/// Encoder lost the connection, too many disconnects are an alert rule.
+pub async fn output_lost(manager: &ChannelManager, reason: &str) {
+    let id = manager.channel.lock().await.id;
+    let now = Local::now();
+    let mut health = manager.output_health.lock().await;
+
+    if !health.disconnected(now, reason) {
+        return;
+    }
+
+    if let Some(pool) = manager.db_pool.clone() {
+        tokio::spawn(uptime::record(pool, id, false, reason.to_string()));
+    }
+
+    error!(target: Target::file_mail(), channel = id; "Output connection lost: {reason}");
+}
@@ -1,24 +1,25 @@
 /// Import text/m3u file and create a playlist out of it
-use std::{
-    io::{Error, ErrorKind},
-    path::Path,
-};
+use std::path::Path;
 
 use tokio::{
-    fs::{create_dir_all, File},
+    fs::File,
     io::{AsyncBufReadExt, BufReader},
 };
 
 use crate::player::utils::{
-    json_reader, json_serializer::JsonPlaylist, json_writer, Media, PlayoutConfig,
+    json_serializer::JsonPlaylist,
+    playlist_store::{PlaylistStorage, PlaylistStore},
+    Media,
 };
+use crate::utils::{config::PlayoutConfig, errors::ServiceError, house_format};
 
 pub async fn import_file(
     config: &PlayoutConfig,
+    store: &PlaylistStorage,
     date: &str,
     channel_name: Option<String>,
     path: &Path,
-) -> Result<String, Error> {
+) -> Result<String, ServiceError> {
     let file = File::open(path).await?;
     let reader = BufReader::new(file);
     let mut playlist = JsonPlaylist {
@@ -28,57 +29,56 @@ pub async fn import_file(
         start_sec: None,
         length: None,
         modified: None,
+        version: None,
         program: vec![],
     };
 
-    let playlist_root = &config.channel.playlists;
-    if !playlist_root.is_dir() {
-        return Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "Playlist folder <b><magenta>{:?}</></b> not exists!",
-                config.channel.playlists,
-            ),
-        ));
-    }
-
-    let d: Vec<&str> = date.split('-').collect();
-    let year = d[0];
-    let month = d[1];
-    let playlist_path = playlist_root.join(year).join(month);
-    let playlist_file = &playlist_path.join(format!("{date}.json"));
-
-    create_dir_all(playlist_path).await?;
-
     let mut lines = reader.lines();
+    let mut rejected = vec![];
+
     while let Some(line) = lines.next_line().await? {
         if !line.starts_with('#') {
             let item = Media::new(0, &line, true).await;
 
             if item.duration > 0.0 {
+                let source = Path::new(&item.source);
+
+                if let Some(probe) = item
+                    .probe
+                    .as_ref()
+                    .filter(|_| house_format::applies(config, source))
+                {
+                    let problems =
+                        house_format::check_probe(&config.storage.house_format, source, probe)
+                            .await;
+
+                    if !problems.is_empty() {
+                        rejected.push(format!("{}: {}", item.source, problems.join(", ")));
+                    }
+                }
+
                 playlist.program.push(item);
             }
         }
     }
 
-    let mut file_exists = false;
+    if !rejected.is_empty() {
+        return Err(ServiceError::BadRequest(format!(
+            "Clips break the house format: {}",
+            rejected.join("; ")
+        )));
+    }
 
-    if playlist_file.is_file() {
-        file_exists = true;
-        let mut existing_data = json_reader(playlist_file).await?;
+    if let Some(mut existing_data) = store.read(date).await? {
         existing_data.program.append(&mut playlist.program);
 
         playlist.program = existing_data.program;
+        playlist.version = existing_data.version;
     };
 
-    let msg = if file_exists {
-        format!("Update playlist from {date} success!")
+    if store.write(&playlist).await? {
+        Ok(format!("Update playlist from {date} success!"))
     } else {
-        format!("Write playlist from {date} success!")
-    };
-
-    match json_writer(playlist_file, playlist).await {
-        Ok(_) => Ok(msg),
-        Err(e) => Err(Error::new(ErrorKind::Other, e)),
+        Ok(format!("Write playlist from {date} success!"))
     }
 }
@@ -8,8 +8,11 @@ use serde::{Deserialize, Serialize};
 use tokio::{fs::File, io::AsyncReadExt, sync::Mutex};
 
 use crate::player::utils::{
-    get_date, is_remote, json_validate::validate_playlist, modified_time, time_from_header, Media,
-    PlayoutConfig,
+    get_date, is_remote,
+    json_validate::validate_playlist,
+    modified_time,
+    playlist_store::{PlaylistStorage, PlaylistStore},
+    time_from_header, Media, PlayoutConfig,
 };
 use crate::utils::{config::DUMMY_LEN, logging::Target};
 
@@ -32,6 +35,10 @@ pub struct JsonPlaylist {
     #[serde(skip_serializing, skip_deserializing)]
     pub modified: Option<String>,
 
+    /// Stored version, only set when the playlist is saved in the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+
     pub program: Vec<Media>,
 }
 
@@ -51,6 +58,7 @@ impl JsonPlaylist {
             length: Some(86400.0),
             path: None,
             modified: None,
+            version: None,
             program: vec![media],
         }
     }
@@ -93,6 +101,7 @@ pub fn set_defaults(playlist: &mut JsonPlaylist) {
 /// which we need to process.
 pub async fn read_json(
     config: &mut PlayoutConfig,
+    store: &PlaylistStorage,
     current_list: Arc<Mutex<Vec<Media>>>,
     path: Option<String>,
     is_alive: Arc<AtomicBool>,
@@ -105,6 +114,40 @@ pub async fn read_json(
     let start_sec = config.playlist.start_sec.unwrap();
     let date = get_date(seek, start_sec, get_next, &config.channel.timezone);
 
+    if let PlaylistStorage::Database(db) = store {
+        let mut playlist = match db.read(&date).await {
+            Ok(Some(p)) => p,
+            Ok(None) => {
+                error!(target: Target::file_mail(), channel = id; "Playlist <b><magenta>{}</></b> not exist!", db.location(&date));
+                return JsonPlaylist::new(date, start_sec);
+            }
+            Err(e) => {
+                error!(target: Target::file_mail(), channel = id; "Playlist from database not readable! {e}");
+                return JsonPlaylist::new(date, start_sec);
+            }
+        };
+
+        // catch empty program list
+        if playlist.program.is_empty() {
+            playlist.program = JsonPlaylist::new(date, start_sec).program;
+        }
+
+        playlist.start_sec = Some(start_sec);
+
+        if !config.general.skip_validation {
+            tokio::spawn(validate_playlist(
+                config_clone,
+                current_list,
+                playlist.clone(),
+                is_alive,
+            ));
+        }
+
+        set_defaults(&mut playlist);
+
+        return playlist;
+    }
+
     if playlist_path.is_dir() || is_remote(&config.channel.playlists.to_string_lossy()) {
         let d: Vec<&str> = date.split('-').collect();
         playlist_path = playlist_path
@@ -1,4 +1,5 @@
 use std::{
+    path::Path,
     process::Stdio,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -17,11 +18,13 @@ use tokio::{
 
 use crate::player::filter::FilterType::Audio;
 use crate::player::utils::{
-    is_close, is_remote, loop_image, sec_to_time, seek_and_length, JsonPlaylist, Media,
+    is_close, is_remote, loop_image, sec_to_time, seek_and_length, vast::AD_BREAK, JsonPlaylist,
+    Media,
 };
 use crate::utils::{
     config::{OutputMode::Null, PlayoutConfig, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT},
     errors::ProcessError,
+    house_format,
     logging::Target,
 };
 use crate::vec_strings;
@@ -157,13 +160,15 @@ async fn check_media(
 /// - total playtime fits target length from config
 ///
 /// This function we run in a thread, to don't block the main function.
+/// Returns the number of found errors.
 pub async fn validate_playlist(
     mut config: PlayoutConfig,
     current_list: Arc<Mutex<Vec<Media>>>,
     mut playlist: JsonPlaylist,
     is_alive: Arc<AtomicBool>,
-) {
+) -> usize {
     let id = config.general.channel_id;
+    let mut errors = 0;
     let date = playlist.date;
 
     if config.text.add_text && !config.text.text_from_filename {
@@ -181,30 +186,85 @@ pub async fn validate_playlist(
 
     for (index, item) in playlist.program.iter_mut().enumerate() {
         if !is_alive.load(Ordering::SeqCst) {
-            return;
+            return errors;
         }
 
         let pos = index + 1;
 
-        if !is_remote(&item.source) {
+        // slideshow folders are probed when they are next
+        let is_folder = Path::new(&item.source).is_dir();
+        // ad breaks without source are filled from the ad server
+        let is_break = item.category == AD_BREAK && item.source.is_empty();
+
+        if !is_remote(&item.source) && !is_folder && !is_break {
             if item.audio.is_empty() {
                 if let Err(e) = item.add_probe(false).await {
                     error!(target: Target::file_mail(), channel = id;
                         "[Validation] Error on position <yellow>{pos:0>3}</> - <yellow>{}</>: {e}",
                         sec_to_time(begin)
                     );
+                    errors += 1;
                 }
             } else if let Err(e) = item.add_probe(true).await {
                 error!(target: Target::file_mail(), channel = id;
                     "[Validation] Error on position <yellow>{pos:0>3}</> - <yellow>{}</>: {e}",
                     sec_to_time(begin)
                 );
+                errors += 1;
+            }
+        }
+
+        if !is_remote(&item.source)
+            && !is_folder
+            && item.probe.is_none()
+            && !item.backup_source.is_empty()
+        {
+            warn!(target: Target::file_mail(), channel = id;
+                "[Validation] Position <yellow>{pos:0>3}</> - <yellow>{}</>: use backup <b><magenta>{}</></b>",
+                sec_to_time(begin),
+                item.backup_source
+            );
+
+            item.source = std::mem::take(&mut item.backup_source);
+
+            if !is_remote(&item.source) {
+                if let Err(e) = item.add_probe(false).await {
+                    error!(target: Target::file_mail(), channel = id;
+                        "[Validation] Error on backup at position <yellow>{pos:0>3}</> - <yellow>{}</>: {e}",
+                        sec_to_time(begin)
+                    );
+                    errors += 1;
+                }
+            }
+        }
+
+        if let Some(probe) = item
+            .probe
+            .as_ref()
+            .filter(|_| house_format::applies(&config, Path::new(&item.source)))
+        {
+            let problems = house_format::check_probe(
+                &config.storage.house_format,
+                Path::new(&item.source),
+                probe,
+            )
+            .await;
+
+            if !problems.is_empty() {
+                error!(target: Target::file_mail(), channel = id;
+                    "[Validation] Position <yellow>{pos:0>3}</> - <yellow>{}</> breaks the house format: {}, source <b><magenta>{}</></b>",
+                    sec_to_time(begin),
+                    problems.join(", "),
+                    item.source
+                );
+                errors += 1;
             }
         }
 
         if item.probe.is_some() {
             if let Err(e) = check_media(item.clone(), pos, begin, &config).await {
                 error!(target: Target::file_mail(), channel = id; "{e}");
+                errors += 1;
             } else if config.general.validate {
                 debug!(target: Target::file_mail(), channel = id;
                     "[Validation] Source at <yellow>{}</>, seems fine: <b><magenta>{}</></b>",
@@ -248,6 +308,7 @@ pub async fn validate_playlist(
             "[Validation] Playlist from <yellow>{date}</> not long enough, <yellow>{}</> needed!",
             sec_to_time(length - begin),
         );
+        errors += 1;
     }
 
     if config.general.validate {
@@ -262,4 +323,6 @@ pub async fn validate_playlist(
         timer.elapsed(),
         sec_to_time(begin - config.playlist.start_sec.unwrap())
     );
+
+    errors
 }
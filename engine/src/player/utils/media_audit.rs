@@ -0,0 +1,263 @@
+/// Media Audit
+///
+/// Check the clips from the upcoming playlists in the background, to find files which are gone,
+/// can't be probed or don't fit the channel, days before they go on air. The last result per
+/// channel is available over the API, new problems are sent to the notifiers.
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, Local, TimeDelta};
+use log::*;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::player::{
+    controller::{ChannelController, ChannelManager},
+    utils::{
+        is_close, is_remote, playlist_store::PlaylistStore, probe::MediaProbe, sec_to_time,
+        slideshow::slide_images, JsonPlaylist, Media,
+    },
+};
+use crate::utils::{
+    config::{PlayoutConfig, ProcessMode, IMAGE_FORMAT},
+    logging::Target,
+    time_machine::time_now,
+};
+
+const AUDIT_INTERVAL: u64 = 21600;
+/// Playlists from today and this number of following days are checked.
+pub const AUDIT_DAYS: i64 = 14;
+/// Problems which are listed in one notification, per date.
+const NOTIFY_ISSUES: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AuditIssue {
+    pub position: usize,
+    pub begin: String,
+    pub source: String,
+    pub problem: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DateAudit {
+    pub date: String,
+    pub clips: usize,
+    pub issues: Vec<AuditIssue>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MediaAudit {
+    pub checked: Option<DateTime<Local>>,
+    pub dates: Vec<DateAudit>,
+}
+
+impl MediaAudit {
+    pub fn issues(&self) -> usize {
+        self.dates.iter().map(|d| d.issues.len()).sum()
+    }
+}
+
+/// Problems from one clip, with the probe result from the source.
+pub fn check_clip(
+    item: &Media,
+    probe: &Result<MediaProbe, String>,
+    config: &PlayoutConfig,
+) -> Vec<String> {
+    let mut problems = vec![];
+
+    if item.out - item.seek <= 0.0 {
+        problems.push("Clip has no playtime".to_string());
+    }
+
+    let probe = match probe {
+        Ok(probe) => probe,
+        Err(e) => {
+            problems.push(e.clone());
+            return problems;
+        }
+    };
+
+    if !config.processing.audio_only && probe.video.is_empty() {
+        problems.push("File has no video stream".to_string());
+    }
+
+    let is_image = Path::new(&item.source).is_dir()
+        || item
+            .source
+            .rsplit_once('.')
+            .is_some_and(|(_, e)| IMAGE_FORMAT.contains(&e.to_lowercase().as_str()));
+
+    if let Some(duration) = probe.format.duration.filter(|_| !is_image) {
+        if !is_close(item.duration, duration, 1.2) {
+            problems.push(format!(
+                "File duration {} differs from playlist value {}",
+                sec_to_time(duration),
+                sec_to_time(item.duration)
+            ));
+        }
+    }
+
+    if !item.audio.is_empty() && !is_remote(&item.audio) && !Path::new(&item.audio).is_file() {
+        problems.push(format!("Audio file not found: {}", item.audio));
+    }
+
+    problems
+}
+
+async fn probe(source: &str) -> Result<MediaProbe, String> {
+    if Path::new(source).is_dir() {
+        let slides = slide_images(source);
+
+        return match slides.first() {
+            Some(first) => MediaProbe::new(first.as_path())
+                .await
+                .map_err(|e| format!("Probe failed: {e}")),
+            None => Err("Folder has no images".to_string()),
+        };
+    }
+
+    if !Path::new(source).is_file() {
+        return Err("File not found".to_string());
+    }
+
+    MediaProbe::new(source)
+        .await
+        .map_err(|e| format!("Probe failed: {e}"))
+}
+
+/// Check all clips from the playlist, files are probed once per audit.
+pub async fn audit_playlist(
+    playlist: &JsonPlaylist,
+    config: &PlayoutConfig,
+    probes: &mut HashMap<String, Result<MediaProbe, String>>,
+) -> DateAudit {
+    let mut begin = config.playlist.start_sec.unwrap_or_default();
+    let mut issues = vec![];
+
+    for (index, item) in playlist.program.iter().enumerate() {
+        if !is_remote(&item.source) {
+            if !probes.contains_key(&item.source) {
+                let result = probe(&item.source).await;
+                probes.insert(item.source.clone(), result);
+            }
+
+            for problem in check_clip(item, &probes[&item.source], config) {
+                issues.push(AuditIssue {
+                    position: index + 1,
+                    begin: sec_to_time(begin),
+                    source: item.source.clone(),
+                    problem,
+                });
+            }
+        }
+
+        begin += item.out - item.seek;
+    }
+
+    DateAudit {
+        date: playlist.date.clone(),
+        clips: playlist.program.len(),
+        issues,
+    }
+}
+
+fn notify(id: i32, audit: &DateAudit) {
+    let mut lines: Vec<String> = audit
+        .issues
+        .iter()
+        .take(NOTIFY_ISSUES)
+        .map(|i| {
+            format!(
+                "Position <yellow>{:0>3}</> - <yellow>{}</>: <b><magenta>{}</></b>: {}",
+                i.position, i.begin, i.source, i.problem
+            )
+        })
+        .collect();
+
+    if audit.issues.len() > NOTIFY_ISSUES {
+        lines.push(format!(
+            "... and {} more",
+            audit.issues.len() - NOTIFY_ISSUES
+        ));
+    }
+
+    error!(target: Target::file_mail(), channel = id;
+        "[Audit] Playlist from <yellow>{}</> has <yellow>{}</> problems:\n{}",
+        audit.date,
+        audit.issues.len(),
+        lines.join("\n")
+    );
+}
+
+/// Audit the playlists from the channel and keep the result, only new problems are notified.
+pub async fn audit_channel(manager: &ChannelManager) {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+    let store = manager.playlist_store().await;
+    let today = time_now(&config.channel.timezone).date_naive();
+    let previous = manager.media_audit.lock().await.clone();
+    let mut probes = HashMap::new();
+    let mut dates = vec![];
+
+    for day in 0..=AUDIT_DAYS {
+        let Some(date) = today.checked_add_signed(TimeDelta::days(day)) else {
+            continue;
+        };
+        let day_config = config.for_weekday(date.weekday());
+        let date = date.format("%Y-%m-%d").to_string();
+
+        let playlist = match store.read(&date).await {
+            Ok(Some(playlist)) => playlist,
+            Ok(None) => continue,
+            Err(e) => {
+                error!(target: Target::file(), channel = id; "[Audit] Read playlist from <yellow>{date}</> failed: {e}");
+                continue;
+            }
+        };
+
+        let audit = audit_playlist(&playlist, &day_config, &mut probes).await;
+        let known = previous
+            .dates
+            .iter()
+            .find(|d| d.date == audit.date)
+            .map(|d| d.issues.as_slice())
+            .unwrap_or_default();
+
+        if audit.issues.iter().any(|i| !known.contains(i)) {
+            notify(id, &audit);
+        }
+
+        dates.push(audit);
+    }
+
+    let audit = MediaAudit {
+        checked: Some(Local::now()),
+        dates,
+    };
+
+    debug!(target: Target::file(), channel = id;
+        "[Audit] Checked <yellow>{}</> playlists, <yellow>{}</> problems",
+        audit.dates.len(),
+        audit.issues()
+    );
+
+    *manager.media_audit.lock().await = audit;
+}
+
+/// Audit loop, runs in its own task as long as the server is running.
+pub async fn run(controllers: Arc<Mutex<ChannelController>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(AUDIT_INTERVAL));
+
+    loop {
+        interval.tick().await;
+
+        let managers = controllers.lock().await.channels.clone();
+
+        for manager in managers {
+            let mode = manager.config.lock().await.processing.mode.clone();
+
+            if manager.channel.lock().await.active && mode == ProcessMode::Playlist {
+                audit_channel(&manager).await;
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     ffi::OsStr,
     fmt,
     io::Error,
-    net::TcpListener,
+    net::{TcpListener, UdpSocket},
     path::{Path, PathBuf},
     process::{exit, Stdio},
     str::FromStr,
@@ -25,11 +25,28 @@ use tokio::{
     sync::Mutex,
 };
 
+pub mod catch_up;
+pub mod channel_state;
+pub mod coming_up;
+pub mod concat;
+pub mod drift;
 pub mod folder;
+pub mod freeze;
+pub mod health;
 pub mod import;
 pub mod json_serializer;
 pub mod json_validate;
+pub mod media_audit;
+pub mod playlist_store;
+pub mod preload;
 pub mod probe;
+pub mod progress;
+pub mod restart;
+pub mod resume;
+pub mod scte35;
+pub mod slideshow;
+pub mod supervisor;
+pub mod vast;
 
 use crate::player::{
     controller::{
@@ -39,12 +56,16 @@ use crate::player::{
     filter::{filter_chains, Filters},
 };
 use crate::utils::{
-    config::{OutputMode::*, PlayoutConfig, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS},
+    config::{OutputMode::*, PlayoutConfig, SeekMode, FFMPEG_IGNORE_ERRORS},
     errors::ServiceError,
     logging::Target,
     time_machine::time_now,
 };
+use channel_state::ChannelState;
+use health::{output_lost, OUTPUT_ERRORS};
 pub use json_serializer::{read_json, JsonPlaylist};
+use progress::{is_progress, EncoderStats};
+use supervisor::is_fatal;
 
 use crate::vec_strings;
 
@@ -173,6 +194,10 @@ pub async fn get_data_map(manager: &ChannelManager) -> Map<String, Value> {
     let channel = manager.channel.lock().await.clone();
     let config = manager.config.lock().await.processing.clone();
     let ingest_is_alive = manager.ingest_is_alive.load(Ordering::SeqCst);
+    let emergency = manager.emergency.load(Ordering::SeqCst);
+    let program = manager.program.lock().await.clone();
+    let drift = manager.drift.lock().await.clone();
+    let state = manager.state.lock().await.state;
 
     let mut data_map = Map::new();
     let current_time = time_in_seconds(&channel.timezone);
@@ -180,8 +205,11 @@ pub async fn get_data_map(manager: &ChannelManager) -> Map<String, Value> {
     let begin = media.begin.unwrap_or(0.0) - shift;
     let played_time = current_time - begin;
 
+    data_map.insert("state".to_string(), json!(state));
     data_map.insert("index".to_string(), json!(media.index));
     data_map.insert("ingest".to_string(), json!(ingest_is_alive));
+    data_map.insert("emergency".to_string(), json!(emergency));
+    data_map.insert("program".to_string(), json!(program));
     data_map.insert("mode".to_string(), json!(config.mode));
     data_map.insert(
         "shift".to_string(),
@@ -192,6 +220,7 @@ pub async fn get_data_map(manager: &ChannelManager) -> Map<String, Value> {
         json!((played_time * 1000.0).round() / 1000.0),
     );
     data_map.insert("media".to_string(), get_media_map(media));
+    data_map.insert("drift".to_string(), json!(drift));
 
     data_map
 }
@@ -230,6 +259,18 @@ pub struct Media {
     )]
     pub audio: String,
 
+    /// Played instead of the source, when the source is missing or can't be probed.
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub backup_source: String,
+
+    /// Seek for this clip, instead of the one from the processing config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seek_mode: Option<SeekMode>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub cmd: Option<Vec<String>>,
 
@@ -254,6 +295,10 @@ pub struct Media {
     #[serde(default, skip_serializing, skip_deserializing)]
     pub skip: bool,
 
+    /// Play faster by this factor, to catch up with the schedule.
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub speed: Option<f64>,
+
     #[serde(default, skip_serializing)]
     pub unit: ProcessUnit,
 }
@@ -282,6 +327,8 @@ impl Media {
             category: String::new(),
             source: src.to_string(),
             audio: String::new(),
+            backup_source: String::new(),
+            seek_mode: None,
             cmd: Some(vec_strings!["-i", src]),
             filter: None,
             custom_filter: String::new(),
@@ -290,6 +337,7 @@ impl Media {
             last_ad: false,
             next_ad: false,
             skip: false,
+            speed: None,
             unit: Decoder,
         }
     }
@@ -361,6 +409,8 @@ impl Default for Media {
             category: String::new(),
             source: String::new(),
             audio: String::new(),
+            backup_source: String::new(),
+            seek_mode: None,
             cmd: Some(vec_strings!["-i", String::new()]),
             filter: None,
             custom_filter: String::new(),
@@ -369,6 +419,7 @@ impl Default for Media {
             last_ad: false,
             next_ad: false,
             skip: false,
+            speed: None,
             unit: Decoder,
         }
     }
@@ -383,6 +434,8 @@ impl PartialEq for Media {
             && self.source == other.source
             && self.category == other.category
             && self.audio == other.audio
+            && self.backup_source == other.backup_source
+            && self.seek_mode == other.seek_mode
             && self.custom_filter == other.custom_filter
     }
 }
@@ -442,6 +495,18 @@ pub fn time_in_seconds(timezone: &Option<Tz>) -> f64 {
         + (local.nanosecond() as f64 / 1000000000.0)
 }
 
+/// Seconds since the clip begin, both in seconds of the day. A clip which started before
+/// midnight has its begin more than half a day after now.
+pub fn played_since(begin: f64, now: f64) -> f64 {
+    let played = now - begin;
+
+    if played < -43200.0 {
+        played + 86400.0
+    } else {
+        played
+    }
+}
+
 /// Get current date for playlist, but check time with conditions:
 ///
 /// - When time is before playlist start, get date from yesterday.
@@ -464,6 +529,39 @@ pub fn get_date(seek: bool, start: f64, get_next: bool, timezone: &Option<Tz>) -
     local.format("%Y-%m-%d").to_string()
 }
 
+/// Get the daylight saving time change, in seconds, between the playlist start on the given date
+/// and the playlist start on the next day.
+///
+/// Positive when the clocks go forward (the playlist day is shorter),
+/// negative when they go back (the playlist day is longer) and zero on normal days.
+pub fn dst_change(date: &str, start: f64, timezone: &Option<Tz>) -> f64 {
+    let tz = time_now(timezone).timezone();
+    let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+        return 0.0;
+    };
+    let time =
+        NaiveTime::from_num_seconds_from_midnight_opt(start as u32 % 86400, 0).unwrap_or_default();
+
+    let offset = |day: NaiveDate| {
+        let local = day.and_time(time);
+
+        // start time can fall into the skipped hour, then take the offset one hour later
+        tz.from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                tz.from_local_datetime(&(local + TimeDelta::hours(1)))
+                    .earliest()
+            })
+            .map(|t| t.offset().fix().local_minus_utc())
+            .unwrap_or_default()
+    };
+
+    match date.succ_opt() {
+        Some(next) => (offset(next) - offset(date)) as f64,
+        None => 0.0,
+    }
+}
+
 pub fn time_from_header(headers: &header::HeaderMap) -> Option<DateTime<Local>> {
     if let Some(time) = headers.get(header::LAST_MODIFIED) {
         if let Ok(t) = time.to_str() {
@@ -581,6 +679,34 @@ pub fn get_delta(config: &PlayoutConfig, begin: &f64) -> (f64, f64) {
     (current_delta, total_delta)
 }
 
+/// Node has a separate audio file or stream.
+pub fn has_audio_input(node: &Media) -> bool {
+    is_remote(&node.audio) || Path::new(&node.audio).is_file()
+}
+
+/// Audio input for an image or a slideshow, a file loops when it is shorter than the clip.
+pub fn image_audio(node: &Media) -> Vec<String> {
+    if is_remote(&node.audio) {
+        return vec_strings!["-i", node.audio];
+    }
+
+    let mut cmd = vec![];
+
+    if Path::new(&node.audio).is_file() {
+        if node.seek > 0.0 {
+            cmd.append(&mut vec_strings!["-ss", node.seek]);
+        }
+
+        if node.duration_audio > 0.0 && node.duration_audio < node.out {
+            cmd.append(&mut vec_strings!["-stream_loop", -1]);
+        }
+
+        cmd.append(&mut vec_strings!["-i", node.audio]);
+    }
+
+    cmd
+}
+
 /// Loop image until target duration is reached.
 pub fn loop_image(config: &PlayoutConfig, node: &Media) -> Vec<String> {
     let duration = node.out - node.seek;
@@ -591,14 +717,7 @@ pub fn loop_image(config: &PlayoutConfig, node: &Media) -> Vec<String> {
         node.source
     );
 
-    if Path::new(&node.audio).is_file() {
-        if node.seek > 0.0 {
-            source_cmd.append(&mut vec_strings!["-ss", node.seek]);
-        }
-
-        source_cmd.append(&mut vec_strings!["-i", node.audio.clone()]);
-    }
-
+    source_cmd.append(&mut image_audio(node));
     source_cmd.append(&mut vec_strings!["-t", duration]);
 
     if config.processing.vtt_enable {
@@ -670,6 +789,48 @@ pub fn loop_filler(config: &PlayoutConfig, node: &Media) -> Vec<String> {
     source_cmd
 }
 
+/// Input options for a stream entry: HTTP streams reconnect when they break off, and with a
+/// fallback, the decoder stops when the stream sends no data, so filler can take over.
+pub fn stream_options(config: &PlayoutConfig, source: &str) -> Vec<String> {
+    let mut cmd = vec![];
+
+    if !is_remote(source) {
+        return cmd;
+    }
+
+    let playlist = &config.playlist;
+
+    if playlist.stream_reconnect > 0 && source.to_lowercase().starts_with("http") {
+        cmd.append(&mut vec_strings![
+            "-reconnect",
+            "1",
+            "-reconnect_streamed",
+            "1",
+            "-reconnect_on_network_error",
+            "1",
+            "-reconnect_delay_max",
+            playlist.stream_reconnect
+        ]);
+
+        if playlist.stream_retries > 0 {
+            cmd.append(&mut vec_strings![
+                "-reconnect_max_retries",
+                playlist.stream_retries
+            ]);
+        }
+    }
+
+    if playlist.stream_fallback > 0.0 {
+        // microseconds
+        cmd.append(&mut vec_strings![
+            "-rw_timeout",
+            (playlist.stream_fallback * 1_000_000.0) as i64
+        ]);
+    }
+
+    cmd
+}
+
 /// Set clip seek in and length value.
 pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String> {
     let loop_count = (node.out / node.duration).ceil() as i32;
@@ -681,7 +842,21 @@ pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String>
     if remote_source && node.probe.clone().and_then(|f| f.format.duration).is_none() {
         node.out -= node.seek;
         node.seek = 0.0;
-    } else if node.seek > 0.5 {
+    }
+
+    let mode = node
+        .seek_mode
+        .clone()
+        .unwrap_or_else(|| config.processing.seek_mode.clone());
+    let output_seek = node.seek > 0.5 && mode == SeekMode::Accurate;
+    // with the seek on the output, the inputs are read from the start
+    let length = if output_seek {
+        node.out
+    } else {
+        node.out - node.seek
+    };
+
+    if node.seek > 0.5 && !output_seek {
         source_cmd.append(&mut vec_strings!["-ss", node.seek]);
     }
 
@@ -691,14 +866,15 @@ pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String>
         source_cmd.append(&mut vec_strings!["-stream_loop", loop_count]);
     }
 
+    source_cmd.append(&mut stream_options(config, &node.source));
     source_cmd.append(&mut vec_strings!["-i", node.source.clone()]);
 
     if node.duration > node.out || remote_source || loop_count > 1 {
-        source_cmd.append(&mut vec_strings!["-t", node.out - node.seek]);
+        source_cmd.append(&mut vec_strings!["-t", length]);
     }
 
     if !node.audio.is_empty() {
-        if node.seek > 0.5 {
+        if node.seek > 0.5 && !output_seek {
             source_cmd.append(&mut vec_strings!["-ss", node.seek]);
         }
 
@@ -712,7 +888,7 @@ pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String>
         source_cmd.append(&mut vec_strings!["-i", node.audio.clone()]);
 
         if cut_audio || loop_audio || remote_source {
-            source_cmd.append(&mut vec_strings!["-t", node.out - node.seek]);
+            source_cmd.append(&mut vec_strings!["-t", length]);
         }
     }
 
@@ -723,7 +899,7 @@ pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String>
             .storage
             .join(config.processing.vtt_dummy.clone().unwrap_or_default());
 
-        if node.seek > 0.5 {
+        if node.seek > 0.5 && !output_seek {
             source_cmd.append(&mut vec_strings!["-ss", node.seek]);
         }
 
@@ -735,7 +911,7 @@ pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String>
             source_cmd.append(&mut vec_strings!["-i", vtt_file.to_string_lossy()]);
 
             if node.duration > node.out || remote_source || loop_count > 1 {
-                source_cmd.append(&mut vec_strings!["-t", node.out - node.seek]);
+                source_cmd.append(&mut vec_strings!["-t", length]);
             }
         } else if vtt_dummy.is_file() {
             source_cmd.append(&mut vec_strings!["-i", vtt_dummy.to_string_lossy()]);
@@ -744,6 +920,22 @@ pub fn seek_and_length(config: &PlayoutConfig, node: &mut Media) -> Vec<String>
         }
     }
 
+    if output_seek {
+        let length = (node.out - node.seek).to_string();
+
+        // options after the last input are for the output, its length counts from the seek
+        match source_cmd
+            .len()
+            .checked_sub(2)
+            .filter(|i| source_cmd[*i] == "-t")
+        {
+            Some(i) => source_cmd[i + 1] = length,
+            None => source_cmd.append(&mut vec_strings!["-t", length]),
+        }
+
+        source_cmd.append(&mut vec_strings!["-ss", node.seek]);
+    }
+
     source_cmd
 }
 
@@ -864,9 +1056,19 @@ pub async fn stderr_reader(
     manager: ChannelManager,
 ) -> Result<(), ServiceError> {
     let id = manager.channel.lock().await.id;
+    let policy = manager.config.lock().await.advanced.supervision.clone();
     let mut lines = buffer.lines();
+    let mut stats = EncoderStats::default();
 
     while let Some(line) = lines.next_line().await? {
+        if is_progress(&line) {
+            if stats.update(&line) {
+                *manager.encoder_stats.lock().await = stats.clone();
+            }
+
+            continue;
+        }
+
         if FFMPEG_IGNORE_ERRORS.iter().any(|i| line.contains(*i))
             || ignore.iter().any(|i| line.contains(i))
         {
@@ -889,13 +1091,16 @@ pub async fn stderr_reader(
                 line.replace("[error] ", "").replace("[fatal] ", "")
             );
 
-            if FFMPEG_UNRECOVERABLE_ERRORS
-                .iter()
-                .any(|i| line.contains(*i))
+            if suffix == Encoder && OUTPUT_ERRORS.iter().any(|e| line.contains(e)) {
+                output_lost(&manager, &line).await;
+            }
+
+            if is_fatal(&policy, &line)
                 || (line.contains("No such file or directory")
                     && !line.contains("failed to delete old segment"))
             {
                 error!(target: Target::file_mail(), channel = id; "Hit unrecoverable error!");
+                manager.set_state(ChannelState::Failed).await;
                 manager.channel.lock().await.active = false;
                 manager.stop_all(false).await?;
             }
@@ -1059,7 +1264,7 @@ pub fn gen_tcp_socket(exclude_socket: String) -> Option<String> {
 
 /// check if tcp port is free
 pub fn is_free_tcp_port(id: i32, url: &str) -> bool {
-    let re = Regex::new(r"^[\w]+://([^/]+)").unwrap();
+    let re = Regex::new(r"^[\w]+://([^/?]+)").unwrap();
     let mut addr = url.to_string();
 
     if let Some(base_url) = re.captures(url).and_then(|u| u.get(1)) {
@@ -1067,12 +1272,14 @@ pub fn is_free_tcp_port(id: i32, url: &str) -> bool {
     }
 
     if let Some(socket) = addr.split_once(':') {
-        if TcpListener::bind((
-            socket.0,
-            socket.1.to_string().parse::<u16>().unwrap_or_default(),
-        ))
-        .is_ok()
-        {
+        let port = socket.1.to_string().parse::<u16>().unwrap_or_default();
+
+        // SRT runs over UDP
+        if url.starts_with("srt://") {
+            if UdpSocket::bind((socket.0, port)).is_ok() {
+                return true;
+            }
+        } else if TcpListener::bind((socket.0, port)).is_ok() {
             return true;
         }
     };
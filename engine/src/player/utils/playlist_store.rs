@@ -0,0 +1,360 @@
+/// Playlist Storage
+///
+/// Playlists are saved as JSON files in the playlist folder from the channel, or in the database.
+/// The database backend writes in transactions, keeps every replaced version in a history
+/// and rejects saves which are based on an outdated version, or have no version for an existing playlist.
+use std::{future::Future, path::PathBuf};
+
+use chrono::{Local, NaiveDate};
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::fs;
+
+use crate::db::{
+    handles,
+    models::{PlaylistRecord, PlaylistWrite},
+};
+use crate::player::utils::{json_reader, json_writer, modified_time, JsonPlaylist};
+use crate::utils::{
+    config::{PlaylistBackend, PlayoutConfig},
+    errors::ServiceError,
+};
+
+pub trait PlaylistStore {
+    /// Read playlist from the given date, `None` when no playlist exists.
+    fn read(
+        &self,
+        date: &str,
+    ) -> impl Future<Output = Result<Option<JsonPlaylist>, ServiceError>> + Send;
+
+    /// Save playlist, returns `true` when an existing playlist was replaced.
+    fn write(
+        &self,
+        playlist: &JsonPlaylist,
+    ) -> impl Future<Output = Result<bool, ServiceError>> + Send;
+
+    /// Delete playlist, returns `false` when there was nothing to delete.
+    fn delete(&self, date: &str) -> impl Future<Output = Result<bool, ServiceError>> + Send;
+
+    /// Modification marker, changes on every save.
+    fn modified(&self, date: &str) -> impl Future<Output = Option<String>> + Send;
+
+    /// Replaced versions from the playlist, newest first.
+    fn history(
+        &self,
+        _date: &str,
+    ) -> impl Future<Output = Result<Vec<JsonPlaylist>, ServiceError>> + Send {
+        async { Ok(vec![]) }
+    }
+
+    /// Dates from the stored playlists, beginning with `from`, in order.
+    fn dates(&self, from: &str) -> impl Future<Output = Result<Vec<String>, ServiceError>> + Send;
+
+    /// Location from the playlist, for log messages.
+    fn location(&self, date: &str) -> String;
+}
+
+/// JSON files, sorted in year/month folders.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn path(&self, date: &str) -> PathBuf {
+        // a single playlist file can be set as playlist path
+        if self
+            .root
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        {
+            return self.root.clone();
+        }
+
+        let d: Vec<&str> = date.split('-').collect();
+
+        self.root
+            .join(d[0])
+            .join(d.get(1).unwrap_or(&""))
+            .join(date)
+            .with_extension("json")
+    }
+}
+
+impl PlaylistStore for FileStore {
+    async fn read(&self, date: &str) -> Result<Option<JsonPlaylist>, ServiceError> {
+        let path = self.path(date);
+
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let mut playlist = json_reader(&path).await?;
+        let path = path.to_string_lossy().to_string();
+
+        playlist.modified = modified_time(&path).await;
+        playlist.path = Some(path);
+
+        Ok(Some(playlist))
+    }
+
+    async fn write(&self, playlist: &JsonPlaylist) -> Result<bool, ServiceError> {
+        let path = self.path(&playlist.date);
+        let exists = path.is_file();
+        let mut playlist = playlist.clone();
+        playlist.version = None;
+
+        if let Some(p) = path.parent() {
+            fs::create_dir_all(p).await?;
+        }
+
+        json_writer(&path, playlist).await?;
+
+        Ok(exists)
+    }
+
+    async fn delete(&self, date: &str) -> Result<bool, ServiceError> {
+        let path = self.path(date);
+
+        if !path.is_file() {
+            return Ok(false);
+        }
+
+        fs::remove_file(path).await?;
+
+        Ok(true)
+    }
+
+    async fn modified(&self, date: &str) -> Option<String> {
+        modified_time(&self.path(date).to_string_lossy()).await
+    }
+
+    async fn dates(&self, from: &str) -> Result<Vec<String>, ServiceError> {
+        // a single playlist file is played every day
+        if self.path(from) == self.root {
+            return Ok(if self.root.is_file() {
+                vec![from.to_string()]
+            } else {
+                vec![]
+            });
+        }
+
+        let mut dates = vec![];
+        let mut folders = vec![self.root.clone()];
+
+        // only the year and month folders are searched
+        for _ in 0..2 {
+            let mut next = vec![];
+
+            for folder in folders {
+                let Ok(mut entries) = fs::read_dir(&folder).await else {
+                    continue;
+                };
+
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.path().is_dir() {
+                        next.push(entry.path());
+                    }
+                }
+            }
+
+            folders = next;
+        }
+
+        for folder in folders {
+            let mut entries = fs::read_dir(&folder).await?;
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+
+                if let Some(date) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .filter(|d| {
+                        NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok()
+                            && path.extension().is_some_and(|e| e == "json")
+                    })
+                    .filter(|d| *d >= from)
+                {
+                    dates.push(date.to_string());
+                }
+            }
+        }
+
+        dates.sort();
+
+        Ok(dates)
+    }
+
+    fn location(&self, date: &str) -> String {
+        self.path(date).to_string_lossy().to_string()
+    }
+}
+
+/// Tables `playlists` and `playlist_history` in the database.
+#[derive(Clone, Debug)]
+pub struct DbStore {
+    pool: Pool<Sqlite>,
+    channel_id: i32,
+}
+
+impl DbStore {
+    pub fn new(pool: Pool<Sqlite>, channel_id: i32) -> Self {
+        Self { pool, channel_id }
+    }
+
+    fn to_playlist(&self, record: PlaylistRecord) -> Result<JsonPlaylist, ServiceError> {
+        Ok(JsonPlaylist {
+            channel: record.name,
+            path: Some(self.location(&record.date)),
+            date: record.date,
+            start_sec: None,
+            length: None,
+            modified: Some(record.modified),
+            version: Some(record.version),
+            program: serde_json::from_str(&record.program)?,
+        })
+    }
+}
+
+impl PlaylistStore for DbStore {
+    async fn read(&self, date: &str) -> Result<Option<JsonPlaylist>, ServiceError> {
+        handles::select_playlist(&self.pool, self.channel_id, date)
+            .await?
+            .map(|r| self.to_playlist(r))
+            .transpose()
+    }
+
+    async fn write(&self, playlist: &JsonPlaylist) -> Result<bool, ServiceError> {
+        let record = PlaylistRecord {
+            id: 0,
+            channel_id: self.channel_id,
+            date: playlist.date.clone(),
+            name: playlist.channel.clone(),
+            program: serde_json::to_string(&playlist.program)?,
+            version: playlist.version.unwrap_or_default(),
+            modified: Local::now().to_rfc3339(),
+        };
+
+        match handles::upsert_playlist(&self.pool, &record, playlist.version).await? {
+            PlaylistWrite::Inserted => Ok(false),
+            PlaylistWrite::Updated => Ok(true),
+            PlaylistWrite::Outdated(version) => Err(ServiceError::Conflict(format!(
+                "Playlist from {} was changed in the meantime (version {version}), reload it before saving!",
+                playlist.date
+            ))),
+            PlaylistWrite::Unversioned(version) => Err(ServiceError::Conflict(format!(
+                "Playlist from {} exists already (version {version}), save it with its version!",
+                playlist.date
+            ))),
+        }
+    }
+
+    async fn delete(&self, date: &str) -> Result<bool, ServiceError> {
+        let result = handles::delete_playlist(&self.pool, self.channel_id, date).await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn modified(&self, date: &str) -> Option<String> {
+        handles::select_playlist_modified(&self.pool, self.channel_id, date)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    async fn history(&self, date: &str) -> Result<Vec<JsonPlaylist>, ServiceError> {
+        handles::select_playlist_history(&self.pool, self.channel_id, date)
+            .await?
+            .into_iter()
+            .map(|r| self.to_playlist(r))
+            .collect()
+    }
+
+    async fn dates(&self, from: &str) -> Result<Vec<String>, ServiceError> {
+        Ok(handles::select_playlist_dates(&self.pool, self.channel_id, from).await?)
+    }
+
+    fn location(&self, date: &str) -> String {
+        format!("database: channel {}, {date}", self.channel_id)
+    }
+}
+
+/// Playlist backend from the channel config.
+#[derive(Clone, Debug)]
+pub enum PlaylistStorage {
+    File(FileStore),
+    Database(DbStore),
+}
+
+impl PlaylistStorage {
+    pub fn new(config: &PlayoutConfig, pool: Option<Pool<Sqlite>>) -> Self {
+        match (&config.playlist.backend, pool) {
+            (PlaylistBackend::Database, Some(pool)) => {
+                Self::Database(DbStore::new(pool, config.general.channel_id))
+            }
+            (backend, _) => {
+                if *backend == PlaylistBackend::Database {
+                    warn!("No database connection, read playlists from files");
+                }
+
+                Self::File(FileStore::new(config.channel.playlists.clone()))
+            }
+        }
+    }
+}
+
+impl PlaylistStore for PlaylistStorage {
+    async fn read(&self, date: &str) -> Result<Option<JsonPlaylist>, ServiceError> {
+        match self {
+            Self::File(store) => store.read(date).await,
+            Self::Database(store) => store.read(date).await,
+        }
+    }
+
+    async fn write(&self, playlist: &JsonPlaylist) -> Result<bool, ServiceError> {
+        match self {
+            Self::File(store) => store.write(playlist).await,
+            Self::Database(store) => store.write(playlist).await,
+        }
+    }
+
+    async fn delete(&self, date: &str) -> Result<bool, ServiceError> {
+        match self {
+            Self::File(store) => store.delete(date).await,
+            Self::Database(store) => store.delete(date).await,
+        }
+    }
+
+    async fn modified(&self, date: &str) -> Option<String> {
+        match self {
+            Self::File(store) => store.modified(date).await,
+            Self::Database(store) => store.modified(date).await,
+        }
+    }
+
+    async fn history(&self, date: &str) -> Result<Vec<JsonPlaylist>, ServiceError> {
+        match self {
+            Self::File(store) => store.history(date).await,
+            Self::Database(store) => store.history(date).await,
+        }
+    }
+
+    async fn dates(&self, from: &str) -> Result<Vec<String>, ServiceError> {
+        match self {
+            Self::File(store) => store.dates(from).await,
+            Self::Database(store) => store.dates(from).await,
+        }
+    }
+
+    fn location(&self, date: &str) -> String {
+        match self {
+            Self::File(store) => store.location(date),
+            Self::Database(store) => store.location(date),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+/// Next Clip
+///
+/// While a clip plays, the next one from the playlist is probed, so a missing or broken file is
+/// reported before its turn, and the probe is ready when the clip starts. When the clip can't be
+/// probed, its backup source takes its place in the list. With `processing.preload` a decoder
+/// reads the first seconds from the clip too, which fills the caches from slow network storage
+/// and shows decoding errors early. Ad breaks get their ads, and clips which break the house format
+/// are replaced by their backup or the filler.
+use std::{path::Path, process::Stdio, time::Duration};
+
+use log::*;
+use tokio::{process::Command, time::timeout};
+
+use crate::player::{
+    controller::ChannelManager,
+    utils::{
+        is_remote,
+        media_audit::check_clip,
+        probe::MediaProbe,
+        sec_to_time,
+        vast::{prepare_break, AD_BREAK},
+        Media,
+    },
+};
+use crate::utils::{house_format, logging::Target};
+use crate::vec_strings;
+
+/// Seconds the preload decoder reads.
+const PRELOAD_LENGTH: f64 = 3.0;
+
+/// Preload decoder which needs longer is stopped.
+const PRELOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Decode the start from the clip, without output.
+pub fn preload_cmd(node: &Media) -> Vec<String> {
+    let mut cmd = vec_strings!["-hide_banner", "-nostats", "-v", "error"];
+
+    if node.seek > 0.0 {
+        cmd.append(&mut vec_strings!["-ss", format!("{:.3}", node.seek)]);
+    }
+
+    cmd.append(&mut vec_strings![
+        "-i",
+        node.source,
+        "-t",
+        PRELOAD_LENGTH,
+        "-f",
+        "null",
+        "-"
+    ]);
+
+    cmd
+}
+
+async fn preload(id: i32, node: &Media) {
+    let child = Command::new("ffmpeg")
+        .args(preload_cmd(node))
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    match timeout(PRELOAD_TIMEOUT, child).await {
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            if !output.status.success() || !stderr.trim().is_empty() {
+                warn!(target: Target::file_mail(), channel = id;
+                    "Next clip <b><magenta>{}</></b> has decoding errors: {}",
+                    node.source,
+                    stderr.trim()
+                );
+            }
+        }
+        Ok(Err(e)) => error!(target: Target::file_mail(), channel = id; "Preload failed: {e}"),
+        Err(_) => warn!(target: Target::file_mail(), channel = id;
+            "Preload from <b><magenta>{}</></b> takes longer then {} seconds",
+            node.source,
+            PRELOAD_TIMEOUT.as_secs()
+        ),
+    }
+}
+
+/// Probe, and preload, the clip on `index` from the current list.
+pub async fn prepare_next(manager: ChannelManager, index: usize) {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+
+    let Some(mut node) = manager.current_list.lock().await.get(index).cloned() else {
+        return;
+    };
+
+    if node.category == AD_BREAK {
+        prepare_break(&manager, &node).await;
+        return;
+    }
+
+    // slideshow folders get their probe from the first image
+    if node.source.is_empty() || Path::new(&node.source).is_dir() {
+        return;
+    }
+
+    let source = node.source.clone();
+    let begin = sec_to_time(node.begin.unwrap_or_default());
+
+    if node.probe.is_none() {
+        let mut result = MediaProbe::new(node.source.as_str()).await;
+
+        if result.is_err() && !node.backup_source.is_empty() {
+            warn!(target: Target::file_mail(), channel = id;
+                "Next clip at <yellow>{begin}</> is not usable: <b><magenta>{}</></b>, play backup: <b><magenta>{}</></b>",
+                node.source,
+                node.backup_source
+            );
+
+            node.source = std::mem::take(&mut node.backup_source);
+            result = MediaProbe::new(node.source.as_str()).await;
+        }
+
+        match result {
+            Ok(probe) => {
+                let problems = check_clip(&node, &Ok(probe.clone()), &config);
+
+                if !problems.is_empty() {
+                    warn!(target: Target::file_mail(), channel = id;
+                        "Next clip at <yellow>{begin}</>: <b><magenta>{}</></b>, {}",
+                        node.source,
+                        problems.join(", ")
+                    );
+                }
+
+                node.probe = Some(probe);
+            }
+            Err(e) => {
+                error!(target: Target::file_mail(), channel = id;
+                    "Next clip at <yellow>{begin}</> is not usable: <b><magenta>{}</></b>, {e}",
+                    node.source
+                );
+
+                return;
+            }
+        }
+
+        if let Some(probe) = node
+            .probe
+            .as_ref()
+            .filter(|_| house_format::applies(&config, Path::new(&node.source)))
+        {
+            let problems = house_format::check_probe(
+                &config.storage.house_format,
+                Path::new(&node.source),
+                probe,
+            )
+            .await;
+
+            if !problems.is_empty() {
+                error!(target: Target::file_mail(), channel = id;
+                    "Next clip at <yellow>{begin}</> breaks the house format: <b><magenta>{}</></b>, {}",
+                    node.source,
+                    problems.join(", ")
+                );
+
+                if node.backup_source.is_empty() {
+                    // empty filler entries play the filler
+                    node.source.clear();
+                    node.probe = None;
+                    node.category = "filler".to_string();
+                } else {
+                    node.source = std::mem::take(&mut node.backup_source);
+                    node.probe = MediaProbe::new(node.source.as_str()).await.ok();
+                }
+            }
+        }
+
+        // the list can be reloaded in the meantime
+        if let Some(item) = manager
+            .current_list
+            .lock()
+            .await
+            .get_mut(index)
+            .filter(|item| item.probe.is_none() && item.source == source)
+        {
+            item.source.clone_from(&node.source);
+            item.backup_source.clone_from(&node.backup_source);
+            item.probe.clone_from(&node.probe);
+            item.category.clone_from(&node.category);
+        }
+    }
+
+    if config.processing.preload && !node.source.is_empty() && !is_remote(&node.source) {
+        preload(id, &node).await;
+    }
+}
@@ -96,6 +96,8 @@ pub struct Stream {
 #[serde_as]
 #[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Format {
+    #[serde(default)]
+    pub format_name: Option<String>,
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub duration: Option<f64>,
     pub nb_streams: i64,
@@ -107,6 +109,9 @@ pub struct Format {
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub struct MediaFormat {
+    /// Container names, like `mov,mp4,m4a,3gp,3g2,mj2`.
+    #[serde(default)]
+    pub format_name: Option<String>,
     pub duration: Option<f64>,
     pub nb_streams: i64,
     pub size: i64,
@@ -115,6 +120,7 @@ pub struct MediaFormat {
 impl MediaFormat {
     pub fn new(format: Format) -> Self {
         Self {
+            format_name: format.format_name,
             duration: format.duration,
             nb_streams: format.nb_streams,
             size: format.size.unwrap_or_default(),
@@ -0,0 +1,72 @@
+/// Encoder Progress
+///
+/// The encoder runs with `-progress pipe:2`, ffmpeg writes then every half second a block
+/// of `key=value` lines to stderr, which ends with `progress=continue` or `progress=end`.
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+/// Last complete progress block from the encoder.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct EncoderStats {
+    pub frame: u64,
+    pub fps: f64,
+    /// Bitrate in kbit/s.
+    pub bitrate: f64,
+    pub total_size: u64,
+    /// Encoded time in seconds.
+    pub out_time: f64,
+    pub speed: f64,
+    pub dup_frames: u64,
+    pub drop_frames: u64,
+    pub updated: Option<DateTime<Local>>,
+}
+
+impl EncoderStats {
+    /// Take one line, returns `true` when the block is complete.
+    pub fn update(&mut self, line: &str) -> bool {
+        let Some((key, value)) = line.split_once('=') else {
+            return false;
+        };
+
+        let value = value.trim();
+
+        match key {
+            "frame" => self.frame = value.parse().unwrap_or_default(),
+            "fps" => self.fps = value.parse().unwrap_or_default(),
+            "bitrate" => {
+                self.bitrate = value
+                    .trim_end_matches("kbits/s")
+                    .parse()
+                    .unwrap_or_default()
+            }
+            "total_size" => self.total_size = value.parse().unwrap_or_default(),
+            "out_time_us" => {
+                self.out_time = value
+                    .parse::<f64>()
+                    .map(|t| t / 1_000_000.0)
+                    .unwrap_or_default()
+            }
+            "speed" => self.speed = value.trim_end_matches('x').parse().unwrap_or_default(),
+            "dup_frames" => self.dup_frames = value.parse().unwrap_or_default(),
+            "drop_frames" => self.drop_frames = value.parse().unwrap_or_default(),
+            "progress" => {
+                self.updated = Some(Local::now());
+
+                return true;
+            }
+            _ => {}
+        }
+
+        false
+    }
+}
+
+/// Progress lines have no log level prefix and the key has no spaces.
+pub fn is_progress(line: &str) -> bool {
+    line.split_once('=').is_some_and(|(key, _)| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    })
+}
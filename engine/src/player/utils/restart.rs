@@ -0,0 +1,65 @@
+/// Restart Policy
+///
+/// Decide what happens when a channel fails: retry with a growing delay, switch to a slate
+/// after too many failures in a row, or stop the channel when it restarts too often in one hour.
+/// Without limits a broken output target gets a new connection every few seconds, forever.
+use std::{cmp, collections::VecDeque, time::Duration};
+
+use crate::utils::config::General;
+
+/// A run which lasted this seconds counts as stable, the failures in a row start new.
+const STABLE_RUN: f64 = 60.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestartAction {
+    Retry(Duration),
+    /// Play the slate instead of the program, then retry.
+    Slate(Duration),
+    Stop,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RestartState {
+    restarts: VecDeque<i64>,
+    delay: u64,
+    pub failures: i64,
+}
+
+impl RestartState {
+    /// Channel failed at `now` (timestamp in seconds), after it was running for `run_time` seconds.
+    pub fn failed(&mut self, general: &General, now: i64, run_time: f64) -> RestartAction {
+        if run_time >= STABLE_RUN {
+            self.failures = 0;
+            self.delay = 0;
+        }
+
+        self.failures += 1;
+        self.restarts.retain(|t| now - t < 3600);
+        self.restarts.push_back(now);
+
+        if general.restart_limit > 0 && self.restarts.len() as i64 > general.restart_limit {
+            return RestartAction::Stop;
+        }
+
+        let min_delay = general.restart_delay.max(1) as u64;
+        let max_delay = cmp::max(general.restart_max_delay.max(0) as u64, min_delay);
+
+        self.delay = match self.delay {
+            0 => min_delay,
+            delay => cmp::min(delay * 2, max_delay),
+        };
+
+        let delay = Duration::from_secs(self.delay);
+
+        if general.restart_slate > 0 && self.failures >= general.restart_slate {
+            return RestartAction::Slate(delay);
+        }
+
+        RestartAction::Retry(delay)
+    }
+
+    /// Restarts in the last hour.
+    pub fn restarts(&self) -> usize {
+        self.restarts.len()
+    }
+}
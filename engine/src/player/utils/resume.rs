@@ -0,0 +1,114 @@
+/// Resume Point
+///
+/// The clip on air and its start time are saved to the channel on every clip change in playlist
+/// mode. After a crash or restart, the init clip is the saved one, with the time since its start
+/// as seek, so the program goes on without a jump. A point from another day, for a changed
+/// playlist, or too far away from the schedule is ignored and the schedule decides like before.
+use chrono::Local;
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::db::handles;
+use crate::player::{controller::ChannelManager, utils::Media};
+use crate::utils::{config::ProcessMode, logging::Target};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResumePoint {
+    /// Playlist date.
+    pub date: String,
+    pub index: usize,
+    pub source: String,
+    /// Seek of the clip when it started.
+    pub seek: f64,
+    /// Unix timestamp in seconds, when the clip started.
+    pub started: f64,
+}
+
+pub fn timestamp() -> f64 {
+    Local::now().timestamp_millis() as f64 / 1000.0
+}
+
+impl ResumePoint {
+    /// Position in the clip at `now`, the clip runs on while the engine is down.
+    pub fn position(&self, now: f64) -> f64 {
+        self.seek + (now - self.started).max(0.0)
+    }
+
+    /// Seek for the node on the saved index, `time_sec` is the playlist time with shift and
+    /// `threshold` the allowed distance to the schedule, 0 allows any.
+    pub fn seek_for(
+        &self,
+        node: &Media,
+        date: &str,
+        now: f64,
+        time_sec: f64,
+        threshold: f64,
+    ) -> Option<f64> {
+        if self.date != date || self.source != node.source {
+            return None;
+        }
+
+        let position = self.position(now);
+
+        // less then a second left is not worth a seek
+        if position < node.seek || position >= node.out - 1.0 {
+            return None;
+        }
+
+        // seconds the resumed clip is behind the schedule
+        let lag = time_sec - node.begin? - (position - node.seek);
+
+        if threshold > 0.0 && lag.abs() > threshold {
+            return None;
+        }
+
+        Some(position)
+    }
+}
+
+/// Save clip, which starts now, as resume point.
+pub async fn record(manager: ChannelManager, node: Media) {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+
+    if config.processing.mode != ProcessMode::Playlist
+        || matches!(
+            node.category.as_str(),
+            "emergency" | "freeze" | "transition" | "secondary"
+        )
+    {
+        return;
+    }
+
+    let (Some(index), Some(pool)) = (node.index, manager.db_pool.clone()) else {
+        return;
+    };
+
+    let point = ResumePoint {
+        date: manager.current_date.lock().await.clone(),
+        index,
+        source: node.source,
+        seek: node.seek,
+        started: timestamp(),
+    };
+
+    let value = serde_json::to_string(&point).unwrap_or_default();
+
+    if let Err(e) = handles::update_resume(&pool, id, &value).await {
+        error!(target: Target::file_mail(), channel = id; "Save resume point failed: {e}");
+    }
+}
+
+/// Last saved point from the channel.
+pub async fn load(manager: &ChannelManager) -> Option<ResumePoint> {
+    let pool = manager.db_pool.clone()?;
+    let id = manager.config.lock().await.general.channel_id;
+
+    match handles::select_resume(&pool, id).await {
+        Ok(value) => value.and_then(|v| serde_json::from_str(&v).ok()),
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = id; "Read resume point failed: {e}");
+            None
+        }
+    }
+}
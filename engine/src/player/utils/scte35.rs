@@ -0,0 +1,585 @@
+/// SCTE-35
+///
+/// With `ingest.scte35` the ingest keeps the SCTE-35 stream from a MPEG-TS input, instead of
+/// dropping it on the encode. In HLS mode the cues go with the segments, and the playlist gets
+/// `#EXT-X-CUE-OUT` and `#EXT-X-CUE-IN` tags on the segments with a cue, for ad insertion after
+/// the output. In stream mode the cues from the ingest are logged and in the event history.
+///
+/// Only `splice_insert` and `time_signal` with segmentation descriptors for breaks and
+/// advertisements are read, encrypted cues are skipped.
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use log::*;
+use tokio::fs;
+
+use crate::player::controller::ChannelManager;
+use crate::utils::{
+    event_history::{self, EventKind},
+    logging::Target,
+};
+use crate::vec_strings;
+
+const PACKET_SIZE: usize = 188;
+const SYNC_BYTE: u8 = 0x47;
+/// Stream type from SCTE-35 in the PMT.
+const STREAM_TYPE_SCTE35: u8 = 0x86;
+const TABLE_SPLICE_INFO: u8 = 0xFC;
+const SPLICE_INSERT: u8 = 0x05;
+const TIME_SIGNAL: u8 = 0x06;
+const SEGMENTATION_DESCRIPTOR: u8 = 0x02;
+/// Segmentation types for the start from breaks, placement opportunities and ad blocks,
+/// the type after each one is its end.
+const SEGMENTATION_STARTS: [u8; 7] = [0x22, 0x30, 0x32, 0x34, 0x36, 0x44, 0x46];
+/// Time between the playlist checks.
+const TAG_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpliceCue {
+    pub event_id: u32,
+    /// Start from a break, otherwise the return to the program.
+    pub out: bool,
+    /// Splice time in seconds, with the PTS adjustment, `None` for immediate.
+    pub pts: Option<f64>,
+    /// Break duration in seconds.
+    pub duration: Option<f64>,
+}
+
+impl SpliceCue {
+    /// HLS tag for the segment with the cue.
+    pub fn hls_tag(&self) -> String {
+        match (self.out, self.duration) {
+            (true, Some(duration)) => format!("#EXT-X-CUE-OUT:{duration:.3}"),
+            (true, None) => "#EXT-X-CUE-OUT".to_string(),
+            (false, _) => "#EXT-X-CUE-IN".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SpliceCue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.out {
+            write!(f, "SCTE-35 break {}", self.event_id)?;
+
+            if let Some(duration) = self.duration {
+                write!(f, " for {duration:.3}s")?;
+            }
+
+            Ok(())
+        } else {
+            write!(f, "SCTE-35 return {}", self.event_id)
+        }
+    }
+}
+
+/// 33 bit time on the 90 kHz clock, from 5 bytes.
+fn time_33(bytes: &[u8]) -> Option<u64> {
+    let b = bytes.get(..5)?;
+
+    Some(
+        (u64::from(b[0] & 0x01) << 32)
+            | (u64::from(b[1]) << 24)
+            | (u64::from(b[2]) << 16)
+            | (u64::from(b[3]) << 8)
+            | u64::from(b[4]),
+    )
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// `splice_time()`, returns the time and the position after it.
+fn splice_time(data: &[u8], pos: usize) -> Option<(Option<u64>, usize)> {
+    if data.get(pos)? & 0x80 != 0 {
+        Some((Some(time_33(data.get(pos..)?)?), pos + 5))
+    } else {
+        Some((None, pos + 1))
+    }
+}
+
+/// `splice_insert()`, returns the cue and the position after the command.
+fn splice_insert(data: &[u8], pos: usize) -> Option<(Option<SpliceCue>, usize)> {
+    let event_id = u32_at(data, pos)?;
+    let mut pos = pos + 4;
+
+    // cancel indicator
+    if data.get(pos)? & 0x80 != 0 {
+        return Some((None, pos + 1));
+    }
+
+    pos += 1;
+
+    let flags = *data.get(pos)?;
+    let out = flags & 0x80 != 0;
+    let program_splice = flags & 0x40 != 0;
+    let has_duration = flags & 0x20 != 0;
+    let immediate = flags & 0x10 != 0;
+    let mut time = None;
+    pos += 1;
+
+    if program_splice {
+        if !immediate {
+            (time, pos) = splice_time(data, pos)?;
+        }
+    } else {
+        let components = *data.get(pos)?;
+        pos += 1;
+
+        for _ in 0..components {
+            // component tag
+            pos += 1;
+
+            if !immediate {
+                let (component_time, next) = splice_time(data, pos)?;
+                time = time.or(component_time);
+                pos = next;
+            }
+        }
+    }
+
+    let mut duration = None;
+
+    if has_duration {
+        duration = Some(time_33(data.get(pos..)?)?);
+        pos += 5;
+    }
+
+    // unique program id, avail num and avails expected
+    pos += 4;
+
+    let cue = SpliceCue {
+        event_id,
+        out,
+        pts: time.map(|t| t as f64),
+        duration: duration.map(|d| d as f64 / 90000.0),
+    };
+
+    Some((Some(cue), pos))
+}
+
+/// Cue from a segmentation descriptor, for breaks and advertisements.
+fn segmentation_cue(data: &[u8], time: Option<u64>) -> Option<SpliceCue> {
+    // identifier CUEI
+    if data.get(..4)? != b"CUEI" {
+        return None;
+    }
+
+    let event_id = u32_at(data, 4)?;
+
+    if data.get(8)? & 0x80 != 0 {
+        return None;
+    }
+
+    let flags = *data.get(9)?;
+    let mut pos = 10;
+
+    if flags & 0x80 == 0 {
+        let components = *data.get(pos)? as usize;
+        pos += 1 + components * 6;
+    }
+
+    let mut duration = None;
+
+    if flags & 0x40 != 0 {
+        let d = data.get(pos..pos + 5)?;
+        duration = Some(d.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)));
+        pos += 5;
+    }
+
+    let upid_length = *data.get(pos + 1)? as usize;
+    pos += 2 + upid_length;
+
+    let type_id = *data.get(pos)?;
+    let out = if SEGMENTATION_STARTS.contains(&type_id) {
+        true
+    } else if SEGMENTATION_STARTS.contains(&type_id.wrapping_sub(1)) {
+        false
+    } else {
+        return None;
+    };
+
+    Some(SpliceCue {
+        event_id,
+        out,
+        pts: time.map(|t| t as f64),
+        duration: duration.filter(|_| out).map(|d| d as f64 / 90000.0),
+    })
+}
+
+/// Cues from a `splice_info_section`.
+pub fn parse_splice_info(section: &[u8]) -> Vec<SpliceCue> {
+    let mut cues = vec![];
+
+    if section.len() < 14 || section[0] != TABLE_SPLICE_INFO || section[4] & 0x80 != 0 {
+        return cues;
+    }
+
+    let section_length = ((usize::from(section[1] & 0x0F) << 8) | usize::from(section[2])) + 3;
+    let Some(section) = section.get(..section_length) else {
+        return cues;
+    };
+    let adjustment = time_33(&section[4..]).unwrap_or_default();
+    let command_length = (usize::from(section[11] & 0x0F) << 8) | usize::from(section[12]);
+    let command_type = section[13];
+    let start = 14;
+
+    let (cue, time, end) = match command_type {
+        SPLICE_INSERT => match splice_insert(section, start) {
+            Some((cue, end)) => (cue, None, end),
+            None => return cues,
+        },
+        TIME_SIGNAL => match splice_time(section, start) {
+            Some((time, end)) => (None, time, end),
+            None => return cues,
+        },
+        _ => return cues,
+    };
+
+    // legacy senders have 0xFFF as command length
+    let end = if command_length == 0xFFF {
+        end
+    } else {
+        start + command_length
+    };
+    let adjust = |t: f64| ((t as u64 + adjustment) & 0x1_FFFF_FFFF) as f64 / 90000.0;
+
+    if let Some(mut cue) = cue {
+        cue.pts = cue.pts.map(adjust);
+        cues.push(cue);
+    }
+
+    if command_type != TIME_SIGNAL {
+        return cues;
+    }
+
+    let Some(loop_length) = section
+        .get(end..end + 2)
+        .map(|b| (usize::from(b[0]) << 8) | usize::from(b[1]))
+    else {
+        return cues;
+    };
+    let mut pos = end + 2;
+    let loop_end = (pos + loop_length).min(section.len().saturating_sub(4));
+
+    while pos + 2 <= loop_end {
+        let tag = section[pos];
+        let length = usize::from(section[pos + 1]);
+
+        if tag == SEGMENTATION_DESCRIPTOR {
+            if let Some(mut cue) = section
+                .get(pos + 2..pos + 2 + length)
+                .and_then(|d| segmentation_cue(d, time))
+            {
+                cue.pts = cue.pts.map(adjust);
+                cues.push(cue);
+            }
+        }
+
+        pos += 2 + length;
+    }
+
+    cues
+}
+
+/// Reads the SCTE-35 cues from a MPEG-TS stream, the PID comes from the PAT and PMT.
+#[derive(Debug, Default)]
+pub struct TsScanner {
+    rest: Vec<u8>,
+    pmt_pids: HashSet<u16>,
+    cue_pids: HashSet<u16>,
+    sections: HashMap<u16, Vec<u8>>,
+}
+
+impl TsScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next bytes, returns the cues which are complete with them.
+    pub fn push(&mut self, data: &[u8]) -> Vec<SpliceCue> {
+        let mut cues = vec![];
+        let mut buf = std::mem::take(&mut self.rest);
+        buf.extend_from_slice(data);
+
+        let mut pos = 0;
+
+        while pos + PACKET_SIZE <= buf.len() {
+            if buf[pos] != SYNC_BYTE {
+                // find the next packet start
+                pos += 1;
+                continue;
+            }
+
+            cues.append(&mut self.packet(&buf[pos..pos + PACKET_SIZE]));
+            pos += PACKET_SIZE;
+        }
+
+        self.rest = buf[pos..].to_vec();
+
+        cues
+    }
+
+    fn packet(&mut self, packet: &[u8]) -> Vec<SpliceCue> {
+        let start = packet[1] & 0x40 != 0;
+        let pid = (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2]);
+        let control = (packet[3] >> 4) & 0x03;
+
+        if pid != 0 && !self.pmt_pids.contains(&pid) && !self.cue_pids.contains(&pid) {
+            return vec![];
+        }
+
+        if control & 0x01 == 0 {
+            return vec![];
+        }
+
+        let mut pos = 4;
+
+        if control & 0x02 != 0 {
+            pos += 1 + usize::from(packet[4]);
+        }
+
+        let Some(payload) = packet.get(pos..) else {
+            return vec![];
+        };
+        let mut sections = vec![];
+        let buffer = self.sections.entry(pid).or_default();
+
+        if start {
+            let Some(pointer) = payload.first().map(|p| usize::from(*p)) else {
+                return vec![];
+            };
+
+            if !buffer.is_empty() {
+                buffer.extend_from_slice(payload.get(1..1 + pointer).unwrap_or_default());
+                sections.append(&mut take_sections(buffer));
+            }
+
+            buffer.clear();
+            buffer.extend_from_slice(payload.get(1 + pointer..).unwrap_or_default());
+        } else if !buffer.is_empty() {
+            buffer.extend_from_slice(payload);
+        }
+
+        sections.append(&mut take_sections(buffer));
+
+        let mut cues = vec![];
+
+        for section in sections {
+            match section[0] {
+                0x00 if pid == 0 => self.read_pat(&section),
+                0x02 => self.read_pmt(&section),
+                TABLE_SPLICE_INFO => cues.append(&mut parse_splice_info(&section)),
+                _ => {}
+            }
+        }
+
+        cues
+    }
+
+    fn read_pat(&mut self, section: &[u8]) {
+        let end = section.len().saturating_sub(4);
+        let mut pos = 8;
+
+        while pos + 4 <= end {
+            let program = (u16::from(section[pos]) << 8) | u16::from(section[pos + 1]);
+
+            if program != 0 {
+                self.pmt_pids.insert(
+                    (u16::from(section[pos + 2] & 0x1F) << 8) | u16::from(section[pos + 3]),
+                );
+            }
+
+            pos += 4;
+        }
+    }
+
+    fn read_pmt(&mut self, section: &[u8]) {
+        if section.len() < 12 {
+            return;
+        }
+
+        let end = section.len().saturating_sub(4);
+        let info_length = (usize::from(section[10] & 0x0F) << 8) | usize::from(section[11]);
+        let mut pos = 12 + info_length;
+
+        while pos + 5 <= end {
+            let stream_type = section[pos];
+            let pid = (u16::from(section[pos + 1] & 0x1F) << 8) | u16::from(section[pos + 2]);
+            let es_length =
+                (usize::from(section[pos + 3] & 0x0F) << 8) | usize::from(section[pos + 4]);
+
+            if stream_type == STREAM_TYPE_SCTE35 {
+                self.cue_pids.insert(pid);
+            }
+
+            pos += 5 + es_length;
+        }
+    }
+}
+
+/// Complete sections from the start of the buffer, stuffing ends the sections in a packet.
+fn take_sections(buffer: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut sections = vec![];
+
+    while buffer.len() >= 3 {
+        if buffer[0] == 0xFF {
+            buffer.clear();
+            break;
+        }
+
+        let length = ((usize::from(buffer[1] & 0x0F) << 8) | usize::from(buffer[2])) + 3;
+
+        if buffer.len() < length {
+            break;
+        }
+
+        sections.push(buffer.drain(..length).collect());
+    }
+
+    sections
+}
+
+/// ffmpeg arguments, which keep the SCTE-35 stream from the ingest input.
+pub fn passthrough_args() -> Vec<String> {
+    vec_strings!["-map", "0:d?", "-c:d", "copy"]
+}
+
+/// Playlist with the cue tags before their segments, tags from an earlier run are replaced.
+pub fn tag_playlist(playlist: &str, tags: &HashMap<String, Vec<String>>) -> String {
+    let mut lines: Vec<&str> = vec![];
+    let mut block = 0;
+
+    for line in playlist.lines() {
+        if line.starts_with("#EXT-X-CUE-OUT") || line.starts_with("#EXT-X-CUE-IN") {
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with('#') {
+            lines.push(line);
+            continue;
+        }
+
+        if let Some(segment_tags) = tags.get(line) {
+            // the tags go before the EXTINF and date from the segment
+            let mut at = lines.len();
+
+            while at > block && lines[at - 1].starts_with("#EXT") && !is_header(lines[at - 1]) {
+                at -= 1;
+            }
+
+            for tag in segment_tags.iter().rev() {
+                lines.insert(at, tag.as_str());
+            }
+        }
+
+        lines.push(line);
+        block = lines.len();
+    }
+
+    let mut tagged = lines.join("\n");
+    tagged.push('\n');
+
+    tagged
+}
+
+fn is_header(line: &str) -> bool {
+    [
+        "#EXTM3U",
+        "#EXT-X-VERSION",
+        "#EXT-X-TARGETDURATION",
+        "#EXT-X-MEDIA-SEQUENCE",
+        "#EXT-X-DISCONTINUITY-SEQUENCE",
+        "#EXT-X-PLAYLIST-TYPE",
+        "#EXT-X-INDEPENDENT-SEGMENTS",
+        "#EXT-X-ALLOW-CACHE",
+    ]
+    .iter()
+    .any(|h| line.starts_with(h))
+}
+
+/// Log the cue and add it to the event history.
+pub fn report(id: i32, cue: &SpliceCue) {
+    info!(target: Target::file_mail(), channel = id; "Live ingest: <yellow>{cue}</>");
+    event_history::push(id, EventKind::Ingest, cue.to_string());
+}
+
+fn segment_path(playlist: &Path, uri: &str) -> PathBuf {
+    let uri = uri.split('?').next().unwrap_or(uri);
+
+    match playlist.parent() {
+        Some(parent) if !uri.starts_with('/') => parent.join(uri),
+        _ => PathBuf::from(uri),
+    }
+}
+
+/// Scan the new segments from the HLS playlist for cues and tag them in the playlist, as long
+/// as the channel runs. ffmpeg writes the playlist new with every segment, the tags are written
+/// again then.
+pub async fn tag_segments(manager: ChannelManager, playlist: PathBuf) {
+    let id = manager.channel.lock().await.id;
+    let mut scanned: HashSet<String> = HashSet::new();
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+
+    while manager.is_alive.load(Ordering::SeqCst) {
+        tokio::time::sleep(TAG_INTERVAL).await;
+
+        let Ok(content) = fs::read_to_string(&playlist).await else {
+            continue;
+        };
+        let segments: Vec<String> = content
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+
+        for uri in &segments {
+            if scanned.contains(uri) {
+                continue;
+            }
+
+            let Ok(data) = fs::read(segment_path(&playlist, uri)).await else {
+                continue;
+            };
+
+            scanned.insert(uri.clone());
+
+            let cues = TsScanner::new().push(&data);
+
+            for cue in &cues {
+                report(id, cue);
+            }
+
+            if !cues.is_empty() {
+                tags.insert(uri.clone(), cues.iter().map(SpliceCue::hls_tag).collect());
+            }
+        }
+
+        // forget the segments which are out of the list
+        scanned.retain(|uri| segments.contains(uri));
+        tags.retain(|uri, _| segments.contains(uri));
+
+        if tags.is_empty() {
+            continue;
+        }
+
+        let tagged = tag_playlist(&content, &tags);
+
+        if tagged != content {
+            let tmp = playlist.with_extension("m3u8.cue");
+
+            if let Err(e) = fs::write(&tmp, &tagged).await {
+                error!(target: Target::file_mail(), channel = id; "Write cue tags: {e}");
+                continue;
+            }
+
+            if let Err(e) = fs::rename(&tmp, &playlist).await {
+                error!(target: Target::file_mail(), channel = id; "Write cue tags: {e}");
+            }
+        }
+    }
+}
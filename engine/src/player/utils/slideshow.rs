@@ -0,0 +1,84 @@
+/// Slideshow
+///
+/// A playlist entry can have a folder with images as source, mostly together with an audio file or
+/// stream, for radio on TV or announcements. The images are shown in name order, each one for
+/// `SLIDE_DURATION` seconds, and the show starts again until the clip length is reached.
+/// Like a single image, the entry needs the length in `out`.
+use std::{fs, io, path::PathBuf};
+
+use crate::player::utils::{concat::quote, image_audio, Media};
+use crate::utils::config::{PlayoutConfig, IMAGE_FORMAT};
+use crate::vec_strings;
+
+/// Seconds one image is shown.
+pub const SLIDE_DURATION: f64 = 10.0;
+
+/// Images from the folder, sorted by name, empty when the source is no folder.
+pub fn slide_images(source: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(source) else {
+        return vec![];
+    };
+
+    let mut images: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension().is_some_and(|e| {
+                    IMAGE_FORMAT.contains(&e.to_string_lossy().to_lowercase().as_str())
+                })
+        })
+        .collect();
+
+    images.sort();
+
+    images
+}
+
+/// List for the concat demuxer. The last entry is the first image again, the demuxer ignores the
+/// duration from the last file, and with the loop it comes next anyway.
+pub fn slideshow_list(images: &[PathBuf], slide: f64) -> String {
+    let mut list = "ffconcat version 1.0\n".to_string();
+
+    for image in images {
+        list.push_str(&format!("file {}\n", quote(&image.to_string_lossy())));
+        list.push_str(&format!("duration {slide}\n"));
+    }
+
+    if let Some(first) = images.first() {
+        list.push_str(&format!("file {}\n", quote(&first.to_string_lossy())));
+    }
+
+    list
+}
+
+/// Write the list and loop it, with the audio, until the clip length is reached.
+pub fn slideshow_cmd(
+    config: &PlayoutConfig,
+    node: &Media,
+    images: &[PathBuf],
+) -> io::Result<Vec<String>> {
+    let list = list_path(config.general.channel_id, node.index.unwrap_or_default());
+
+    fs::write(&list, slideshow_list(images, SLIDE_DURATION))?;
+
+    let mut cmd = vec_strings![
+        "-stream_loop",
+        "-1",
+        "-f",
+        "concat",
+        "-safe",
+        "0",
+        "-i",
+        list.to_string_lossy()
+    ];
+
+    cmd.append(&mut image_audio(node));
+    cmd.append(&mut vec_strings!["-t", node.out - node.seek]);
+
+    Ok(cmd)
+}
+
+fn list_path(id: i32, index: usize) -> PathBuf {
+    std::env::temp_dir().join(format!("ffplayout_slides_{id}_{index}.txt"))
+}
@@ -0,0 +1,155 @@
+/// Process Supervision
+///
+/// Decoder and ingest processes are started again, when they end. A process which ends faster
+/// than `min_runtime` counts as failed start: the next start waits a backoff, which doubles up to
+/// `max_backoff`, and after `max_failures` in a row the channel gives up. ffmpeg messages from the
+/// fatal list stop the channel at once. The state of all processes is shown by the status API.
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tokio::time::sleep;
+
+use crate::player::controller::{ChannelManager, ProcessUnit};
+use crate::utils::{advanced_config::SupervisionConfig, config::FFMPEG_UNRECOVERABLE_ERRORS};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Respawn {
+    Now,
+    After(Duration),
+    GiveUp,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ProcessState {
+    pub running: bool,
+    pub starts: u64,
+    /// Failed starts in a row.
+    pub failures: i64,
+    pub last_start: Option<DateTime<Local>>,
+    pub last_exit: Option<DateTime<Local>>,
+    /// Milliseconds the next start waits.
+    pub backoff: u64,
+}
+
+impl ProcessState {
+    pub fn started(&mut self, now: DateTime<Local>) {
+        self.running = true;
+        self.starts += 1;
+        self.last_start = Some(now);
+    }
+
+    /// Stopped on purpose, this is no failure.
+    pub fn stopped(&mut self, now: DateTime<Local>) {
+        if self.running {
+            self.running = false;
+            self.last_exit = Some(now);
+        }
+    }
+
+    /// Process ended by itself, decide about the next start.
+    pub fn ended(&mut self, policy: &SupervisionConfig, now: DateTime<Local>) -> Respawn {
+        let run_time = self
+            .last_start
+            .map(|start| (now - start).num_milliseconds())
+            .unwrap_or_default();
+
+        self.running = false;
+        self.last_exit = Some(now);
+
+        if run_time >= policy.min_runtime() {
+            self.failures = 0;
+            self.backoff = 0;
+
+            return Respawn::Now;
+        }
+
+        self.failures += 1;
+
+        if policy.max_failures() > 0 && self.failures > policy.max_failures() {
+            return Respawn::GiveUp;
+        }
+
+        self.backoff = match self.backoff {
+            0 => policy.backoff(),
+            backoff => (backoff * 2).min(policy.max_backoff()),
+        };
+
+        match self.backoff {
+            0 => Respawn::Now,
+            backoff => Respawn::After(Duration::from_millis(backoff)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Supervisor {
+    pub decoder: ProcessState,
+    pub encoder: ProcessState,
+    pub ingest: ProcessState,
+    /// Channel restarts in the last hour, from the restart policy.
+    pub channel_restarts: usize,
+    /// Channel failures in a row.
+    pub channel_failures: i64,
+}
+
+impl Supervisor {
+    pub fn unit(&mut self, unit: ProcessUnit) -> &mut ProcessState {
+        match unit {
+            ProcessUnit::Decoder => &mut self.decoder,
+            ProcessUnit::Encoder => &mut self.encoder,
+            ProcessUnit::Ingest => &mut self.ingest,
+        }
+    }
+}
+
+/// Message from ffmpeg, after which the channel stops. A configured list replaces the built-in one.
+pub fn is_fatal(policy: &SupervisionConfig, line: &str) -> bool {
+    match &policy.fatal_errors {
+        Some(list) => list
+            .split(';')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .any(|e| line.contains(e)),
+        None => FFMPEG_UNRECOVERABLE_ERRORS.iter().any(|e| line.contains(e)),
+    }
+}
+
+pub async fn started(manager: &ChannelManager, unit: ProcessUnit) {
+    manager
+        .supervisor
+        .lock()
+        .await
+        .unit(unit)
+        .started(Local::now());
+}
+
+pub async fn stopped(manager: &ChannelManager, unit: ProcessUnit) {
+    manager
+        .supervisor
+        .lock()
+        .await
+        .unit(unit)
+        .stopped(Local::now());
+}
+
+/// Process ended by itself, wait the backoff before it starts again.
+/// Returns `false`, when it failed too often in a row.
+pub async fn respawn(manager: &ChannelManager, unit: ProcessUnit) -> bool {
+    let policy = manager.config.lock().await.advanced.supervision.clone();
+    let respawn = manager
+        .supervisor
+        .lock()
+        .await
+        .unit(unit)
+        .ended(&policy, Local::now());
+
+    match respawn {
+        Respawn::Now => true,
+        Respawn::After(delay) => {
+            sleep(delay).await;
+            true
+        }
+        Respawn::GiveUp => false,
+    }
+}
@@ -0,0 +1,622 @@
+/// VAST Ad Breaks
+///
+/// Playlist entries with the category `ad_break` are placeholders for ads, their length is the
+/// length of the break. While the clip before the break plays, the VAST tag from
+/// `playlist.vast_url` is requested, wrappers are followed, and the creatives which fit into the
+/// break are downloaded to a cache. They take the place of the break in the list, with the
+/// category `advertisement`, the rest of the break plays the source of the entry, or the filler.
+///
+/// When the ads play, the impressions and the tracking events `start`, `firstQuartile`,
+/// `midpoint`, `thirdQuartile` and `complete` are sent to the ad server. Without ads in time,
+/// the break entry plays like a normal clip.
+use std::{
+    collections::{HashSet, VecDeque},
+    env,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures_util::future::join_all;
+use log::*;
+use rand::Rng;
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    time::{sleep_until, Instant},
+};
+
+use crate::player::{
+    controller::ChannelManager,
+    utils::{sec_to_time, Media},
+};
+use crate::utils::logging::Target;
+
+/// Category from the placeholder entries.
+pub const AD_BREAK: &str = "ad_break";
+
+/// Category from the ads in the list.
+pub const ADVERTISEMENT: &str = "advertisement";
+
+/// Wrappers which are followed, before the ad is dropped.
+const MAX_WRAPPERS: usize = 5;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Creatives are removed from the cache after this time, and downloaded again when needed.
+const CACHE_AGE: Duration = Duration::from_secs(7 * 86400);
+
+/// Tracking events and their position in the ad.
+const QUARTILES: [(&str, f64); 4] = [
+    ("firstQuartile", 0.25),
+    ("midpoint", 0.5),
+    ("thirdQuartile", 0.75),
+    ("complete", 1.0),
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MediaFile {
+    pub url: String,
+    pub mime: String,
+    pub width: i64,
+    pub height: i64,
+    pub bitrate: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VastAd {
+    pub id: String,
+    pub sequence: i64,
+    pub title: String,
+    /// Seconds from the linear creative.
+    pub duration: f64,
+    pub media_files: Vec<MediaFile>,
+    pub impressions: Vec<String>,
+    /// URLs per tracking event, like `start` or `midpoint`.
+    pub tracking: Vec<(String, String)>,
+    pub errors: Vec<String>,
+    /// Next VAST document, when the ad is a wrapper.
+    pub wrapper: Option<String>,
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Elements with this name, as attributes and content. VAST has no elements inside elements
+/// with the same name.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut found = vec![];
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+
+        // <Ad must not match <AdSystem
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            rest = after;
+            continue;
+        }
+
+        let Some(end) = after.find('>') else {
+            break;
+        };
+        let attributes = after[..end].trim();
+
+        if let Some(attributes) = attributes.strip_suffix('/') {
+            found.push((attributes.trim(), ""));
+            rest = &after[end + 1..];
+            continue;
+        }
+
+        let body = &after[end + 1..];
+        let Some(stop) = body.find(&close) else {
+            break;
+        };
+
+        found.push((attributes, &body[..stop]));
+        rest = &body[stop + close.len()..];
+    }
+
+    found
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let pattern = format!("{name}=");
+    let mut rest = attributes;
+
+    while let Some(pos) = rest.find(&pattern) {
+        let value = &rest[pos + pattern.len()..];
+
+        if pos == 0 || rest[..pos].ends_with(char::is_whitespace) {
+            let quote = value.chars().next().filter(|q| *q == '"' || *q == '\'')?;
+
+            return value[1..].split(quote).next().map(unescape);
+        }
+
+        rest = value;
+    }
+
+    None
+}
+
+/// Text from an element, with or without CDATA.
+fn text(content: &str) -> String {
+    let content = content.trim();
+
+    match content
+        .strip_prefix("<![CDATA[")
+        .and_then(|c| c.strip_suffix("]]>"))
+    {
+        Some(data) => data.trim().to_string(),
+        None => unescape(content),
+    }
+}
+
+fn texts(xml: &str, name: &str) -> Vec<String> {
+    elements(xml, name)
+        .into_iter()
+        .map(|(_, content)| text(content))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Seconds from a VAST duration, like `00:00:30.000`.
+pub fn parse_duration(value: &str) -> Option<f64> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+
+    if parts.len() != 3 {
+        return None;
+    }
+
+    parts.iter().try_fold(0.0, |sec, part| {
+        Some(sec * 60.0 + part.parse::<f64>().ok()?)
+    })
+}
+
+/// Ads from a VAST document, ordered by their sequence in the pod.
+pub fn parse_vast(xml: &str) -> Vec<VastAd> {
+    let mut ads = vec![];
+
+    for (attributes, body) in elements(xml, "Ad") {
+        let mut ad = VastAd {
+            id: attribute(attributes, "id").unwrap_or_default(),
+            sequence: attribute(attributes, "sequence")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let content = if let Some((_, inline)) = elements(body, "InLine").first() {
+            *inline
+        } else if let Some((_, wrapper)) = elements(body, "Wrapper").first() {
+            ad.wrapper = texts(wrapper, "VASTAdTagURI").into_iter().next();
+            *wrapper
+        } else {
+            continue;
+        };
+
+        ad.title = texts(content, "AdTitle")
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        ad.impressions = texts(content, "Impression");
+        ad.errors = texts(content, "Error");
+
+        for (_, linear) in elements(content, "Linear") {
+            if ad.duration == 0.0 {
+                ad.duration = texts(linear, "Duration")
+                    .first()
+                    .and_then(|d| parse_duration(d))
+                    .unwrap_or_default();
+            }
+
+            for (attributes, url) in elements(linear, "Tracking") {
+                if let Some(event) = attribute(attributes, "event") {
+                    ad.tracking.push((event, text(url)));
+                }
+            }
+
+            for (attributes, url) in elements(linear, "MediaFile") {
+                let number = |name| {
+                    attribute(attributes, name)
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or_default()
+                };
+
+                ad.media_files.push(MediaFile {
+                    url: text(url),
+                    mime: attribute(attributes, "type").unwrap_or_default(),
+                    width: number("width"),
+                    height: number("height"),
+                    bitrate: number("bitrate"),
+                });
+            }
+        }
+
+        ads.push(ad);
+    }
+
+    ads.sort_by_key(|ad| ad.sequence);
+
+    ads
+}
+
+/// Video file for the channel: MP4 first, then the one with the closest width.
+pub fn pick_media_file(files: &[MediaFile], width: i64) -> Option<&MediaFile> {
+    files
+        .iter()
+        .filter(|f| !f.url.is_empty() && (f.mime.is_empty() || f.mime.starts_with("video/")))
+        .min_by_key(|f| (f.mime != "video/mp4", (f.width - width).abs(), -f.bitrate))
+}
+
+/// Replace the macros, like `[CACHEBUSTING]`, in the URL.
+pub fn fill_macros(url: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(url.to_string(), |url, (name, value)| {
+        url.replace(&format!("[{name}]"), value)
+    })
+}
+
+fn macros(id: i32, duration: f64) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "CACHEBUSTING",
+            rand::thread_rng()
+                .gen_range(10_000_000..100_000_000)
+                .to_string(),
+        ),
+        (
+            "TIMESTAMP",
+            Utc::now()
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string()
+                .replace(':', "%3A"),
+        ),
+        ("DURATION", format!("{duration:.0}")),
+        ("CHANNEL", id.to_string()),
+    ]
+}
+
+/// Creative in the cache, the name comes from the URL.
+pub fn cache_path(dir: &Path, url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+
+    let extension = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|p| p.rsplit('/').next())
+        .and_then(|f| f.rsplit_once('.'))
+        .map(|(_, e)| e.to_lowercase())
+        .filter(|e| e.len() <= 4 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| "mp4".to_string());
+
+    dir.join(format!("{:016x}.{extension}", hasher.finish()))
+}
+
+/// Key for the tracking from an ad, its begin in milliseconds.
+fn tracking_key(begin: f64) -> i64 {
+    (begin * 1000.0).round() as i64
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    client
+        .get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Request the ads and follow the wrappers, the trackers from a wrapper are added to its ads.
+async fn request_ads(
+    client: &reqwest::Client,
+    url: String,
+    id: i32,
+) -> Result<Vec<VastAd>, String> {
+    let mut ads = vec![];
+    let mut pending = VecDeque::from([(url, VastAd::default(), 0)]);
+
+    while let Some((url, parent, depth)) = pending.pop_front() {
+        let xml = match fetch(client, &url).await {
+            Ok(xml) => xml,
+            Err(e) if depth == 0 => return Err(e),
+            Err(e) => {
+                warn!(target: Target::file(), channel = id; "VAST wrapper failed: {e}");
+                ping(client, &parent.errors, &[("ERRORCODE", "301".to_string())]).await;
+                continue;
+            }
+        };
+
+        for mut ad in parse_vast(&xml) {
+            ad.impressions.extend(parent.impressions.iter().cloned());
+            ad.tracking.extend(parent.tracking.iter().cloned());
+            ad.errors.extend(parent.errors.iter().cloned());
+
+            if depth > 0 {
+                ad.sequence = parent.sequence;
+            }
+
+            match ad.wrapper.take() {
+                Some(next) if depth < MAX_WRAPPERS => pending.push_back((next, ad, depth + 1)),
+                Some(_) => {
+                    warn!(target: Target::file(), channel = id; "VAST: more then {MAX_WRAPPERS} wrappers, drop ad <yellow>{}</>", ad.id);
+                    ping(client, &ad.errors, &[("ERRORCODE", "302".to_string())]).await;
+                }
+                None => ads.push(ad),
+            }
+        }
+    }
+
+    Ok(ads)
+}
+
+/// Send the tracking requests, the answers are not needed.
+async fn ping(client: &reqwest::Client, urls: &[String], values: &[(&str, String)]) {
+    join_all(urls.iter().map(|url| {
+        client
+            .get(fill_macros(url, values))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+    }))
+    .await;
+}
+
+async fn download(client: &reqwest::Client, url: &str, path: &Path) -> Result<(), String> {
+    if path.is_file() {
+        return Ok(());
+    }
+
+    let part = path.with_extension("part");
+    let mut response = client
+        .get(url)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let mut file = fs::File::create(&part).await.map_err(|e| e.to_string())?;
+
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+    }
+
+    file.flush().await.map_err(|e| e.to_string())?;
+    fs::rename(&part, path).await.map_err(|e| e.to_string())
+}
+
+async fn prune_cache(dir: &Path) {
+    let Ok(mut entries) = fs::read_dir(dir).await else {
+        return;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let old = entry
+            .metadata()
+            .await
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > CACHE_AGE);
+
+        if old {
+            let _ = fs::remove_file(entry.path()).await;
+        }
+    }
+}
+
+/// Ads and the rest of the break, for the place of the break in the list.
+pub fn break_nodes(break_node: &Media, mut ads: Vec<Media>) -> Vec<Media> {
+    let begin = break_node.begin.unwrap_or_default();
+    let length = break_node.out - break_node.seek;
+    let mut offset = 0.0;
+
+    for ad in &mut ads {
+        ad.begin = Some(begin + offset);
+        ad.category = ADVERTISEMENT.to_string();
+        offset += ad.out - ad.seek;
+    }
+
+    if length - offset >= 1.0 {
+        // not a break anymore, so it is not requested again
+        let mut rest = break_node.clone();
+        rest.begin = Some(begin + offset);
+        rest.out = rest.seek + length - offset;
+        rest.category = "filler".to_string();
+
+        ads.push(rest);
+    }
+
+    ads
+}
+
+/// Request the ads for the break in the list and put them in its place.
+pub async fn prepare_break(manager: &ChannelManager, node: &Media) {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+
+    if config.playlist.vast_url.is_empty() {
+        return;
+    }
+
+    let length = node.out - node.seek;
+    let begin = node.begin.unwrap_or_default();
+    let client = reqwest::Client::new();
+    let url = fill_macros(&config.playlist.vast_url, &macros(id, length));
+
+    let ads = match request_ads(&client, url, id).await {
+        Ok(ads) => ads,
+        Err(e) => {
+            warn!(target: Target::file_mail(), channel = id; "Ad break at <yellow>{}</>: ad server failed: {e}", sec_to_time(begin));
+            return;
+        }
+    };
+
+    let dir = env::temp_dir().join(format!("ffplayout_ads_{id}"));
+
+    if let Err(e) = fs::create_dir_all(&dir).await {
+        error!(target: Target::file_mail(), channel = id; "Ad cache <b><magenta>{dir:?}</></b>: {e}");
+        return;
+    }
+
+    prune_cache(&dir).await;
+
+    let mut nodes = vec![];
+    let mut tracking = vec![];
+    let mut offset = 0.0;
+
+    for ad in ads {
+        if ad.duration <= 0.0 || offset + ad.duration > length + 0.1 {
+            continue;
+        }
+
+        let Some(file) = pick_media_file(&ad.media_files, config.processing.width) else {
+            debug!(target: Target::file(), channel = id; "VAST: no video file for ad <yellow>{}</>", ad.id);
+            ping(&client, &ad.errors, &[("ERRORCODE", "403".to_string())]).await;
+            continue;
+        };
+        let path = cache_path(&dir, &file.url);
+
+        if let Err(e) = download(&client, &file.url, &path).await {
+            warn!(target: Target::file(), channel = id; "VAST: download from ad <yellow>{}</> failed: {e}", ad.id);
+            ping(&client, &ad.errors, &[("ERRORCODE", "401".to_string())]).await;
+            continue;
+        }
+
+        let mut media = Media::new(0, &path.to_string_lossy(), true).await;
+
+        if media.probe.is_none() || offset + media.duration > length + 0.1 {
+            if media.probe.is_none() {
+                let _ = fs::remove_file(&path).await;
+                ping(&client, &ad.errors, &[("ERRORCODE", "405".to_string())]).await;
+            }
+
+            continue;
+        }
+
+        if !ad.title.is_empty() {
+            media.title = Some(ad.title.clone());
+        }
+
+        tracking.push((tracking_key(begin + offset), ad));
+        offset += media.duration;
+        nodes.push(media);
+    }
+
+    if nodes.is_empty() {
+        info!(target: Target::file_mail(), channel = id; "Ad break at <yellow>{}</>: no ads", sec_to_time(begin));
+        return;
+    }
+
+    let count = nodes.len();
+    let nodes = break_nodes(node, nodes);
+    let mut list = manager.current_list.lock().await;
+
+    // the list can be reloaded, or the break is already playing
+    let Some(pos) = list
+        .iter()
+        .position(|n| n.category == AD_BREAK && n.begin == node.begin && n.source == node.source)
+        .filter(|pos| *pos >= manager.current_index.load(Ordering::SeqCst))
+    else {
+        warn!(target: Target::file_mail(), channel = id; "Ad break at <yellow>{}</>: ads came too late", sec_to_time(begin));
+        return;
+    };
+
+    list.splice(pos..=pos, nodes);
+
+    for (i, item) in list.iter_mut().enumerate() {
+        item.index = Some(i);
+    }
+
+    let planned: HashSet<i64> = list
+        .iter()
+        .filter(|n| n.category == ADVERTISEMENT)
+        .filter_map(|n| n.begin.map(tracking_key))
+        .collect();
+    drop(list);
+
+    let mut ad_tracking = manager.ad_tracking.lock().await;
+    ad_tracking.retain(|key, _| planned.contains(key));
+    ad_tracking.extend(tracking);
+
+    info!(target: Target::file_mail(), channel = id;
+        "Ad break at <yellow>{}</>: <yellow>{count}</> ads with <yellow>{offset:.2}</> seconds",
+        sec_to_time(begin)
+    );
+}
+
+async fn is_playing(manager: &ChannelManager, begin: f64) -> bool {
+    manager
+        .current_media
+        .lock()
+        .await
+        .as_ref()
+        .and_then(|m| m.begin)
+        .is_some_and(|b| tracking_key(b) == tracking_key(begin))
+}
+
+/// Send the impressions and tracking events, while the ad plays.
+pub async fn track(manager: ChannelManager, node: Media) {
+    if node.category != ADVERTISEMENT {
+        return;
+    }
+
+    let Some(begin) = node.begin else {
+        return;
+    };
+    let Some(ad) = manager
+        .ad_tracking
+        .lock()
+        .await
+        .remove(&tracking_key(begin))
+    else {
+        return;
+    };
+
+    let start = Instant::now();
+    let duration = node.out - node.seek;
+    let id = manager.config.lock().await.general.channel_id;
+    let client = reqwest::Client::new();
+    let values = macros(id, duration);
+    let event = |name: &str| -> Vec<String> {
+        ad.tracking
+            .iter()
+            .filter(|(e, _)| e == name)
+            .map(|(_, url)| url.clone())
+            .collect()
+    };
+
+    debug!(target: Target::file(), channel = id; "VAST: ad <yellow>{}</> starts", ad.id);
+
+    ping(&client, &ad.impressions, &values).await;
+    ping(&client, &event("start"), &values).await;
+
+    for (name, share) in QUARTILES {
+        // complete is sent shortly before the end, when the ad is still on air
+        let at = if name == "complete" {
+            duration - 0.5
+        } else {
+            duration * share
+        };
+        sleep_until(start + Duration::from_secs_f64(at.max(0.0))).await;
+
+        if !is_playing(&manager, begin).await {
+            return;
+        }
+
+        ping(&client, &event(name), &values).await;
+    }
+}
@@ -1,6 +1,10 @@
 use std::{
-    sync::{atomic::Ordering, Arc},
-    time::Duration,
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use actix_web::web;
@@ -8,8 +12,10 @@ use actix_web_lab::{
     sse::{self, Sse},
     util::InfallibleStream,
 };
+use log::Level;
 use tokio::{
     sync::{
+        broadcast::error::RecvError,
         mpsc::{self, error::SendError},
         Mutex,
     },
@@ -18,20 +24,30 @@ use tokio::{
 use tokio_stream::wrappers::ReceiverStream;
 
 use crate::player::{controller::ChannelManager, utils::get_data_map};
-use crate::sse::Endpoint;
-use crate::utils::system;
+use crate::sse::{ClipEvent, Endpoint, CLIP_STREAM};
+use crate::utils::{logging::LOG_STREAM, system};
+
+/// Clip events per channel, which reconnecting clients can get again.
+const REPLAY_SIZE: usize = 50;
 
 #[derive(Debug, Clone)]
 struct Client {
     manager: ChannelManager,
+    channel: i32,
     endpoint: Endpoint,
     sender: mpsc::Sender<sse::Event>,
 }
 
 impl Client {
-    fn new(manager: ChannelManager, endpoint: Endpoint, sender: mpsc::Sender<sse::Event>) -> Self {
+    fn new(
+        manager: ChannelManager,
+        channel: i32,
+        endpoint: Endpoint,
+        sender: mpsc::Sender<sse::Event>,
+    ) -> Self {
         Self {
             manager,
+            channel,
             endpoint,
             sender,
         }
@@ -40,25 +56,91 @@ impl Client {
 
 pub struct Broadcaster {
     inner: Mutex<BroadcasterInner>,
+    /// Starts from the time in milliseconds, so IDs stay increasing over a restart.
+    next_id: AtomicU64,
 }
 
 #[derive(Debug, Clone, Default)]
 struct BroadcasterInner {
     clients: Vec<Client>,
+    replay: HashMap<i32, VecDeque<(u64, String)>>,
+}
+
+/// Events from the buffer, which came after the last ID from the client.
+pub fn replay_events(buffer: &VecDeque<(u64, String)>, last_id: u64) -> Vec<(u64, String)> {
+    buffer
+        .iter()
+        .filter(|(id, _)| *id > last_id)
+        .cloned()
+        .collect()
+}
+
+fn clip_event(id: u64, message: String) -> sse::Event {
+    sse::Data::new(message)
+        .event("clip")
+        .id(id.to_string())
+        .into()
 }
 
 impl Broadcaster {
     /// Constructs new broadcaster and spawns ping loop.
     pub fn create() -> Arc<Self> {
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
         let this = Arc::new(Self {
             inner: Mutex::new(BroadcasterInner::default()),
+            next_id: AtomicU64::new(start),
         });
 
         Self::spawn_ping(Arc::clone(&this));
+        Self::spawn_clips(Arc::clone(&this));
 
         this
     }
 
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Forward clip starts from the playout to the clients and the replay buffer.
+    fn spawn_clips(this: Arc<Self>) {
+        let mut receiver = CLIP_STREAM.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(clip) => this.send_clip(&clip).await,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    async fn send_clip(&self, clip: &ClipEvent) {
+        let id = self.next_id();
+        let message = serde_json::to_string(clip).unwrap_or_default();
+        let mut inner = self.inner.lock().await;
+        let buffer = inner.replay.entry(clip.channel).or_default();
+
+        buffer.push_back((id, message.clone()));
+
+        if buffer.len() > REPLAY_SIZE {
+            buffer.pop_front();
+        }
+
+        for client in inner
+            .clients
+            .iter()
+            .filter(|c| c.endpoint == Endpoint::Clip && c.channel == clip.channel)
+        {
+            // failed clients get removed from the ping loop
+            let _ = client.sender.try_send(clip_event(id, message.clone()));
+        }
+    }
+
     /// Pings clients every 10 seconds to see if they are alive and remove them from the broadcast
     /// list if not.
     fn spawn_ping(this: Arc<Self>) {
@@ -74,17 +156,30 @@ impl Broadcaster {
     }
 
     /// Registers client with broadcaster, returning an SSE response body.
+    ///
+    /// With the ID from the last event the client got, the missed clip events are sent first.
     pub async fn new_client(
         &self,
         manager: ChannelManager,
         endpoint: Endpoint,
+        last_id: Option<u64>,
     ) -> Sse<InfallibleStream<ReceiverStream<sse::Event>>> {
-        let (tx, rx) = mpsc::channel(10);
+        let (tx, rx) = mpsc::channel(REPLAY_SIZE + 10);
+        let channel = manager.channel.lock().await.id;
+        let mut inner = self.inner.lock().await;
 
         tx.send(sse::Data::new("connected").into()).await.unwrap();
 
-        let client = Client::new(manager, endpoint, tx);
-        self.inner.lock().await.clients.push(client);
+        if let (Endpoint::Clip, Some(last_id), Some(buffer)) =
+            (&endpoint, last_id, inner.replay.get(&channel))
+        {
+            for (id, message) in replay_events(buffer, last_id) {
+                tx.send(clip_event(id, message)).await.unwrap();
+            }
+        }
+
+        let client = Client::new(manager, channel, endpoint, tx);
+        inner.clients.push(client);
 
         Sse::from_infallible_receiver(rx)
     }
@@ -96,8 +191,22 @@ impl Broadcaster {
         // every client needs its own stats
         for (index, client) in inner.clients.iter().enumerate() {
             let mut sender_result = Err(SendError(sse::Event::Comment("closed".into())));
+            let id = self.next_id().to_string();
 
             match client.endpoint {
+                Endpoint::Encoder => {
+                    let message = if client.manager.is_alive.load(Ordering::SeqCst) {
+                        let stats = client.manager.encoder_stats.lock().await.clone();
+                        serde_json::to_string(&stats).unwrap_or_default()
+                    } else {
+                        "not running".to_string()
+                    };
+
+                    sender_result = client
+                        .sender
+                        .send(sse::Data::new(message).id(id).into())
+                        .await;
+                }
                 Endpoint::Playout => {
                     let media_map = get_data_map(&client.manager).await;
 
@@ -107,7 +216,25 @@ impl Broadcaster {
                         "not running".to_string()
                     };
 
-                    sender_result = client.sender.send(sse::Data::new(message).into()).await;
+                    sender_result = client
+                        .sender
+                        .send(sse::Data::new(message).id(id).into())
+                        .await;
+                }
+                Endpoint::State => {
+                    let state = client.manager.state.lock().await.clone();
+                    let message = serde_json::to_string(&state).unwrap_or_default();
+
+                    sender_result = client
+                        .sender
+                        .send(sse::Data::new(message).id(id).into())
+                        .await;
+                }
+                // clip events come from the playout, here only check if the client is gone
+                Endpoint::Clip => {
+                    if !client.sender.is_closed() {
+                        continue;
+                    }
                 }
                 Endpoint::System => {
                     let config = client.manager.config.lock().await.clone();
@@ -115,7 +242,7 @@ impl Broadcaster {
                     if let Ok(stat) = web::block(move || system::stat(&config)).await {
                         sender_result = client
                             .sender
-                            .send(sse::Data::new(stat.to_string()).into())
+                            .send(sse::Data::new(stat.to_string()).id(id).into())
                             .await;
                     }
                 }
@@ -131,3 +258,42 @@ impl Broadcaster {
         }
     }
 }
+
+/// Stream log lines from one channel, which have the given level or a more important one.
+pub async fn log_client(
+    channel: i32,
+    level: Level,
+) -> Sse<InfallibleStream<ReceiverStream<sse::Event>>> {
+    let (tx, rx) = mpsc::channel(100);
+    let mut receiver = LOG_STREAM.subscribe();
+
+    tx.send(sse::Data::new("connected").into()).await.unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let event: sse::Event = tokio::select! {
+                _ = tx.closed() => break,
+                line = receiver.recv() => match line {
+                    Ok(line) => {
+                        if line.channel != channel || line.level > level {
+                            continue;
+                        }
+
+                        sse::Data::new(serde_json::to_string(&line).unwrap_or_default()).into()
+                    }
+                    // slow clients miss lines, tell them how many
+                    Err(RecvError::Lagged(skipped)) => {
+                        sse::Data::new(skipped.to_string()).event("lagged").into()
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Sse::from_infallible_receiver(rx)
+}
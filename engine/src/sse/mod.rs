@@ -2,14 +2,19 @@ use std::{
     collections::HashSet,
     fmt,
     str::FromStr,
+    sync::LazyLock,
     time::{Duration, SystemTime},
 };
 
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use uuid::Uuid;
 
-use crate::utils::errors::ServiceError;
+use crate::player::{
+    controller::ChannelManager,
+    utils::{time_in_seconds, Media},
+};
+use crate::utils::{errors::ServiceError, time_machine::time_now};
 
 pub mod broadcast;
 pub mod routes;
@@ -17,7 +22,10 @@ pub mod routes;
 #[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Endpoint {
+    Encoder,
     Playout,
+    State,
+    Clip,
     #[default]
     System,
 }
@@ -27,7 +35,10 @@ impl FromStr for Endpoint {
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         match input {
+            "encoder" => Ok(Self::Encoder),
             "playout" => Ok(Self::Playout),
+            "state" => Ok(Self::State),
+            "clip" => Ok(Self::Clip),
             "system" => Ok(Self::System),
             _ => Err("Missing endpoint".to_string()),
         }
@@ -37,12 +48,100 @@ impl FromStr for Endpoint {
 impl fmt::Display for Endpoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Self::Encoder => write!(f, "encoder"),
             Self::Playout => write!(f, "playout"),
+            Self::State => write!(f, "state"),
+            Self::Clip => write!(f, "clip"),
             Self::System => write!(f, "system"),
         }
     }
 }
 
+/// Clip starts from all channels, for the `clip` endpoint.
+pub static CLIP_STREAM: LazyLock<broadcast::Sender<ClipEvent>> =
+    LazyLock::new(|| broadcast::channel(100).0);
+
+/// Sent when a clip starts to play.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ClipEvent {
+    #[serde(skip)]
+    pub channel: i32,
+    /// Position in the playlist, `None` for clips outside of it, like from the filler.
+    pub index: Option<usize>,
+    pub source: String,
+    pub title: Option<String>,
+    pub category: String,
+    pub duration: f64,
+    #[serde(rename = "in")]
+    pub seek: f64,
+    pub out: f64,
+    /// Start from the playlist, in seconds from the day.
+    pub scheduled: Option<f64>,
+    /// Real start, in seconds from the day.
+    pub started: f64,
+    /// How much later the clip started than planned, negative when it started earlier.
+    pub delay: Option<f64>,
+    pub timestamp: String,
+}
+
+impl ClipEvent {
+    pub fn new(
+        channel: i32,
+        node: &Media,
+        time_shift: f64,
+        started: f64,
+        timestamp: String,
+    ) -> Self {
+        let round = |v: f64| (v * 1000.0).round() / 1000.0;
+        let scheduled = node.begin.map(|begin| begin - time_shift);
+        let delay = scheduled.map(|scheduled| {
+            let mut delay = started - scheduled;
+
+            // start and schedule on different sides from midnight
+            if delay > 43200.0 {
+                delay -= 86400.0;
+            } else if delay < -43200.0 {
+                delay += 86400.0;
+            }
+
+            round(delay)
+        });
+
+        Self {
+            channel,
+            index: node.index,
+            source: node.source.clone(),
+            title: node.title.clone(),
+            category: node.category.clone(),
+            duration: node.duration,
+            seek: node.seek,
+            out: node.out,
+            scheduled: scheduled.map(round),
+            started: round(started),
+            delay,
+            timestamp,
+        }
+    }
+}
+
+/// Publish the start from a clip, when someone is listening.
+pub async fn clip_started(manager: &ChannelManager, node: &Media) {
+    if CLIP_STREAM.receiver_count() == 0 {
+        return;
+    }
+
+    let channel = manager.channel.lock().await.clone();
+    let now = time_now(&channel.timezone);
+
+    let _ = CLIP_STREAM.send(ClipEvent::new(
+        channel.id,
+        node,
+        channel.time_shift,
+        time_in_seconds(&channel.timezone),
+        now.to_rfc3339(),
+    ));
+}
+
 #[derive(Debug, Eq, Hash, PartialEq, Clone, Copy)]
 pub struct UuidData {
     pub uuid: Uuid,
@@ -1,12 +1,18 @@
-use actix_web::{get, post, web, Responder};
+use std::str::FromStr;
+
+use actix_web::{get, post, web, HttpRequest, Responder};
 use actix_web_grants::proc_macro::protect;
+use log::Level;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use super::{check_uuid, prune_uuids, SseAuthState, UuidData};
 use crate::db::models::Role;
 use crate::player::controller::ChannelController;
-use crate::sse::{broadcast::Broadcaster, Endpoint};
+use crate::sse::{
+    broadcast::{log_client, Broadcaster},
+    Endpoint,
+};
 use crate::utils::errors::ServiceError;
 
 #[derive(Deserialize, Serialize)]
@@ -22,6 +28,12 @@ impl User {
     }
 }
 
+#[derive(Deserialize)]
+struct LogUser {
+    uuid: String,
+    level: Option<String>,
+}
+
 /// **Get generated UUID**
 ///
 /// ```BASH
@@ -67,8 +79,15 @@ async fn validate_uuid(
 /// ```BASH
 /// curl -X GET 'http://127.0.0.1:8787/data/event/1?endpoint=system&uuid=f2f8c29b-712a-48c5-8919-b535d3a05a3a'
 /// ```
+///
+/// With `endpoint=clip` every clip start is sent as `clip` event, with source, title, category,
+/// playlist index and the scheduled and the real start.
+///
+/// Events have IDs, a client which reconnects with the `Last-Event-ID` header gets the clip events
+/// it missed, from the last 50 per channel.
 #[get("/event/{id}")]
 async fn event_stream(
+    req: HttpRequest,
     broadcaster: web::Data<Broadcaster>,
     data: web::Data<SseAuthState>,
     id: web::Path<i32>,
@@ -86,7 +105,49 @@ async fn event_stream(
         .await
         .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
 
+    let last_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+
     Ok(broadcaster
-        .new_client(manager.clone(), user.endpoint.clone())
+        .new_client(manager.clone(), user.endpoint.clone(), last_id)
         .await)
 }
+
+/// **Stream channel logs**
+///
+/// ```BASH
+/// curl -X GET 'http://127.0.0.1:8787/data/log/1?level=warn&uuid=f2f8c29b-712a-48c5-8919-b535d3a05a3a'
+/// ```
+///
+/// Every event is a log line from the engine or ffmpeg, like:
+/// `{"timestamp": "2024-05-20 06:00:01.123456+02:00", "level": "WARN", "message": "..."}`.
+/// `level` is optional (error, warn, info, debug or trace), default is debug.
+#[get("/log/{id}")]
+async fn log_stream(
+    data: web::Data<SseAuthState>,
+    id: web::Path<i32>,
+    user: web::Query<LogUser>,
+    controllers: web::Data<Mutex<ChannelController>>,
+) -> Result<impl Responder, ServiceError> {
+    let mut uuids = data.uuids.lock().await;
+
+    check_uuid(&mut uuids, user.uuid.as_str())?;
+
+    controllers
+        .lock()
+        .await
+        .get(*id)
+        .await
+        .ok_or(ServiceError::BadRequest("Channel not found".to_string()))?;
+
+    let level = match &user.level {
+        Some(level) => Level::from_str(level)
+            .map_err(|_| ServiceError::BadRequest(format!("Unknown log level: {level}")))?,
+        None => Level::Debug,
+    };
+
+    Ok(log_client(*id, level).await)
+}
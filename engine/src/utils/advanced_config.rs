@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, NoneAsEmptyString};
@@ -8,7 +8,7 @@ use tokio::io::AsyncReadExt;
 use ts_rs::TS;
 
 use crate::db::{handles, models::AdvancedConfiguration};
-use crate::utils::ServiceError;
+use crate::utils::{config::OutputMode, expand_env, priority::Priority, ServiceError};
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export, export_to = "advanced_config.d.ts")]
@@ -17,11 +17,30 @@ pub struct AdvancedConfig {
     pub encoder: EncoderConfig,
     pub filter: FilterConfig,
     pub ingest: IngestConfig,
+    /// Priority for the decoder and encoder processes.
+    #[serde(default)]
+    pub process: ProcessConfig,
+    /// Restarts of the decoder and ingest processes, and which errors stop the channel.
+    #[serde(default)]
+    pub supervision: SupervisionConfig,
+    /// Decoder, encoder and filter settings for a specific output mode, keyed by mode name.
+    #[serde(default)]
+    pub output_overrides: BTreeMap<String, OutputOverride>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
+pub struct OutputOverride {
+    pub decoder: DecoderConfig,
+    pub encoder: EncoderConfig,
+    pub filter: FilterConfig,
 }
 
 #[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
 pub struct DecoderConfig {
     #[ts(type = "string")]
     #[serde_as(as = "NoneAsEmptyString")]
@@ -40,6 +59,7 @@ pub struct DecoderConfig {
 #[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
 pub struct EncoderConfig {
     #[ts(type = "string")]
     #[serde_as(as = "NoneAsEmptyString")]
@@ -52,6 +72,61 @@ pub struct EncoderConfig {
 #[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
+pub struct ProcessConfig {
+    /// Nice level from -20 to 19, negative values need root or CAP_SYS_NICE.
+    pub nice: Option<i32>,
+    /// CPUs for the processes, like `0-3,8`.
+    #[ts(type = "string")]
+    #[serde_as(as = "NoneAsEmptyString")]
+    pub cpu_affinity: Option<String>,
+    /// IO class and level: `realtime:0-7`, `best-effort:0-7` or `idle`.
+    #[ts(type = "string")]
+    #[serde_as(as = "NoneAsEmptyString")]
+    pub io_priority: Option<String>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
+pub struct SupervisionConfig {
+    /// Milliseconds a process must run, shorter runs count as failed start. Default 300.
+    pub min_runtime: Option<i64>,
+    /// Failed starts in a row, before the channel stops, 0 has no limit. Default 10.
+    pub max_failures: Option<i64>,
+    /// Milliseconds before the start after a failed one, doubled for each failure. Default 0.
+    pub backoff: Option<i64>,
+    /// Longest wait before a start in milliseconds. Default 10000.
+    pub max_backoff: Option<i64>,
+    /// ffmpeg messages which stop the channel, separated by `;`, replace the built-in list.
+    #[ts(type = "string")]
+    #[serde_as(as = "NoneAsEmptyString")]
+    pub fatal_errors: Option<String>,
+}
+
+impl SupervisionConfig {
+    pub fn min_runtime(&self) -> i64 {
+        self.min_runtime.unwrap_or(300).max(0)
+    }
+
+    pub fn max_failures(&self) -> i64 {
+        self.max_failures.unwrap_or(10).max(0)
+    }
+
+    pub fn backoff(&self) -> u64 {
+        self.backoff.unwrap_or(0).max(0) as u64
+    }
+
+    pub fn max_backoff(&self) -> u64 {
+        (self.max_backoff.unwrap_or(10_000).max(0) as u64).max(self.backoff())
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
 pub struct IngestConfig {
     #[ts(type = "string")]
     #[serde_as(as = "NoneAsEmptyString")]
@@ -64,6 +139,7 @@ pub struct IngestConfig {
 #[serde_as]
 #[derive(Debug, Default, Serialize, Deserialize, Clone, TS)]
 #[ts(export, export_to = "advanced_config.d.ts")]
+#[serde(default)]
 pub struct FilterConfig {
     #[ts(type = "string")]
     #[serde_as(as = "NoneAsEmptyString")]
@@ -136,6 +212,16 @@ pub struct FilterConfig {
     pub split: Option<String>,
 }
 
+fn split_param(param: &Option<String>) -> Option<Vec<String>> {
+    param.as_ref().and_then(|p| split(&expand_env(p)))
+}
+
+fn merge(target: &mut Option<String>, value: &Option<String>) {
+    if value.is_some() {
+        target.clone_from(value);
+    }
+}
+
 impl AdvancedConfig {
     pub fn new(config: AdvancedConfiguration) -> Self {
         Self {
@@ -143,18 +229,18 @@ impl AdvancedConfig {
                 input_param: config.decoder_input_param.clone(),
                 output_param: config.decoder_output_param.clone(),
                 input_cmd: match config.decoder_input_param {
-                    Some(input_param) => split(&input_param),
+                    Some(input_param) => split(&expand_env(&input_param)),
                     None => None,
                 },
                 output_cmd: match config.decoder_output_param {
-                    Some(output_param) => split(&output_param),
+                    Some(output_param) => split(&expand_env(&output_param)),
                     None => None,
                 },
             },
             encoder: EncoderConfig {
                 input_param: config.encoder_input_param.clone(),
                 input_cmd: match config.encoder_input_param {
-                    Some(input_param) => split(&input_param),
+                    Some(input_param) => split(&expand_env(&input_param)),
                     None => None,
                 },
             },
@@ -186,14 +272,87 @@ impl AdvancedConfig {
             ingest: IngestConfig {
                 input_param: config.ingest_input_param.clone(),
                 input_cmd: match config.ingest_input_param {
-                    Some(input_param) => split(&input_param),
+                    Some(input_param) => split(&expand_env(&input_param)),
                     None => None,
                 },
             },
+            process: ProcessConfig {
+                nice: config.process_nice,
+                cpu_affinity: config.process_cpu_affinity,
+                io_priority: config.process_io_priority,
+            },
+            supervision: SupervisionConfig {
+                min_runtime: config.supervision_min_runtime,
+                max_failures: config.supervision_max_failures,
+                backoff: config.supervision_backoff,
+                max_backoff: config.supervision_max_backoff,
+                fatal_errors: config.supervision_fatal_errors,
+            },
+            output_overrides: serde_json::from_str(&config.output_overrides).unwrap_or_default(),
+        }
+    }
+
+    /// Check that all override keys are valid output modes, the process priority and supervision.
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        Priority::new(&self.process).map_err(ServiceError::BadRequest)?;
+
+        let s = &self.supervision;
+
+        if [s.min_runtime, s.max_failures, s.backoff, s.max_backoff]
+            .iter()
+            .flatten()
+            .any(|v| *v < 0)
+        {
+            return Err(ServiceError::BadRequest(
+                "Supervision values can't be negative".to_string(),
+            ));
+        }
+
+        for mode in self.output_overrides.keys() {
+            mode.parse::<OutputMode>()
+                .map_err(|e| ServiceError::BadRequest(format!("Output override '{mode}': {e}")))?;
         }
+
+        Ok(())
+    }
+
+    /// Config with the override from the given output mode applied,
+    /// values which are not set in the override are taken from the base config.
+    pub fn for_mode(&self, mode: &OutputMode) -> Self {
+        let mut config = self.clone();
+
+        let Some(o) = self.output_overrides.get(&mode.to_string()) else {
+            return config;
+        };
+
+        merge(&mut config.decoder.input_param, &o.decoder.input_param);
+        merge(&mut config.decoder.output_param, &o.decoder.output_param);
+        merge(&mut config.encoder.input_param, &o.encoder.input_param);
+
+        if let (Ok(mut filter), Ok(Some(values))) = (
+            serde_json::to_value(&config.filter),
+            serde_json::to_value(&o.filter).map(|v| v.as_object().cloned()),
+        ) {
+            for (key, value) in values {
+                // unset filters are serialized as empty string
+                if value.as_str().is_some_and(|v| !v.is_empty()) {
+                    filter[key] = value;
+                }
+            }
+
+            if let Ok(filter) = serde_json::from_value(filter) {
+                config.filter = filter;
+            }
+        }
+
+        config.decoder.input_cmd = split_param(&config.decoder.input_param);
+        config.decoder.output_cmd = split_param(&config.decoder.output_param);
+        config.encoder.input_cmd = split_param(&config.encoder.input_param);
+
+        config
     }
 
-    pub async fn dump(pool: &Pool<Sqlite>, id: i32) -> Result<(), ServiceError> {
+    pub async fn dump(pool: &Pool<Sqlite>, id: i32, path: &Path) -> Result<(), ServiceError> {
         let config = Self::new(handles::select_advanced_configuration(pool, id).await?);
         let f_keys = [
             "deinterlace",
@@ -287,7 +446,7 @@ impl AdvancedConfig {
             }
         };
 
-        tokio::fs::write(&format!("advanced_{id}.toml"), doc.to_string()).await?;
+        tokio::fs::write(path, doc.to_string()).await?;
 
         Ok(())
     }
@@ -299,6 +458,7 @@ impl AdvancedConfig {
             file.read_to_string(&mut contents).await?;
 
             let config: Self = toml_edit::de::from_str(&contents).unwrap();
+            config.validate()?;
 
             handles::update_advanced_configuration(pool, id, config).await?;
         } else {
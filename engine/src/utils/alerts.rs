@@ -0,0 +1,366 @@
+/// Alert Rules
+///
+/// Admins define per channel which metric is watched and when it alarms. When the condition holds
+/// for the given seconds, the alert goes to the notifier of the rule, and when the condition is over,
+/// a resolve message follows. A monitor task checks all enabled rules every few seconds.
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeDelta};
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::sync::Mutex;
+
+use crate::db::{handles, models::AlertRule};
+use crate::player::controller::{ChannelController, ChannelManager};
+use crate::utils::{
+    config::NotifierKind,
+    errors::ServiceError,
+    event_history::{self, EventKind},
+    logging::{send_mail, Target},
+    notifier::send_notification,
+};
+
+const CHECK_INTERVAL: u64 = 5;
+/// Encoder stats, which are older, count as zero.
+const STATS_TIMEOUT: i64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// 1 when the channel plays, otherwise 0.
+    Running,
+    /// 1 when a live source is connected, otherwise 0.
+    Ingest,
+    /// 1 when the output is connected, otherwise 0.
+    Connected,
+    /// Schedule drift in seconds, without sign.
+    Drift,
+    /// Output availability in percent, over the last 24 hours.
+    Availability,
+    /// Output disconnects in the last hour.
+    Disconnects,
+    Fps,
+    Speed,
+    /// Problems from the last media audit, in the upcoming playlists.
+    AuditIssues,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "running" => Ok(Self::Running),
+            "ingest" => Ok(Self::Ingest),
+            "connected" => Ok(Self::Connected),
+            "drift" => Ok(Self::Drift),
+            "availability" => Ok(Self::Availability),
+            "disconnects" => Ok(Self::Disconnects),
+            "fps" => Ok(Self::Fps),
+            "speed" => Ok(Self::Speed),
+            "audit_issues" => Ok(Self::AuditIssues),
+            _ => Err(format!("Unknown metric: \"{input}\"")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Above,
+    AboveOrEqual,
+    Below,
+    BelowOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            ">" => Ok(Self::Above),
+            ">=" => Ok(Self::AboveOrEqual),
+            "<" => Ok(Self::Below),
+            "<=" => Ok(Self::BelowOrEqual),
+            "=" | "==" => Ok(Self::Equal),
+            "!=" => Ok(Self::NotEqual),
+            _ => Err(format!("Unknown condition: \"{input}\"")),
+        }
+    }
+}
+
+impl Condition {
+    pub fn check(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Above => value > threshold,
+            Self::AboveOrEqual => value >= threshold,
+            Self::Below => value < threshold,
+            Self::BelowOrEqual => value <= threshold,
+            Self::Equal => (value - threshold).abs() < f64::EPSILON,
+            Self::NotEqual => (value - threshold).abs() >= f64::EPSILON,
+        }
+    }
+}
+
+/// Where the alert goes: `log` writes an error to the channel log, which reaches mail and chat
+/// by their log levels, the others send directly to the channel recipient or notifiers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Notify {
+    Log,
+    Mail,
+    Chat(NotifierKind),
+}
+
+impl FromStr for Notify {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "" | "log" => Ok(Self::Log),
+            "mail" => Ok(Self::Mail),
+            "telegram" => Ok(Self::Chat(NotifierKind::Telegram)),
+            "slack" => Ok(Self::Chat(NotifierKind::Slack)),
+            "discord" => Ok(Self::Chat(NotifierKind::Discord)),
+            _ => Err(format!("Unknown notifier: \"{input}\"")),
+        }
+    }
+}
+
+/// Check rule from the API, an empty notifier becomes `log`.
+pub fn validate(rule: &mut AlertRule) -> Result<(), ServiceError> {
+    rule.name = rule.name.trim().to_string();
+    rule.notifier = rule.notifier.trim().to_lowercase();
+
+    if rule.name.is_empty() {
+        return Err(ServiceError::BadRequest(
+            "Alert rule needs a name".to_string(),
+        ));
+    }
+
+    if rule.duration < 0 || !rule.threshold.is_finite() {
+        return Err(ServiceError::BadRequest(format!(
+            "Invalid threshold or duration in alert rule \"{}\"",
+            rule.name
+        )));
+    }
+
+    Metric::from_str(&rule.metric).map_err(ServiceError::BadRequest)?;
+    Condition::from_str(&rule.condition).map_err(ServiceError::BadRequest)?;
+    Notify::from_str(&rule.notifier).map_err(ServiceError::BadRequest)?;
+
+    if rule.notifier.is_empty() {
+        rule.notifier = "log".to_string();
+    }
+
+    Ok(())
+}
+
+/// Snapshot from the channel state.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    pub running: bool,
+    pub ingest: bool,
+    pub connected: bool,
+    pub drift: f64,
+    pub availability: f64,
+    pub disconnects: usize,
+    pub fps: f64,
+    pub speed: f64,
+    pub audit_issues: usize,
+}
+
+impl Metrics {
+    pub async fn collect(manager: &ChannelManager, now: DateTime<Local>) -> Self {
+        let running = manager.is_alive.load(Ordering::SeqCst);
+        let health = manager.output_health.lock().await;
+        let stats = manager.encoder_stats.lock().await;
+        let fresh = running
+            && stats
+                .updated
+                .is_some_and(|t| (now - t).num_seconds() < STATS_TIMEOUT);
+
+        Self {
+            running,
+            ingest: manager.ingest_is_alive.load(Ordering::SeqCst),
+            connected: health.is_connected(),
+            drift: manager.drift.lock().await.seconds.abs(),
+            availability: health.availability(now),
+            disconnects: health.disconnects(now, TimeDelta::hours(1)),
+            fps: if fresh { stats.fps } else { 0.0 },
+            speed: if fresh { stats.speed } else { 0.0 },
+            audit_issues: manager.media_audit.lock().await.issues(),
+        }
+    }
+
+    pub fn value(&self, metric: Metric) -> f64 {
+        let flag = |b: bool| if b { 1.0 } else { 0.0 };
+
+        match metric {
+            Metric::Running => flag(self.running),
+            Metric::Ingest => flag(self.ingest),
+            Metric::Connected => flag(self.connected),
+            Metric::Drift => self.drift,
+            Metric::Availability => self.availability,
+            Metric::Disconnects => self.disconnects as f64,
+            Metric::Fps => self.fps,
+            Metric::Speed => self.speed,
+            Metric::AuditIssues => self.audit_issues as f64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    Fire,
+    Resolve,
+}
+
+#[derive(Clone, Debug, Default)]
+struct RuleState {
+    since: Option<DateTime<Local>>,
+    fired: bool,
+}
+
+/// Since when the condition from each rule holds, and if the alert went out.
+#[derive(Clone, Debug, Default)]
+pub struct AlertState {
+    rules: HashMap<i32, RuleState>,
+}
+
+impl AlertState {
+    /// Check rule against the current value, returns when the alert goes out or is resolved.
+    pub fn check(
+        &mut self,
+        rule: &AlertRule,
+        value: f64,
+        now: DateTime<Local>,
+    ) -> Option<Transition> {
+        let condition = Condition::from_str(&rule.condition).ok()?;
+        let state = self.rules.entry(rule.id).or_default();
+
+        if !condition.check(value, rule.threshold) {
+            let fired = state.fired;
+            *state = RuleState::default();
+
+            return fired.then_some(Transition::Resolve);
+        }
+
+        let since = *state.since.get_or_insert(now);
+
+        if !state.fired && (now - since).num_seconds() >= rule.duration {
+            state.fired = true;
+            return Some(Transition::Fire);
+        }
+
+        None
+    }
+
+    /// Forget deleted and disabled rules.
+    pub fn retain(&mut self, rules: &[AlertRule]) {
+        self.rules.retain(|id, _| rules.iter().any(|r| r.id == *id));
+    }
+}
+
+async fn notify(manager: &ChannelManager, rule: &AlertRule, transition: Transition, value: f64) {
+    let id = rule.channel_id;
+    let message = match transition {
+        Transition::Fire => format!(
+            "Alert \"{}\": {} is {value:.2} ({} {}) for {} seconds",
+            rule.name, rule.metric, rule.condition, rule.threshold, rule.duration
+        ),
+        Transition::Resolve => format!("Resolved \"{}\": {} is {value:.2}", rule.name, rule.metric),
+    };
+
+    event_history::push(id, EventKind::Alert, message.clone());
+
+    let notify = Notify::from_str(&rule.notifier).unwrap_or(Notify::Log);
+
+    if notify == Notify::Log {
+        match transition {
+            Transition::Fire => error!(target: Target::file_mail(), channel = id; "{message}"),
+            Transition::Resolve => warn!(target: Target::file_mail(), channel = id; "{message}"),
+        }
+
+        return;
+    }
+
+    info!(target: Target::file(), channel = id; "{message}");
+
+    let mail = manager.config.lock().await.mail.clone();
+
+    match notify {
+        Notify::Mail if mail.recipient.contains('@') => {
+            if let Err(e) = send_mail(&mail, message).await {
+                error!(target: Target::file(), channel = id; "Failed to send alert mail: {e}");
+            }
+        }
+        Notify::Chat(kind) => {
+            for notifier in mail.notifiers.iter().filter(|n| n.kind == kind) {
+                if let Err(e) = send_notification(notifier, &mail.subject, &message).await {
+                    error!(target: Target::file(), channel = id; "Failed to send alert: {e}");
+                }
+            }
+        }
+        _ => {
+            warn!(target: Target::file(), channel = id; "Alert \"{}\" has no recipient for <yellow>{}</>", rule.name, rule.notifier);
+        }
+    }
+}
+
+/// Monitor loop, runs in its own task as long as the server is running.
+pub async fn run(pool: Pool<Sqlite>, controllers: Arc<Mutex<ChannelController>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL));
+    let mut state = AlertState::default();
+
+    loop {
+        interval.tick().await;
+
+        let rules = match handles::select_enabled_alert_rules(&pool).await {
+            Ok(rules) => rules,
+            Err(e) => {
+                error!("Load alert rules failed: {e}");
+                continue;
+            }
+        };
+
+        state.retain(&rules);
+
+        let now = Local::now();
+        let mut channels: HashMap<i32, Option<(ChannelManager, Metrics)>> = HashMap::new();
+
+        for rule in &rules {
+            let Ok(metric) = Metric::from_str(&rule.metric) else {
+                continue;
+            };
+
+            if !channels.contains_key(&rule.channel_id) {
+                let manager = controllers.lock().await.get(rule.channel_id).await;
+                let mut entry = None;
+
+                // channels which are stopped on purpose are not watched
+                if let Some(manager) = manager {
+                    if manager.channel.lock().await.active {
+                        let metrics = Metrics::collect(&manager, now).await;
+                        entry = Some((manager, metrics));
+                    }
+                }
+
+                channels.insert(rule.channel_id, entry);
+            }
+
+            let Some(Some((manager, metrics))) = channels.get(&rule.channel_id) else {
+                continue;
+            };
+            let value = metrics.value(metric);
+
+            if let Some(transition) = state.check(rule, value, now) {
+                notify(manager, rule, transition, value).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+/// AMCP Control
+///
+/// `--amcp ADDR:PORT` opens a TCP server with a small part of the AMCP protocol from CasparCG, so
+/// broadcast automation which speaks AMCP can drive the channels. The AMCP channel is the
+/// ffplayout channel, layers are ignored:
+///
+/// - `LOADBG 1 "CLIP"` loads a clip from the current playlist, `PLAY 1` plays it.
+/// - `PLAY 1 "CLIP"` plays a clip from the current playlist now, `PLAY 1` alone starts a stopped
+///   channel.
+/// - `STOP 1` stops the channel.
+/// - `CG 1 ADD` and `CG 1 UPDATE` show the template data in the text overlay, `CG 1 CLEAR` and
+///   `CG 1 STOP` remove it.
+/// - `CLEAR 1` removes the loaded clip and the text.
+/// - `INFO`, `VERSION`, `PING` and `BYE`.
+///
+/// Clip names are like in CasparCG: the path from the storage, without extension and case
+/// insensitive. AMCP has no authentication, the port should only be reachable from the control
+/// network.
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+};
+
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
+
+use crate::player::{
+    controller::{ChannelController, ChannelManager},
+    utils::Media,
+};
+use crate::utils::{
+    control::{remote_control, ProcessCtl, RemoteCtl},
+    logging::Target,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AmcpCommand {
+    /// `LOADBG`, the clip plays with the next `PLAY`.
+    Load {
+        channel: i32,
+        clip: String,
+    },
+    /// `PLAY`, with a clip or the loaded one.
+    Play {
+        channel: i32,
+        clip: Option<String>,
+    },
+    Stop(i32),
+    Clear(i32),
+    /// `CG ADD/UPDATE`, and `CG CLEAR/STOP` with empty text.
+    Text {
+        channel: i32,
+        text: String,
+    },
+    Info,
+    Version,
+    Ping,
+    Bye,
+}
+
+/// Split a command line into parameters, quoted parameters can have spaces and escapes.
+pub fn split_params(line: &str) -> Vec<String> {
+    let mut params = vec![];
+    let mut param = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => match chars.next() {
+                Some('n') => param.push('\n'),
+                Some(c) => param.push(c),
+                None => {}
+            },
+            '"' => {
+                // empty quotes are a parameter too
+                if quoted && param.is_empty() {
+                    params.push(String::new());
+                }
+
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !param.is_empty() {
+                    params.push(std::mem::take(&mut param));
+                }
+            }
+            c => param.push(c),
+        }
+    }
+
+    if !param.is_empty() {
+        params.push(param);
+    }
+
+    params
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Text from CG template data: the first value from the XML of the CasparCG client, from a
+/// JSON object, or the plain text.
+pub fn template_text(data: &str) -> String {
+    let data = data.trim();
+
+    if data.starts_with('<') {
+        return data
+            .split_once("value=\"")
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(value, _)| unescape_xml(value))
+            .unwrap_or_default();
+    }
+
+    if data.starts_with('{') {
+        if let Ok(serde_json::Value::Object(map)) = serde_json::from_str(data) {
+            return map
+                .values()
+                .find_map(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default();
+        }
+    }
+
+    data.to_string()
+}
+
+fn clip_name(source: &str) -> String {
+    Path::new(source)
+        .with_extension("")
+        .to_string_lossy()
+        .replace('\\', "/")
+        .to_lowercase()
+}
+
+/// Index from the first clip with this name in the playlist, from `from` on.
+pub fn find_clip(list: &[Media], from: usize, name: &str) -> Option<usize> {
+    let name = clip_name(name);
+
+    list.iter()
+        .enumerate()
+        .skip(from)
+        .find(|(_, node)| {
+            let source = clip_name(&node.source);
+
+            source == name || source.ends_with(&format!("/{name}"))
+        })
+        .map(|(i, _)| i)
+}
+
+fn channel(param: Option<&String>) -> Result<i32, u16> {
+    // the layer after the dash is ignored
+    param
+        .and_then(|p| p.split('-').next())
+        .and_then(|c| c.parse::<i32>().ok())
+        .ok_or(401)
+}
+
+/// Command from the parameters of a line, or the AMCP error code.
+pub fn parse_command(params: &[String]) -> Result<AmcpCommand, u16> {
+    let Some(name) = params.first().map(|p| p.to_uppercase()) else {
+        return Err(400);
+    };
+
+    let command = match name.as_str() {
+        "LOADBG" | "LOAD" => AmcpCommand::Load {
+            channel: channel(params.get(1))?,
+            clip: params.get(2).cloned().ok_or(402)?,
+        },
+        "PLAY" => AmcpCommand::Play {
+            channel: channel(params.get(1))?,
+            clip: params.get(2).cloned(),
+        },
+        "STOP" => AmcpCommand::Stop(channel(params.get(1))?),
+        "CLEAR" => AmcpCommand::Clear(channel(params.get(1))?),
+        "CG" => {
+            let channel = channel(params.get(1))?;
+            let action = params.get(2).map(|p| p.to_uppercase()).ok_or(402)?;
+
+            match action.as_str() {
+                // CG 1-20 ADD 1 "template" 1 "data"
+                "ADD" => AmcpCommand::Text {
+                    channel,
+                    text: template_text(params.get(6).map_or("", |d| d.as_str())),
+                },
+                // CG 1-20 UPDATE 1 "data"
+                "UPDATE" => AmcpCommand::Text {
+                    channel,
+                    text: template_text(params.get(4).ok_or(402)?),
+                },
+                "CLEAR" | "STOP" | "REMOVE" => AmcpCommand::Text {
+                    channel,
+                    text: String::new(),
+                },
+                _ => return Err(400),
+            }
+        }
+        "INFO" => AmcpCommand::Info,
+        "VERSION" => AmcpCommand::Version,
+        "PING" => AmcpCommand::Ping,
+        "BYE" => AmcpCommand::Bye,
+        _ => return Err(400),
+    };
+
+    Ok(command)
+}
+
+/// Reply line for a return code.
+pub fn reply(code: u16, name: &str) -> String {
+    match code {
+        202 => format!("202 {name} OK\r\n"),
+        400 => "400 ERROR\r\n".to_string(),
+        401 => format!("401 {name} ERROR\r\n"),
+        _ => format!("{code} {name} FAILED\r\n"),
+    }
+}
+
+/// Clips from `LOADBG`, per channel.
+type Loaded = Arc<Mutex<HashMap<i32, usize>>>;
+
+async fn info(controllers: &Arc<Mutex<ChannelController>>) -> String {
+    let mut lines = "200 INFO OK\r\n".to_string();
+    let managers = controllers.lock().await.channels.clone();
+
+    for manager in managers {
+        let id = manager.channel.lock().await.id;
+        let state = manager.state.lock().await.state;
+
+        lines.push_str(&format!("{id} {}\r\n", state.to_string().to_uppercase()));
+    }
+
+    lines.push_str("\r\n");
+
+    lines
+}
+
+/// Next clip with this name in the playlist, or the first one before.
+async fn find_playlist_clip(manager: &ChannelManager, clip: &str) -> Result<usize, u16> {
+    let index = manager.current_index.load(Ordering::SeqCst);
+    let list = manager.current_list.lock().await;
+
+    find_clip(&list, index, clip)
+        .or_else(|| find_clip(&list, 0, clip))
+        .ok_or(404)
+}
+
+async fn run_command(
+    pool: &Pool<Sqlite>,
+    manager: &ChannelManager,
+    loaded: &Loaded,
+    command: AmcpCommand,
+) -> Result<(), u16> {
+    let control = match command {
+        AmcpCommand::Load { channel, clip } => {
+            let index = find_playlist_clip(manager, &clip).await?;
+            loaded.lock().await.insert(channel, index);
+
+            return Ok(());
+        }
+        AmcpCommand::Play {
+            clip: Some(clip), ..
+        } => RemoteCtl::Jump(find_playlist_clip(manager, &clip).await?),
+        AmcpCommand::Play { channel, .. } => match loaded.lock().await.remove(&channel) {
+            Some(index) => RemoteCtl::Jump(index),
+            None if manager.is_alive.load(Ordering::SeqCst) => return Ok(()),
+            None => RemoteCtl::Process(ProcessCtl::Start),
+        },
+        AmcpCommand::Stop(_) => RemoteCtl::Process(ProcessCtl::Stop),
+        AmcpCommand::Clear(channel) => {
+            loaded.lock().await.remove(&channel);
+            RemoteCtl::Text(String::new())
+        }
+        AmcpCommand::Text { text, .. } => RemoteCtl::Text(text),
+        _ => return Ok(()),
+    };
+
+    if let Err(e) = remote_control(pool, manager, control).await {
+        let id = manager.channel.lock().await.id;
+        error!(target: Target::file(), channel = id; "AMCP: {e}");
+
+        return Err(501);
+    }
+
+    Ok(())
+}
+
+async fn handle(
+    pool: &Pool<Sqlite>,
+    controllers: &Arc<Mutex<ChannelController>>,
+    loaded: &Loaded,
+    line: &str,
+) -> Option<String> {
+    let params = split_params(line);
+    let name = params.first().map(|p| p.to_uppercase()).unwrap_or_default();
+
+    let command = match parse_command(&params) {
+        Ok(command) => command,
+        Err(code) => return Some(reply(code, &name)),
+    };
+
+    let channel = match &command {
+        AmcpCommand::Info => return Some(info(controllers).await),
+        AmcpCommand::Version => {
+            return Some(format!(
+                "201 VERSION OK\r\nffplayout {}\r\n",
+                env!("CARGO_PKG_VERSION")
+            ))
+        }
+        AmcpCommand::Ping => return Some("PONG\r\n".to_string()),
+        AmcpCommand::Bye => return None,
+        AmcpCommand::Load { channel, .. }
+        | AmcpCommand::Play { channel, .. }
+        | AmcpCommand::Text { channel, .. } => *channel,
+        AmcpCommand::Stop(channel) | AmcpCommand::Clear(channel) => *channel,
+    };
+
+    let Some(manager) = controllers.lock().await.get(channel).await else {
+        return Some(reply(401, &name));
+    };
+
+    info!(target: Target::file(), channel = channel; "AMCP command: <yellow>{}</>", line.trim());
+
+    match run_command(pool, &manager, loaded, command).await {
+        Ok(_) => Some(reply(202, &name)),
+        Err(code) => Some(reply(code, &name)),
+    }
+}
+
+async fn client(
+    stream: TcpStream,
+    pool: Pool<Sqlite>,
+    controllers: Arc<Mutex<ChannelController>>,
+    loaded: Loaded,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(answer) = handle(&pool, &controllers, &loaded, &line).await else {
+            break;
+        };
+
+        if writer.write_all(answer.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// AMCP server, runs in its own task as long as the server is running.
+pub async fn run(addr: String, pool: Pool<Sqlite>, controllers: Arc<Mutex<ChannelController>>) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("AMCP: listen on <b><magenta>{addr}</></b> failed: {e}");
+            return;
+        }
+    };
+    let loaded = Loaded::default();
+
+    info!("AMCP: listen on <b><magenta>{addr}</></b>");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("AMCP: connection from {peer}");
+
+                tokio::spawn(client(
+                    stream,
+                    pool.clone(),
+                    controllers.clone(),
+                    loaded.clone(),
+                ));
+            }
+            Err(e) => error!("AMCP: accept failed: {e}"),
+        }
+    }
+}
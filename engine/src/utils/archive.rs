@@ -0,0 +1,260 @@
+/// System Export/Import
+///
+/// The whole instance state, database snapshot and the playlists from all channels,
+/// is packed in a single gzip compressed JSON archive. Importing an archive replaces
+/// the current database, so over the API the archive is only staged and applied on next start.
+///
+/// Playlists are written to the playlist folders from the channels in the restored database.
+/// The paths in the archive are not used.
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use async_walkdir::WalkDir;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Local;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteConnectOptions, Pool, Sqlite, SqlitePool};
+use tokio::fs;
+use tokio_stream::StreamExt;
+
+use crate::db::{db_backup, db_restore_to, handles, DB_PATH};
+use crate::utils::{errors::ServiceError, expand_path};
+
+const PENDING_FILE: &str = "ffplayout_import.json.gz";
+
+/// Limit for the archive, compressed and uncompressed.
+pub const MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ArchiveChannel {
+    pub id: i32,
+    pub playlists: String,
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Archive {
+    pub version: String,
+    pub created: String,
+    pub database: String,
+    pub channels: Vec<ArchiveChannel>,
+}
+
+impl Archive {
+    pub fn decode(data: &[u8]) -> Result<Self, ServiceError> {
+        let mut json = String::new();
+        GzDecoder::new(data)
+            .take(MAX_SIZE + 1)
+            .read_to_string(&mut json)
+            .map_err(|e| ServiceError::BadRequest(format!("Archive is broken: {e}")))?;
+
+        if json.len() as u64 > MAX_SIZE {
+            return Err(ServiceError::BadRequest(format!(
+                "Archive is bigger than {} MB",
+                MAX_SIZE / 1024 / 1024
+            )));
+        }
+
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, ServiceError> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&serde_json::to_vec(self)?)?;
+
+        Ok(encoder.finish()?)
+    }
+}
+
+fn temp_db() -> PathBuf {
+    env::temp_dir().join(format!(
+        "ffplayout_{}.db",
+        Local::now().format("%Y%m%d%H%M%S%f")
+    ))
+}
+
+async fn read_playlists(path: &Path) -> Result<BTreeMap<String, String>, ServiceError> {
+    let mut files = BTreeMap::new();
+    let mut entries = WalkDir::new(path);
+
+    while let Some(entry) = entries.next().await {
+        let file = entry.map_err(|e| e.to_string())?.path();
+
+        if file.is_file() && file.extension().is_some_and(|e| e == "json") {
+            if let Ok(relative) = file.strip_prefix(path) {
+                files.insert(
+                    relative.to_string_lossy().to_string(),
+                    fs::read_to_string(&file).await?,
+                );
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Pack database and playlists from all channels.
+pub async fn export(pool: &Pool<Sqlite>) -> Result<Vec<u8>, ServiceError> {
+    let db_file = temp_db();
+
+    db_backup(pool, &db_file).await.map_err(|e| e.to_string())?;
+    let database = STANDARD.encode(fs::read(&db_file).await?);
+    fs::remove_file(&db_file).await?;
+
+    let mut channels = vec![];
+
+    for channel in handles::select_related_channels(pool, None).await? {
+        let path = expand_path(&channel.playlists);
+        let files = if path.is_dir() {
+            read_playlists(&path).await?
+        } else {
+            BTreeMap::new()
+        };
+
+        channels.push(ArchiveChannel {
+            id: channel.id,
+            playlists: channel.playlists,
+            files,
+        });
+    }
+
+    Archive {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        created: Local::now().to_rfc3339(),
+        database,
+        channels,
+    }
+    .encode()
+}
+
+/// Playlist folders from the channels in the database file of the archive.
+///
+/// Only id and playlists are read, so it works also with a database from an older version.
+async fn archive_playlists(db_file: &Path) -> Result<HashMap<i32, PathBuf>, ServiceError> {
+    let conn = SqlitePool::connect_with(
+        SqliteConnectOptions::new()
+            .filename(db_file)
+            .read_only(true),
+    )
+    .await?;
+    let channels: Result<Vec<(i32, String)>, _> =
+        sqlx::query_as("SELECT id, playlists FROM channels")
+            .fetch_all(&conn)
+            .await;
+    conn.close().await;
+
+    Ok(channels?
+        .into_iter()
+        .map(|(id, playlists)| (id, expand_path(&playlists)))
+        .collect())
+}
+
+/// Replace database and write playlists from archive.
+///
+/// The pool gets closed by the database restore, so it can't be used afterwards,
+/// also not when writing the playlists fails.
+pub async fn import(pool: &Pool<Sqlite>, data: &[u8]) -> Result<(), ServiceError> {
+    let db_path = DB_PATH.as_ref().map_err(|e| e.to_string())?;
+
+    import_to(pool, data, db_path).await
+}
+
+/// Replace the database file `db_path` and write playlists from archive, see [`import`].
+pub async fn import_to(
+    pool: &Pool<Sqlite>,
+    data: &[u8],
+    db_path: &Path,
+) -> Result<(), ServiceError> {
+    let archive = Archive::decode(data)?;
+    let db_file = temp_db();
+
+    fs::write(
+        &db_file,
+        STANDARD
+            .decode(&archive.database)
+            .map_err(|e| e.to_string())?,
+    )
+    .await?;
+
+    let restored = match archive_playlists(&db_file).await {
+        Ok(playlists) => db_restore_to(pool, &db_file, db_path)
+            .await
+            .map(|_| playlists)
+            .map_err(|e| ServiceError::from(e.to_string())),
+        Err(e) => Err(e),
+    };
+    fs::remove_file(&db_file).await?;
+    let playlists = restored?;
+
+    for channel in archive.channels {
+        let Some(root) = playlists.get(&channel.id) else {
+            warn!(
+                "Skip playlists from channel {}, it is not in the archive database",
+                channel.id
+            );
+            continue;
+        };
+
+        for (relative, content) in channel.files {
+            let relative = Path::new(&relative);
+
+            // don't write outside of the playlist folder
+            if !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            {
+                warn!("Skip playlist: <b><magenta>{}</></b>", relative.display());
+                continue;
+            }
+
+            let target = root.join(relative);
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+
+            fs::write(target, content).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn pending_path() -> Option<PathBuf> {
+    DB_PATH
+        .as_ref()
+        .ok()
+        .map(|p| p.with_file_name(PENDING_FILE))
+}
+
+/// Validate archive and save it for the import on next start.
+pub async fn stage_import(data: &[u8]) -> Result<(), ServiceError> {
+    Archive::decode(data)?;
+
+    let path = pending_path().ok_or(ServiceError::InternalServerError)?;
+    fs::write(path, data).await?;
+
+    Ok(())
+}
+
+/// Import a staged archive, returns true when an import was done.
+///
+/// The archive is kept when the import fails, so it can be tried again.
+pub async fn apply_pending(pool: &Pool<Sqlite>) -> Result<bool, ServiceError> {
+    let Some(path) = pending_path().filter(|p| p.is_file()) else {
+        return Ok(false);
+    };
+
+    let data = fs::read(&path).await?;
+
+    import(pool, &data).await?;
+    fs::remove_file(&path).await?;
+
+    Ok(true)
+}
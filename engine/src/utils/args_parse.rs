@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::MetadataExt;
@@ -7,20 +10,33 @@ use clap::Parser;
 use inquire::{Confirm, CustomType, Password, PasswordDisplayMode, Text};
 use sqlx::{Pool, Sqlite};
 
-#[cfg(target_family = "unix")]
 use tokio::fs;
 
 use crate::db::{
-    handles,
-    models::{Channel, User},
+    db_backup, db_pre_migration_backup, db_restore, db_version, handles,
+    models::{Channel, ConfigPreset, User},
+    pending_migrations,
 };
 use crate::utils::{
     advanced_config::AdvancedConfig,
-    config::{OutputMode, PlayoutConfig},
+    archive,
+    as_run::{export_range, AsRunReport},
+    channels::{drop_channel, insert_channel},
+    clock::ClockSource,
+    config::{get_config, OutputMode, PlayoutConfig},
     copy_assets,
+    legacy_config::migrate_yaml,
+    media_metadata::import_metadata,
+    users::{change_user, list_users, remove_user, UserUpdate},
 };
 use crate::ARGS;
 
+/// Paths from the package installation, like the defaults in the database.
+const DEFAULT_STORAGE: &str = "/var/lib/ffplayout/tv-media";
+const DEFAULT_PLAYLISTS: &str = "/var/lib/ffplayout/playlists";
+const DEFAULT_LOGS: &str = "/var/log/ffplayout";
+const DEFAULT_PUBLIC: &str = "/usr/share/ffplayout/public";
+
 #[derive(Parser, Debug, Default, Clone)]
 #[clap(version,
     about = "ffplayout - 24/7 broadcasting solution",
@@ -41,13 +57,29 @@ pub struct Args {
     )]
     pub init: bool,
 
-    #[clap(short, long, help_heading = Some("Initial Setup"), help = "Create admin user")]
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup"),
+        help = "Don't ask, values which are not given stay like they are"
+    )]
+    pub yes: bool,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup"),
+        help = "Use the default paths for storage, playlists, logs and public files"
+    )]
+    pub default_paths: bool,
+
+    #[clap(short, long, env = "ADMIN_USER", help_heading = Some("Initial Setup"), help = "Create admin user")]
     pub username: Option<String>,
 
-    #[clap(short, long, help_heading = Some("Initial Setup"), help = "Admin mail address")]
+    #[clap(short, long, env = "ADMIN_MAIL", help_heading = Some("Initial Setup"), help = "Admin mail address")]
     pub mail: Option<String>,
 
-    #[clap(short, long, help_heading = Some("Initial Setup"), help = "Admin password")]
+    #[clap(short, long, env = "ADMIN_PASSWORD", help_heading = Some("Initial Setup"), help = "Admin password")]
     pub password: Option<String>,
 
     #[clap(long, env, help_heading = Some("Initial Setup"), help = "Storage root path")]
@@ -74,15 +106,128 @@ pub struct Args {
     #[clap(long, env, help_heading = Some("Initial Setup / General"), help = "Path to public files, also HLS playlists")]
     pub public: Option<String>,
 
-    #[clap(long, help_heading = Some("Initial Setup / Playlist"), help = "Path to playlist, or playlist root folder")]
+    #[clap(long, env, help_heading = Some("Initial Setup / Playlist"), help = "Path to playlist, or playlist root folder")]
     pub playlists: Option<String>,
 
+    #[clap(long, env, help_heading = Some("Initial Setup / Backup"), help = "Enable scheduled backups to this path")]
+    pub backup_path: Option<String>,
+
+    #[clap(long, env, help_heading = Some("Initial Setup / Backup"), help = "Backup interval in hours")]
+    pub backup_interval: Option<u32>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup / Backup"),
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of backups to keep"
+    )]
+    pub backup_keep: Option<u32>,
+
+    #[clap(long, env, help_heading = Some("Initial Setup / Backup"), help = "Add a media file manifest to backups")]
+    pub backup_media_manifest: bool,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup / Backup"),
+        help = "Command for uploading backups, gets the backup folder as argument"
+    )]
+    pub backup_upload: Option<String>,
+
+    #[clap(long, env, help_heading = Some("Initial Setup / General"), help = "Keep play history for the given days, 0 disables it")]
+    pub play_history_days: Option<u32>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup"),
+        help = "Filler folder which is shared by all channels, relative paths are inside the storage root"
+    )]
+    pub filler_pool: Option<String>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup"),
+        help = "Sentry DSN for reporting panics and repeated errors, empty disables it"
+    )]
+    pub sentry_dsn: Option<String>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("Initial Setup"),
+        help = "Reference clock for the playout time: ntp://host[:port] or ptp:///dev/ptp0, empty uses the system clock"
+    )]
+    pub clock_source: Option<String>,
+
     #[clap(long, help_heading = Some("General"), help = "Add or update a global admin user")]
     pub user_set: bool,
 
+    #[clap(long, help_heading = Some("General / Users"), help = "List users with role and channels")]
+    pub list_users: bool,
+
+    #[clap(
+        long,
+        help_heading = Some("General / Users"),
+        help = "Change the user with --password, --mail, --role and --channel, asks for a new password without them"
+    )]
+    pub update_user: Option<String>,
+
+    #[clap(
+        long,
+        help_heading = Some("General / Users"),
+        help = "Role for --update-user: global_admin, channel_admin, user, guest"
+    )]
+    pub role: Option<String>,
+
+    #[clap(long, help_heading = Some("General / Users"), help = "Delete the user with the given name")]
+    pub delete_user: Option<String>,
+
     #[clap(long, env, help_heading = Some("General"), help = "Path to database file")]
     pub db: Option<PathBuf>,
 
+    #[clap(long, help_heading = Some("General"), help = "Write a consistent database snapshot to file")]
+    pub backup_db: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Export as-run report from channel to CSV file, or PDF with .pdf, - for stdout"
+    )]
+    pub export_history: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        num_args = 1..=2,
+        value_names = ["START", "END"],
+        help = "Date range for --export-history, END is exclusive, without END one day is exported"
+    )]
+    pub history_range: Option<Vec<String>>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Restore database from backup file. WARNING: this will replace all configurations!"
+    )]
+    pub restore_db: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Export database and playlists from all channels to archive file"
+    )]
+    pub export_system: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Import system archive. WARNING: this will replace all configurations!"
+    )]
+    pub import_system: Option<PathBuf>,
+
     #[clap(
         long,
         help_heading = Some("General"),
@@ -90,6 +235,21 @@ pub struct Args {
     )]
     pub drop_db: bool,
 
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Backup the database and apply pending migrations, with --dry-run only list them"
+    )]
+    pub migrate_db: bool,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Backup the database and apply pending migrations at start, without --migrate-db"
+    )]
+    pub auto_migrate: bool,
+
     #[clap(
         long,
         help_heading = Some("General"),
@@ -110,12 +270,112 @@ pub struct Args {
     #[clap(long, help_heading = Some("General"), help = "Import channel configuration from file")]
     pub import_config: Option<PathBuf>,
 
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        value_name = "FILE",
+        help = "Show the fields which differ between the channel configuration and a file"
+    )]
+    pub diff_config: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Import title, category and custom metadata for media files from a CSV file"
+    )]
+    pub import_metadata: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Migrate ffplayout.yml (and advanced.yml from same folder) from older versions to channel"
+    )]
+    pub migrate_yaml: Option<PathBuf>,
+
     #[clap(long, help_heading = Some("General"), help = "List available channel ids")]
     pub list_channels: bool,
 
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Check ffmpeg, database, paths, ports and service user, and print what to fix"
+    )]
+    pub doctor: bool,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Probe the media files from all or the given channels and report unreadable ones"
+    )]
+    pub scan_media: bool,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Check the config from all or the given channels, the exit code tells the failure class"
+    )]
+    pub check_config: bool,
+
+    #[clap(long, help_heading = Some("General"), help = "Add a new channel with the given name")]
+    pub add_channel: Option<String>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Copy configuration for --add-channel from this channel id"
+    )]
+    pub from_channel: Option<i32>,
+
+    #[clap(long, help_heading = Some("General"), help = "Rename the channel from --channel")]
+    pub rename_channel: Option<String>,
+
+    #[clap(
+        long,
+        help_heading = Some("General"),
+        help = "Delete the channel(s) from --channel, media and playlist folders are kept"
+    )]
+    pub delete_channel: bool,
+
+    #[clap(long, help_heading = Some("General / Presets"), help = "List available config presets")]
+    pub list_presets: bool,
+
+    #[clap(
+        long,
+        help_heading = Some("General / Presets"),
+        help = "Save processing and output settings from channel as named config preset"
+    )]
+    pub save_preset: Option<String>,
+
+    #[clap(long, help_heading = Some("General / Presets"), help = "Apply named config preset to channel(s)")]
+    pub apply_preset: Option<String>,
+
     #[clap(short, env, long, help_heading = Some("General"), help = "Listen on IP:PORT, like: 127.0.0.1:8787")]
     pub listen: Option<String>,
 
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Listen for OSC remote control on UDP IP:PORT, like: 0.0.0.0:9000"
+    )]
+    pub osc: Option<String>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Listen for AMCP control, like from CasparCG automation, on TCP IP:PORT, like: 0.0.0.0:5250"
+    )]
+    pub amcp: Option<String>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "On shutdown, let the current clips finish, wait at most the given seconds"
+    )]
+    pub drain: Option<u64>,
+
     #[clap(
         long,
         env,
@@ -133,6 +393,41 @@ pub struct Args {
     #[clap(long, env, help_heading = Some("General"), help = "Add timestamp to log line")]
     pub log_timestamp: bool,
 
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Rotate log file also when it is larger than the given MB"
+    )]
+    pub log_max_size: Option<u64>,
+
+    #[clap(long, env, help_heading = Some("General"), help = "Compress rotated log files")]
+    pub log_compress: bool,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Log format: text, json (one JSON record per line)"
+    )]
+    pub log_format: Option<String>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Send logs to syslog, like: udp://127.0.0.1:514, tcp://127.0.0.1:514 or unix:///dev/log"
+    )]
+    pub syslog: Option<String>,
+
+    #[clap(
+        long,
+        env,
+        help_heading = Some("General"),
+        help = "Syslog facility: user, daemon, local0 to local7, etc."
+    )]
+    pub syslog_facility: Option<String>,
+
     #[clap(
         short,
         long,
@@ -170,12 +465,77 @@ pub struct Args {
     #[clap(short = 'T', long, help_heading = Some("Playlist"), help = "JSON template file for generating playlist")]
     pub template: Option<PathBuf>,
 
+    #[clap(
+        long,
+        help_heading = Some("Playlist"),
+        help = "Template by name, from the templates folder in the playlist path",
+        conflicts_with = "template"
+    )]
+    pub template_name: Option<String>,
+
+    #[clap(
+        long,
+        help_heading = Some("Playlist"),
+        help = "Maximum share from a template category in percent, like: news=20",
+        value_parser = parse_quota,
+        num_args = 1..,
+    )]
+    pub quota: Option<Vec<(String, f64)>>,
+
+    #[clap(long, help_heading = Some("Playlist"), help = "Seed for shuffle, the same seed generates the same playlists")]
+    pub seed: Option<u64>,
+
+    #[clap(long, help_heading = Some("Playlist"), help = "Print generated playlists to stdout, instead of saving them")]
+    pub stdout: bool,
+
     #[clap(long, help_heading = Some("Playlist"), help = "Only validate given playlist")]
     pub validate: bool,
 
+    #[clap(
+        long,
+        help_heading = Some("Playlist"),
+        help = "Validate all stored playlists from today on, from all or the given channels",
+        conflicts_with = "validate"
+    )]
+    pub validate_all: bool,
+
     #[clap(long, env, help_heading = Some("Playout"), help = "Run playout without webserver and frontend")]
     pub foreground: bool,
 
+    #[clap(
+        long,
+        help_heading = Some("Playout"),
+        help = "Run in foreground with a terminal dashboard instead of the log output"
+    )]
+    pub tui: bool,
+
+    #[clap(
+        long,
+        help_heading = Some("Playout"),
+        num_args = 0..=1,
+        default_missing_value = "60",
+        value_name = "SECONDS",
+        help = "Run the channel settings against a test source and report fps, speed and CPU"
+    )]
+    pub benchmark: Option<u64>,
+
+    #[clap(
+        long,
+        help_heading = Some("Playout"),
+        num_args = 0..=1,
+        default_missing_value = "10",
+        value_name = "SECONDS",
+        help = "Send a test signal to every stream target and report if it was accepted"
+    )]
+    pub test_output: Option<u64>,
+
+    #[clap(
+        long,
+        help_heading = Some("Playout"),
+        help = "Print the ffmpeg commands for the playlist from today, without running them. With --migrate-db list the pending migrations"
+    )]
+    pub dry_run: bool,
+
     #[clap(short, long, help_heading = Some("Playout"), help = "Play folder content")]
     pub folder: Option<PathBuf>,
 
@@ -189,7 +549,35 @@ pub struct Args {
     pub skip_validation: bool,
 }
 
-fn global_user(args: &mut Args) {
+/// Quota for the playlist generator, like `news=20`.
+pub fn parse_quota(value: &str) -> Result<(String, f64), String> {
+    let (category, percent) = value
+        .split_once('=')
+        .ok_or(format!("Quota needs category=percent, got: {value}"))?;
+    let percent = percent
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("Quota percent {percent}: {e}"))?;
+
+    if category.trim().is_empty() || !(0.0..=100.0).contains(&percent) {
+        return Err(format!(
+            "Quota needs a category and a percent from 0 to 100, got: {value}"
+        ));
+    }
+
+    Ok((category.trim().to_string(), percent))
+}
+
+fn global_user(args: &mut Args) -> Result<(), i32> {
+    if args.yes {
+        if args.username.is_none() || args.password.is_none() || args.mail.is_none() {
+            eprintln!("Admin user needed! Use `--username`, `--mail` and `--password`");
+            return Err(1);
+        }
+
+        return Ok(());
+    }
+
     if args.username.is_none() {
         args.username = Text::new("Username:").prompt().ok();
     }
@@ -204,6 +592,55 @@ fn global_user(args: &mut Args) {
     if args.mail.is_none() {
         args.mail = Text::new("Email:").prompt().ok();
     }
+
+    Ok(())
+}
+
+/// Apply named config preset to the channel.
+async fn apply_preset(pool: &Pool<Sqlite>, name: &str, id: i32) -> Result<(), String> {
+    let preset = handles::select_config_preset(pool, name)
+        .await
+        .map_err(|_| format!("Config preset \"{name}\" not found!"))?;
+    let mut config = PlayoutConfig::new(pool, id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    preset.apply(&mut config).map_err(|e| e.to_string())?;
+
+    handles::update_configuration(pool, config.general.id, config)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    println!("Apply config preset \"{name}\" to channel {id} done...");
+
+    Ok(())
+}
+
+/// Value from the arguments, the default path or the prompt. With `--yes` the current value
+/// stays, instead of asking for it.
+fn init_value(
+    arg: Option<String>,
+    default_path: Option<&str>,
+    prompt: &str,
+    current: String,
+) -> String {
+    if let Some(value) = arg {
+        return value;
+    }
+
+    if let Some(path) = default_path.filter(|_| ARGS.default_paths) {
+        return path.to_string();
+    }
+
+    if ARGS.yes {
+        return current;
+    }
+
+    Text::new(prompt)
+        .with_default(&current)
+        .with_formatter(&clean_input)
+        .prompt()
+        .unwrap_or(current)
 }
 
 fn clean_input(input: &str) -> String {
@@ -213,10 +650,162 @@ fn clean_input(input: &str) -> String {
         .to_string()
 }
 
+/// As-run report from one channel, to file or stdout.
+async fn export_history(pool: &Pool<Sqlite>, path: &Path) -> Result<(), String> {
+    let (Some([id]), Some(range)) = (ARGS.channel.as_deref(), &ARGS.history_range) else {
+        return Err(
+            "One channel and a date range are needed! Use `--channel 1 --history-range 2024-10-01`"
+                .to_string(),
+        );
+    };
+
+    let (start, end) = export_range(range)?;
+    let channel = handles::select_channel(pool, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let items = handles::select_as_run(pool, *id, &start, &end)
+        .await
+        .map_err(|e| e.to_string())?;
+    let report = AsRunReport::new(channel.name, start, end, items);
+
+    let data = if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+    {
+        report.to_pdf()
+    } else {
+        report.to_csv().into_bytes()
+    };
+
+    if path == Path::new("-") {
+        return std::io::stdout()
+            .write_all(&data)
+            .map_err(|e| e.to_string());
+    }
+
+    fs::write(path, data).await.map_err(|e| e.to_string())?;
+
+    println!("Export {} clips to: {}", report.items.len(), path.display());
+
+    Ok(())
+}
+
+/// List the pending migrations and apply them after confirmation, with a backup before.
+async fn migrate_db(pool: &Pool<Sqlite>) -> Result<(), String> {
+    let pending = pending_migrations(pool).await;
+
+    match db_version(pool).await {
+        Some(version) => println!("Database version: {version}"),
+        None => println!("Database is new"),
+    }
+
+    if pending.is_empty() {
+        println!("No pending migrations");
+        return Ok(());
+    }
+
+    println!("Pending migrations:");
+
+    for (version, description) in &pending {
+        println!("  {version}: {description}");
+    }
+
+    if ARGS.dry_run {
+        return Ok(());
+    }
+
+    if !ARGS.yes
+        && !Confirm::new("Apply migrations:")
+            .with_default(false)
+            .prompt()
+            .unwrap_or(false)
+    {
+        return Err("Migrations not applied".to_string());
+    }
+
+    if db_version(pool).await.is_some() {
+        let path = db_pre_migration_backup(pool)
+            .await
+            .map_err(|e| format!("Backup failed, migrations not applied: {e}"))?;
+
+        println!("Backup database to: {}", path.display());
+    }
+
+    handles::db_migrate(pool).await.map_err(|e| e.to_string())?;
+
+    println!("Applied {} migrations", pending.len());
+
+    Ok(())
+}
+
 pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
     let mut args = ARGS.clone();
 
+    if let Some(path) = &ARGS.import_system {
+        let result = match fs::read(path).await {
+            Ok(data) => archive::import(pool, &data)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        return match result {
+            Ok(_) => {
+                println!("Import system archive from: {}", path.display());
+                Err(0)
+            }
+            Err(e) => {
+                eprintln!("Import system archive: {e}");
+                Err(1)
+            }
+        };
+    }
+
+    if let Some(path) = &ARGS.restore_db {
+        return match db_restore(pool, path).await {
+            Ok(_) => {
+                println!("Restore database from: {}", path.display());
+                Err(0)
+            }
+            Err(e) => {
+                eprintln!("Restore database: {e}");
+                Err(1)
+            }
+        };
+    }
+
+    if ARGS.migrate_db {
+        return match migrate_db(pool).await {
+            Ok(_) => Err(0),
+            Err(e) => {
+                eprintln!("Migrate database: {e}");
+                Err(1)
+            }
+        };
+    }
+
     if !args.dump_advanced && !args.dump_config && !args.drop_db {
+        let pending = pending_migrations(pool).await;
+
+        // a new database gets its tables without asking
+        if !pending.is_empty() && db_version(pool).await.is_some() {
+            if !ARGS.auto_migrate {
+                eprintln!(
+                    "Database has {} pending migrations, run `ffplayout --migrate-db` first!",
+                    pending.len()
+                );
+                return Err(1);
+            }
+
+            match db_pre_migration_backup(pool).await {
+                Ok(path) => println!("Backup database to: {}", path.display()),
+                Err(e) => {
+                    eprintln!("Backup failed, migrations not applied: {e}");
+                    return Err(1);
+                }
+            }
+        }
+
         if let Err(e) = handles::db_migrate(pool).await {
             panic!("{e}");
         };
@@ -233,72 +822,44 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
         let mut global = handles::select_global(pool).await.map_err(|_| 1)?;
 
         if check_user.unwrap_or_default().is_empty() {
-            global_user(&mut args);
+            global_user(&mut args)?;
         }
 
-        if let Some(st) = args.storage {
-            global.storage = st;
-        } else {
-            global.storage = Text::new("Storage path:")
-                .with_default(&global.storage)
-                .with_formatter(&clean_input)
-                .prompt()
-                .unwrap_or(global.storage);
-        }
-
-        if let Some(pl) = args.playlists {
-            global.playlists = pl;
-        } else {
-            global.playlists = Text::new("Playlist path:")
-                .with_default(&global.playlists)
-                .with_formatter(&clean_input)
-                .prompt()
-                .unwrap_or(global.playlists);
-        }
-
-        if let Some(lp) = args.logs {
-            global.logs = lp;
-        } else {
-            global.logs = Text::new("Logging path:")
-                .with_default(&global.logs)
-                .with_formatter(&clean_input)
-                .prompt()
-                .unwrap_or(global.logs);
-        }
-
-        if let Some(p) = args.public {
-            global.public = p;
-        } else {
-            global.public = Text::new("Public (HLS) path:")
-                .with_default(&global.public)
-                .with_formatter(&clean_input)
-                .prompt()
-                .unwrap_or(global.public);
-        }
-
-        if let Some(smtp) = args.smtp_server {
-            global.smtp_server = smtp;
-        } else {
-            global.smtp_server = Text::new("SMTP server:")
-                .with_default(&global.smtp_server)
-                .with_formatter(&clean_input)
-                .prompt()
-                .unwrap_or(global.smtp_server);
-        }
-
-        if let Some(user) = args.smtp_user {
-            global.smtp_user = user;
-        } else {
-            global.smtp_user = Text::new("SMTP user:")
-                .with_default(&global.smtp_user)
-                .with_formatter(&clean_input)
-                .prompt()
-                .unwrap_or(global.smtp_user);
-        }
+        global.storage = init_value(
+            args.storage.take(),
+            Some(DEFAULT_STORAGE),
+            "Storage path:",
+            global.storage,
+        );
+        global.playlists = init_value(
+            args.playlists.take(),
+            Some(DEFAULT_PLAYLISTS),
+            "Playlist path:",
+            global.playlists,
+        );
+        global.logs = init_value(
+            args.logs.take(),
+            Some(DEFAULT_LOGS),
+            "Logging path:",
+            global.logs,
+        );
+        global.public = init_value(
+            args.public.take(),
+            Some(DEFAULT_PUBLIC),
+            "Public (HLS) path:",
+            global.public,
+        );
+        global.smtp_server = init_value(
+            args.smtp_server.take(),
+            None,
+            "SMTP server:",
+            global.smtp_server,
+        );
+        global.smtp_user = init_value(args.smtp_user.take(), None, "SMTP user:", global.smtp_user);
 
         if let Some(pass) = args.smtp_password {
             global.smtp_password = pass;
-        } else {
+        } else if !args.yes {
             let pass = Password::new("SMTP password:")
                 .with_help_message("Hit enter to use existing one")
                 .with_display_mode(PasswordDisplayMode::Masked)
@@ -312,7 +873,7 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
 
         if args.smtp_starttls {
             global.smtp_starttls = true;
-        } else {
+        } else if !args.yes {
             global.smtp_starttls = Confirm::new("SMTP use TLS")
                 .with_default(false)
                 .prompt()
@@ -321,13 +882,56 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
 
         if let Some(port) = args.smtp_port {
             global.smtp_port = port;
-        } else {
+        } else if !args.yes {
             global.smtp_port = CustomType::<u16>::new("SMTP port:")
                 .with_default(global.smtp_port)
                 .prompt()
                 .unwrap_or(global.smtp_port);
         }
 
+        if let Some(path) = args.backup_path {
+            global.backup_enable = !path.is_empty();
+            global.backup_path = path;
+        }
+
+        if let Some(interval) = args.backup_interval {
+            global.backup_interval = interval;
+        }
+
+        if let Some(keep) = args.backup_keep {
+            global.backup_keep = keep;
+        }
+
+        if args.backup_media_manifest {
+            global.backup_media_manifest = true;
+        }
+
+        if let Some(cmd) = args.backup_upload {
+            global.backup_upload = cmd;
+        }
+
+        if let Some(days) = args.play_history_days {
+            global.play_history_days = days;
+        }
+
+        if let Some(path) = args.filler_pool {
+            global.filler_pool = path;
+        }
+
+        if let Some(dsn) = args.sentry_dsn {
+            global.sentry_dsn = dsn;
+        }
+
+        if let Some(source) = args.clock_source {
+            match source.parse::<ClockSource>() {
+                Ok(_) => global.clock_source = source,
+                Err(e) => {
+                    eprintln!("{e}");
+                    error_code = 1;
+                }
+            }
+        }
+
         if let Err(e) = handles::update_global(pool, global.clone()).await {
             eprintln!("{e}");
             error_code = 1;
@@ -367,7 +971,7 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
 
         println!("\nSet global settings done...");
     } else if args.user_set {
-        global_user(&mut args);
+        global_user(&mut args)?;
     }
 
     if let Some(username) = args.username {
@@ -393,6 +997,114 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
         println!("Create/update global admin user \"{username}\" done...");
     }
 
+    if ARGS.list_users {
+        match list_users(pool).await {
+            Ok(lines) => {
+                println!("Users:\n{}", lines.join("\n"));
+                error_code = 0;
+            }
+            Err(e) => {
+                eprintln!("List users: {e}");
+                error_code = 1;
+            }
+        }
+    }
+
+    if let Some(name) = &ARGS.update_user {
+        let mut update = UserUpdate {
+            mail: ARGS.mail.clone(),
+            password: ARGS.password.clone(),
+            role: ARGS.role.clone(),
+            channels: ARGS.channel.clone(),
+        };
+
+        if update.is_empty() && !ARGS.yes {
+            update.password = Password::new(&format!("New password for \"{name}\":"))
+                .with_display_mode(PasswordDisplayMode::Masked)
+                .prompt()
+                .ok();
+        }
+
+        if update.is_empty() {
+            eprintln!("Nothing to change! Use `--password`, `--mail`, `--role` or `--channel`");
+            error_code = 1;
+        } else {
+            match change_user(pool, name, update).await {
+                Ok(_) => {
+                    println!("Update user \"{name}\" done...");
+                    error_code = 0;
+                }
+                Err(e) => {
+                    eprintln!("Update user: {e}");
+                    error_code = 1;
+                }
+            }
+        }
+    }
+
+    if let Some(name) = &ARGS.delete_user {
+        if ARGS.yes
+            || Confirm::new(&format!("Delete user \"{name}\"?"))
+                .with_default(false)
+                .prompt()
+                .unwrap_or(false)
+        {
+            match remove_user(pool, name).await {
+                Ok(_) => {
+                    println!("Delete user \"{name}\" done...");
+                    error_code = 0;
+                }
+                Err(e) => {
+                    eprintln!("Delete user: {e}");
+                    error_code = 1;
+                }
+            }
+        } else {
+            error_code = 1;
+        }
+    }
+
+    if let Some(path) = &ARGS.export_system {
+        let result = match archive::export(pool).await {
+            Ok(data) => fs::write(path, data).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(_) => {
+                println!("Export system archive to: {}", path.display());
+                error_code = 0;
+            }
+            Err(e) => {
+                eprintln!("Export system archive: {e}");
+                error_code = 1;
+            }
+        };
+    }
+
+    if let Some(path) = &ARGS.backup_db {
+        match db_backup(pool, path).await {
+            Ok(_) => {
+                println!("Backup database to: {}", path.display());
+                error_code = 0;
+            }
+            Err(e) => {
+                eprintln!("Backup database: {e}");
+                error_code = 1;
+            }
+        };
+    }
+
+    if let Some(path) = &ARGS.export_history {
+        error_code = match export_history(pool, path).await {
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!("Export history: {e}");
+                1
+            }
+        };
+    }
+
     if ARGS.list_channels {
         let chl = channels
             .iter()
@@ -410,10 +1122,86 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
         error_code = 0;
     }
 
+    if let Some(path) = &ARGS.migrate_yaml {
+        if let Some(channel) = &ARGS.channel {
+            for id in channel {
+                match migrate_yaml(pool, *id, path).await {
+                    Ok(_) => {
+                        println!("Migrate {} to channel {id} done...", path.display());
+                        error_code = 0;
+                    }
+                    Err(e) => {
+                        eprintln!("Migrate config: {e}");
+                        error_code = 1;
+                    }
+                };
+            }
+        } else {
+            eprintln!("Channel ID(s) needed! Use `--channel 1 ...`");
+            error_code = 1;
+        }
+    }
+
+    if let Some(name) = &ARGS.add_channel {
+        let base = channels
+            .iter()
+            .find(|c| c.id == ARGS.from_channel.unwrap_or(1))
+            .cloned()
+            .unwrap_or_default();
+        let id = channels.iter().map(|c| c.id).max().unwrap_or_default() + 1;
+        // replace the id folder from the base channel with the new id
+        let channel_path = |path: &str| {
+            let mut path = Path::new(path);
+
+            if path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().parse::<i32>().is_ok())
+            {
+                path = path.parent().unwrap_or(path);
+            }
+
+            path.join(id.to_string()).to_string_lossy().to_string()
+        };
+
+        let channel = Channel {
+            name: name.clone(),
+            preview_url: base
+                .preview_url
+                .replace(&format!("/{}/", base.id), &format!("/{id}/")),
+            extra_extensions: base.extra_extensions.clone(),
+            public: channel_path(&base.public),
+            playlists: channel_path(&base.playlists),
+            storage: channel_path(&base.storage),
+            timezone: base.timezone,
+            ..Default::default()
+        };
+
+        match insert_channel(pool, channel, ARGS.from_channel).await {
+            Ok((channel, _)) => {
+                println!("Add channel {}: '{}'", channel.id, channel.name);
+                error_code = 0;
+
+                // the preset is for the new channel
+                if let Some(name) = &ARGS.apply_preset {
+                    if let Err(e) = apply_preset(pool, name, channel.id).await {
+                        eprintln!("Apply preset: {e}");
+                        error_code = 1;
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Add channel: {e}");
+                error_code = 1;
+            }
+        };
+    }
+
     if ARGS.dump_advanced {
         if let Some(channel) = &ARGS.channel {
             for id in channel {
-                match AdvancedConfig::dump(pool, *id).await {
+                match AdvancedConfig::dump(pool, *id, Path::new(&format!("advanced_{id}.toml")))
+                    .await
+                {
                     Ok(_) => {
                         println!("Dump config to: advanced_{id}.toml");
                         error_code = 0;
@@ -433,7 +1221,9 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
     if ARGS.dump_config {
         if let Some(channel) = &ARGS.channel {
             for id in channel {
-                match PlayoutConfig::dump(pool, *id).await {
+                match PlayoutConfig::dump(pool, *id, Path::new(&format!("ffplayout_{id}.toml")))
+                    .await
+                {
                     Ok(_) => {
                         println!("Dump config to: ffplayout_{id}.toml");
                         error_code = 0;
@@ -490,6 +1280,189 @@ pub async fn run_args(pool: &Pool<Sqlite>) -> Result<(), i32> {
         }
     }
 
+    if let Some(path) = &ARGS.import_metadata {
+        match (&ARGS.channel, fs::read_to_string(path).await) {
+            (Some(channel), Ok(text)) => {
+                error_code = 0;
+
+                for id in channel {
+                    let result = match get_config(pool, *id).await {
+                        Ok(config) => import_metadata(pool, &config, &text).await,
+                        Err(e) => Err(e),
+                    };
+
+                    match result {
+                        Ok(report) => {
+                            println!("Channel {id}: {}", report.lines().join("\n"));
+                        }
+                        Err(e) => {
+                            eprintln!("Channel {id}: {e}");
+                            error_code = 1;
+                        }
+                    }
+                }
+            }
+            (None, _) => {
+                eprintln!("Channel ID(s) needed! Use `--channel 1 ...`");
+                error_code = 1;
+            }
+            (_, Err(e)) => {
+                eprintln!("Read {path:?} failed: {e}");
+                error_code = 1;
+            }
+        }
+    }
+
+    if ARGS.list_presets {
+        match handles::select_config_presets(pool).await {
+            Ok(presets) => {
+                println!(
+                    "Available config presets:\n{}",
+                    presets
+                        .iter()
+                        .map(|p| format!(
+                            "    {}: '{}' ({}x{}, {} fps, {})",
+                            p.id,
+                            p.name,
+                            p.processing_width,
+                            p.processing_height,
+                            p.processing_fps,
+                            p.output_mode
+                        ))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                );
+                error_code = 0;
+            }
+            Err(e) => {
+                eprintln!("List presets: {e}");
+                error_code = 1;
+            }
+        };
+    }
+
+    if let Some(name) = &ARGS.save_preset {
+        match ARGS.channel.as_ref().and_then(|c| c.first()) {
+            Some(id) => {
+                let result = match PlayoutConfig::new(pool, *id).await {
+                    Ok(config) => {
+                        let preset = ConfigPreset::from(name, &config);
+
+                        let saved = match handles::select_config_preset(pool, name).await {
+                            Ok(old) => handles::update_config_preset(pool, old.id, preset).await,
+                            Err(_) => handles::insert_config_preset(pool, preset).await,
+                        };
+
+                        saved.map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+
+                match result {
+                    Ok(_) => {
+                        println!("Save config preset \"{name}\" from channel {id} done...");
+                        error_code = 0;
+                    }
+                    Err(e) => {
+                        eprintln!("Save preset: {e}");
+                        error_code = 1;
+                    }
+                }
+            }
+            None => {
+                eprintln!("Channel ID needed! Use `--channel 1`");
+                error_code = 1;
+            }
+        }
+    }
+
+    if let Some(name) = ARGS
+        .apply_preset
+        .as_ref()
+        .filter(|_| ARGS.add_channel.is_none())
+    {
+        if let Some(channel) = &ARGS.channel {
+            for id in channel {
+                match apply_preset(pool, name, *id).await {
+                    Ok(_) => error_code = 0,
+                    Err(e) => {
+                        eprintln!("Apply preset: {e}");
+                        error_code = 1;
+                    }
+                }
+            }
+        } else {
+            eprintln!("Channel ID(s) needed! Use `--channel 1 ...`");
+            error_code = 1;
+        }
+    }
+
+    if let Some(name) = &ARGS.rename_channel {
+        if let Some(&[id]) = ARGS.channel.as_deref() {
+            let result = match handles::select_channel(pool, &id).await {
+                Ok(mut channel) => {
+                    channel.name.clone_from(name);
+
+                    handles::update_channel(pool, id, channel).await
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(_) => {
+                    println!("Rename channel {id} to '{name}'");
+                    error_code = 0;
+                }
+                Err(e) => {
+                    eprintln!("Rename channel: {e}");
+                    error_code = 1;
+                }
+            }
+        } else {
+            eprintln!("Channel ID needed! Use `--channel 1`");
+            error_code = 1;
+        }
+    }
+
+    if ARGS.delete_channel {
+        if let Some(channel) = &ARGS.channel {
+            for id in channel {
+                let name = channels
+                    .iter()
+                    .find(|c| c.id == *id)
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default();
+
+                if !ARGS.yes
+                    && !Confirm::new(&format!("Delete channel {id}: '{name}'?"))
+                        .with_default(false)
+                        .prompt()
+                        .unwrap_or(false)
+                {
+                    error_code = 1;
+                    continue;
+                }
+
+                match drop_channel(pool, *id).await {
+                    Ok(channel) => {
+                        println!(
+                            "Delete channel {id}: '{}', the folders are kept",
+                            channel.name
+                        );
+                        error_code = 0;
+                    }
+                    Err(e) => {
+                        eprintln!("Delete channel {id}: {e}");
+                        error_code = 1;
+                    }
+                }
+            }
+        } else {
+            eprintln!("Channel ID(s) needed! Use `--channel 1 ...`");
+            error_code = 1;
+        }
+    }
+
     if error_code > -1 {
         Err(error_code)
     } else {
@@ -0,0 +1,316 @@
+/// As-Run Reports
+///
+/// Export the play history from a time range as CSV or PDF, with the total airtime per program.
+/// The PDF is plain text in a monospace font, one clip per line, so it needs no extra dependencies.
+/// The same report can be exported with `--export-history`, without the web server.
+use std::{collections::HashMap, path::Path};
+
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use serde::Serialize;
+
+use crate::db::models::PlayHistory;
+use crate::player::utils::sec_to_time;
+use crate::utils::play_history::HISTORY_TIME_FORMAT;
+
+/// A4 landscape, in points.
+const PAGE_WIDTH: usize = 842;
+const PAGE_HEIGHT: usize = 595;
+const MARGIN: usize = 30;
+const FONT_SIZE: usize = 8;
+const LINE_HEIGHT: usize = 10;
+/// Courier characters are 0.6 of the font size wide.
+const LINE_CHARS: usize = (PAGE_WIDTH - 2 * MARGIN) * 10 / (FONT_SIZE * 6);
+const PAGE_LINES: usize = (PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProgramTotal {
+    pub program: String,
+    pub count: usize,
+    /// Airtime in seconds.
+    pub duration: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AsRunReport {
+    pub channel: String,
+    pub start: String,
+    pub end: String,
+    pub items: Vec<PlayHistory>,
+    pub totals: Vec<ProgramTotal>,
+}
+
+/// Title from the clip, or the file name when it has no title.
+pub fn program_name(item: &PlayHistory) -> String {
+    match item.title.as_ref().filter(|t| !t.trim().is_empty()) {
+        Some(title) => title.trim().to_string(),
+        None => Path::new(&item.source)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| item.source.clone()),
+    }
+}
+
+fn range_time(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|d| d.and_time(Default::default()))
+        })
+        .map_err(|e| format!("Invalid time {value}: {e}"))
+}
+
+/// Start and end from `--history-range`, like in the API the end is exclusive. Without end,
+/// the report covers one day.
+pub fn export_range(range: &[String]) -> Result<(String, String), String> {
+    let start = range_time(range.first().ok_or("Start is required")?)?;
+    let end = match range.get(1) {
+        Some(end) => range_time(end)?,
+        None => start + TimeDelta::days(1),
+    };
+
+    if end <= start {
+        return Err("End must be after start".to_string());
+    }
+
+    Ok((
+        start.format(HISTORY_TIME_FORMAT).to_string(),
+        end.format(HISTORY_TIME_FORMAT).to_string(),
+    ))
+}
+
+/// Time which the clip was on air, without the cut parts.
+fn airtime(item: &PlayHistory) -> f64 {
+    (item.out - item.seek).max(0.0)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Cut or pad text to a fixed column width.
+fn column(value: &str, width: usize) -> String {
+    let mut text: String = value.chars().take(width).collect();
+
+    if value.chars().count() > width && width > 3 {
+        text = text.chars().take(width - 3).collect::<String>() + "...";
+    }
+
+    format!("{text:<width$}")
+}
+
+/// Escape text for a PDF string, characters outside of Latin-1 are replaced.
+fn pdf_text(value: &str) -> String {
+    let mut text = String::new();
+
+    for c in value.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                text.push('\\');
+                text.push(c);
+            }
+            ' '..='~' => text.push(c),
+            '\u{a0}'..='\u{ff}' => text.push_str(&format!("\\{:03o}", c as u32)),
+            _ => text.push('?'),
+        }
+    }
+
+    text
+}
+
+impl AsRunReport {
+    pub fn new(channel: String, start: String, end: String, items: Vec<PlayHistory>) -> Self {
+        let mut totals: HashMap<String, ProgramTotal> = HashMap::new();
+
+        for item in &items {
+            let program = program_name(item);
+            let total = totals.entry(program.clone()).or_insert(ProgramTotal {
+                program,
+                count: 0,
+                duration: 0.0,
+            });
+
+            total.count += 1;
+            total.duration += airtime(item);
+        }
+
+        let mut totals: Vec<ProgramTotal> = totals.into_values().collect();
+        totals.sort_by(|a, b| {
+            b.duration
+                .total_cmp(&a.duration)
+                .then_with(|| a.program.cmp(&b.program))
+        });
+
+        Self {
+            channel,
+            start,
+            end,
+            items,
+            totals,
+        }
+    }
+
+    pub fn airtime(&self) -> f64 {
+        self.items.iter().map(airtime).sum()
+    }
+
+    /// Clips and program totals as two CSV tables, separated by an empty line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "started,ended,program,source,category,in,out,airtime\n".to_string();
+
+        for item in &self.items {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{:.3},{:.3},{:.3}\n",
+                item.started,
+                item.ended,
+                csv_field(&program_name(item)),
+                csv_field(&item.source),
+                csv_field(&item.category),
+                item.seek,
+                item.out,
+                airtime(item)
+            ));
+        }
+
+        csv.push_str("\nprogram,count,airtime\n");
+
+        for total in &self.totals {
+            csv.push_str(&format!(
+                "{},{},{:.3}\n",
+                csv_field(&total.program),
+                total.count,
+                total.duration
+            ));
+        }
+
+        csv
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let program_width = 50;
+        let source_width = LINE_CHARS - 23 - 12 - program_width - 14 - 4;
+        let mut lines = vec![
+            format!("As-run report: {}", self.channel),
+            format!("Period: {} - {}", self.start, self.end),
+            format!(
+                "Clips: {}, airtime: {}",
+                self.items.len(),
+                sec_to_time(self.airtime())
+            ),
+            String::new(),
+            format!(
+                "{} {} {} {} {}",
+                column("Started", 23),
+                column("Airtime", 12),
+                column("Program", program_width),
+                column("Category", 14),
+                "Source"
+            ),
+        ];
+
+        for item in &self.items {
+            lines.push(format!(
+                "{} {} {} {} {}",
+                column(&item.started, 23),
+                column(&sec_to_time(airtime(item)), 12),
+                column(&program_name(item), program_width),
+                column(&item.category, 14),
+                column(&item.source, source_width).trim_end()
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "{} {} {}",
+            column("Program", program_width + 36),
+            column("Count", 8),
+            "Airtime"
+        ));
+
+        for total in &self.totals {
+            lines.push(format!(
+                "{} {} {}",
+                column(&total.program, program_width + 36),
+                column(&total.count.to_string(), 8),
+                sec_to_time(total.duration)
+            ));
+        }
+
+        lines
+    }
+
+    /// Simple PDF document, with page numbers in the footer.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let lines = self.lines();
+        let pages: Vec<&[String]> = lines.chunks(PAGE_LINES - 2).collect();
+        let count = pages.len();
+        // catalog, page tree and font come first, then page and content per page
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {count} >>",
+                (0..count)
+                    .map(|i| format!("{} 0 R", 4 + i * 2))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+        ];
+
+        for (i, page) in pages.iter().enumerate() {
+            let mut content = format!(
+                "BT /F1 {FONT_SIZE} Tf {LINE_HEIGHT} TL {MARGIN} {} Td\n",
+                PAGE_HEIGHT - MARGIN - FONT_SIZE
+            );
+
+            for line in page.iter() {
+                content.push_str(&format!("({}) Tj T*\n", pdf_text(line)));
+            }
+
+            content.push_str(&format!(
+                "ET\nBT /F1 {FONT_SIZE} Tf {} {} Td (Page {} / {count}) Tj ET\n",
+                PAGE_WIDTH - MARGIN - 80,
+                MARGIN / 2,
+                i + 1
+            ));
+
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                5 + i * 2
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{content}endstream",
+                content.len()
+            ));
+        }
+
+        let mut pdf = "%PDF-1.4\n".to_string();
+        let mut offsets = vec![];
+
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{object}\nendobj\n", i + 1));
+        }
+
+        let xref = pdf.len();
+        pdf.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+
+        for offset in offsets {
+            pdf.push_str(&format!("{offset:010} 00000 n \n"));
+        }
+
+        pdf.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        ));
+
+        pdf.into_bytes()
+    }
+}
@@ -0,0 +1,221 @@
+/// Scheduled Backups
+///
+/// Write in a fixed interval a snapshot from the database, the channel configurations
+/// and the playlists to the backup path. Optional a manifest from the media storage is added,
+/// and an upload command can be called with the backup folder as argument.
+///
+/// There is no built-in S3 or FTP client, the upload is only this command, for example a script
+/// which runs `rclone` or `aws s3 cp`. The settings are read before every backup, so changes
+/// don't need a restart.
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use async_walkdir::WalkDir;
+use chrono::Local;
+use log::*;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use tokio::{fs, process::Command, time::Instant};
+use tokio_stream::StreamExt;
+
+use crate::db::{db_backup, handles, models::GlobalSettings};
+use crate::utils::{advanced_config::AdvancedConfig, config::PlayoutConfig, errors::ServiceError};
+
+const PREFIX: &str = "backup_";
+
+/// How often the settings are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+async fn copy_tree(source: &Path, target: &Path, extension: &str) -> Result<(), ServiceError> {
+    let mut entries = WalkDir::new(source);
+
+    while let Some(entry) = entries.next().await {
+        let path = entry.map_err(|e| e.to_string())?.path();
+
+        if path.is_file() && path.extension().is_some_and(|e| e == extension) {
+            if let Ok(relative) = path.strip_prefix(source) {
+                let target_file = target.join(relative);
+
+                if let Some(parent) = target_file.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+
+                fs::copy(&path, &target_file).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn media_manifest(storage: &Path, target: &Path) -> Result<(), ServiceError> {
+    let mut entries = WalkDir::new(storage);
+    let mut files = vec![];
+
+    while let Some(entry) = entries.next().await {
+        let path = entry.map_err(|e| e.to_string())?.path();
+
+        if path.is_file() {
+            let size = fs::metadata(&path)
+                .await
+                .map(|m| m.len())
+                .unwrap_or_default();
+            let source = path.strip_prefix(storage).unwrap_or(&path);
+
+            files.push(json!({ "source": source, "size": size }));
+        }
+    }
+
+    fs::write(target, serde_json::to_string_pretty(&files)?).await?;
+
+    Ok(())
+}
+
+/// Create a new backup folder and return its path.
+pub async fn create(pool: &Pool<Sqlite>, global: &GlobalSettings) -> Result<PathBuf, ServiceError> {
+    let folder = Path::new(&global.backup_path).join(format!(
+        "{PREFIX}{}",
+        Local::now().format("%Y-%m-%d_%H%M%S")
+    ));
+
+    fs::create_dir_all(&folder).await?;
+
+    db_backup(pool, &folder.join("ffplayout.db"))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for channel in handles::select_related_channels(pool, None).await? {
+        let id = channel.id;
+
+        PlayoutConfig::dump(pool, id, &folder.join(format!("ffplayout_{id}.toml"))).await?;
+        AdvancedConfig::dump(pool, id, &folder.join(format!("advanced_{id}.toml"))).await?;
+
+        let playlists = Path::new(&channel.playlists);
+
+        if playlists.is_dir() {
+            copy_tree(
+                playlists,
+                &folder.join("playlists").join(id.to_string()),
+                "json",
+            )
+            .await?;
+        }
+
+        if global.backup_media_manifest {
+            media_manifest(
+                Path::new(&channel.storage),
+                &folder.join(format!("media_{id}.json")),
+            )
+            .await?;
+        }
+    }
+
+    Ok(folder)
+}
+
+/// Delete the oldest backups, until only `keep` folders are left. The newest one is always kept.
+pub async fn rotate(backup_path: &Path, keep: usize) -> Result<(), ServiceError> {
+    let keep = keep.max(1);
+    let mut backups = vec![];
+    let mut entries = fs::read_dir(backup_path).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.path().is_dir() && entry.file_name().to_string_lossy().starts_with(PREFIX) {
+            backups.push(entry.path());
+        }
+    }
+
+    // folder names contain the timestamp, so sorting by name sorts by age
+    backups.sort();
+
+    if backups.len() > keep {
+        for folder in &backups[..backups.len() - keep] {
+            debug!("Remove old backup: <b><magenta>{}</></b>", folder.display());
+            fs::remove_dir_all(folder).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn upload(cmd: &str, folder: &Path) -> Result<(), ServiceError> {
+    let status = Command::new(cmd)
+        .arg(folder)
+        .stdout(Stdio::null())
+        .status()
+        .await?;
+
+    if !status.success() {
+        return Err(ServiceError::Conflict(format!(
+            "Upload command exit with: {status}"
+        )));
+    }
+
+    Ok(())
+}
+
+async fn backup(pool: &Pool<Sqlite>, global: &GlobalSettings) {
+    match create(pool, global).await {
+        Ok(folder) => {
+            info!("Backup written to: <b><magenta>{}</></b>", folder.display());
+
+            if !global.backup_upload.is_empty() {
+                if let Err(e) = upload(&global.backup_upload, &folder).await {
+                    error!("Backup upload failed: {e}");
+                }
+            }
+
+            if let Err(e) =
+                rotate(Path::new(&global.backup_path), global.backup_keep as usize).await
+            {
+                error!("Backup rotation failed: {e}");
+            }
+        }
+        Err(e) => error!("Backup failed: {e}"),
+    }
+}
+
+/// Backup loop, runs in its own task as long as the server is running.
+/// The global settings are read every minute, a backup is written when the interval
+/// since the last one is over.
+pub async fn run(pool: Pool<Sqlite>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut last = Instant::now();
+    let mut enabled = false;
+
+    loop {
+        interval.tick().await;
+
+        let global = match handles::select_global(&pool).await {
+            Ok(global) => global,
+            Err(e) => {
+                error!("Backup settings can't be read: {e}");
+                continue;
+            }
+        };
+        let active = global.backup_enable && !global.backup_path.is_empty();
+        let hours = u64::from(global.backup_interval.max(1));
+
+        if active != enabled {
+            enabled = active;
+            last = Instant::now();
+
+            if active {
+                info!(
+                    "Backup every <yellow>{hours}</> hours to: <b><magenta>{}</></b>",
+                    global.backup_path
+                );
+            } else {
+                info!("Scheduled backups are disabled");
+            }
+        }
+
+        if active && last.elapsed() >= Duration::from_secs(hours * 3600) {
+            last = Instant::now();
+            backup(&pool, &global).await;
+        }
+    }
+}
@@ -0,0 +1,411 @@
+/// Encoder Benchmark
+///
+/// `--benchmark` runs the processing and output settings from a channel against a generated test
+/// clip, as fast as it goes, for the given seconds. The outputs are written to a temporary folder
+/// instead of their targets, so a running stream is not touched. At the end the reached fps,
+/// speed and CPU usage are printed, a speed under 1.0 means the box can't hold the settings.
+use std::{
+    path::Path,
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use sqlx::{Pool, Sqlite};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command},
+    sync::Mutex,
+};
+
+use crate::player::{
+    controller::ProcessUnit::*,
+    utils::{
+        prepare_output_cmd,
+        progress::{is_progress, EncoderStats},
+        Media,
+    },
+};
+use crate::utils::{
+    config::{get_config, OutputMode, PlayoutConfig},
+    errors::ServiceError,
+};
+use crate::vec_strings;
+use crate::ARGS;
+
+/// Length from the generated test clip, it loops as long as the benchmark runs.
+const CLIP_LENGTH: u64 = 10;
+
+/// Needed speed, real sources take more time to decode than the test clip.
+const MIN_SPEED: f64 = 1.1;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BenchmarkResult {
+    pub fps: f64,
+    pub speed: f64,
+    /// Average CPU usage in percent, 100 is one core.
+    pub cpu: f64,
+    pub cpu_max: f64,
+}
+
+impl BenchmarkResult {
+    pub fn sustainable(&self) -> bool {
+        self.speed >= MIN_SPEED
+    }
+}
+
+/// Test clip in the channel format, with test pattern and sine tone on all audio tracks.
+pub fn test_clip_cmd(config: &PlayoutConfig, path: &Path) -> Vec<String> {
+    let proc = &config.processing;
+    let mut cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "error",
+        "-y",
+        "-f",
+        "lavfi",
+        "-i",
+        format!(
+            "testsrc2=size={}x{}:rate={}",
+            proc.width, proc.height, proc.fps
+        ),
+        "-f",
+        "lavfi",
+        "-i",
+        "sine=frequency=1000:sample_rate=48000",
+        "-map",
+        "0:v"
+    ];
+
+    for _ in 0..proc.audio_tracks.max(1) {
+        cmd.append(&mut vec_strings!["-map", "1:a"]);
+    }
+
+    cmd.append(&mut vec_strings![
+        "-t",
+        CLIP_LENGTH,
+        "-c:v",
+        "mpeg2video",
+        "-q:v",
+        "2",
+        "-pix_fmt",
+        "yuv420p",
+        "-c:a",
+        "pcm_s16le",
+        "-ac",
+        proc.audio_channels,
+        path.to_string_lossy()
+    ]);
+
+    cmd
+}
+
+fn in_dir(dir: &Path, path: &str) -> String {
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(path.to_string());
+
+    dir.join(name).to_string_lossy().to_string()
+}
+
+/// Output parameters with all targets in `dir`. Streams get a file in their format, tee outputs
+/// go to the null muxer, pipes stay like they are.
+pub fn redirect_outputs(cmd: &[String], dir: &Path) -> Vec<String> {
+    let mut new_cmd = cmd.to_vec();
+    let mut format: Option<usize> = None;
+    let mut count = 0;
+
+    for (i, param) in cmd.iter().enumerate() {
+        let prev = if i > 0 { cmd[i - 1].as_str() } else { "-" };
+
+        if prev == "-f" {
+            format = Some(i);
+            continue;
+        }
+
+        if prev == "-hls_segment_filename" {
+            new_cmd[i] = in_dir(dir, param);
+            continue;
+        }
+
+        if i < cmd.len() - 1 && (param.starts_with('-') || prev.starts_with('-')) {
+            continue;
+        }
+
+        match format.take().map(|f| (f, cmd[f].as_str())) {
+            Some((f, "tee")) => {
+                new_cmd[f] = "null".to_string();
+                new_cmd[i] = "-".to_string();
+            }
+            Some((_, "null")) => {}
+            _ if param == "-" || param.starts_with("pipe:") => {}
+            Some((_, fmt)) if param.contains("://") => {
+                new_cmd[i] = dir
+                    .join(format!("output_{count}.{fmt}"))
+                    .to_string_lossy()
+                    .to_string();
+            }
+            _ => new_cmd[i] = in_dir(dir, param),
+        }
+
+        count += 1;
+    }
+
+    new_cmd
+}
+
+/// Read the progress from the last process, and keep the last error lines.
+fn read_stats(child: &mut Child, stats: Arc<Mutex<EncoderStats>>, errors: Arc<Mutex<Vec<String>>>) {
+    let Some(stderr) = child.stderr.take() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        let mut block = EncoderStats::default();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if is_progress(&line) {
+                if block.update(&line) {
+                    *stats.lock().await = block.clone();
+                }
+            } else {
+                let mut errors = errors.lock().await;
+                errors.push(line);
+
+                if errors.len() > 5 {
+                    errors.remove(0);
+                }
+            }
+        }
+    });
+}
+
+fn spawn(cmd: Vec<String>, stdin: Stdio, stdout: Stdio) -> Result<Child, ServiceError> {
+    Ok(Command::new("ffmpeg")
+        .args(cmd)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?)
+}
+
+async fn run_pipeline(
+    config: &PlayoutConfig,
+    dir: &Path,
+    seconds: u64,
+) -> Result<BenchmarkResult, ServiceError> {
+    let clip = dir.join("test_clip.mkv");
+    let output = Command::new("ffmpeg")
+        .args(test_clip_cmd(config, &clip))
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(ServiceError::Conflict(format!(
+            "Test clip failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let clip = clip.to_string_lossy().to_string();
+    let mut node = Media::new(0, &clip, true).await;
+    node.cmd = Some(vec_strings!["-stream_loop", "-1", "-i", clip]);
+    node.add_filter(config, &None).await;
+
+    let prefix = vec_strings!["-hide_banner", "-nostats", "-v", "error"];
+    let progress = vec_strings!["-progress", "pipe:2"];
+    let mut processes = vec![];
+
+    let mut dec_cmd = prefix.clone();
+
+    if let Some(input_cmd) = &config.advanced.decoder.input_cmd {
+        dec_cmd.append(&mut input_cmd.clone());
+    }
+
+    if config.output.mode == OutputMode::HLS {
+        // in HLS mode the decoder writes the output too
+        dec_cmd.append(&mut progress.clone());
+        dec_cmd.append(&mut node.cmd.clone().unwrap_or_default());
+        processes.push(spawn(
+            prepare_output_cmd(config, dec_cmd, &node.filter),
+            Stdio::null(),
+            Stdio::null(),
+        )?);
+    } else {
+        let desktop = config.output.mode == OutputMode::Desktop;
+
+        if desktop {
+            dec_cmd.append(&mut progress.clone());
+        }
+
+        dec_cmd.append(&mut node.cmd.clone().unwrap_or_default());
+
+        if let Some(mut filter) = node.filter.clone() {
+            dec_cmd.append(&mut filter.cmd());
+            dec_cmd.append(&mut filter.map());
+        }
+
+        if let Some(cmd) = &config.processing.cmd {
+            dec_cmd.extend_from_slice(cmd);
+        }
+
+        if desktop {
+            // the desktop output is only a player
+            processes.push(spawn(dec_cmd, Stdio::null(), Stdio::null())?);
+        } else {
+            let mut decoder = spawn(dec_cmd, Stdio::null(), Stdio::piped())?;
+            let mut enc_prefix = prefix.clone();
+            let mut media = Media {
+                unit: Encoder,
+                ..Default::default()
+            };
+            media.add_filter(config, &None).await;
+
+            enc_prefix.append(&mut progress.clone());
+
+            if let Some(input_cmd) = &config.advanced.encoder.input_cmd {
+                enc_prefix.append(&mut input_cmd.clone());
+            }
+
+            enc_prefix.append(&mut vec_strings!["-i", "pipe:0"]);
+
+            let mut encoder = spawn(
+                prepare_output_cmd(config, enc_prefix, &media.filter),
+                Stdio::piped(),
+                Stdio::null(),
+            )?;
+
+            if let (Some(mut dec_out), Some(mut enc_in)) =
+                (decoder.stdout.take(), encoder.stdin.take())
+            {
+                tokio::spawn(async move { tokio::io::copy(&mut dec_out, &mut enc_in).await });
+            }
+
+            processes.push(decoder);
+            processes.push(encoder);
+        }
+    }
+
+    let stats = Arc::new(Mutex::new(EncoderStats::default()));
+    let errors = Arc::new(Mutex::new(vec![]));
+
+    if let Some(last) = processes.last_mut() {
+        read_stats(last, stats.clone(), errors.clone());
+    }
+
+    let pids: Vec<Pid> = processes
+        .iter()
+        .filter_map(|p| p.id())
+        .map(Pid::from_u32)
+        .collect();
+    let mut sys = System::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut samples = vec![];
+    let timer = Instant::now();
+
+    while timer.elapsed() < Duration::from_secs(seconds) {
+        interval.tick().await;
+
+        if processes
+            .iter_mut()
+            .any(|p| p.try_wait().is_ok_and(|s| s.is_some()))
+        {
+            break;
+        }
+
+        sys.refresh_processes(ProcessesToUpdate::Some(&pids), true);
+
+        // the first values have no time span yet
+        if timer.elapsed() > Duration::from_secs(1) {
+            samples.push(
+                pids.iter()
+                    .filter_map(|pid| sys.process(*pid))
+                    .map(|p| p.cpu_usage() as f64)
+                    .sum::<f64>(),
+            );
+        }
+    }
+
+    for process in &mut processes {
+        let _ = process.kill().await;
+    }
+
+    let stats = stats.lock().await.clone();
+
+    if stats.frame == 0 && stats.out_time == 0.0 {
+        return Err(ServiceError::Conflict(format!(
+            "Benchmark failed: {}",
+            errors.lock().await.join(" | ")
+        )));
+    }
+
+    Ok(BenchmarkResult {
+        fps: stats.fps,
+        speed: stats.speed,
+        cpu: samples.iter().sum::<f64>() / samples.len().max(1) as f64,
+        cpu_max: samples.iter().copied().fold(0.0, f64::max),
+    })
+}
+
+async fn benchmark(
+    pool: &Pool<Sqlite>,
+    id: i32,
+    seconds: u64,
+) -> Result<BenchmarkResult, ServiceError> {
+    let mut config = get_config(pool, id).await?;
+    let dir = std::env::temp_dir().join(format!("ffplayout_benchmark_{id}"));
+
+    fs::create_dir_all(&dir).await?;
+
+    config.output.output_cmd = config
+        .output
+        .output_cmd
+        .as_ref()
+        .map(|cmd| redirect_outputs(cmd, &dir));
+
+    let result = run_pipeline(&config, &dir, seconds).await;
+    let _ = fs::remove_dir_all(&dir).await;
+
+    result
+}
+
+/// Benchmark the selected channels, one after the other. Returns the exit code, `1` when a
+/// channel can't hold its settings.
+pub async fn run(pool: &Pool<Sqlite>, seconds: u64) -> i32 {
+    let cores = std::thread::available_parallelism()
+        .map(std::num::NonZero::get)
+        .unwrap_or(1);
+    let mut code = 0;
+
+    for id in ARGS.channel.clone().unwrap_or_else(|| vec![1]) {
+        println!("Channel {id}: run benchmark for {seconds} seconds ...");
+
+        match benchmark(pool, id, seconds).await {
+            Ok(result) => {
+                println!(
+                    "  fps: {:.1}, speed: {:.2}x, CPU: {:.0}% average, {:.0}% max ({cores} cores)",
+                    result.fps, result.speed, result.cpu, result.cpu_max
+                );
+
+                if result.sustainable() {
+                    println!("  The settings run in real time.");
+                } else {
+                    println!("  The settings are too heavy, a speed from {MIN_SPEED}x is needed!");
+                    code = 1;
+                }
+            }
+            Err(e) => {
+                println!("  {e}");
+                code = 1;
+            }
+        }
+    }
+
+    code
+}
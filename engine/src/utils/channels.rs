@@ -1,13 +1,21 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use log::*;
 use sqlx::{Pool, Sqlite};
-use tokio::sync::Mutex;
+use tokio::{fs, sync::Mutex};
 
 use super::logging::MailQueue;
 use crate::db::{handles, models::Channel};
 use crate::player::controller::{ChannelController, ChannelManager};
-use crate::utils::{config::get_config, copy_assets, errors::ServiceError};
+use crate::utils::{
+    advanced_config::AdvancedConfig,
+    config::{get_config, PlayoutConfig},
+    copy_assets,
+    errors::ServiceError,
+};
 
 async fn map_global_admins(conn: &Pool<Sqlite>) -> Result<(), ServiceError> {
     let channels = handles::select_related_channels(conn, None).await?;
@@ -25,35 +33,156 @@ async fn map_global_admins(conn: &Pool<Sqlite>) -> Result<(), ServiceError> {
     Ok(())
 }
 
-pub async fn create_channel(
+/// Create the folders from the channel, new folders get the owner from their parent.
+pub async fn scaffold_paths(channel: &Channel) -> Result<(), std::io::Error> {
+    for path in [&channel.public, &channel.playlists, &channel.storage] {
+        let path = Path::new(path);
+
+        // a single playlist file can be set as playlist path
+        if path.as_os_str().is_empty()
+            || path.is_dir()
+            || path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+        {
+            continue;
+        }
+
+        fs::create_dir_all(path).await?;
+
+        #[cfg(target_family = "unix")]
+        {
+            use nix::unistd::{chown, Gid, Uid};
+            use std::os::unix::fs::MetadataExt;
+
+            if let Some(meta) = path.parent().and_then(|p| p.metadata().ok()) {
+                if Uid::current().is_root() {
+                    chown(
+                        path,
+                        Some(Uid::from_raw(meta.uid())),
+                        Some(Gid::from_raw(meta.gid())),
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the paths of the template channel with the paths of the new channel.
+fn substitute_paths(value: &str, source: &Channel, target: &Channel) -> String {
+    let mut paths = [
+        (&source.public, &target.public),
+        (&source.playlists, &target.playlists),
+        (&source.storage, &target.storage),
+    ];
+
+    // longest path first, in case one path is the parent of another
+    paths.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+    paths
+        .iter()
+        .filter(|(from, _)| !from.is_empty())
+        .fold(value.to_string(), |v, (from, to)| v.replace(*from, to))
+}
+
+/// Copy playout config, advanced config and text presets from the template channel.
+async fn copy_template(
+    conn: &Pool<Sqlite>,
+    template: i32,
+    channel: &Channel,
+) -> Result<(), ServiceError> {
+    let source = handles::select_channel(conn, &template).await?;
+    let config_id = handles::select_configuration(conn, channel.id).await?.id;
+    let mut config = PlayoutConfig::new(conn, template).await?;
+    let advanced =
+        AdvancedConfig::new(handles::select_advanced_configuration(conn, template).await?);
+
+    config.output.output_param = substitute_paths(&config.output.output_param, &source, channel);
+    config.ingest.input_param = substitute_paths(&config.ingest.input_param, &source, channel);
+    config.task.path = PathBuf::from(substitute_paths(
+        &config.task.path.to_string_lossy(),
+        &source,
+        channel,
+    ));
+
+    handles::update_configuration(conn, config_id, config).await?;
+    handles::update_advanced_configuration(conn, channel.id, advanced).await?;
+
+    for mut preset in handles::select_presets(conn, template).await? {
+        preset.channel_id = channel.id;
+        handles::insert_preset(conn, preset).await?;
+    }
+
+    info!(
+        "Channel <yellow>{}</> created from template channel <yellow>{template}</>",
+        channel.id
+    );
+
+    Ok(())
+}
+
+/// Write a new channel with its configuration to the database,
+/// optional the configuration is copied from a template channel.
+pub async fn insert_channel(
     conn: &Pool<Sqlite>,
-    controllers: Arc<Mutex<ChannelController>>,
-    queue: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
     target_channel: Channel,
-) -> Result<Channel, ServiceError> {
+    template: Option<i32>,
+) -> Result<(Channel, PlayoutConfig), ServiceError> {
     const OUTPUT_PARM: &str = "-c:v libx264 -crf 23 -x264-params keyint=50:min-keyint=25:scenecut=-1 -maxrate 1300k -bufsize 2600k -preset faster -tune zerolatency -profile:v Main -level 3.1 -c:a aac -ar 44100 -b:a 128k -flags +cgop -f hls -hls_time 6 -hls_list_size 600 -hls_flags append_list+delete_segments+omit_endlist -hls_segment_filename live/stream-%d.ts live/stream.m3u8";
 
+    if let Some(id) = template {
+        if handles::select_channel(conn, &id).await.is_err() {
+            return Err(ServiceError::BadRequest(format!(
+                "Template channel ({id}) not exists!"
+            )));
+        }
+    }
+
     let channel = handles::insert_channel(conn, target_channel).await?;
 
-    handles::new_channel_presets(conn, channel.id).await?;
+    if let Err(e) = scaffold_paths(&channel).await {
+        error!("Create channel folders: {e}");
+    }
+
     handles::update_channel(conn, channel.id, channel.clone()).await?;
     handles::insert_advanced_configuration(conn, channel.id).await?;
     handles::insert_configuration(conn, channel.id, OUTPUT_PARM).await?;
 
+    match template {
+        Some(id) => copy_template(conn, id, &channel).await?,
+        None => {
+            handles::new_channel_presets(conn, channel.id).await?;
+        }
+    }
+
     let config = get_config(conn, channel.id).await?;
 
     if let Err(e) = copy_assets(&PathBuf::from(&config.storage.path)).await {
         error!("{e}");
     };
 
+    map_global_admins(conn).await?;
+
+    Ok((channel, config))
+}
+
+pub async fn create_channel(
+    conn: &Pool<Sqlite>,
+    controllers: Arc<Mutex<ChannelController>>,
+    queue: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
+    target_channel: Channel,
+    template: Option<i32>,
+) -> Result<Channel, ServiceError> {
+    let (channel, config) = insert_channel(conn, target_channel, template).await?;
+
     let m_queue = Arc::new(Mutex::new(MailQueue::new(channel.id, config.mail.clone())));
     let manager = ChannelManager::new(Some(conn.clone()), channel.clone(), config);
 
     controllers.lock().await.add(manager);
     queue.lock().await.push(m_queue.clone());
 
-    map_global_admins(conn).await?;
-
     Ok(channel)
 }
 
@@ -63,8 +192,7 @@ pub async fn delete_channel(
     controllers: Arc<Mutex<ChannelController>>,
     queue: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
 ) -> Result<(), ServiceError> {
-    let channel = handles::select_channel(conn, &id).await?;
-    handles::delete_channel(conn, &channel.id).await?;
+    drop_channel(conn, id).await?;
     controllers.lock().await.remove(id).await;
     let mut queue_guard = queue.lock().await;
     let mut new_queue = Vec::with_capacity(queue_guard.len());
@@ -77,7 +205,15 @@ pub async fn delete_channel(
 
     *queue_guard = new_queue;
 
+    Ok(())
+}
+
+/// Delete the channel from the database only, the folders are kept.
+pub async fn drop_channel(conn: &Pool<Sqlite>, id: i32) -> Result<Channel, ServiceError> {
+    let channel = handles::select_channel(conn, &id).await?;
+    handles::delete_channel(conn, &channel.id).await?;
+
     map_global_admins(conn).await?;
 
-    Ok(())
+    Ok(channel)
 }
@@ -0,0 +1,323 @@
+/// Playout Clock
+///
+/// The playout time is the system time plus the offset to a reference clock, which can be an NTP
+/// server or a PTP hardware clock, so channels on different servers follow the same house time.
+/// The offset is measured every minute and changed slowly, so the schedule doesn't jump. When the
+/// system clock steps, the offset takes the step back, until the next measurement.
+use std::{
+    io,
+    path::PathBuf,
+    str::FromStr,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use log::*;
+use serde::Serialize;
+use tokio::{net::UdpSocket, time::Instant};
+
+/// Seconds between two measurements.
+const SYNC_INTERVAL: u64 = 64;
+/// Milliseconds per second, which the offset changes at most, like the 500 ppm from ntpd.
+const SLEW_RATE: f64 = 0.5;
+/// Bigger differences, like at start, are applied at once.
+const STEP_LIMIT: f64 = 1000.0;
+/// Difference between system and monotonic time, which counts as step.
+const STEP_DETECT: f64 = 200.0;
+/// Seconds from 1900, the NTP epoch, to 1970.
+const NTP_EPOCH: f64 = 2_208_988_800.0;
+/// PTP clocks run in TAI, which is ahead of UTC by the leap seconds since 1972.
+const TAI_OFFSET: f64 = 37.0;
+
+static CLOCK: LazyLock<RwLock<ClockStatus>> = LazyLock::new(|| RwLock::new(ClockStatus::new()));
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClockSource {
+    System,
+    /// NTP server as `host:port`.
+    Ntp(String),
+    /// PTP hardware clock device, like `/dev/ptp0`.
+    Ptp(PathBuf),
+}
+
+impl FromStr for ClockSource {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+
+        if input.is_empty() || input == "system" {
+            Ok(Self::System)
+        } else if let Some(host) = input.strip_prefix("ntp://") {
+            let host = host.trim_end_matches('/');
+
+            if host.is_empty() {
+                return Err(format!("NTP server is missing in \"{input}\""));
+            }
+
+            if host
+                .rsplit_once(':')
+                .is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+            {
+                Ok(Self::Ntp(host.to_string()))
+            } else {
+                Ok(Self::Ntp(format!("{host}:123")))
+            }
+        } else if let Some(device) = input.strip_prefix("ptp://") {
+            if device.is_empty() {
+                return Err(format!("PTP device is missing in \"{input}\""));
+            }
+
+            Ok(Self::Ptp(PathBuf::from(device)))
+        } else {
+            Err(format!(
+                "Unknown clock source \"{input}\", use ntp://host[:port] or ptp:///dev/ptp0"
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for ClockSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::Ntp(host) => write!(f, "ntp://{host}"),
+            Self::Ptp(device) => write!(f, "ptp://{}", device.display()),
+        }
+    }
+}
+
+/// State of the playout clock, offsets are in milliseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct ClockStatus {
+    pub source: String,
+    /// Offset from system to playout time, which is applied right now.
+    pub offset: f64,
+    /// Last measured offset to the reference.
+    pub measured: Option<f64>,
+    pub synced: Option<DateTime<Utc>>,
+    /// Steps from the system clock, which were taken back.
+    pub steps: usize,
+    pub error: Option<String>,
+}
+
+impl ClockStatus {
+    fn new() -> Self {
+        Self {
+            source: ClockSource::System.to_string(),
+            offset: 0.0,
+            measured: None,
+            synced: None,
+            steps: 0,
+            error: None,
+        }
+    }
+}
+
+/// Offset which is added to the system time.
+pub fn offset() -> TimeDelta {
+    match CLOCK.read() {
+        Ok(clock) => TimeDelta::microseconds((clock.offset * 1000.0) as i64),
+        Err(_) => TimeDelta::zero(),
+    }
+}
+
+pub fn status() -> ClockStatus {
+    CLOCK
+        .read()
+        .map(|c| c.clone())
+        .unwrap_or_else(|_| ClockStatus::new())
+}
+
+/// Follow the reference slowly, offsets are in milliseconds.
+#[derive(Clone, Debug, Default)]
+pub struct Discipline {
+    pub offset: f64,
+    target: f64,
+    synced: bool,
+}
+
+impl Discipline {
+    /// New measured offset to the reference, the first one and big differences are applied at once.
+    pub fn measure(&mut self, measured: f64) {
+        if !self.synced || (measured - self.offset).abs() > STEP_LIMIT {
+            self.offset = measured;
+            self.synced = true;
+        }
+
+        self.target = measured;
+    }
+
+    /// Move the offset to the target, with the slew rate.
+    pub fn tick(&mut self, seconds: f64) {
+        let limit = SLEW_RATE * seconds;
+
+        self.offset += (self.target - self.offset).clamp(-limit, limit);
+    }
+
+    /// System clock jumped, the playout time stays continuous.
+    pub fn system_step(&mut self, step: f64) {
+        self.offset -= step;
+        self.target -= step;
+    }
+}
+
+/// Seconds since 1970 from a NTP timestamp.
+pub fn ntp_time(data: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as f64;
+    let fraction = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as f64;
+
+    seconds - NTP_EPOCH + fraction / 4_294_967_296.0
+}
+
+/// Offset in milliseconds from send and receive times of client and server.
+pub fn ntp_offset(sent: f64, received: f64, transmitted: f64, arrived: f64) -> f64 {
+    ((received - sent) + (transmitted - arrived)) / 2.0 * 1000.0
+}
+
+fn unix_now() -> f64 {
+    Utc::now().timestamp_micros() as f64 / 1_000_000.0
+}
+
+async fn measure_ntp(host: &str) -> io::Result<f64> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(host).await?;
+
+    // leap indicator 0, version 4, client mode
+    let mut request = [0u8; 48];
+    request[0] = 0x23;
+
+    let sent = unix_now();
+    socket.send(&request).await?;
+
+    let mut reply = [0u8; 48];
+    let num = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "NTP server sends no reply"))??;
+    let arrived = unix_now();
+
+    if num < 48 || reply[1] == 0 {
+        return Err(io::Error::other("NTP server is not synchronized"));
+    }
+
+    Ok(ntp_offset(
+        sent,
+        ntp_time(&reply[32..40]),
+        ntp_time(&reply[40..48]),
+        arrived,
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn measure_ptp(device: &std::path::Path) -> io::Result<f64> {
+    use std::os::fd::AsRawFd;
+
+    use nix::time::{clock_gettime, ClockId};
+
+    let file = std::fs::File::open(device)?;
+    // dynamic clock id from the file descriptor, like FD_TO_CLOCKID in the kernel headers
+    let clock = ClockId::from_raw((!file.as_raw_fd() << 3) | 3);
+
+    let before = unix_now();
+    let ptp = clock_gettime(clock)?;
+    let after = unix_now();
+    let ptp = ptp.tv_sec() as f64 + ptp.tv_nsec() as f64 / 1_000_000_000.0 - TAI_OFFSET;
+
+    Ok((ptp - (before + after) / 2.0) * 1000.0)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn measure_ptp(_device: &std::path::Path) -> io::Result<f64> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+async fn measure(source: &ClockSource) -> io::Result<f64> {
+    match source {
+        ClockSource::System => Ok(0.0),
+        ClockSource::Ntp(host) => measure_ntp(host).await,
+        ClockSource::Ptp(device) => measure_ptp(device),
+    }
+}
+
+/// Clock loop, runs in its own task as long as the server is running.
+pub async fn run(source: ClockSource) {
+    if let Ok(mut clock) = CLOCK.write() {
+        clock.source = source.to_string();
+    }
+
+    if source == ClockSource::System {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut discipline = Discipline::default();
+    let mut last = (Instant::now(), unix_now());
+    let mut next_sync = Instant::now();
+
+    loop {
+        interval.tick().await;
+
+        let now = (Instant::now(), unix_now());
+        let elapsed = (now.0 - last.0).as_secs_f64();
+        let step = (now.1 - last.1 - elapsed) * 1000.0;
+        let mut stepped = false;
+
+        last = now;
+
+        if step.abs() > STEP_DETECT {
+            warn!(
+                "System clock stepped by <yellow>{step:.0}</> ms, playout clock stays continuous"
+            );
+
+            discipline.system_step(step);
+            stepped = true;
+        }
+
+        discipline.tick(elapsed);
+
+        let mut measured = None;
+        let mut error = None;
+
+        if now.0 >= next_sync {
+            next_sync = now.0 + Duration::from_secs(SYNC_INTERVAL);
+
+            match measure(&source).await {
+                Ok(offset) => {
+                    discipline.measure(offset);
+                    measured = Some(offset);
+                }
+                Err(e) => error = Some(format!("{source}: {e}")),
+            }
+        }
+
+        let Ok(mut clock) = CLOCK.write() else {
+            continue;
+        };
+
+        clock.offset = discipline.offset;
+
+        if stepped {
+            clock.steps += 1;
+        }
+
+        if measured.is_some() {
+            if clock.synced.is_none() {
+                info!(
+                    "Playout clock follows <yellow>{source}</>, offset <yellow>{:.1}</> ms",
+                    discipline.offset
+                );
+            }
+
+            clock.measured = measured;
+            clock.synced = Some(Utc::now());
+            clock.error = None;
+        } else if let Some(e) = error {
+            if clock.error.as_ref() != Some(&e) {
+                error!("Measure playout clock failed: {e}");
+            }
+
+            clock.error = Some(e);
+        }
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
+    collections::BTreeMap,
     fmt,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-use chrono::NaiveTime;
+use chrono::{NaiveTime, Weekday};
 use chrono_tz::Tz;
 use flexi_logger::Level;
+use log::*;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use shlex::split;
@@ -15,7 +17,10 @@ use tokio::{fs, io::AsyncReadExt};
 use ts_rs::TS;
 
 use crate::db::{handles, models};
-use crate::utils::{files::norm_abs_path, gen_tcp_socket, time_to_sec};
+use crate::utils::{
+    expand_env, expand_path, files::norm_abs_path, gen_tcp_socket, logging::Target, plugins,
+    secrets, time_to_sec,
+};
 use crate::vec_strings;
 use crate::AdvancedConfig;
 use crate::ARGS;
@@ -140,6 +145,214 @@ impl FromStr for ProcessMode {
     }
 }
 
+/// Where the playlists from a channel are stored.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistBackend {
+    #[default]
+    File,
+    Database,
+}
+
+impl fmt::Display for PlaylistBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlaylistBackend::File => write!(f, "file"),
+            PlaylistBackend::Database => write!(f, "database"),
+        }
+    }
+}
+
+impl FromStr for PlaylistBackend {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "file" => Ok(Self::File),
+            "database" => Ok(Self::Database),
+            _ => Err("Use 'file' or 'database'".to_string()),
+        }
+    }
+}
+
+/// What happens when the clocks go forward and the playlist day has only 23 hours.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum DstForward {
+    /// Keep playing without a gap, clip start times move one hour later.
+    #[default]
+    Shift,
+    /// Skip the missing hour, so all following clips start on time.
+    Trim,
+}
+
+impl fmt::Display for DstForward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DstForward::Shift => write!(f, "shift"),
+            DstForward::Trim => write!(f, "trim"),
+        }
+    }
+}
+
+impl FromStr for DstForward {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "shift" => Ok(Self::Shift),
+            "trim" => Ok(Self::Trim),
+            _ => Err("Use 'shift' or 'trim'".to_string()),
+        }
+    }
+}
+
+/// What happens when the clocks go back and the playlist day has 25 hours.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum DstBackward {
+    /// Keep playing without a gap, clip start times move one hour earlier.
+    #[default]
+    Shift,
+    /// Play one hour filler, so all following clips start on time.
+    Filler,
+}
+
+impl fmt::Display for DstBackward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DstBackward::Shift => write!(f, "shift"),
+            DstBackward::Filler => write!(f, "filler"),
+        }
+    }
+}
+
+impl FromStr for DstBackward {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "shift" => Ok(Self::Shift),
+            "filler" => Ok(Self::Filler),
+            _ => Err("Use 'shift' or 'filler'".to_string()),
+        }
+    }
+}
+
+/// Video, which the encoder generates from the audio in audio only mode.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum Visualization {
+    /// The output has no video.
+    #[default]
+    Off,
+    /// Waveform from the first audio track.
+    Waves,
+    /// Scrolling frequency spectrum from the first audio track.
+    Spectrum,
+}
+
+impl fmt::Display for Visualization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Visualization::Off => write!(f, "off"),
+            Visualization::Waves => write!(f, "waves"),
+            Visualization::Spectrum => write!(f, "spectrum"),
+        }
+    }
+}
+
+impl FromStr for Visualization {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "off" => Ok(Self::Off),
+            "waves" => Ok(Self::Waves),
+            "spectrum" => Ok(Self::Spectrum),
+            _ => Err("Use 'off', 'waves' or 'spectrum'".to_string()),
+        }
+    }
+}
+
+/// How the decoder seeks to the in point of a clip.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum SeekMode {
+    /// Seek in the input, to the keyframe before the point and drop the frames up to it.
+    #[default]
+    Fast,
+    /// Decode the clip from the start and trim the output, exact also with stream copy.
+    Accurate,
+}
+
+impl fmt::Display for SeekMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SeekMode::Fast => write!(f, "fast"),
+            SeekMode::Accurate => write!(f, "accurate"),
+        }
+    }
+}
+
+impl FromStr for SeekMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "fast" => Ok(Self::Fast),
+            "accurate" => Ok(Self::Accurate),
+            _ => Err("Use 'fast' or 'accurate'".to_string()),
+        }
+    }
+}
+
+/// What happens when the playout is behind the schedule.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUp {
+    /// End the playout when it is more than the stop threshold behind, it starts again on the schedule.
+    #[default]
+    Restart,
+    /// Jump to the clip which should run now, when the playout is more than the stop threshold behind.
+    Skip,
+    /// Cut the end from the next clips, by at most the catch up rate.
+    Trim,
+    /// Play the next clips faster, by at most the catch up rate.
+    Speed,
+}
+
+impl fmt::Display for CatchUp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CatchUp::Restart => write!(f, "restart"),
+            CatchUp::Skip => write!(f, "skip"),
+            CatchUp::Trim => write!(f, "trim"),
+            CatchUp::Speed => write!(f, "speed"),
+        }
+    }
+}
+
+impl FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "restart" => Ok(Self::Restart),
+            "skip" => Ok(Self::Skip),
+            "trim" => Ok(Self::Trim),
+            "speed" => Ok(Self::Speed),
+            _ => Err("Use 'restart', 'skip', 'trim' or 'speed'".to_string()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize, TS)]
 pub struct Template {
     pub sources: Vec<Source>,
@@ -152,7 +365,13 @@ pub struct Source {
     #[ts(type = "string")]
     pub duration: NaiveTime,
     pub shuffle: bool,
+    #[serde(default)]
     pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Category for the generated clips, quotas from the command line count on it.
+    #[serde(default)]
+    pub category: String,
 }
 
 /// Channel Config
@@ -186,6 +405,7 @@ pub struct Channel {
     pub public: PathBuf,
     pub playlists: PathBuf,
     pub storage: PathBuf,
+    pub filler_pool: PathBuf,
     pub shared: bool,
     #[ts(type = "string")]
     pub timezone: Option<Tz>,
@@ -194,14 +414,42 @@ pub struct Channel {
 impl Channel {
     pub fn new(config: &models::GlobalSettings, channel: models::Channel) -> Self {
         Self {
-            logs: PathBuf::from(config.logs.clone()),
-            public: PathBuf::from(channel.public.clone()),
-            playlists: PathBuf::from(channel.playlists.clone()),
-            storage: PathBuf::from(channel.storage.clone()),
+            logs: expand_path(&config.logs),
+            public: expand_path(&channel.public),
+            playlists: expand_path(&channel.playlists),
+            storage: expand_path(&channel.storage),
+            filler_pool: filler_pool(config),
             shared: config.shared,
             timezone: channel.timezone,
         }
     }
+
+    /// Root for the filler path, the shared filler pool or the channel storage.
+    pub fn filler_root(&self, shared_filler: bool) -> &Path {
+        if shared_filler && !self.filler_pool.as_os_str().is_empty() {
+            &self.filler_pool
+        } else {
+            &self.storage
+        }
+    }
+}
+
+/// Shared filler pool from the global settings.
+///
+/// Relative paths are joined to the global storage, which is the storage root from all channels
+/// in shared storage mode. So the pool works in containers without knowing the mount point.
+fn filler_pool(config: &models::GlobalSettings) -> PathBuf {
+    if config.filler_pool.is_empty() {
+        return PathBuf::new();
+    }
+
+    let path = expand_path(&config.filler_pool);
+
+    if path.is_relative() {
+        expand_path(&config.storage).join(path)
+    } else {
+        path
+    }
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, TS)]
@@ -214,6 +462,38 @@ pub struct General {
     #[serde(skip_serializing, skip_deserializing)]
     pub channel_id: i32,
     pub stop_threshold: f64,
+    /// Restart a stalled decoder or encoder after this seconds without data, 0 disables it.
+    #[serde(default)]
+    pub watchdog: i64,
+    /// Alarm when the playout drifts more than this seconds from the schedule, 0 disables it.
+    #[serde(default)]
+    pub drift_alarm: f64,
+    /// Stop the channel when it restarts more than this times in one hour, 0 disables the limit.
+    #[serde(default)]
+    pub restart_limit: i64,
+    /// First delay in seconds before a restart, doubled on every failure in a row.
+    #[serde(default)]
+    pub restart_delay: i64,
+    #[serde(default)]
+    pub restart_max_delay: i64,
+    /// Play the slate after this failures in a row, 0 disables it.
+    #[serde(default)]
+    pub restart_slate: i64,
+    /// Alarm when a disk from storage, public or log path is used more than this percent, 0 disables it.
+    #[serde(default)]
+    pub disk_alarm: i64,
+    /// Delete HLS segments, which are in no playlist, when the public disk is used more than this percent.
+    #[serde(default)]
+    pub disk_cleanup: i64,
+    /// Token for the button endpoints, like from Stream Deck or Companion, empty disables them.
+    #[serde(default)]
+    pub control_token: String,
+    /// Targets for the tally, separated by `;`: HTTP and MQTT URLs or helper programs.
+    #[serde(default)]
+    pub tally: String,
+    /// Plugin programs, separated by `;`, they talk JSON over stdin and stdout.
+    #[serde(default)]
+    pub plugins: String,
     #[ts(skip)]
     #[serde(skip_serializing, skip_deserializing)]
     pub generate: Option<Vec<String>>,
@@ -226,6 +506,18 @@ pub struct General {
     #[ts(skip)]
     #[serde(skip_serializing, skip_deserializing)]
     pub template: Option<Template>,
+    /// Seed for the shuffle in the playlist generator, the same seed gives the same playlists.
+    #[ts(skip)]
+    #[serde(skip_serializing, skip_deserializing)]
+    pub seed: Option<u64>,
+    /// Maximum share per category in percent, from the template length.
+    #[ts(skip)]
+    #[serde(skip_serializing, skip_deserializing)]
+    pub quotas: Vec<(String, f64)>,
+    /// Print the generated playlists, instead of saving them.
+    #[ts(skip)]
+    #[serde(skip_serializing, skip_deserializing)]
+    pub generate_stdout: bool,
     #[ts(skip)]
     #[serde(skip_serializing, skip_deserializing)]
     pub skip_validation: bool,
@@ -240,10 +532,24 @@ impl General {
             id: config.id,
             channel_id: config.channel_id,
             stop_threshold: config.general_stop_threshold,
+            watchdog: config.general_watchdog,
+            drift_alarm: config.general_drift_alarm,
+            restart_limit: config.general_restart_limit,
+            restart_delay: config.general_restart_delay,
+            restart_max_delay: config.general_restart_max_delay,
+            restart_slate: config.general_restart_slate,
+            disk_alarm: config.general_disk_alarm,
+            disk_cleanup: config.general_disk_cleanup,
+            control_token: config.general_control_token.clone(),
+            tally: config.general_tally.clone(),
+            plugins: config.general_plugins.clone(),
             generate: None,
             ffmpeg_filters: vec![],
             ffmpeg_libs: vec![],
             template: None,
+            seed: None,
+            quotas: vec![],
+            generate_stdout: false,
             skip_validation: false,
             validate: false,
         }
@@ -275,6 +581,18 @@ pub struct Mail {
     #[ts(type = "string")]
     pub mail_level: Level,
     pub interval: i64,
+    /// After a mail was sent, collect messages for this seconds before the next one, 0 disables it.
+    #[serde(default)]
+    pub digest: i64,
+    /// Skip messages which were already sent in the last seconds, 0 disables it.
+    #[serde(default)]
+    pub dedup_window: i64,
+    /// Level filter per error class, like `ffmpeg` or `playlist`, on top of the other levels.
+    #[serde(default)]
+    #[ts(type = "Record<string, string>")]
+    pub class_levels: BTreeMap<String, Level>,
+    #[serde(default)]
+    pub notifiers: Vec<Notifier>,
 }
 
 impl Mail {
@@ -290,6 +608,10 @@ impl Mail {
             recipient: config.mail_recipient.clone(),
             mail_level: string_to_log_level(config.mail_level.clone()),
             interval: config.mail_interval,
+            digest: config.mail_digest,
+            dedup_window: config.mail_dedup_window,
+            class_levels: serde_json::from_str(&config.mail_class_levels).unwrap_or_default(),
+            notifiers: serde_json::from_str(&config.mail_notifiers).unwrap_or_default(),
         }
     }
 }
@@ -307,10 +629,38 @@ impl Default for Mail {
             recipient: String::default(),
             mail_level: Level::Debug,
             interval: i64::default(),
+            digest: i64::default(),
+            dedup_window: i64::default(),
+            class_levels: BTreeMap::new(),
+            notifiers: vec![],
         }
     }
 }
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum NotifierKind {
+    #[default]
+    Telegram,
+    Slack,
+    Discord,
+}
+
+/// Send log messages to a chat service, in the same interval as the mails.
+///
+/// `token` is the bot token for Telegram and the webhook URL for Slack and Discord.
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+pub struct Notifier {
+    pub kind: NotifierKind,
+    pub token: String,
+    #[serde(default)]
+    pub chat_id: String,
+    #[ts(type = "string")]
+    pub level: Level,
+}
+
 #[derive(Debug, Default, Clone, Deserialize, Serialize, TS)]
 #[ts(export, export_to = "playout_config.d.ts")]
 pub struct Logging {
@@ -360,6 +710,22 @@ pub struct Processing {
     pub vtt_enable: bool,
     #[serde(default)]
     pub vtt_dummy: Option<String>,
+    /// Cut every clip to whole frames and the matching audio samples,
+    /// so the clips join without a gap or overlap.
+    #[serde(default)]
+    pub gapless: bool,
+    /// Read clips from one decoder process, as long as they need no own input options.
+    #[serde(default)]
+    pub persistent_decoder: bool,
+    /// Decode the first seconds from the next clip, while the current one is playing.
+    #[serde(default)]
+    pub preload: bool,
+    /// Video for stream outputs in audio only mode.
+    #[serde(default)]
+    pub visualization: Visualization,
+    /// Seek for clips with an in point, playlist entries can override it.
+    #[serde(default)]
+    pub seek_mode: SeekMode,
     #[ts(skip)]
     #[serde(skip_serializing, skip_deserializing)]
     pub cmd: Option<Vec<String>>,
@@ -389,6 +755,11 @@ impl Processing {
             custom_filter: config.processing_filter.clone(),
             vtt_enable: config.processing_vtt_enable,
             vtt_dummy: config.processing_vtt_dummy.clone(),
+            gapless: config.processing_gapless,
+            persistent_decoder: config.processing_persistent_decoder,
+            preload: config.processing_preload,
+            visualization: config.processing_visualization.parse().unwrap_or_default(),
+            seek_mode: config.processing_seek_mode.parse().unwrap_or_default(),
             cmd: None,
         }
     }
@@ -400,6 +771,24 @@ pub struct Ingest {
     pub enable: bool,
     pub input_param: String,
     pub custom_filter: String,
+    /// Source B for the program switch, like a studio stream or the output from another channel.
+    #[serde(default)]
+    pub secondary_source: String,
+    /// Key for streams to an external RTMP server, like nginx-rtmp, which calls the publish callbacks.
+    #[serde(default)]
+    pub stream_key: String,
+    /// Keep SCTE-35 cues from a MPEG-TS ingest, for ad insertion after the output.
+    #[serde(default)]
+    pub scte35: bool,
+    /// Passphrase for the encryption from an SRT ingest, 10 to 79 characters.
+    #[serde(default)]
+    pub srt_passphrase: String,
+    /// Latency from an SRT ingest in milliseconds.
+    #[serde(default)]
+    pub srt_latency: i64,
+    /// Allowed stream ids from an SRT ingest, separated by `,`, a `*` at the end matches the rest.
+    #[serde(default)]
+    pub srt_streamid: String,
     #[ts(skip)]
     #[serde(skip_serializing, skip_deserializing)]
     pub input_cmd: Option<Vec<String>>,
@@ -411,6 +800,12 @@ impl Ingest {
             enable: config.ingest_enable,
             input_param: config.ingest_param.clone(),
             custom_filter: config.ingest_filter.clone(),
+            secondary_source: config.ingest_secondary_source.clone(),
+            stream_key: config.ingest_stream_key.clone(),
+            scte35: config.ingest_scte35,
+            srt_passphrase: config.ingest_srt_passphrase.clone(),
+            srt_latency: config.ingest_srt_latency,
+            srt_streamid: config.ingest_srt_streamid.clone(),
             input_cmd: None,
         }
     }
@@ -428,6 +823,37 @@ pub struct Playlist {
     #[serde(skip_serializing, skip_deserializing)]
     pub length_sec: Option<f64>,
     pub infinit: bool,
+    #[serde(default)]
+    pub weekday_overrides: Vec<WeekdayOverride>,
+    #[serde(default)]
+    pub backend: PlaylistBackend,
+    #[serde(default)]
+    pub dst_forward: DstForward,
+    #[serde(default)]
+    pub dst_backward: DstBackward,
+    #[serde(default)]
+    pub catch_up: CatchUp,
+    /// Percent of a clip, which is cut or played faster to catch up.
+    #[serde(default)]
+    pub catch_up_rate: f64,
+    /// Max seconds between reconnects to a HTTP stream entry, 0 disables reconnects.
+    #[serde(default)]
+    pub stream_reconnect: i64,
+    /// Reconnects to a HTTP stream entry, before it fails, 0 has no limit.
+    #[serde(default)]
+    pub stream_retries: i64,
+    /// Seconds a stream entry can send no data, before filler plays the rest, 0 disables it.
+    #[serde(default)]
+    pub stream_fallback: f64,
+    /// Check the playlists for the next days, 0 disables the check.
+    #[serde(default)]
+    pub check_days: i64,
+    /// Notify about a missing or invalid playlist this hours before it starts.
+    #[serde(default)]
+    pub check_hours: i64,
+    /// VAST tag from the ad server, for the `ad_break` entries.
+    #[serde(default)]
+    pub vast_url: String,
 }
 
 impl Playlist {
@@ -438,10 +864,55 @@ impl Playlist {
             length: config.playlist_length.clone(),
             length_sec: None,
             infinit: config.playlist_infinit,
+            weekday_overrides: serde_json::from_str(&config.playlist_weekday_overrides)
+                .unwrap_or_default(),
+            backend: config.playlist_backend.parse().unwrap_or_default(),
+            dst_forward: config.playlist_dst_forward.parse().unwrap_or_default(),
+            dst_backward: config.playlist_dst_backward.parse().unwrap_or_default(),
+            catch_up: config.playlist_catch_up.parse().unwrap_or_default(),
+            catch_up_rate: config.playlist_catch_up_rate,
+            stream_reconnect: config.playlist_stream_reconnect,
+            stream_retries: config.playlist_stream_retries,
+            stream_fallback: config.playlist_stream_fallback,
+            check_days: config.playlist_check_days,
+            check_hours: config.playlist_check_hours,
+            vast_url: config.playlist_vast_url.clone(),
         }
     }
 }
 
+/// Override playlist and filler settings on the given weekdays.
+///
+/// Days are named in english, like `mon`, `tue` or `saturday`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+pub struct WeekdayOverride {
+    pub days: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filler: Option<String>,
+}
+
+impl WeekdayOverride {
+    pub fn matches(&self, weekday: Weekday) -> bool {
+        self.days
+            .iter()
+            .any(|d| d.trim().parse::<Weekday>().is_ok_and(|d| d == weekday))
+    }
+
+    /// Day names which are not a weekday.
+    pub fn invalid_days(&self) -> Vec<String> {
+        self.days
+            .iter()
+            .filter(|d| d.trim().parse::<Weekday>().is_err())
+            .cloned()
+            .collect()
+    }
+}
+
 #[derive(Debug, Default, Clone, Deserialize, Serialize, TS)]
 #[ts(export, export_to = "playout_config.d.ts")]
 pub struct Storage {
@@ -457,8 +928,26 @@ pub struct Storage {
     pub filler_path: PathBuf,
     pub extensions: Vec<String>,
     pub shuffle: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub shared_filler: bool,
     #[serde(skip_deserializing)]
     pub shared_storage: bool,
+    /// Image or clip for the emergency slate, relative to the channel storage.
+    #[serde(default)]
+    pub emergency_slate: String,
+    /// Audio, which loops under the emergency slate, can be empty.
+    #[serde(default)]
+    pub emergency_audio: String,
+    #[serde(default)]
+    pub house_format: HouseFormat,
+    /// Template image for the coming up cards, relative to the channel storage.
+    #[serde(default)]
+    pub coming_up: String,
+    /// Programs on a coming up card.
+    #[serde(default)]
+    pub coming_up_count: i64,
 }
 
 impl Storage {
@@ -474,11 +963,56 @@ impl Storage {
                 .map(String::from)
                 .collect(),
             shuffle: config.storage_shuffle,
+            tags: config
+                .storage_tags
+                .split(';')
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect(),
+            shared_filler: config.storage_shared_filler,
             shared_storage,
+            emergency_slate: config.storage_emergency_slate.clone(),
+            emergency_audio: config.storage_emergency_audio.clone(),
+            house_format: serde_json::from_str(&config.storage_house_format).unwrap_or_default(),
+            coming_up: config.storage_coming_up.clone(),
+            coming_up_count: config.storage_coming_up_count,
         }
     }
 }
 
+/// House format for the media in the storage, rules without value are not checked.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, TS, PartialEq)]
+#[ts(export, export_to = "playout_config.d.ts")]
+pub struct HouseFormat {
+    /// Container names from ffprobe, like `mov` or `matroska`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub containers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub video_codecs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio_codecs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    /// Integrated loudness in LUFS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness_min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness_max: Option<f64>,
+    /// Seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_duration: Option<f64>,
+}
+
+impl HouseFormat {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Debug, Default, Clone, Deserialize, Serialize, TS)]
 #[ts(export, export_to = "playout_config.d.ts")]
 pub struct Text {
@@ -500,6 +1034,9 @@ pub struct Text {
     pub text_from_filename: bool,
     pub style: String,
     pub regex: String,
+    /// Names from more text layers, comma separated.
+    #[serde(default)]
+    pub layers: String,
 }
 
 impl Text {
@@ -514,6 +1051,7 @@ impl Text {
             text_from_filename: config.text_from_filename,
             style: config.text_style.clone(),
             regex: config.text_regex.clone(),
+            layers: config.text_layers.clone(),
         }
     }
 }
@@ -548,6 +1086,24 @@ pub struct Output {
     #[ts(skip)]
     #[serde(skip_serializing, skip_deserializing)]
     pub output_cmd: Option<Vec<String>>,
+    /// Additional encoders, which get the same feed as the main output, not used in HLS mode.
+    #[serde(default)]
+    pub renditions: Vec<Rendition>,
+    /// Seconds between decoder and encoder, 0 disables the delay buffer.
+    #[serde(default)]
+    pub delay: f64,
+    /// Folder for the delay buffer, empty keeps it in memory.
+    #[serde(default)]
+    pub delay_path: String,
+    /// Seconds of the program which are recorded for instant replays, 0 disables the recording.
+    #[serde(default)]
+    pub replay: f64,
+    /// Folder for the replay recording, empty uses the temp folder.
+    #[serde(default)]
+    pub replay_path: String,
+    /// Headers and delivery from the public files, like the HLS playlists.
+    #[serde(default)]
+    pub public_server: PublicServer,
 }
 
 impl Output {
@@ -558,10 +1114,70 @@ impl Output {
             output_count: 0,
             output_filter: None,
             output_cmd: None,
+            renditions: serde_json::from_str(&config.output_renditions).unwrap_or_default(),
+            delay: config.output_delay,
+            delay_path: config.output_delay_path.clone(),
+            replay: config.output_replay,
+            replay_path: config.output_replay_path.clone(),
+            public_server: serde_json::from_str(&config.output_public_server).unwrap_or_default(),
+        }
+    }
+}
+
+/// Delivery from the public files, the server answers without reverse proxy.
+#[derive(Debug, Clone, Deserialize, Serialize, TS, PartialEq)]
+#[ts(export, export_to = "playout_config.d.ts")]
+pub struct PublicServer {
+    /// Origins which get CORS headers, `*` allows all, empty sends no CORS headers.
+    #[serde(default)]
+    pub cors_origins: Vec<String>,
+    /// Cache-Control value per file extension, like `m3u8` or `ts`.
+    #[serde(default = "default_cache_control")]
+    pub cache_control: BTreeMap<String, String>,
+    /// Answer range requests, without them the whole file is sent.
+    #[serde(default = "default_true")]
+    pub ranges: bool,
+    /// Compress playlists with gzip, for clients which accept it.
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+impl Default for PublicServer {
+    fn default() -> Self {
+        Self {
+            cors_origins: vec![],
+            cache_control: default_cache_control(),
+            ranges: true,
+            gzip: false,
         }
     }
 }
 
+/// Playlists change all the time, segments are kept for a short time, the names come again after
+/// a restart.
+fn default_cache_control() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("m3u8".to_string(), "no-cache".to_string()),
+        ("ts".to_string(), "max-age=60".to_string()),
+        ("m4s".to_string(), "max-age=60".to_string()),
+        ("vtt".to_string(), "max-age=60".to_string()),
+    ])
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Encoder with own bitrate or destination, it reads the decoded feed from the main output.
+///
+/// `output_param` has the same format as the main output parameters.
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+#[ts(export, export_to = "playout_config.d.ts")]
+pub struct Rendition {
+    pub name: String,
+    pub output_param: String,
+}
+
 pub fn string_to_log_level(l: String) -> Level {
     match l.to_lowercase().as_str() {
         "error" => Level::Error,
@@ -602,7 +1218,6 @@ impl PlayoutConfig {
         let adv_config = handles::select_advanced_configuration(pool, channel_id).await?;
 
         let channel = Channel::new(&global, channel);
-        let advanced = AdvancedConfig::new(adv_config);
         let general = General::new(&config);
         let mail = Mail::new(&global, &config);
         let logging = Logging::new(&config);
@@ -612,6 +1227,7 @@ impl PlayoutConfig {
         let mut text = Text::new(&config);
         let task = Task::new(&config);
         let mut output = Output::new(&config);
+        let advanced = AdvancedConfig::new(adv_config).for_mode(&output.mode);
 
         if !channel.storage.is_dir() {
             tokio::fs::create_dir_all(&channel.storage)
@@ -629,7 +1245,28 @@ impl PlayoutConfig {
             tokio::fs::create_dir_all(&channel.logs).await?;
         }
 
-        let (filler_path, _, filler) = norm_abs_path(&channel.storage, &config.storage_filler)?;
+        if storage.shared_filler {
+            if channel.filler_pool.as_os_str().is_empty() {
+                warn!(target: Target::file_mail(), channel = channel_id;
+                    "No shared filler pool is set, use filler from channel storage"
+                );
+            } else if !channel.filler_pool.is_dir() {
+                warn!(target: Target::file_mail(), channel = channel_id;
+                    "Shared filler pool not exists: <b><magenta>{:?}</></b>",
+                    channel.filler_pool
+                );
+            }
+        }
+
+        // the config keeps the variables, only the paths get the values
+        let (_, _, filler) = norm_abs_path(
+            channel.filler_root(storage.shared_filler),
+            &config.storage_filler,
+        )?;
+        let (filler_path, _, _) = norm_abs_path(
+            channel.filler_root(storage.shared_filler),
+            &expand_path(&config.storage_filler).to_string_lossy(),
+        )?;
 
         storage.filler = filler;
         storage.filler_path = filler_path;
@@ -642,7 +1279,11 @@ impl PlayoutConfig {
             playlist.length_sec = Some(86400.0);
         }
 
-        let (logo_path, _, logo) = norm_abs_path(&channel.storage, &processing.logo)?;
+        let (_, _, logo) = norm_abs_path(&channel.storage, &processing.logo)?;
+        let (logo_path, _, _) = norm_abs_path(
+            &channel.storage,
+            &expand_path(&processing.logo).to_string_lossy(),
+        )?;
 
         if processing.add_logo && !logo_path.is_file() {
             processing.add_logo = false;
@@ -703,14 +1344,24 @@ impl PlayoutConfig {
 
         processing.cmd = Some(process_cmd);
 
-        ingest.input_cmd = split(ingest.input_param.as_str());
+        ingest.input_cmd = split(&expand_env(&ingest.input_param));
 
         output.output_count = 1;
         output.output_filter = None;
 
+        // `plugin:<name>` takes the output parameters from a plugin
+        let output_param = match output.output_param.trim().strip_prefix("plugin:") {
+            Some(name) if output.mode != OutputMode::Null => {
+                plugins::output_param(&general, &processing, name.trim())
+                    .await
+                    .map_err(|e| ServiceError::BadRequest(e.to_string()))?
+            }
+            _ => output.output_param.clone(),
+        };
+
         if output.mode == OutputMode::Null {
             output.output_cmd = Some(vec_strings!["-f", "null", "-"]);
-        } else if let Some(mut cmd) = split(output.output_param.as_str()) {
+        } else if let Some(mut cmd) = split(&expand_env(&output_param)) {
             // get output count according to the var_stream_map value, or by counting output parameters
             if let Some(i) = cmd.clone().iter().position(|m| m == "-var_stream_map") {
                 output.output_count = cmd[i + 1].split_whitespace().count();
@@ -799,7 +1450,9 @@ impl PlayoutConfig {
             text.node_pos = None;
         }
 
-        let (font_path, _, font) = norm_abs_path(&channel.storage, &text.font)?;
+        let (_, _, font) = norm_abs_path(&channel.storage, &text.font)?;
+        let (font_path, _, _) =
+            norm_abs_path(&channel.storage, &expand_path(&text.font).to_string_lossy())?;
         text.font = font;
         text.font_path = font_path.to_string_lossy().to_string();
 
@@ -819,11 +1472,274 @@ impl PlayoutConfig {
         })
     }
 
-    pub async fn dump(pool: &Pool<Sqlite>, id: i32) -> Result<(), ServiceError> {
-        let config = Self::new(pool, id).await?;
+    /// Return a copy of the config, with the overrides for the given weekday applied.
+    pub fn for_weekday(&self, weekday: Weekday) -> Self {
+        let mut config = self.clone();
+
+        for o in self
+            .playlist
+            .weekday_overrides
+            .iter()
+            .filter(|o| o.matches(weekday))
+        {
+            if let Some(start) = &o.day_start {
+                config.playlist.day_start.clone_from(start);
+                config.playlist.start_sec = Some(time_to_sec(start, &config.channel.timezone));
+            }
+
+            if let Some(length) = &o.length {
+                config.playlist.length.clone_from(length);
+                config.playlist.length_sec = if length.contains(':') {
+                    Some(time_to_sec(length, &config.channel.timezone))
+                } else {
+                    Some(86400.0)
+                };
+            }
+
+            if let Some(filler) = &o.filler {
+                let root = config.channel.filler_root(config.storage.shared_filler);
+
+                match norm_abs_path(root, filler).and_then(|(_, _, filler)| {
+                    norm_abs_path(root, &expand_path(&filler).to_string_lossy())
+                        .map(|(path, _, _)| (path, filler))
+                }) {
+                    Ok((filler_path, filler)) => {
+                        config.storage.filler = filler;
+                        config.storage.filler_path = filler_path;
+                    }
+                    Err(e) => {
+                        error!(target: Target::file_mail(), channel = config.general.channel_id; "Weekday filler <b><magenta>{filler}</></b>: {e}");
+                    }
+                }
+            }
+        }
+
+        config
+    }
+
+    /// Take the weekday overrides from a config, where they are resolved already,
+    /// so the filler path is not checked again for every clip.
+    pub fn keep_weekday(&self, resolved: &Self, weekday: Weekday) -> Self {
+        let mut config = self.clone();
+
+        for o in self
+            .playlist
+            .weekday_overrides
+            .iter()
+            .filter(|o| o.matches(weekday))
+        {
+            if o.day_start.is_some() {
+                config
+                    .playlist
+                    .day_start
+                    .clone_from(&resolved.playlist.day_start);
+                config.playlist.start_sec = resolved.playlist.start_sec;
+            }
+
+            if o.length.is_some() {
+                config.playlist.length.clone_from(&resolved.playlist.length);
+                config.playlist.length_sec = resolved.playlist.length_sec;
+            }
+
+            if o.filler.is_some() {
+                config.storage.filler.clone_from(&resolved.storage.filler);
+                config
+                    .storage
+                    .filler_path
+                    .clone_from(&resolved.storage.filler_path);
+            }
+        }
+
+        config
+    }
+
+    /// Weekday overrides need valid day names, otherwise they never match.
+    pub fn check_weekday_overrides(&self) -> Result<(), ServiceError> {
+        let invalid: Vec<String> = self
+            .playlist
+            .weekday_overrides
+            .iter()
+            .flat_map(WeekdayOverride::invalid_days)
+            .collect();
+
+        if !invalid.is_empty() {
+            return Err(ServiceError::BadRequest(format!(
+                "Invalid weekday in playlist overrides: {}",
+                invalid.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Mask secrets, like stream keys, in output, rendition and ingest parameters and the notifier tokens.
+    pub fn redact(&mut self) {
+        self.output.output_param = secrets::redact(&self.output.output_param);
+        self.ingest.input_param = secrets::redact(&self.ingest.input_param);
+        self.playlist.vast_url = secrets::redact(&self.playlist.vast_url);
+        self.general.tally = secrets::redact(&self.general.tally);
+
+        if !self.ingest.stream_key.is_empty() {
+            self.ingest.stream_key = secrets::MASK.to_string();
+        }
+
+        if !self.ingest.srt_passphrase.is_empty() {
+            self.ingest.srt_passphrase = secrets::MASK.to_string();
+        }
+
+        if !self.general.control_token.is_empty() {
+            self.general.control_token = secrets::MASK.to_string();
+        }
+
+        for rendition in &mut self.output.renditions {
+            rendition.output_param = secrets::redact(&rendition.output_param);
+        }
+
+        for notifier in &mut self.mail.notifiers {
+            notifier.token = secrets::MASK.to_string();
+        }
+    }
+
+    /// Put masked secrets back, from the stored config.
+    pub fn restore_secrets(&mut self, stored: &Self) {
+        self.output.output_param =
+            secrets::restore(&self.output.output_param, &stored.output.output_param);
+        self.ingest.input_param =
+            secrets::restore(&self.ingest.input_param, &stored.ingest.input_param);
+        self.playlist.vast_url =
+            secrets::restore(&self.playlist.vast_url, &stored.playlist.vast_url);
+        self.general.tally = secrets::restore(&self.general.tally, &stored.general.tally);
+
+        if self.ingest.stream_key == secrets::MASK {
+            self.ingest.stream_key.clone_from(&stored.ingest.stream_key);
+        }
+
+        if self.ingest.srt_passphrase == secrets::MASK {
+            self.ingest
+                .srt_passphrase
+                .clone_from(&stored.ingest.srt_passphrase);
+        }
+
+        if self.general.control_token == secrets::MASK {
+            self.general
+                .control_token
+                .clone_from(&stored.general.control_token);
+        }
+
+        for rendition in &mut self.output.renditions {
+            if let Some(r) = stored
+                .output
+                .renditions
+                .iter()
+                .find(|r| r.name == rendition.name)
+            {
+                rendition.output_param = secrets::restore(&rendition.output_param, &r.output_param);
+            }
+        }
+
+        for (i, notifier) in self.mail.notifiers.iter_mut().enumerate() {
+            if notifier.token == secrets::MASK {
+                if let Some(n) = stored.mail.notifiers.get(i) {
+                    notifier.token.clone_from(&n.token);
+                }
+            }
+        }
+    }
+
+    /// Reset locked fields to the stored values.
+    ///
+    /// Fields are written as `section.field`, like `output.output_param`, or only `section` to lock the whole section.
+    /// Fields which are not serialized, like the ffmpeg commands, get lost, so the config needs to be saved and reloaded.
+    pub fn keep_locked(&mut self, stored: &Self, locked: &[String]) -> Result<(), ServiceError> {
+        if locked.is_empty() {
+            return Ok(());
+        }
+
+        let mut config = serde_json::to_value(&*self)?;
+        let stored_config = serde_json::to_value(stored)?;
+
+        for field in locked {
+            match field.split_once('.') {
+                Some((section, key)) => {
+                    if let Some(value) = stored_config[section].get(key) {
+                        config[section][key] = value.clone();
+                    }
+                }
+                None => {
+                    if let Some(value) = stored_config.get(field) {
+                        config[field] = value.clone();
+                    }
+                }
+            }
+        }
+
+        *self = serde_json::from_value(config)?;
+
+        Ok(())
+    }
+
+    /// Check for settings which are valid on their own, but don't work well together.
+    pub fn warnings(&self) -> Vec<String> {
+        let processing = &self.processing;
+        let mut warnings = vec![];
+
+        #[cfg(target_family = "unix")]
+        if self.advanced.process.nice.is_some_and(|n| n < 0)
+            && !nix::unistd::Uid::effective().is_root()
+        {
+            warnings.push(
+                "Negative nice level needs root or CAP_SYS_NICE, without it the level is ignored"
+                    .to_string(),
+            );
+        }
+
+        if processing.copy_video
+            && (processing.add_logo || self.text.add_text || !processing.custom_filter.is_empty())
+        {
+            warnings.push(
+                "Video stream copy is enabled, logo, text overlay and custom filter are ignored"
+                    .to_string(),
+            );
+        }
+
+        if processing.copy_audio
+            && (processing.volume != 1.0 || !processing.custom_filter.is_empty())
+        {
+            warnings.push(
+                "Audio stream copy is enabled, volume and custom filter are ignored".to_string(),
+            );
+        }
+
+        if processing.copy_audio {
+            warnings.push(
+                "Audio stream copy is enabled, silent audio for dummy clips is generated with aevalsrc and can't be copied".to_string(),
+            );
+        }
+
+        if processing.vtt_enable && self.output.mode != OutputMode::HLS {
+            warnings.push(format!(
+                "WebVTT subtitles only work in HLS mode, output mode is {}",
+                self.output.mode
+            ));
+        }
+
+        let passphrase = self.ingest.srt_passphrase.chars().count();
+
+        if passphrase > 0 && !(10..=79).contains(&passphrase) {
+            warnings.push(format!(
+                "SRT passphrase has {passphrase} characters, it needs 10 to 79"
+            ));
+        }
+
+        warnings
+    }
+
+    pub async fn dump(pool: &Pool<Sqlite>, id: i32, path: &Path) -> Result<(), ServiceError> {
+        let mut config = Self::new(pool, id).await?;
+        config.redact();
 
         let toml_string = toml_edit::ser::to_string_pretty(&config)?;
-        tokio::fs::write(&format!("ffplayout_{id}.toml"), toml_string).await?;
+        tokio::fs::write(path, toml_string).await?;
 
         Ok(())
     }
@@ -834,7 +1750,12 @@ impl PlayoutConfig {
             let mut contents = String::new();
             file.read_to_string(&mut contents).await?;
 
-            let config: PlayoutConfig = toml_edit::de::from_str(&contents).unwrap();
+            let mut config: PlayoutConfig = toml_edit::de::from_str(&contents).unwrap();
+            config.check_weekday_overrides()?;
+
+            if let Ok(stored) = Self::new(pool, id).await {
+                config.restore_secrets(&stored);
+            }
 
             handles::update_configuration(pool, id, config).await?;
         } else {
@@ -878,6 +1799,13 @@ fn pre_audio_codec(proc_filter: &str, ingest_filter: &str, channel_count: u8) ->
     codec
 }
 
+/// Named templates are stored in the `templates` folder from the playlist path.
+pub fn template_path(playlists: &Path, name: &str) -> PathBuf {
+    playlists
+        .join("templates")
+        .join(format!("{}.json", sanitize_filename::sanitize(name)))
+}
+
 /// Read command line arguments, and override the config with them.
 pub async fn get_config(
     pool: &Pool<Sqlite>,
@@ -889,8 +1817,33 @@ pub async fn get_config(
     config.general.generate = args.generate;
     config.general.validate = args.validate;
     config.general.skip_validation = args.skip_validation;
+    config.general.seed = args.seed;
+    config.general.quotas = args.quota.unwrap_or_default();
+    config.general.generate_stdout = args.stdout;
+
+    let template_file = match (args.template, &args.template_name) {
+        (Some(file), _) => Some(file),
+        (None, Some(name)) => {
+            let playlists = args
+                .playlists
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or(config.channel.playlists.clone());
+            let file = template_path(&playlists, name);
+
+            if !file.is_file() {
+                return Err(ServiceError::BadRequest(format!(
+                    "Template {} not exists!",
+                    file.display()
+                )));
+            }
+
+            Some(file)
+        }
+        (None, None) => None,
+    };
 
-    if let Some(template_file) = args.template {
+    if let Some(template_file) = template_file {
         let mut f = fs::File::options()
             .read(true)
             .write(false)
@@ -0,0 +1,360 @@
+/// Config Check
+///
+/// `--check-config` loads the stored configuration from the channels and checks it in full,
+/// more strict than the player at startup, which turns missing parts off and goes on: paths,
+/// logo, filler, emergency slate and font are checked and probed, and the output parameters
+/// run a short test encode into a temporary folder. Every failure class has its own exit code,
+/// so deploy pipelines can react on it.
+use std::path::Path;
+
+use faccess::{AccessMode, PathExt};
+use sqlx::{Pool, Sqlite};
+use tokio::{fs, process::Command};
+
+use crate::db::{handles, models::Configuration};
+use crate::player::{
+    controller::ProcessUnit::*,
+    utils::{prepare_output_cmd, probe::MediaProbe, Media},
+};
+use crate::utils::{
+    benchmark::{redirect_outputs, test_clip_cmd},
+    config::{get_config, OutputMode, PlayoutConfig},
+    files::norm_abs_path,
+};
+use crate::vec_strings;
+use crate::ARGS;
+
+/// Failure class, the value is the exit code. With more failures, the lowest code wins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckClass {
+    /// Channel or configuration can't be loaded.
+    Load = 2,
+    /// Paths are missing or have the wrong rights.
+    Paths = 3,
+    /// Logo, filler, emergency slate or font are missing or not readable.
+    Media = 4,
+    /// Output parameters don't work with ffmpeg.
+    Output = 5,
+    /// Settings, which don't work well together.
+    Warning = 6,
+}
+
+impl CheckClass {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Load => "load",
+            Self::Paths => "paths",
+            Self::Media => "media",
+            Self::Output => "output",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// Result from `--check-config` for one channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigCheck {
+    pub channel: i32,
+    pub name: String,
+    pub failures: Vec<(CheckClass, String)>,
+}
+
+impl ConfigCheck {
+    /// Exit code from the worst failure, `0` without failures.
+    pub fn code(&self) -> i32 {
+        self.failures
+            .iter()
+            .map(|(class, _)| class.code())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Report lines, for the terminal.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Channel {} ({}): {}",
+            self.channel,
+            self.name,
+            if self.failures.is_empty() {
+                "ok".to_string()
+            } else {
+                format!("{} failures", self.failures.len())
+            }
+        )];
+
+        for (class, message) in &self.failures {
+            lines.push(format!("  [{}] {message}", class.name()));
+        }
+
+        lines
+    }
+
+    fn fail(&mut self, class: CheckClass, message: impl Into<String>) {
+        self.failures.push((class, message.into()));
+    }
+}
+
+fn check_dir(check: &mut ConfigCheck, name: &str, path: &Path, mode: AccessMode) {
+    if !path.is_dir() {
+        check.fail(CheckClass::Paths, format!("{name} {path:?} not exists"));
+    } else if path.access(mode).is_err() {
+        let access = if mode == AccessMode::READ {
+            "readable"
+        } else {
+            "writable"
+        };
+
+        check.fail(
+            CheckClass::Paths,
+            format!("{name} {path:?} is not {access}"),
+        );
+    }
+}
+
+async fn check_file(check: &mut ConfigCheck, name: &str, path: &Path) {
+    if !path.is_file() {
+        check.fail(CheckClass::Media, format!("{name} {path:?} not exists"));
+    } else if let Err(e) = MediaProbe::new(path).await {
+        check.fail(
+            CheckClass::Media,
+            format!("{name} {path:?} is not readable: {e}"),
+        );
+    }
+}
+
+/// Media from the storage settings, relative to the channel storage.
+async fn check_storage_file(
+    check: &mut ConfigCheck,
+    config: &PlayoutConfig,
+    name: &str,
+    file: &str,
+) {
+    if file.is_empty() {
+        return;
+    }
+
+    match norm_abs_path(&config.channel.storage, file) {
+        Ok((path, _, _)) => check_file(check, name, &path).await,
+        Err(e) => check.fail(CheckClass::Media, format!("{name} {file}: {e}")),
+    }
+}
+
+/// Short encode from a test clip with the output parameters, the targets are redirected to a
+/// temporary folder, so only the parameters are tested, not the connection.
+async fn check_output(check: &mut ConfigCheck, config: &PlayoutConfig) {
+    let Some(output_cmd) = &config.output.output_cmd else {
+        check.fail(CheckClass::Output, "Output parameters can't be parsed");
+        return;
+    };
+
+    let dir = std::env::temp_dir().join(format!("ffplayout_check_{}", check.channel));
+
+    if let Err(e) = fs::create_dir_all(&dir).await {
+        check.fail(CheckClass::Output, format!("Test folder {dir:?}: {e}"));
+        return;
+    }
+
+    let mut config = config.clone();
+    config.output.output_cmd = Some(redirect_outputs(output_cmd, &dir));
+
+    let clip = dir.join("test_clip.mkv");
+    let result = match Command::new("ffmpeg")
+        .args(test_clip_cmd(&config, &clip))
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => {
+            let clip = clip.to_string_lossy().to_string();
+            let mut cmd = vec_strings!["-hide_banner", "-nostats", "-v", "error"];
+
+            // in HLS mode the decoder writes the output too
+            let mut node = if config.output.mode == OutputMode::HLS {
+                Media::new(0, &clip, true).await
+            } else {
+                if let Some(input_cmd) = &config.advanced.encoder.input_cmd {
+                    cmd.append(&mut input_cmd.clone());
+                }
+
+                Media {
+                    unit: Encoder,
+                    ..Default::default()
+                }
+            };
+            node.add_filter(&config, &None).await;
+
+            cmd.append(&mut vec_strings!["-t", "2", "-i", clip]);
+
+            Command::new("ffmpeg")
+                .args(prepare_output_cmd(&config, cmd, &node.filter))
+                .output()
+                .await
+        }
+        result => result,
+    };
+
+    match result {
+        Ok(output) if !output.status.success() => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let lines: Vec<&str> = stderr.lines().collect();
+
+            check.fail(
+                CheckClass::Output,
+                format!(
+                    "Output test failed: {}",
+                    lines[lines.len().saturating_sub(3)..].join(" | ")
+                ),
+            );
+        }
+        Err(e) => check.fail(CheckClass::Output, format!("ffmpeg can't run: {e}")),
+        _ => {}
+    }
+
+    let _ = fs::remove_dir_all(&dir).await;
+}
+
+/// Check the loaded config, `stored` is the configuration like it is in the database, the
+/// player turns missing parts off while loading.
+pub async fn check_channel(
+    config: &PlayoutConfig,
+    stored: &Configuration,
+    name: String,
+) -> ConfigCheck {
+    let mut check = ConfigCheck {
+        channel: config.general.channel_id,
+        name,
+        ..Default::default()
+    };
+    let channel = &config.channel;
+
+    check_dir(&mut check, "Storage", &channel.storage, AccessMode::READ);
+    check_dir(
+        &mut check,
+        "Playlists",
+        &channel.playlists,
+        AccessMode::WRITE,
+    );
+    check_dir(&mut check, "Logs", &channel.logs, AccessMode::WRITE);
+
+    if config.output.mode == OutputMode::HLS {
+        check_dir(&mut check, "Public", &channel.public, AccessMode::WRITE);
+    }
+
+    if config.storage.shared_filler {
+        check_dir(
+            &mut check,
+            "Filler pool",
+            &channel.filler_pool,
+            AccessMode::READ,
+        );
+    }
+
+    if stored.processing_add_logo {
+        check_file(&mut check, "Logo", Path::new(&config.processing.logo_path)).await;
+    }
+
+    if !stored.storage_filler.is_empty() {
+        let filler = &config.storage.filler_path;
+
+        if filler.is_dir() {
+            check_dir(&mut check, "Filler", filler, AccessMode::READ);
+        } else {
+            check_file(&mut check, "Filler", filler).await;
+        }
+    }
+
+    check_storage_file(
+        &mut check,
+        config,
+        "Emergency slate",
+        &config.storage.emergency_slate,
+    )
+    .await;
+    check_storage_file(
+        &mut check,
+        config,
+        "Emergency audio",
+        &config.storage.emergency_audio,
+    )
+    .await;
+
+    if config.text.add_text && !Path::new(&config.text.font_path).is_file() {
+        check.fail(
+            CheckClass::Media,
+            format!("Font {:?} not exists", config.text.font_path),
+        );
+    }
+
+    // the desktop output needs a display
+    if config.output.mode != OutputMode::Desktop {
+        check_output(&mut check, config).await;
+    }
+
+    for warning in config.warnings() {
+        check.fail(CheckClass::Warning, warning);
+    }
+
+    check
+}
+
+/// Check the config from all or the selected channels. Returns the exit code from the worst
+/// failure, see [`CheckClass`], or `1` when the channels can't be read.
+pub async fn run(pool: &Pool<Sqlite>) -> i32 {
+    let channels = match handles::select_related_channels(pool, None).await {
+        Ok(channels) => channels,
+        Err(e) => {
+            eprintln!("Read channels failed: {e}");
+            return 1;
+        }
+    };
+    let mut code = 0;
+
+    if let Some(ids) = &ARGS.channel {
+        for id in ids
+            .iter()
+            .filter(|id| !channels.iter().any(|c| c.id == **id))
+        {
+            println!("Channel {id}: [load] channel not exists");
+            code = CheckClass::Load.code();
+        }
+    }
+
+    for channel in channels {
+        if ARGS
+            .channel
+            .as_ref()
+            .is_some_and(|c| !c.contains(&channel.id))
+        {
+            continue;
+        }
+
+        let loaded = match handles::select_configuration(pool, channel.id).await {
+            Ok(stored) => get_config(pool, channel.id)
+                .await
+                .map(|config| (config, stored)),
+            Err(e) => Err(e.into()),
+        };
+
+        let check = match loaded {
+            Ok((config, stored)) => check_channel(&config, &stored, channel.name.clone()).await,
+            Err(e) => ConfigCheck {
+                channel: channel.id,
+                name: channel.name.clone(),
+                failures: vec![(CheckClass::Load, e.to_string())],
+            },
+        };
+
+        for line in check.lines() {
+            println!("{line}");
+        }
+
+        if check.code() > 0 && (code == 0 || check.code() < code) {
+            code = check.code();
+        }
+    }
+
+    code
+}
@@ -0,0 +1,133 @@
+/// Config Diff
+///
+/// `--diff-config FILE` compares the stored config from a channel with a TOML file, in the format
+/// from `--dump-config`, and prints the fields which `--import-config` would change. Masked
+/// secrets in the file keep the stored value, like on the import, changed secrets are shown
+/// masked.
+use std::path::Path;
+
+use serde_json::Value;
+use sqlx::{Pool, Sqlite};
+
+use crate::utils::{config::PlayoutConfig, errors::ServiceError};
+use crate::ARGS;
+
+/// One changed field, the values are JSON formatted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigChange {
+    /// Field path, like `processing.width`.
+    pub field: String,
+    pub stored: String,
+    pub file: String,
+}
+
+fn changed_fields(prefix: &str, a: &Value, b: &Value, fields: &mut Vec<String>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let field = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+
+                changed_fields(
+                    &field,
+                    a.get(key).unwrap_or(&Value::Null),
+                    b.get(key).unwrap_or(&Value::Null),
+                    fields,
+                );
+            }
+        }
+        _ if a != b => fields.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+fn value_at(value: &Value, field: &str) -> String {
+    field
+        .split('.')
+        .try_fold(value, |v, key| v.get(key))
+        .unwrap_or(&Value::Null)
+        .to_string()
+}
+
+/// Field level differences, `file` must have the secrets restored.
+pub fn diff_configs(stored: &PlayoutConfig, file: &PlayoutConfig) -> Vec<ConfigChange> {
+    let to_value = |config: &PlayoutConfig| serde_json::to_value(config).unwrap_or_default();
+    let redacted = |config: &PlayoutConfig| {
+        let mut config = config.clone();
+        config.redact();
+        to_value(&config)
+    };
+
+    let mut fields = vec![];
+    changed_fields("", &to_value(stored), &to_value(file), &mut fields);
+
+    let (stored, file) = (redacted(stored), redacted(file));
+
+    fields
+        .into_iter()
+        .map(|field| {
+            let mut change = ConfigChange {
+                stored: value_at(&stored, &field),
+                file: value_at(&file, &field),
+                field,
+            };
+
+            // only the secret inside is different
+            if change.stored == change.file {
+                change.file = format!("{} (secret changed)", change.file);
+            }
+
+            change
+        })
+        .collect()
+}
+
+/// Read the config file and compare it with the stored config from the channel.
+pub async fn diff_file(
+    pool: &Pool<Sqlite>,
+    id: i32,
+    path: &Path,
+) -> Result<Vec<ConfigChange>, ServiceError> {
+    let stored = PlayoutConfig::new(pool, id).await?;
+    let contents = tokio::fs::read_to_string(path).await?;
+    let mut config: PlayoutConfig = toml_edit::de::from_str(&contents)
+        .map_err(|e| ServiceError::BadRequest(format!("{path:?}: {e}")))?;
+
+    config.restore_secrets(&stored);
+
+    Ok(diff_configs(&stored, &config))
+}
+
+/// Print the differences for the selected channels. Returns the exit code like `diff`: `0`
+/// without differences, `1` with differences and `2` on errors.
+pub async fn run(pool: &Pool<Sqlite>, path: &Path) -> i32 {
+    let mut code = 0;
+
+    for id in ARGS.channel.clone().unwrap_or_else(|| vec![1]) {
+        match diff_file(pool, id, path).await {
+            Ok(changes) if changes.is_empty() => println!("Channel {id}: no differences"),
+            Ok(changes) => {
+                println!("Channel {id}: {} fields differ", changes.len());
+
+                for change in changes {
+                    println!("  {}: {} -> {}", change.field, change.stored, change.file);
+                }
+
+                code = code.max(1);
+            }
+            Err(e) => {
+                eprintln!("Channel {id}: {e}");
+                code = 2;
+            }
+        }
+    }
+
+    code
+}
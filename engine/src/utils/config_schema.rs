@@ -0,0 +1,705 @@
+/// Config Schema
+///
+/// JSON Schema from the playout config, so frontends and other tools can render and validate
+/// config forms without a hardcoded field list. Fields marked with `x-restart` start decoder and
+/// encoder of a running channel again, all other fields are applied without a restart.
+use serde_json::{json, Map, Value};
+
+const FFMPEG_LEVELS: [&str; 8] = [
+    "QUIET", "PANIC", "FATAL", "ERROR", "WARNING", "INFO", "VERBOSE", "DEBUG",
+];
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "description": description })
+}
+
+fn integer(description: &str, min: i64, max: Option<i64>) -> Value {
+    let mut value = json!({ "type": "integer", "description": description, "minimum": min });
+
+    if let Some(max) = max {
+        value["maximum"] = json!(max);
+    }
+
+    value
+}
+
+fn number(description: &str, min: f64, max: Option<f64>) -> Value {
+    let mut value = json!({ "type": "number", "description": description, "minimum": min });
+
+    if let Some(max) = max {
+        value["maximum"] = json!(max);
+    }
+
+    value
+}
+
+fn enumeration(description: &str, values: &[&str]) -> Value {
+    json!({ "type": "string", "description": description, "enum": values })
+}
+
+fn strings(description: &str) -> Value {
+    json!({ "type": "array", "description": description, "items": { "type": "string" } })
+}
+
+fn time(description: &str) -> Value {
+    json!({
+        "type": "string",
+        "description": description,
+        "pattern": r"^(now|none|(\d{1,2}:)?\d{1,2}:\d{1,2}(\.\d+)?)?$"
+    })
+}
+
+fn restart(mut value: Value) -> Value {
+    value["x-restart"] = json!(true);
+    value
+}
+
+fn read_only(mut value: Value) -> Value {
+    value["readOnly"] = json!(true);
+    value
+}
+
+fn section(description: &str, fields: Vec<(&str, Value)>) -> Value {
+    let properties: Map<String, Value> = fields
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+    json!({
+        "type": "object",
+        "description": description,
+        "properties": properties,
+    })
+}
+
+/// Build the schema for the config object, like it is returned from `/api/playout/config/{id}`.
+pub fn playout_config_schema() -> Value {
+    let general = section(
+        "General settings",
+        vec![
+            (
+                "stop_threshold",
+                number(
+                    "Stop playout when it is out of sync by more than the given seconds, 0 disables the check",
+                    0.0,
+                    None,
+                ),
+            ),
+            (
+                "drift_alarm",
+                number(
+                    "Send an alarm when the playout drifts more than the given seconds from the schedule, 0 disables the alarm",
+                    0.0,
+                    None,
+                ),
+            ),
+            (
+                "watchdog",
+                integer(
+                    "Restart a stalled decoder or encoder after the given seconds without data, 0 disables the watchdog",
+                    0,
+                    None,
+                ),
+            ),
+            (
+                "restart_limit",
+                integer(
+                    "Stop the channel when it restarts more than the given times in one hour, 0 disables the limit",
+                    0,
+                    None,
+                ),
+            ),
+            (
+                "restart_delay",
+                integer(
+                    "Seconds before the first restart, doubled on every failure in a row",
+                    1,
+                    None,
+                ),
+            ),
+            (
+                "restart_max_delay",
+                integer("Maximum seconds between restarts", 1, None),
+            ),
+            (
+                "restart_slate",
+                integer(
+                    "Play filler instead of the program after the given failures in a row, 0 disables it",
+                    0,
+                    None,
+                ),
+            ),
+            (
+                "disk_alarm",
+                integer(
+                    "Alarm when a disk from the storage, public or log path is used more than the given percent, 0 disables it",
+                    0,
+                    Some(100),
+                ),
+            ),
+            (
+                "disk_cleanup",
+                integer(
+                    "Delete HLS segments, which are in no playlist, when the public disk is used more than the given percent, 0 disables it",
+                    0,
+                    Some(100),
+                ),
+            ),
+            (
+                "control_token",
+                string("Token for the button endpoints, like from Stream Deck or Companion, empty disables them"),
+            ),
+            (
+                "tally",
+                string("Targets for the tally, separated by semicolons: HTTP and MQTT URLs, or helper programs which get the channel id and the tally"),
+            ),
+            (
+                "plugins",
+                restart(string("Plugin programs, separated by semicolons, for outputs, clip filters and event hooks")),
+            ),
+        ],
+    );
+
+    let mail = section(
+        "Notifications by mail and chat services, mail server settings are global",
+        vec![
+            ("show", read_only(boolean("Mail server is configured"))),
+            ("subject", string("Mail subject")),
+            (
+                "recipient",
+                string("Receiver address, multiple addresses are separated by comma"),
+            ),
+            (
+                "mail_level",
+                enumeration(
+                    "Send only messages with this level",
+                    &["ERROR", "WARN", "INFO"],
+                ),
+            ),
+            (
+                "interval",
+                integer(
+                    "Collect messages for the given seconds before sending",
+                    30,
+                    None,
+                ),
+            ),
+            (
+                "digest",
+                integer(
+                    "After a mail was sent, collect messages for the given seconds before the next one, 0 disables the digest",
+                    0,
+                    None,
+                ),
+            ),
+            (
+                "dedup_window",
+                integer(
+                    "Skip messages which were already sent in the last seconds, 0 disables it",
+                    0,
+                    None,
+                ),
+            ),
+            (
+                "class_levels",
+                json!({
+                    "type": "object",
+                    "description": "Send only messages up to this level, per error class",
+                    "propertyNames": { "enum": ["ffmpeg", "playlist", "folder", "ingest", "output", "filter", "system"] },
+                    "additionalProperties": { "enum": ["ERROR", "WARN", "INFO"] },
+                }),
+            ),
+            (
+                "notifiers",
+                json!({
+                    "type": "array",
+                    "description": "Send messages also to Telegram, Slack or Discord",
+                    "items": {
+                        "type": "object",
+                        "required": ["kind", "token", "level"],
+                        "properties": {
+                            "kind": enumeration("Chat service", &["telegram", "slack", "discord"]),
+                            "token": string("Bot token for Telegram, webhook URL for Slack and Discord, masked in responses"),
+                            "chat_id": string("Telegram chat id"),
+                            "level": enumeration("Send only messages with this level", &["ERROR", "WARN", "INFO"]),
+                        },
+                    },
+                }),
+            ),
+        ],
+    );
+
+    let logging = section(
+        "Logging",
+        vec![
+            (
+                "ffmpeg_level",
+                enumeration("Log level for the decoder and encoder", &FFMPEG_LEVELS),
+            ),
+            (
+                "ingest_level",
+                enumeration("Log level for the ingest server", &FFMPEG_LEVELS),
+            ),
+            (
+                "detect_silence",
+                boolean("Log when a clip contains long silence"),
+            ),
+            (
+                "ignore_lines",
+                strings("Skip ffmpeg log lines which contain one of the entries"),
+            ),
+        ],
+    );
+
+    let processing = section(
+        "Processing, all clips are converted to the same format",
+        vec![
+            (
+                "mode",
+                restart(enumeration(
+                    "Play clips from a playlist or in random order from the storage",
+                    &["playlist", "folder"],
+                )),
+            ),
+            (
+                "audio_only",
+                restart(boolean("Ignore video streams, use a still image instead")),
+            ),
+            (
+                "copy_audio",
+                restart(boolean("Copy the audio stream without encoding")),
+            ),
+            (
+                "copy_video",
+                restart(boolean("Copy the video stream without encoding")),
+            ),
+            ("width", restart(integer("Video width", 2, Some(7680)))),
+            ("height", restart(integer("Video height", 2, Some(4320)))),
+            ("aspect", restart(number("Display aspect ratio", 0.1, None))),
+            (
+                "fps",
+                restart(number("Frames per second", 1.0, Some(240.0))),
+            ),
+            ("add_logo", boolean("Overlay a logo")),
+            ("logo", string("Logo path, relative to the storage")),
+            (
+                "logo_scale",
+                string("Logo size in width:height, empty keeps the original size"),
+            ),
+            ("logo_opacity", number("Logo opacity", 0.0, Some(1.0))),
+            (
+                "logo_position",
+                string("Logo position in x:y, overlay filter syntax"),
+            ),
+            (
+                "audio_tracks",
+                restart(integer("Number of audio tracks", 1, Some(16))),
+            ),
+            (
+                "audio_track_index",
+                integer("Use only this audio track, -1 uses all", -1, None),
+            ),
+            (
+                "audio_channels",
+                restart(integer("Number of audio channels", 1, Some(8))),
+            ),
+            ("volume", number("Audio volume", 0.0, Some(4.0))),
+            (
+                "custom_filter",
+                string("Additional ffmpeg filter, in -filter_complex syntax"),
+            ),
+            (
+                "vtt_enable",
+                restart(boolean("Add WebVTT subtitles in HLS mode")),
+            ),
+            (
+                "vtt_dummy",
+                json!({
+                    "type": ["string", "null"],
+                    "description": "Empty subtitle file for clips without subtitles",
+                }),
+            ),
+            (
+                "gapless",
+                boolean("Cut clips to whole frames and audio samples, for seamless joins"),
+            ),
+            (
+                "persistent_decoder",
+                boolean("Read clips with one decoder process, in stream, desktop and null mode"),
+            ),
+            (
+                "preload",
+                boolean("Decode the first seconds from the next clip in advance, to warm up slow network storage"),
+            ),
+            (
+                "visualization",
+                restart(enumeration(
+                    "Video from the audio for the stream output, when audio only is on",
+                    &["off", "waves", "spectrum"],
+                )),
+            ),
+            (
+                "seek_mode",
+                enumeration(
+                    "Seek fast in the input, or decode from the start for frame accurate in points",
+                    &["fast", "accurate"],
+                ),
+            ),
+        ],
+    );
+
+    let ingest = section(
+        "Live ingest, switch from playlist to a live stream",
+        vec![
+            ("enable", restart(boolean("Run the ingest server"))),
+            (
+                "input_param",
+                string("ffmpeg input parameters for the ingest server"),
+            ),
+            (
+                "custom_filter",
+                string("Additional ffmpeg filter for the live stream"),
+            ),
+            (
+                "secondary_source",
+                string("Source B for the program switch, like a studio stream or the output from another channel"),
+            ),
+            (
+                "stream_key",
+                string("Stream key for an external RTMP server, which calls the publish callbacks"),
+            ),
+            (
+                "scte35",
+                boolean("Keep SCTE-35 cues from a MPEG-TS ingest, and tag them in the HLS playlist"),
+            ),
+            (
+                "srt_passphrase",
+                string("Passphrase for an srt:// ingest, 10 to 79 characters, masked in responses"),
+            ),
+            (
+                "srt_latency",
+                integer("Latency for an srt:// ingest in milliseconds", 0, Some(10000)),
+            ),
+            (
+                "srt_streamid",
+                string("Allowed stream ids for an srt:// ingest, separated by commas, a * at the end matches the rest"),
+            ),
+        ],
+    );
+
+    let playlist = section(
+        "Playlist",
+        vec![
+            (
+                "day_start",
+                restart(time(
+                    "Start time from the playlist, in hh:mm:ss, empty or now uses the current time",
+                )),
+            ),
+            (
+                "length",
+                restart(time(
+                    "Length from the playlist, in hh:mm:ss, empty means 24 hours",
+                )),
+            ),
+            ("infinit", restart(boolean("Loop one playlist endless"))),
+            (
+                "backend",
+                restart(enumeration(
+                    "Store playlists as JSON files or in the database",
+                    &["file", "database"],
+                )),
+            ),
+            (
+                "dst_forward",
+                enumeration(
+                    "When the clocks go forward, move clip start times or skip the missing hour",
+                    &["shift", "trim"],
+                ),
+            ),
+            (
+                "dst_backward",
+                enumeration(
+                    "When the clocks go back, move clip start times or play one hour filler",
+                    &["shift", "filler"],
+                ),
+            ),
+            (
+                "catch_up",
+                enumeration(
+                    "When the playout is behind the schedule, restart or skip to the current clip after the stop threshold, or trim or speed up the next clips",
+                    &["restart", "skip", "trim", "speed"],
+                ),
+            ),
+            (
+                "catch_up_rate",
+                number(
+                    "Percent of a clip, which is cut or played faster to catch up",
+                    0.0,
+                    Some(20.0),
+                ),
+            ),
+            (
+                "stream_reconnect",
+                integer(
+                    "Max seconds between reconnects, when a HTTP stream entry breaks off, 0 disables reconnects",
+                    0,
+                    Some(120),
+                ),
+            ),
+            (
+                "stream_retries",
+                integer(
+                    "Reconnects to a HTTP stream entry, 0 has no limit, needs ffmpeg 7 or newer",
+                    0,
+                    Some(100),
+                ),
+            ),
+            (
+                "stream_fallback",
+                number(
+                    "Seconds a stream entry can send no data, before filler plays the rest of its time, 0 disables it",
+                    0.0,
+                    Some(600.0),
+                ),
+            ),
+            (
+                "check_days",
+                integer(
+                    "Check that the playlists for the given next days exist and fill the length, 0 disables the check",
+                    0,
+                    Some(31),
+                ),
+            ),
+            (
+                "check_hours",
+                integer(
+                    "Notify about a missing or invalid playlist the given hours before it starts",
+                    0,
+                    None,
+                ),
+            ),
+            (
+                "vast_url",
+                string("VAST tag from the ad server, which fills the ad_break entries from the playlist, empty plays their source"),
+            ),
+            (
+                "weekday_overrides",
+                restart(json!({
+                    "type": "array",
+                    "description": "Other day start, length or filler on specific weekdays",
+                    "items": {
+                        "type": "object",
+                        "required": ["days"],
+                        "properties": {
+                            "days": strings("Weekdays in english, like mon or saturday"),
+                            "day_start": time("Start time on these days"),
+                            "length": time("Length on these days"),
+                            "filler": string("Filler on these days"),
+                        },
+                    },
+                })),
+            ),
+        ],
+    );
+
+    let storage = section(
+        "Storage",
+        vec![
+            (
+                "filler",
+                string("Clip or folder for filling gaps, relative to the storage"),
+            ),
+            (
+                "shared_filler",
+                boolean("Filler path is relative to the shared filler pool instead of the channel storage"),
+            ),
+            (
+                "extensions",
+                strings("File extensions, which are used in folder mode"),
+            ),
+            (
+                "shuffle",
+                boolean("Play clips in random order in folder mode"),
+            ),
+            (
+                "emergency_slate",
+                string("Image or clip for the emergency slate, relative to the channel storage"),
+            ),
+            (
+                "emergency_audio",
+                string("Audio, which loops under the emergency slate, can be empty"),
+            ),
+            (
+                "tags",
+                restart(strings(
+                    "Play only files with one of these tags in folder mode, empty plays all files",
+                )),
+            ),
+            (
+                "house_format",
+                json!({
+                    "type": "object",
+                    "description": "House format, uploads and playlist clips which break a rule are rejected, empty rules are not checked",
+                    "properties": {
+                        "containers": strings("Container names from ffprobe, like mov or matroska"),
+                        "video_codecs": strings("Video codecs, like h264"),
+                        "audio_codecs": strings("Audio codecs, like aac"),
+                        "width": integer("Video width", 1, None),
+                        "height": integer("Video height", 1, None),
+                        "fps": number("Frame rate", 1.0, None),
+                        "loudness_min": number("Lowest integrated loudness in LUFS", -70.0, Some(0.0)),
+                        "loudness_max": number("Highest integrated loudness in LUFS", -70.0, Some(0.0)),
+                        "min_duration": number("Minimum duration in seconds", 0.0, None),
+                    },
+                }),
+            ),
+            (
+                "coming_up",
+                string("Template image for coming up cards in gaps, relative to the channel storage, empty uses the filler"),
+            ),
+            (
+                "coming_up_count",
+                integer("Programs on a coming up card", 1, Some(10)),
+            ),
+            (
+                "shared_storage",
+                read_only(boolean("Storage is shared with other channels")),
+            ),
+        ],
+    );
+
+    let text = section(
+        "Text overlay",
+        vec![
+            (
+                "add_text",
+                restart(boolean("Enable text overlay over ZeroMQ")),
+            ),
+            ("font", string("Font path, relative to the storage")),
+            (
+                "text_from_filename",
+                restart(boolean("Show text from the clip name")),
+            ),
+            (
+                "style",
+                string("drawtext filter options for the text from the clip name"),
+            ),
+            (
+                "regex",
+                string("Regular expression to extract the text from the clip name"),
+            ),
+            (
+                "layers",
+                restart(string(
+                    "Names from more text layers, comma separated, like lower_third,ticker",
+                )),
+            ),
+        ],
+    );
+
+    let task = section(
+        "Run a program on every clip change, with the current clip as JSON argument",
+        vec![
+            ("enable", boolean("Run the task")),
+            ("path", string("Path to the program")),
+        ],
+    );
+
+    let output = section(
+        "Output",
+        vec![
+            (
+                "mode",
+                restart(enumeration(
+                    "Output mode",
+                    &["desktop", "hls", "null", "stream"],
+                )),
+            ),
+            (
+                "output_param",
+                restart(string(
+                    "ffmpeg output parameters, secrets are masked in responses",
+                )),
+            ),
+            (
+                "renditions",
+                restart(json!({
+                    "type": "array",
+                    "description": "Additional encoders, which share the decoded feed, not used in HLS mode",
+                    "items": {
+                        "type": "object",
+                        "required": ["name", "output_param"],
+                        "properties": {
+                            "name": string("Name in the log"),
+                            "output_param": string("ffmpeg output parameters, secrets are masked in responses"),
+                        },
+                    },
+                })),
+            ),
+            (
+                "delay",
+                restart(number(
+                    "Seconds between decoder and encoder, for dropping content before it is on air, 0 disables it, not used in HLS mode",
+                    0.0,
+                    None,
+                )),
+            ),
+            (
+                "delay_path",
+                restart(string("Folder for the delay buffer, empty keeps it in memory")),
+            ),
+            (
+                "replay",
+                restart(number(
+                    "Seconds of the program which are recorded for instant replays, 0 disables it, not used in HLS mode",
+                    0.0,
+                    None,
+                )),
+            ),
+            (
+                "replay_path",
+                restart(string("Folder for the replay recording, empty uses the temp folder")),
+            ),
+            (
+                "public_server",
+                json!({
+                    "type": "object",
+                    "description": "Delivery from the public files, like the HLS playlists and segments",
+                    "properties": {
+                        "cors_origins": strings("Origins which get CORS headers, * allows all, empty sends no CORS headers"),
+                        "cache_control": {
+                            "type": "object",
+                            "description": "Cache-Control value per file extension",
+                            "additionalProperties": { "type": "string" },
+                        },
+                        "ranges": boolean("Answer range requests, without them the whole file is sent"),
+                        "gzip": boolean("Compress playlists with gzip, for clients which accept it"),
+                    },
+                }),
+            ),
+        ],
+    );
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "PlayoutConfig",
+        "type": "object",
+        "properties": {
+            "general": general,
+            "mail": mail,
+            "logging": logging,
+            "processing": processing,
+            "ingest": ingest,
+            "playlist": playlist,
+            "storage": storage,
+            "text": text,
+            "task": task,
+            "output": output,
+        },
+    })
+}
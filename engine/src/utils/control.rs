@@ -4,14 +4,23 @@ use log::*;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use sqlx::{Pool, Sqlite};
+use tokio::time::{timeout, Duration};
 use zeromq::{Socket, SocketRecv, SocketSend, ZmqMessage};
 
 use crate::db::handles;
 use crate::player::{
     controller::{ChannelManager, ProcessUnit::*},
-    utils::{get_delta, get_media_map},
+    filter::{v_drawtext::zmq_text, volume_unit},
+    input::emergency::{end_emergency, start_emergency},
+    utils::{channel_state::ChannelAction, get_delta, get_media_map},
+};
+use crate::utils::{
+    config::OutputMode::*,
+    errors::ServiceError,
+    logging::Target,
+    text_layers::{self, animation_frame, TextAnimation},
+    TextFilter,
 };
-use crate::utils::{config::OutputMode::*, errors::ServiceError, logging::Target, TextFilter};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct TextParams {
@@ -99,6 +108,9 @@ impl fmt::Display for PlayerCtl {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Process {
     pub command: ProcessCtl,
+    /// Stop after the current clip, wait at most this seconds.
+    #[serde(default)]
+    pub drain: Option<u64>,
 }
 
 async fn zmq_send(msg: &str, socket_addr: &str) -> Result<String, Box<dyn Error>> {
@@ -111,23 +123,64 @@ async fn zmq_send(msg: &str, socket_addr: &str) -> Result<String, Box<dyn Error>
     Ok(response)
 }
 
-pub async fn send_message(
-    manager: ChannelManager,
-    message: TextFilter,
+/// Send a command to the filters from the process, which plays right now.
+/// In HLS mode this is the decoder or the ingest, otherwise the encoder.
+pub async fn filter_command(
+    manager: &ChannelManager,
+    command: &str,
+) -> Result<String, ServiceError> {
+    let config = manager.config.lock().await.clone();
+    let socket = if config.output.mode == HLS && manager.ingest_is_alive.load(Ordering::SeqCst) {
+        config.text.zmq_server_socket
+    } else {
+        config.text.zmq_stream_socket
+    };
+
+    let Some(socket) = socket else {
+        return Err(ServiceError::ServiceUnavailable(
+            "No filter control running".to_string(),
+        ));
+    };
+
+    let reply = match timeout(Duration::from_secs(5), zmq_send(command, &socket)).await {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(e)) => {
+            return Err(ServiceError::ServiceUnavailable(format!(
+                "Filter command failed: {e}"
+            )))
+        }
+        Err(_) => {
+            return Err(ServiceError::ServiceUnavailable(
+                "Filter command got no reply".to_string(),
+            ))
+        }
+    };
+
+    // the reply starts with the return code from ffmpeg
+    if reply.starts_with("0 ") {
+        Ok(reply)
+    } else {
+        Err(ServiceError::ServiceUnavailable(format!(
+            "Filter command failed: {reply}"
+        )))
+    }
+}
+
+/// Send the drawtext options to the layer, in the process which plays right now.
+async fn send_text(
+    manager: &ChannelManager,
+    layer: &str,
+    message: &TextFilter,
 ) -> Result<Map<String, Value>, ServiceError> {
-    let filter = message.to_string();
     let mut data_map = Map::new();
     let config = manager.config.lock().await.clone();
     let id = config.general.channel_id;
+    let filter = zmq_text(&config, &message.to_string());
 
     if config.text.zmq_stream_socket.is_some() {
-        if let Some(clips_filter) = manager.filter_chain.clone() {
-            *clips_filter.lock().await = vec![filter.clone()];
-        }
-
         if config.output.mode == HLS {
             if manager.ingest_is_alive.load(Ordering::SeqCst) {
-                let filter_server = format!("drawtext@dyntext reinit {filter}");
+                let filter_server = format!("drawtext@{layer} reinit {filter}");
 
                 if let Ok(reply) = zmq_send(
                     &filter_server,
@@ -144,7 +197,7 @@ pub async fn send_message(
         }
 
         if config.output.mode != HLS || !manager.ingest_is_alive.load(Ordering::SeqCst) {
-            let filter_stream = format!("drawtext@dyntext reinit {filter}");
+            let filter_stream = format!("drawtext@{layer} reinit {filter}");
 
             if let Ok(reply) = zmq_send(
                 &filter_stream,
@@ -163,6 +216,36 @@ pub async fn send_message(
     ))
 }
 
+/// Send a text message to its layer, animations and the expire time run in the background.
+pub async fn send_message(
+    manager: ChannelManager,
+    mut message: TextFilter,
+) -> Result<Map<String, Value>, ServiceError> {
+    let config = manager.config.lock().await.clone();
+    let layer = text_layers::check_message(&config, &mut message)?;
+    let number = text_layers::next_message(&manager, &layer).await;
+    let animation_in = message.animation_in.filter(|a| *a != TextAnimation::None);
+
+    if config.text.zmq_stream_socket.is_some() {
+        if let Some(clips_filter) = manager.filter_chain.clone() {
+            text_layers::store_text(&mut *clips_filter.lock().await, &layer, message.to_string());
+        }
+    }
+
+    // an animation starts out of view
+    let first = match animation_in {
+        Some(animation) => animation_frame(&message, &config.text.style, animation, 1.0),
+        None => message.clone(),
+    };
+    let reply = send_text(&manager, &layer, &first).await?;
+
+    if animation_in.is_some() || message.expire.is_some() {
+        tokio::spawn(text_layers::animate(manager, layer, message, number));
+    }
+
+    Ok(reply)
+}
+
 pub async fn control_state(
     conn: &Pool<Sqlite>,
     manager: &ChannelManager,
@@ -228,3 +311,155 @@ pub async fn control_state(
 
     Ok(data_map)
 }
+
+/// Play the clip with this index from the current playlist now.
+pub async fn jump_to(
+    conn: &Pool<Sqlite>,
+    manager: &ChannelManager,
+    index: usize,
+) -> Result<Map<String, Value>, ServiceError> {
+    let config = manager.config.lock().await.clone();
+    let id = config.general.channel_id;
+    let current_date = manager.current_date.lock().await.clone();
+    let Some(mut media) = manager.current_list.lock().await.get(index).cloned() else {
+        return Err(ServiceError::BadRequest(
+            "Clip is not in the playlist".to_string(),
+        ));
+    };
+    let (shift, _) = get_delta(&config, &media.begin.unwrap_or(0.0));
+    let mut data_map = Map::new();
+
+    info!(target: Target::file_mail(), channel = id; "Jump to clip <yellow>{}</>", index + 1);
+
+    manager.current_index.store(index, Ordering::SeqCst);
+
+    if let Err(e) = media.add_probe(false).await {
+        error!(target: Target::file_mail(), channel = id; "{e:?}");
+    };
+
+    data_map.insert("operation".to_string(), json!("jump"));
+    data_map.insert("shifted_seconds".to_string(), json!(shift));
+    data_map.insert("media".to_string(), get_media_map(media));
+
+    manager.channel.lock().await.time_shift = shift;
+    handles::update_stat(conn, id, Some(current_date), shift).await?;
+    manager.stop(Decoder).await?;
+
+    Ok(data_map)
+}
+
+/// Command from a remote control protocol, like OSC or AMCP.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteCtl {
+    Player(PlayerCtl),
+    Process(ProcessCtl),
+    /// Play the clip with this index from the current playlist.
+    Jump(usize),
+    Text(String),
+    Volume(f64),
+    /// Emergency slate on or off.
+    Slate,
+    Status,
+}
+
+/// Command from a button action, like `next`, `slate` or `start`.
+pub fn button_command(action: &str) -> Result<RemoteCtl, ServiceError> {
+    let command = match action {
+        "back" | "next" | "reset" => RemoteCtl::Player(action.parse()?),
+        "start" | "stop" => RemoteCtl::Process(action.parse()?),
+        "slate" => RemoteCtl::Slate,
+        "status" => RemoteCtl::Status,
+        _ => {
+            return Err(ServiceError::BadRequest(format!(
+                "Unknown button action {action}"
+            )))
+        }
+    };
+
+    Ok(command)
+}
+
+/// Run a remote command, with the same checks as the API.
+pub async fn remote_control(
+    pool: &Pool<Sqlite>,
+    manager: &ChannelManager,
+    command: RemoteCtl,
+) -> Result<(), ServiceError> {
+    let state = manager.state.lock().await.state;
+
+    match command {
+        RemoteCtl::Player(control) => {
+            state.check(ChannelAction::Playout)?;
+
+            if manager.is_processing.swap(true, Ordering::SeqCst) {
+                return Err(ServiceError::Conflict(
+                    "A command is already being processed, please wait".to_string(),
+                ));
+            }
+
+            let result = control_state(pool, manager, &control).await;
+            manager.is_processing.store(false, Ordering::SeqCst);
+
+            result?;
+        }
+        RemoteCtl::Jump(index) => {
+            state.check(ChannelAction::Playout)?;
+
+            if manager.is_processing.swap(true, Ordering::SeqCst) {
+                return Err(ServiceError::Conflict(
+                    "A command is already being processed, please wait".to_string(),
+                ));
+            }
+
+            let result = jump_to(pool, manager, index).await;
+            manager.is_processing.store(false, Ordering::SeqCst);
+
+            result?;
+        }
+        RemoteCtl::Process(ProcessCtl::Start) => {
+            state.check(ChannelAction::Start)?;
+            manager.list_init.store(true, Ordering::SeqCst);
+
+            if !manager.is_alive.load(Ordering::SeqCst) {
+                manager.channel.lock().await.active = true;
+                manager.start().await?;
+            }
+        }
+        RemoteCtl::Process(_) => {
+            state.check(ChannelAction::Stop)?;
+            manager.channel.lock().await.active = false;
+            manager.stop_all(true).await?;
+        }
+        RemoteCtl::Text(text) => {
+            let message = TextFilter {
+                text: Some(text),
+                ..Default::default()
+            };
+
+            send_message(manager.clone(), message).await?;
+        }
+        RemoteCtl::Volume(volume) => {
+            if volume_unit(&manager.config.lock().await.clone()).is_none() {
+                return Err(ServiceError::Conflict(
+                    "Volume can't change while the channel runs".to_string(),
+                ));
+            }
+
+            filter_command(manager, &format!("volume@volume volume {volume}")).await?;
+
+            // until the next restart or config change
+            manager.config.lock().await.processing.volume = volume;
+        }
+        RemoteCtl::Slate => {
+            if manager.emergency.load(Ordering::SeqCst) {
+                end_emergency(manager).await?;
+            } else {
+                state.check(ChannelAction::Emergency)?;
+                start_emergency(manager).await?;
+            }
+        }
+        RemoteCtl::Status => {}
+    }
+
+    Ok(())
+}
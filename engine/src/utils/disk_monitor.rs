@@ -0,0 +1,126 @@
+/// Disk Monitor
+///
+/// Watch the space on the disks from the storage, public and log path of every channel, so a full
+/// disk is reported before ffmpeg fails to write. When the disk with the HLS public path runs full,
+/// segments which are in no playlist anymore are deleted.
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+
+use log::*;
+use tokio::sync::Mutex;
+
+use crate::player::controller::{cleanup_hls_path, ChannelController};
+use crate::utils::{
+    config::OutputMode,
+    logging::{log_file_path, Target},
+    system::disk_space,
+};
+
+const CHECK_INTERVAL: u64 = 60;
+/// Segments which are younger could be written right now.
+const SEGMENT_MIN_AGE: Duration = Duration::from_secs(120);
+/// The alarm ends, when the usage is this percent under the limit.
+const HYSTERESIS: f64 = 2.0;
+
+/// Used space in percent.
+pub fn usage_percent(total: u64, available: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    total.saturating_sub(available) as f64 / total as f64 * 100.0
+}
+
+/// Channel and path kind, which are over the limit.
+#[derive(Clone, Debug, Default)]
+pub struct DiskAlarm {
+    alarmed: HashSet<(i32, &'static str)>,
+}
+
+impl DiskAlarm {
+    /// Returns `Some(true)` when the alarm starts and `Some(false)` when it ends.
+    pub fn update(
+        &mut self,
+        channel: i32,
+        kind: &'static str,
+        usage: f64,
+        limit: i64,
+    ) -> Option<bool> {
+        let key = (channel, kind);
+
+        if limit > 0 && usage >= limit as f64 {
+            return self.alarmed.insert(key).then_some(true);
+        }
+
+        if self.alarmed.contains(&key) && (limit <= 0 || usage < limit as f64 - HYSTERESIS) {
+            self.alarmed.remove(&key);
+            return Some(false);
+        }
+
+        None
+    }
+}
+
+/// Monitor loop, runs in its own task as long as the server is running.
+pub async fn run(controllers: Arc<Mutex<ChannelController>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL));
+    let mut alarm = DiskAlarm::default();
+
+    loop {
+        interval.tick().await;
+
+        let managers = controllers.lock().await.channels.clone();
+
+        for manager in managers {
+            let config = manager.config.lock().await.clone();
+            let id = config.general.channel_id;
+            let paths: [(&'static str, PathBuf); 3] = [
+                ("storage", config.channel.storage.clone()),
+                ("public", config.channel.public.clone()),
+                ("log", log_file_path()),
+            ];
+
+            for (kind, path) in paths {
+                let Some((total, available)) = disk_space(&path) else {
+                    continue;
+                };
+                let usage = usage_percent(total, available);
+
+                match alarm.update(id, kind, usage, config.general.disk_alarm) {
+                    Some(true) => {
+                        error!(target: Target::file_mail(), channel = id;
+                            "Disk from {kind} path <b><magenta>{path:?}</></b> is <yellow>{usage:.1}%</> full, <yellow>{}</> MB left",
+                            available / 1_048_576
+                        );
+                    }
+                    Some(false) => {
+                        info!(target: Target::file_mail(), channel = id;
+                            "Disk from {kind} path <b><magenta>{path:?}</></b> is back to <yellow>{usage:.1}%</>"
+                        );
+                    }
+                    None => {}
+                }
+
+                if kind != "public"
+                    || config.output.mode != OutputMode::HLS
+                    || config.general.disk_cleanup <= 0
+                    || usage < config.general.disk_cleanup as f64
+                {
+                    continue;
+                }
+
+                match cleanup_hls_path(&path, SEGMENT_MIN_AGE).await {
+                    Ok((0, _)) => {}
+                    Ok((count, size)) => {
+                        warn!(target: Target::file_mail(), channel = id;
+                            "Disk from public path is <yellow>{usage:.1}%</> full, deleted <yellow>{count}</> old segments with <yellow>{}</> MB",
+                            size / 1_048_576
+                        );
+                    }
+                    Err(e) => {
+                        error!(target: Target::file_mail(), channel = id; "Cleanup HLS segments failed: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
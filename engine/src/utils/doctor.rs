@@ -0,0 +1,603 @@
+/// Doctor
+///
+/// `--doctor` checks the installation, without starting a channel: ffmpeg with its version,
+/// filters and muxers, the database and its schema version, the paths, the ports and the user
+/// from the systemd service. Every check prints one line, problems get a hint how to fix them.
+use std::{fmt, net::TcpListener, path::PathBuf, process::Stdio};
+
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::MetadataExt;
+
+use faccess::{AccessMode, PathExt};
+#[cfg(target_family = "unix")]
+use nix::unistd::{Uid, User};
+use regex::Regex;
+use sqlx::{sqlite::SqliteConnectOptions, Pool, Row, Sqlite, SqlitePool};
+use tokio::process::Command;
+
+use crate::db::{handles, DB_PATH};
+use crate::ARGS;
+
+/// Oldest ffmpeg version, which has everything the engine needs.
+pub const MIN_FFMPEG: (u32, u32) = (5, 0);
+
+/// Filters the engine uses in every setup.
+const FILTERS: [&str; 18] = [
+    "aevalsrc",
+    "afade",
+    "apad",
+    "aresample",
+    "atrim",
+    "color",
+    "fade",
+    "format",
+    "fps",
+    "movie",
+    "overlay",
+    "pad",
+    "scale",
+    "setdar",
+    "tpad",
+    "trim",
+    "volume",
+    "yadif",
+];
+
+/// Filters for single features: text overlay, text updates and the program crossfade.
+const OPTIONAL_FILTERS: [&str; 4] = ["drawtext", "zmq", "xfade", "acrossfade"];
+
+const MUXERS: [&str; 3] = ["hls", "mpegts", "null"];
+
+const SERVICE_FILES: [&str; 3] = [
+    "/etc/systemd/system/ffplayout.service",
+    "/lib/systemd/system/ffplayout.service",
+    "/usr/lib/systemd/system/ffplayout.service",
+];
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8787";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Ok => write!(f, "[ OK ]"),
+            Self::Warn => write!(f, "[WARN]"),
+            Self::Fail => write!(f, "[FAIL]"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    failed: bool,
+}
+
+impl Report {
+    fn section(&self, name: &str) {
+        println!("\n{name}");
+    }
+
+    fn line(&mut self, level: Level, message: impl fmt::Display, hint: Option<String>) {
+        println!("  {level} {message}");
+
+        if let Some(hint) = hint {
+            println!("         -> {hint}");
+        }
+
+        if level == Level::Fail {
+            self.failed = true;
+        }
+    }
+
+    fn ok(&mut self, message: impl fmt::Display) {
+        self.line(Level::Ok, message, None);
+    }
+
+    fn warn(&mut self, message: impl fmt::Display, hint: impl Into<String>) {
+        self.line(Level::Warn, message, Some(hint.into()));
+    }
+
+    fn fail(&mut self, message: impl fmt::Display, hint: impl Into<String>) {
+        self.line(Level::Fail, message, Some(hint.into()));
+    }
+}
+
+/// Version from the first line of `ffmpeg -version`, `None` for git builds.
+pub fn ffmpeg_version(line: &str) -> Option<(u32, u32)> {
+    let version = line
+        .split_whitespace()
+        .skip_while(|w| *w != "version")
+        .nth(1)?
+        .trim_start_matches('n');
+    let number: String = version
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let mut parts = number.split('.').map(|p| p.parse::<u32>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or_default();
+
+    // builds from a date, like 2024-05-20-git-...
+    if major > 100 {
+        return None;
+    }
+
+    Some((major, minor))
+}
+
+/// Names from `ffmpeg -filters` or `ffmpeg -muxers`.
+pub fn ffmpeg_names(output: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut list = false;
+
+    for line in output.lines() {
+        // the list starts after the legend
+        if line.trim_start().starts_with("--") {
+            list = true;
+            continue;
+        }
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        if words.len() > 1 && (list || line.contains("->")) {
+            names.extend(words[1].split(',').map(|n| n.to_string()));
+        }
+    }
+
+    names
+}
+
+/// Value from `User=` in the service unit.
+pub fn service_user(unit: &str) -> Option<String> {
+    unit.lines()
+        .filter_map(|l| l.trim().strip_prefix("User="))
+        .last()
+        .map(|u| u.trim().to_string())
+}
+
+/// Address from `-l` or `--listen` in the `ExecStart` line.
+pub fn service_listen(unit: &str) -> Option<String> {
+    let exec = unit
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("ExecStart="))
+        .last()?;
+    let args = shlex::split(exec)?;
+
+    args.iter()
+        .position(|a| a == "-l" || a == "--listen")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| {
+            args.iter()
+                .find_map(|a| a.strip_prefix("--listen=").map(str::to_string))
+        })
+}
+
+/// Host and port from an ingest input, like `-f flv -listen 1 -i rtmp://0.0.0.0:1936/live/stream`.
+pub fn ingest_address(input_param: &str) -> Option<String> {
+    let args = shlex::split(input_param)?;
+    let url = args
+        .iter()
+        .position(|a| a == "-i")
+        .and_then(|i| args.get(i + 1))?;
+    let re = Regex::new(r"^[\w]+://([^/?]+)").unwrap();
+
+    re.captures(url)
+        .and_then(|c| c.get(1))
+        .map(|a| a.as_str().to_string())
+        .filter(|a| a.contains(':'))
+}
+
+async fn command_output(name: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(name)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+async fn check_ffmpeg(report: &mut Report) {
+    report.section("ffmpeg");
+
+    let version = match command_output("ffmpeg", &["-hide_banner", "-version"]).await {
+        Ok(out) => out.lines().next().unwrap_or_default().to_string(),
+        Err(e) => {
+            report.fail(
+                format!("ffmpeg not found: {e}"),
+                "install ffmpeg from your distribution, or put a static build in /usr/local/bin",
+            );
+            return;
+        }
+    };
+
+    match ffmpeg_version(&version) {
+        Some((major, minor)) if (major, minor) < MIN_FFMPEG => report.fail(
+            format!("ffmpeg {major}.{minor} is too old"),
+            format!("install ffmpeg v{}.{} or newer", MIN_FFMPEG.0, MIN_FFMPEG.1),
+        ),
+        Some((major, minor)) => report.ok(format!("ffmpeg {major}.{minor}")),
+        None => report.warn(
+            format!("unknown ffmpeg version: {version}"),
+            format!(
+                "git builds work, when they are newer then v{}.{}",
+                MIN_FFMPEG.0, MIN_FFMPEG.1
+            ),
+        ),
+    }
+
+    for (name, needed) in [("ffprobe", true), ("ffplay", false)] {
+        match command_output(name, &["-hide_banner", "-version"]).await {
+            Ok(_) => report.ok(format!("{name} found")),
+            Err(e) if needed => report.fail(
+                format!("{name} not found: {e}"),
+                "ffprobe comes with ffmpeg, install the full package",
+            ),
+            Err(_) => report.warn(
+                format!("{name} not found"),
+                "only needed for the desktop output",
+            ),
+        }
+    }
+
+    let filters = ffmpeg_names(
+        &command_output("ffmpeg", &["-hide_banner", "-filters"])
+            .await
+            .unwrap_or_default(),
+    );
+    let missing = FILTERS
+        .iter()
+        .filter(|f| !filters.iter().any(|n| n == *f))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        report.ok(format!("{} filters", FILTERS.len()));
+    } else {
+        report.fail(
+            format!("missing filters: {}", missing.join(", ")),
+            "use a full ffmpeg build, the static builds from johnvansickle.com have all of them",
+        );
+    }
+
+    for filter in OPTIONAL_FILTERS {
+        if !filters.iter().any(|n| n == filter) {
+            report.warn(
+                format!("no {filter} filter"),
+                match filter {
+                    "drawtext" => "text overlay is not possible, ffmpeg needs libfreetype",
+                    "zmq" => "text updates over the API are not possible, ffmpeg needs libzmq",
+                    _ => "the program switch can only cut",
+                },
+            );
+        }
+    }
+
+    let muxers = ffmpeg_names(
+        &command_output("ffmpeg", &["-hide_banner", "-muxers"])
+            .await
+            .unwrap_or_default(),
+    );
+    let missing = MUXERS
+        .iter()
+        .filter(|m| !muxers.iter().any(|n| n == *m))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        report.ok(format!("muxers: {}", MUXERS.join(", ")));
+    } else {
+        report.fail(
+            format!("missing muxers: {}", missing.join(", ")),
+            "use a full ffmpeg build",
+        );
+    }
+}
+
+async fn check_database(report: &mut Report) -> Option<(PathBuf, Pool<Sqlite>)> {
+    report.section("Database");
+
+    let path = match DB_PATH.as_ref() {
+        Ok(path) => path.to_path_buf(),
+        Err(e) => {
+            report.fail(
+                format!("database path: {e}"),
+                "set a writable path with --db, or fix the rights from /usr/share/ffplayout/db",
+            );
+            return None;
+        }
+    };
+
+    if !path.is_file() {
+        report.fail(
+            format!("no database in {}", path.display()),
+            "run `ffplayout -i` to create it",
+        );
+        return None;
+    }
+
+    let pool =
+        match SqlitePool::connect_with(SqliteConnectOptions::new().filename(&path).read_only(true))
+            .await
+        {
+            Ok(pool) => pool,
+            Err(e) => {
+                report.fail(
+                    format!("open {}: {e}", path.display()),
+                    "check the rights from the file and its folder",
+                );
+                return None;
+            }
+        };
+
+    report.ok(format!("database {}", path.display()));
+
+    if !path.writable() {
+        report.fail(
+            format!("database {} is read only", path.display()),
+            "the engine needs write access to the file and its folder",
+        );
+    }
+
+    let latest = sqlx::migrate!("../migrations")
+        .migrations
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default();
+
+    let version: Result<Option<i64>, sqlx::Error> =
+        sqlx::query("SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1")
+            .fetch_one(&pool)
+            .await
+            .and_then(|row| row.try_get(0));
+
+    match version {
+        Ok(Some(v)) if v == latest => report.ok(format!("schema version {v}")),
+        Ok(Some(v)) if v < latest => report.warn(
+            format!("schema version {v}, this ffplayout has {latest}"),
+            "migrations run on the next start, make a backup first with --backup-db",
+        ),
+        Ok(Some(v)) => report.fail(
+            format!("schema version {v} is newer then this ffplayout ({latest})"),
+            "update ffplayout, or restore a backup with --restore-db",
+        ),
+        Ok(None) | Err(_) => report.fail(
+            "database has no schema",
+            "run `ffplayout -i` to initialize it",
+        ),
+    }
+
+    Some((path, pool))
+}
+
+/// Paths from the global settings and the channels.
+async fn check_paths(report: &mut Report, pool: &Pool<Sqlite>) -> Vec<PathBuf> {
+    report.section("Paths");
+
+    let mut paths = vec![];
+
+    match handles::select_global(pool).await {
+        Ok(global) => {
+            paths.push(("logs", global.logs, AccessMode::WRITE));
+            paths.push(("playlists", global.playlists, AccessMode::WRITE));
+            paths.push(("public", global.public, AccessMode::WRITE));
+            paths.push(("storage", global.storage, AccessMode::READ));
+        }
+        Err(e) => {
+            report.fail(
+                format!("global settings: {e}"),
+                "run `ffplayout -i` to set them",
+            );
+        }
+    }
+
+    for channel in handles::select_related_channels(pool, None)
+        .await
+        .unwrap_or_default()
+    {
+        paths.push(("public", channel.public, AccessMode::WRITE));
+        paths.push(("playlists", channel.playlists, AccessMode::WRITE));
+        paths.push(("storage", channel.storage, AccessMode::READ));
+    }
+
+    let mut checked = vec![];
+
+    for (name, path, mode) in paths {
+        let dir = PathBuf::from(&path);
+
+        // channels share the global paths mostly
+        if path.is_empty() || checked.contains(&dir) {
+            continue;
+        } else if !dir.is_dir() {
+            report.fail(
+                format!("{name} {path} not exists"),
+                format!("create it: `mkdir -p {path}`"),
+            );
+        } else if dir.access(mode).is_err() {
+            let access = if mode == AccessMode::READ {
+                "readable"
+            } else {
+                "writable"
+            };
+
+            report.fail(
+                format!("{name} {path} is not {access}"),
+                format!("give the service user access: `chown -R ffpu: {path}`"),
+            );
+        } else {
+            report.ok(format!("{name} {path}"));
+        }
+
+        checked.push(dir);
+    }
+
+    checked
+}
+
+fn port_free(address: &str) -> bool {
+    TcpListener::bind(address).is_ok()
+}
+
+async fn check_ports(report: &mut Report, pool: Option<&Pool<Sqlite>>, unit: Option<&str>) {
+    report.section("Ports");
+
+    let listen = ARGS
+        .listen
+        .clone()
+        .or_else(|| unit.and_then(service_listen))
+        .unwrap_or(DEFAULT_LISTEN.to_string());
+
+    if port_free(&listen) {
+        report.ok(format!("web server {listen}"));
+    } else {
+        report.warn(
+            format!("web server address {listen} is in use"),
+            "ffplayout runs already, or another program uses the port: `ss -ltnp`",
+        );
+    }
+
+    let Some(pool) = pool else {
+        return;
+    };
+
+    for channel in handles::select_related_channels(pool, None)
+        .await
+        .unwrap_or_default()
+    {
+        let Ok(config) = handles::select_configuration(pool, channel.id).await else {
+            continue;
+        };
+
+        if !config.ingest_enable {
+            continue;
+        }
+
+        match ingest_address(&config.ingest_input_param.unwrap_or_default()) {
+            Some(address) if port_free(&address) => {
+                report.ok(format!("channel {} ingest {address}", channel.id));
+            }
+            Some(address) => report.warn(
+                format!("channel {} ingest address {address} is in use", channel.id),
+                "every channel needs its own ingest port, when the channel runs it uses it itself",
+            ),
+            None => report.warn(
+                format!("channel {} ingest has no address", channel.id),
+                "the ingest input needs a listen url, like: -f live_flv -listen 1 -i rtmp://0.0.0.0:1936/live/stream",
+            ),
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn check_service(report: &mut Report, unit: Option<(&str, &str)>, paths: &[PathBuf]) {
+    report.section("Service");
+
+    let Some((file, unit)) = unit else {
+        report.ok("no systemd service");
+        return;
+    };
+
+    let Some(name) = service_user(unit) else {
+        report.warn(
+            format!("{file} has no User"),
+            "without User= the service runs as root, the packages use ffpu",
+        );
+        return;
+    };
+
+    let user = match User::from_name(&name) {
+        Ok(Some(user)) => user,
+        _ => {
+            report.fail(
+                format!("service user {name} from {file} not exists"),
+                format!("create it: `useradd -r -s /usr/sbin/nologin {name}`"),
+            );
+            return;
+        }
+    };
+
+    report.ok(format!("service user {name}"));
+
+    let current = Uid::current();
+
+    if current != user.uid && !current.is_root() {
+        let me = User::from_uid(current)
+            .ok()
+            .flatten()
+            .map(|u| u.name)
+            .unwrap_or(current.to_string());
+
+        report.warn(
+            format!("doctor runs as {me}, the service as {name}"),
+            format!("the results can differ for the service: `sudo -u {name} ffplayout --doctor`"),
+        );
+    }
+
+    for path in paths {
+        if let Ok(meta) = path.metadata() {
+            if meta.uid() != user.uid.as_raw() && !Uid::from_raw(meta.uid()).is_root() {
+                report.warn(
+                    format!("{} belongs not to {name}", path.display()),
+                    format!("`chown -R {name}: {}`", path.display()),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn check_service(report: &mut Report, _unit: Option<(&str, &str)>, _paths: &[PathBuf]) {
+    report.section("Service");
+    report.ok("service user and file owners are only checked on unix");
+}
+
+/// Run all checks, returns the exit code.
+pub async fn run() -> i32 {
+    let mut report = Report::default();
+    let unit = SERVICE_FILES
+        .iter()
+        .find_map(|f| std::fs::read_to_string(f).ok().map(|u| (*f, u)));
+
+    check_ffmpeg(&mut report).await;
+
+    let database = check_database(&mut report).await;
+    let mut paths = vec![];
+
+    if let Some((db, pool)) = &database {
+        paths = check_paths(&mut report, pool).await;
+        paths.push(db.clone());
+    }
+
+    check_ports(
+        &mut report,
+        database.as_ref().map(|(_, pool)| pool),
+        unit.as_ref().map(|(_, u)| u.as_str()),
+    )
+    .await;
+
+    check_service(
+        &mut report,
+        unit.as_ref().map(|(f, u)| (*f, u.as_str())),
+        &paths,
+    );
+
+    if let Some((_, pool)) = database {
+        pool.close().await;
+    }
+
+    if report.failed {
+        println!("\nSome checks failed, fix them before you start ffplayout.");
+        1
+    } else {
+        println!("\nAll needed checks passed.");
+        0
+    }
+}
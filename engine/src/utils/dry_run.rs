@@ -0,0 +1,145 @@
+/// Dry Run
+///
+/// `--dry-run` walks the playlist from today and prints the ffmpeg commands, which the player
+/// would run: the encoder for the output and the decoder for every clip, with all filters.
+/// Nothing is started, so filters and parameters can be debugged offline. The clips are probed
+/// like in the playout, missing clips get the filler.
+use sqlx::{Pool, Sqlite};
+
+use crate::db::handles;
+use crate::player::{
+    controller::ChannelManager,
+    input::playlist::gen_source,
+    output::{decoder_cmd, encoder_cmd, writer_cmd},
+    utils::{folder::fill_filler_list, playlist_store::PlaylistStorage, read_json, sec_to_time},
+};
+use crate::utils::{
+    config::{get_config, OutputMode, ProcessMode},
+    errors::ServiceError,
+    logging::fmt_cmd,
+};
+use crate::ARGS;
+
+/// Commands for the playlist from today, with a comment line before each one.
+pub async fn commands(manager: &ChannelManager) -> Result<Vec<String>, ServiceError> {
+    let mut config = manager.config.lock().await.clone();
+
+    if config.processing.mode == ProcessMode::Folder {
+        return Err(ServiceError::Conflict(
+            "Dry run needs the playlist mode".to_string(),
+        ));
+    }
+
+    let log_format = format!("level+{}", config.logging.ffmpeg_level.to_lowercase());
+    config.general.skip_validation = true;
+
+    let store = PlaylistStorage::new(&config, manager.db_pool.clone());
+    fill_filler_list(&config, Some(manager.filler_list.clone())).await;
+
+    let playlist = read_json(
+        &mut config,
+        &store,
+        manager.current_list.clone(),
+        None,
+        manager.is_alive.clone(),
+        false,
+        false,
+    )
+    .await;
+    let last_index = playlist.program.len().saturating_sub(1);
+    let mut lines = vec![format!(
+        "# Playlist {}, {} clips, output: {}",
+        playlist.date,
+        playlist.program.len(),
+        config.output.mode
+    )];
+
+    *manager.current_list.lock().await = playlist.program.clone();
+
+    match encoder_cmd(&config, &log_format).await {
+        Some((program, cmd)) => {
+            lines.push("# Encoder".to_string());
+            lines.push(format!("{program} {}", fmt_cmd(&cmd)));
+        }
+        None => lines.push("# No encoder, the decoder writes the HLS output".to_string()),
+    }
+
+    for node in playlist.program {
+        let node = gen_source(&config, node, manager, last_index).await;
+
+        lines.push(format!(
+            "# {} {} ({})",
+            sec_to_time(node.begin.unwrap_or_default()),
+            node.source,
+            sec_to_time(node.out - node.seek)
+        ));
+
+        let Some(cmd) = node.cmd.clone() else {
+            lines.push("# No command, the playout stops here".to_string());
+            break;
+        };
+
+        if node.skip {
+            lines.push("# Skipped, the clip is shorter than one second".to_string());
+            continue;
+        }
+
+        let cmd = if config.output.mode == OutputMode::HLS {
+            writer_cmd(&config, &log_format, &node, cmd, 1.0)
+        } else {
+            decoder_cmd(
+                &config,
+                &log_format,
+                cmd,
+                node.filter.clone(),
+                config.output.delay > 0.0,
+            )
+        };
+
+        lines.push(format!("ffmpeg {}", fmt_cmd(&cmd)));
+    }
+
+    Ok(lines)
+}
+
+/// Print the commands from the selected channels. Returns the exit code.
+pub async fn run(pool: &Pool<Sqlite>) -> i32 {
+    let mut code = 0;
+
+    for id in ARGS.channel.clone().unwrap_or_else(|| vec![1]) {
+        let manager = match (
+            get_config(pool, id).await,
+            handles::select_channel(pool, &id).await,
+        ) {
+            (Ok(config), Ok(channel)) => ChannelManager::new(Some(pool.clone()), channel, config),
+            (Err(e), _) => {
+                eprintln!("Channel {id}: {e}");
+                code = 1;
+                continue;
+            }
+            (_, Err(e)) => {
+                eprintln!("Channel {id}: {e}");
+                code = 1;
+                continue;
+            }
+        };
+
+        println!("# Channel {id}");
+
+        match commands(&manager).await {
+            Ok(lines) => {
+                for line in lines {
+                    println!("{line}");
+                }
+            }
+            Err(e) => {
+                eprintln!("Channel {id}: {e}");
+                code = 1;
+            }
+        }
+
+        println!();
+    }
+
+    code
+}
@@ -0,0 +1,227 @@
+/// Error Reporting
+///
+/// Send panics, internal server errors and repeated ffmpeg failures to Sentry,
+/// or to any other service which accepts Sentry events, like GlitchTip.
+/// The reporting is active when a DSN is set in the global settings.
+use std::{
+    collections::{HashMap, VecDeque},
+    panic,
+    sync::{LazyLock, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use chrono::Local;
+use log::*;
+use serde_json::{json, Map, Value};
+use sysinfo::System;
+use uuid::Uuid;
+
+use crate::db::models::GlobalSettings;
+
+/// Report ffmpeg errors from one channel, when they happen this often in the window.
+const FFMPEG_FAILURES: usize = 3;
+const FFMPEG_WINDOW: i64 = 600;
+
+static REPORTER: OnceLock<Dsn> = OnceLock::new();
+static FAILURES: LazyLock<Mutex<FailureCounter>> =
+    LazyLock::new(|| Mutex::new(FailureCounter::default()));
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dsn {
+    pub store_url: String,
+    pub key: String,
+}
+
+/// Split DSN `https://<key>@<host>/<project>` in store endpoint and public key.
+pub fn parse_dsn(dsn: &str) -> Option<Dsn> {
+    let (scheme, rest) = dsn.trim().split_once("://")?;
+    let (key, address) = rest.split_once('@')?;
+    let (host, project) = address.trim_end_matches('/').rsplit_once('/')?;
+    let key = key.split(':').next().unwrap_or_default();
+
+    if key.is_empty() || host.is_empty() || project.is_empty() {
+        return None;
+    }
+
+    Some(Dsn {
+        store_url: format!("{scheme}://{host}/api/{project}/store/"),
+        key: key.to_string(),
+    })
+}
+
+/// ffmpeg errors per channel, with their time in seconds.
+#[derive(Debug, Default)]
+pub struct FailureCounter {
+    times: HashMap<i32, VecDeque<i64>>,
+}
+
+impl FailureCounter {
+    /// Add failure, returns `true` when the limit is reached, then counting starts new.
+    pub fn push(&mut self, channel: i32, time: i64) -> bool {
+        let times = self.times.entry(channel).or_default();
+
+        times.retain(|t| time - t < FFMPEG_WINDOW);
+        times.push_back(time);
+
+        if times.len() >= FFMPEG_FAILURES {
+            times.clear();
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Activate reporting and capture panics, an invalid DSN is only logged.
+pub fn init(global: &GlobalSettings) {
+    if global.sentry_dsn.is_empty() {
+        return;
+    }
+
+    let Some(dsn) = parse_dsn(&global.sentry_dsn) else {
+        error!("Invalid Sentry DSN, error reporting is disabled");
+        return;
+    };
+
+    if REPORTER.set(dsn).is_err() {
+        return;
+    }
+
+    let hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let payload = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".to_string());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+
+        if let Some((dsn, event)) = event(
+            "fatal",
+            format!("{payload} at {location}"),
+            vec![("kind", "panic".to_string())],
+        ) {
+            // the process can exit after the hook, so send the event before
+            let _ = thread::spawn(move || {
+                if let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    runtime.block_on(send(dsn, event));
+                }
+            })
+            .join();
+        }
+
+        hook(info);
+    }));
+}
+
+fn event(level: &str, message: String, tags: Vec<(&str, String)>) -> Option<(&'static Dsn, Value)> {
+    let dsn = REPORTER.get()?;
+    let tags: Map<String, Value> = tags
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), Value::String(v)))
+        .collect();
+
+    Some((
+        dsn,
+        json!({
+            "event_id": Uuid::new_v4().simple().to_string(),
+            "timestamp": Local::now().to_rfc3339(),
+            "platform": "other",
+            "level": level,
+            "logger": "ffplayout",
+            "release": format!("ffplayout@{}", env!("CARGO_PKG_VERSION")),
+            "server_name": System::host_name().unwrap_or_default(),
+            "message": { "formatted": message },
+            "tags": tags,
+        }),
+    ))
+}
+
+async fn send(dsn: &Dsn, event: Value) {
+    let auth = format!(
+        "Sentry sentry_version=7, sentry_key={}, sentry_client=ffplayout/{}",
+        dsn.key,
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let result = reqwest::Client::new()
+        .post(&dsn.store_url)
+        .header("X-Sentry-Auth", auth)
+        .timeout(Duration::from_secs(5))
+        .json(&event)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+
+    if let Err(e) = result {
+        error!("Send error report failed: {}", e.without_url());
+    }
+}
+
+/// Send event in the background, does nothing when reporting is disabled.
+pub fn capture(level: &str, message: String, tags: Vec<(&str, String)>) {
+    if let (Some((dsn, event)), Ok(runtime)) = (
+        event(level, message, tags),
+        tokio::runtime::Handle::try_current(),
+    ) {
+        runtime.spawn(send(dsn, event));
+    }
+}
+
+/// ffmpeg error from a channel, reported when it happens repeatedly.
+pub fn ffmpeg_failure(channel: i32, message: &str, time: i64) {
+    if REPORTER.get().is_none() {
+        return;
+    }
+
+    let repeated = FAILURES
+        .lock()
+        .map(|mut f| f.push(channel, time))
+        .unwrap_or_default();
+
+    if repeated {
+        capture(
+            "error",
+            format!("Repeated ffmpeg failures: {message}"),
+            vec![
+                ("kind", "ffmpeg".to_string()),
+                ("channel", channel.to_string()),
+            ],
+        );
+    }
+}
+
+/// Middleware, which reports responses with server errors.
+pub async fn report_errors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let route = format!("{} {}", req.method(), req.path());
+    let res = next.call(req).await?;
+
+    if res.status().is_server_error() {
+        if let Some(e) = res.response().error() {
+            capture(
+                "error",
+                e.to_string(),
+                vec![("kind", "service".to_string()), ("route", route)],
+            );
+        }
+    }
+
+    Ok(res)
+}
@@ -0,0 +1,71 @@
+/// Event History
+///
+/// State changes, output and ingest connections and errors from every channel are saved in the database,
+/// so a frontend which was closed or lost the connection can load what happened in the meantime.
+/// Only the newest events per channel are kept.
+use std::sync::OnceLock;
+
+use chrono::Local;
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::db::{handles, models::HistoryEvent};
+use crate::utils::play_history::HISTORY_TIME_FORMAT;
+
+/// Events per channel.
+pub const MAX_EVENTS: i64 = 1000;
+
+static EVENTS: OnceLock<UnboundedSender<HistoryEvent>> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    State,
+    Output,
+    Ingest,
+    Error,
+    Alert,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::State => "state",
+            Self::Output => "output",
+            Self::Ingest => "ingest",
+            Self::Error => "error",
+            Self::Alert => "alert",
+        }
+    }
+}
+
+/// Save events from now on, in a background task.
+pub fn init(pool: Pool<Sqlite>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    if EVENTS.set(sender).is_ok() {
+        tokio::spawn(run(pool, receiver));
+    }
+}
+
+async fn run(pool: Pool<Sqlite>, mut receiver: UnboundedReceiver<HistoryEvent>) {
+    while let Some(event) = receiver.recv().await {
+        // no channel in the log message, otherwise it would be saved again
+        if let Err(e) = handles::insert_history_event(&pool, &event, MAX_EVENTS).await {
+            error!("Save event history failed: {e}");
+        }
+    }
+}
+
+/// Add event to the history, does nothing before [`init`].
+pub fn push(channel_id: i32, kind: EventKind, message: impl Into<String>) {
+    if let Some(sender) = EVENTS.get() {
+        let _ = sender.send(HistoryEvent {
+            id: 0,
+            channel_id,
+            time: Local::now().format(HISTORY_TIME_FORMAT).to_string(),
+            kind: kind.as_str().to_string(),
+            message: message.into(),
+        });
+    }
+}
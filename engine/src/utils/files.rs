@@ -12,7 +12,7 @@ use tokio::{fs, io::AsyncWriteExt};
 
 use crate::db::models::Channel;
 use crate::player::utils::{file_extension, probe::MediaProbe};
-use crate::utils::{config::PlayoutConfig, errors::ServiceError};
+use crate::utils::{config::PlayoutConfig, errors::ServiceError, house_format};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PathObject {
@@ -414,6 +414,25 @@ pub async fn upload(
                 }
             }
         }
+
+        if !abs_path && house_format::applies(config, &filepath) {
+            f.flush().await?;
+            drop(f);
+
+            let problems = house_format::check_file(&config.storage.house_format, &filepath).await;
+
+            if !problems.is_empty() {
+                info!("Delete file, which breaks the house format: {filepath:?}");
+
+                tokio::fs::remove_file(&filepath).await?;
+
+                return Err(ServiceError::BadRequest(format!(
+                    "{} breaks the house format: {}",
+                    filepath.file_name().unwrap_or_default().to_string_lossy(),
+                    problems.join(", ")
+                )));
+            }
+        }
     }
 
     Ok(HttpResponse::Ok().into())
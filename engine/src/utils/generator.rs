@@ -3,24 +3,28 @@
 /// You can call ffplayout[.exe] -g YYYY-mm-dd - YYYY-mm-dd to generate JSON playlists.
 ///
 /// The generator takes the files from storage, which are set in config.
-/// It also respect the shuffle/sort mode.
-use std::io::Error;
+/// It also respect the shuffle/sort mode, with `--seed` the shuffle is repeatable.
+/// Template blocks can have a category, `--quota` limits its share from the template length.
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
 
 use async_iterator::Iterator;
 use async_walkdir::{Filtering, WalkDir};
-use chrono::Timelike;
+use chrono::{NaiveTime, Timelike};
 use lexical_sort::{natural_lexical_cmp, StringSort};
 use log::*;
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use tokio::fs;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
 use tokio_stream::StreamExt;
 
 use crate::player::{
     controller::ChannelManager,
     utils::{
-        folder::{fill_filler_list, FolderSource},
+        folder::{fill_filler_list, seeded_rng, tagged_files, FolderSource},
         get_date_range, include_file_extension,
         json_serializer::JsonPlaylist,
+        playlist_store::{PlaylistStorage, PlaylistStore},
         sum_durations, Media,
     },
 };
@@ -30,7 +34,7 @@ use crate::utils::{
     time_to_sec,
 };
 
-pub fn random_list(clip_list: Vec<Media>, total_length: f64) -> Vec<Media> {
+pub fn random_list(clip_list: Vec<Media>, total_length: f64, rng: &mut StdRng) -> Vec<Media> {
     let mut max_attempts = 10000;
     let mut randomized_clip_list: Vec<Media> = vec![];
     let mut target_duration = 0.0;
@@ -39,7 +43,7 @@ pub fn random_list(clip_list: Vec<Media>, total_length: f64) -> Vec<Media> {
     let mut last_clip = Media::default();
 
     while target_duration < total_length && max_attempts > 0 {
-        let index = rand::thread_rng().gen_range(0..clip_list_length);
+        let index = rng.gen_range(0..clip_list_length);
         let selected_clip = clip_list[index].clone();
         let selected_clip_count = randomized_clip_list
             .iter()
@@ -123,25 +127,50 @@ pub async fn filler_list(config: &PlayoutConfig, total_length: f64) -> Vec<Media
     filler_clip_list
 }
 
+/// Seconds which are left for the category, `None` when it has no quota.
+pub fn quota_left(
+    quotas: &[(String, f64)],
+    category: &str,
+    template_length: f64,
+    used: f64,
+) -> Option<f64> {
+    quotas
+        .iter()
+        .find(|(c, _)| !category.is_empty() && c == category)
+        .map(|(_, percent)| (template_length * percent / 100.0 - used).max(0.0))
+}
+
+fn time_sec(time: &NaiveTime) -> f64 {
+    time.num_seconds_from_midnight() as f64
+}
+
 pub async fn generate_from_template(
     config: &PlayoutConfig,
     manager: &ChannelManager,
     template: Template,
 ) -> FolderSource {
     let mut media_list = vec![];
-    let mut rng = thread_rng();
+    let mut rng = seeded_rng(config.general.seed);
     let mut index: usize = 0;
     let id = config.general.channel_id;
+    let template_length: f64 = template.sources.iter().map(|s| time_sec(&s.duration)).sum();
+    let mut used: HashMap<String, f64> = HashMap::new();
 
     for source in template.sources {
         let mut source_list = vec![];
-        let duration = (source.duration.hour() as f64 * 3600.0)
-            + (source.duration.minute() as f64 * 60.0)
-            + source.duration.second() as f64;
+        let duration = time_sec(&source.duration);
 
         debug!(target: Target::all(), channel = id; "Generating playlist block with <yellow>{duration:.2}</> seconds length");
 
-        for path in source.paths {
+        let tagged = tagged_files(config, manager.db_pool.as_ref(), &source.tags).await;
+        let mut paths = source.paths;
+
+        // only tags are set, search them in the whole storage
+        if paths.is_empty() && tagged.is_some() {
+            paths.push(config.channel.storage.clone());
+        }
+
+        for path in paths {
             debug!("Search files in <b><magenta>{path:?}</></b>");
             let mut file_list = vec![];
 
@@ -160,6 +189,10 @@ pub async fn generate_from_template(
             loop {
                 match entries.next().await {
                     Some(Ok(entry)) => {
+                        if tagged.as_ref().is_some_and(|t| !t.contains(&entry.path())) {
+                            continue;
+                        }
+
                         let file = entry.path().to_string_lossy().to_string();
 
                         file_list.push(file);
@@ -182,15 +215,38 @@ pub async fn generate_from_template(
             }
         }
 
+        let mut length = duration;
+
+        if let Some(left) = quota_left(
+            &config.general.quotas,
+            &source.category,
+            template_length,
+            used.get(&source.category).copied().unwrap_or_default(),
+        )
+        .filter(|left| *left < duration)
+        {
+            debug!(target: Target::all(), channel = id;
+                "Quota from category <yellow>{}</> leaves <yellow>{left:.2}</> seconds, fill the rest",
+                source.category
+            );
+
+            length = left;
+        }
+
         let mut timed_list = if source.shuffle {
             source_list.shuffle(&mut rng);
 
-            random_list(source_list, duration)
+            random_list(source_list, length, &mut rng)
         } else {
-            ordered_list(source_list, duration)
+            ordered_list(source_list, length)
         };
 
+        for item in &mut timed_list {
+            item.category.clone_from(&source.category);
+        }
+
         let total_length = sum_durations(&timed_list);
+        *used.entry(source.category.clone()).or_default() += total_length;
 
         if duration > total_length {
             let mut filler = filler_list(config, duration - total_length).await;
@@ -227,11 +283,12 @@ pub async fn playlist_generator(manager: &ChannelManager) -> Result<Vec<JsonPlay
         }
     };
     let playlist_root = &config.channel.playlists;
+    let store = PlaylistStorage::new(&config, manager.db_pool.clone());
     let mut playlists = vec![];
     let mut date_range = vec![];
     let mut from_template = false;
 
-    if !playlist_root.is_dir() {
+    if matches!(store, PlaylistStorage::File(_)) && !playlist_root.is_dir() {
         error!(
             target: Target::all(), channel = id;
             "Playlist folder <b><magenta>{:?}</></b> not exists!",
@@ -259,21 +316,14 @@ pub async fn playlist_generator(manager: &ChannelManager) -> Result<Vec<JsonPlay
     let list_length = manager.current_list.lock().await.len();
 
     for date in date_range {
-        let d: Vec<&str> = date.split('-').collect();
-        let year = d[0];
-        let month = d[1];
-        let playlist_path = playlist_root.join(year).join(month);
-        let playlist_file = &playlist_path.join(format!("{date}.json"));
         let mut length = 0.0;
         let mut round = 0;
 
-        fs::create_dir_all(playlist_path).await?;
-
-        if playlist_file.is_file() {
+        if !config.general.generate_stdout && store.modified(&date).await.is_some() {
             warn!(
                 target: Target::all(), channel = id;
                 "Playlist exists, skip: <b><magenta>{}</></b>",
-                playlist_file.display()
+                store.location(&date)
             );
 
             continue;
@@ -282,7 +332,7 @@ pub async fn playlist_generator(manager: &ChannelManager) -> Result<Vec<JsonPlay
         info!(
             target: Target::all(), channel = id;
             "Generate playlist: <b><magenta>{}</></b>",
-            playlist_file.display()
+            store.location(&date)
         );
 
         let mut playlist = JsonPlaylist {
@@ -292,6 +342,7 @@ pub async fn playlist_generator(manager: &ChannelManager) -> Result<Vec<JsonPlay
             start_sec: None,
             length: None,
             modified: None,
+            version: None,
             program: vec![],
         };
 
@@ -323,8 +374,14 @@ pub async fn playlist_generator(manager: &ChannelManager) -> Result<Vec<JsonPlay
             }
         }
 
-        let json: String = serde_json::to_string_pretty(&playlist)?;
-        fs::write(playlist_file, json).await?;
+        if config.general.generate_stdout {
+            println!("{}", serde_json::to_string_pretty(&playlist)?);
+        } else {
+            store
+                .write(&playlist)
+                .await
+                .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
+        }
 
         playlists.push(playlist);
     }
@@ -0,0 +1,196 @@
+/// House Format
+///
+/// Rules for the media from a channel, in `storage.house_format`: container, codecs, resolution,
+/// frame rate, loudness and minimum duration. Uploads which break a rule are deleted again,
+/// imported playlists with such clips are rejected, and the playlist validation reports them.
+/// When the next clip breaks a rule, its backup or the filler plays instead.
+///
+/// Only files with an extension from `storage.extensions` are checked, images are not checked.
+/// The loudness needs to decode the audio, the values are kept until the file changes.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{LazyLock, Mutex},
+    time::SystemTime,
+};
+
+use tokio::{fs, process::Command};
+
+use crate::player::utils::{fps_calc, is_close, probe::MediaProbe, sec_to_time};
+use crate::utils::{
+    config::{HouseFormat, PlayoutConfig, IMAGE_FORMAT},
+    errors::ProcessError,
+};
+
+/// Measured loudness per file, with the modification time.
+static LOUDNESS: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, f64)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The file is a clip, which must follow the house format.
+pub fn applies(config: &PlayoutConfig, path: &Path) -> bool {
+    let Some(extension) = path.extension().map(|e| e.to_string_lossy().to_lowercase()) else {
+        return false;
+    };
+
+    !config.storage.house_format.is_empty()
+        && !IMAGE_FORMAT.contains(&extension.as_str())
+        && config
+            .storage
+            .extensions
+            .iter()
+            .any(|e| e.to_lowercase() == extension)
+}
+
+fn allowed(list: &[String], value: &str) -> bool {
+    list.is_empty() || list.iter().any(|v| v.eq_ignore_ascii_case(value))
+}
+
+/// Rules which the probe breaks, the loudness is not checked here.
+pub fn violations(rules: &HouseFormat, probe: &MediaProbe) -> Vec<String> {
+    let mut problems = vec![];
+
+    if !rules.containers.is_empty() {
+        let name = probe.format.format_name.clone().unwrap_or_default();
+
+        if !name.split(',').any(|n| allowed(&rules.containers, n)) {
+            problems.push(format!("container {name} is not allowed"));
+        }
+    }
+
+    for stream in &probe.video {
+        let codec = stream.codec_name.clone().unwrap_or_default();
+
+        if !allowed(&rules.video_codecs, &codec) {
+            problems.push(format!("video codec {codec} is not allowed"));
+        }
+    }
+
+    for stream in &probe.audio {
+        let codec = stream.codec_name.clone().unwrap_or_default();
+
+        if !allowed(&rules.audio_codecs, &codec) {
+            problems.push(format!("audio codec {codec} is not allowed"));
+        }
+    }
+
+    if let Some(video) = probe.video.first() {
+        let (width, height) = (
+            video.width.unwrap_or_default(),
+            video.height.unwrap_or_default(),
+        );
+
+        if rules.width.is_some_and(|w| w != width) || rules.height.is_some_and(|h| h != height) {
+            problems.push(format!(
+                "resolution {width}x{height}, needs {}x{}",
+                rules.width.map_or("*".to_string(), |w| w.to_string()),
+                rules.height.map_or("*".to_string(), |h| h.to_string())
+            ));
+        }
+
+        let fps = fps_calc(&video.frame_rate, 0.0);
+
+        if let Some(needed) = rules.fps.filter(|f| !is_close(*f, fps, 0.01)) {
+            problems.push(format!("frame rate {fps:.3}, needs {needed}"));
+        }
+    } else if rules.width.is_some() || rules.height.is_some() || rules.fps.is_some() {
+        problems.push("no video stream".to_string());
+    }
+
+    let duration = probe.format.duration.unwrap_or_default();
+
+    if let Some(min) = rules.min_duration.filter(|m| duration < *m) {
+        problems.push(format!(
+            "duration {} is shorter than {}",
+            sec_to_time(duration),
+            sec_to_time(min)
+        ));
+    }
+
+    problems
+}
+
+/// Integrated loudness from the summary of the ebur128 filter.
+pub fn parse_loudness(output: &str) -> Option<f64> {
+    output
+        .rsplit_once("Integrated loudness:")?
+        .1
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("I:"))?
+        .trim()
+        .trim_end_matches("LUFS")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Problem with the loudness, when it is out of the range.
+pub fn loudness_violation(rules: &HouseFormat, lufs: f64) -> Option<String> {
+    let range = match (rules.loudness_min, rules.loudness_max) {
+        (Some(min), Some(max)) if lufs < min || lufs > max => format!("{min} to {max}"),
+        (Some(min), None) if lufs < min => format!("at least {min}"),
+        (None, Some(max)) if lufs > max => format!("at most {max}"),
+        _ => return None,
+    };
+
+    Some(format!("loudness {lufs:.1} LUFS, needs {range} LUFS"))
+}
+
+/// Integrated loudness from the first audio track.
+pub async fn loudness(path: &Path) -> Result<f64, ProcessError> {
+    let modified = fs::metadata(path).await?.modified()?;
+
+    if let Some((time, lufs)) = LOUDNESS.lock().unwrap().get(path) {
+        if *time == modified {
+            return Ok(*lufs);
+        }
+    }
+
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(path)
+        .args([
+            "-map",
+            "0:a:0",
+            "-filter:a",
+            "ebur128=framelog=quiet",
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    let lufs = parse_loudness(&String::from_utf8_lossy(&output.stderr))
+        .ok_or_else(|| ProcessError::Custom("ffmpeg has no loudness".to_string()))?;
+
+    LOUDNESS
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (modified, lufs));
+
+    Ok(lufs)
+}
+
+/// All rules which the file breaks, empty when it follows the house format.
+pub async fn check_file(rules: &HouseFormat, path: &Path) -> Vec<String> {
+    match MediaProbe::new(path).await {
+        Ok(probe) => check_probe(rules, path, &probe).await,
+        Err(e) => vec![format!("probe failed: {e}")],
+    }
+}
+
+/// Like [`check_file`], with the probe which is already there.
+pub async fn check_probe(rules: &HouseFormat, path: &Path, probe: &MediaProbe) -> Vec<String> {
+    let mut problems = violations(rules, probe);
+
+    if (rules.loudness_min.is_some() || rules.loudness_max.is_some()) && !probe.audio.is_empty() {
+        match loudness(path).await {
+            Ok(lufs) => problems.extend(loudness_violation(rules, lufs)),
+            Err(e) => problems.push(format!("loudness not measured: {e}")),
+        }
+    }
+
+    problems
+}
@@ -0,0 +1,361 @@
+/// Legacy YAML Config
+///
+/// Read the `ffplayout.yml` and `advanced.yml` from older ffplayout versions (Python and Rust up to v0.20)
+/// and write the values to the database. Fields which are missing in the YAML keep their current value.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::*;
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+use tokio::fs;
+
+use crate::db::handles;
+use crate::utils::{
+    advanced_config::{AdvancedConfig, FilterConfig},
+    config::{string_to_log_level, OutputMode, PlayoutConfig, ProcessMode},
+    errors::ServiceError,
+    files::norm_abs_path,
+};
+
+macro_rules! set {
+    ($target:expr, $value:expr) => {
+        if let Some(v) = $value {
+            $target = v;
+        }
+    };
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct General {
+    stop_threshold: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Mail {
+    subject: Option<String>,
+    smtp_server: Option<String>,
+    starttls: Option<bool>,
+    sender_addr: Option<String>,
+    sender_pass: Option<String>,
+    recipient: Option<String>,
+    mail_level: Option<String>,
+    interval: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Logging {
+    path: Option<String>,
+    ffmpeg_level: Option<String>,
+    ingest_level: Option<String>,
+    detect_silence: Option<bool>,
+    ignore_lines: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Processing {
+    mode: Option<String>,
+    audio_only: Option<bool>,
+    copy_audio: Option<bool>,
+    copy_video: Option<bool>,
+    width: Option<i64>,
+    height: Option<i64>,
+    aspect: Option<f64>,
+    fps: Option<f64>,
+    add_logo: Option<bool>,
+    logo: Option<String>,
+    logo_scale: Option<String>,
+    logo_opacity: Option<f64>,
+    logo_position: Option<String>,
+    audio_tracks: Option<i32>,
+    audio_track_index: Option<i32>,
+    audio_channels: Option<u8>,
+    volume: Option<f64>,
+    custom_filter: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Ingest {
+    enable: Option<bool>,
+    input_param: Option<String>,
+    custom_filter: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Playlist {
+    path: Option<String>,
+    day_start: Option<String>,
+    length: Option<String>,
+    infinit: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Storage {
+    path: Option<String>,
+    filler: Option<String>,
+    extensions: Option<Vec<String>>,
+    shuffle: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Text {
+    add_text: Option<bool>,
+    text_from_filename: Option<bool>,
+    #[serde(alias = "font")]
+    fontfile: Option<String>,
+    style: Option<String>,
+    regex: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Task {
+    enable: Option<bool>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Output {
+    mode: Option<String>,
+    output_param: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LegacyConfig {
+    general: General,
+    mail: Mail,
+    logging: Logging,
+    processing: Processing,
+    ingest: Ingest,
+    playlist: Playlist,
+    storage: Storage,
+    text: Text,
+    task: Task,
+    #[serde(alias = "output")]
+    out: Output,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Params {
+    input_param: Option<String>,
+    output_param: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LegacyAdvanced {
+    decoder: Params,
+    encoder: Params,
+    ingest: Params,
+    #[serde(alias = "filter")]
+    filters: BTreeMap<String, Option<String>>,
+}
+
+/// Make a media path relative to the storage, like the config expects it.
+fn storage_path(storage: &Path, path: &str) -> String {
+    if Path::new(path).is_absolute() && !path.starts_with(&*storage.to_string_lossy()) {
+        warn!(
+            "<b><magenta>{path}</></b> is outside of storage, copy it to: <b><magenta>{}</></b>",
+            storage.display()
+        );
+    }
+
+    norm_abs_path(storage, path)
+        .map(|(_, _, p)| p)
+        .unwrap_or(path.to_string())
+}
+
+/// Parse a mode from the YAML, an unknown mode stops the import.
+fn parse_mode<T: FromStr<Err = String>>(
+    name: &str,
+    value: Option<&str>,
+) -> Result<Option<T>, ServiceError> {
+    value
+        .map(|m| {
+            T::from_str(m.trim()).map_err(|e| {
+                ServiceError::BadRequest(format!("Invalid {name} mode \"{m}\" in YAML config: {e}"))
+            })
+        })
+        .transpose()
+}
+
+impl LegacyConfig {
+    /// Map values to current config, channel paths and global mail settings are updated directly.
+    pub async fn migrate(self, pool: &Pool<Sqlite>, id: i32) -> Result<(), ServiceError> {
+        let process_mode =
+            parse_mode::<ProcessMode>("processing", self.processing.mode.as_deref())?;
+        let output_mode = parse_mode::<OutputMode>("output", self.out.mode.as_deref())?;
+        let mut config = PlayoutConfig::new(pool, id).await?;
+        let mut channel = handles::select_channel(pool, &id).await?;
+        let mut global = handles::select_global(pool).await?;
+
+        set!(channel.playlists, self.playlist.path);
+        set!(channel.storage, self.storage.path);
+        set!(global.logs, self.logging.path);
+        set!(global.smtp_server, self.mail.smtp_server);
+        set!(global.smtp_starttls, self.mail.starttls);
+        set!(global.smtp_user, self.mail.sender_addr);
+        set!(global.smtp_password, self.mail.sender_pass);
+
+        let storage = PathBuf::from(&channel.storage);
+
+        set!(config.general.stop_threshold, self.general.stop_threshold);
+
+        set!(config.mail.subject, self.mail.subject);
+        set!(config.mail.recipient, self.mail.recipient);
+        set!(config.mail.interval, self.mail.interval);
+        set!(
+            config.mail.mail_level,
+            self.mail.mail_level.map(string_to_log_level)
+        );
+
+        set!(config.logging.ffmpeg_level, self.logging.ffmpeg_level);
+        set!(config.logging.ingest_level, self.logging.ingest_level);
+        set!(config.logging.detect_silence, self.logging.detect_silence);
+        set!(config.logging.ignore_lines, self.logging.ignore_lines);
+
+        set!(config.processing.mode, process_mode);
+        set!(config.processing.audio_only, self.processing.audio_only);
+        set!(config.processing.copy_audio, self.processing.copy_audio);
+        set!(config.processing.copy_video, self.processing.copy_video);
+        set!(config.processing.width, self.processing.width);
+        set!(config.processing.height, self.processing.height);
+        set!(config.processing.aspect, self.processing.aspect);
+        set!(config.processing.fps, self.processing.fps);
+        set!(config.processing.add_logo, self.processing.add_logo);
+        set!(
+            config.processing.logo,
+            self.processing.logo.map(|l| storage_path(&storage, &l))
+        );
+        set!(config.processing.logo_scale, self.processing.logo_scale);
+        set!(config.processing.logo_opacity, self.processing.logo_opacity);
+        set!(
+            config.processing.logo_position,
+            self.processing.logo_position
+        );
+        set!(config.processing.audio_tracks, self.processing.audio_tracks);
+        set!(
+            config.processing.audio_track_index,
+            self.processing.audio_track_index
+        );
+        set!(
+            config.processing.audio_channels,
+            self.processing.audio_channels
+        );
+        set!(config.processing.volume, self.processing.volume);
+        set!(
+            config.processing.custom_filter,
+            self.processing.custom_filter
+        );
+
+        set!(config.ingest.enable, self.ingest.enable);
+        set!(config.ingest.input_param, self.ingest.input_param);
+        set!(config.ingest.custom_filter, self.ingest.custom_filter);
+
+        set!(config.playlist.day_start, self.playlist.day_start);
+        set!(config.playlist.length, self.playlist.length);
+        set!(config.playlist.infinit, self.playlist.infinit);
+
+        set!(
+            config.storage.filler,
+            self.storage.filler.map(|f| storage_path(&storage, &f))
+        );
+        set!(config.storage.extensions, self.storage.extensions);
+        set!(config.storage.shuffle, self.storage.shuffle);
+
+        set!(config.text.add_text, self.text.add_text);
+        set!(config.text.text_from_filename, self.text.text_from_filename);
+        set!(
+            config.text.font,
+            self.text.fontfile.map(|f| storage_path(&storage, &f))
+        );
+        set!(config.text.style, self.text.style);
+        set!(config.text.regex, self.text.regex);
+
+        set!(config.task.enable, self.task.enable);
+        set!(config.task.path, self.task.path);
+
+        set!(config.output.mode, output_mode);
+        set!(config.output.output_param, self.out.output_param);
+
+        handles::update_channel(pool, id, channel).await?;
+        handles::update_global(pool, global).await?;
+        handles::update_configuration(pool, config.general.id, config).await?;
+
+        Ok(())
+    }
+}
+
+impl LegacyAdvanced {
+    pub async fn migrate(self, pool: &Pool<Sqlite>, id: i32) -> Result<(), ServiceError> {
+        let mut config =
+            AdvancedConfig::new(handles::select_advanced_configuration(pool, id).await?);
+
+        set!(
+            config.decoder.input_param,
+            self.decoder.input_param.map(Some)
+        );
+        set!(
+            config.decoder.output_param,
+            self.decoder.output_param.map(Some)
+        );
+        set!(
+            config.encoder.input_param,
+            self.encoder.input_param.map(Some)
+        );
+        set!(config.ingest.input_param, self.ingest.input_param.map(Some));
+
+        if !self.filters.is_empty() {
+            let mut filter = serde_json::to_value(&config.filter)?;
+
+            if let Some(map) = filter.as_object_mut() {
+                for (key, value) in self.filters {
+                    // empty filters are valid and mean: use the default
+                    map.insert(key, serde_json::Value::String(value.unwrap_or_default()));
+                }
+            }
+
+            config.filter = serde_json::from_value::<FilterConfig>(filter)?;
+        }
+
+        handles::update_advanced_configuration(pool, id, config).await?;
+
+        Ok(())
+    }
+}
+
+/// Migrate `ffplayout.yml`, and when it exists the `advanced.yml` in the same folder, to the given channel.
+pub async fn migrate_yaml(pool: &Pool<Sqlite>, id: i32, path: &Path) -> Result<(), ServiceError> {
+    if !path.is_file() {
+        return Err(ServiceError::BadRequest("Path not exists!".to_string()));
+    }
+
+    let legacy: LegacyConfig =
+        serde_yaml::from_str(&fs::read_to_string(path).await?).map_err(|e| e.to_string())?;
+    legacy.migrate(pool, id).await?;
+
+    let advanced_path = path.with_file_name("advanced.yml");
+
+    if advanced_path.is_file() {
+        let advanced: LegacyAdvanced =
+            serde_yaml::from_str(&fs::read_to_string(&advanced_path).await?)
+                .map_err(|e| e.to_string())?;
+        advanced.migrate(pool, id).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+/// Log Search
+///
+/// Search the channel log files, the current one and the rotated ones, by date range, level and text.
+/// Compressed files are not searched, they are listed in the result instead.
+use std::{path::Path, str::FromStr};
+
+use chrono::{NaiveDate, TimeDelta};
+use flexi_logger::Level;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncBufReadExt, BufReader},
+};
+
+use crate::utils::{
+    errors::ServiceError,
+    logging::{log_file_path, strip_tags, LogLine},
+};
+
+const MAX_LIMIT: usize = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct LogQuery {
+    /// First day, in `YYYY-MM-DD`.
+    pub from: String,
+    /// Last day, same as `from` when empty.
+    #[serde(default)]
+    pub to: String,
+    /// Only lines with this level or a more important one.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Case insensitive text, without color tags.
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LogSearch {
+    pub lines: Vec<LogLine>,
+    /// More lines would match, but the limit is reached.
+    pub truncated: bool,
+    /// Compressed files in the date range, which are not searched.
+    pub skipped: Vec<String>,
+}
+
+/// Date from the file name: `ffplayout_1.log` is the current file, rotated files look like
+/// `ffplayout_1_2024-05-20.log`, `ffplayout_1_2024-05-20.restart-0001.log` or the same with `.gz`.
+pub(crate) fn file_date(name: &str, channel: i32) -> Option<&str> {
+    let rest = name.strip_prefix(&format!("ffplayout_{channel}"))?;
+
+    if rest == ".log" {
+        return Some("");
+    }
+
+    let date = rest.strip_prefix('_')?.get(..10)?;
+
+    if rest[11..].starts_with(".log") || rest[11..].starts_with(".restart-") {
+        Some(date)
+    } else {
+        None
+    }
+}
+
+/// Read one line from the text or JSON format.
+pub fn parse_log_line(line: &str) -> Option<LogLine> {
+    if line.starts_with('{') {
+        let record: serde_json::Value = serde_json::from_str(line).ok()?;
+
+        return Some(LogLine {
+            channel: record["channel"].as_i64().unwrap_or_default() as i32,
+            timestamp: record["timestamp"].as_str()?.to_string(),
+            level: Level::from_str(record["level"].as_str()?).ok()?,
+            message: record["message"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+
+    let (timestamp, rest) = line.strip_prefix('[')?.split_once("] [")?;
+    let (level, message) = rest.split_once("] ")?;
+
+    Some(LogLine {
+        channel: 0,
+        timestamp: timestamp.to_string(),
+        level: Level::from_str(level.trim()).ok()?,
+        message: message.to_string(),
+    })
+}
+
+/// Validated search parameters.
+struct Filter {
+    from: String,
+    to: String,
+    level: Level,
+    text: String,
+}
+
+impl Filter {
+    fn new(query: &LogQuery) -> Result<Self, ServiceError> {
+        let parse = |date: &str| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                ServiceError::BadRequest(format!("Date must be in format YYYY-MM-DD: {date}"))
+            })
+        };
+
+        let from = parse(&query.from)?;
+        let to = if query.to.is_empty() {
+            from
+        } else {
+            parse(&query.to)?
+        };
+        let level = match &query.level {
+            Some(l) => Level::from_str(l)
+                .map_err(|_| ServiceError::BadRequest(format!("Unknown log level: {l}")))?,
+            None => Level::Trace,
+        };
+
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            level,
+            text: query.text.to_lowercase(),
+        })
+    }
+
+    /// Files are named after the day they are created, so they can't contain older lines.
+    /// The day before is included, for files which are rotated a bit after midnight.
+    fn file_in_range(&self, date: &str) -> bool {
+        let before = NaiveDate::parse_from_str(&self.from, "%Y-%m-%d")
+            .map(|d| (d - TimeDelta::days(1)).to_string())
+            .unwrap_or_default();
+
+        date.is_empty() || (date >= before.as_str() && date <= self.to.as_str())
+    }
+
+    fn matches(&self, line: &LogLine) -> bool {
+        line.level <= self.level
+            && line
+                .timestamp
+                .get(..10)
+                .is_some_and(|d| d >= self.from.as_str() && d <= self.to.as_str())
+            && (self.text.is_empty()
+                || strip_tags(&line.message)
+                    .to_lowercase()
+                    .contains(&self.text))
+    }
+}
+
+async fn search_file(
+    path: &Path,
+    filter: &Filter,
+    result: &mut LogSearch,
+    limit: usize,
+) -> Result<(), ServiceError> {
+    let mut lines = BufReader::new(File::open(path).await?).lines();
+    let mut last: Option<LogLine> = None;
+
+    while let Some(line) = lines.next_line().await? {
+        match parse_log_line(&line) {
+            Some(log_line) => {
+                if let Some(l) = last.take() {
+                    if filter.matches(&l) {
+                        if result.lines.len() >= limit {
+                            result.truncated = true;
+                            return Ok(());
+                        }
+
+                        result.lines.push(l);
+                    }
+                }
+
+                last = Some(log_line);
+            }
+            // messages over multiple lines, like ffmpeg output
+            None => {
+                if let Some(l) = last.as_mut() {
+                    l.message.push('\n');
+                    l.message.push_str(&line);
+                }
+            }
+        }
+    }
+
+    if let Some(l) = last {
+        if filter.matches(&l) {
+            if result.lines.len() >= limit {
+                result.truncated = true;
+            } else {
+                result.lines.push(l);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn search_logs(channel: i32, query: &LogQuery) -> Result<LogSearch, ServiceError> {
+    let filter = Filter::new(query)?;
+    let limit = query.limit.unwrap_or(500).min(MAX_LIMIT);
+    let mut result = LogSearch::default();
+    let mut files = vec![];
+    let mut entries = fs::read_dir(log_file_path()).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        let Some(date) = file_date(&name, channel) else {
+            continue;
+        };
+
+        if !filter.file_in_range(date) {
+            continue;
+        }
+
+        if name.ends_with(".gz") {
+            result.skipped.push(name);
+        } else {
+            files.push((date.to_string(), entry.path()));
+        }
+    }
+
+    // oldest first, the current file has no date and comes last
+    files.sort_by(|a, b| (a.0.is_empty(), &a.0, &a.1).cmp(&(b.0.is_empty(), &b.0, &b.1)));
+    result.skipped.sort();
+
+    for (_, path) in files {
+        search_file(&path, &filter, &mut result, limit).await?;
+
+        if result.truncated {
+            break;
+        }
+    }
+
+    Ok(result)
+}
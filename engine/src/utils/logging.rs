@@ -1,12 +1,17 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
 use std::{
     collections::{hash_map, HashMap},
     env,
     io::{self, ErrorKind, Write},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
     path::PathBuf,
-    sync::{Arc, RwLock},
-    time::Duration,
+    process,
+    sync::{Arc, LazyLock, Mutex as StdMutex, RwLock},
+    time::{Duration, Instant},
 };
 
+use chrono::Local;
 use flexi_logger::{
     writers::{FileLogWriter, LogWriter},
     Age, Cleanup, Criterion, DeferredNow, FileSpec, Level, LogSpecification, Logger, Naming,
@@ -16,29 +21,97 @@ use lettre::{
     message::header, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
     AsyncTransport, Message, Tokio1Executor,
 };
-use log::{kv::Value, *};
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    *,
+};
 use paris::formatter::colorize_string;
 use regex::Regex;
-use tokio::{sync::Mutex, time::interval};
+use serde::Serialize;
+use serde_json::{json, Map};
+use tokio::{
+    sync::{broadcast, Mutex},
+    time::interval,
+};
 
 use super::ARGS;
 
 use crate::db::GLOBAL_SETTINGS;
 use crate::utils::{
-    config::Mail, errors::ProcessError, round_to_nearest_ten, time_machine::time_now,
+    config::Mail,
+    error_report,
+    errors::ProcessError,
+    event_history::{self, EventKind},
+    mask_env,
+    notifier::send_notification,
+    round_to_nearest_ten,
+    time_machine::time_now,
 };
 
 const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f%:z";
+const JSON_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6f%:z";
+
+/// Live log lines from all channels, for the log stream endpoint.
+pub static LOG_STREAM: LazyLock<broadcast::Sender<LogLine>> =
+    LazyLock::new(|| broadcast::channel(1000).0);
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LogLine {
+    #[serde(skip)]
+    pub channel: i32,
+    pub timestamp: String,
+    pub level: Level,
+    pub message: String,
+}
+
+/// Send channel log lines to the live stream, when someone is listening.
+/// Errors are also saved in the event history.
+fn publish(now: &mut DeferredNow, record: &Record) {
+    let channel = record_channel(record);
+
+    if channel > 0 && record.level() == Level::Error {
+        event_history::push(
+            channel,
+            EventKind::Error,
+            strip_tags(&record.args().to_string()),
+        );
+    }
+
+    if LOG_STREAM.receiver_count() == 0 {
+        return;
+    }
+
+    if channel > 0 {
+        let _ = LOG_STREAM.send(LogLine {
+            channel,
+            timestamp: now.now().format(TIME_FORMAT).to_string(),
+            level: record.level(),
+            message: record.args().to_string(),
+        });
+    }
+}
+
+fn record_channel(record: &Record) -> i32 {
+    i32::try_from(
+        record
+            .key_values()
+            .get("channel".into())
+            .and_then(|v| Value::to_i64(&v))
+            .unwrap_or(0),
+    )
+    .unwrap_or(0)
+}
 
 #[derive(Debug)]
 pub struct Target;
 
 impl Target {
     pub fn all() -> &'static str {
-        if ARGS.log_to_console {
-            "{_Default}"
-        } else {
-            "{file,mail,_Default}"
+        match (ARGS.log_to_console, ARGS.syslog.is_some()) {
+            (true, false) => "{_Default}",
+            (true, true) => "{syslog,_Default}",
+            (false, false) => "{file,mail,_Default}",
+            (false, true) => "{file,mail,syslog,_Default}",
         }
     }
 
@@ -47,7 +120,11 @@ impl Target {
     }
 
     pub fn file() -> &'static str {
-        "{file}"
+        if ARGS.syslog.is_some() {
+            "{file,syslog}"
+        } else {
+            "{file}"
+        }
     }
 
     pub fn mail() -> &'static str {
@@ -55,7 +132,11 @@ impl Target {
     }
 
     pub fn file_mail() -> &'static str {
-        "{file,mail}"
+        if ARGS.syslog.is_some() {
+            "{file,mail,syslog}"
+        } else {
+            "{file,mail}"
+        }
     }
 }
 
@@ -63,7 +144,8 @@ pub struct LogConsole;
 
 impl LogWriter for LogConsole {
     fn write(&self, now: &mut DeferredNow, record: &Record<'_>) -> std::io::Result<()> {
-        console_formatter(&mut std::io::stderr(), now, record)?;
+        publish(now, record);
+        console_format()(&mut std::io::stderr(), now, record)?;
 
         println!();
         Ok(())
@@ -73,6 +155,25 @@ impl LogWriter for LogConsole {
     }
 }
 
+/// Rotate every day, and with a size limit also when the file gets too big.
+fn rotate_criterion() -> Criterion {
+    match ARGS.log_max_size {
+        Some(size) if size > 0 => Criterion::AgeOrSize(Age::Day, size * 1024 * 1024),
+        _ => Criterion::Age(Age::Day),
+    }
+}
+
+/// Keep the youngest rotated file readable, when the older ones are compressed.
+fn rotate_cleanup() -> Cleanup {
+    let count = ARGS.log_backup_count.unwrap_or(14);
+
+    if ARGS.log_compress {
+        Cleanup::KeepLogAndCompressedFiles(1, count.saturating_sub(1))
+    } else {
+        Cleanup::KeepLogFiles(count)
+    }
+}
+
 pub struct MultiFileLogger {
     log_path: PathBuf,
     writers: RwLock<HashMap<i32, Arc<FileLogWriter>>>,
@@ -99,15 +200,19 @@ impl MultiFileLogger {
                     .basename("ffplayout")
                     .discriminant(channel.to_string()),
             )
-            .format(file_formatter)
+            .format(if json_format() {
+                json_formatter
+            } else {
+                file_formatter
+            })
             .append()
             .rotate(
-                Criterion::Age(Age::Day),
+                rotate_criterion(),
                 Naming::TimestampsCustomFormat {
                     current_infix: Some(""),
                     format: "%Y-%m-%d",
                 },
-                Cleanup::KeepLogFiles(ARGS.log_backup_count.unwrap_or(14)),
+                rotate_cleanup(),
             )
             .try_build()
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
@@ -123,14 +228,9 @@ impl MultiFileLogger {
 
 impl LogWriter for MultiFileLogger {
     fn write(&self, now: &mut DeferredNow, record: &Record) -> io::Result<()> {
-        let channel = i32::try_from(
-            record
-                .key_values()
-                .get("channel".into())
-                .and_then(|v| Value::to_i64(&v))
-                .unwrap_or(0),
-        )
-        .unwrap_or(0);
+        let channel = record_channel(record);
+
+        publish(now, record);
 
         let writer = self.get_writer(channel)?;
         writer.write(now, record)
@@ -143,6 +243,15 @@ impl LogWriter for MultiFileLogger {
         }
         Ok(())
     }
+
+    /// Open the log files again, after logrotate moved them.
+    fn reopen_output(&self) -> Result<(), flexi_logger::FlexiLoggerError> {
+        let writers = self.writers.read().unwrap();
+        for writer in writers.values() {
+            writer.reopen_outputfile()?;
+        }
+        Ok(())
+    }
 }
 
 pub struct LogMailer {
@@ -157,19 +266,18 @@ impl LogMailer {
 
 impl LogWriter for LogMailer {
     fn write(&self, now: &mut DeferredNow, record: &Record<'_>) -> std::io::Result<()> {
-        let id = i32::try_from(
-            record
-                .key_values()
-                .get("channel".into())
-                .and_then(|v| Value::to_i64(&v))
-                .unwrap_or(0),
-        )
-        .unwrap_or(0);
+        let id = record_channel(record);
 
-        let message = record.args().to_string();
+        let message = strip_tags(&record.args().to_string());
         let level = record.level();
+        let class = error_class(record.module_path().unwrap_or_default(), &message);
         let mail_queues = self.mail_queues.clone();
-        let now = now.now().format("%Y-%m-%d %H:%M:%S");
+        let time = now.now().timestamp();
+        let now = now.now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+        if level == Level::Error && class == "ffmpeg" {
+            error_report::ffmpeg_failure(id, &message, time);
+        }
 
         tokio::spawn(async move {
             let mut queues = mail_queues.lock().await;
@@ -177,20 +285,13 @@ impl LogWriter for LogMailer {
             for queue in queues.iter_mut() {
                 let mut q_lock = queue.lock().await;
 
-                let msg = strip_tags(&message);
-
-                if q_lock.id == id && q_lock.level_eq(level) && !q_lock.raw_lines.contains(&msg) {
-                    q_lock.push_raw(msg.clone());
-                    q_lock.push(format!("[{now}] [{:>5}] {}", level, msg));
+                if q_lock.id == id {
+                    if q_lock.class_level_eq(class, level) {
+                        q_lock.push(level, message, &now, time);
+                    }
 
                     break;
                 }
-
-                if q_lock.raw_lines.len() > 1000 {
-                    let last = q_lock.raw_lines.pop().unwrap();
-                    q_lock.clear_raw();
-                    q_lock.push_raw(last);
-                }
             }
         });
 
@@ -201,12 +302,308 @@ impl LogWriter for LogMailer {
     }
 }
 
+/// Timeout for connecting and writing to a syslog server over TCP.
+const SYSLOG_TIMEOUT: Duration = Duration::from_secs(2);
+const SYSLOG_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+enum SyslogAddress {
+    Udp(String),
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl SyslogAddress {
+    fn parse(address: &str) -> io::Result<Self> {
+        match address.split_once("://") {
+            Some(("udp", addr)) => Ok(Self::Udp(addr.to_string())),
+            Some(("tcp", addr)) => Ok(Self::Tcp(addr.to_string())),
+            #[cfg(unix)]
+            Some(("unix", path)) => Ok(Self::Unix(PathBuf::from(path))),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported syslog address: {address}"),
+            )),
+        }
+    }
+
+    fn connect(&self) -> io::Result<SyslogSocket> {
+        match self {
+            Self::Udp(addr) => {
+                let server = addr.to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(ErrorKind::NotFound, format!("Can't resolve {addr}"))
+                })?;
+                let socket = if server.is_ipv6() {
+                    UdpSocket::bind("[::]:0")?
+                } else {
+                    UdpSocket::bind("0.0.0.0:0")?
+                };
+                socket.connect(server)?;
+
+                Ok(SyslogSocket::Udp(socket))
+            }
+            Self::Tcp(addr) => {
+                let mut error =
+                    io::Error::new(ErrorKind::NotFound, format!("Can't resolve {addr}"));
+
+                for server in addr.to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&server, SYSLOG_TIMEOUT) {
+                        Ok(stream) => {
+                            stream.set_write_timeout(Some(SYSLOG_TIMEOUT))?;
+
+                            return Ok(SyslogSocket::Tcp(stream));
+                        }
+                        Err(e) => error = e,
+                    }
+                }
+
+                Err(error)
+            }
+            #[cfg(unix)]
+            Self::Unix(path) => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(path)?;
+
+                Ok(SyslogSocket::Unix(socket))
+            }
+        }
+    }
+}
+
+enum SyslogSocket {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+impl SyslogSocket {
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Self::Udp(socket) => socket.send(line.as_bytes()).map(|_| ()),
+            // octet counting from RFC 6587, so messages can contain line breaks
+            Self::Tcp(stream) => stream.write_all(format!("{} {line}", line.len()).as_bytes()),
+            #[cfg(unix)]
+            Self::Unix(socket) => socket.send(line.as_bytes()).map(|_| ()),
+        }
+    }
+}
+
+/// Map names like `daemon` or `local3` to the syslog facility number.
+pub fn syslog_facility(name: &str) -> Option<u8> {
+    let facility = match name.to_lowercase().as_str() {
+        "kern" => 0,
+        "user" => 1,
+        "mail" => 2,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "lpr" => 6,
+        "news" => 7,
+        "uucp" => 8,
+        "cron" => 9,
+        "authpriv" => 10,
+        "ftp" => 11,
+        local => local
+            .strip_prefix("local")
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| *n < 8)
+            .map(|n| n + 16)?,
+    };
+
+    Some(facility)
+}
+
+/// Map log levels to the syslog severity.
+pub fn syslog_severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+#[derive(Default)]
+struct SyslogState {
+    socket: Option<SyslogSocket>,
+    /// No new connection before this time, after a failed one.
+    retry: Option<Instant>,
+    backoff: Duration,
+}
+
+impl SyslogState {
+    fn failed(&mut self) {
+        self.socket = None;
+        self.backoff = (self.backoff * 2).clamp(Duration::from_secs(1), SYSLOG_MAX_BACKOFF);
+        self.retry = Some(Instant::now() + self.backoff);
+    }
+}
+
+/// Send log messages in RFC 5424 format to a syslog server.
+///
+/// The connection is opened with the first message and reopened after a failed send. While the
+/// server is not reachable, a new connection is tried after a growing delay, up to one minute,
+/// messages in between are dropped.
+pub struct LogSyslog {
+    address: SyslogAddress,
+    facility: u8,
+    hostname: String,
+    state: StdMutex<SyslogState>,
+}
+
+impl LogSyslog {
+    pub fn new(address: &str, facility: &str) -> io::Result<Self> {
+        let facility = syslog_facility(facility).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown syslog facility: {facility}"),
+            )
+        })?;
+        let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .or_else(|| env::var("HOSTNAME").ok())
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty())
+            .unwrap_or("-".to_string());
+
+        Ok(Self {
+            address: SyslogAddress::parse(address)?,
+            facility,
+            hostname,
+            state: StdMutex::new(SyslogState::default()),
+        })
+    }
+
+    fn format(&self, now: &mut DeferredNow, record: &Record) -> io::Result<String> {
+        let data = match record
+            .key_values()
+            .get("channel".into())
+            .and_then(|v| Value::to_i64(&v))
+        {
+            Some(channel) => format!("[ffplayout@32473 channel=\"{channel}\"]"),
+            None => "-".to_string(),
+        };
+        let mut message = vec![];
+
+        if json_format() {
+            json_formatter(&mut message, now, record)?;
+        } else {
+            write!(message, "{}", strip_tags(&record.args().to_string()))?;
+        }
+
+        Ok(format!(
+            "<{}>1 {} {} ffplayout {} - {data} {}",
+            (self.facility << 3) | syslog_severity(record.level()),
+            now.format_rfc3339(),
+            self.hostname,
+            process::id(),
+            String::from_utf8_lossy(&message)
+        ))
+    }
+}
+
+impl LogWriter for LogSyslog {
+    fn write(&self, now: &mut DeferredNow, record: &Record<'_>) -> io::Result<()> {
+        let line = self.format(now, record)?;
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| io::Error::new(ErrorKind::Other, e.to_string()))?;
+
+        if state.socket.is_none() {
+            // don't block every log call with a new connection to an unreachable server
+            if state.retry.is_some_and(|retry| Instant::now() < retry) {
+                return Ok(());
+            }
+
+            match self.address.connect() {
+                Ok(socket) => state.socket = Some(socket),
+                Err(e) => {
+                    state.failed();
+                    return Err(e);
+                }
+            }
+        }
+
+        let result = match state.socket.as_mut() {
+            Some(s) => s.send(&line),
+            None => Ok(()),
+        };
+
+        if result.is_ok() {
+            state.retry = None;
+            state.backoff = Duration::ZERO;
+        } else {
+            state.failed();
+        }
+
+        result
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Error class from the module and the message, for level filters per class.
+pub fn error_class(module: &str, message: &str) -> &'static str {
+    let module = module.trim_start_matches("ffplayout::");
+
+    if ["[Decoder]", "[Encoder]", "[Ingest]"]
+        .iter()
+        .any(|unit| message.starts_with(unit))
+    {
+        "ffmpeg"
+    } else if module.starts_with("player::input::playlist") || module.contains("json_") {
+        "playlist"
+    } else if module.contains("folder") {
+        "folder"
+    } else if module.starts_with("player::input::ingest") {
+        "ingest"
+    } else if module.starts_with("player::output") {
+        "output"
+    } else if module.starts_with("player::filter") {
+        "filter"
+    } else {
+        "system"
+    }
+}
+
+/// Message in the mail queue, identical messages are counted instead of repeated.
+#[derive(Clone, Debug)]
+pub struct QueueLine {
+    pub level: Level,
+    pub message: String,
+    pub first: String,
+    pub last: String,
+    pub count: usize,
+}
+
+impl QueueLine {
+    fn text(&self) -> String {
+        if self.count > 1 {
+            format!(
+                "[{}] [{:>5}] {} (repeated {} times, last at {})",
+                self.first, self.level, self.message, self.count, self.last
+            )
+        } else {
+            format!("[{}] [{:>5}] {}", self.first, self.level, self.message)
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MailQueue {
     pub id: i32,
     pub config: Mail,
-    pub lines: Vec<String>,
-    pub raw_lines: Vec<String>,
+    pub lines: Vec<QueueLine>,
+    /// Sent messages with the timestamp, for the deduplication window.
+    pub sent: HashMap<String, i64>,
+    /// Messages which are skipped since the last mail.
+    pub suppressed: usize,
+    /// Timestamp from the last mail, for the digest.
+    pub last_sent: i64,
 }
 
 impl MailQueue {
@@ -215,12 +612,25 @@ impl MailQueue {
             id,
             config,
             lines: vec![],
-            raw_lines: vec![],
+            sent: HashMap::new(),
+            suppressed: 0,
+            last_sent: 0,
         }
     }
 
+    /// Level is wanted from the mail or from one of the notifiers.
     pub fn level_eq(&self, level: Level) -> bool {
-        level <= self.config.mail_level
+        level <= self.config.mail_level || self.config.notifiers.iter().any(|n| level <= n.level)
+    }
+
+    /// Level filter from the error class, on top of the mail and notifier levels.
+    pub fn class_level_eq(&self, class: &str, level: Level) -> bool {
+        self.level_eq(level)
+            && self
+                .config
+                .class_levels
+                .get(class)
+                .is_none_or(|class_level| level <= *class_level)
     }
 
     pub fn update(&mut self, config: Mail) {
@@ -229,30 +639,85 @@ impl MailQueue {
 
     pub fn clear(&mut self) {
         self.lines.clear();
+        self.suppressed = 0;
     }
 
-    pub fn clear_raw(&mut self) {
-        self.raw_lines.clear();
+    pub fn clear_sent(&mut self) {
+        self.sent.clear();
     }
 
-    pub fn push(&mut self, line: String) {
-        self.lines.push(line);
+    /// Add message, repeated messages only increase the counter
+    /// and messages from the deduplication window are skipped.
+    pub fn push(&mut self, level: Level, message: String, now: &str, time: i64) {
+        if let Some(line) = self.lines.iter_mut().find(|l| l.message == message) {
+            line.count += 1;
+            now.clone_into(&mut line.last);
+            return;
+        }
+
+        if self
+            .sent
+            .get(&message)
+            .is_some_and(|sent| time - sent < self.config.dedup_window)
+        {
+            self.suppressed += 1;
+            return;
+        }
+
+        if self.lines.len() >= 1000 {
+            self.suppressed += 1;
+            return;
+        }
+
+        self.lines.push(QueueLine {
+            level,
+            message,
+            first: now.to_string(),
+            last: now.to_string(),
+            count: 1,
+        });
     }
 
-    pub fn push_raw(&mut self, line: String) {
-        self.raw_lines.push(line);
+    /// Digest waits after the last mail, messages are collected in the meantime.
+    pub fn is_due(&self, time: i64) -> bool {
+        !self.lines.is_empty() && time - self.last_sent >= self.config.digest
     }
 
-    fn text(&self) -> String {
-        self.lines.join("\n")
+    /// Remember sent messages and forget the ones outside of the deduplication window.
+    pub fn mark_sent(&mut self, time: i64) {
+        let window = self.config.dedup_window;
+
+        self.last_sent = time;
+        self.sent.retain(|_, sent| time - *sent < window);
+
+        if window > 0 {
+            for line in &self.lines {
+                self.sent.insert(line.message.clone(), time);
+            }
+        }
     }
 
-    fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+    /// Lines with the given level, or a more important one.
+    pub fn text(&self, max_level: Level) -> String {
+        let mut text = self
+            .lines
+            .iter()
+            .filter(|line| line.level <= max_level)
+            .map(QueueLine::text)
+            .collect::<Vec<_>>();
+
+        if !text.is_empty() && self.suppressed > 0 {
+            text.push(format!(
+                "\n{} repeated messages were skipped",
+                self.suppressed
+            ));
+        }
+
+        text.join("\n")
     }
 }
 
-fn strip_tags(input: &str) -> String {
+pub fn strip_tags(input: &str) -> String {
     let re = Regex::new(r"<[^>]*>").unwrap();
     re.replace_all(input, "").to_string()
 }
@@ -303,6 +768,61 @@ fn file_formatter(
     )
 }
 
+/// Collect the key-value pairs from a log record, like the channel id.
+struct JsonFields(Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.insert(
+            key.to_string(),
+            serde_json::to_value(value).unwrap_or_default(),
+        );
+
+        Ok(())
+    }
+}
+
+fn json_format() -> bool {
+    ARGS.log_format
+        .as_deref()
+        .is_some_and(|f| f.eq_ignore_ascii_case("json"))
+}
+
+/// Newline-delimited JSON records, for log collectors like Loki or Elasticsearch.
+fn json_formatter(w: &mut dyn Write, now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+    let mut fields = JsonFields(Map::new());
+    record
+        .key_values()
+        .visit(&mut fields)
+        .map_err(|e| io::Error::new(ErrorKind::Other, e.to_string()))?;
+
+    let channel = fields.0.remove("channel");
+    let time = if ARGS.fake_time.is_some() {
+        time_now(&None).format(JSON_TIME_FORMAT)
+    } else {
+        now.now().format(JSON_TIME_FORMAT)
+    };
+
+    let line = json!({
+        "timestamp": time.to_string(),
+        "level": record.level().as_str(),
+        "channel": channel,
+        "target": record.module_path().unwrap_or_default(),
+        "message": strip_tags(&record.args().to_string()),
+        "fields": fields.0,
+    });
+
+    write!(w, "{line}")
+}
+
+fn console_format() -> flexi_logger::FormatFunction {
+    if json_format() {
+        json_formatter
+    } else {
+        console_formatter
+    }
+}
+
 pub fn log_file_path() -> PathBuf {
     let config = GLOBAL_SETTINGS.get().unwrap();
     let mut log_path = PathBuf::from(&ARGS.logs.as_ref().unwrap_or(&config.logs));
@@ -319,7 +839,7 @@ pub fn log_file_path() -> PathBuf {
 }
 
 fn file_logger() -> Box<dyn LogWriter> {
-    if ARGS.log_to_console {
+    if ARGS.log_to_console && !ARGS.tui {
         Box::new(LogConsole)
     } else {
         Box::new(MultiFileLogger::new(log_file_path()))
@@ -364,6 +884,7 @@ pub async fn send_mail(config: &Mail, msg: String) -> Result<(), ProcessError> {
 /// Basic Mail Queue
 ///
 /// Check every give seconds for messages and send them.
+/// With a digest time, further mails wait until the digest time is over after the last mail.
 pub fn mail_queue(mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>) {
     tokio::spawn(async move {
         let sec = 10;
@@ -373,6 +894,7 @@ pub fn mail_queue(mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>) {
         loop {
             interval.tick().await;
             let mut tasks = vec![];
+            let mut notifications = vec![];
 
             // Reset the counter after one day
             if counter >= 86400 {
@@ -383,6 +905,7 @@ pub fn mail_queue(mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>) {
 
             {
                 let mut queues = mail_queues.lock().await;
+                let time = Local::now().timestamp();
 
                 // Process mail queues and send emails
                 for queue in queues.iter_mut() {
@@ -391,12 +914,28 @@ pub fn mail_queue(mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>) {
 
                     let expire = round_to_nearest_ten(q_lock.config.interval.max(30));
 
-                    if interval % expire == 0 && !q_lock.is_empty() {
-                        if q_lock.config.recipient.contains('@') {
-                            tasks.push((q_lock.config.clone(), q_lock.text().clone(), q_lock.id));
+                    if interval % expire == 0 && q_lock.is_due(time) {
+                        let text = q_lock.text(q_lock.config.mail_level);
+
+                        if q_lock.config.recipient.contains('@') && !text.is_empty() {
+                            tasks.push((q_lock.config.clone(), text, q_lock.id));
+                        }
+
+                        for notifier in &q_lock.config.notifiers {
+                            let text = q_lock.text(notifier.level);
+
+                            if !text.is_empty() {
+                                notifications.push((
+                                    notifier.clone(),
+                                    q_lock.config.subject.clone(),
+                                    text,
+                                    q_lock.id,
+                                ));
+                            }
                         }
 
                         // Clear the messages after sending the email
+                        q_lock.mark_sent(time);
                         q_lock.clear();
                     }
                 }
@@ -407,6 +946,12 @@ pub fn mail_queue(mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>) {
                     error!(target: "{file}", channel = id; "Failed to send mail: {e}");
                 }
             }
+
+            for (notifier, subject, text, id) in notifications {
+                if let Err(e) = send_notification(&notifier, &subject, &text).await {
+                    error!(target: "{file}", channel = id; "Failed to send notification: {e}");
+                }
+            }
         }
     });
 }
@@ -415,7 +960,8 @@ pub fn mail_queue(mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>) {
 ///
 /// - console logger
 /// - file logger
-/// - mail logger
+/// - mail and chat notifications
+/// - syslog logger, when an address is set
 pub fn init_logging(
     mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
 ) -> io::Result<flexi_logger::LoggerHandle> {
@@ -452,12 +998,28 @@ pub fn init_logging(
         .module("sqlx", LevelFilter::Error)
         .module("tokio", LevelFilter::Error);
 
-    let logger = Logger::with(builder.build())
+    let mut logger = Logger::with(builder.build())
         .write_mode(WriteMode::Async)
-        .format(console_formatter)
-        .log_to_stderr()
+        .format(console_format());
+
+    // the dashboard shows the log lines itself
+    logger = if ARGS.tui {
+        logger.do_not_log()
+    } else {
+        logger.log_to_stderr()
+    };
+
+    logger = logger
         .add_writer("file", file_logger())
-        .add_writer("mail", Box::new(LogMailer::new(mail_queues)))
+        .add_writer("mail", Box::new(LogMailer::new(mail_queues)));
+
+    if let Some(address) = &ARGS.syslog {
+        let facility = ARGS.syslog_facility.as_deref().unwrap_or("daemon");
+
+        logger = logger.add_writer("syslog", Box::new(LogSyslog::new(address, facility)?));
+    }
+
+    let logger = logger
         .start()
         .map_err(|e| io::Error::new(ErrorKind::Other, e.to_string()))?;
 
@@ -486,6 +1048,7 @@ pub fn log_line(id: i32, line: &str, level: &str) {
     }
 }
 
+/// Command for the log, values from environment variables are masked.
 pub fn fmt_cmd(cmd: &[String]) -> String {
     let mut formatted_cmd = Vec::new();
     let mut quote_next = false;
@@ -519,5 +1082,5 @@ pub fn fmt_cmd(cmd: &[String]) -> String {
         }
     }
 
-    formatted_cmd.join(" ")
+    mask_env(&formatted_cmd.join(" "))
 }
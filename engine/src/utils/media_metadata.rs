@@ -0,0 +1,290 @@
+/// Media Metadata
+///
+/// Import titles, categories and custom fields for existing media files from a CSV file, like
+/// the export from a media asset management. Rows are matched to the files by the `filename`
+/// column, or by the `house_id` column, which matches a file with this ID from an earlier import
+/// or a file with the ID as name. All other columns are stored as custom fields.
+///
+/// The program API and the playout take title and category from here, when the playlist has
+/// none, so the EPG and the overlays get real titles.
+use std::{collections::HashMap, path::Path};
+
+use async_walkdir::WalkDir;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+use tokio_stream::StreamExt;
+
+use crate::db::{handles, models::MediaEntry};
+use crate::player::utils::{include_file_extension, Media};
+use crate::utils::{config::PlayoutConfig, errors::ServiceError};
+
+/// Columns with their own field, all other columns are custom fields.
+const COLUMNS: [&str; 5] = ["filename", "path", "house_id", "title", "category"];
+
+/// Result from a metadata import.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct MetadataImport {
+    pub rows: usize,
+    pub imported: usize,
+    /// Line number and reason, from rows without a matching file.
+    pub skipped: Vec<(usize, String)>,
+}
+
+impl MetadataImport {
+    /// Report lines, for the terminal.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} rows, {} imported, {} skipped",
+            self.rows,
+            self.imported,
+            self.skipped.len()
+        )];
+
+        for (line, reason) in &self.skipped {
+            lines.push(format!("  line {line}: {reason}"));
+        }
+
+        lines
+    }
+}
+
+/// Parse CSV text, with quoted fields. The delimiter is `;` when the header has no `,`.
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let text = text.trim_start_matches('\u{feff}');
+    let header = text.lines().next().unwrap_or_default();
+    let delimiter = if header.contains(';') && !header.contains(',') {
+        ';'
+    } else {
+        ','
+    };
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c if c == delimiter && !quoted => row.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+fn unique(matches: Vec<&String>, key: &str) -> Result<String, String> {
+    match matches.as_slice() {
+        [path] => Ok(path.to_string()),
+        [] => Err(format!("no file for \"{key}\"")),
+        _ => Err(format!("{} files for \"{key}\"", matches.len())),
+    }
+}
+
+/// Path relative to the storage, for the filename or the house ID from a row.
+///
+/// `files` are all files from the storage, `house_ids` maps the known IDs to their files.
+pub fn find_file(
+    files: &[String],
+    house_ids: &HashMap<String, String>,
+    filename: &str,
+    house_id: &str,
+) -> Result<String, String> {
+    if !filename.is_empty() {
+        let name = filename.trim_start_matches("./");
+
+        if files.iter().any(|f| f == name) {
+            return Ok(name.to_string());
+        }
+
+        return unique(
+            files
+                .iter()
+                .filter(|f| Path::new(f).ends_with(name))
+                .collect(),
+            filename,
+        );
+    }
+
+    if house_id.is_empty() {
+        return Err("no filename or house ID".to_string());
+    }
+
+    if let Some(path) = house_ids.get(house_id) {
+        return Ok(path.clone());
+    }
+
+    unique(
+        files
+            .iter()
+            .filter(|f| Path::new(f).file_stem().is_some_and(|s| s == house_id))
+            .collect(),
+        house_id,
+    )
+}
+
+async fn storage_files(config: &PlayoutConfig) -> Result<Vec<String>, ServiceError> {
+    let storage = &config.channel.storage;
+
+    if !storage.is_dir() {
+        return Err(ServiceError::Conflict(format!(
+            "Storage {storage:?} not exists"
+        )));
+    }
+
+    let mut files = vec![];
+    let mut entries = WalkDir::new(storage);
+
+    while let Some(entry) = entries.next().await {
+        let path = entry.map_err(|e| e.to_string())?.path();
+
+        if path.is_file() && include_file_extension(config, &path) {
+            files.push(
+                path.strip_prefix(storage)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+    }
+
+    Ok(files)
+}
+
+/// Import the metadata from CSV text into the channel. Empty cells keep the stored values.
+pub async fn import_metadata(
+    pool: &Pool<Sqlite>,
+    config: &PlayoutConfig,
+    text: &str,
+) -> Result<MetadataImport, ServiceError> {
+    let mut rows = parse_csv(text).into_iter();
+    let header: Vec<String> = rows
+        .next()
+        .unwrap_or_default()
+        .iter()
+        .map(|h| h.trim().to_lowercase().replace([' ', '-'], "_"))
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let file_column = column("filename").or_else(|| column("path"));
+    let id_column = column("house_id");
+
+    if file_column.is_none() && id_column.is_none() {
+        return Err(ServiceError::BadRequest(
+            "CSV needs a filename or house_id column".to_string(),
+        ));
+    }
+
+    let id = config.general.channel_id;
+    let files = storage_files(config).await?;
+    let house_ids: HashMap<String, String> = handles::select_house_ids(pool, id)
+        .await?
+        .into_iter()
+        .collect();
+    let mut report = MetadataImport::default();
+
+    for (i, row) in rows.enumerate() {
+        if row.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+
+        let value = |index: Option<usize>| {
+            index
+                .and_then(|c| row.get(c))
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+        let house_id = value(id_column);
+        let title = value(column("title"));
+        let category = value(column("category"));
+
+        report.rows += 1;
+
+        let path = match find_file(&files, &house_ids, &value(file_column), &house_id) {
+            Ok(path) => path,
+            Err(reason) => {
+                // header is line 1
+                report.skipped.push((i + 2, reason));
+                continue;
+            }
+        };
+
+        let mut entry = handles::select_media(pool, id, &path)
+            .await?
+            .unwrap_or(MediaEntry {
+                path,
+                ..Default::default()
+            });
+
+        if !house_id.is_empty() {
+            entry.house_id = house_id;
+        }
+
+        if !title.is_empty() {
+            entry.title = title;
+        }
+
+        if !category.is_empty() {
+            entry.category = category;
+        }
+
+        for (c, name) in header.iter().enumerate() {
+            if name.is_empty() || COLUMNS.contains(&name.as_str()) {
+                continue;
+            }
+
+            if let Some(v) = row.get(c).map(|v| v.trim()).filter(|v| !v.is_empty()) {
+                entry.metadata.insert(name.clone(), v.to_string());
+            }
+        }
+
+        handles::insert_or_update_media_metadata(pool, id, &entry).await?;
+        report.imported += 1;
+    }
+
+    Ok(report)
+}
+
+/// Title and category from the metadata, when the clip has none.
+pub async fn apply_metadata(pool: &Pool<Sqlite>, config: &PlayoutConfig, node: &mut Media) {
+    let has_title = node.title.as_ref().is_some_and(|t| !t.is_empty());
+
+    if (has_title && !node.category.is_empty()) || node.source.is_empty() {
+        return;
+    }
+
+    let source = Path::new(&node.source);
+    let path = source
+        .strip_prefix(&config.channel.storage)
+        .unwrap_or(source);
+
+    if path.is_absolute() {
+        return;
+    }
+
+    if let Ok(Some(entry)) =
+        handles::select_media(pool, config.general.channel_id, &path.to_string_lossy()).await
+    {
+        if !has_title && !entry.title.is_empty() {
+            node.title = Some(entry.title);
+        }
+
+        if node.category.is_empty() && !entry.category.is_empty() {
+            node.category = entry.category;
+        }
+    }
+}
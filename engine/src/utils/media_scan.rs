@@ -0,0 +1,237 @@
+/// Media Scan
+///
+/// `--scan-media` walks the storage from the channels and probes all media files. The results
+/// are stored in the database, files which are unchanged since the last scan are not probed
+/// again, so it is cheap enough to run it from cron. At the end unreadable files and files which
+/// don't fit the channel are listed.
+use std::{collections::HashMap, path::Path, time::UNIX_EPOCH};
+
+use async_walkdir::WalkDir;
+use sqlx::{Pool, Sqlite};
+use tokio::fs;
+use tokio_stream::StreamExt;
+
+use crate::db::{handles, models::MediaScan};
+use crate::player::utils::{file_extension, include_file_extension, probe::MediaProbe};
+use crate::utils::{
+    config::{get_config, PlayoutConfig, IMAGE_FORMAT},
+    errors::ServiceError,
+};
+use crate::ARGS;
+
+/// Result from `--scan-media` for one channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanReport {
+    pub channel: i32,
+    pub name: String,
+    pub files: usize,
+    /// New or changed files.
+    pub probed: usize,
+    /// Entries from deleted files.
+    pub removed: usize,
+    pub unreadable: Vec<(String, String)>,
+    pub nonconforming: Vec<(String, String)>,
+}
+
+impl ScanReport {
+    /// Report lines, for the terminal.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Channel {} ({}): {} files, {} probed, {} removed, {} unreadable, {} not conforming",
+            self.channel,
+            self.name,
+            self.files,
+            self.probed,
+            self.removed,
+            self.unreadable.len(),
+            self.nonconforming.len()
+        )];
+
+        for (path, error) in &self.unreadable {
+            lines.push(format!("  unreadable: {path}: {error}"));
+        }
+
+        for (path, issues) in &self.nonconforming {
+            lines.push(format!("  not conforming: {path}: {issues}"));
+        }
+
+        lines
+    }
+}
+
+/// Problems from the probe, which the channel can't play like expected.
+pub fn conformance(probe: &MediaProbe, config: &PlayoutConfig, image: bool) -> Vec<String> {
+    let mut issues = vec![];
+
+    if config.processing.audio_only {
+        if probe.audio.is_empty() {
+            issues.push("no audio stream".to_string());
+        }
+    } else if probe.video.is_empty() {
+        issues.push("no video stream".to_string());
+    }
+
+    if !image && probe.format.duration.unwrap_or_default() <= 0.0 {
+        issues.push("no duration".to_string());
+    }
+
+    issues
+}
+
+async fn probe_file(path: &Path, entry: &mut MediaScan) {
+    match MediaProbe::new(path).await {
+        Ok(probe) => {
+            entry.duration = probe.format.duration.unwrap_or_default();
+            entry.probe = serde_json::to_string(&probe).unwrap_or_default();
+            entry.error.clear();
+        }
+        Err(e) => {
+            entry.duration = 0.0;
+            entry.probe.clear();
+            entry.error = e.to_string();
+        }
+    }
+}
+
+pub async fn scan_channel(
+    pool: &Pool<Sqlite>,
+    config: &PlayoutConfig,
+    name: String,
+) -> Result<ScanReport, ServiceError> {
+    let id = config.general.channel_id;
+    let storage = &config.channel.storage;
+
+    if !storage.is_dir() {
+        return Err(ServiceError::Conflict(format!(
+            "Storage {storage:?} not exists"
+        )));
+    }
+
+    let mut cached: HashMap<String, MediaScan> = handles::select_media_scans(pool, id)
+        .await?
+        .into_iter()
+        .map(|s| (s.path.clone(), s))
+        .collect();
+    let mut report = ScanReport {
+        channel: id,
+        name,
+        ..Default::default()
+    };
+    let mut entries = WalkDir::new(storage);
+
+    while let Some(entry) = entries.next().await {
+        let path = entry.map_err(|e| e.to_string())?.path();
+
+        if !path.is_file() || !include_file_extension(config, &path) {
+            continue;
+        }
+
+        let source = path
+            .strip_prefix(storage)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        let metadata = fs::metadata(&path).await?;
+        let size = metadata.len() as i64;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+
+        report.files += 1;
+
+        let entry = match cached.remove(&source) {
+            Some(entry) if entry.size == size && entry.modified == modified => entry,
+            _ => {
+                let mut entry = MediaScan {
+                    path: source.clone(),
+                    size,
+                    modified,
+                    ..Default::default()
+                };
+
+                probe_file(&path, &mut entry).await;
+                handles::insert_or_update_media_scan(pool, id, &entry).await?;
+                report.probed += 1;
+
+                entry
+            }
+        };
+
+        if !entry.error.is_empty() {
+            report.unreadable.push((source, entry.error));
+            continue;
+        }
+
+        let image = file_extension(&path)
+            .is_some_and(|ext| IMAGE_FORMAT.contains(&ext.to_lowercase().as_str()));
+        let issues = serde_json::from_str::<MediaProbe>(&entry.probe)
+            .map(|probe| conformance(&probe, config, image))
+            .unwrap_or_else(|_| vec!["no probe result".to_string()]);
+
+        if !issues.is_empty() {
+            report.nonconforming.push((source, issues.join(", ")));
+        }
+    }
+
+    let removed: Vec<String> = cached.into_keys().collect();
+
+    if !removed.is_empty() {
+        handles::delete_media_scans(pool, id, &removed).await?;
+    }
+
+    report.removed = removed.len();
+
+    Ok(report)
+}
+
+/// Scan the storage from all or the selected channels. Returns the exit code, `1` when files are
+/// unreadable or not conforming.
+pub async fn run(pool: &Pool<Sqlite>) -> i32 {
+    let channels = match handles::select_related_channels(pool, None).await {
+        Ok(channels) => channels,
+        Err(e) => {
+            eprintln!("Read channels failed: {e}");
+            return 1;
+        }
+    };
+    let mut code = 0;
+
+    for channel in channels {
+        if ARGS
+            .channel
+            .as_ref()
+            .is_some_and(|c| !c.contains(&channel.id))
+        {
+            continue;
+        }
+
+        let result = match get_config(pool, channel.id).await {
+            Ok(config) => scan_channel(pool, &config, channel.name.clone()).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(report) => {
+                if !report.unreadable.is_empty() || !report.nonconforming.is_empty() {
+                    code = 1;
+                }
+
+                for line in report.lines() {
+                    println!("{line}");
+                }
+            }
+            Err(e) => {
+                println!(
+                    "Channel {} ({}): scan failed: {e}",
+                    channel.id, channel.name
+                );
+                code = 1;
+            }
+        }
+    }
+
+    code
+}
@@ -1,6 +1,8 @@
 use std::{
+    collections::HashSet,
     env, fmt,
     path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
 };
 
 #[cfg(target_family = "unix")]
@@ -19,22 +21,61 @@ use serde::{
 };
 
 pub mod advanced_config;
+pub mod alerts;
+pub mod amcp;
+pub mod archive;
 pub mod args_parse;
+pub mod as_run;
+pub mod backup;
+pub mod benchmark;
 pub mod channels;
+pub mod clock;
 pub mod config;
+pub mod config_check;
+pub mod config_diff;
+pub mod config_schema;
 pub mod control;
+pub mod disk_monitor;
+pub mod doctor;
+pub mod dry_run;
+pub mod error_report;
 pub mod errors;
+pub mod event_history;
 pub mod files;
 pub mod generator;
+pub mod house_format;
+pub mod legacy_config;
+pub mod log_search;
 pub mod logging;
+pub mod media_metadata;
+pub mod media_scan;
+pub mod notifier;
+pub mod osc;
+pub mod output_test;
+pub mod play_history;
 pub mod playlist;
+pub mod plugins;
+pub mod priority;
+pub mod public_server;
+pub mod reload;
+pub mod schedule_check;
+pub mod secrets;
+pub mod share;
 pub mod system;
+pub mod systemd;
+pub mod tally;
 pub mod task_runner;
+pub mod text_layers;
 pub mod time_machine;
+pub mod tui;
+pub mod uptime;
+pub mod users;
 
-use crate::db::GLOBAL_SETTINGS;
+use crate::db::{models::TextPreset, GLOBAL_SETTINGS};
 use crate::player::utils::time_to_sec;
-use crate::utils::{errors::ServiceError, logging::log_file_path};
+use crate::utils::{
+    errors::ServiceError, logging::log_file_path, secrets, text_layers::TextAnimation,
+};
 use crate::ARGS;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -56,6 +97,22 @@ pub struct TextFilter {
     pub boxcolor: Option<String>,
     #[serde(default, deserialize_with = "deserialize_number_or_string")]
     pub boxborderw: Option<String>,
+    /// Layer from `text.layers`, without it the default text.
+    #[serde(default)]
+    pub layer: Option<String>,
+    /// Font file, relative to the storage.
+    #[serde(default)]
+    pub font: Option<String>,
+    #[serde(default)]
+    pub animation_in: Option<TextAnimation>,
+    #[serde(default)]
+    pub animation_out: Option<TextAnimation>,
+    /// Seconds from the animations.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// Seconds until the text goes out.
+    #[serde(default)]
+    pub expire: Option<f64>,
 }
 
 /// Deserialize number or string
@@ -94,6 +151,26 @@ where
     deserializer.deserialize_any(StringOrNumberVisitor)
 }
 
+impl From<TextPreset> for TextFilter {
+    fn from(preset: TextPreset) -> Self {
+        let value = |v: String| Some(v).filter(|v| !v.is_empty());
+
+        Self {
+            text: Some(preset.text),
+            x: value(preset.x),
+            y: value(preset.y),
+            fontsize: value(preset.fontsize),
+            line_spacing: value(preset.line_spacing),
+            fontcolor: value(preset.fontcolor),
+            alpha: value(preset.alpha),
+            r#box: value(preset.r#box),
+            boxcolor: value(preset.boxcolor),
+            boxborderw: value(preset.boxborderw),
+            ..Default::default()
+        }
+    }
+}
+
 impl fmt::Display for TextFilter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let escaped_text = self
@@ -152,6 +229,11 @@ impl fmt::Display for TextFilter {
                 s.push_str(&format!(":boxborderw={v}"));
             }
         }
+        if let Some(v) = &self.font {
+            if !v.is_empty() {
+                s.push_str(&format!(":fontfile='{v}'"));
+            }
+        }
 
         write!(f, "{s}")
     }
@@ -183,23 +265,50 @@ pub fn public_path() -> PathBuf {
 }
 
 pub async fn read_log_file(channel_id: &i32, date: &str) -> Result<String, ServiceError> {
-    let date_str = if date.is_empty() {
-        String::new()
-    } else {
-        format!("_{date}")
-    };
+    // with a size limit one day can have more files, read them in the order they are written
+    let mut parts = vec![];
+    let mut entries = fs::read_dir(log_file_path()).await?;
 
-    let log_path = log_file_path()
-        .join(format!("ffplayout_{channel_id}{date_str}.log"))
-        .clean();
-    let file_size = fs::metadata(&log_path).await?.len() as f64;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if log_search::file_date(&name, *channel_id) == Some(date) {
+            parts.push(entry.path());
+        }
+    }
+
+    if parts.is_empty() {
+        return Err(ServiceError::NoContent(format!(
+            "No log file for channel {channel_id} {date}"
+        )));
+    }
+
+    parts.sort();
+
+    let mut file_size = 0.0;
+
+    for part in &parts {
+        if part.extension().is_some_and(|e| e != "gz") {
+            file_size += fs::metadata(part).await?.len() as f64;
+        }
+    }
 
-    let log_content = if file_size > 5000000.0 {
+    if file_size > 5000000.0 {
         error!("Log file to big: {}", sizeof_fmt(file_size));
-        format!("The log file is larger ({}) than the hard limit of 5MB, the probability is very high that something is wrong with the playout.\nCheck this on the server with `less {log_path:?}`.", sizeof_fmt(file_size))
-    } else {
-        fs::read_to_string(log_path).await?
-    };
+        return Ok(format!("The log file is larger ({}) than the hard limit of 5MB, the probability is very high that something is wrong with the playout.\nCheck this on the server with `less {:?}`.", sizeof_fmt(file_size), parts[parts.len() - 1]));
+    }
+
+    let mut log_content = String::new();
+
+    for part in parts {
+        if part.extension().is_some_and(|e| e == "gz") {
+            log_content.push_str(&format!(
+                "The log file is compressed, check it on the server with `zless {part:?}`.\n"
+            ));
+        } else {
+            log_content.push_str(&fs::read_to_string(part).await?);
+        }
+    }
 
     Ok(log_content)
 }
@@ -275,6 +384,51 @@ pub fn round_to_nearest_ten(num: i64) -> i64 {
     }
 }
 
+static ENV_VAR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+/// Values from variables in parameters, they are masked in the logged commands.
+static EXPANDED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+fn expand(input: &str, secret: bool) -> String {
+    ENV_VAR
+        .replace_all(input, |caps: &regex::Captures| match env::var(&caps[1]) {
+            Ok(value) => {
+                if secret && !value.trim().is_empty() {
+                    EXPANDED.lock().unwrap().insert(value.clone());
+                }
+
+                value
+            }
+            Err(_) => caps[0].to_string(),
+        })
+        .to_string()
+}
+
+/// Replace `${VAR}` with the value of the environment variable `VAR`.
+///
+/// Unknown variables are kept as they are, so they show up in the command log.
+/// The values can be stream keys, so they are masked in the logged commands.
+pub fn expand_env(input: &str) -> String {
+    expand(input, true)
+}
+
+/// Replace `${VAR}` in a path, the values are not masked in logs.
+pub fn expand_path(input: &str) -> PathBuf {
+    PathBuf::from(expand(input, false))
+}
+
+/// Replace the values from expanded variables with a mask.
+pub fn mask_env(input: &str) -> String {
+    EXPANDED
+        .lock()
+        .unwrap()
+        .iter()
+        .fold(input.to_string(), |text, value| {
+            text.replace(value.as_str(), secrets::MASK)
+        })
+}
+
 pub async fn copy_assets(storage_path: &Path) -> Result<(), std::io::Error> {
     if storage_path.is_dir() {
         let target = storage_path.join("00-assets");
@@ -0,0 +1,61 @@
+/// Chat Notifications
+///
+/// Besides mails, log messages can be sent to Telegram, Slack and Discord.
+/// Every channel has its own list of notifiers, each one with its own log level.
+use serde_json::json;
+
+use crate::utils::{
+    config::{Notifier, NotifierKind},
+    errors::ProcessError,
+};
+
+/// Cut text to the given number of characters, services reject longer messages.
+pub fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max.saturating_sub(3)) {
+        Some((index, _)) if text.chars().count() > max => format!("{}...", &text[..index]),
+        _ => text.to_string(),
+    }
+}
+
+/// Send one message with the collected log lines.
+pub async fn send_notification(
+    notifier: &Notifier,
+    subject: &str,
+    text: &str,
+) -> Result<(), ProcessError> {
+    let message = format!("{subject}\n\n{text}");
+    let client = reqwest::Client::new();
+
+    let request = match notifier.kind {
+        NotifierKind::Telegram => client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                notifier.token
+            ))
+            .json(&json!({
+                "chat_id": notifier.chat_id,
+                "text": truncate(&message, 4096),
+            })),
+        NotifierKind::Slack => client
+            .post(&notifier.token)
+            .json(&json!({ "text": truncate(&message, 40000) })),
+        NotifierKind::Discord => client
+            .post(&notifier.token)
+            .json(&json!({ "content": truncate(&message, 2000) })),
+    };
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ProcessError::Custom(e.without_url().to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(ProcessError::Custom(format!(
+            "{:?} notification failed with status {}",
+            notifier.kind,
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
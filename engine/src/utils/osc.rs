@@ -0,0 +1,355 @@
+/// OSC Remote Control
+///
+/// `--osc ADDR:PORT` listens for OSC messages over UDP, so hardware control surfaces and show
+/// control software, like QLab or TouchOSC, can drive the channels. Addresses have the form
+/// `/ffplayout/{channel}/{command}`, with the commands `next`, `back`, `reset`, `start`, `stop`,
+/// `status`, `text` with a string and `volume` with a number.
+///
+/// Every command is answered with a `reply` message to the sender, and senders get a `clip`
+/// message on every clip start from their channels. OSC has no authentication, the port should
+/// only be reachable from the control network.
+use std::{collections::VecDeque, net::SocketAddr, sync::Arc};
+
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::{
+    net::UdpSocket,
+    sync::{broadcast::error::RecvError, Mutex},
+};
+
+use crate::player::controller::{ChannelController, ChannelManager};
+use crate::sse::CLIP_STREAM;
+use crate::utils::{
+    control::{remote_control, RemoteCtl},
+    logging::Target,
+};
+
+/// Senders which get the clip messages.
+const MAX_PEERS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+impl OscArg {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(v) => Some(f64::from(*v)),
+            Self::Float(v) => Some(f64::from(*v)),
+            Self::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            Self::Str(_) => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+fn push_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+
+    // null terminated, padded to 4 bytes
+    buf.push(0);
+
+    while buf.len() % 4 != 0 {
+        buf.push(0);
+    }
+}
+
+fn read_string(buf: &[u8], pos: &mut usize) -> Option<String> {
+    let rest = buf.get(*pos..)?;
+    let end = rest.iter().position(|b| *b == 0)?;
+    let value = String::from_utf8(rest[..end].to_vec()).ok()?;
+
+    *pos += (end + 4) & !3;
+
+    Some(value)
+}
+
+fn read_bytes<const N: usize>(buf: &[u8], pos: &mut usize) -> Option<[u8; N]> {
+    let bytes = buf.get(*pos..*pos + N)?.try_into().ok()?;
+    *pos += N;
+
+    Some(bytes)
+}
+
+impl OscMessage {
+    pub fn new(address: impl Into<String>, args: Vec<OscArg>) -> Self {
+        Self {
+            address: address.into(),
+            args,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        let mut tags = ",".to_string();
+        let mut data = vec![];
+
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(v) => {
+                    tags.push('i');
+                    data.extend_from_slice(&v.to_be_bytes());
+                }
+                OscArg::Float(v) => {
+                    tags.push('f');
+                    data.extend_from_slice(&v.to_be_bytes());
+                }
+                OscArg::Str(v) => {
+                    tags.push('s');
+                    push_string(&mut data, v);
+                }
+                OscArg::Bool(v) => tags.push(if *v { 'T' } else { 'F' }),
+            }
+        }
+
+        push_string(&mut buf, &self.address);
+        push_string(&mut buf, &tags);
+        buf.append(&mut data);
+
+        buf
+    }
+
+    /// Messages from a packet, bundles are unpacked and run at once. `None` for broken packets.
+    pub fn decode(buf: &[u8]) -> Option<Vec<Self>> {
+        if let Some(bundle) = buf.strip_prefix(b"#bundle\0") {
+            // skip the time tag
+            let mut rest = bundle.get(8..)?;
+            let mut messages = vec![];
+
+            while !rest.is_empty() {
+                let size = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+
+                messages.append(&mut Self::decode(rest.get(4..4 + size)?)?);
+                rest = &rest[4 + size..];
+            }
+
+            return Some(messages);
+        }
+
+        let mut pos = 0;
+        let address = read_string(buf, &mut pos)?;
+        let mut args = vec![];
+
+        if !address.starts_with('/') {
+            return None;
+        }
+
+        // old senders have no type tags
+        if pos < buf.len() {
+            let tags = read_string(buf, &mut pos)?;
+
+            for tag in tags.strip_prefix(',')?.chars() {
+                let arg = match tag {
+                    'i' => OscArg::Int(i32::from_be_bytes(read_bytes(buf, &mut pos)?)),
+                    'f' => OscArg::Float(f32::from_be_bytes(read_bytes(buf, &mut pos)?)),
+                    'h' => OscArg::Int(i64::from_be_bytes(read_bytes(buf, &mut pos)?) as i32),
+                    'd' => OscArg::Float(f64::from_be_bytes(read_bytes(buf, &mut pos)?) as f32),
+                    's' | 'S' => OscArg::Str(read_string(buf, &mut pos)?),
+                    'T' => OscArg::Bool(true),
+                    'F' => OscArg::Bool(false),
+                    'N' | 'I' => continue,
+                    _ => return None,
+                };
+
+                args.push(arg);
+            }
+        }
+
+        Some(vec![Self { address, args }])
+    }
+}
+
+/// Channel and command from a message. `None` for a button release: buttons send `1` when they
+/// are pressed and `0` when they are released.
+pub fn parse_command(message: &OscMessage) -> Result<Option<(i32, RemoteCtl)>, String> {
+    let mut parts = message.address.trim_start_matches('/').split('/');
+    let (Some("ffplayout"), Some(id), Some(name), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(format!("Unknown address {}", message.address));
+    };
+    let id = id
+        .parse::<i32>()
+        .map_err(|_| format!("Unknown channel {id}"))?;
+    let first = message.args.first();
+
+    let command = match name {
+        "text" => RemoteCtl::Text(
+            first
+                .and_then(OscArg::as_str)
+                .ok_or("Text needs a string")?
+                .to_string(),
+        ),
+        "volume" => RemoteCtl::Volume(
+            first
+                .and_then(OscArg::as_f64)
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .ok_or("Volume needs a positive number")?,
+        ),
+        _ if first.and_then(OscArg::as_f64) == Some(0.0) => return Ok(None),
+        "status" => RemoteCtl::Status,
+        "start" | "stop" => RemoteCtl::Process(name.parse()?),
+        "back" | "next" | "reset" => RemoteCtl::Player(name.parse()?),
+        _ => return Err(format!("Unknown command {name}")),
+    };
+
+    Ok(Some((id, command)))
+}
+
+async fn status(id: i32, manager: &ChannelManager) -> OscMessage {
+    let state = manager.state.lock().await.state;
+    let media = manager
+        .current_media
+        .lock()
+        .await
+        .clone()
+        .unwrap_or_default();
+
+    OscMessage::new(
+        format!("/ffplayout/{id}/status"),
+        vec![
+            OscArg::Str(state.to_string()),
+            OscArg::Int(media.index.map_or(-1, |i| i as i32)),
+            OscArg::Str(media.source),
+            OscArg::Str(media.title.unwrap_or_default()),
+            OscArg::Float((media.out - media.seek) as f32),
+        ],
+    )
+}
+
+async fn handle(
+    pool: &Pool<Sqlite>,
+    controllers: &Arc<Mutex<ChannelController>>,
+    message: &OscMessage,
+) -> Option<Vec<OscMessage>> {
+    let (id, command) = match parse_command(message) {
+        Ok(command) => command?,
+        Err(e) => {
+            debug!("OSC: {e}");
+            return None;
+        }
+    };
+    let name = message.address.rsplit('/').next().unwrap_or_default();
+    let reply = |result: &str| {
+        OscMessage::new(
+            format!("/ffplayout/{id}/reply"),
+            vec![
+                OscArg::Str(name.to_string()),
+                OscArg::Str(result.to_string()),
+            ],
+        )
+    };
+
+    let Some(manager) = controllers.lock().await.get(id).await else {
+        return Some(vec![reply("channel not found")]);
+    };
+
+    info!(target: Target::file(), channel = id; "OSC command: <yellow>{name}</>");
+
+    match remote_control(pool, &manager, command).await {
+        Ok(_) => Some(vec![reply("ok"), status(id, &manager).await]),
+        Err(e) => Some(vec![reply(&e.to_string())]),
+    }
+}
+
+async fn send(socket: &UdpSocket, message: &OscMessage, peer: SocketAddr) {
+    if let Err(e) = socket.send_to(&message.encode(), peer).await {
+        debug!("OSC: send to {peer} failed: {e}");
+    }
+}
+
+/// Send the clip starts to the senders, as `/ffplayout/{channel}/clip` with source, title,
+/// category and duration.
+async fn clip_feedback(socket: Arc<UdpSocket>, peers: Arc<Mutex<VecDeque<SocketAddr>>>) {
+    let mut receiver = CLIP_STREAM.subscribe();
+
+    loop {
+        let clip = match receiver.recv().await {
+            Ok(clip) => clip,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        let message = OscMessage::new(
+            format!("/ffplayout/{}/clip", clip.channel),
+            vec![
+                OscArg::Str(clip.source),
+                OscArg::Str(clip.title.unwrap_or_default()),
+                OscArg::Str(clip.category),
+                OscArg::Float((clip.out - clip.seek) as f32),
+            ],
+        );
+
+        for peer in peers.lock().await.iter() {
+            send(&socket, &message, *peer).await;
+        }
+    }
+}
+
+/// OSC server, runs in its own task as long as the server is running.
+pub async fn run(addr: String, pool: Pool<Sqlite>, controllers: Arc<Mutex<ChannelController>>) {
+    let socket = match UdpSocket::bind(&addr).await {
+        Ok(socket) => Arc::new(socket),
+        Err(e) => {
+            error!("OSC: listen on <b><magenta>{addr}</></b> failed: {e}");
+            return;
+        }
+    };
+    let peers = Arc::new(Mutex::new(VecDeque::new()));
+    let mut buf = [0u8; 65536];
+
+    info!("OSC: listen on <b><magenta>{addr}</></b>");
+
+    tokio::spawn(clip_feedback(socket.clone(), peers.clone()));
+
+    loop {
+        let (size, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                error!("OSC: receive failed: {e}");
+                continue;
+            }
+        };
+
+        let Some(messages) = OscMessage::decode(&buf[..size]) else {
+            debug!("OSC: broken packet from {peer}");
+            continue;
+        };
+
+        {
+            let mut peers = peers.lock().await;
+
+            if !peers.contains(&peer) {
+                if peers.len() >= MAX_PEERS {
+                    peers.pop_front();
+                }
+
+                peers.push_back(peer);
+            }
+        }
+
+        for message in messages {
+            for reply in handle(&pool, &controllers, &message)
+                .await
+                .unwrap_or_default()
+            {
+                send(&socket, &reply, peer).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+/// Output Test
+///
+/// `--test-output` sends color bars with a sine tone for some seconds to every target from the
+/// stream output, one after the other, and reports if the target took the stream. So stream
+/// keys, ingest servers and firewalls can be checked before a channel goes on air. Tee outputs are
+/// split in their single targets, secrets from the targets are masked in the report.
+use std::time::Duration;
+
+use sqlx::{Pool, Sqlite};
+use tokio::{process::Command, time::timeout};
+
+use crate::player::{
+    controller::ProcessUnit::*,
+    utils::{prepare_output_cmd, Media},
+};
+use crate::utils::{
+    config::{get_config, OutputMode, PlayoutConfig},
+    secrets::redact,
+};
+use crate::vec_strings;
+use crate::ARGS;
+
+/// Extra time for connect and handshake, before a target counts as hanging.
+const CONNECT_TIMEOUT: u64 = 15;
+
+/// One target from the output parameters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OutputTarget {
+    pub url: String,
+    /// Output parameters only for this target, the url is the last one.
+    pub cmd: Vec<String>,
+}
+
+/// Split the output parameters into one command per target, the parameters before a target
+/// belong to it. Tee outputs get one command per slave, with the format from the slave options.
+pub fn output_targets(cmd: &[String]) -> Vec<OutputTarget> {
+    let mut targets = vec![];
+    let mut start = 0;
+
+    for (i, param) in cmd.iter().enumerate() {
+        let prev = if i > 0 { cmd[i - 1].as_str() } else { "-" };
+
+        if i < cmd.len() - 1 && (param.starts_with('-') || prev.starts_with('-')) {
+            continue;
+        }
+
+        let params = &cmd[start..i];
+        start = i + 1;
+
+        let tee = params
+            .iter()
+            .rposition(|p| p == "-f")
+            .filter(|f| params.get(f + 1).is_some_and(|p| p == "tee"));
+
+        match tee {
+            Some(f) => {
+                let mut base = params.to_vec();
+                base.drain(f..f + 2);
+
+                for slave in param.split('|') {
+                    let (options, url) = slave
+                        .strip_prefix('[')
+                        .and_then(|s| s.split_once(']'))
+                        .unwrap_or(("", slave));
+                    let mut cmd = base.clone();
+
+                    if let Some(format) = options.split(':').find_map(|o| o.strip_prefix("f=")) {
+                        cmd.append(&mut vec_strings!["-f", format]);
+                    }
+
+                    cmd.push(url.to_string());
+                    targets.push(OutputTarget {
+                        url: url.to_string(),
+                        cmd,
+                    });
+                }
+            }
+            None => {
+                let mut cmd = params.to_vec();
+                cmd.push(param.clone());
+                targets.push(OutputTarget {
+                    url: param.clone(),
+                    cmd,
+                });
+            }
+        }
+    }
+
+    targets
+}
+
+/// Color bars with a sine tone on all audio tracks, in real time.
+fn test_signal_cmd(config: &PlayoutConfig, seconds: u64) -> Vec<String> {
+    let proc = &config.processing;
+    let mut graph = format!(
+        "smptebars=size={}x{}:rate={}[out0]",
+        proc.width, proc.height, proc.fps
+    );
+
+    for i in 1..=proc.audio_tracks.max(1) {
+        graph.push_str(&format!(";sine=frequency=1000:sample_rate=48000[out{i}]"));
+    }
+
+    vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-v",
+        "error",
+        "-re",
+        "-f",
+        "lavfi",
+        "-t",
+        seconds,
+        "-i",
+        graph
+    ]
+}
+
+async fn test_target(
+    config: &PlayoutConfig,
+    target: &OutputTarget,
+    seconds: u64,
+) -> Result<(), String> {
+    // the test signal goes plain to one target, without the output filter
+    let mut config = config.clone();
+    config.output.output_count = 1;
+    config.output.output_filter = None;
+    config.output.output_cmd = Some(target.cmd.clone());
+
+    let mut node = Media {
+        unit: Encoder,
+        ..Default::default()
+    };
+    node.add_filter(&config, &None).await;
+
+    let child = Command::new("ffmpeg")
+        .args(prepare_output_cmd(
+            &config,
+            test_signal_cmd(&config, seconds),
+            &node.filter,
+        ))
+        .kill_on_drop(true)
+        .output();
+
+    match timeout(Duration::from_secs(seconds + CONNECT_TIMEOUT), child).await {
+        Ok(Ok(output)) if output.status.success() => Ok(()),
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let lines: Vec<&str> = stderr.lines().collect();
+
+            Err(redact(&lines[lines.len().saturating_sub(3)..].join(" | ")))
+        }
+        Ok(Err(e)) => Err(format!("ffmpeg can't run: {e}")),
+        Err(_) => Err("no answer, the target hangs".to_string()),
+    }
+}
+
+/// Test the targets from the selected channels. Returns the exit code, `1` when a target fails.
+pub async fn run(pool: &Pool<Sqlite>, seconds: u64) -> i32 {
+    let mut code = 0;
+
+    for id in ARGS.channel.clone().unwrap_or_else(|| vec![1]) {
+        let config = match get_config(pool, id).await {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Channel {id}: {e}");
+                code = 1;
+                continue;
+            }
+        };
+
+        if config.output.mode != OutputMode::Stream {
+            println!(
+                "Channel {id}: output is {}, only stream targets can be tested",
+                config.output.mode
+            );
+            continue;
+        }
+
+        let targets: Vec<OutputTarget> =
+            output_targets(config.output.output_cmd.as_deref().unwrap_or_default())
+                .into_iter()
+                .filter(|t| t.url != "-" && !t.url.starts_with("pipe:"))
+                .collect();
+
+        println!(
+            "Channel {id}: test {} targets for {seconds} seconds ...",
+            targets.len()
+        );
+
+        for target in targets {
+            match test_target(&config, &target, seconds).await {
+                Ok(_) => println!("  {}: ok", redact(&target.url)),
+                Err(e) => {
+                    println!("  {}: failed: {e}", redact(&target.url));
+                    code = 1;
+                }
+            }
+        }
+    }
+
+    code
+}
@@ -0,0 +1,121 @@
+/// Play History
+///
+/// Every clip which gets played out is saved with its start time in the database,
+/// so it can be looked up later what was on air at a specific time. The end is planned from
+/// the clip length first, and corrected when the next clip starts or the channel stops.
+/// Times are in the timezone from the channel.
+/// Entries older than the `play_history_days` from the global settings are removed once per hour,
+/// a value of 0 disables the history.
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::{Local, TimeDelta};
+use chrono_tz::Tz;
+use log::*;
+use sqlx::{Pool, Sqlite};
+
+use crate::db::{
+    handles,
+    models::{GlobalSettings, PlayHistory},
+    GLOBAL_SETTINGS,
+};
+use crate::player::utils::Media;
+use crate::utils::{logging::Target, time_machine::time_now};
+
+/// Time format from the history, sorts the same as text and as time.
+pub const HISTORY_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+fn history_enabled() -> bool {
+    GLOBAL_SETTINGS
+        .get()
+        .is_some_and(|g| g.play_history_days > 0)
+}
+
+/// Entry from the clip which is on air, per channel.
+static ON_AIR: LazyLock<Mutex<HashMap<i32, i64>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Set the real end from the clip which was on air.
+async fn close(pool: &Pool<Sqlite>, channel_id: i32, ended: &str) {
+    let Some(id) = ON_AIR.lock().unwrap().remove(&channel_id) else {
+        return;
+    };
+
+    if let Err(e) = handles::update_play_history_end(pool, id, ended).await {
+        error!(target: Target::file_mail(), channel = channel_id; "Save play history end failed: {e}");
+    }
+}
+
+/// Save clip, which starts now, to the history. The clip before ends now.
+pub async fn record(pool: Pool<Sqlite>, channel_id: i32, timezone: Option<Tz>, node: Media) {
+    if !history_enabled() {
+        return;
+    }
+
+    let started = time_now(&timezone);
+    let start = started.format(HISTORY_TIME_FORMAT).to_string();
+
+    close(&pool, channel_id, &start).await;
+
+    let ended = started
+        + TimeDelta::try_milliseconds(((node.out - node.seek) * 1000.0) as i64).unwrap_or_default();
+
+    let item = PlayHistory {
+        id: 0,
+        channel_id,
+        started: start,
+        ended: ended.format(HISTORY_TIME_FORMAT).to_string(),
+        source: node.source,
+        title: node.title,
+        category: node.category,
+        seek: node.seek,
+        out: node.out,
+        duration: node.duration,
+    };
+
+    match handles::insert_play_history(&pool, &item).await {
+        Ok(result) => {
+            ON_AIR
+                .lock()
+                .unwrap()
+                .insert(channel_id, result.last_insert_rowid());
+        }
+        Err(e) => {
+            error!(target: Target::file_mail(), channel = channel_id; "Save play history failed: {e}");
+        }
+    }
+}
+
+/// The channel stopped, the clip on air ends now.
+pub async fn finish(pool: Pool<Sqlite>, channel_id: i32, timezone: Option<Tz>) {
+    let ended = time_now(&timezone).format(HISTORY_TIME_FORMAT).to_string();
+
+    close(&pool, channel_id, &ended).await;
+}
+
+/// Cleanup loop, runs in its own task as long as the server is running.
+pub async fn run(pool: Pool<Sqlite>, global: GlobalSettings) {
+    let days = i64::from(global.play_history_days);
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        let before = (Local::now() - TimeDelta::try_days(days).unwrap_or_default())
+            .format(HISTORY_TIME_FORMAT)
+            .to_string();
+
+        match handles::delete_play_history(&pool, &before).await {
+            Ok(result) if result.rows_affected() > 0 => {
+                debug!(
+                    "Removed {} entries from play history",
+                    result.rows_affected()
+                );
+            }
+            Ok(_) => {}
+            Err(e) => error!("Play history cleanup failed: {e}"),
+        }
+    }
+}
@@ -1,86 +1,62 @@
-use std::path::PathBuf;
-
 use log::*;
-use tokio::fs;
 
 use crate::player::controller::ChannelManager;
-use crate::player::utils::{json_reader, json_writer, JsonPlaylist};
-use crate::utils::{
-    config::PlayoutConfig, errors::ServiceError, files::norm_abs_path,
-    generator::playlist_generator,
+use crate::player::utils::{
+    playlist_store::{PlaylistStorage, PlaylistStore},
+    JsonPlaylist,
 };
+use crate::utils::{errors::ServiceError, files::norm_abs_path, generator::playlist_generator};
 
 pub async fn read_playlist(
-    config: &PlayoutConfig,
+    store: &PlaylistStorage,
     date: String,
 ) -> Result<JsonPlaylist, ServiceError> {
-    let d: Vec<&str> = date.split('-').collect();
-    let mut playlist_path = config.channel.playlists.clone();
-
-    playlist_path = playlist_path
-        .join(d[0])
-        .join(d[1])
-        .join(date.clone())
-        .with_extension("json");
-
-    match json_reader(&playlist_path).await {
-        Ok(p) => Ok(p),
+    match store.read(&date).await {
+        Ok(Some(p)) => Ok(p),
+        Ok(None) => Err(ServiceError::NoContent(format!(
+            "Playlist from {date} not exists!"
+        ))),
         Err(e) => Err(ServiceError::NoContent(e.to_string())),
     }
 }
 
 pub async fn write_playlist(
-    config: &PlayoutConfig,
+    store: &PlaylistStorage,
     json_data: JsonPlaylist,
 ) -> Result<String, ServiceError> {
     let date = json_data.date.clone();
-    let d: Vec<&str> = date.split('-').collect();
-    let mut playlist_path = config.channel.playlists.clone();
-
-    if !playlist_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| ext.eq_ignore_ascii_case("json"))
-        .unwrap_or(false)
-    {
-        playlist_path = playlist_path
-            .join(d[0])
-            .join(d[1])
-            .join(date.clone())
-            .with_extension("json");
-    }
-
-    let mut file_exists = false;
 
-    if let Some(p) = playlist_path.parent() {
-        fs::create_dir_all(p).await?;
-    }
-
-    if playlist_path.is_file() {
-        file_exists = true;
-        if let Ok(existing_data) = json_reader(&playlist_path).await {
-            if json_data == existing_data {
-                return Err(ServiceError::Conflict(format!(
-                    "Playlist from {date}, already exists!"
-                )));
-            }
+    if let Ok(Some(existing_data)) = store.read(&date).await {
+        if json_data == existing_data {
+            return Err(ServiceError::Conflict(format!(
+                "Playlist from {date}, already exists!"
+            )));
         }
     }
 
-    match json_writer(&playlist_path, json_data).await {
-        Ok(..) => {
-            return if file_exists {
-                Ok(format!("Update playlist from {date} success!"))
-            } else {
-                Ok(format!("Write playlist from {date} success!"))
-            };
-        }
+    match store.write(&json_data).await {
+        Ok(true) => Ok(format!("Update playlist from {date} success!")),
+        Ok(false) => Ok(format!("Write playlist from {date} success!")),
+        Err(ServiceError::Conflict(e)) => Err(ServiceError::Conflict(e)),
         Err(e) => {
             error!("{e}");
+            Err(ServiceError::InternalServerError)
         }
     }
+}
 
-    Err(ServiceError::InternalServerError)
+/// Replaced versions from a playlist, only the database backend has a history.
+pub async fn playlist_history(
+    store: &PlaylistStorage,
+    date: &str,
+) -> Result<Vec<JsonPlaylist>, ServiceError> {
+    if matches!(store, PlaylistStorage::File(_)) {
+        return Err(ServiceError::BadRequest(
+            "Playlist history needs the database backend!".to_string(),
+        ));
+    }
+
+    store.history(date).await
 }
 
 pub async fn generate_playlist(manager: ChannelManager) -> Result<JsonPlaylist, ServiceError> {
@@ -121,25 +97,13 @@ pub async fn generate_playlist(manager: ChannelManager) -> Result<JsonPlaylist,
     }
 }
 
-pub async fn delete_playlist(config: &PlayoutConfig, date: &str) -> Result<String, ServiceError> {
-    let d: Vec<&str> = date.split('-').collect();
-    let mut playlist_path = PathBuf::from(&config.channel.playlists);
-
-    playlist_path = playlist_path
-        .join(d[0])
-        .join(d[1])
-        .join(date)
-        .with_extension("json");
-
-    if playlist_path.is_file() {
-        match fs::remove_file(playlist_path).await {
-            Ok(..) => Ok(format!("Delete playlist from {date} success!")),
-            Err(e) => {
-                error!("{e}");
-                Err(ServiceError::InternalServerError)
-            }
+pub async fn delete_playlist(store: &PlaylistStorage, date: &str) -> Result<String, ServiceError> {
+    match store.delete(date).await {
+        Ok(true) => Ok(format!("Delete playlist from {date} success!")),
+        Ok(false) => Ok(format!("No playlist to delete on: {date}")),
+        Err(e) => {
+            error!("{e}");
+            Err(ServiceError::InternalServerError)
         }
-    } else {
-        Ok(format!("No playlist to delete on: {date}"))
     }
 }
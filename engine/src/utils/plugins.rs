@@ -0,0 +1,285 @@
+/// Plugins
+///
+/// Plugins are programs from `general.plugins`, separated by `;`. For every call the plugin gets
+/// one JSON request on stdin and writes one JSON answer to stdout, the last line counts. An
+/// answer with `error` is a failed call. WASM plugins run with a WASI runtime as command, like
+/// `wasmtime run plugin.wasm`.
+///
+/// Requests have a `type`:
+///
+/// - `describe`: the plugin answers with its `name`, the `outputs` it has, `clip_filter` when it
+///   changes the filters of clips, and the `events` it wants, `*` for all.
+/// - `output`: with the output `name` and the channel format, the answer has the
+///   `output_param` for ffmpeg. Outputs are used with `plugin:<name>` as output parameters.
+/// - `clip`: with the next `clip`, the answer has the `custom_filter` for it.
+/// - `event`: with the `event` name and its `data`, the answer is not used.
+///
+/// The description is kept until the plugin program changes.
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Stdio,
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{io::AsyncWriteExt, process::Command, time::timeout};
+
+use crate::player::{controller::ChannelManager, utils::Media};
+use crate::utils::{
+    config::{General, PlayoutConfig, Processing},
+    errors::ProcessError,
+    logging::Target,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Description {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    #[serde(default)]
+    pub clip_filter: bool,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl Description {
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.iter().any(|e| e == "*" || e == event)
+    }
+}
+
+/// Descriptions per plugin command, with the modification time from the program.
+static DESCRIPTIONS: LazyLock<Mutex<HashMap<String, (Option<SystemTime>, Description)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Plugin commands from the config.
+pub fn commands(plugins: &str) -> Vec<String> {
+    plugins
+        .split(';')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// JSON answer from the plugin output, the last line which is not empty.
+pub fn parse_answer(output: &str) -> Result<Value, ProcessError> {
+    let line = output
+        .lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .ok_or_else(|| ProcessError::Custom("Plugin has no answer".to_string()))?;
+    let answer: Value = serde_json::from_str(line)
+        .map_err(|e| ProcessError::Custom(format!("Plugin answer is no JSON: {e}")))?;
+
+    if let Some(error) = answer.get("error").filter(|e| !e.is_null()) {
+        return Err(ProcessError::Custom(format!(
+            "Plugin error: {}",
+            error
+                .as_str()
+                .map_or_else(|| error.to_string(), str::to_string)
+        )));
+    }
+
+    Ok(answer)
+}
+
+/// String field from the answer.
+pub fn answer_string(answer: &Value, field: &str) -> Result<String, ProcessError> {
+    answer
+        .get(field)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| ProcessError::Custom(format!("Plugin answer has no {field}")))
+}
+
+fn program(command: &str) -> Result<Vec<String>, ProcessError> {
+    shlex::split(command)
+        .filter(|a| !a.is_empty())
+        .ok_or_else(|| ProcessError::Custom(format!("Broken plugin command: {command}")))
+}
+
+async fn run(args: &[String], request: &Value) -> Result<Value, ProcessError> {
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!("{request}\n").as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+
+    if !output.status.success() {
+        return Err(ProcessError::Custom(format!(
+            "Plugin failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    parse_answer(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Send one request to the plugin.
+pub async fn call(command: &str, request: &Value) -> Result<Value, ProcessError> {
+    let args = program(command)?;
+
+    timeout(TIMEOUT, run(&args, request))
+        .await
+        .map_err(|_| ProcessError::Custom("Plugin takes too long".to_string()))?
+}
+
+/// Description from the plugin, from the cache when the program is the same.
+pub async fn describe(command: &str) -> Result<Description, ProcessError> {
+    let args = program(command)?;
+    let modified = Path::new(&args[0])
+        .metadata()
+        .and_then(|m| m.modified())
+        .ok();
+
+    if let Some((time, description)) = DESCRIPTIONS.lock().unwrap().get(command) {
+        if *time == modified {
+            return Ok(description.clone());
+        }
+    }
+
+    let answer = call(command, &json!({ "type": "describe" })).await?;
+    let description: Description = serde_json::from_value(answer)
+        .map_err(|e| ProcessError::Custom(format!("Plugin description is broken: {e}")))?;
+
+    DESCRIPTIONS
+        .lock()
+        .unwrap()
+        .insert(command.to_string(), (modified, description.clone()));
+
+    Ok(description)
+}
+
+/// Plugins with their descriptions, failed ones are logged and left out.
+async fn described(id: i32, plugins: &str) -> Vec<(String, Description)> {
+    let mut list = vec![];
+
+    for command in commands(plugins) {
+        match describe(&command).await {
+            Ok(description) => list.push((command, description)),
+            Err(e) => {
+                error!(target: Target::file_mail(), channel = id; "Plugin <b><magenta>{command}</></b>: {e}");
+            }
+        }
+    }
+
+    list
+}
+
+/// Output parameters from the plugin which has the output.
+pub async fn output_param(
+    general: &General,
+    processing: &Processing,
+    name: &str,
+) -> Result<String, ProcessError> {
+    let id = general.channel_id;
+    let (command, _) = described(id, &general.plugins)
+        .await
+        .into_iter()
+        .find(|(_, d)| d.outputs.iter().any(|o| o == name))
+        .ok_or_else(|| ProcessError::Custom(format!("No plugin has the output {name}")))?;
+
+    let answer = call(
+        &command,
+        &json!({
+            "type": "output",
+            "name": name,
+            "channel": id,
+            "width": processing.width,
+            "height": processing.height,
+            "fps": processing.fps,
+        }),
+    )
+    .await?;
+
+    answer_string(&answer, "output_param")
+}
+
+/// Clip data for the requests.
+pub fn clip_json(node: &Media) -> Value {
+    let mut clip = serde_json::to_value(node).unwrap_or_else(|_| json!({}));
+
+    clip["begin"] = json!(node.begin);
+    clip["custom_filter"] = json!(node.custom_filter);
+
+    clip
+}
+
+/// Let the plugins change the filter of the clip, one after the other.
+pub async fn clip_filter(config: &PlayoutConfig, node: &mut Media) {
+    let id = config.general.channel_id;
+
+    if config.general.plugins.trim().is_empty() || node.source.is_empty() {
+        return;
+    }
+
+    for (command, _) in described(id, &config.general.plugins)
+        .await
+        .into_iter()
+        .filter(|(_, d)| d.clip_filter)
+    {
+        let request = json!({
+            "type": "clip",
+            "channel": id,
+            "clip": clip_json(node),
+        });
+
+        match call(&command, &request)
+            .await
+            .and_then(|a| answer_string(&a, "custom_filter"))
+        {
+            Ok(filter) => node.custom_filter = filter,
+            Err(e) => {
+                error!(target: Target::file_mail(), channel = id; "Plugin <b><magenta>{command}</></b>: {e}");
+            }
+        }
+    }
+}
+
+/// Send the event to the plugins which want it. Runs in its own task.
+pub fn event(manager: &ChannelManager, event: &str, data: Value) {
+    let manager = manager.clone();
+    let event = event.to_string();
+
+    tokio::spawn(async move {
+        let config = manager.config.lock().await.clone();
+        let id = config.general.channel_id;
+
+        if config.general.plugins.trim().is_empty() {
+            return;
+        }
+
+        let request = json!({
+            "type": "event",
+            "event": event,
+            "channel": id,
+            "data": data,
+        });
+
+        for (command, _) in described(id, &config.general.plugins)
+            .await
+            .into_iter()
+            .filter(|(_, d)| d.wants(&event))
+        {
+            if let Err(e) = call(&command, &request).await {
+                error!(target: Target::file_mail(), channel = id; "Plugin <b><magenta>{command}</></b>: {e}");
+            }
+        }
+    });
+}
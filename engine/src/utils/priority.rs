@@ -0,0 +1,153 @@
+/// Process Priority
+///
+/// Nice level, CPU affinity and IO priority for the decoder and encoder processes of a channel, so
+/// a critical channel isn't starved by other jobs on the same host. The values are set in the
+/// child before ffmpeg runs, so all its threads get them.
+use tokio::process::Command;
+
+use crate::utils::advanced_config::ProcessConfig;
+
+const IOPRIO_CLASS_SHIFT: i32 = 13;
+
+/// CPU numbers from a list, like `0-3,8`.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, String> {
+    let mut cpus = vec![];
+
+    for part in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid CPU \"{s}\" in \"{list}\""))
+        };
+
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+
+                if first > last {
+                    return Err(format!("Invalid CPU range \"{part}\""));
+                }
+
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(parse(part)?),
+        }
+    }
+
+    cpus.sort_unstable();
+    cpus.dedup();
+
+    Ok(cpus)
+}
+
+/// IO class and level, like `ioprio_set` takes it: `realtime:0-7`, `best-effort:0-7` or `idle`.
+pub fn parse_io_priority(value: &str) -> Result<i32, String> {
+    let (class, level) = match value.trim().split_once(':') {
+        Some((class, level)) => (
+            class.trim(),
+            level
+                .trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|l| (0..=7).contains(l))
+                .ok_or_else(|| format!("IO priority level must be 0 to 7, got \"{level}\""))?,
+        ),
+        None => (value.trim(), 0),
+    };
+
+    let class = match class {
+        "realtime" => 1,
+        "best-effort" => 2,
+        "idle" => 3,
+        _ => {
+            return Err(format!(
+                "Unknown IO class \"{class}\", use realtime, best-effort or idle"
+            ))
+        }
+    };
+
+    Ok((class << IOPRIO_CLASS_SHIFT) | level)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Priority {
+    pub nice: Option<i32>,
+    pub cpus: Vec<usize>,
+    pub io: Option<i32>,
+}
+
+impl Priority {
+    pub fn new(config: &ProcessConfig) -> Result<Self, String> {
+        if let Some(nice) = config.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(format!("Nice level must be -20 to 19, got {nice}"));
+            }
+        }
+
+        Ok(Self {
+            nice: config.nice,
+            cpus: match &config.cpu_affinity {
+                Some(list) => parse_cpu_list(list)?,
+                None => vec![],
+            },
+            io: config
+                .io_priority
+                .as_deref()
+                .map(parse_io_priority)
+                .transpose()?,
+        })
+    }
+
+    /// Runs in the child, between fork and exec. Failures, like missing permissions for a
+    /// negative nice level, leave the default and don't stop the process.
+    #[cfg(target_os = "linux")]
+    fn apply(&self) {
+        use nix::{
+            libc,
+            sched::{sched_setaffinity, CpuSet},
+            unistd::Pid,
+        };
+
+        if let Some(nice) = self.nice {
+            unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+        }
+
+        if !self.cpus.is_empty() {
+            let mut set = CpuSet::new();
+
+            for cpu in &self.cpus {
+                let _ = set.set(*cpu);
+            }
+
+            let _ = sched_setaffinity(Pid::from_raw(0), &set);
+        }
+
+        if let Some(io) = self.io {
+            // who 1 is IOPRIO_WHO_PROCESS, 0 the calling process
+            unsafe { libc::syscall(libc::SYS_ioprio_set, 1, 0, io) };
+        }
+    }
+}
+
+/// Set the priority from the advanced config for the process. Invalid values are skipped,
+/// they are rejected when the config is saved.
+pub fn set_priority(command: &mut Command, config: &ProcessConfig) {
+    let Ok(priority) = Priority::new(config) else {
+        return;
+    };
+
+    if priority == Priority::default() {
+        return;
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        command.pre_exec(move || {
+            priority.apply();
+            Ok(())
+        });
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = command;
+}
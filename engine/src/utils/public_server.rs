@@ -0,0 +1,154 @@
+/// Public Server
+///
+/// The public files from a channel, like HLS playlists, segments and subtitles, are served
+/// without reverse proxy with the settings from `output.public_server`:
+///
+/// - CORS headers for the allowed origins, with answers to the preflight requests.
+/// - Cache-Control per file extension, playlists get `no-cache` by default.
+/// - Range requests can be turned off, then the whole file is sent.
+/// - Playlists can be compressed with gzip, for clients which accept it.
+use std::{io::Write, path::Path};
+
+use actix_files::{file_extension_to_mime, NamedFile};
+use actix_web::{
+    http::header::{self, ContentDisposition, DispositionType, HeaderMap, HeaderValue},
+    HttpRequest, HttpResponse,
+};
+use flate2::{write::GzEncoder, Compression};
+use tokio::fs;
+
+use crate::utils::{config::PublicServer, errors::ServiceError};
+
+/// Value for `Access-Control-Allow-Origin`, when the origin is allowed.
+pub fn allowed_origin(origins: &[String], origin: &str) -> Option<String> {
+    if origins.iter().any(|o| o.trim() == "*") {
+        return Some("*".to_string());
+    }
+
+    let origin = origin.trim().trim_end_matches('/');
+
+    origins
+        .iter()
+        .any(|o| o.trim().trim_end_matches('/').eq_ignore_ascii_case(origin))
+        .then(|| origin.to_string())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Cache-Control value for the file.
+pub fn cache_control<'a>(settings: &'a PublicServer, path: &Path) -> Option<&'a str> {
+    settings
+        .cache_control
+        .get(&extension(path))
+        .map(String::as_str)
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// The client takes gzip, from the `Accept-Encoding` header.
+pub fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|coding| {
+        let mut parts = coding.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let quality = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse::<f64>().ok())
+            .unwrap_or(1.0);
+
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && quality > 0.0
+    })
+}
+
+pub fn gzip(data: &[u8]) -> Result<Vec<u8>, ServiceError> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// CORS headers for the request origin, also used for the preflight answers.
+pub fn add_cors(settings: &PublicServer, req: &HttpRequest, headers: &mut HeaderMap) {
+    let Some(origin) = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|o| o.to_str().ok())
+        .and_then(|o| allowed_origin(&settings.cors_origins, o))
+    else {
+        return;
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&origin) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+    }
+
+    if origin != "*" {
+        headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    }
+
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("GET, HEAD, OPTIONS"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("Range"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        HeaderValue::from_static("Content-Length, Content-Range"),
+    );
+}
+
+/// Answer for the public file, with the headers from the settings.
+pub async fn response(
+    settings: &PublicServer,
+    req: &HttpRequest,
+    path: &Path,
+) -> Result<HttpResponse, ServiceError> {
+    let extension = extension(path);
+    let gzip_playlist = settings.gzip
+        && extension == "m3u8"
+        && req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|a| a.to_str().ok())
+            .is_some_and(accepts_gzip);
+
+    let mut response = if gzip_playlist || !settings.ranges {
+        let mut data = fs::read(path).await?;
+        let mut builder = HttpResponse::Ok();
+
+        builder
+            .content_type(file_extension_to_mime(&extension))
+            .insert_header((header::ACCEPT_RANGES, "none"));
+
+        if gzip_playlist {
+            data = gzip(&data)?;
+
+            builder
+                .insert_header((header::CONTENT_ENCODING, "gzip"))
+                .insert_header((header::VARY, "Accept-Encoding"));
+        }
+
+        builder.body(data)
+    } else {
+        NamedFile::open(path)?
+            .use_last_modified(true)
+            .set_content_disposition(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![],
+            })
+            .into_response(req)
+    };
+
+    if let Some(value) = cache_control(settings, path).and_then(|v| HeaderValue::from_str(v).ok()) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+
+    add_cors(settings, req, response.headers_mut());
+
+    Ok(response)
+}
@@ -0,0 +1,198 @@
+/// Reload
+///
+/// SIGHUP re-reads the global settings and the configs from all channels and applies them, like a
+/// change over the API: only the affected parts from a running channel restart. New channels are
+/// added and started, when they are active. The log files are opened again, so logrotate can move
+/// them without `copytruncate`.
+///
+/// Some global settings are only read at start, like the secret, the log and public path or the
+/// history task, a warning lists them when they changed.
+use std::sync::Arc;
+
+use flexi_logger::LoggerHandle;
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::sync::Mutex;
+
+use crate::db::{handles, models::GlobalSettings, GLOBAL_SETTINGS};
+use crate::player::controller::{ChannelController, ChannelManager};
+use crate::utils::{
+    config::get_config,
+    errors::ServiceError,
+    logging::{MailQueue, Target},
+    systemd,
+};
+
+/// Global settings, which changed and need a restart from the service.
+pub fn restart_needed(old: &GlobalSettings, new: &GlobalSettings) -> Vec<&'static str> {
+    let mut names = vec![];
+
+    if old.secret != new.secret {
+        names.push("secret");
+    }
+
+    if old.logs != new.logs {
+        names.push("logs");
+    }
+
+    if old.public != new.public {
+        names.push("public");
+    }
+
+    if old.clock_source != new.clock_source {
+        names.push("clock_source");
+    }
+
+    if old.sentry_dsn != new.sentry_dsn {
+        names.push("sentry_dsn");
+    }
+
+    if old.play_history_days != new.play_history_days {
+        names.push("play_history_days");
+    }
+
+    names
+}
+
+async fn reload_channel(
+    pool: &Pool<Sqlite>,
+    manager: &ChannelManager,
+    mail_queues: &Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
+    id: i32,
+) -> Result<(), ServiceError> {
+    let channel = handles::select_channel(pool, &id).await?;
+    let config = get_config(pool, id).await?;
+
+    for queue in mail_queues.lock().await.iter() {
+        let mut queue_lock = queue.lock().await;
+
+        if queue_lock.id == id {
+            if queue_lock.config.recipient != config.mail.recipient {
+                queue_lock.clear_sent();
+            }
+
+            queue_lock.update(config.mail.clone());
+            break;
+        }
+    }
+
+    let applied = manager.update_config(config).await;
+    manager.clone().update_channel(&channel).await;
+
+    info!(target: Target::file_mail(), channel = id; "Reload config: {applied:?}");
+
+    Ok(())
+}
+
+/// Re-read global settings and channel configs, and apply them to the running channels.
+pub async fn reload(
+    pool: &Pool<Sqlite>,
+    controllers: Arc<Mutex<ChannelController>>,
+    mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
+    logger: Option<&LoggerHandle>,
+) {
+    if let Some(Err(e)) = logger.map(LoggerHandle::reopen_output) {
+        error!("Reopen log files failed: {e}");
+    }
+
+    match (handles::select_global(pool).await, GLOBAL_SETTINGS.get()) {
+        (Ok(global), Some(old)) => {
+            let names = restart_needed(old, &global);
+
+            if !names.is_empty() {
+                warn!(
+                    "Global settings <yellow>{}</> are only read at start, restart the service to apply them",
+                    names.join(", ")
+                );
+            }
+        }
+        (Err(e), _) => error!("Reload global settings failed: {e}"),
+        _ => {}
+    }
+
+    let channels = match handles::select_related_channels(pool, None).await {
+        Ok(channels) => channels,
+        Err(e) => {
+            error!("Reload channels failed: {e}");
+            return;
+        }
+    };
+    let managers = controllers.lock().await.channels.clone();
+    let mut known = vec![];
+
+    for manager in &managers {
+        let id = manager.channel.lock().await.id;
+        known.push(id);
+
+        if !channels.iter().any(|c| c.id == id) {
+            warn!(target: Target::file_mail(), channel = id; "Channel is deleted, restart the service to remove it");
+            continue;
+        }
+
+        if let Err(e) = reload_channel(pool, manager, &mail_queues, id).await {
+            error!(target: Target::file_mail(), channel = id; "Reload config failed: {e}");
+        }
+    }
+
+    for channel in channels.into_iter().filter(|c| !known.contains(&c.id)) {
+        let config = match get_config(pool, channel.id).await {
+            Ok(config) => config,
+            Err(e) => {
+                error!(target: Target::file_mail(), channel = channel.id; "Load new channel failed: {e}");
+                continue;
+            }
+        };
+        let manager = ChannelManager::new(Some(pool.clone()), channel.clone(), config.clone());
+
+        controllers.lock().await.add(manager.clone());
+        mail_queues
+            .lock()
+            .await
+            .push(Arc::new(Mutex::new(MailQueue::new(
+                channel.id,
+                config.mail,
+            ))));
+
+        info!(target: Target::file_mail(), channel = channel.id; "Reload config: new channel added");
+
+        if channel.active {
+            if let Err(e) = manager.start().await {
+                error!(target: Target::file_mail(), channel = channel.id; "Start new channel failed: {e}");
+            }
+        }
+    }
+}
+
+/// Wait for SIGHUP and reload, as long as the service runs.
+#[cfg(unix)]
+pub async fn run(
+    pool: Pool<Sqlite>,
+    controllers: Arc<Mutex<ChannelController>>,
+    mail_queues: Arc<Mutex<Vec<Arc<Mutex<MailQueue>>>>>,
+    logger: Option<LoggerHandle>,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Listen on SIGHUP failed: {e}");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        info!("Got SIGHUP, reload settings and channel configs");
+        systemd::notify("RELOADING=1");
+
+        reload(
+            &pool,
+            controllers.clone(),
+            mail_queues.clone(),
+            logger.as_ref(),
+        )
+        .await;
+
+        systemd::notify("READY=1");
+    }
+}
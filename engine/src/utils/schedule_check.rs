@@ -0,0 +1,305 @@
+/// Schedule Check
+///
+/// Look ahead for the playlists of the next days, so a missing or too short schedule is noticed
+/// before it goes on air. Problems are written to the channel log when they are found, and sent
+/// to mail and chat notifiers the configured hours before the playlist starts.
+///
+/// With `--validate-all` every stored playlist from today on is validated once, for a nightly
+/// check from cron.
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta};
+use log::*;
+use sqlx::{Pool, Sqlite};
+use tokio::sync::Mutex;
+
+use crate::db::handles;
+use crate::player::{
+    controller::ChannelController,
+    utils::{
+        get_date,
+        json_validate::validate_playlist,
+        playlist_store::{PlaylistStorage, PlaylistStore},
+        sec_to_time, JsonPlaylist,
+    },
+};
+use crate::utils::{
+    config::{get_config, PlayoutConfig, ProcessMode},
+    errors::ServiceError,
+    logging::Target,
+    time_machine::time_now,
+};
+use crate::ARGS;
+
+const CHECK_INTERVAL: u64 = 600;
+/// Missing playtime, which is not counted as too short.
+const LENGTH_TOLERANCE: f64 = 1.0;
+
+/// Problem with the playlist from one date, `None` when it is fine.
+pub fn check_playlist(playlist: Option<&JsonPlaylist>, config: &PlayoutConfig) -> Option<String> {
+    let Some(playlist) = playlist else {
+        return Some("Playlist is missing".to_string());
+    };
+
+    if playlist.program.is_empty() {
+        return Some("Playlist has no clips".to_string());
+    }
+
+    if config.playlist.infinit {
+        return None;
+    }
+
+    let length = config.playlist.length_sec.unwrap_or(86400.0);
+    let playtime: f64 = playlist
+        .program
+        .iter()
+        .map(|item| (item.out - item.seek).max(0.0))
+        .sum();
+
+    if playtime + LENGTH_TOLERANCE < length {
+        return Some(format!(
+            "Playlist is too short, <yellow>{}</> are missing",
+            sec_to_time(length - playtime)
+        ));
+    }
+
+    None
+}
+
+#[derive(Clone, Debug)]
+struct Problem {
+    text: String,
+    notified: bool,
+}
+
+async fn check_channel(
+    config: &PlayoutConfig,
+    store: &PlaylistStorage,
+    problems: &mut HashMap<(i32, String), Problem>,
+) {
+    let id = config.general.channel_id;
+    let now = time_now(&config.channel.timezone).naive_local();
+    let today = now.date();
+
+    for day in 0..=config.playlist.check_days {
+        let Some(date) = today.checked_add_signed(TimeDelta::days(day)) else {
+            continue;
+        };
+        let config = config.for_weekday(date.weekday());
+        let starts = start_time(date, config.playlist.start_sec.unwrap_or_default());
+
+        // playlist is already on air
+        if starts <= now {
+            continue;
+        }
+
+        let key = (id, date.format("%Y-%m-%d").to_string());
+        let playlist = match store.read(&key.1).await {
+            Ok(playlist) => playlist,
+            Err(e) => {
+                error!(target: Target::file(), channel = id; "Check playlist from <yellow>{}</> failed: {e}", key.1);
+                continue;
+            }
+        };
+
+        let Some(text) = check_playlist(playlist.as_ref(), &config) else {
+            if problems.remove(&key).is_some_and(|p| p.notified) {
+                info!(target: Target::file_mail(), channel = id; "Playlist from <yellow>{}</> is fine now", key.1);
+            }
+
+            continue;
+        };
+
+        let hours = (starts - now).num_minutes() as f64 / 60.0;
+        let problem = problems.entry(key.clone()).or_insert(Problem {
+            text: String::new(),
+            notified: false,
+        });
+
+        if problem.text != text {
+            warn!(target: Target::file(), channel = id; "Playlist from <yellow>{}</>: {text}", key.1);
+            problem.text = text;
+            problem.notified = false;
+        }
+
+        if !problem.notified && hours <= config.playlist.check_hours as f64 {
+            error!(target: Target::file_mail(), channel = id;
+                "Playlist from <yellow>{}</> starts in <yellow>{hours:.1}</> hours: {}",
+                key.1,
+                problem.text
+            );
+            problem.notified = true;
+        }
+    }
+
+    // forget dates which are on air or out of range now
+    let last = today + TimeDelta::days(config.playlist.check_days);
+    problems.retain(|(channel, date), _| {
+        *channel != id
+            || NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok_and(|d| d >= today && d <= last)
+    });
+}
+
+fn start_time(date: NaiveDate, start_sec: f64) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default()
+        + TimeDelta::try_milliseconds((start_sec * 1000.0) as i64).unwrap_or_default()
+}
+
+/// Check loop, runs in its own task as long as the server is running.
+pub async fn run(controllers: Arc<Mutex<ChannelController>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL));
+    let mut problems = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let managers = controllers.lock().await.channels.clone();
+
+        for manager in managers {
+            if !manager.channel.lock().await.active {
+                continue;
+            }
+
+            let config = manager.config.lock().await.clone();
+
+            if config.playlist.check_days < 1 || config.processing.mode != ProcessMode::Playlist {
+                continue;
+            }
+
+            let store = PlaylistStorage::new(&config, manager.db_pool.clone());
+
+            check_channel(&config, &store, &mut problems).await;
+        }
+    }
+}
+
+/// Result from `--validate-all` for one channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidateSummary {
+    pub channel: i32,
+    pub name: String,
+    pub checked: usize,
+    /// Dates with problems, with the number of errors.
+    pub failed: Vec<(String, usize)>,
+}
+
+impl ValidateSummary {
+    /// Summary lines, for the terminal.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Channel {} ({}): {} playlists checked, {} with errors",
+            self.channel,
+            self.name,
+            self.checked,
+            self.failed.len()
+        )];
+
+        for (date, errors) in &self.failed {
+            lines.push(format!("  {date}: {errors} errors"));
+        }
+
+        lines
+    }
+}
+
+async fn validate_channel(
+    pool: &Pool<Sqlite>,
+    id: i32,
+    name: String,
+) -> Result<ValidateSummary, ServiceError> {
+    let mut config = get_config(pool, id).await?;
+    config.general.validate = true;
+
+    let store = PlaylistStorage::new(&config, Some(pool.clone()));
+    let today = get_date(
+        true,
+        config.playlist.start_sec.unwrap_or_default(),
+        false,
+        &config.channel.timezone,
+    );
+    let mut summary = ValidateSummary {
+        channel: id,
+        name,
+        ..Default::default()
+    };
+
+    for date in store.dates(&today).await? {
+        summary.checked += 1;
+
+        let playlist = match store.read(&date).await {
+            Ok(Some(playlist)) => playlist,
+            Ok(None) => continue,
+            Err(e) => {
+                error!(target: Target::file(), channel = id; "Playlist from <yellow>{date}</> is not readable: {e}");
+                summary.failed.push((date, 1));
+                continue;
+            }
+        };
+
+        let config = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map(|d| config.for_weekday(d.weekday()))
+            .unwrap_or(config.clone());
+
+        let errors = validate_playlist(
+            config,
+            Arc::new(Mutex::new(Vec::new())),
+            playlist,
+            Arc::new(AtomicBool::new(true)),
+        )
+        .await;
+
+        if errors > 0 {
+            summary.failed.push((date, errors));
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Validate the stored playlists from today on, from all or the selected channels. Returns the
+/// exit code, `1` when a playlist has errors.
+pub async fn validate_all(pool: &Pool<Sqlite>) -> i32 {
+    let channels = match handles::select_related_channels(pool, None).await {
+        Ok(channels) => channels,
+        Err(e) => {
+            eprintln!("Read channels failed: {e}");
+            return 1;
+        }
+    };
+    let mut code = 0;
+
+    for channel in channels {
+        if ARGS
+            .channel
+            .as_ref()
+            .is_some_and(|c| !c.contains(&channel.id))
+        {
+            continue;
+        }
+
+        match validate_channel(pool, channel.id, channel.name.clone()).await {
+            Ok(summary) => {
+                if !summary.failed.is_empty() {
+                    code = 1;
+                }
+
+                for line in summary.lines() {
+                    println!("{line}");
+                }
+            }
+            Err(e) => {
+                println!(
+                    "Channel {} ({}): validation failed: {e}",
+                    channel.id, channel.name
+                );
+                code = 1;
+            }
+        }
+    }
+
+    code
+}
@@ -0,0 +1,114 @@
+/// Share Links
+///
+/// A share link opens one file from the channel storage without login, until it expires. The
+/// link has the channel, the path and the expire time, signed with a key from the global secret,
+/// so links can't be changed to other files or times. A new global secret ends all links.
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    hkdf::{Salt, HKDF_SHA256},
+    hmac,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::errors::ServiceError;
+
+/// Seconds a link is valid, without `lifetime` in the request.
+pub const DEFAULT_LIFETIME: i64 = 86400;
+
+/// Longest lifetime from a link, 30 days.
+pub const MAX_LIFETIME: i64 = 30 * 86400;
+
+#[derive(Debug, Deserialize)]
+pub struct ShareRequest {
+    /// File, relative to the channel storage.
+    pub source: String,
+    /// Seconds the link is valid.
+    pub lifetime: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ShareLink {
+    pub url: String,
+    /// Unix timestamp.
+    pub expires: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareQuery {
+    pub expires: i64,
+    pub signature: String,
+    #[serde(default)]
+    pub download: bool,
+}
+
+fn key(secret: &str) -> hmac::Key {
+    let prk = Salt::new(HKDF_SHA256, b"ffplayout").extract(secret.as_bytes());
+    let okm = prk
+        .expand(&[b"share links"], hmac::HMAC_SHA256)
+        .expect("Derive share key");
+
+    hmac::Key::from(okm)
+}
+
+fn message(channel: i32, path: &str, expires: i64) -> String {
+    format!("{channel}\n{}\n{expires}", path.trim_start_matches('/'))
+}
+
+/// Signature for the file from the channel, valid until `expires`.
+pub fn sign(secret: &str, channel: i32, path: &str, expires: i64) -> String {
+    let tag = hmac::sign(&key(secret), message(channel, path, expires).as_bytes());
+
+    URL_SAFE_NO_PAD.encode(tag.as_ref())
+}
+
+/// Check the signature and the expire time, `now` is a unix timestamp.
+pub fn verify(
+    secret: &str,
+    channel: i32,
+    path: &str,
+    query: &ShareQuery,
+    now: i64,
+) -> Result<(), ServiceError> {
+    let signature = URL_SAFE_NO_PAD
+        .decode(&query.signature)
+        .map_err(|_| ServiceError::Forbidden("Share link is not valid".to_string()))?;
+
+    hmac::verify(
+        &key(secret),
+        message(channel, path, query.expires).as_bytes(),
+        &signature,
+    )
+    .map_err(|_| ServiceError::Forbidden("Share link is not valid".to_string()))?;
+
+    if query.expires < now {
+        return Err(ServiceError::Forbidden("Share link is expired".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Percent encoding for the path in the link, the slashes stay.
+pub fn encode_path(path: &str) -> String {
+    path.trim_start_matches('/')
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+/// Link for the file, relative to the server address.
+pub fn share_link(secret: &str, channel: i32, path: &str, expires: i64) -> ShareLink {
+    ShareLink {
+        url: format!(
+            "/share/{channel}/{}?expires={expires}&signature={}",
+            encode_path(path),
+            sign(secret, channel, path, expires)
+        ),
+        expires,
+    }
+}
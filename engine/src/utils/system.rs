@@ -1,10 +1,13 @@
-use std::fmt;
+use std::{fmt, path::Path};
 
 use local_ip_address::list_afinet_netifas;
 use serde::Serialize;
 use sysinfo::System;
 
-use crate::utils::config::PlayoutConfig;
+use crate::utils::{
+    clock::{self, ClockStatus},
+    config::PlayoutConfig,
+};
 use crate::{DISKS, NETWORKS, SYS};
 
 const IGNORE_INTERFACES: [&str; 7] = ["docker", "lxdbr", "tab", "tun", "virbr", "veth", "vnet"];
@@ -69,6 +72,7 @@ pub struct SystemStat {
     pub storage: Storage,
     pub swap: Swap,
     pub system: MySystem,
+    pub clock: ClockStatus,
 }
 
 impl fmt::Display for SystemStat {
@@ -172,5 +176,18 @@ pub fn stat(config: &PlayoutConfig) -> SystemStat {
         storage,
         swap,
         system,
+        clock: clock::status(),
     }
 }
+
+/// Total and available space on the disk, which holds the path.
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let mut disks = DISKS.lock().ok()?;
+    disks.refresh(true);
+
+    disks
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| (d.total_space(), d.available_space()))
+}
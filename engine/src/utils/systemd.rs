@@ -0,0 +1,121 @@
+/// Systemd Integration
+///
+/// With `Type=notify` systemd waits for `READY=1` before the unit counts as started, so a failed
+/// start shows up as failed unit, instead of a restart loop. With `WatchdogSec` the engine sends
+/// keep alive pings from the runtime, a blocked engine gets restarted. With a socket unit the API
+/// listener comes from systemd. Without systemd the functions do nothing.
+use std::{env, net::TcpListener, time::Duration};
+
+use log::*;
+
+/// First file descriptor from the socket activation.
+#[cfg(target_os = "linux")]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Send a state to systemd, like `READY=1`. Returns `false` without notify socket.
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) -> bool {
+    use std::os::{
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram},
+    };
+
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return false;
+    };
+
+    // a leading @ stands for the abstract namespace
+    let result = match path.as_encoded_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)
+            .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr)),
+        None => socket.send_to(state.as_bytes(), &path),
+    };
+
+    if let Err(e) = &result {
+        debug!("Notify systemd with <yellow>{state}</> failed: {e}");
+    }
+
+    result.is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) -> bool {
+    false
+}
+
+/// Interval for the watchdog pings, half from `WatchdogSec`, when the watchdog is for us.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+
+    if env::var("WATCHDOG_PID")
+        .ok()
+        .is_some_and(|pid| pid.parse::<u32>().ok() != Some(std::process::id()))
+    {
+        return None;
+    }
+
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Ping the systemd watchdog, as long as the runtime is alive.
+pub async fn watchdog(interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+
+    loop {
+        timer.tick().await;
+        notify("WATCHDOG=1");
+    }
+}
+
+/// Number of sockets from the socket activation, when they are for us.
+///
+/// The variables are removed, so child processes don't take the sockets. Changing the
+/// environment is only safe while no other thread runs, call it first in `main`.
+#[cfg(target_os = "linux")]
+pub fn listen_fds() -> Option<i32> {
+    let pid = env::var("LISTEN_PID").ok()?.parse::<u32>().ok();
+    let fds = env::var("LISTEN_FDS").ok()?.parse::<i32>().ok();
+
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    if pid != Some(std::process::id()) {
+        return None;
+    }
+
+    fds.filter(|fds| *fds > 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listen_fds() -> Option<i32> {
+    None
+}
+
+/// Listener from the socket activation, with the number of sockets from [`listen_fds`].
+/// Only the first socket is used, it must be a TCP socket.
+#[cfg(target_os = "linux")]
+pub fn listener(fds: Option<i32>) -> Option<TcpListener> {
+    use std::os::unix::io::FromRawFd;
+
+    fds?;
+
+    // systemd passes the sockets open, starting from fd 3
+    let listener = unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) };
+
+    match listener.set_nonblocking(true) {
+        Ok(_) => Some(listener),
+        Err(e) => {
+            error!("Socket from systemd is not usable: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listener(_fds: Option<i32>) -> Option<TcpListener> {
+    None
+}
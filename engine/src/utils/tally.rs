@@ -0,0 +1,273 @@
+/// Tally
+///
+/// Studio tally lights and multiviewers show what is on air: the program, a live ingest or the
+/// emergency slate. `general.tally` has the targets, separated by `;`, which get every change:
+///
+/// - `http://` and `https://` URLs get a POST with the tally as JSON.
+/// - `mqtt://` URLs publish the JSON, retained, to the topic from the path.
+/// - Other targets are helper programs, like for GPIO, which run with the channel id and the tally
+///   as arguments.
+///
+/// `{channel}` and `{tally}` are replaced in the targets.
+use std::{fmt, process::Stdio, sync::atomic::Ordering, time::Duration};
+
+use log::*;
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    process::Command,
+    time::timeout,
+};
+
+use crate::player::{controller::ChannelManager, utils::channel_state::ChannelState};
+use crate::utils::{errors::ProcessError, logging::Target, secrets};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Tally {
+    #[default]
+    Off,
+    Program,
+    Live,
+    Emergency,
+}
+
+impl Tally {
+    pub fn new(state: ChannelState, emergency: bool) -> Self {
+        match state {
+            ChannelState::Stopped
+            | ChannelState::Starting
+            | ChannelState::Stopping
+            | ChannelState::Failed => Self::Off,
+            _ if emergency => Self::Emergency,
+            ChannelState::OnAirLive => Self::Live,
+            ChannelState::OnAirPlaylist | ChannelState::Fallback => Self::Program,
+        }
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Off => write!(f, "off"),
+            Self::Program => write!(f, "program"),
+            Self::Live => write!(f, "live"),
+            Self::Emergency => write!(f, "emergency"),
+        }
+    }
+}
+
+fn mqtt_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// MQTT packet with the fixed header, the length is a variable byte integer.
+pub fn mqtt_packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut length = body.len();
+
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+
+        if length > 0 {
+            byte |= 0x80;
+        }
+
+        packet.push(byte);
+
+        if length == 0 {
+            break;
+        }
+    }
+
+    packet.extend_from_slice(body);
+
+    packet
+}
+
+/// CONNECT for MQTT 3.1.1, with clean session.
+pub fn mqtt_connect(client_id: &str, username: &str, password: Option<&str>) -> Vec<u8> {
+    let mut flags = 0x02;
+    let mut body = vec![];
+
+    if !username.is_empty() {
+        flags |= 0x80;
+    }
+
+    if password.is_some() {
+        flags |= 0x40;
+    }
+
+    mqtt_string(&mut body, b"MQTT");
+    body.extend_from_slice(&[4, flags, 0, 30]);
+    mqtt_string(&mut body, client_id.as_bytes());
+
+    if !username.is_empty() {
+        mqtt_string(&mut body, username.as_bytes());
+    }
+
+    if let Some(password) = password {
+        mqtt_string(&mut body, password.as_bytes());
+    }
+
+    mqtt_packet(0x10, &body)
+}
+
+/// PUBLISH with QoS 0 and the retain flag, so new subscribers get the current tally.
+pub fn mqtt_publish(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = vec![];
+
+    mqtt_string(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+
+    mqtt_packet(0x31, &body)
+}
+
+async fn publish(id: i32, target: &str, payload: &str) -> Result<(), ProcessError> {
+    let url = reqwest::Url::parse(target).map_err(|e| ProcessError::Custom(e.to_string()))?;
+    let host = url.host_str().unwrap_or("127.0.0.1");
+    let topic = url.path().trim_start_matches('/');
+
+    if topic.is_empty() {
+        return Err(ProcessError::Custom("MQTT target has no topic".to_string()));
+    }
+
+    let mut stream = TcpStream::connect((host, url.port().unwrap_or(1883))).await?;
+    let mut ack = [0; 4];
+
+    stream
+        .write_all(&mqtt_connect(
+            &format!("ffplayout-{id}"),
+            url.username(),
+            url.password(),
+        ))
+        .await?;
+    stream.read_exact(&mut ack).await?;
+
+    // CONNACK with return code 0
+    if ack[0] != 0x20 || ack[3] != 0 {
+        return Err(ProcessError::Custom(format!(
+            "MQTT broker refused the connection with code {}",
+            ack[3]
+        )));
+    }
+
+    stream
+        .write_all(&mqtt_publish(topic, payload.as_bytes()))
+        .await?;
+    stream.write_all(&[0xE0, 0]).await?;
+
+    Ok(())
+}
+
+async fn post(target: &str, payload: &str) -> Result<(), ProcessError> {
+    let response = reqwest::Client::new()
+        .post(target)
+        .header("Content-Type", "application/json")
+        .body(payload.to_string())
+        .send()
+        .await
+        .map_err(|e| ProcessError::Custom(e.without_url().to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(ProcessError::Custom(format!(
+            "Tally request failed with status {}",
+            response.status()
+        )));
+    }
+
+    Ok(())
+}
+
+async fn helper(id: i32, target: &str, tally: Tally) -> Result<(), ProcessError> {
+    let args = shlex::split(target)
+        .filter(|a| !a.is_empty())
+        .ok_or_else(|| ProcessError::Custom(format!("Broken tally command: {target}")))?;
+
+    let output = Command::new(&args[0])
+        .args(&args[1..])
+        .args([id.to_string(), tally.to_string()])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(ProcessError::Custom(format!(
+            "Tally helper failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Send the tally to one target.
+pub async fn send(
+    id: i32,
+    target: &str,
+    tally: Tally,
+    state: ChannelState,
+) -> Result<(), ProcessError> {
+    let target = target
+        .replace("{channel}", &id.to_string())
+        .replace("{tally}", &tally.to_string());
+    let payload = json!({
+        "channel": id,
+        "tally": tally.to_string(),
+        "state": state.to_string(),
+    })
+    .to_string();
+
+    let result = if target.starts_with("http://") || target.starts_with("https://") {
+        timeout(TIMEOUT, post(&target, &payload)).await
+    } else if target.starts_with("mqtt://") {
+        timeout(TIMEOUT, publish(id, &target, &payload)).await
+    } else {
+        timeout(TIMEOUT * 2, helper(id, &target, tally)).await
+    };
+
+    result.map_err(|_| ProcessError::Custom("Tally target takes too long".to_string()))?
+}
+
+/// Send the tally, when it changed. Runs in its own task, the lock keeps the order of the changes.
+pub fn update(manager: &ChannelManager) {
+    let manager = manager.clone();
+
+    tokio::spawn(async move {
+        let mut sent = manager.tally.lock().await;
+        let state = manager.state.lock().await.state;
+        let tally = Tally::new(state, manager.emergency.load(Ordering::SeqCst));
+
+        if *sent == tally {
+            return;
+        }
+
+        *sent = tally;
+
+        let config = manager.config.lock().await.clone();
+        let id = config.general.channel_id;
+
+        if config.general.tally.trim().is_empty() {
+            return;
+        }
+
+        info!(target: Target::file(), channel = id; "Tally: <yellow>{tally}</>");
+
+        for target in config.general.tally.split(';').map(str::trim) {
+            if target.is_empty() {
+                continue;
+            }
+
+            if let Err(e) = send(id, target, tally, state).await {
+                error!(target: Target::file_mail(), channel = id;
+                    "Tally <b><magenta>{}</></b>: {e}", secrets::redact(target)
+                );
+            }
+        }
+    });
+}
@@ -0,0 +1,272 @@
+/// Text Layers
+///
+/// Next to the default text, `text.layers` names more drawtext filters, like
+/// `lower_third,ticker`, which get their text independent from each other, over the text
+/// endpoint with `layer`. A message can bring its own font, fade or slide in and out, and go
+/// away after `expire` seconds. The animations run in steps over ZeroMQ, a new message for the
+/// layer stops a running animation from the layer.
+use std::time::Duration;
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{controller::ChannelManager, filter::v_drawtext::zmq_text};
+use crate::utils::{
+    config::PlayoutConfig, control::filter_command, errors::ServiceError, files::norm_abs_path,
+    logging::Target, TextFilter,
+};
+
+/// Instance name from the default text.
+pub const DEFAULT_LAYER: &str = "dyntext";
+
+/// Time between the animation steps.
+const STEP: Duration = Duration::from_millis(40);
+
+/// Seconds from an animation, without `duration` in the message.
+const DEFAULT_DURATION: f64 = 0.5;
+
+/// Animation for the text, slides come in from the edge and go out to it.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAnimation {
+    #[default]
+    None,
+    Fade,
+    SlideLeft,
+    SlideRight,
+    SlideTop,
+    SlideBottom,
+}
+
+/// Valid layer names, filter instances can only have letters, numbers and underscores.
+pub fn layer_names(layers: &str) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+
+    for name in layers.split(',').map(str::trim) {
+        if !name.is_empty()
+            && name != DEFAULT_LAYER
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+
+    names
+}
+
+/// Message from the layer in the filter chain, the default text has no prefix.
+pub fn layer_text(chain: &[String], layer: &str) -> Option<String> {
+    if layer == DEFAULT_LAYER {
+        return chain
+            .iter()
+            .find(|l| !l.starts_with('@') && l.contains("text"))
+            .cloned();
+    }
+
+    let prefix = format!("@{layer} ");
+
+    chain
+        .iter()
+        .find_map(|l| l.strip_prefix(&prefix).map(str::to_string))
+}
+
+/// Keep the message for the layer, so the next clips start with it.
+pub fn store_text(chain: &mut Vec<String>, layer: &str, message: String) {
+    if layer == DEFAULT_LAYER {
+        chain.retain(|l| l.starts_with('@'));
+        chain.insert(0, message);
+    } else {
+        let prefix = format!("@{layer} ");
+
+        chain.retain(|l| !l.starts_with(&prefix));
+        chain.push(format!("{prefix}{message}"));
+    }
+}
+
+/// Value from the message, or from the style, like `x=(w-text_w)/2`.
+fn option_value(value: &Option<String>, style: &str, name: &str) -> String {
+    if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+        return value.clone();
+    }
+
+    style
+        .split(':')
+        .find_map(|o| o.trim().strip_prefix(&format!("{name}=")))
+        .map(|v| v.trim_matches('\'').to_string())
+        .unwrap_or_else(|| "0".to_string())
+}
+
+/// Message for a step from the animation, `k` goes from `0` (in place) to `1` (out of view).
+pub fn animation_frame(
+    message: &TextFilter,
+    style: &str,
+    animation: TextAnimation,
+    k: f64,
+) -> TextFilter {
+    let mut frame = message.clone();
+    let x = option_value(&message.x, style, "x");
+    let y = option_value(&message.y, style, "y");
+
+    match animation {
+        TextAnimation::None => {}
+        TextAnimation::Fade => {
+            let alpha = message
+                .alpha
+                .as_ref()
+                .and_then(|a| a.parse::<f64>().ok())
+                .unwrap_or(1.0);
+
+            frame.alpha = Some(format!("{:.3}", alpha * (1.0 - k)));
+        }
+        TextAnimation::SlideLeft => frame.x = Some(format!("({x})-(({x})+text_w)*{k:.3}")),
+        TextAnimation::SlideRight => frame.x = Some(format!("({x})+(w-({x}))*{k:.3}")),
+        TextAnimation::SlideTop => frame.y = Some(format!("({y})-(({y})+text_h)*{k:.3}")),
+        TextAnimation::SlideBottom => frame.y = Some(format!("({y})+(h-({y}))*{k:.3}")),
+    }
+
+    frame
+}
+
+/// Check the layer and the font from the message, the font gets its full path.
+/// Returns the layer name.
+pub fn check_message(
+    config: &PlayoutConfig,
+    message: &mut TextFilter,
+) -> Result<String, ServiceError> {
+    let layer = message
+        .layer
+        .clone()
+        .filter(|l| !l.is_empty())
+        .unwrap_or_else(|| DEFAULT_LAYER.to_string());
+
+    if layer != DEFAULT_LAYER && !layer_names(&config.text.layers).contains(&layer) {
+        return Err(ServiceError::BadRequest(format!(
+            "Text layer {layer} not found"
+        )));
+    }
+
+    if let Some(font) = message.font.as_ref().filter(|f| !f.is_empty()) {
+        let (path, _, _) = norm_abs_path(&config.channel.storage, font)?;
+
+        if !path.is_file() {
+            return Err(ServiceError::BadRequest(format!("Font {font} not found")));
+        }
+
+        message.font = Some(path.to_string_lossy().to_string());
+    }
+
+    if message.expire.is_some_and(|e| !e.is_finite() || e < 0.0)
+        || message.duration.is_some_and(|d| !d.is_finite() || d < 0.0)
+    {
+        return Err(ServiceError::BadRequest(
+            "Duration and expire must be positive seconds".to_string(),
+        ));
+    }
+
+    Ok(layer)
+}
+
+/// Number for the next message from the layer.
+pub async fn next_message(manager: &ChannelManager, layer: &str) -> u64 {
+    let mut layers = manager.text_layers.lock().await;
+    let number = layers.entry(layer.to_string()).or_default();
+    *number += 1;
+
+    *number
+}
+
+async fn is_current(manager: &ChannelManager, layer: &str, number: u64) -> bool {
+    manager.text_layers.lock().await.get(layer) == Some(&number)
+}
+
+async fn send_frame(manager: &ChannelManager, layer: &str, frame: &TextFilter) -> bool {
+    let config = manager.config.lock().await.clone();
+    let command = format!(
+        "drawtext@{layer} reinit {}",
+        zmq_text(&config, &frame.to_string())
+    );
+
+    match filter_command(manager, &command).await {
+        Ok(_) => true,
+        Err(e) => {
+            debug!(target: Target::file(), channel = config.general.channel_id; "Text layer {layer}: {e}");
+            false
+        }
+    }
+}
+
+/// Run the animation, `false` when a new message came or the filter is gone.
+async fn run_animation(
+    manager: &ChannelManager,
+    layer: &str,
+    message: &TextFilter,
+    number: u64,
+    animation: TextAnimation,
+    coming_in: bool,
+) -> bool {
+    let style = manager.config.lock().await.text.style.clone();
+    let duration = message.duration.unwrap_or(DEFAULT_DURATION);
+    let steps = (duration / STEP.as_secs_f64()).ceil().max(1.0) as u32;
+
+    for step in 1..=steps {
+        if !is_current(manager, layer, number).await {
+            return false;
+        }
+
+        let progress = f64::from(step) / f64::from(steps);
+        let k = if coming_in { 1.0 - progress } else { progress };
+
+        if !send_frame(
+            manager,
+            layer,
+            &animation_frame(message, &style, animation, k),
+        )
+        .await
+        {
+            return false;
+        }
+
+        tokio::time::sleep(STEP).await;
+    }
+
+    true
+}
+
+/// Animate the message in, and out again after the expire time.
+pub async fn animate(manager: ChannelManager, layer: String, message: TextFilter, number: u64) {
+    let animated = |a: Option<TextAnimation>| a.filter(|a| *a != TextAnimation::None);
+
+    if let Some(animation) = animated(message.animation_in) {
+        if !run_animation(&manager, &layer, &message, number, animation, true).await {
+            return;
+        }
+    }
+
+    let Some(expire) = message.expire else {
+        return;
+    };
+
+    tokio::time::sleep(Duration::from_secs_f64(expire)).await;
+
+    if let Some(animation) = animated(message.animation_out) {
+        if !run_animation(&manager, &layer, &message, number, animation, false).await {
+            return;
+        }
+    }
+
+    if !is_current(&manager, &layer, number).await {
+        return;
+    }
+
+    let clear = TextFilter {
+        text: Some(String::new()),
+        ..Default::default()
+    };
+
+    if let Some(chain) = &manager.filter_chain {
+        store_text(&mut *chain.lock().await, &layer, clear.to_string());
+    }
+
+    send_frame(&manager, &layer, &clear).await;
+}
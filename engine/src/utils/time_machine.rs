@@ -10,6 +10,8 @@ use std::{
 use chrono::{prelude::*, TimeDelta};
 use chrono_tz::Tz;
 
+use crate::utils::clock;
+
 // Thread-local storage for time offset when mocking the time
 static DATE_TIME_DIFF: LazyLock<Arc<RwLock<Option<TimeDelta>>>> =
     LazyLock::new(|| Arc::new(RwLock::new(None)));
@@ -38,7 +40,7 @@ pub fn set_mock_time(fake_time: &Option<String>) -> Result<(), io::Error> {
 
 // Function to get the current time, using either real or mock time based on `--fake-time`
 pub fn time_now(timezone: &Option<Tz>) -> DateTime<Tz> {
-    let utc_now: DateTime<Utc> = Utc::now();
+    let utc_now: DateTime<Utc> = Utc::now() + clock::offset();
 
     let tz = match timezone {
         Some(tz) => *tz,
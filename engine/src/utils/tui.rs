@@ -0,0 +1,210 @@
+/// Terminal Dashboard
+///
+/// With `--tui` the foreground mode shows a dashboard instead of the log output: one row per
+/// channel with state, current clip, remaining time and encoder speed, and the recent log lines
+/// below. It is redrawn every second, so it works over SSH without extra terminal features.
+use std::{collections::VecDeque, io::Write, sync::Arc, time::Duration};
+
+use chrono::Local;
+use log::Level;
+#[cfg(unix)]
+use nix::libc;
+use tokio::sync::{broadcast::error::RecvError, Mutex};
+
+use crate::player::{
+    controller::ChannelController,
+    utils::{played_since, sec_to_time, time_in_seconds, Media},
+};
+use crate::utils::logging::{strip_tags, LogLine, LOG_STREAM};
+
+/// Encoder stats which are older are not shown anymore.
+const STATS_TIMEOUT: i64 = 5;
+
+/// Size, when the terminal has none, like in a pipe, or on systems without `TIOCGWINSZ`.
+const DEFAULT_SIZE: (usize, usize) = (100, 30);
+
+/// Row for one channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelRow {
+    pub id: i32,
+    pub name: String,
+    pub state: String,
+    pub clip: String,
+    pub remaining: Option<f64>,
+    pub speed: Option<f64>,
+}
+
+/// Seconds until the clip ends, `None` without clip or begin.
+pub fn remaining(media: &Media, now: f64, shift: f64) -> Option<f64> {
+    let played = played_since(media.begin? - shift, now);
+    let length = (media.out - media.seek) / media.speed.unwrap_or(1.0);
+
+    Some((length - played).max(0.0))
+}
+
+/// Cut the text to the width, with `…` at the end.
+pub fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return format!("{text:width$}");
+    }
+
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+
+    cut
+}
+
+#[cfg(unix)]
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_col > 0
+        && size.ws_row > 0
+    {
+        return (size.ws_col as usize, size.ws_row as usize);
+    }
+
+    DEFAULT_SIZE
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> (usize, usize) {
+    DEFAULT_SIZE
+}
+
+async fn channel_rows(controllers: &Arc<Mutex<ChannelController>>) -> Vec<ChannelRow> {
+    let managers = controllers.lock().await.channels.clone();
+    let mut rows = vec![];
+
+    for manager in managers {
+        let channel = manager.channel.lock().await.clone();
+        let media = manager.current_media.lock().await.clone();
+        let stats = manager.encoder_stats.lock().await.clone();
+        let now = time_in_seconds(&channel.timezone);
+
+        let clip = media
+            .as_ref()
+            .map(|m| m.title.clone().unwrap_or(m.source.clone()))
+            .unwrap_or_default();
+        let speed = stats
+            .updated
+            .filter(|t| (Local::now() - *t).num_seconds() < STATS_TIMEOUT)
+            .map(|_| stats.speed);
+
+        rows.push(ChannelRow {
+            id: channel.id,
+            name: channel.name,
+            state: manager.state.lock().await.state.to_string(),
+            clip,
+            remaining: media
+                .as_ref()
+                .and_then(|m| remaining(m, now, channel.time_shift)),
+            speed,
+        });
+    }
+
+    rows
+}
+
+fn level_tag(level: Level) -> &'static str {
+    match level {
+        Level::Error => "\x1b[91m[ERROR]\x1b[0m",
+        Level::Warn => "\x1b[33m[ WARN]\x1b[0m",
+        Level::Info => "\x1b[92m[ INFO]\x1b[0m",
+        Level::Debug => "\x1b[94m[DEBUG]\x1b[0m",
+        Level::Trace => "\x1b[93m[TRACE]\x1b[0m",
+    }
+}
+
+/// Whole screen, as one string.
+fn draw(rows: &[ChannelRow], logs: &VecDeque<LogLine>, (width, height): (usize, usize)) -> String {
+    let clip_width = width.saturating_sub(62).max(10);
+    let mut screen = String::from("\x1b[H\x1b[J");
+
+    screen.push_str(&format!(
+        "\x1b[1mffplayout\x1b[0m  {}  Ctrl+C: stop\r\n\r\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S")
+    ));
+    screen.push_str(&format!(
+        "\x1b[7m{:>3}  {}  {}  {}  {:>8}  {:>6}\x1b[0m\r\n",
+        "ID",
+        fit("Channel", 16),
+        fit("State", 16),
+        fit("Clip", clip_width),
+        "Left",
+        "Speed"
+    ));
+
+    for row in rows {
+        let speed = row
+            .speed
+            .map(|s| format!("{s:.2}x"))
+            .unwrap_or("-".to_string());
+        let speed = if row.speed.is_some_and(|s| s < 0.98) {
+            format!("\x1b[91m{speed:>6}\x1b[0m")
+        } else {
+            format!("{speed:>6}")
+        };
+
+        screen.push_str(&format!(
+            "{:>3}  {}  {}  {}  {:>8}  {speed}\r\n",
+            row.id,
+            fit(&row.name, 16),
+            fit(&row.state, 16),
+            fit(&row.clip, clip_width),
+            row.remaining
+                .map(|r| sec_to_time(r).chars().take(8).collect())
+                .unwrap_or("-".to_string()),
+        ));
+    }
+
+    screen.push_str("\r\n\x1b[1mLog\x1b[0m\r\n");
+
+    let space = height.saturating_sub(rows.len() + 6);
+
+    for line in logs.iter().skip(logs.len().saturating_sub(space)) {
+        let time = line.timestamp.get(11..19).unwrap_or_default();
+        let prefix = format!("{time} {} {}: ", level_tag(line.level), line.channel);
+        // the level tag has 9 invisible chars
+        let text_width = width.saturating_sub(prefix.chars().count() - 9);
+
+        screen.push_str(&format!(
+            "{prefix}{}\r\n",
+            fit(&strip_tags(&line.message), text_width).trim_end()
+        ));
+    }
+
+    screen
+}
+
+/// Dashboard loop, runs as long as the foreground channels run.
+pub async fn run(controllers: Arc<Mutex<ChannelController>>) {
+    let mut log_rx = LOG_STREAM.subscribe();
+    let mut logs = VecDeque::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let rows = channel_rows(&controllers).await;
+                let screen = draw(&rows, &logs, terminal_size());
+                let mut stdout = std::io::stdout().lock();
+
+                let _ = stdout.write_all(screen.as_bytes());
+                let _ = stdout.flush();
+            }
+            line = log_rx.recv() => match line {
+                Ok(line) => {
+                    logs.push_back(line);
+
+                    if logs.len() > 200 {
+                        logs.pop_front();
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+}
@@ -0,0 +1,202 @@
+/// Uptime Reports
+///
+/// Every time a channel goes on or off air, the change is saved with its cause in the database.
+/// From these events a monthly report is built, with the on-air time in percent and a list of all outages.
+/// Time before the first event of a channel is not counted.
+use chrono::{Local, Months, NaiveDate, NaiveDateTime};
+use log::*;
+use serde::Serialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::db::{handles, models::ChannelEvent};
+use crate::utils::{errors::ServiceError, logging::Target, play_history::HISTORY_TIME_FORMAT};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Outage {
+    pub start: String,
+    pub end: String,
+    /// Duration in seconds.
+    pub duration: f64,
+    pub cause: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AvailabilityReport {
+    pub channel_id: i32,
+    pub month: String,
+    /// Begin of the measured time, later than the month start when there was no event before.
+    pub start: String,
+    pub end: String,
+    pub on_air: f64,
+    pub off_air: f64,
+    /// On-air time in percent.
+    pub availability: f64,
+    pub outages: Vec<Outage>,
+}
+
+impl AvailabilityReport {
+    /// Summary and outages as two CSV tables, separated by an empty line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!(
+            "channel_id,month,start,end,on_air,off_air,availability\n{},{},{},{},{:.3},{:.3},{:.2}\n\nstart,end,duration,cause\n",
+            self.channel_id,
+            self.month,
+            self.start,
+            self.end,
+            self.on_air,
+            self.off_air,
+            self.availability
+        );
+
+        for outage in &self.outages {
+            csv.push_str(&format!(
+                "{},{},{:.3},\"{}\"\n",
+                outage.start,
+                outage.end,
+                outage.duration,
+                outage.cause.replace('"', "\"\"")
+            ));
+        }
+
+        csv
+    }
+}
+
+/// Save on-air change, errors are only logged.
+pub async fn record(pool: Pool<Sqlite>, channel_id: i32, on_air: bool, cause: String) {
+    let event = ChannelEvent {
+        id: 0,
+        channel_id,
+        time: Local::now().format(HISTORY_TIME_FORMAT).to_string(),
+        on_air,
+        cause,
+    };
+
+    if let Err(e) = handles::insert_channel_event(&pool, &event).await {
+        error!(target: Target::file_mail(), channel = channel_id; "Save channel event failed: {e}");
+    }
+}
+
+/// First and last time from a month in `YYYY-MM` format.
+pub fn month_range(month: &str) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let start = NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").ok()?;
+    let end = start.checked_add_months(Months::new(1))?;
+
+    Some((start.and_hms_opt(0, 0, 0)?, end.and_hms_opt(0, 0, 0)?))
+}
+
+/// Build the report from the events between `start` and `end`,
+/// `initial` is the last event before `start`.
+pub fn build_report(
+    channel_id: i32,
+    month: &str,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    initial: Option<ChannelEvent>,
+    events: &[ChannelEvent],
+) -> AvailabilityReport {
+    let format = |t: NaiveDateTime| t.format(HISTORY_TIME_FORMAT).to_string();
+    let mut state = initial.map(|e| (e.on_air, start, e.cause));
+    let mut measured = state.as_ref().map(|_| start);
+    let mut on_air = 0.0;
+    let mut off_air = 0.0;
+    let mut outages = vec![];
+
+    let mut close = |on: bool, since: NaiveDateTime, until: NaiveDateTime, cause: String| {
+        let duration = (until - since).num_milliseconds() as f64 / 1000.0;
+
+        if duration <= 0.0 {
+            return;
+        }
+
+        if on {
+            on_air += duration;
+        } else {
+            off_air += duration;
+            outages.push(Outage {
+                start: format(since),
+                end: format(until),
+                duration,
+                cause,
+            });
+        }
+    };
+
+    for event in events {
+        let Ok(time) = NaiveDateTime::parse_from_str(&event.time, HISTORY_TIME_FORMAT) else {
+            continue;
+        };
+        let time = time.clamp(start, end);
+
+        match state.take() {
+            Some((on, since, cause)) if on == event.on_air => state = Some((on, since, cause)),
+            Some((on, since, cause)) => {
+                close(on, since, time, cause);
+                state = Some((event.on_air, time, event.cause.clone()));
+            }
+            None => {
+                measured = Some(time);
+                state = Some((event.on_air, time, event.cause.clone()));
+            }
+        }
+    }
+
+    if let Some((on, since, cause)) = state {
+        close(on, since, end, cause);
+    }
+
+    let total = on_air + off_air;
+    let availability = if total > 0.0 {
+        (on_air / total * 10000.0).round() / 100.0
+    } else {
+        100.0
+    };
+
+    AvailabilityReport {
+        channel_id,
+        month: month.to_string(),
+        start: format(measured.unwrap_or(end)),
+        end: format(end),
+        on_air,
+        off_air,
+        availability,
+        outages,
+    }
+}
+
+/// Report for the given month, a running month ends now.
+pub async fn monthly_report(
+    pool: &Pool<Sqlite>,
+    channel_id: i32,
+    month: &str,
+) -> Result<AvailabilityReport, ServiceError> {
+    let (start, end) = month_range(month).ok_or_else(|| {
+        ServiceError::BadRequest(format!("Invalid month \"{month}\", use YYYY-MM"))
+    })?;
+    let now = Local::now().naive_local();
+
+    if start > now {
+        return Err(ServiceError::BadRequest(format!(
+            "Month {month} is in the future"
+        )));
+    }
+
+    let end = end.min(now);
+    let initial = handles::select_last_channel_event(
+        pool,
+        channel_id,
+        &start.format(HISTORY_TIME_FORMAT).to_string(),
+    )
+    .await?;
+    let events = handles::select_channel_events(
+        pool,
+        channel_id,
+        &start.format(HISTORY_TIME_FORMAT).to_string(),
+        &end.format(HISTORY_TIME_FORMAT).to_string(),
+    )
+    .await?;
+
+    Ok(build_report(
+        channel_id, month, start, end, initial, &events,
+    ))
+}
@@ -0,0 +1,134 @@
+/// User Management
+///
+/// List, change and delete users from the command line, for servers without web access or
+/// when nobody can log in anymore. Users are addressed by name, like on the login.
+use sqlx::{Pool, Sqlite};
+
+use crate::db::{handles, models::Role};
+use crate::utils::errors::ServiceError;
+
+/// Changes for one user, `None` keeps the stored value.
+#[derive(Clone, Debug, Default)]
+pub struct UserUpdate {
+    pub mail: Option<String>,
+    pub password: Option<String>,
+    pub role: Option<String>,
+    /// Replaces the assigned channels.
+    pub channels: Option<Vec<i32>>,
+}
+
+impl UserUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.mail.is_none()
+            && self.password.is_none()
+            && self.role.is_none()
+            && self.channels.is_none()
+    }
+}
+
+/// One line per user, with role and channels.
+pub async fn list_users(conn: &Pool<Sqlite>) -> Result<Vec<String>, ServiceError> {
+    let mut lines = vec![];
+
+    for item in handles::select_users(conn).await? {
+        let user = handles::select_user(conn, item.id).await?;
+        let role = match user.role_id {
+            Some(id) => handles::select_role(conn, &id).await.unwrap_or_default(),
+            None => Role::default(),
+        };
+        let channels = user
+            .channel_ids
+            .unwrap_or_default()
+            .iter()
+            .filter(|c| **c > 0)
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        lines.push(format!(
+            "    {}: '{}' <{}>, {role}, channels: [{channels}]",
+            user.id,
+            user.username,
+            user.mail.unwrap_or_default()
+        ));
+    }
+
+    Ok(lines)
+}
+
+async fn user_id(conn: &Pool<Sqlite>, name: &str) -> Result<(i32, Option<i32>), ServiceError> {
+    handles::select_login(conn, name)
+        .await
+        .ok()
+        .filter(|u| u.id > 0)
+        .map(|u| (u.id, u.role_id))
+        .ok_or_else(|| ServiceError::BadRequest(format!("User \"{name}\" not found!")))
+}
+
+/// The last global admin must stay, otherwise nobody can manage the server.
+async fn check_last_admin(conn: &Pool<Sqlite>, id: i32) -> Result<(), ServiceError> {
+    let admins = handles::select_global_admins(conn).await?;
+
+    if admins.len() == 1 && admins[0].id == id {
+        return Err(ServiceError::Conflict(
+            "This is the last global admin!".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+pub async fn change_user(
+    conn: &Pool<Sqlite>,
+    name: &str,
+    update: UserUpdate,
+) -> Result<(), ServiceError> {
+    let (id, role_id) = user_id(conn, name).await?;
+
+    let new_role = match &update.role {
+        Some(role) => Some(
+            handles::select_role_id(conn, role)
+                .await?
+                .ok_or_else(|| ServiceError::BadRequest(format!("Unknown role \"{role}\"")))?,
+        ),
+        None => None,
+    };
+
+    if role_id == Some(1) && new_role.is_some_and(|r| r != 1) {
+        check_last_admin(conn, id).await?;
+    }
+
+    let password_hash = match update.password {
+        Some(password) if password.is_empty() => {
+            return Err(ServiceError::BadRequest("Password is empty".to_string()))
+        }
+        Some(password) => Some(handles::hash_password(password).await?),
+        None => None,
+    };
+
+    handles::update_user_login(conn, id, update.mail, password_hash, new_role).await?;
+
+    if let Some(channels) = update.channels {
+        for channel in handles::select_related_channels(conn, Some(id)).await? {
+            if !channels.contains(&channel.id) {
+                handles::delete_user_channel(conn, id, channel.id).await?;
+            }
+        }
+
+        handles::insert_user_channel(conn, id, channels).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn remove_user(conn: &Pool<Sqlite>, name: &str) -> Result<(), ServiceError> {
+    let (id, role_id) = user_id(conn, name).await?;
+
+    if role_id == Some(1) {
+        check_last_admin(conn, id).await?;
+    }
+
+    handles::delete_user(conn, id).await?;
+
+    Ok(())
+}
@@ -0,0 +1,6 @@
+ALTER TABLE global ADD backup_enable INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE global ADD backup_path TEXT NOT NULL DEFAULT "/var/lib/ffplayout/backup";
+ALTER TABLE global ADD backup_interval INTEGER NOT NULL DEFAULT 24;
+ALTER TABLE global ADD backup_keep INTEGER NOT NULL DEFAULT 7;
+ALTER TABLE global ADD backup_media_manifest INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE global ADD backup_upload TEXT NOT NULL DEFAULT "";
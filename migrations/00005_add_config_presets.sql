@@ -0,0 +1,13 @@
+CREATE TABLE
+    config_presets (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        processing_width INTEGER NOT NULL DEFAULT 1280,
+        processing_height INTEGER NOT NULL DEFAULT 720,
+        processing_aspect REAL NOT NULL DEFAULT 1.778,
+        processing_fps REAL NOT NULL DEFAULT 25,
+        processing_audio_channels INTEGER NOT NULL DEFAULT 2,
+        output_mode TEXT NOT NULL DEFAULT "hls",
+        output_param TEXT NOT NULL,
+        UNIQUE (name)
+    );
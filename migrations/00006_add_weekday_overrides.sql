@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_weekday_overrides TEXT NOT NULL DEFAULT "[]";
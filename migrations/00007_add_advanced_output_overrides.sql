@@ -0,0 +1,2 @@
+ALTER TABLE advanced_configurations
+ADD COLUMN output_overrides TEXT NOT NULL DEFAULT "{}";
@@ -0,0 +1,26 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_backend TEXT NOT NULL DEFAULT "file";
+
+CREATE TABLE
+    playlists (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel_id INTEGER NOT NULL,
+        date TEXT NOT NULL,
+        name TEXT NOT NULL DEFAULT "",
+        program TEXT NOT NULL DEFAULT "[]",
+        version INTEGER NOT NULL DEFAULT 1,
+        modified TEXT NOT NULL,
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE,
+        UNIQUE (channel_id, date)
+    );
+
+CREATE TABLE
+    playlist_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        name TEXT NOT NULL DEFAULT "",
+        program TEXT NOT NULL DEFAULT "[]",
+        modified TEXT NOT NULL,
+        FOREIGN KEY (playlist_id) REFERENCES playlists (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
@@ -0,0 +1,18 @@
+ALTER TABLE global ADD play_history_days INTEGER NOT NULL DEFAULT 30;
+
+CREATE TABLE
+    play_history (
+        id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        started TEXT NOT NULL,
+        ended TEXT NOT NULL,
+        source TEXT NOT NULL,
+        title TEXT,
+        category TEXT NOT NULL DEFAULT "",
+        seek REAL NOT NULL DEFAULT 0,
+        out REAL NOT NULL DEFAULT 0,
+        duration REAL NOT NULL DEFAULT 0,
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
+
+CREATE INDEX play_history_channel_started ON play_history (channel_id, started);
@@ -0,0 +1,29 @@
+ALTER TABLE configurations
+ADD COLUMN storage_tags TEXT NOT NULL DEFAULT "";
+
+CREATE TABLE
+    tags (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE,
+        UNIQUE (channel_id, name)
+    );
+
+CREATE TABLE
+    media (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        channel_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE,
+        UNIQUE (channel_id, path)
+    );
+
+CREATE TABLE
+    media_tags (
+        media_id INTEGER NOT NULL,
+        tag_id INTEGER NOT NULL,
+        PRIMARY KEY (media_id, tag_id),
+        FOREIGN KEY (media_id) REFERENCES media (id) ON UPDATE CASCADE ON DELETE CASCADE,
+        FOREIGN KEY (tag_id) REFERENCES tags (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
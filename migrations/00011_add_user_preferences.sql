@@ -0,0 +1,8 @@
+CREATE TABLE
+    user_preferences (
+        user_id INTEGER NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        FOREIGN KEY (user_id) REFERENCES user (id) ON UPDATE CASCADE ON DELETE CASCADE,
+        PRIMARY KEY (user_id, key)
+    );
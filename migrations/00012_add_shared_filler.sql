@@ -0,0 +1,4 @@
+ALTER TABLE global ADD filler_pool TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE configurations
+ADD COLUMN storage_shared_filler INTEGER NOT NULL DEFAULT 0;
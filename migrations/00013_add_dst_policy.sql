@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_dst_forward TEXT NOT NULL DEFAULT "shift";
+
+ALTER TABLE configurations
+ADD COLUMN playlist_dst_backward TEXT NOT NULL DEFAULT "shift";
@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN locked_fields TEXT NOT NULL DEFAULT "";
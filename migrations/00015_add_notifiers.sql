@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN mail_notifiers TEXT NOT NULL DEFAULT "[]";
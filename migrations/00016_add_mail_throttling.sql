@@ -0,0 +1,8 @@
+ALTER TABLE configurations
+ADD COLUMN mail_digest INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE configurations
+ADD COLUMN mail_dedup_window INTEGER NOT NULL DEFAULT 3600;
+
+ALTER TABLE configurations
+ADD COLUMN mail_class_levels TEXT NOT NULL DEFAULT "{}";
@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN general_watchdog INTEGER NOT NULL DEFAULT 30;
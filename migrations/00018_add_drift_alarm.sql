@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN general_drift_alarm REAL NOT NULL DEFAULT 3.0;
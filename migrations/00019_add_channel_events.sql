@@ -0,0 +1,11 @@
+CREATE TABLE
+    channel_events (
+        id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        time TEXT NOT NULL,
+        on_air INTEGER NOT NULL DEFAULT 0,
+        cause TEXT NOT NULL DEFAULT "",
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
+
+CREATE INDEX channel_events_channel_time ON channel_events (channel_id, time);
@@ -0,0 +1 @@
+ALTER TABLE global ADD sentry_dsn TEXT NOT NULL DEFAULT "";
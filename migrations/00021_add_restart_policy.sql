@@ -0,0 +1,11 @@
+ALTER TABLE configurations
+ADD COLUMN general_restart_limit INTEGER NOT NULL DEFAULT 10;
+
+ALTER TABLE configurations
+ADD COLUMN general_restart_delay INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE configurations
+ADD COLUMN general_restart_max_delay INTEGER NOT NULL DEFAULT 180;
+
+ALTER TABLE configurations
+ADD COLUMN general_restart_slate INTEGER NOT NULL DEFAULT 3;
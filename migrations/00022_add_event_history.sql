@@ -0,0 +1,11 @@
+CREATE TABLE
+    event_history (
+        id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        time TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        message TEXT NOT NULL DEFAULT "",
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
+
+CREATE INDEX event_history_channel_id ON event_history (channel_id, id);
@@ -0,0 +1,35 @@
+CREATE TABLE
+    alert_rules (
+        id INTEGER PRIMARY KEY,
+        channel_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        metric TEXT NOT NULL,
+        condition TEXT NOT NULL DEFAULT ">",
+        threshold REAL NOT NULL DEFAULT 0,
+        duration INTEGER NOT NULL DEFAULT 0,
+        notifier TEXT NOT NULL DEFAULT "log",
+        enabled INTEGER NOT NULL DEFAULT 1,
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
+
+CREATE INDEX alert_rules_channel_id ON alert_rules (channel_id);
+
+-- Replaces the fixed alarm from the output health, for existing and new channels.
+INSERT INTO
+    alert_rules (channel_id, name, metric, condition, threshold)
+SELECT
+    id,
+    'Output is unstable',
+    'disconnects',
+    '>=',
+    3
+FROM
+    channels;
+
+CREATE TRIGGER alert_rules_default AFTER INSERT ON channels
+BEGIN
+    INSERT INTO
+        alert_rules (channel_id, name, metric, condition, threshold)
+    VALUES
+        (NEW.id, 'Output is unstable', 'disconnects', '>=', 3);
+END;
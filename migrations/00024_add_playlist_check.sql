@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_check_days INTEGER NOT NULL DEFAULT 2;
+
+ALTER TABLE configurations
+ADD COLUMN playlist_check_hours INTEGER NOT NULL DEFAULT 12;
@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN general_disk_alarm INTEGER NOT NULL DEFAULT 90;
+
+ALTER TABLE configurations
+ADD COLUMN general_disk_cleanup INTEGER NOT NULL DEFAULT 95;
@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN output_renditions TEXT NOT NULL DEFAULT "[]";
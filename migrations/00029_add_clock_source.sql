@@ -0,0 +1 @@
+ALTER TABLE global ADD clock_source TEXT NOT NULL DEFAULT "";
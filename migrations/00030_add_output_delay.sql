@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN output_delay REAL NOT NULL DEFAULT 0;
+
+ALTER TABLE configurations
+ADD COLUMN output_delay_path TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN output_replay REAL NOT NULL DEFAULT 0;
+
+ALTER TABLE configurations
+ADD COLUMN output_replay_path TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN storage_emergency_slate TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE configurations
+ADD COLUMN storage_emergency_audio TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,8 @@
+ALTER TABLE advanced_configurations
+ADD COLUMN process_nice INTEGER;
+
+ALTER TABLE advanced_configurations
+ADD COLUMN process_cpu_affinity TEXT;
+
+ALTER TABLE advanced_configurations
+ADD COLUMN process_io_priority TEXT;
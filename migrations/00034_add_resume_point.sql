@@ -0,0 +1,2 @@
+ALTER TABLE channels
+ADD COLUMN resume_point TEXT;
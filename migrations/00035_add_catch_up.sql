@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_catch_up TEXT NOT NULL DEFAULT "restart";
+
+ALTER TABLE configurations
+ADD COLUMN playlist_catch_up_rate REAL NOT NULL DEFAULT 5;
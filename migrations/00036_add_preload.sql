@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN processing_preload INTEGER NOT NULL DEFAULT 0;
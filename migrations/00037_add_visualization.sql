@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN processing_visualization TEXT NOT NULL DEFAULT "off";
@@ -0,0 +1,8 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_stream_reconnect INTEGER NOT NULL DEFAULT 5;
+
+ALTER TABLE configurations
+ADD COLUMN playlist_stream_retries INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE configurations
+ADD COLUMN playlist_stream_fallback REAL NOT NULL DEFAULT 0;
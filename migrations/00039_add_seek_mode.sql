@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN processing_seek_mode TEXT NOT NULL DEFAULT "fast";
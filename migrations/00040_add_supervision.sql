@@ -0,0 +1,14 @@
+ALTER TABLE advanced_configurations
+ADD COLUMN supervision_min_runtime INTEGER;
+
+ALTER TABLE advanced_configurations
+ADD COLUMN supervision_max_failures INTEGER;
+
+ALTER TABLE advanced_configurations
+ADD COLUMN supervision_backoff INTEGER;
+
+ALTER TABLE advanced_configurations
+ADD COLUMN supervision_max_backoff INTEGER;
+
+ALTER TABLE advanced_configurations
+ADD COLUMN supervision_fatal_errors TEXT;
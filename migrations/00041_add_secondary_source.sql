@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN ingest_secondary_source TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,13 @@
+CREATE TABLE
+    media_scan (
+        channel_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        size INTEGER NOT NULL DEFAULT 0,
+        modified INTEGER NOT NULL DEFAULT 0,
+        duration REAL NOT NULL DEFAULT 0,
+        probe TEXT NOT NULL DEFAULT "",
+        error TEXT NOT NULL DEFAULT "",
+        scanned TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (channel_id, path),
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
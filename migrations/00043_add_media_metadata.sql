@@ -0,0 +1,13 @@
+ALTER TABLE media
+ADD COLUMN house_id TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE media
+ADD COLUMN title TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE media
+ADD COLUMN category TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE media
+ADD COLUMN metadata TEXT NOT NULL DEFAULT "{}";
+
+CREATE INDEX media_house_id ON media (channel_id, house_id);
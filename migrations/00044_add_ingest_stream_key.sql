@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN ingest_stream_key TEXT NOT NULL DEFAULT "";
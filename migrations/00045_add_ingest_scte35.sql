@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN ingest_scte35 INTEGER NOT NULL DEFAULT 0;
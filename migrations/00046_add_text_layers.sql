@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN text_layers TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN general_control_token TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN playlist_vast_url TEXT NOT NULL DEFAULT "";
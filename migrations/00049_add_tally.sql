@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN general_tally TEXT NOT NULL DEFAULT "";
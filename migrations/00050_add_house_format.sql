@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN storage_house_format TEXT NOT NULL DEFAULT "{}";
@@ -0,0 +1,5 @@
+ALTER TABLE configurations
+ADD COLUMN storage_coming_up TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE configurations
+ADD COLUMN storage_coming_up_count INTEGER NOT NULL DEFAULT 3;
@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN output_public_server TEXT NOT NULL DEFAULT "{}";
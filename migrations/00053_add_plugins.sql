@@ -0,0 +1,2 @@
+ALTER TABLE configurations
+ADD COLUMN general_plugins TEXT NOT NULL DEFAULT "";
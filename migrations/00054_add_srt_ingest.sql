@@ -0,0 +1,8 @@
+ALTER TABLE configurations
+ADD COLUMN ingest_srt_passphrase TEXT NOT NULL DEFAULT "";
+
+ALTER TABLE configurations
+ADD COLUMN ingest_srt_latency INTEGER NOT NULL DEFAULT 120;
+
+ALTER TABLE configurations
+ADD COLUMN ingest_srt_streamid TEXT NOT NULL DEFAULT "";
@@ -0,0 +1,27 @@
+CREATE TABLE
+    playlist_history_new (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id INTEGER,
+        channel_id INTEGER NOT NULL,
+        date TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        name TEXT NOT NULL DEFAULT "",
+        program TEXT NOT NULL DEFAULT "[]",
+        modified TEXT NOT NULL,
+        FOREIGN KEY (playlist_id) REFERENCES playlists (id) ON UPDATE CASCADE ON DELETE SET NULL,
+        FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+    );
+
+INSERT INTO
+    playlist_history_new (id, playlist_id, channel_id, date, version, name, program, modified)
+SELECT
+    h.id, h.playlist_id, p.channel_id, p.date, h.version, h.name, h.program, h.modified
+FROM
+    playlist_history h
+    INNER JOIN playlists p ON p.id = h.playlist_id;
+
+DROP TABLE playlist_history;
+
+ALTER TABLE playlist_history_new RENAME TO playlist_history;
+
+CREATE INDEX playlist_history_channel_date ON playlist_history (channel_id, date);
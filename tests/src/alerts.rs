@@ -0,0 +1,129 @@
+use sqlx::sqlite::SqlitePoolOptions;
+
+use chrono::{Local, TimeDelta};
+use serial_test::serial;
+
+use ffplayout::db::{handles, models};
+use ffplayout::player::controller::ChannelManager;
+use ffplayout::utils::{
+    alerts::{validate, AlertState, Transition},
+    config::PlayoutConfig,
+    disk_monitor::{usage_percent, DiskAlarm},
+};
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[actix_web::test]
+#[serial]
+async fn event_history_ring_buffer() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.unwrap();
+
+    for i in 1..=3 {
+        let event = models::HistoryEvent {
+            channel_id: 1,
+            time: format!("2024-10-15 14:30:0{i}.000"),
+            kind: "state".to_string(),
+            message: format!("event {i}"),
+            ..Default::default()
+        };
+
+        handles::insert_history_event(&pool, &event, 2)
+            .await
+            .unwrap();
+    }
+
+    let events = handles::select_history_events(&pool, 1, 0, 100)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].message, "event 2");
+
+    let events = handles::select_history_events(&pool, 1, events[0].id, 100)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].message, "event 3");
+}
+
+#[actix_web::test]
+#[serial]
+async fn alert_rule_transitions() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.unwrap();
+
+    let rules = handles::select_alert_rules(&pool, 1).await.unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].metric, "disconnects");
+
+    let mut rule = models::AlertRule {
+        id: 2,
+        channel_id: 1,
+        name: " Encoder too slow ".to_string(),
+        metric: "speed".to_string(),
+        condition: "<".to_string(),
+        threshold: 0.95,
+        duration: 10,
+        ..Default::default()
+    };
+    validate(&mut rule).unwrap();
+    assert_eq!(rule.name, "Encoder too slow");
+    assert_eq!(rule.notifier, "log");
+
+    let mut state = AlertState::default();
+    let now = Local::now();
+
+    assert_eq!(state.check(&rule, 0.8, now), None);
+    assert_eq!(state.check(&rule, 0.8, now + TimeDelta::seconds(5)), None);
+    assert_eq!(
+        state.check(&rule, 0.8, now + TimeDelta::seconds(10)),
+        Some(Transition::Fire)
+    );
+    assert_eq!(state.check(&rule, 0.8, now + TimeDelta::seconds(15)), None);
+    assert_eq!(
+        state.check(&rule, 1.0, now + TimeDelta::seconds(20)),
+        Some(Transition::Resolve)
+    );
+
+    rule.metric = "bitrate".to_string();
+    assert!(validate(&mut rule).is_err());
+}
+
+#[test]
+fn disk_usage_alarm() {
+    assert_eq!(usage_percent(0, 0), 0.0);
+    assert_eq!(usage_percent(200, 20), 90.0);
+
+    let mut alarm = DiskAlarm::default();
+
+    assert_eq!(alarm.update(1, "public", 85.0, 90), None);
+    assert_eq!(alarm.update(1, "public", 91.0, 90), Some(true));
+    assert_eq!(alarm.update(1, "public", 95.0, 90), None);
+    assert_eq!(alarm.update(1, "storage", 95.0, 90), Some(true));
+    assert_eq!(alarm.update(1, "public", 89.0, 90), None);
+    assert_eq!(alarm.update(1, "public", 87.5, 90), Some(false));
+    assert_eq!(alarm.update(1, "storage", 95.0, 0), Some(false));
+}
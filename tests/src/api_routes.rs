@@ -1,13 +1,33 @@
-use actix_web::{get, web, App, Error, HttpResponse, Responder};
+use std::{collections::HashSet, future::poll_fn, path::PathBuf, time::Duration};
 
-use serde_json::json;
+use actix_web::{
+    body::MessageBody, dev::ServiceResponse, get, http::header, test, web, App, Error,
+    HttpResponse, Responder,
+};
+use actix_web_httpauth::middleware::HttpAuthentication;
+
+use serde_json::{json, Value};
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use tokio::{sync::Mutex, time::timeout};
 
-use ffplayout::api::routes::login;
-use ffplayout::db::{handles, init_globales, models::User};
-use ffplayout::player::controller::ChannelManager;
+use ffplayout::api::{
+    auth::{encode_jwt, Claims},
+    routes::{
+        add_config_preset, add_tag, apply_config_preset, delay_dump, delay_status, export_archive,
+        get_config_presets, get_event_history, get_play_history, get_playlist_history,
+        get_shared_file, get_tags, import_archive, instant_replay, login, share_file, update_tag,
+    },
+};
+use ffplayout::db::{
+    handles, init_globales,
+    models::{Role, User},
+};
+use ffplayout::player::controller::{ChannelController, ChannelManager};
+use ffplayout::sse::{
+    broadcast::Broadcaster, routes::event_stream, ClipEvent, SseAuthState, UuidData, CLIP_STREAM,
+};
 use ffplayout::utils::config::PlayoutConfig;
-// use ffplayout::validator;
+use ffplayout::validator;
 
 async fn prepare_config() -> (PlayoutConfig, ChannelManager, Pool<Sqlite>) {
     let pool = SqlitePoolOptions::new()
@@ -45,6 +65,66 @@ async fn prepare_config() -> (PlayoutConfig, ChannelManager, Pool<Sqlite>) {
     (config, manager, pool)
 }
 
+/// Global settings can be set only once, the first test sets them.
+async fn init_settings(pool: &Pool<Sqlite>) {
+    let _ = init_globales(pool).await;
+}
+
+async fn token(role: Role, channels: Vec<i32>) -> String {
+    let user = User {
+        id: 1,
+        mail: None,
+        username: "admin".to_string(),
+        password: String::new(),
+        role_id: None,
+        channel_ids: Some(channels),
+        token: None,
+    };
+
+    encode_jwt(Claims::new(user, role, 1)).await.unwrap()
+}
+
+async fn start_api() -> (actix_test::TestServer, String) {
+    let (_, manager, pool) = prepare_config().await;
+    init_settings(&pool).await;
+
+    manager.config.lock().await.channel.storage = PathBuf::from("assets/media_mix");
+
+    let mut controller = ChannelController::new();
+    controller.add(manager);
+    let controllers = web::Data::new(Mutex::new(controller));
+
+    let srv = actix_test::start(move || {
+        let auth = HttpAuthentication::bearer(validator);
+
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(controllers.clone())
+            .service(
+                web::scope("/api")
+                    .wrap(auth)
+                    .service(get_config_presets)
+                    .service(add_config_preset)
+                    .service(apply_config_preset)
+                    .service(export_archive)
+                    .service(import_archive)
+                    .service(get_playlist_history)
+                    .service(get_play_history)
+                    .service(get_tags)
+                    .service(add_tag)
+                    .service(update_tag)
+                    .service(get_event_history)
+                    .service(delay_status)
+                    .service(delay_dump)
+                    .service(instant_replay)
+                    .service(share_file),
+            )
+            .service(get_shared_file)
+    });
+
+    (srv, token(Role::GlobalAdmin, vec![1]).await)
+}
+
 #[get("/")]
 async fn get_handler() -> Result<impl Responder, Error> {
     Ok(HttpResponse::Ok())
@@ -64,7 +144,7 @@ async fn test_get() {
 async fn test_login() {
     let (_, _, pool) = prepare_config().await;
 
-    init_globales(&pool).await.unwrap();
+    init_settings(&pool).await;
 
     let srv = actix_test::start(move || {
         let db_pool = web::Data::new(pool.clone());
@@ -91,3 +171,520 @@ async fn test_login() {
 
     assert_eq!(res.status().as_u16(), 400);
 }
+
+#[actix_web::test]
+async fn test_user_preferences() {
+    let (_, _, pool) = prepare_config().await;
+    let user = handles::select_login(&pool, "admin").await.unwrap();
+
+    let preferences = vec![
+        ("default_channel".to_string(), Some("2".to_string())),
+        ("ui.theme".to_string(), Some("\"dark\"".to_string())),
+    ];
+    handles::update_preferences(&pool, user.id, &preferences)
+        .await
+        .unwrap();
+
+    // null values remove the entry
+    handles::update_preferences(&pool, user.id, &[("ui.theme".to_string(), None)])
+        .await
+        .unwrap();
+
+    let stored = handles::select_preferences(&pool, user.id).await.unwrap();
+
+    assert_eq!(
+        stored,
+        vec![("default_channel".to_string(), "2".to_string())]
+    );
+}
+
+#[actix_web::test]
+async fn api_config_presets() {
+    let (srv, admin) = start_api().await;
+
+    let res = srv.get("/api/config/presets").send().await.unwrap();
+    assert_eq!(res.status().as_u16(), 401);
+
+    let res = srv
+        .get("/api/config/presets")
+        .bearer_auth("no.valid.token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 401);
+
+    // presets are only for global admins
+    let channel_admin = token(Role::ChannelAdmin, vec![1]).await;
+
+    let res = srv
+        .get("/api/config/presets")
+        .bearer_auth(&channel_admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let preset = json!({
+        "name": "YouTube 1080p", "processing_width": 1920, "processing_height": 1080,
+        "processing_aspect": 1.778, "processing_fps": 25.0, "processing_audio_channels": 2,
+        "output_mode": "stream", "output_param": "-c:v libx264 -f flv rtmp://127.0.0.1/live/stream"
+    });
+
+    let res = srv
+        .post("/api/config/presets")
+        .bearer_auth(&admin)
+        .send_json(&preset)
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let res = srv
+        .post("/api/config/presets")
+        .bearer_auth(&admin)
+        .send_json(&preset)
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 409);
+
+    let mut invalid = preset.clone();
+    invalid["name"] = json!("Radio");
+    invalid["output_mode"] = json!("radio");
+
+    let res = srv
+        .post("/api/config/presets")
+        .bearer_auth(&admin)
+        .send_json(&invalid)
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+
+    let mut res = srv
+        .get("/api/config/presets")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    let presets = res.json::<Value>().await.unwrap();
+    assert!(presets
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p["name"] == "YouTube 1080p"));
+
+    for path in [
+        "/api/playout/config/9/preset/YouTube%201080p",
+        "/api/playout/config/1/preset/Missing",
+    ] {
+        let res = srv.put(path).bearer_auth(&admin).send().await.unwrap();
+        assert_eq!(res.status().as_u16(), 400, "{path}");
+    }
+}
+
+#[actix_web::test]
+async fn api_archive() {
+    let (srv, admin) = start_api().await;
+
+    // archives are only for global admins
+    let channel_admin = token(Role::ChannelAdmin, vec![1]).await;
+
+    let res = srv
+        .get("/api/archive/export")
+        .bearer_auth(&channel_admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let res = srv
+        .post("/api/archive/import")
+        .bearer_auth(&channel_admin)
+        .send_body("no gzip")
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let res = srv
+        .get("/api/archive/export")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/gzip"
+    );
+
+    let res = srv
+        .post("/api/archive/import")
+        .bearer_auth(&admin)
+        .send_body("no gzip")
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn api_playlist_history() {
+    let (srv, admin) = start_api().await;
+
+    // user from an other channel
+    let user = token(Role::User, vec![2]).await;
+
+    let res = srv
+        .get("/api/playlist/1/history?date=2024-01-01")
+        .bearer_auth(&user)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    // playlists are in files, they have no history
+    let res = srv
+        .get("/api/playlist/1/history?date=2024-01-01")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+
+    let res = srv
+        .get("/api/playlist/9/history?date=2024-01-01")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn api_play_history() {
+    let (srv, admin) = start_api().await;
+
+    let res = srv.get("/api/history/1").send().await.unwrap();
+    assert_eq!(res.status().as_u16(), 401);
+
+    // user from an other channel
+    let user = token(Role::User, vec![2]).await;
+
+    let res = srv
+        .get("/api/history/1")
+        .bearer_auth(&user)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let mut res = srv
+        .get("/api/history/1")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(res.json::<Value>().await.unwrap()["total"], 0);
+
+    let res = srv
+        .get("/api/history/1?start=yesterday")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn api_tags() {
+    let (srv, admin) = start_api().await;
+
+    let res = srv
+        .get("/api/tags/1")
+        .bearer_auth(token(Role::User, vec![2]).await)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let res = srv
+        .get("/api/tags/1")
+        .bearer_auth(token(Role::User, vec![1]).await)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let res = srv
+        .post("/api/tags/1/")
+        .bearer_auth(&admin)
+        .send_json(&json!({"name": "news"}))
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let res = srv
+        .post("/api/tags/1/")
+        .bearer_auth(&admin)
+        .send_json(&json!({"name": " news "}))
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 409);
+
+    let res = srv
+        .post("/api/tags/1/")
+        .bearer_auth(&admin)
+        .send_json(&json!({"name": "news,sports"}))
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+
+    let res = srv
+        .put("/api/tags/1/999")
+        .bearer_auth(&admin)
+        .send_json(&json!({"name": "sports"}))
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 204);
+}
+
+#[actix_web::test]
+async fn api_event_history() {
+    let (srv, admin) = start_api().await;
+
+    let res = srv
+        .get("/api/event/1")
+        .bearer_auth(token(Role::User, vec![2]).await)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let mut res = srv
+        .get("/api/event/1?since=0")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    assert!(res.json::<Value>().await.unwrap().is_array());
+}
+
+#[actix_web::test]
+async fn api_delay_buffer() {
+    let (srv, admin) = start_api().await;
+
+    let res = srv
+        .post("/api/control/1/delay/dump/")
+        .bearer_auth(token(Role::User, vec![2]).await)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let mut res = srv
+        .get("/api/control/1/delay")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+    assert!(res.json::<Value>().await.unwrap().is_object());
+
+    // no delay buffer, while the channel is stopped
+    let res = srv
+        .post("/api/control/1/delay/dump/")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 409);
+
+    let res = srv
+        .post("/api/control/9/delay/dump/")
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn api_instant_replay() {
+    let (srv, admin) = start_api().await;
+    let replay = json!({"from": 30, "duration": 10});
+
+    let res = srv
+        .post("/api/control/1/replay/")
+        .bearer_auth(token(Role::User, vec![2]).await)
+        .send_json(&replay)
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    // nothing is recorded, while the channel is stopped
+    let res = srv
+        .post("/api/control/1/replay/")
+        .bearer_auth(&admin)
+        .send_json(&replay)
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 409);
+
+    let res = srv
+        .post("/api/control/9/replay/")
+        .bearer_auth(&admin)
+        .send_json(&replay)
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn api_share_links() {
+    let (srv, admin) = start_api().await;
+
+    let res = srv
+        .post("/api/file/1/share/")
+        .bearer_auth(token(Role::User, vec![2]).await)
+        .send_json(&json!({"source": "still.jpg"}))
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    for (payload, channel) in [
+        (json!({"source": "missing.mp4"}), 1),
+        (json!({"source": "still.jpg", "lifetime": 0}), 1),
+        (json!({"source": "still.jpg"}), 9),
+    ] {
+        let res = srv
+            .post(format!("/api/file/{channel}/share/"))
+            .bearer_auth(&admin)
+            .send_json(&payload)
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 400, "{payload}");
+    }
+
+    let mut res = srv
+        .post("/api/file/1/share/")
+        .bearer_auth(&admin)
+        .send_json(&json!({"source": "still.jpg", "lifetime": 60}))
+        .await
+        .unwrap();
+    assert!(res.status().is_success());
+
+    let link = res.json::<Value>().await.unwrap();
+    let url = link["url"].as_str().unwrap();
+
+    // shared files need no login
+    let res = srv.get(url).send().await.unwrap();
+    assert!(res.status().is_success());
+
+    let res = srv
+        .get(url.replace("still.jpg", "audio.mp3"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+
+    let res = srv
+        .get("/share/1/still.jpg?expires=0&signature=abc")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 403);
+}
+
+/// Read from the event stream until `until` comes.
+async fn read_events<B: MessageBody>(res: ServiceResponse<B>, until: &str) -> String
+where
+    B::Error: std::fmt::Debug,
+{
+    let mut body = Box::pin(res.into_body());
+    let mut text = String::new();
+
+    while !text.contains(until) {
+        let chunk = timeout(
+            Duration::from_secs(5),
+            poll_fn(|cx| body.as_mut().poll_next(cx)),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+        text.push_str(&String::from_utf8_lossy(&chunk));
+    }
+
+    text
+}
+
+#[actix_web::test]
+async fn api_clip_events_replay() {
+    let (_, manager, _) = prepare_config().await;
+    let uuid = UuidData::new();
+    let auth_state = web::Data::new(SseAuthState {
+        uuids: Mutex::new(HashSet::from([uuid])),
+    });
+    let broadcaster = web::Data::from(Broadcaster::create());
+
+    let mut controller = ChannelController::new();
+    controller.add(manager);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(auth_state)
+            .app_data(broadcaster)
+            .app_data(web::Data::new(Mutex::new(controller)))
+            .service(web::scope("/data").service(event_stream)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/data/event/1?endpoint=clip&uuid=f2f8c29b-712a-48c5-8919-b535d3a05a3a")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 401);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/data/event/9?endpoint=clip&uuid={}", uuid.uuid))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 400);
+
+    for source in ["first.mp4", "second.mp4", "third.mp4"] {
+        CLIP_STREAM
+            .send(ClipEvent {
+                channel: 1,
+                source: source.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    // the broadcaster fills the replay buffer in the background
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let uri = format!("/data/event/1?endpoint=clip&uuid={}", uuid.uuid);
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Last-Event-ID", "0"))
+        .to_request();
+    let events = read_events(test::call_service(&app, req).await, "third.mp4").await;
+
+    assert!(events.contains("first.mp4"));
+    assert!(events.contains("second.mp4"));
+
+    let first_id = events
+        .lines()
+        .find_map(|l| l.strip_prefix("id:"))
+        .unwrap()
+        .trim()
+        .to_string();
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Last-Event-ID", first_id))
+        .to_request();
+    let events = read_events(test::call_service(&app, req).await, "third.mp4").await;
+
+    assert!(!events.contains("first.mp4"));
+    assert!(events.contains("second.mp4"));
+}
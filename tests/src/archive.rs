@@ -0,0 +1,164 @@
+use std::env;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+
+use ffplayout::db::handles;
+use ffplayout::utils::archive::{export, import, import_to, Archive, ArchiveChannel};
+
+async fn prepare_pool(playlists: &str) -> Pool<Sqlite> {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query("UPDATE channels SET playlists = $1")
+        .bind(playlists)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    pool
+}
+
+#[tokio::test]
+async fn archive_export_round_trip() {
+    let playlists = env::temp_dir().join("ffplayout_archive_export");
+    let _ = tokio::fs::remove_dir_all(&playlists).await;
+    tokio::fs::create_dir_all(playlists.join("2024/01"))
+        .await
+        .unwrap();
+    tokio::fs::write(
+        playlists.join("2024/01/2024-01-01.json"),
+        r#"{"channel":"1","date":"2024-01-01","program":[]}"#,
+    )
+    .await
+    .unwrap();
+
+    let pool = prepare_pool(&playlists.to_string_lossy()).await;
+    let archive = Archive::decode(&export(&pool).await.unwrap()).unwrap();
+
+    assert_eq!(archive.channels.len(), 1);
+    assert_eq!(archive.channels[0].id, 1);
+    assert_eq!(
+        archive.channels[0]
+            .files
+            .get("2024/01/2024-01-01.json")
+            .unwrap(),
+        r#"{"channel":"1","date":"2024-01-01","program":[]}"#
+    );
+
+    // the database snapshot opens as its own database, with the same channels
+    let db_file = env::temp_dir().join("ffplayout_archive_export.db");
+    tokio::fs::write(&db_file, STANDARD.decode(&archive.database).unwrap())
+        .await
+        .unwrap();
+
+    let restored = SqlitePoolOptions::new()
+        .connect(&format!("sqlite://{}", db_file.display()))
+        .await
+        .unwrap();
+    let channels = handles::select_related_channels(&restored, None)
+        .await
+        .unwrap();
+
+    assert_eq!(channels.len(), 1);
+    assert_eq!(channels[0].playlists, playlists.to_string_lossy());
+
+    restored.close().await;
+
+    // encode and decode give the same archive
+    let again = Archive::decode(&archive.encode().unwrap()).unwrap();
+    assert_eq!(again.database, archive.database);
+    assert_eq!(again.channels[0].files, archive.channels[0].files);
+
+    let _ = tokio::fs::remove_file(&db_file).await;
+    let _ = tokio::fs::remove_dir_all(&playlists).await;
+}
+
+#[tokio::test]
+async fn archive_failed_import() {
+    let playlists = env::temp_dir().join("ffplayout_archive_failed");
+    let _ = tokio::fs::remove_dir_all(&playlists).await;
+    let pool = prepare_pool(&playlists.to_string_lossy()).await;
+
+    assert!(Archive::decode(b"no gzip").is_err());
+    assert!(import(&pool, b"no gzip").await.is_err());
+
+    let broken = Archive {
+        version: "0.0.0".to_string(),
+        database: STANDARD.encode("no sqlite database"),
+        channels: vec![ArchiveChannel {
+            id: 1,
+            playlists: "/tmp/elsewhere".to_string(),
+            files: [("2024-01-01.json".to_string(), "{}".to_string())].into(),
+        }],
+        ..Default::default()
+    };
+
+    assert!(import(&pool, &broken.encode().unwrap()).await.is_err());
+
+    // the database stays usable and no playlist is written
+    assert!(!pool.is_closed());
+    assert_eq!(
+        handles::select_related_channels(&pool, None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(!playlists.exists());
+}
+
+#[tokio::test]
+async fn archive_import_empty_database() {
+    let dir = env::temp_dir().join("ffplayout_archive_import");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+
+    let playlists = dir.join("playlists");
+    let source = prepare_pool(&playlists.to_string_lossy()).await;
+    let mut archive = Archive::decode(&export(&source).await.unwrap()).unwrap();
+    archive.channels[0].playlists = "/tmp/elsewhere".to_string();
+    archive.channels[0].files = [(
+        "2024/01/2024-01-01.json".to_string(),
+        r#"{"channel":"1","date":"2024-01-01","program":[]}"#.to_string(),
+    )]
+    .into();
+
+    // a new database, without any channel
+    let db_file = dir.join("ffplayout.db");
+    let pool = SqlitePoolOptions::new()
+        .connect(&format!("sqlite://{}?mode=rwc", db_file.display()))
+        .await
+        .unwrap();
+
+    import_to(&pool, &archive.encode().unwrap(), &db_file)
+        .await
+        .unwrap();
+
+    assert!(pool.is_closed());
+
+    // the playlists go to the folder of the restored channel
+    assert_eq!(
+        tokio::fs::read_to_string(playlists.join("2024/01/2024-01-01.json"))
+            .await
+            .unwrap(),
+        r#"{"channel":"1","date":"2024-01-01","program":[]}"#
+    );
+
+    let restored = SqlitePoolOptions::new()
+        .connect(&format!("sqlite://{}", db_file.display()))
+        .await
+        .unwrap();
+    let channels = handles::select_related_channels(&restored, None)
+        .await
+        .unwrap();
+
+    assert_eq!(channels.len(), 1);
+    assert_eq!(channels[0].playlists, playlists.to_string_lossy());
+
+    restored.close().await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+}
@@ -0,0 +1,61 @@
+use std::{env, fs, path::Path};
+
+use ffplayout::utils::backup::rotate;
+
+fn prepare_folder(name: &str) -> std::path::PathBuf {
+    let path = env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&path);
+
+    for day in 1..=5 {
+        fs::create_dir_all(path.join(format!("backup_2024-01-0{day}_120000"))).unwrap();
+    }
+
+    fs::create_dir_all(path.join("other")).unwrap();
+    fs::write(path.join("backup_notes.txt"), "keep").unwrap();
+
+    path
+}
+
+fn names(path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(path)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+
+    names
+}
+
+#[tokio::test]
+async fn backup_rotate() {
+    let path = prepare_folder("ffplayout_backup_rotate");
+
+    rotate(&path, 10).await.unwrap();
+    assert_eq!(names(&path).len(), 7);
+
+    rotate(&path, 2).await.unwrap();
+    assert_eq!(
+        names(&path),
+        vec![
+            "backup_2024-01-04_120000",
+            "backup_2024-01-05_120000",
+            "backup_notes.txt",
+            "other"
+        ]
+    );
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[tokio::test]
+async fn backup_rotate_keeps_newest() {
+    let path = prepare_folder("ffplayout_backup_rotate_zero");
+
+    rotate(&path, 0).await.unwrap();
+    assert_eq!(
+        names(&path),
+        vec!["backup_2024-01-05_120000", "backup_notes.txt", "other"]
+    );
+
+    fs::remove_dir_all(&path).unwrap();
+}
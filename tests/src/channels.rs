@@ -0,0 +1,165 @@
+use std::path::PathBuf;
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use serial_test::serial;
+
+use ffplayout::db::{handles, models};
+use ffplayout::player::controller::ChannelManager;
+use ffplayout::utils::{
+    advanced_config::AdvancedConfig,
+    channels::{drop_channel, insert_channel, scaffold_paths},
+    config::PlayoutConfig,
+    errors::ServiceError,
+};
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[actix_web::test]
+#[serial]
+async fn channel_scaffold_and_drop() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+    let root = std::env::temp_dir().join("ffplayout_scaffold");
+    let _ = std::fs::remove_dir_all(&root);
+
+    let channel = models::Channel {
+        public: root.join("public/2").to_string_lossy().to_string(),
+        playlists: root.join("playlists/2.json").to_string_lossy().to_string(),
+        storage: root.join("storage/2").to_string_lossy().to_string(),
+        ..Default::default()
+    };
+
+    scaffold_paths(&channel).await.unwrap();
+    assert!(root.join("public/2").is_dir());
+    assert!(root.join("storage/2").is_dir());
+    assert!(!root.join("playlists/2.json").exists());
+
+    let _ = std::fs::remove_dir_all(&root);
+
+    let channel = drop_channel(&pool, 1).await.unwrap();
+    assert_eq!(channel.id, 1);
+    assert!(handles::select_channel(&pool, &1).await.is_err());
+    assert!(drop_channel(&pool, 1).await.is_err());
+}
+
+#[actix_web::test]
+#[serial]
+async fn channel_from_template() {
+    let (mut config, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+    let root = std::env::temp_dir().join("ffplayout_template");
+
+    // save channel 1 as template
+    config.processing.width = 1280;
+    config.output.output_param =
+        "-f hls -hls_segment_filename assets/hls/stream-%d.ts assets/hls/stream.m3u8".to_string();
+    config.task.path = PathBuf::from("assets/storage/task.sh");
+
+    let config_id = handles::select_configuration(&pool, 1).await.unwrap().id;
+    handles::update_configuration(&pool, config_id, config)
+        .await
+        .unwrap();
+
+    let mut advanced = AdvancedConfig::new(
+        handles::select_advanced_configuration(&pool, 1)
+            .await
+            .unwrap(),
+    );
+    advanced.decoder.input_param = Some("-re".to_string());
+    handles::update_advanced_configuration(&pool, 1, advanced)
+        .await
+        .unwrap();
+
+    let presets = handles::select_presets(&pool, 1).await.unwrap();
+
+    let public = root.join("hls/2").to_string_lossy().to_string();
+    let storage = root.join("storage/2").to_string_lossy().to_string();
+    let channel = models::Channel {
+        name: "Channel 2".to_string(),
+        public: public.clone(),
+        playlists: root.join("playlists/2").to_string_lossy().to_string(),
+        storage: storage.clone(),
+        ..Default::default()
+    };
+
+    let (channel, config) = insert_channel(&pool, channel, Some(1)).await.unwrap();
+    let advanced = AdvancedConfig::new(
+        handles::select_advanced_configuration(&pool, channel.id)
+            .await
+            .unwrap(),
+    );
+
+    assert_eq!(channel.id, 2);
+    assert_eq!(config.processing.width, 1280);
+    assert_eq!(
+        config.output.output_param,
+        format!("-f hls -hls_segment_filename {public}/stream-%d.ts {public}/stream.m3u8")
+    );
+    assert_eq!(
+        config.task.path,
+        PathBuf::from(format!("{storage}/task.sh"))
+    );
+    assert_eq!(advanced.decoder.input_param, Some("-re".to_string()));
+    assert_eq!(
+        handles::select_presets(&pool, channel.id)
+            .await
+            .unwrap()
+            .len(),
+        presets.len()
+    );
+
+    // the template stays as it is
+    let template = PlayoutConfig::new(&pool, 1).await.unwrap();
+    assert!(template
+        .output
+        .output_param
+        .contains("assets/hls/stream.m3u8"));
+}
+
+#[actix_web::test]
+#[serial]
+async fn channel_from_missing_template() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+
+    let channel = models::Channel {
+        name: "Channel 2".to_string(),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        insert_channel(&pool, channel, Some(9)).await,
+        Err(ServiceError::BadRequest(_))
+    ));
+    assert_eq!(
+        handles::select_related_channels(&pool, None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
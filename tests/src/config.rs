@@ -0,0 +1,266 @@
+use std::path::{Path, PathBuf};
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use chrono::Weekday;
+use serial_test::serial;
+
+use ffplayout::db::{handles, models};
+use ffplayout::player::controller::ChannelManager;
+use ffplayout::utils::{
+    advanced_config::{AdvancedConfig, OutputOverride},
+    config::{Channel, OutputMode, PlayoutConfig, WeekdayOverride},
+    config_check::{check_channel, CheckClass},
+    config_diff::diff_configs,
+    config_schema::playout_config_schema,
+    reload::restart_needed,
+};
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[actix_web::test]
+#[serial]
+async fn weekday_overrides() {
+    let (mut config, _) = prepare_config().await;
+
+    config.playlist.weekday_overrides = vec![WeekdayOverride {
+        days: vec!["sat".to_string(), "Sunday".to_string()],
+        day_start: Some("08:00:00".to_string()),
+        ..Default::default()
+    }];
+
+    let weekend = config.for_weekday(Weekday::Sun);
+    let workday = config.for_weekday(Weekday::Fri);
+
+    assert_eq!(weekend.playlist.day_start, "08:00:00");
+    assert_eq!(weekend.playlist.start_sec, Some(28800.0));
+    assert_eq!(workday.playlist.day_start, config.playlist.day_start);
+    assert_eq!(workday.playlist.start_sec, config.playlist.start_sec);
+    assert!(config.check_weekday_overrides().is_ok());
+
+    // a hot config change keeps the resolved override
+    let mut changed = config.clone();
+    changed.processing.logo = "new_logo.png".to_string();
+    let kept = changed.keep_weekday(&weekend, Weekday::Sun);
+
+    assert_eq!(kept.playlist.day_start, "08:00:00");
+    assert_eq!(kept.playlist.start_sec, Some(28800.0));
+    assert_eq!(kept.processing.logo, "new_logo.png");
+    assert_eq!(kept.storage.filler, config.storage.filler);
+
+    config.playlist.weekday_overrides[0]
+        .days
+        .push("holiday".to_string());
+
+    assert!(config.check_weekday_overrides().is_err());
+}
+
+#[test]
+fn config_diff_fields() {
+    let mut stored = PlayoutConfig::default();
+    stored.output.output_param = "-f flv rtmp://a.rtmp.youtube.com/live2/abcd-1234".to_string();
+
+    let mut file = stored.clone();
+    assert!(diff_configs(&stored, &file).is_empty());
+
+    file.processing.width = stored.processing.width + 256;
+    file.output.output_param = stored.output.output_param.replace("abcd-1234", "efgh-5678");
+
+    let changes = diff_configs(&stored, &file);
+    let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+
+    assert_eq!(fields, vec!["output.output_param", "processing.width"]);
+    assert_eq!(changes[1].file, (stored.processing.width + 256).to_string());
+
+    // the new stream key is not printed
+    assert!(!changes[0].file.contains("efgh-5678"));
+    assert!(changes[0].file.ends_with("(secret changed)"));
+}
+
+#[actix_web::test]
+#[serial]
+async fn config_schema_fields() {
+    let (config, _) = prepare_config().await;
+    let schema = playout_config_schema();
+    let config = serde_json::to_value(&config).unwrap();
+
+    for (section, fields) in config.as_object().unwrap() {
+        for field in fields.as_object().unwrap().keys() {
+            assert!(
+                schema["properties"][section]["properties"][field].is_object(),
+                "{section}.{field} missing in schema"
+            );
+        }
+    }
+}
+
+#[test]
+fn advanced_output_overrides() {
+    let mut advanced = AdvancedConfig::default();
+    advanced.encoder.input_param = Some("-hwaccel qsv".to_string());
+    advanced.filter.fps = Some("fps={}".to_string());
+
+    let mut output = OutputOverride::default();
+    output.encoder.input_param = Some("-hwaccel vulkan".to_string());
+    advanced
+        .output_overrides
+        .insert("desktop".to_string(), output);
+
+    let desktop = advanced.for_mode(&OutputMode::Desktop);
+    let hls = advanced.for_mode(&OutputMode::HLS);
+
+    assert_eq!(
+        desktop.encoder.input_cmd,
+        Some(vec!["-hwaccel".to_string(), "vulkan".to_string()])
+    );
+    assert_eq!(desktop.filter.fps, Some("fps={}".to_string()));
+    assert_eq!(hls.encoder.input_param, Some("-hwaccel qsv".to_string()));
+    assert!(advanced.validate().is_ok());
+
+    advanced
+        .output_overrides
+        .insert("rtmp".to_string(), OutputOverride::default());
+    assert!(advanced.validate().is_err());
+}
+
+#[test]
+fn shared_filler_pool() {
+    let mut global = models::GlobalSettings {
+        storage: "/tv-media".to_string(),
+        filler_pool: "filler".to_string(),
+        ..Default::default()
+    };
+    let channel = models::Channel {
+        storage: "/tv-media/2".to_string(),
+        ..Default::default()
+    };
+
+    let config = Channel::new(&global, channel.clone());
+
+    assert_eq!(config.filler_pool, PathBuf::from("/tv-media/filler"));
+    assert_eq!(config.filler_root(true), Path::new("/tv-media/filler"));
+    assert_eq!(config.filler_root(false), Path::new("/tv-media/2"));
+
+    // without pool the channel storage is used
+    global.filler_pool = String::new();
+    let config = Channel::new(&global, channel);
+
+    assert_eq!(config.filler_root(true), Path::new("/tv-media/2"));
+}
+
+#[actix_web::test]
+#[serial]
+async fn config_warnings() {
+    let (mut config, _) = prepare_config().await;
+
+    config.processing.copy_video = false;
+    config.processing.copy_audio = false;
+    config.processing.vtt_enable = false;
+
+    assert!(config.warnings().is_empty());
+
+    config.processing.copy_video = true;
+    config.processing.add_logo = true;
+    config.processing.vtt_enable = true;
+    config.output.mode = OutputMode::Stream;
+
+    assert_eq!(config.warnings().len(), 2);
+}
+
+#[actix_web::test]
+#[serial]
+async fn keep_locked_config_fields() {
+    let (stored, _) = prepare_config().await;
+    let mut config = stored.clone();
+
+    config.output.output_param = "-f flv rtmp://example.org/live/stream".to_string();
+    config.output.mode = OutputMode::Null;
+    config.logging.ffmpeg_level = "DEBUG".to_string();
+
+    config
+        .keep_locked(
+            &stored,
+            &["output.output_param".to_string(), "logging".to_string()],
+        )
+        .unwrap();
+
+    assert_eq!(config.output.output_param, stored.output.output_param);
+    assert_eq!(config.logging.ffmpeg_level, stored.logging.ffmpeg_level);
+    assert_eq!(config.output.mode, OutputMode::Null);
+}
+
+#[actix_web::test]
+#[serial]
+async fn check_config_classes() {
+    let (mut config, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+    let mut stored = handles::select_configuration(&pool, 1).await.unwrap();
+
+    stored.processing_add_logo = true;
+    stored.storage_filler = String::new();
+    config.processing.logo_path = "assets/missing_logo.png".to_string();
+    config.processing.copy_video = false;
+    config.processing.copy_audio = false;
+    config.processing.vtt_enable = false;
+    config.storage.emergency_slate = String::new();
+    config.text.add_text = false;
+    config.output.mode = OutputMode::Desktop;
+
+    let check = check_channel(&config, &stored, "Channel 1".to_string()).await;
+    assert_eq!(check.failures.len(), 1);
+    assert_eq!(check.failures[0].0, CheckClass::Media);
+    assert_eq!(check.code(), 4);
+
+    // the worst class wins
+    config.processing.vtt_enable = true;
+    let check = check_channel(&config, &stored, "Channel 1".to_string()).await;
+    assert_eq!(check.failures.len(), 2);
+    assert_eq!(check.code(), 4);
+
+    stored.processing_add_logo = false;
+    let check = check_channel(&config, &stored, "Channel 1".to_string()).await;
+    assert_eq!(check.code(), CheckClass::Warning.code());
+    assert!(check.lines()[1].starts_with("  [warning]"));
+}
+
+#[test]
+fn reload_global_settings() {
+    let old = models::GlobalSettings::default();
+    let mut new = old.clone();
+
+    // channel paths and mail settings go to the channels with the reload
+    new.storage = "/mnt/storage".to_string();
+    new.smtp_server = "mail.example.org".to_string();
+    assert!(restart_needed(&old, &new).is_empty());
+
+    // backup settings are read before every backup
+    new.backup_keep = 3;
+    new.backup_interval = 12;
+    assert!(restart_needed(&old, &new).is_empty());
+
+    new.logs = "/var/log/playout".to_string();
+    assert_eq!(restart_needed(&old, &new), vec!["logs"]);
+}
@@ -0,0 +1,185 @@
+use ffplayout::player::utils::{channel_state::ChannelState, Media};
+use ffplayout::utils::{
+    amcp::{find_clip, parse_command as amcp_command, split_params, template_text, AmcpCommand},
+    config::PlayoutConfig,
+    control::{button_command, PlayerCtl, Process, ProcessCtl, RemoteCtl},
+    osc::{parse_command, OscArg, OscMessage},
+    secrets::{token_matches, MASK},
+    tally::{mqtt_connect, mqtt_packet, mqtt_publish, Tally},
+};
+
+#[test]
+fn process_stop_with_drain() {
+    let proc: Process = serde_json::from_str(r#"{"command": "stop", "drain": 600}"#).unwrap();
+
+    assert_eq!(proc.command, ProcessCtl::Stop);
+    assert_eq!(proc.drain, Some(600));
+
+    let proc: Process = serde_json::from_str(r#"{"command": "stop"}"#).unwrap();
+
+    assert_eq!(proc.drain, None);
+}
+
+#[test]
+fn amcp_commands() {
+    let params = split_params(
+        r#"CG 1-20 ADD 1 "lower third" 1 "<templateData><componentData id=\"f0\"><data id=\"text\" value=\"Tom &amp; Jerry\"/></componentData></templateData>""#,
+    );
+
+    assert_eq!(params.len(), 7);
+    assert_eq!(params[4], "lower third");
+    assert_eq!(
+        amcp_command(&params),
+        Ok(AmcpCommand::Text {
+            channel: 1,
+            text: "Tom & Jerry".to_string()
+        })
+    );
+
+    assert_eq!(template_text(r#"{"f0": "Breaking News"}"#), "Breaking News");
+    assert_eq!(template_text("plain"), "plain");
+
+    assert_eq!(
+        amcp_command(&split_params("loadbg 2-10 NEWS/OPENER AUTO")),
+        Ok(AmcpCommand::Load {
+            channel: 2,
+            clip: "NEWS/OPENER".to_string()
+        })
+    );
+    assert_eq!(
+        amcp_command(&split_params("PLAY 1")),
+        Ok(AmcpCommand::Play {
+            channel: 1,
+            clip: None
+        })
+    );
+    assert_eq!(amcp_command(&split_params("PLAY x")), Err(401));
+    assert_eq!(amcp_command(&split_params("LOADBG 1")), Err(402));
+    assert_eq!(amcp_command(&split_params("SWAP 1 2")), Err(400));
+
+    let list: Vec<Media> = [
+        "/tv-media/news/Opener.mp4",
+        "/tv-media/clip.mp4",
+        "/tv-media/news/opener.mov",
+    ]
+    .iter()
+    .map(|source| Media {
+        source: source.to_string(),
+        ..Default::default()
+    })
+    .collect();
+
+    assert_eq!(find_clip(&list, 0, "NEWS/OPENER"), Some(0));
+    assert_eq!(find_clip(&list, 1, "news/opener"), Some(2));
+    assert_eq!(find_clip(&list, 0, "CLIP"), Some(1));
+    assert_eq!(find_clip(&list, 0, "OPENER"), Some(0));
+    assert_eq!(find_clip(&list, 0, "PENER"), None);
+}
+
+#[test]
+fn osc_messages() {
+    let message = OscMessage::new(
+        "/ffplayout/2/text",
+        vec![OscArg::Str("Hello".to_string()), OscArg::Float(0.5)],
+    );
+    let packet = message.encode();
+
+    assert_eq!(packet.len() % 4, 0);
+    assert_eq!(&packet[..20], b"/ffplayout/2/text\0\0\0");
+    assert_eq!(OscMessage::decode(&packet), Some(vec![message.clone()]));
+
+    // bundle with time tag and two messages
+    let next = OscMessage::new("/ffplayout/1/next", vec![OscArg::Int(1)]).encode();
+    let mut bundle = b"#bundle\0".to_vec();
+    bundle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+    for part in [&next, &packet] {
+        bundle.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        bundle.extend_from_slice(part);
+    }
+
+    let messages = OscMessage::decode(&bundle).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(
+        parse_command(&messages[0]),
+        Ok(Some((1, RemoteCtl::Player(PlayerCtl::Next))))
+    );
+    assert_eq!(
+        parse_command(&messages[1]),
+        Ok(Some((2, RemoteCtl::Text("Hello".to_string()))))
+    );
+
+    // button release
+    let release = OscMessage::new("/ffplayout/1/stop", vec![OscArg::Float(0.0)]);
+    assert_eq!(parse_command(&release), Ok(None));
+
+    let volume = OscMessage::new("/ffplayout/1/volume", vec![OscArg::Float(0.0)]);
+    assert_eq!(
+        parse_command(&volume),
+        Ok(Some((1, RemoteCtl::Volume(0.0))))
+    );
+
+    assert!(parse_command(&OscMessage::new("/other/1/next", vec![])).is_err());
+    assert!(OscMessage::decode(b"/ffplayout").is_none());
+}
+
+#[test]
+fn button_control_token() {
+    assert!(token_matches("abc123", "abc123"));
+    assert!(!token_matches("abc123", "abc124"));
+    assert!(!token_matches("abc123", "abc"));
+    // an empty token disables the endpoints
+    assert!(!token_matches("", ""));
+
+    assert_eq!(
+        button_command("next").unwrap(),
+        RemoteCtl::Player(PlayerCtl::Next)
+    );
+    assert_eq!(
+        button_command("stop").unwrap(),
+        RemoteCtl::Process(ProcessCtl::Stop)
+    );
+    assert_eq!(button_command("slate").unwrap(), RemoteCtl::Slate);
+    assert!(button_command("restart").is_err());
+
+    let mut config = PlayoutConfig::default();
+    config.general.control_token = "deck-token".to_string();
+    let stored = config.clone();
+    config.redact();
+
+    assert_eq!(config.general.control_token, MASK);
+
+    config.restore_secrets(&stored);
+    assert_eq!(config.general.control_token, "deck-token");
+}
+
+#[test]
+fn tally_state() {
+    assert_eq!(
+        Tally::new(ChannelState::OnAirPlaylist, false),
+        Tally::Program
+    );
+    assert_eq!(Tally::new(ChannelState::Fallback, false), Tally::Program);
+    assert_eq!(Tally::new(ChannelState::OnAirLive, false), Tally::Live);
+    assert_eq!(Tally::new(ChannelState::Fallback, true), Tally::Emergency);
+    assert_eq!(Tally::new(ChannelState::Stopped, true), Tally::Off);
+    assert_eq!(Tally::Emergency.to_string(), "emergency");
+
+    // remaining length over 127 bytes needs two bytes
+    let packet = mqtt_packet(0x31, &[0; 200]);
+    assert_eq!(&packet[..3], &[0x31, 0xC8, 0x01]);
+    assert_eq!(packet.len(), 203);
+
+    assert_eq!(
+        mqtt_connect("ffplayout-1", "", None),
+        vec![
+            0x10, 23, 0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 30, 0, 11, b'f', b'f', b'p', b'l',
+            b'a', b'y', b'o', b'u', b't', b'-', b'1'
+        ]
+    );
+    assert_eq!(mqtt_connect("c", "user", Some("pass"))[9], 0xC2);
+    assert_eq!(
+        mqtt_publish("a/b", b"on"),
+        vec![0x31, 7, 0, 3, b'a', b'/', b'b', b'o', b'n']
+    );
+}
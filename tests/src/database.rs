@@ -0,0 +1,246 @@
+use std::env;
+
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+
+use ffplayout::db::{
+    db_backup, db_restore_to, db_version, handles, models::ConfigPreset, pending_migrations,
+    pid_path, running_instance,
+};
+use ffplayout::utils::{
+    config::{OutputMode, PlayoutConfig},
+    errors::ServiceError,
+};
+
+async fn file_pool(path: &std::path::Path) -> Pool<Sqlite> {
+    SqlitePoolOptions::new()
+        .connect(&format!("sqlite://{}?mode=rwc", path.display()))
+        .await
+        .unwrap()
+}
+
+async fn channel_name(pool: &Pool<Sqlite>) -> String {
+    handles::select_channel(pool, &1).await.unwrap().name
+}
+
+#[tokio::test]
+async fn database_backup_restore() {
+    let dir = env::temp_dir().join("ffplayout_database_restore");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+
+    let db_file = dir.join("ffplayout.db");
+    let backup = dir.join("backup.db");
+
+    let pool = file_pool(&db_file).await;
+    handles::db_migrate(&pool).await.unwrap();
+    let name = channel_name(&pool).await;
+
+    db_backup(&pool, &backup).await.unwrap();
+
+    // an existing target is not overwritten
+    assert!(db_backup(&pool, &backup).await.is_err());
+
+    sqlx::query("UPDATE channels SET name = 'changed' WHERE id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // a stale WAL would replay the change over the restored database
+    let wal = dir.join("ffplayout.db-wal");
+    let shm = dir.join("ffplayout.db-shm");
+
+    db_restore_to(&pool, &backup, &db_file).await.unwrap();
+
+    assert!(pool.is_closed());
+    assert!(!wal.exists());
+    assert!(!shm.exists());
+
+    let pool = file_pool(&db_file).await;
+    assert_eq!(channel_name(&pool).await, name);
+
+    // broken backups are rejected, before the database is touched
+    let broken = dir.join("broken.db");
+    tokio::fs::write(&broken, "no database").await.unwrap();
+
+    assert!(db_restore_to(&pool, &broken, &db_file).await.is_err());
+    assert!(db_restore_to(&pool, &dir.join("missing.db"), &db_file)
+        .await
+        .is_err());
+    assert!(!pool.is_closed());
+
+    pool.close().await;
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+}
+
+#[test]
+fn database_running_instance() {
+    let dir = env::temp_dir().join("ffplayout_database_pid");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let db_file = dir.join("ffplayout.db");
+
+    assert_eq!(pid_path(&db_file), dir.join("ffplayout.pid"));
+    assert_eq!(running_instance(&db_file), None);
+
+    // the own process doesn't block
+    std::fs::write(pid_path(&db_file), std::process::id().to_string()).unwrap();
+    assert_eq!(running_instance(&db_file), None);
+
+    // stale pid files are ignored
+    std::fs::write(pid_path(&db_file), "4194304").unwrap();
+    assert_eq!(running_instance(&db_file), None);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn database_broken_secret() {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query("UPDATE configurations SET output_param = 'enc:v1:broken', ingest_param = '-i plain' WHERE channel_id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let config = handles::select_configuration(&pool, 1).await.unwrap();
+
+    // ciphertext must not reach the ffmpeg command line
+    assert_eq!(config.output_param, "");
+    assert_eq!(config.ingest_param, "-i plain");
+}
+
+#[test]
+fn database_config_preset() {
+    let mut source = PlayoutConfig::default();
+    source.processing.width = 1920;
+    source.processing.height = 1080;
+    source.output.mode = OutputMode::Stream;
+    source.output.output_param = "-f flv rtmp://127.0.0.1/live/stream".to_string();
+
+    let preset = ConfigPreset::from("YouTube 1080p", &source);
+    let mut config = PlayoutConfig::default();
+
+    preset.apply(&mut config).unwrap();
+
+    assert_eq!(config.processing.width, 1920);
+    assert_eq!(config.processing.height, 1080);
+    assert_eq!(config.output.mode, OutputMode::Stream);
+    assert_eq!(config.output.output_param, source.output.output_param);
+
+    let mut broken = preset.clone();
+    broken.output_mode = "rtmp".to_string();
+    let mut config = PlayoutConfig::default();
+
+    assert!(matches!(
+        broken.apply(&mut config),
+        Err(ServiceError::BadRequest(_))
+    ));
+    // nothing is applied from a broken preset
+    assert_eq!(
+        config.processing.width,
+        PlayoutConfig::default().processing.width
+    );
+}
+
+#[tokio::test]
+async fn database_media_move_delete() {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    let news = vec!["news".to_string()];
+
+    for path in ["clip.mp4", "folder/a.mp4", "folder/b.mp4", "folder_2/c.mp4"] {
+        handles::update_media_tags(&pool, 1, path, &news)
+            .await
+            .unwrap();
+    }
+
+    handles::move_media(&pool, 1, "clip.mp4", "renamed.mp4")
+        .await
+        .unwrap();
+    assert!(handles::select_media(&pool, 1, "clip.mp4")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        handles::select_media(&pool, 1, "renamed.mp4")
+            .await
+            .unwrap()
+            .unwrap()
+            .tags,
+        news
+    );
+
+    // a folder takes all files with it, but not the ones with the same prefix
+    handles::move_media(&pool, 1, "folder", "archive/folder")
+        .await
+        .unwrap();
+    let paths: Vec<String> = handles::select_tagged_media(&pool, 1, &news, false)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|m| m.path)
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "archive/folder/a.mp4",
+            "archive/folder/b.mp4",
+            "folder_2/c.mp4",
+            "renamed.mp4"
+        ]
+    );
+
+    handles::delete_media(&pool, 1, "archive").await.unwrap();
+    handles::delete_media(&pool, 1, "renamed.mp4")
+        .await
+        .unwrap();
+    let tagged = handles::select_tagged_media(&pool, 1, &news, false)
+        .await
+        .unwrap();
+    assert_eq!(tagged.len(), 1);
+    assert_eq!(tagged[0].path, "folder_2/c.mp4");
+
+    // the tag is still there, without media
+    let tags = handles::select_tags(&pool, 1).await.unwrap();
+    assert_eq!(tags[0].media, 1);
+}
+
+#[tokio::test]
+async fn migrations_pending() {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    // new database, all migrations are pending
+    assert_eq!(db_version(&pool).await, None);
+    let pending = pending_migrations(&pool).await;
+    assert!(!pending.is_empty());
+
+    handles::db_migrate(&pool).await.unwrap();
+
+    assert_eq!(db_version(&pool).await, pending.last().map(|m| m.0));
+    assert!(pending_migrations(&pool).await.is_empty());
+
+    // roll back the last one in the history
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    assert_eq!(
+        pending_migrations(&pool).await,
+        pending[pending.len() - 1..]
+    );
+}
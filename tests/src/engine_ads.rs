@@ -0,0 +1,201 @@
+use std::{collections::HashMap, path::Path};
+
+use ffplayout::player::utils::{
+    scte35::{parse_splice_info, tag_playlist, SpliceCue, TsScanner},
+    vast::{
+        break_nodes, cache_path, fill_macros, parse_duration, parse_vast, pick_media_file,
+        ADVERTISEMENT,
+    },
+    Media,
+};
+
+#[test]
+fn scte35_cues() {
+    // splice_insert: event 42, out of network at 10s for 30s
+    let section = [
+        0xFC, 0x30, 0x25, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xF0, 0x14, 0x05, 0x00,
+        0x00, 0x00, 0x2A, 0x7F, 0xEF, 0xFE, 0x00, 0x0D, 0xBB, 0xA0, 0xFE, 0x00, 0x29, 0x33, 0x70,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let cue = SpliceCue {
+        event_id: 42,
+        out: true,
+        pts: Some(10.0),
+        duration: Some(30.0),
+    };
+
+    assert_eq!(parse_splice_info(&section), vec![cue.clone()]);
+    assert_eq!(cue.hls_tag(), "#EXT-X-CUE-OUT:30.000");
+
+    let packet = |pid: u16, section: &[u8]| {
+        let mut packet = vec![0x47, 0x40 | (pid >> 8) as u8, pid as u8, 0x10, 0x00];
+        packet.extend_from_slice(section);
+        packet.resize(188, 0xFF);
+        packet
+    };
+    let pat = [
+        0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xE1, 0x00, 0, 0, 0, 0,
+    ];
+    let pmt = [
+        0x02, 0xB0, 0x12, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x00, 0x86, 0xE1, 0x02,
+        0xF0, 0x00, 0, 0, 0, 0,
+    ];
+    let mut stream = packet(0, &pat);
+    stream.append(&mut packet(0x100, &pmt));
+    stream.append(&mut packet(0x102, &section));
+
+    let mut scanner = TsScanner::new();
+
+    // cues come only with the complete packet
+    assert!(scanner.push(&stream[..400]).is_empty());
+    assert_eq!(scanner.push(&stream[400..]), vec![cue]);
+
+    let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:5\n#EXTINF:6.000000,\nstream-5.ts\n#EXTINF:6.000000,\nstream-6.ts\n";
+    let tags = HashMap::from([(
+        "stream-6.ts".to_string(),
+        vec!["#EXT-X-CUE-OUT:30.000".to_string()],
+    )]);
+    let tagged = tag_playlist(playlist, &tags);
+
+    assert_eq!(
+        tagged,
+        playlist.replace(
+            "stream-5.ts\n#EXTINF",
+            "stream-5.ts\n#EXT-X-CUE-OUT:30.000\n#EXTINF"
+        )
+    );
+    // tags are not added twice
+    assert_eq!(tag_playlist(&tagged, &tags), tagged);
+}
+
+#[test]
+fn vast_ad_break() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<VAST version="4.0">
+  <Ad id="second" sequence="2">
+    <InLine>
+      <AdSystem>Test</AdSystem>
+      <AdTitle>Second Spot</AdTitle>
+      <Impression><![CDATA[https://ads.example.org/imp?ad=2&cb=[CACHEBUSTING]]]></Impression>
+      <Creatives>
+        <Creative>
+          <Linear>
+            <Duration>00:00:15.000</Duration>
+            <MediaFiles>
+              <MediaFile delivery="progressive" type="video/mp4" width="1920" height="1080" bitrate="6000">https://cdn.example.org/2_1080.mp4</MediaFile>
+            </MediaFiles>
+          </Linear>
+        </Creative>
+      </Creatives>
+    </InLine>
+  </Ad>
+  <Ad id="first" sequence="1">
+    <InLine>
+      <AdTitle>First Spot</AdTitle>
+      <Impression>https://ads.example.org/imp?ad=1&amp;x=1</Impression>
+      <Error>https://ads.example.org/err?code=[ERRORCODE]</Error>
+      <Creatives>
+        <Creative>
+          <Linear>
+            <Duration>00:00:30</Duration>
+            <TrackingEvents>
+              <Tracking event="start">https://ads.example.org/start</Tracking>
+              <Tracking event="midpoint">https://ads.example.org/mid</Tracking>
+            </TrackingEvents>
+            <MediaFiles>
+              <MediaFile type="application/javascript" apiFramework="VPAID">https://cdn.example.org/vpaid.js</MediaFile>
+              <MediaFile type="video/webm" width="1024">https://cdn.example.org/1.webm</MediaFile>
+              <MediaFile type="video/mp4" width="640" bitrate="800">https://cdn.example.org/1_360.mp4</MediaFile>
+              <MediaFile type="video/mp4" width="1280" bitrate="3000">https://cdn.example.org/1_720.mp4</MediaFile>
+            </MediaFiles>
+          </Linear>
+        </Creative>
+      </Creatives>
+    </InLine>
+  </Ad>
+  <Ad id="wrapped">
+    <Wrapper>
+      <VASTAdTagURI><![CDATA[https://other.example.org/vast.xml]]></VASTAdTagURI>
+      <Impression>https://ads.example.org/wrapper</Impression>
+    </Wrapper>
+  </Ad>
+</VAST>"#;
+
+    let ads = parse_vast(xml);
+
+    assert_eq!(ads.len(), 3);
+    assert_eq!(ads[0].id, "wrapped");
+    assert_eq!(
+        ads[0].wrapper.as_deref(),
+        Some("https://other.example.org/vast.xml")
+    );
+    assert_eq!(ads[1].title, "First Spot");
+    assert_eq!(ads[1].duration, 30.0);
+    assert_eq!(
+        ads[1].impressions,
+        vec!["https://ads.example.org/imp?ad=1&x=1"]
+    );
+    assert_eq!(
+        ads[1].tracking[1],
+        (
+            "midpoint".to_string(),
+            "https://ads.example.org/mid".to_string()
+        )
+    );
+    assert_eq!(ads[2].duration, 15.0);
+    assert_eq!(
+        ads[2].impressions,
+        vec!["https://ads.example.org/imp?ad=2&cb=[CACHEBUSTING]"]
+    );
+
+    let file = pick_media_file(&ads[1].media_files, 1280).unwrap();
+    assert_eq!(file.url, "https://cdn.example.org/1_720.mp4");
+
+    assert_eq!(parse_duration("00:01:02.500"), Some(62.5));
+    assert_eq!(parse_duration("62"), None);
+    assert_eq!(
+        fill_macros(
+            "https://ads.example.org/vast?d=[DURATION]&cb=[CACHEBUSTING]",
+            &[
+                ("DURATION", "120".to_string()),
+                ("CACHEBUSTING", "42".to_string())
+            ]
+        ),
+        "https://ads.example.org/vast?d=120&cb=42"
+    );
+
+    let dir = Path::new("/tmp/ffplayout_ads_1");
+    let path = cache_path(dir, "https://cdn.example.org/1_720.MP4?token=abc");
+    assert_eq!(path.extension().unwrap(), "mp4");
+    assert_eq!(
+        path,
+        cache_path(dir, "https://cdn.example.org/1_720.MP4?token=abc")
+    );
+
+    let ad_break = Media {
+        begin: Some(3600.0),
+        out: 120.0,
+        duration: 120.0,
+        category: "ad_break".to_string(),
+        ..Media::default()
+    };
+    let spot = |out| Media {
+        out,
+        duration: out,
+        ..Media::default()
+    };
+    let nodes = break_nodes(&ad_break, vec![spot(30.0), spot(15.0)]);
+
+    assert_eq!(nodes.len(), 3);
+    assert_eq!(nodes[1].begin, Some(3630.0));
+    assert_eq!(nodes[1].category, ADVERTISEMENT);
+    // the rest is not requested again
+    assert_eq!(nodes[2].begin, Some(3645.0));
+    assert_eq!(nodes[2].out, 75.0);
+    assert_eq!(nodes[2].category, "filler");
+
+    assert_eq!(
+        break_nodes(&ad_break, vec![spot(60.0), spot(59.5)]).len(),
+        2
+    );
+}
@@ -6,6 +6,7 @@ use ffplayout::db::handles;
 use ffplayout::player::{
     controller::{ChannelManager, ProcessUnit::*},
     input::playlist::gen_source,
+    output::{decoder_cmd, encoder_cmd},
     utils::prepare_output_cmd,
     utils::Media,
 };
@@ -65,6 +66,50 @@ async fn video_audio_input() {
     assert_eq!(media.filter.unwrap().map(), test_filter_map);
 }
 
+#[tokio::test]
+async fn dry_run_commands() {
+    let (mut config, manager) = get_config().await;
+
+    config.output.mode = Null;
+    config.output.output_cmd = Some(vec_strings!["-f", "null", "-"]);
+    config.processing.add_logo = false;
+
+    let media_obj = Media::new(0, "./assets/media_mix/with_audio.mp4", true).await;
+    let media = gen_source(&config, media_obj, &manager, 1).await;
+    let dec_cmd = decoder_cmd(
+        &config,
+        "level+error",
+        media.cmd.clone().unwrap(),
+        media.filter.clone(),
+        false,
+    );
+
+    assert_eq!(
+        dec_cmd[..6],
+        vec_strings![
+            "-hide_banner",
+            "-nostats",
+            "-v",
+            "level+error",
+            "-i",
+            "./assets/media_mix/with_audio.mp4"
+        ]
+    );
+    assert!(dec_cmd.contains(&"-filter_complex".to_string()));
+    assert!(dec_cmd.ends_with(&vec_strings!["-f", "mpegts", "-"]));
+
+    let dec_cmd = decoder_cmd(&config, "level+error", media.cmd.unwrap(), None, true);
+    assert_eq!(dec_cmd[4], "-re");
+
+    let (program, enc_cmd) = encoder_cmd(&config, "level+error").await.unwrap();
+    assert_eq!(program, "ffmpeg");
+    assert!(enc_cmd.contains(&"pipe:0".to_string()));
+    assert!(enc_cmd.ends_with(&vec_strings!["-f", "null", "-"]));
+
+    config.output.mode = HLS;
+    assert!(encoder_cmd(&config, "level+error").await.is_none());
+}
+
 #[tokio::test]
 async fn video_audio_custom_filter1_input() {
     let (mut config, manager) = get_config().await;
@@ -1875,3 +1920,23 @@ async fn multi_video_multi_audio_hls() {
 
     assert_eq!(enc_cmd, test_cmd);
 }
+
+#[tokio::test]
+async fn backup_source_for_missing_clip() {
+    let (config, manager) = get_config().await;
+
+    let mut media_obj = Media::new(0, "./assets/media_mix/not_existing.mp4", false).await;
+    media_obj.out = 10.0;
+    media_obj.duration = 10.0;
+    media_obj.backup_source = "./assets/media_mix/with_audio.mp4".to_string();
+
+    let media = gen_source(&config, media_obj, &manager, 1).await;
+
+    assert_eq!(media.source, "./assets/media_mix/with_audio.mp4");
+    assert!(media.backup_source.is_empty());
+    assert!(media.probe.is_some());
+    assert!(media
+        .cmd
+        .unwrap()
+        .contains(&"./assets/media_mix/with_audio.mp4".to_string()));
+}
@@ -0,0 +1,220 @@
+use std::path::Path;
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use serial_test::serial;
+
+use ffplayout::db::handles;
+use ffplayout::player::{
+    controller::{ChannelManager, ProcessUnit},
+    filter::{gapless_length, v_drawtext::zmq_text, volume_unit},
+    utils::{
+        coming_up::{card_text, clock_time, next_programs, render_cmd},
+        Media,
+    },
+};
+use ffplayout::utils::{
+    config::{OutputMode, PlayoutConfig, Visualization},
+    text_layers::{animation_frame, layer_names, layer_text, store_text, TextAnimation},
+    TextFilter,
+};
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[test]
+fn gapless_clip_length() {
+    assert_eq!(gapless_length(0.0, 10.0, 25.0), (250, 480000));
+    assert_eq!(gapless_length(0.0, 0.01, 25.0), (1, 1920));
+    assert_eq!(gapless_length(0.0, 10.0, 0.0), (0, 0));
+
+    // rounding differences don't add up from clip to clip
+    let fps = 30000.0 / 1001.0;
+    let first = gapless_length(0.0, 10.0, fps);
+    let second = gapless_length(10.0, 10.0, fps);
+
+    assert_eq!(first, (300, 480480));
+    assert_eq!(second, (299, 478878));
+    assert_eq!(
+        gapless_length(0.0, 20.0, fps),
+        (first.0 + second.0, first.1 + second.1)
+    );
+}
+
+#[test]
+fn live_filter_control() {
+    let mut config = PlayoutConfig::default();
+    config.output.mode = OutputMode::Stream;
+    config.processing.audio_tracks = 1;
+    config.processing.audio_track_index = -1;
+
+    // without zmq the volume is fixed in the decoder
+    assert_eq!(volume_unit(&config), None);
+
+    config.text.add_text = true;
+    config.text.zmq_stream_socket = Some("127.0.0.1:5555".to_string());
+
+    assert_eq!(volume_unit(&config), Some(ProcessUnit::Encoder));
+
+    config.output.mode = OutputMode::HLS;
+
+    assert_eq!(volume_unit(&config), Some(ProcessUnit::Decoder));
+
+    config.processing.copy_audio = true;
+
+    assert_eq!(volume_unit(&config), None);
+
+    config.text.style = "x=10:y=10:fontsize=24".to_string();
+
+    assert_eq!(
+        zmq_text(&config, "text='News':fontsize=32"),
+        "x=10:y=10:fontsize=24:text='News':fontsize=32"
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn audio_only_visualization() {
+    let (mut config, _) = prepare_config().await;
+    config.output.mode = OutputMode::Stream;
+    config.output.output_filter = None;
+    config.processing.audio_only = true;
+    config.processing.width = 1280;
+    config.processing.height = 720;
+    config.processing.fps = 25.0;
+
+    let mut media = Media {
+        unit: ProcessUnit::Encoder,
+        ..Media::default()
+    };
+    media.add_filter(&config, &None).await;
+    assert!(media.filter.unwrap().video_chain.is_empty());
+
+    config.processing.visualization = Visualization::Waves;
+    media.add_filter(&config, &None).await;
+
+    let mut filter = media.filter.unwrap();
+    assert_eq!(
+        filter.cmd(),
+        vec![
+            "-filter_complex",
+            "[0:a:0]showwaves=s=1280x720:mode=cline:rate=25,format=yuv420p[vout0]"
+        ]
+    );
+    assert!(filter.map().contains(&"[vout0]".to_string()));
+    assert!(filter.map().contains(&"0:a:0".to_string()));
+}
+
+#[test]
+fn text_layer_animation() {
+    assert_eq!(
+        layer_names("lower_third, ticker,dyntext,bad-name,,ticker"),
+        vec!["lower_third", "ticker"]
+    );
+
+    let mut chain = vec![];
+    store_text(&mut chain, "dyntext", "text='news'".to_string());
+    store_text(&mut chain, "ticker", "text='old'".to_string());
+    store_text(&mut chain, "ticker", "text='breaking'".to_string());
+
+    assert_eq!(
+        layer_text(&chain, "dyntext"),
+        Some("text='news'".to_string())
+    );
+    assert_eq!(
+        layer_text(&chain, "ticker"),
+        Some("text='breaking'".to_string())
+    );
+    assert_eq!(layer_text(&chain, "lower_third"), None);
+
+    let message = TextFilter {
+        text: Some("Jane Doe".to_string()),
+        x: Some("80".to_string()),
+        alpha: Some("0.8".to_string()),
+        ..Default::default()
+    };
+
+    let frame = animation_frame(&message, "", TextAnimation::Fade, 0.5);
+    assert_eq!(frame.alpha, Some("0.400".to_string()));
+
+    let frame = animation_frame(&message, "", TextAnimation::SlideLeft, 1.0);
+    assert_eq!(frame.x, Some("(80)-((80)+text_w)*1.000".to_string()));
+
+    // the position comes from the style, without it in the message
+    let frame = animation_frame(
+        &message,
+        "y=h-160:fontsize=42",
+        TextAnimation::SlideBottom,
+        0.0,
+    );
+    assert_eq!(frame.y, Some("(h-160)+(h-(h-160))*0.000".to_string()));
+}
+
+#[actix_web::test]
+#[serial]
+async fn coming_up_card() {
+    let (config, _) = prepare_config().await;
+    let clip = |source: &str, category: &str, begin: f64, title: Option<&str>| Media {
+        source: source.to_string(),
+        category: category.to_string(),
+        begin: Some(begin),
+        title: title.map(str::to_string),
+        ..Media::default()
+    };
+    let list = vec![
+        clip("missing.mp4", "", 72000.0, None),
+        clip("/media/ads.mp4", "advertisement", 72030.0, None),
+        clip("/media/evening_news.mp4", "", 72900.0, Some("Evening News")),
+        clip("/media/filler.mp4", "filler", 73800.0, None),
+        clip("/media/weather.mp4", "", 88200.0, None),
+        clip("/media/late.mp4", "", 90000.0, None),
+    ];
+
+    assert_eq!(clock_time(72900.0), "20:15");
+    assert_eq!(clock_time(88200.0), "00:30");
+
+    let programs = next_programs(&list, 0, 2);
+    assert_eq!(
+        programs,
+        vec![
+            ("20:15".to_string(), "Evening News".to_string()),
+            ("00:30".to_string(), "weather".to_string()),
+        ]
+    );
+    assert!(next_programs(&list, 5, 3).is_empty());
+    assert_eq!(
+        card_text(&programs),
+        "Coming up\n\n20:15  Evening News\n00:30  weather"
+    );
+
+    let cmd = render_cmd(
+        &config,
+        Path::new("/media/card.png"),
+        Path::new("/tmp/it's.txt"),
+        Path::new("/tmp/card.png"),
+    );
+    assert!(cmd[cmd.len() - 4].contains("drawtext=textfile='/tmp/it'\\''s.txt'"));
+    assert_eq!(cmd.last().unwrap(), "/tmp/card.png");
+}
@@ -7,7 +7,10 @@ use chrono::NaiveTime;
 use sqlx::sqlite::SqlitePoolOptions;
 
 use ffplayout::db::handles;
-use ffplayout::player::{controller::ChannelManager, utils::*};
+use ffplayout::player::{
+    controller::ChannelManager,
+    utils::{folder::seeded_rng, *},
+};
 use ffplayout::utils::config::ProcessMode::Playlist;
 use ffplayout::utils::playlist::generate_playlist;
 use ffplayout::utils::{
@@ -49,13 +52,39 @@ async fn test_random_list() {
         Media::new(0, "./assets/media_mix/ad.mp4", true).await,         // 25 seconds
     ];
 
-    let r_list = random_list(clip_list.clone(), 200.0);
+    let r_list = random_list(clip_list.clone(), 200.0, &mut seeded_rng(None));
     let r_duration = sum_durations(&r_list);
 
     assert!(200.0 >= r_duration, "duration is {r_duration}");
     assert!(r_duration >= 170.0);
 }
 
+#[tokio::test]
+async fn test_seeded_random_list() {
+    let clip_list = vec![
+        Media::new(0, "./assets/media_mix/with_audio.mp4", true).await,
+        Media::new(0, "./assets/media_mix/dual_audio.mp4", true).await,
+        Media::new(0, "./assets/media_mix/av_sync.mp4", true).await,
+        Media::new(0, "./assets/media_mix/ad.mp4", true).await,
+    ];
+
+    let list_a = random_list(clip_list.clone(), 200.0, &mut seeded_rng(Some(42)));
+    let list_b = random_list(clip_list, 200.0, &mut seeded_rng(Some(42)));
+
+    assert_eq!(list_a, list_b);
+}
+
+#[test]
+fn test_quota_left() {
+    let quotas = vec![("news".to_string(), 25.0)];
+
+    assert_eq!(quota_left(&quotas, "news", 86400.0, 0.0), Some(21600.0));
+    assert_eq!(quota_left(&quotas, "news", 86400.0, 20000.0), Some(1600.0));
+    assert_eq!(quota_left(&quotas, "news", 86400.0, 30000.0), Some(0.0));
+    assert_eq!(quota_left(&quotas, "movies", 86400.0, 0.0), None);
+    assert_eq!(quota_left(&quotas, "", 86400.0, 0.0), None);
+}
+
 #[tokio::test]
 async fn test_ordered_list() {
     let clip_list = vec![
@@ -132,12 +161,14 @@ async fn test_generate_playlist_from_template() {
                 duration: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
                 shuffle: false,
                 paths: vec![PathBuf::from("assets/")],
+                ..Default::default()
             },
             Source {
                 start: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
                 duration: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
                 shuffle: true,
                 paths: vec![PathBuf::from("assets/")],
+                ..Default::default()
             },
         ],
     });
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use ffplayout::player::{
+    input::{
+        emergency::emergency_cmd,
+        program::{crossfade_filter, crossfade_node},
+        srt::{handshake, listener_url, socket_addr, stream_id_matches, Handshake},
+    },
+    utils::{
+        concat::{chainable, list_content},
+        image_audio,
+        preload::preload_cmd,
+        seek_and_length,
+        slideshow::slideshow_list,
+        stream_options, Media,
+    },
+};
+use ffplayout::utils::config::{Ingest, PlayoutConfig, SeekMode};
+
+#[test]
+fn concat_list_entry() {
+    let config = PlayoutConfig::default();
+    let media = Media {
+        source: "/media/it's.mp4".to_string(),
+        duration: 60.0,
+        out: 30.0,
+        ..Media::default()
+    };
+
+    assert_eq!(
+        list_content(&media, Path::new("/tmp/ffplayout_concat_1/2.link")),
+        "ffconcat version 1.0\nfile '/media/it'\\''s.mp4'\noutpoint 30\nduration 30\nfile '/tmp/ffplayout_concat_1/2.link'\noption safe 0\n"
+    );
+
+    // clips without probe, or which are not on disk, need their own decoder
+    assert!(!chainable(&media, &config));
+}
+
+#[tokio::test]
+async fn emergency_slate_command() {
+    let mut node = Media::new(0, "/tmp/slate.png", false).await;
+    node.out = 60.0;
+
+    let cmd = emergency_cmd(&node).join(" ");
+    assert_eq!(cmd, "-loop 1 -i /tmp/slate.png -t 60");
+
+    node.source = "/tmp/slate.mp4".to_string();
+    node.audio = "/tmp/loop.mp3".to_string();
+
+    let cmd = emergency_cmd(&node).join(" ");
+    assert_eq!(
+        cmd,
+        "-stream_loop -1 -i /tmp/slate.mp4 -stream_loop -1 -i /tmp/loop.mp3 -t 60"
+    );
+}
+
+#[test]
+fn preload_next_clip_command() {
+    let mut node = Media {
+        source: "/mnt/nfs/clip.mp4".to_string(),
+        out: 60.0,
+        duration: 60.0,
+        ..Media::default()
+    };
+
+    assert_eq!(
+        preload_cmd(&node).join(" "),
+        "-hide_banner -nostats -v error -i /mnt/nfs/clip.mp4 -t 3 -f null -"
+    );
+
+    node.seek = 12.5;
+
+    assert!(preload_cmd(&node)
+        .join(" ")
+        .contains("-ss 12.500 -i /mnt/nfs/clip.mp4 -t 3"));
+}
+
+#[test]
+fn slideshow_and_image_audio() {
+    let images = vec![
+        PathBuf::from("/slides/a.jpg"),
+        PathBuf::from("/slides/b.png"),
+    ];
+
+    assert_eq!(
+        slideshow_list(&images, 10.0),
+        "ffconcat version 1.0\nfile '/slides/a.jpg'\nduration 10\nfile '/slides/b.png'\nduration 10\nfile '/slides/a.jpg'\n"
+    );
+
+    let node = Media {
+        audio: "https://radio.example.org/live.mp3".to_string(),
+        out: 3600.0,
+        ..Media::default()
+    };
+
+    assert_eq!(
+        image_audio(&node),
+        vec!["-i", "https://radio.example.org/live.mp3"]
+    );
+    assert!(image_audio(&Media::default()).is_empty());
+}
+
+#[test]
+fn stream_reconnect_options() {
+    let mut config = PlayoutConfig::default();
+
+    assert!(stream_options(&config, "https://example.org/live.m3u8").is_empty());
+
+    config.playlist.stream_reconnect = 5;
+    config.playlist.stream_retries = 3;
+    config.playlist.stream_fallback = 10.0;
+
+    assert_eq!(
+        stream_options(&config, "https://example.org/live.m3u8").join(" "),
+        "-reconnect 1 -reconnect_streamed 1 -reconnect_on_network_error 1 -reconnect_delay_max 5 -reconnect_max_retries 3 -rw_timeout 10000000"
+    );
+    assert_eq!(
+        stream_options(&config, "srt://example.org:9000"),
+        vec!["-rw_timeout", "10000000"]
+    );
+    assert!(stream_options(&config, "/tv-media/clip.mp4").is_empty());
+}
+
+#[test]
+fn seek_modes() {
+    let config = PlayoutConfig::default();
+    let mut node = Media {
+        source: "/tv-media/promo.mp4".to_string(),
+        seek: 10.0,
+        out: 50.0,
+        duration: 100.0,
+        ..Media::default()
+    };
+
+    assert_eq!(
+        seek_and_length(&config, &mut node),
+        vec!["-ss", "10", "-i", "/tv-media/promo.mp4", "-t", "40"]
+    );
+
+    node.seek_mode = Some(SeekMode::Accurate);
+
+    assert_eq!(
+        seek_and_length(&config, &mut node),
+        vec!["-i", "/tv-media/promo.mp4", "-t", "40", "-ss", "10"]
+    );
+}
+
+#[tokio::test]
+async fn program_crossfade() {
+    let mut config = PlayoutConfig::default();
+    config.processing.width = 1280;
+    config.processing.height = 720;
+    config.processing.fps = 25.0;
+    config.processing.audio_tracks = 1;
+
+    let cmd = crossfade_filter(&config, 1.5).cmd().join(" ");
+
+    assert!(cmd.starts_with("-filter_complex [0:v]scale=1280:720:"));
+    assert!(cmd.contains("[fade_a][fade_b]xfade=transition=fade:duration=1.5:offset=0[vout0]"));
+    assert!(cmd.contains("[afade_a][afade_b]acrossfade=d=1.5[aout0]"));
+
+    let from = Media::new(0, "./assets/media_mix/with_audio.mp4", true).await;
+    let to = Media::new(1, "./assets/media_mix/av_sync.mp4", true).await;
+    let node = crossfade_node(&config, &from, 12.0, &to, 0.0, 1.5).unwrap();
+    let cmd = node.cmd.unwrap().join(" ");
+
+    assert_eq!(node.category, "transition");
+    assert_eq!(node.out, 1.5);
+    assert_eq!(
+        cmd,
+        "-ss 12.000 -t 1.5 -i ./assets/media_mix/with_audio.mp4 -t 1.5 -i ./assets/media_mix/av_sync.mp4"
+    );
+
+    // without probe, or without decoding, it is a cut
+    let unknown = Media::new(2, "./assets/media_mix/with_audio.mp4", false).await;
+    assert!(crossfade_node(&config, &unknown, 0.0, &to, 0.0, 1.5).is_none());
+
+    config.processing.copy_video = true;
+    assert!(crossfade_node(&config, &from, 0.0, &to, 0.0, 1.5).is_none());
+}
+
+#[test]
+fn srt_listener() {
+    let ingest = Ingest {
+        srt_passphrase: "secret pass&1".to_string(),
+        srt_latency: 200,
+        srt_streamid: "studio1, remote/*".to_string(),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        listener_url("srt://0.0.0.0:40077?mode=caller&latency=50&pkt_size=1316", &ingest, None),
+        "srt://0.0.0.0:40077?pkt_size=1316&mode=listener&latency=200000&passphrase=secret%20pass%261"
+    );
+    assert_eq!(
+        listener_url("srt://0.0.0.0:40077", &ingest, Some(5000)),
+        "srt://127.0.0.1:5000?mode=listener&latency=200000&passphrase=secret%20pass%261"
+    );
+    assert_eq!(
+        listener_url("srt://0.0.0.0:40077?latency=50", &Ingest::default(), None),
+        "srt://0.0.0.0:40077?latency=50&mode=listener"
+    );
+    assert_eq!(
+        socket_addr("srt://0.0.0.0:40077?mode=listener").as_deref(),
+        Some("0.0.0.0:40077")
+    );
+
+    assert!(stream_id_matches("", "anything"));
+    assert!(stream_id_matches(&ingest.srt_streamid, "studio1"));
+    assert!(stream_id_matches(&ingest.srt_streamid, "remote/berlin"));
+    assert!(!stream_id_matches(&ingest.srt_streamid, "studio2"));
+
+    // handshake header with a conclusion and the stream id extension
+    let mut packet = vec![0; 64];
+    packet[0] = 0x80;
+    packet[36..40].copy_from_slice(&0xFFFF_FFFFu32.to_be_bytes());
+    packet.extend_from_slice(&[0, 5, 0, 2]);
+    packet.extend_from_slice(b"duts\01oi");
+    assert_eq!(
+        handshake(&packet),
+        Some(Handshake::Conclusion(Some("studio1".to_string())))
+    );
+
+    packet[36..40].copy_from_slice(&1u32.to_be_bytes());
+    assert_eq!(handshake(&packet), Some(Handshake::Setup));
+    assert_eq!(handshake(&packet[..40]), None);
+
+    packet[0] = 0;
+    assert_eq!(handshake(&packet), None);
+}
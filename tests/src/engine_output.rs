@@ -0,0 +1,230 @@
+use std::path::PathBuf;
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use chrono::{Local, TimeDelta};
+use serial_test::serial;
+
+use ffplayout::db::handles;
+use ffplayout::player::{
+    controller::ChannelManager,
+    output::{
+        hls_continuity, rendition_cmd, replay_cmd, replay_window, OutputStatus, OutputSwitch,
+    },
+    utils::{
+        health::OutputHealth,
+        progress::{is_progress, EncoderStats},
+    },
+};
+use ffplayout::utils::config::{PlayoutConfig, Rendition};
+use ffplayout::vec_strings;
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[test]
+fn output_availability() {
+    let start = Local::now() - TimeDelta::hours(2);
+    let mut health = OutputHealth::default();
+
+    health.connected(start);
+    assert!(health.disconnected(start + TimeDelta::minutes(30), "Broken pipe"));
+    assert!(!health.disconnected(start + TimeDelta::minutes(31), "Broken pipe"));
+    assert_eq!(
+        health.connected(start + TimeDelta::minutes(36)),
+        Some(360.0)
+    );
+
+    let now = start + TimeDelta::hours(2);
+
+    assert!((health.availability(now) - 95.0).abs() < 0.001);
+    assert_eq!(health.disconnects(now, TimeDelta::hours(3)), 1);
+}
+
+#[test]
+fn parse_encoder_progress() {
+    let block = "frame=250\nfps=25.00\nstream_0_0_q=28.0\nbitrate=4512.3kbits/s\ntotal_size=5640192\nout_time_us=10000000\nout_time=00:00:10.000000\ndup_frames=1\ndrop_frames=3\nspeed=1.01x\nprogress=continue";
+    let mut stats = EncoderStats::default();
+
+    for line in block.lines() {
+        assert!(is_progress(line));
+        stats.update(line);
+    }
+
+    assert_eq!(stats.frame, 250);
+    assert_eq!(stats.bitrate, 4512.3);
+    assert_eq!(stats.out_time, 10.0);
+    assert_eq!(stats.speed, 1.01);
+    assert_eq!(stats.drop_frames, 3);
+    assert!(stats.updated.is_some());
+    assert!(!is_progress(
+        "[info] Output #0, flv, to 'rtmp://127.0.0.1/live':"
+    ));
+    assert!(!is_progress("[h264 @ 0x55d5] [error] mb_type=12 invalid"));
+}
+
+#[test]
+fn rendition_command_and_secrets() {
+    let mut config = PlayoutConfig::default();
+    config.output.renditions = vec![Rendition {
+        name: "low".to_string(),
+        output_param: "-c:v libx264 -b:v 800k -f flv rtmp://127.0.0.1/live/low-key".to_string(),
+    }];
+
+    let cmd = rendition_cmd(&config, &config.output.renditions[0], "level+error").unwrap();
+    let input = cmd.iter().position(|c| c == "-i").unwrap();
+
+    assert_eq!(cmd[input + 1], "pipe:0");
+    assert!(!cmd.contains(&"-re".to_string()));
+    assert_eq!(cmd.last().unwrap(), "rtmp://127.0.0.1/live/low-key");
+
+    let stored = config.clone();
+    config.redact();
+
+    assert!(!config.output.renditions[0].output_param.contains("low-key"));
+
+    config.restore_secrets(&stored);
+
+    assert_eq!(
+        config.output.renditions[0].output_param,
+        stored.output.renditions[0].output_param
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn output_delay_config() {
+    let (mut config, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+
+    assert_eq!(config.output.delay, 0.0);
+    assert!(manager.delay.lock().await.is_none());
+
+    config.output.delay = 12.5;
+    config.output.delay_path = "/var/cache/ffplayout".to_string();
+
+    handles::update_configuration(&pool, 1, config)
+        .await
+        .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+
+    assert_eq!(config.output.delay, 12.5);
+    assert_eq!(config.output.delay_path, "/var/cache/ffplayout");
+}
+
+#[test]
+fn replay_window_and_command() {
+    // segments started 6 to 1 seconds ago
+    let ages = [6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
+
+    assert_eq!(replay_window(&ages, 4.5, 2.0), Some(1..=3));
+    assert_eq!(replay_window(&ages, 3.0, 3.0), Some(3..=5));
+    assert_eq!(replay_window(&ages, 7.0, 2.0), None);
+
+    let mut config = PlayoutConfig::default();
+    config.processing.fps = 25.0;
+    config.processing.cmd = Some(vec![
+        "-f".to_string(),
+        "mpegts".to_string(),
+        "-".to_string(),
+    ]);
+
+    let inputs = [PathBuf::from("/tmp/1.ts"), PathBuf::from("/tmp/2.ts")];
+    let cmd = replay_cmd(&config, &inputs, 10.0, 0.5, "level+error").join(" ");
+
+    assert!(cmd.contains("-readrate 0.5 -i concat:/tmp/1.ts|/tmp/2.ts"));
+    assert!(cmd.contains("-vf setpts=N/(25*0.5)/TB"));
+    assert!(cmd.contains("-af asetpts=N/SR/TB,volume=0,apad"));
+    assert!(cmd.ends_with("-t 20.000 -f mpegts -"));
+}
+
+#[test]
+fn hls_continuity_flags() {
+    let mut cmd = vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "hls",
+        "-hls_time",
+        6,
+        "/live/stream.m3u8"
+    ];
+    hls_continuity(&mut cmd, None);
+
+    assert_eq!(
+        cmd.join(" "),
+        "-c:v libx264 -f hls -hls_time 6 -hls_flags append_list+omit_endlist /live/stream.m3u8"
+    );
+
+    let mut cmd = vec_strings![
+        "-f",
+        "hls",
+        "-hls_flags",
+        "delete_segments+append_list",
+        "/live/stream.m3u8"
+    ];
+    hls_continuity(&mut cmd, Some(120));
+
+    assert_eq!(
+        cmd.join(" "),
+        "-f hls -hls_flags delete_segments+append_list+omit_endlist+discont_start -start_number 120 /live/stream.m3u8"
+    );
+}
+
+#[tokio::test]
+async fn output_switch() {
+    let mut config = PlayoutConfig::default();
+    config.output.renditions = ["public", "internal"]
+        .iter()
+        .map(|name| Rendition {
+            name: name.to_string(),
+            output_param: "-c copy -f null -".to_string(),
+        })
+        .collect();
+
+    let switch = OutputSwitch::default();
+
+    assert!(switch.set("public", false));
+    assert!(!switch.set("public", false));
+    assert!(switch.is_stopped("public"));
+    assert!(!switch.is_stopped("internal"));
+
+    let status = switch.status(&config).await;
+
+    assert_eq!(
+        status[0],
+        OutputStatus {
+            name: "public".to_string(),
+            enabled: false,
+            running: false,
+        }
+    );
+    assert!(status[1].enabled);
+
+    // clones share the state, like the one from the channel manager
+    assert!(switch.clone().set("public", true));
+    assert!(!switch.is_stopped("public"));
+}
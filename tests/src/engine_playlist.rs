@@ -1,11 +1,22 @@
 use serial_test::serial;
 use sqlx::sqlite::SqlitePoolOptions;
 
-use ffplayout::db::handles;
+use ffplayout::db::{
+    handles,
+    models::{HistoryFilter, PlayHistory},
+};
 use ffplayout::player::output::player;
-use ffplayout::player::{controller::ChannelManager, input::playlist::gen_source, utils::Media};
+use ffplayout::player::{
+    controller::ChannelManager,
+    input::playlist::gen_source,
+    utils::{
+        playlist_store::{DbStore, FileStore, PlaylistStore},
+        JsonPlaylist, Media,
+    },
+};
 use ffplayout::utils::config::OutputMode::Null;
 use ffplayout::utils::config::{PlayoutConfig, ProcessMode::Playlist};
+use ffplayout::utils::errors::ServiceError;
 use ffplayout::utils::time_machine::set_mock_time;
 use ffplayout::vec_strings;
 
@@ -339,3 +350,191 @@ async fn playlist_change_at_six() {
 
     assert_eq!(playlist_date, "2023-02-09");
 }
+
+#[tokio::test]
+#[serial]
+async fn playlist_database_store() {
+    let (_, manager) = prepare_config().await;
+    let store = DbStore::new(manager.db_pool.clone().unwrap(), 1);
+    let mut playlist = JsonPlaylist::new("2024-02-09".to_string(), 0.0);
+
+    assert!(store.read("2024-02-09").await.unwrap().is_none());
+    assert!(!store.write(&playlist).await.unwrap());
+
+    let stored = store.read("2024-02-09").await.unwrap().unwrap();
+    assert_eq!(stored.version, Some(1));
+    assert_eq!(stored, playlist);
+
+    // save from the current version, the old one goes to the history
+    playlist.version = stored.version;
+    playlist.program.push(Media::default());
+    assert!(store.write(&playlist).await.unwrap());
+
+    // an other save based on version 1 is outdated now
+    assert!(store.write(&playlist).await.is_err());
+
+    let history = store.history("2024-02-09").await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].version, Some(1));
+
+    // the history stays, also after the playlist is deleted and saved new
+    assert!(store.delete("2024-02-09").await.unwrap());
+    assert_eq!(store.history("2024-02-09").await.unwrap().len(), 1);
+
+    playlist.version = None;
+    assert!(!store.write(&playlist).await.unwrap());
+
+    // an existing playlist is only replaced with its version
+    assert!(matches!(
+        store.write(&playlist).await,
+        Err(ServiceError::Conflict(_))
+    ));
+
+    playlist.version = store.read("2024-02-09").await.unwrap().unwrap().version;
+    assert!(store.write(&playlist).await.unwrap());
+
+    let history = store.history("2024-02-09").await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].version, Some(1));
+    assert_eq!(history[0].program.len(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn playlist_store_dates() {
+    let (_, manager) = prepare_config().await;
+    let files = FileStore::new("assets/playlists".into());
+
+    assert_eq!(
+        files.dates("2024-01-31").await.unwrap(),
+        vec!["2024-01-31", "2024-02-01", "2024-02-02", "2024-03-19"]
+    );
+    assert!(files.dates("2025-01-01").await.unwrap().is_empty());
+
+    let store = DbStore::new(manager.db_pool.clone().unwrap(), 1);
+
+    for date in ["2024-02-10", "2024-02-08", "2024-02-09"] {
+        store
+            .write(&JsonPlaylist::new(date.to_string(), 0.0))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        store.dates("2024-02-09").await.unwrap(),
+        vec!["2024-02-09", "2024-02-10"]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn play_history_filter() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+
+    for (i, (started, ended)) in [
+        ("2024-02-09 14:20:00.000", "2024-02-09 14:30:00.000"),
+        ("2024-02-09 14:30:00.000", "2024-02-09 14:40:00.000"),
+        ("2024-02-10 06:00:00.000", "2024-02-10 06:10:00.000"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let item = PlayHistory {
+            channel_id: 1,
+            started: started.to_string(),
+            ended: ended.to_string(),
+            source: format!("clip_{i}.mp4"),
+            out: 600.0,
+            duration: 600.0,
+            ..Default::default()
+        };
+
+        handles::insert_play_history(&pool, &item).await.unwrap();
+    }
+
+    let filter = HistoryFilter {
+        at: Some("2024-02-09 14:32:00.000".to_string()),
+        ..Default::default()
+    };
+    let items = handles::select_play_history(&pool, 1, &filter, 50, 0)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].source, "clip_1.mp4");
+
+    let filter = HistoryFilter {
+        search: Some("clip_2".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        handles::count_play_history(&pool, 1, &filter)
+            .await
+            .unwrap(),
+        1
+    );
+
+    // newest first, second page
+    let items = handles::select_play_history(&pool, 1, &HistoryFilter::default(), 2, 2)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].source, "clip_0.mp4");
+
+    handles::delete_play_history(&pool, "2024-02-10 00:00:00.000")
+        .await
+        .unwrap();
+    assert_eq!(
+        handles::count_play_history(&pool, 1, &HistoryFilter::default())
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn media_tags_filter() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+
+    handles::update_media_tags(&pool, 1, "a.mp4", &vec_strings!["news", "sports"])
+        .await
+        .unwrap();
+    handles::update_media_tags(&pool, 1, "b.mp4", &vec_strings!["news"])
+        .await
+        .unwrap();
+
+    let any = handles::select_tagged_media(&pool, 1, &vec_strings!["news", "sports"], false)
+        .await
+        .unwrap();
+    assert_eq!(any.len(), 2);
+
+    let all = handles::select_tagged_media(&pool, 1, &vec_strings!["news", "sports"], true)
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].path, "a.mp4");
+    assert_eq!(all[0].tags.len(), 2);
+
+    // deleting a tag removes it from the media files
+    let tags = handles::select_tags(&pool, 1).await.unwrap();
+    let sports = tags.iter().find(|t| t.name == "sports").unwrap();
+    assert_eq!(sports.media, 1);
+
+    handles::delete_tag(&pool, 1, sports.id).await.unwrap();
+    let media = handles::select_media(&pool, 1, "a.mp4")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(media.tags, vec_strings!["news"]);
+
+    // without tags the media entry is removed
+    handles::update_media_tags(&pool, 1, "b.mp4", &[])
+        .await
+        .unwrap();
+    assert!(handles::select_media(&pool, 1, "b.mp4")
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::Ordering;
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use ffplayout::db::handles;
+use ffplayout::player::controller::{ChannelManager, ConfigApply, Reload};
+use ffplayout::utils::config::PlayoutConfig;
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[tokio::test]
+async fn reload_hot_changes() {
+    let (mut config, _) = prepare_config().await;
+    config.text.zmq_stream_socket = Some("127.0.0.1:5555".to_string());
+
+    let mut new = config.clone();
+    new.text.font = "new_font.ttf".to_string();
+    let reload = Reload::new(&config, &new);
+
+    assert!(reload.text);
+    assert!(!reload.full);
+    assert!(!reload.decoder);
+
+    let mut new = config.clone();
+    new.processing.logo = "new_logo.png".to_string();
+    let reload = Reload::new(&config, &new);
+
+    assert!(reload.decoder);
+    assert!(!reload.full);
+
+    let mut new = config.clone();
+    new.storage.filler = "new_filler.mp4".to_string();
+    let reload = Reload::new(&config, &new);
+
+    assert!(reload.filler);
+    assert!(reload.decoder);
+    assert!(!reload.full);
+
+    let mut new = config.clone();
+    new.ingest.input_param = "-f live_flv -listen 1 -i rtmp://127.0.0.1:1937/live".to_string();
+    let reload = Reload::new(&config, &new);
+
+    assert!(reload.ingest);
+    assert!(!reload.full);
+    assert!(!reload.decoder);
+}
+
+#[tokio::test]
+async fn reload_restart_changes() {
+    let (config, _) = prepare_config().await;
+
+    let mut new = config.clone();
+    new.processing.width += 2;
+
+    assert!(Reload::new(&config, &new).full);
+
+    let mut new = config.clone();
+    new.processing.fps += 1.0;
+
+    assert!(Reload::new(&config, &new).full);
+
+    let mut new = config.clone();
+    new.playlist.day_start = "06:00:00".to_string();
+
+    assert!(Reload::new(&config, &new).full);
+
+    let mut new = config.clone();
+    new.ingest.enable = !config.ingest.enable;
+
+    assert!(Reload::new(&config, &new).full);
+
+    assert!(!Reload::new(&config, &config.clone()).full);
+}
+
+#[tokio::test]
+async fn update_config_stopped() {
+    let (config, manager) = prepare_config().await;
+
+    let mut new = config.clone();
+    new.processing.width += 2;
+
+    assert_eq!(manager.update_config(new).await, ConfigApply::Saved);
+    assert_eq!(
+        manager.config.lock().await.processing.width,
+        config.processing.width + 2
+    );
+}
+
+#[tokio::test]
+async fn update_config_running() {
+    let (config, manager) = prepare_config().await;
+    manager.is_alive.store(true, Ordering::SeqCst);
+    manager.list_init.store(false, Ordering::SeqCst);
+
+    let mut new = config.clone();
+    new.processing.logo = "new_logo.png".to_string();
+
+    assert_eq!(manager.update_config(new).await, ConfigApply::Reload);
+    assert!(manager.list_init.load(Ordering::SeqCst));
+    assert!(manager.is_alive.load(Ordering::SeqCst));
+    assert_eq!(manager.config.lock().await.processing.logo, "new_logo.png");
+
+    let mut new = manager.config.lock().await.clone();
+    new.processing.width += 2;
+
+    assert_eq!(manager.update_config(new).await, ConfigApply::Restart);
+    assert!(!manager.is_alive.load(Ordering::SeqCst));
+    assert_eq!(
+        manager.config.lock().await.processing.width,
+        config.processing.width + 2
+    );
+}
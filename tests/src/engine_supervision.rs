@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use chrono::{Local, TimeDelta, TimeZone};
+use serial_test::serial;
+
+use ffplayout::db::handles;
+use ffplayout::player::{
+    controller::ChannelManager,
+    utils::{
+        catch_up::catch_up,
+        channel_state::{ChannelAction, ChannelState, StateTracker},
+        drift::update_drift,
+        freeze::{frame_position, grab_cmd},
+        media_audit::check_clip,
+        restart::{RestartAction, RestartState},
+        resume::ResumePoint,
+        supervisor::{is_fatal, ProcessState, Respawn},
+        JsonPlaylist, Media,
+    },
+};
+use ffplayout::utils::{
+    advanced_config::SupervisionConfig,
+    config::{CatchUp, PlayoutConfig},
+    schedule_check::check_playlist,
+};
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[actix_web::test]
+#[serial]
+async fn schedule_drift_alarm() {
+    let (config, manager) = prepare_config().await;
+
+    assert_eq!(config.general.drift_alarm, 3.0);
+
+    update_drift(&manager, &config, -1.5).await;
+    assert_eq!(manager.drift.lock().await.seconds, 1.5);
+    assert!(!manager.drift.lock().await.alarm);
+
+    update_drift(&manager, &config, -4.0).await;
+    assert!(manager.drift.lock().await.alarm);
+
+    update_drift(&manager, &config, 0.2).await;
+    assert_eq!(manager.drift.lock().await.seconds, -0.2);
+    assert!(!manager.drift.lock().await.alarm);
+}
+
+#[actix_web::test]
+#[serial]
+async fn restart_policy() {
+    let (mut config, _) = prepare_config().await;
+    let mut restart = RestartState::default();
+    let secs = Duration::from_secs;
+
+    assert_eq!(config.general.restart_slate, 3);
+    config.general.restart_limit = 5;
+    config.general.restart_max_delay = 4;
+
+    assert_eq!(
+        restart.failed(&config.general, 0, 2.0),
+        RestartAction::Retry(secs(1))
+    );
+    assert_eq!(
+        restart.failed(&config.general, 10, 2.0),
+        RestartAction::Retry(secs(2))
+    );
+    assert_eq!(
+        restart.failed(&config.general, 20, 2.0),
+        RestartAction::Slate(secs(4))
+    );
+    assert_eq!(
+        restart.failed(&config.general, 30, 2.0),
+        RestartAction::Slate(secs(4))
+    );
+
+    // a stable run resets the failures in a row, but not the restarts in the last hour
+    assert_eq!(
+        restart.failed(&config.general, 200, 120.0),
+        RestartAction::Retry(secs(1))
+    );
+    assert_eq!(
+        restart.failed(&config.general, 210, 2.0),
+        RestartAction::Stop
+    );
+
+    let mut restart = RestartState::default();
+    assert_eq!(
+        restart.failed(&config.general, 4000, 2.0),
+        RestartAction::Retry(secs(1))
+    );
+    assert_eq!(restart.restarts(), 1);
+}
+
+#[actix_web::test]
+#[serial]
+async fn schedule_check_playlist() {
+    let (config, _) = prepare_config().await;
+    let mut playlist = JsonPlaylist::new("2024-10-16".to_string(), 0.0);
+
+    assert_eq!(
+        check_playlist(None, &config),
+        Some("Playlist is missing".to_string())
+    );
+    assert!(check_playlist(Some(&playlist), &config)
+        .unwrap()
+        .contains("too short"));
+
+    playlist.program[0].out = 86400.0;
+    assert_eq!(check_playlist(Some(&playlist), &config), None);
+
+    playlist.program.clear();
+    assert_eq!(
+        check_playlist(Some(&playlist), &config),
+        Some("Playlist has no clips".to_string())
+    );
+}
+
+#[actix_web::test]
+#[serial]
+async fn media_audit_clip_problems() {
+    let (config, _) = prepare_config().await;
+    let mut media = Media {
+        source: "/not/existing/clip.mp4".to_string(),
+        duration: 30.0,
+        out: 30.0,
+        ..Media::default()
+    };
+
+    let problems = check_clip(&media, &Err("File not found".to_string()), &config);
+    assert_eq!(problems, vec!["File not found".to_string()]);
+
+    media.out = 0.0;
+    let problems = check_clip(&media, &Err("File not found".to_string()), &config);
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0], "Clip has no playtime");
+}
+
+#[tokio::test]
+async fn freeze_frame_position() {
+    let mut node = Media::new(0, "assets/media_mix/with_audio.mp4", false).await;
+    node.seek = 10.0;
+    node.out = 60.0;
+    node.begin = Some(3600.0);
+
+    assert_eq!(frame_position(&node, 3620.0, 0.0), 30.0);
+    // time shift moves the begin
+    assert_eq!(frame_position(&node, 3620.0, 5.0), 35.0);
+    assert_eq!(frame_position(&node, 3700.0, 0.0), 59.9);
+
+    // clip started before midnight
+    node.begin = Some(86390.0);
+    assert_eq!(frame_position(&node, 5.0, 0.0), 25.0);
+    // begin a moment ahead is no clip from yesterday
+    node.begin = Some(3620.5);
+    assert_eq!(frame_position(&node, 3620.0, 0.0), 10.0);
+
+    let cmd = grab_cmd("/tmp/clip.mp4", 30.0, "/tmp/frame.png").join(" ");
+    assert!(cmd.contains("-ss 30.000 -i /tmp/clip.mp4 -map 0:v:0 -frames:v 1"));
+
+    let cmd = grab_cmd("/tmp/1.ts", -0.1, "/tmp/frame.png").join(" ");
+    assert!(cmd.contains("-sseof -0.100 -i /tmp/1.ts"));
+    assert!(cmd.ends_with("-y /tmp/frame.png"));
+}
+
+#[tokio::test]
+async fn resume_point_seek() {
+    let mut node = Media::new(3, "assets/media_mix/with_audio.mp4", false).await;
+    node.seek = 0.0;
+    node.out = 120.0;
+    node.begin = Some(3600.0);
+
+    let point = ResumePoint {
+        date: "2024-05-01".to_string(),
+        index: 3,
+        source: node.source.clone(),
+        seek: 0.0,
+        started: 1000.0,
+    };
+
+    // 30 seconds since the clip started, playout is 2 seconds behind the schedule
+    assert_eq!(point.position(1030.0), 30.0);
+    assert_eq!(
+        point.seek_for(&node, "2024-05-01", 1030.0, 3632.0, 10.0),
+        Some(30.0)
+    );
+    assert_eq!(
+        point.seek_for(&node, "2024-05-01", 1030.0, 3632.0, 0.0),
+        Some(30.0)
+    );
+
+    // too far away from the schedule, other day or other clip
+    assert_eq!(
+        point.seek_for(&node, "2024-05-01", 1030.0, 3650.0, 10.0),
+        None
+    );
+    assert_eq!(
+        point.seek_for(&node, "2024-05-02", 1030.0, 3632.0, 10.0),
+        None
+    );
+
+    // clip is over
+    assert_eq!(
+        point.seek_for(&node, "2024-05-01", 1200.0, 3800.0, 0.0),
+        None
+    );
+
+    node.source = "assets/media_mix/av_sync.mp4".to_string();
+    assert_eq!(
+        point.seek_for(&node, "2024-05-01", 1030.0, 3632.0, 10.0),
+        None
+    );
+}
+
+#[test]
+fn catch_up_strategies() {
+    let mut config = PlayoutConfig::default();
+    config.playlist.catch_up_rate = 5.0;
+
+    let clip = Media {
+        out: 100.0,
+        duration: 100.0,
+        ..Media::default()
+    };
+
+    // restart and skip don't change the clip
+    let mut node = clip.clone();
+    assert_eq!(catch_up(&mut node, &config, 10.0), 0.0);
+    assert_eq!(node.out, 100.0);
+
+    config.playlist.catch_up = CatchUp::Trim;
+    let mut node = clip.clone();
+    assert_eq!(catch_up(&mut node, &config, 10.0), 5.0);
+    assert_eq!(node.out, 95.0);
+
+    let mut node = clip.clone();
+    assert_eq!(catch_up(&mut node, &config, 2.0), 2.0);
+    assert_eq!(catch_up(&mut node, &config, 0.2), 0.0);
+
+    config.playlist.catch_up = CatchUp::Speed;
+    let mut node = clip.clone();
+    let gained = catch_up(&mut node, &config, 10.0);
+    assert_eq!(node.speed, Some(1.05));
+    assert!((gained - (100.0 - 100.0 / 1.05)).abs() < 0.001);
+    assert_eq!(node.out, 100.0);
+
+    // copied streams can only be trimmed
+    config.processing.copy_video = true;
+    let mut node = clip.clone();
+    assert_eq!(catch_up(&mut node, &config, 10.0), 5.0);
+    assert_eq!(node.speed, None);
+}
+
+#[test]
+fn process_supervision() {
+    let start = Local.with_ymd_and_hms(2024, 5, 20, 6, 0, 0).unwrap();
+    let policy = SupervisionConfig {
+        max_failures: Some(2),
+        backoff: Some(500),
+        max_backoff: Some(800),
+        ..SupervisionConfig::default()
+    };
+    let mut state = ProcessState::default();
+
+    state.started(start);
+    assert_eq!(
+        state.ended(&policy, start + TimeDelta::seconds(60)),
+        Respawn::Now
+    );
+
+    state.started(start);
+    assert_eq!(
+        state.ended(&policy, start + TimeDelta::milliseconds(100)),
+        Respawn::After(Duration::from_millis(500))
+    );
+
+    state.started(start);
+    assert_eq!(
+        state.ended(&policy, start),
+        Respawn::After(Duration::from_millis(800))
+    );
+
+    state.started(start);
+    assert_eq!(state.ended(&policy, start), Respawn::GiveUp);
+    assert_eq!(state.starts, 4);
+    assert_eq!(state.failures, 3);
+
+    assert!(is_fatal(&policy, "[error] Unrecognized option 'foo'."));
+
+    let policy = SupervisionConfig {
+        fatal_errors: Some("Out of memory; Device busy".to_string()),
+        ..SupervisionConfig::default()
+    };
+
+    assert!(is_fatal(&policy, "[fatal] Device busy"));
+    assert!(!is_fatal(&policy, "[error] Unrecognized option 'foo'."));
+}
+
+#[test]
+fn channel_state_transitions() {
+    let now = Local.with_ymd_and_hms(2024, 5, 20, 18, 0, 0).unwrap();
+    let mut tracker = StateTracker::default();
+
+    assert!(!tracker.set(ChannelState::OnAirPlaylist, now));
+    assert!(tracker.set(ChannelState::Starting, now));
+    assert!(tracker.set(ChannelState::OnAirPlaylist, now));
+    assert!(tracker.set(ChannelState::OnAirLive, now + TimeDelta::seconds(5)));
+    assert_eq!(tracker.previous, ChannelState::OnAirPlaylist);
+    assert_eq!(tracker.since, Some(now + TimeDelta::seconds(5)));
+
+    assert!(tracker.set(ChannelState::Stopping, now));
+    assert!(!tracker.set(ChannelState::Fallback, now));
+    assert_eq!(tracker.state, ChannelState::Stopping);
+    assert!(tracker.set(ChannelState::Stopped, now));
+    assert!(tracker.set(ChannelState::Failed, now));
+    assert!(tracker.set(ChannelState::Starting, now));
+
+    assert!(ChannelState::Stopped.check(ChannelAction::Start).is_ok());
+    assert!(ChannelState::Stopped.check(ChannelAction::Stop).is_err());
+    assert!(ChannelState::OnAirLive.check(ChannelAction::Start).is_err());
+    assert!(ChannelState::OnAirLive
+        .check(ChannelAction::Playout)
+        .is_err());
+    assert!(ChannelState::Fallback.check(ChannelAction::Playout).is_ok());
+    assert!(ChannelState::Stopping
+        .check(ChannelAction::Emergency)
+        .is_err());
+    assert!(ChannelState::Failed.check(ChannelAction::Restart).is_ok());
+}
@@ -0,0 +1,102 @@
+use std::{env, path::Path};
+
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+
+use ffplayout::db::handles;
+use ffplayout::utils::legacy_config::migrate_yaml;
+
+async fn prepare_pool() -> Pool<Sqlite> {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    pool
+}
+
+#[tokio::test]
+async fn legacy_yaml_mapping() {
+    let pool = prepare_pool().await;
+
+    migrate_yaml(&pool, 1, Path::new("assets/legacy/ffplayout.yml"))
+        .await
+        .unwrap();
+
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let global = handles::select_global(&pool).await.unwrap();
+    let config = handles::select_configuration(&pool, 1).await.unwrap();
+    let advanced = handles::select_advanced_configuration(&pool, 1)
+        .await
+        .unwrap();
+
+    assert_eq!(channel.playlists, "/var/lib/ffplayout/playlists");
+    assert_eq!(channel.storage, "/var/lib/ffplayout/tv-media");
+    assert_eq!(global.logs, "/var/log/ffplayout/");
+    assert_eq!(global.smtp_server, "mail.example.org");
+    assert_eq!(global.smtp_user, "ffplayout@example.org");
+    assert_eq!(global.smtp_password, "abc123");
+
+    assert_eq!(config.general_stop_threshold, 11.0);
+    assert_eq!(config.mail_recipient, "playout@example.org");
+    assert_eq!(config.processing_mode, "playlist");
+    assert_eq!(config.processing_width, 1024);
+    assert_eq!(config.processing_fps, 25.0);
+    // media paths are stored relative to the storage
+    assert_eq!(config.processing_logo, "logo/logo.png");
+    assert_eq!(config.storage_filler, "filler/filler.mp4");
+    assert_eq!(config.playlist_day_start, "05:59:25");
+    assert_eq!(config.output_mode, "hls");
+    assert!(config.output_param.starts_with("-c:v libx264 -crf 23"));
+    assert!(config.logging_ignore.contains("error while decoding MB"));
+
+    assert!(advanced
+        .decoder_output_param
+        .is_some_and(|p| p.starts_with("-c:v mpeg2video")));
+    assert_eq!(advanced.decoder_input_param, None);
+    assert_eq!(
+        advanced.filter_deinterlace,
+        Some("bwdif=mode=0".to_string())
+    );
+    assert_eq!(advanced.filter_scale, None);
+}
+
+#[tokio::test]
+async fn legacy_yaml_invalid_mode() {
+    let pool = prepare_pool().await;
+    let stored = handles::select_configuration(&pool, 1).await.unwrap();
+    let dir = env::temp_dir().join("ffplayout_legacy_invalid");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+
+    let yaml = tokio::fs::read_to_string("assets/legacy/ffplayout.yml")
+        .await
+        .unwrap()
+        .replace("    mode: hls", "    mode: rtmp");
+    tokio::fs::write(dir.join("ffplayout.yml"), yaml)
+        .await
+        .unwrap();
+
+    let result = migrate_yaml(&pool, 1, &dir.join("ffplayout.yml")).await;
+
+    assert!(result.is_err_and(|e| e.to_string().contains("rtmp")));
+
+    // nothing is written from a broken config
+    let config = handles::select_configuration(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+
+    assert_eq!(config.processing_width, stored.processing_width);
+    assert_eq!(channel.storage, "assets/storage");
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
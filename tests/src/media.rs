@@ -0,0 +1,202 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use serial_test::serial;
+
+use ffplayout::db::handles;
+use ffplayout::player::{
+    controller::ChannelManager,
+    utils::probe::{AudioStream, MediaFormat, MediaProbe, VideoStream},
+};
+use ffplayout::utils::{
+    config::{HouseFormat, PlayoutConfig},
+    house_format::{loudness_violation, parse_loudness, violations},
+    media_metadata::{find_file, parse_csv},
+    media_scan::{conformance, scan_channel},
+};
+use ffplayout::vec_strings;
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[actix_web::test]
+#[serial]
+async fn media_scan_report() {
+    let (mut config, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+    config.channel.storage = PathBuf::from("assets/media_mix");
+
+    let first = scan_channel(&pool, &config, "Channel 1".to_string())
+        .await
+        .unwrap();
+    assert!(first.files > 0);
+    assert_eq!(first.probed, first.files);
+    assert!(first.unreadable.is_empty());
+
+    // unchanged files come from the database
+    let second = scan_channel(&pool, &config, "Channel 1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(second.files, first.files);
+    assert_eq!(second.probed, 0);
+    assert_eq!(second.nonconforming, first.nonconforming);
+
+    let probe = MediaProbe {
+        format: MediaFormat {
+            duration: Some(10.0),
+            ..Default::default()
+        },
+        audio: vec![AudioStream::default()],
+        video: vec![],
+    };
+    assert_eq!(conformance(&probe, &config, false), vec!["no video stream"]);
+
+    config.processing.audio_only = true;
+    assert!(conformance(&probe, &config, false).is_empty());
+
+    let image = MediaProbe {
+        video: vec![VideoStream::default()],
+        ..Default::default()
+    };
+    config.processing.audio_only = false;
+    assert!(conformance(&image, &config, true).is_empty());
+    assert_eq!(conformance(&image, &config, false), vec!["no duration"]);
+}
+
+#[test]
+fn media_metadata_csv() {
+    let rows = parse_csv(
+        "\u{feff}house_id;filename;title\r\nAD1001;;\"Sale; \"\"Summer\"\"\"\r\n;b/clip.mp4;Clip\n",
+    );
+
+    assert_eq!(
+        rows,
+        vec![
+            vec_strings!["house_id", "filename", "title"],
+            vec_strings!["AD1001", "", "Sale; \"Summer\""],
+            vec_strings!["", "b/clip.mp4", "Clip"],
+        ]
+    );
+
+    let files = vec_strings!["a/AD1001.mp4", "b/clip.mp4", "c/clip.mp4", "c/AD1002.mov"];
+    let mut house_ids = HashMap::new();
+    house_ids.insert("AD1002".to_string(), "c/AD1002.mov".to_string());
+
+    assert_eq!(
+        find_file(&files, &house_ids, "b/clip.mp4", ""),
+        Ok("b/clip.mp4".to_string())
+    );
+    assert_eq!(
+        find_file(&files, &house_ids, "AD1001.mp4", ""),
+        Ok("a/AD1001.mp4".to_string())
+    );
+    assert_eq!(
+        find_file(&files, &house_ids, "", "AD1001"),
+        Ok("a/AD1001.mp4".to_string())
+    );
+    assert_eq!(
+        find_file(&files, &house_ids, "", "AD1002"),
+        Ok("c/AD1002.mov".to_string())
+    );
+    // the file name alone is not unique
+    assert!(find_file(&files, &house_ids, "clip.mp4", "").is_err());
+    assert!(find_file(&files, &house_ids, "", "AD1003").is_err());
+    assert!(find_file(&files, &house_ids, "", "").is_err());
+}
+
+#[test]
+fn house_format_rules() {
+    let rules = HouseFormat {
+        containers: vec_strings!["mp4"],
+        video_codecs: vec_strings!["h264"],
+        width: Some(1920),
+        height: Some(1080),
+        fps: Some(25.0),
+        loudness_min: Some(-24.0),
+        loudness_max: Some(-22.0),
+        min_duration: Some(5.0),
+        ..Default::default()
+    };
+    let mut probe = MediaProbe {
+        format: MediaFormat {
+            format_name: Some("mov,mp4,m4a,3gp,3g2,mj2".to_string()),
+            duration: Some(30.0),
+            ..Default::default()
+        },
+        audio: vec![AudioStream {
+            codec_name: Some("mp2".to_string()),
+            ..Default::default()
+        }],
+        video: vec![VideoStream {
+            codec_name: Some("h264".to_string()),
+            width: Some(1920),
+            height: Some(1080),
+            frame_rate: "25/1".to_string(),
+            ..Default::default()
+        }],
+    };
+
+    assert!(!rules.is_empty());
+    assert!(HouseFormat::default().is_empty());
+    assert!(violations(&rules, &probe).is_empty());
+
+    probe.format.format_name = Some("matroska,webm".to_string());
+    probe.format.duration = Some(3.0);
+    probe.video[0].codec_name = Some("hevc".to_string());
+    probe.video[0].width = Some(1280);
+    probe.video[0].frame_rate = "30000/1001".to_string();
+
+    assert_eq!(
+        violations(&rules, &probe),
+        vec![
+            "container matroska,webm is not allowed",
+            "video codec hevc is not allowed",
+            "resolution 1280x1080, needs 1920x1080",
+            "frame rate 29.970, needs 25",
+            "duration 00:00:03.000 is shorter than 00:00:05.000",
+        ]
+    );
+
+    probe.video.clear();
+    assert!(violations(&rules, &probe).contains(&"no video stream".to_string()));
+
+    let summary = "[Parsed_ebur128_0 @ 0x5580] Summary:
+
+  Integrated loudness:
+    I:         -23.4 LUFS
+    Threshold: -33.6 LUFS
+
+  Loudness range:
+    LRA:         5.2 LU";
+
+    assert_eq!(parse_loudness(summary), Some(-23.4));
+    assert_eq!(parse_loudness("no audio"), None);
+    assert_eq!(loudness_violation(&rules, -23.4), None);
+    assert_eq!(
+        loudness_violation(&rules, -18.04),
+        Some("loudness -18.0 LUFS, needs -24 to -22 LUFS".to_string())
+    );
+}
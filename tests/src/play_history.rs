@@ -0,0 +1,62 @@
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+
+use ffplayout::db::{handles, init_globales, models::HistoryFilter};
+use ffplayout::player::utils::Media;
+use ffplayout::utils::play_history::{finish, record, HISTORY_TIME_FORMAT};
+
+async fn prepare_pool() -> Pool<Sqlite> {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+    init_globales(&pool).await.unwrap();
+
+    pool
+}
+
+async fn clip(source: &str) -> Media {
+    let mut node = Media::new(0, source, false).await;
+    node.out = 600.0;
+    node.duration = 600.0;
+
+    node
+}
+
+#[tokio::test]
+async fn play_history_actual_end() {
+    let pool = prepare_pool().await;
+
+    record(pool.clone(), 1, None, clip("clip_0.mp4").await).await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    record(pool.clone(), 1, None, clip("clip_1.mp4").await).await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    finish(pool.clone(), 1, None).await;
+
+    // newest first
+    let items = handles::select_play_history(&pool, 1, &HistoryFilter::default(), 50, 0)
+        .await
+        .unwrap();
+
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1].source, "clip_0.mp4");
+
+    // the first clip ends, when the second starts, not after its planned 10 minutes
+    assert_eq!(items[1].ended, items[0].started);
+    assert!(items[0].ended > items[0].started);
+
+    // the second clip ends with the channel stop, long before its planned 10 minutes
+    let planned = chrono::NaiveDateTime::parse_from_str(&items[0].started, HISTORY_TIME_FORMAT)
+        .unwrap()
+        + chrono::TimeDelta::try_seconds(600).unwrap();
+    assert!(items[0].ended < planned.format(HISTORY_TIME_FORMAT).to_string());
+
+    // a stopped channel has no clip on air anymore
+    let ended = items[0].ended.clone();
+    finish(pool.clone(), 1, None).await;
+
+    let items = handles::select_play_history(&pool, 1, &HistoryFilter::default(), 50, 0)
+        .await
+        .unwrap();
+    assert_eq!(items[0].ended, ended);
+}
@@ -0,0 +1,40 @@
+use ffplayout::player::utils::Media;
+use ffplayout::utils::plugins::{answer_string, clip_json, commands, parse_answer, Description};
+
+#[tokio::test]
+async fn plugin_protocol() {
+    assert_eq!(
+        commands(" /usr/bin/ndi-out ;; python3 /opt/logo.py;"),
+        vec!["/usr/bin/ndi-out", "python3 /opt/logo.py"]
+    );
+
+    let answer = parse_answer("loading\n{\"output_param\": \"-f null -\"}\n\n").unwrap();
+    assert_eq!(answer_string(&answer, "output_param").unwrap(), "-f null -");
+    assert!(answer_string(&answer, "custom_filter").is_err());
+
+    assert!(parse_answer("").is_err());
+    assert!(parse_answer("no json").is_err());
+    assert!(parse_answer(r#"{"error": "no device"}"#).is_err());
+    assert!(parse_answer(r#"{"error": null, "custom_filter": ""}"#).is_ok());
+
+    let description: Description =
+        serde_json::from_str(r#"{"name": "ndi", "outputs": ["ndi"], "events": ["state"]}"#)
+            .unwrap();
+    assert!(!description.clip_filter);
+    assert!(description.wants("state"));
+    assert!(!description.wants("clip_started"));
+    assert!(Description {
+        events: vec!["*".to_string()],
+        ..Default::default()
+    }
+    .wants("clip_started"));
+
+    let mut node = Media::new(0, "assets/media_mix/with_audio.mp4", false).await;
+    node.begin = Some(3600.0);
+    node.custom_filter = "volume=0.5[c_a_out]".to_string();
+
+    let clip = clip_json(&node);
+    assert_eq!(clip["source"], "assets/media_mix/with_audio.mp4");
+    assert_eq!(clip["begin"], 3600.0);
+    assert_eq!(clip["custom_filter"], "volume=0.5[c_a_out]");
+}
@@ -0,0 +1,118 @@
+use chrono::TimeDelta;
+
+use ffplayout::db::models;
+use ffplayout::utils::{
+    as_run::{export_range, AsRunReport},
+    uptime::{build_report, month_range},
+};
+
+#[test]
+fn monthly_availability() {
+    let event = |time: &str, on_air: bool, cause: &str| models::ChannelEvent {
+        id: 0,
+        channel_id: 1,
+        time: time.to_string(),
+        on_air,
+        cause: cause.to_string(),
+    };
+
+    let (start, end) = month_range("2024-02").unwrap();
+    assert_eq!(end - start, TimeDelta::days(29));
+    assert!(month_range("2024-13").is_none());
+
+    let events = vec![
+        event("2024-02-10 12:00:00.000", false, "Connection refused"),
+        event("2024-02-10 12:00:30.000", false, "Broken pipe"),
+        event("2024-02-10 12:10:00.000", true, "reconnected"),
+    ];
+    let initial = Some(event("2024-01-20 08:00:00.000", true, "started"));
+    let report = build_report(1, "2024-02", start, end, initial, &events);
+
+    assert_eq!(report.off_air, 600.0);
+    assert_eq!(report.on_air, 29.0 * 86400.0 - 600.0);
+    assert_eq!(report.availability, 99.98);
+    assert_eq!(report.outages.len(), 1);
+    assert_eq!(report.outages[0].cause, "Connection refused");
+    assert!(report.to_csv().ends_with(
+        "2024-02-10 12:00:00.000,2024-02-10 12:10:00.000,600.000,\"Connection refused\"\n"
+    ));
+
+    // without event before the month, only the time after the first event counts
+    let report = build_report(1, "2024-02", start, end, None, &events);
+    assert_eq!(report.start, "2024-02-10 12:00:00.000");
+    assert_eq!(report.outages.len(), 1);
+}
+
+#[test]
+fn as_run_totals() {
+    let clip = |started: &str, source: &str, title: Option<&str>, seek: f64, out: f64| {
+        models::PlayHistory {
+            started: started.to_string(),
+            source: source.to_string(),
+            title: title.map(|t| t.to_string()),
+            seek,
+            out,
+            duration: out,
+            ..Default::default()
+        }
+    };
+
+    let items = vec![
+        clip(
+            "2024-10-15 10:00:00.000",
+            "/tv/news.mp4",
+            Some("News, daily"),
+            0.0,
+            600.0,
+        ),
+        clip("2024-10-15 10:10:00.000", "/tv/spot_a.mp4", None, 5.0, 35.0),
+        clip(
+            "2024-10-15 10:10:30.000",
+            "/tv/news.mp4",
+            Some("News, daily"),
+            0.0,
+            300.0,
+        ),
+    ];
+    let report = AsRunReport::new(
+        "Channel 1".to_string(),
+        "2024-10-15 00:00:00.000".to_string(),
+        "2024-10-16 00:00:00.000".to_string(),
+        items,
+    );
+
+    assert_eq!(report.airtime(), 930.0);
+    assert_eq!(report.totals[0].program, "News, daily");
+    assert_eq!(report.totals[0].count, 2);
+    assert_eq!(report.totals[0].duration, 900.0);
+    assert_eq!(report.totals[1].program, "spot_a");
+
+    let csv = report.to_csv();
+    assert!(csv.contains("\"News, daily\",2,900.000\n"));
+    assert!(csv.contains("spot_a,/tv/spot_a.mp4,,5.000,35.000,30.000\n"));
+
+    let pdf = report.to_pdf();
+    assert!(pdf.starts_with(b"%PDF-1.4"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+}
+
+#[test]
+fn as_run_export_range() {
+    assert_eq!(
+        export_range(&["2024-10-15".to_string()]),
+        Ok((
+            "2024-10-15 00:00:00.000".to_string(),
+            "2024-10-16 00:00:00.000".to_string()
+        ))
+    );
+    assert_eq!(
+        export_range(&["2024-10-01T06:00:00".to_string(), "2024-11-01".to_string()]),
+        Ok((
+            "2024-10-01 06:00:00.000".to_string(),
+            "2024-11-01 00:00:00.000".to_string()
+        ))
+    );
+    assert!(export_range(&["2024-10-15".to_string(), "2024-10-14".to_string()]).is_err());
+    assert!(export_range(&["15.10.2024".to_string()]).is_err());
+    assert!(export_range(&[]).is_err());
+}
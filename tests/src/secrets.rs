@@ -0,0 +1,51 @@
+use ffplayout::utils::{
+    config::PlayoutConfig,
+    secrets::{redact, restore, SecretKey, MASK},
+};
+
+#[test]
+fn redact_and_restore_secrets() {
+    let param = "-f flv rtmp://a.rtmp.youtube.com/live2/abcd-1234 -f mpegts srt://127.0.0.1:40051?mode=caller&passphrase=secret123";
+    let redacted = redact(param);
+
+    assert!(!redacted.contains("abcd-1234"));
+    assert!(!redacted.contains("secret123"));
+    assert!(redacted.contains(&format!("live2/{MASK}")));
+    assert_eq!(restore(&redacted, param), param);
+
+    let changed = redacted.replace("live2", "live3");
+    assert_eq!(restore(&changed, param), param.replace("live2", "live3"));
+}
+
+#[test]
+fn encrypt_secrets() {
+    let key = SecretKey::new("my-global-secret");
+    let encrypted = key.encrypt("passphrase=secret123").unwrap();
+
+    assert_ne!(encrypted, "passphrase=secret123");
+    assert_eq!(key.decrypt(&encrypted).unwrap(), "passphrase=secret123");
+    assert_eq!(key.decrypt("plain").unwrap(), "plain");
+    assert!(SecretKey::new("other-secret").decrypt(&encrypted).is_err());
+}
+
+#[test]
+fn ingest_stream_key_secret() {
+    let mut config = PlayoutConfig::default();
+    config.redact();
+
+    // an empty key stays empty, the ingest server listens itself
+    assert!(config.ingest.stream_key.is_empty());
+
+    config.ingest.stream_key = "my-secret-key".to_string();
+    let stored = config.clone();
+    config.redact();
+
+    assert_eq!(config.ingest.stream_key, MASK);
+
+    config.restore_secrets(&stored);
+    assert_eq!(config.ingest.stream_key, "my-secret-key");
+
+    config.ingest.stream_key = "new-key".to_string();
+    config.restore_secrets(&stored);
+    assert_eq!(config.ingest.stream_key, "new-key");
+}
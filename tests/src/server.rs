@@ -0,0 +1,131 @@
+use std::path::Path;
+
+use ffplayout::utils::{
+    config::PublicServer,
+    public_server::{accepts_gzip, allowed_origin, cache_control, gzip},
+    share::{encode_path, share_link, sign, verify, ShareQuery},
+};
+use ffplayout::vec_strings;
+
+#[test]
+fn public_server_headers() {
+    let origins = vec_strings!["https://player.example.org/", "https://www.example.org"];
+
+    assert_eq!(
+        allowed_origin(&origins, "https://player.example.org"),
+        Some("https://player.example.org".to_string())
+    );
+    assert_eq!(
+        allowed_origin(&origins, "https://WWW.example.org"),
+        Some("https://WWW.example.org".to_string())
+    );
+    assert_eq!(allowed_origin(&origins, "https://evil.example.org"), None);
+    assert_eq!(
+        allowed_origin(&vec_strings!["*"], "https://any.org"),
+        Some("*".to_string())
+    );
+    assert_eq!(allowed_origin(&[], "https://any.org"), None);
+
+    let settings = PublicServer::default();
+    let parsed: PublicServer = serde_json::from_str("{}").unwrap();
+
+    assert_eq!(parsed, settings);
+    assert!(settings.ranges);
+    assert_eq!(
+        cache_control(&settings, Path::new("live/stream.M3U8")),
+        Some("no-cache")
+    );
+    assert_eq!(
+        cache_control(&settings, Path::new("live/stream0.ts")),
+        Some("max-age=60")
+    );
+    assert_eq!(cache_control(&settings, Path::new("live/index.html")), None);
+
+    assert!(accepts_gzip("deflate, gzip;q=0.8"));
+    assert!(accepts_gzip("*"));
+    assert!(!accepts_gzip("gzip;q=0, br"));
+    assert!(!accepts_gzip("identity"));
+
+    let data = b"#EXTM3U\n".repeat(100);
+    let packed = gzip(&data).unwrap();
+    assert!(packed.len() < data.len());
+    assert_eq!(&packed[..2], &[0x1f, 0x8b]);
+}
+
+#[test]
+fn share_link_signature() {
+    let secret = "global-secret";
+    let link = share_link(secret, 1, "/clips/new promo.mp4", 2000);
+
+    assert_eq!(link.expires, 2000);
+    assert!(link
+        .url
+        .starts_with("/share/1/clips/new%20promo.mp4?expires=2000&signature="));
+    assert_eq!(encode_path("a/ü&b.mp4"), "a/%C3%BC%26b.mp4");
+
+    let query = |expires: i64, signature: String| ShareQuery {
+        expires,
+        signature,
+        download: false,
+    };
+    let signature = sign(secret, 1, "clips/new promo.mp4", 2000);
+
+    assert!(verify(
+        secret,
+        1,
+        "clips/new promo.mp4",
+        &query(2000, signature.clone()),
+        1000
+    )
+    .is_ok());
+    // expired
+    assert!(verify(
+        secret,
+        1,
+        "clips/new promo.mp4",
+        &query(2000, signature.clone()),
+        2001
+    )
+    .is_err());
+    // other file, channel, time or secret
+    assert!(verify(
+        secret,
+        1,
+        "clips/other.mp4",
+        &query(2000, signature.clone()),
+        1000
+    )
+    .is_err());
+    assert!(verify(
+        secret,
+        2,
+        "clips/new promo.mp4",
+        &query(2000, signature.clone()),
+        1000
+    )
+    .is_err());
+    assert!(verify(
+        secret,
+        1,
+        "clips/new promo.mp4",
+        &query(9000, signature.clone()),
+        1000
+    )
+    .is_err());
+    assert!(verify(
+        "new-secret",
+        1,
+        "clips/new promo.mp4",
+        &query(2000, signature),
+        1000
+    )
+    .is_err());
+    assert!(verify(
+        secret,
+        1,
+        "clips/new promo.mp4",
+        &query(2000, "!!".to_string()),
+        1000
+    )
+    .is_err());
+}
@@ -0,0 +1,45 @@
+use std::collections::VecDeque;
+
+use ffplayout::player::utils::Media;
+use ffplayout::sse::{broadcast::replay_events, ClipEvent};
+
+#[test]
+fn clip_event_delay() {
+    let mut node = Media {
+        begin: Some(86399.0),
+        index: Some(3),
+        title: Some("Late Show".to_string()),
+        ..Default::default()
+    };
+
+    let event = ClipEvent::new(1, &node, 0.0, 1.5, String::new());
+
+    assert_eq!(event.index, Some(3));
+    assert_eq!(event.title.as_deref(), Some("Late Show"));
+    assert_eq!(event.scheduled, Some(86399.0));
+    // started after midnight
+    assert_eq!(event.delay, Some(2.5));
+
+    // time shift moves the schedule
+    let event = ClipEvent::new(1, &node, 100.0, 86300.0, String::new());
+    assert_eq!(event.delay, Some(1.0));
+
+    node.begin = None;
+    assert_eq!(
+        ClipEvent::new(1, &node, 0.0, 10.0, String::new()).delay,
+        None
+    );
+}
+
+#[test]
+fn sse_replay() {
+    let buffer: VecDeque<(u64, String)> = (1..=5).map(|i| (i, format!("clip {i}"))).collect();
+
+    assert_eq!(
+        replay_events(&buffer, 3),
+        vec![(4, "clip 4".to_string()), (5, "clip 5".to_string())]
+    );
+    assert!(replay_events(&buffer, 5).is_empty());
+    // the client missed more than the buffer holds
+    assert_eq!(replay_events(&buffer, 0).len(), 5);
+}
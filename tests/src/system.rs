@@ -0,0 +1,291 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serial_test::serial;
+
+use ffplayout::player::utils::Media;
+use ffplayout::utils::{
+    advanced_config::{AdvancedConfig, ProcessConfig},
+    benchmark::{redirect_outputs, BenchmarkResult},
+    clock::{ntp_offset, ntp_time, ClockSource, Discipline},
+    doctor::{ffmpeg_names, ffmpeg_version, ingest_address, service_listen, service_user},
+    output_test::{output_targets, OutputTarget},
+    priority::{parse_cpu_list, parse_io_priority, Priority},
+    systemd::{notify, watchdog_interval},
+    tui::{fit, remaining},
+};
+use ffplayout::vec_strings;
+
+#[test]
+fn playout_clock_discipline() {
+    assert_eq!("".parse::<ClockSource>(), Ok(ClockSource::System));
+    assert_eq!(
+        "ntp://ntp.example.org".parse::<ClockSource>(),
+        Ok(ClockSource::Ntp("ntp.example.org:123".to_string()))
+    );
+    assert_eq!(
+        "ntp://10.0.0.1:1123".parse::<ClockSource>(),
+        Ok(ClockSource::Ntp("10.0.0.1:1123".to_string()))
+    );
+    assert_eq!(
+        "ptp:///dev/ptp0".parse::<ClockSource>(),
+        Ok(ClockSource::Ptp(PathBuf::from("/dev/ptp0")))
+    );
+    assert!("gps://".parse::<ClockSource>().is_err());
+
+    // 1970-01-01 00:00:01.5 in NTP format
+    let mut stamp = (2_208_988_801_u32).to_be_bytes().to_vec();
+    stamp.extend(0x8000_0000_u32.to_be_bytes());
+
+    assert_eq!(ntp_time(&stamp), 1.5);
+
+    // server is 100 ms ahead, 20 ms round trip
+    assert_eq!(ntp_offset(10.0, 10.11, 10.11, 10.02).round(), 100.0);
+
+    let mut discipline = Discipline::default();
+
+    // first measurement is applied at once
+    discipline.measure(400.0);
+    assert_eq!(discipline.offset, 400.0);
+
+    // small changes are slewed
+    discipline.measure(410.0);
+    discipline.tick(4.0);
+    assert_eq!(discipline.offset, 402.0);
+    discipline.tick(100.0);
+    assert_eq!(discipline.offset, 410.0);
+
+    // big changes are stepped
+    discipline.measure(-2000.0);
+    assert_eq!(discipline.offset, -2000.0);
+
+    // system clock jumps 5 seconds ahead, playout time stays
+    discipline.system_step(5000.0);
+    discipline.tick(1.0);
+    assert_eq!(discipline.offset, -7000.0);
+}
+
+#[test]
+fn process_priority_values() {
+    assert_eq!(parse_cpu_list("0-3,8"), Ok(vec![0, 1, 2, 3, 8]));
+    assert_eq!(parse_cpu_list(" 2, 1,2 "), Ok(vec![1, 2]));
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("a").is_err());
+
+    assert_eq!(parse_io_priority("best-effort:4"), Ok((2 << 13) | 4));
+    assert_eq!(parse_io_priority("idle"), Ok(3 << 13));
+    assert!(parse_io_priority("realtime:8").is_err());
+    assert!(parse_io_priority("fast").is_err());
+
+    let mut config = AdvancedConfig::default();
+    assert!(config.validate().is_ok());
+
+    config.process = ProcessConfig {
+        nice: Some(-5),
+        cpu_affinity: Some("0-1".to_string()),
+        io_priority: Some("realtime:0".to_string()),
+    };
+
+    let priority = Priority::new(&config.process).unwrap();
+    assert_eq!(priority.cpus, vec![0, 1]);
+    assert_eq!(priority.io, Some(1 << 13));
+
+    config.process.nice = Some(20);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn benchmark_outputs() {
+    let dir = Path::new("/tmp/bench");
+
+    assert_eq!(
+        redirect_outputs(
+            &vec_strings![
+                "-c:v",
+                "libx264",
+                "-f",
+                "flv",
+                "rtmp://localhost/live/stream"
+            ],
+            dir
+        ),
+        vec_strings!["-c:v", "libx264", "-f", "flv", "/tmp/bench/output_0.flv"]
+    );
+    assert_eq!(
+        redirect_outputs(
+            &vec_strings![
+                "-f",
+                "hls",
+                "-hls_segment_filename",
+                "/usr/share/ffplayout/public/live/stream-%d.ts",
+                "/usr/share/ffplayout/public/live/stream.m3u8"
+            ],
+            dir
+        ),
+        vec_strings![
+            "-f",
+            "hls",
+            "-hls_segment_filename",
+            "/tmp/bench/stream-%d.ts",
+            "/tmp/bench/stream.m3u8"
+        ]
+    );
+    assert_eq!(
+        redirect_outputs(
+            &vec_strings!["-f", "tee", "[f=flv]rtmp://a/b|[f=hls]live/x.m3u8"],
+            dir
+        ),
+        vec_strings!["-f", "null", "-"]
+    );
+    assert_eq!(
+        redirect_outputs(&vec_strings!["-f", "null", "-"], dir),
+        vec_strings!["-f", "null", "-"]
+    );
+
+    let result = BenchmarkResult {
+        speed: 1.05,
+        ..Default::default()
+    };
+    assert!(!result.sustainable());
+}
+
+#[test]
+#[serial]
+fn systemd_notify() {
+    std::env::remove_var("NOTIFY_SOCKET");
+    assert!(!notify("READY=1"));
+
+    std::env::set_var("WATCHDOG_USEC", "30000000");
+    std::env::remove_var("WATCHDOG_PID");
+    assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+
+    // the watchdog is for another process
+    std::env::set_var("WATCHDOG_PID", "1");
+    assert_eq!(watchdog_interval(), None);
+
+    std::env::remove_var("WATCHDOG_USEC");
+    std::env::remove_var("WATCHDOG_PID");
+    assert_eq!(watchdog_interval(), None);
+}
+
+#[test]
+fn output_test_targets() {
+    let targets = output_targets(&vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "flv",
+        "rtmp://a/live/key",
+        "-c:v",
+        "libx265",
+        "-f",
+        "mpegts",
+        "srt://b:9000?passphrase=secret"
+    ]);
+
+    assert_eq!(
+        targets,
+        vec![
+            OutputTarget {
+                url: "rtmp://a/live/key".to_string(),
+                cmd: vec_strings!["-c:v", "libx264", "-f", "flv", "rtmp://a/live/key"],
+            },
+            OutputTarget {
+                url: "srt://b:9000?passphrase=secret".to_string(),
+                cmd: vec_strings![
+                    "-c:v",
+                    "libx265",
+                    "-f",
+                    "mpegts",
+                    "srt://b:9000?passphrase=secret"
+                ],
+            },
+        ]
+    );
+
+    let targets = output_targets(&vec_strings![
+        "-c:v",
+        "libx264",
+        "-f",
+        "tee",
+        "[f=flv:onfail=ignore]rtmp://a/live/key|srt://b:9000"
+    ]);
+
+    assert_eq!(targets.len(), 2);
+    assert_eq!(
+        targets[0].cmd,
+        vec_strings!["-c:v", "libx264", "-f", "flv", "rtmp://a/live/key"]
+    );
+    assert_eq!(targets[1].url, "srt://b:9000");
+    assert_eq!(
+        targets[1].cmd,
+        vec_strings!["-c:v", "libx264", "srt://b:9000"]
+    );
+}
+
+#[test]
+fn doctor_parser() {
+    assert_eq!(
+        ffmpeg_version("ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023"),
+        Some((6, 1))
+    );
+    assert_eq!(
+        ffmpeg_version("ffmpeg version n7.0 Copyright"),
+        Some((7, 0))
+    );
+    assert_eq!(
+        ffmpeg_version("ffmpeg version 2024-05-20-git-1234 Copyright"),
+        None
+    );
+    assert_eq!(
+        ffmpeg_version("ffmpeg version N-113093-gabc Copyright"),
+        None
+    );
+
+    let filters = " T.. = Timeline support\n V = Video input/output\n TSC xfade             VV->V      Cross fade one video with another video.\n ... movie             |->N       Read from a movie source.";
+    assert_eq!(ffmpeg_names(filters), vec!["xfade", "movie"]);
+
+    let muxers = " .E = Muxing supported\n --\n  E 3g2             3GP2 (3GPP file format)\n  E mpegts          MPEG-TS (MPEG-2 Transport Stream)";
+    assert_eq!(ffmpeg_names(muxers), vec!["3g2", "mpegts"]);
+
+    let unit = "[Service]\nExecStart=/usr/bin/ffplayout -l 0.0.0.0:8787\nUser=ffpu\n";
+    assert_eq!(service_user(unit), Some("ffpu".to_string()));
+    assert_eq!(service_listen(unit), Some("0.0.0.0:8787".to_string()));
+
+    assert_eq!(
+        ingest_address("-f live_flv -listen 1 -i rtmp://0.0.0.0:1936/live/stream"),
+        Some("0.0.0.0:1936".to_string())
+    );
+    assert_eq!(
+        ingest_address("-f mpegts -i 'srt://0.0.0.0:40077?mode=listener'"),
+        Some("0.0.0.0:40077".to_string())
+    );
+}
+
+#[tokio::test]
+async fn tui_remaining() {
+    let mut node = Media::new(0, "assets/media_mix/with_audio.mp4", false).await;
+    node.seek = 10.0;
+    node.out = 70.0;
+
+    assert_eq!(remaining(&node, 3620.0, 0.0), None);
+
+    node.begin = Some(3600.0);
+    assert_eq!(remaining(&node, 3620.0, 0.0), Some(40.0));
+    assert_eq!(remaining(&node, 3700.0, 0.0), Some(0.0));
+    // time shift moves the begin
+    assert_eq!(remaining(&node, 3620.0, 10.0), Some(30.0));
+
+    node.speed = Some(1.2);
+    assert_eq!(remaining(&node, 3620.0, 0.0), Some(30.0));
+
+    // clip started before midnight
+    node.speed = None;
+    node.begin = Some(86390.0);
+    assert_eq!(remaining(&node, 5.0, 0.0), Some(45.0));
+
+    assert_eq!(fit("clip.mp4", 10), "clip.mp4  ");
+    assert_eq!(fit("a_long_clip_name.mp4", 10), "a_long_cl…");
+}
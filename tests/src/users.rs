@@ -0,0 +1,89 @@
+use sqlx::sqlite::SqlitePoolOptions;
+
+use serial_test::serial;
+
+use ffplayout::db::{handles, models};
+use ffplayout::player::controller::ChannelManager;
+use ffplayout::utils::{
+    config::PlayoutConfig,
+    users::{change_user, list_users, remove_user, UserUpdate},
+};
+
+async fn prepare_config() -> (PlayoutConfig, ChannelManager) {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    handles::db_migrate(&pool).await.unwrap();
+
+    sqlx::query(
+        r#"
+        UPDATE global SET public = "assets/hls", logs = "assets/log", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE channels SET public = "assets/hls", playlists = "assets/playlists", storage = "assets/storage";
+        UPDATE configurations SET processing_width = 1024, processing_height = 576;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let config = PlayoutConfig::new(&pool, 1).await.unwrap();
+    let channel = handles::select_channel(&pool, &1).await.unwrap();
+    let manager = ChannelManager::new(Some(pool), channel, config.clone());
+
+    (config, manager)
+}
+
+#[actix_web::test]
+#[serial]
+async fn user_management() {
+    let (_, manager) = prepare_config().await;
+    let pool = manager.db_pool.clone().unwrap();
+
+    for (name, role_id) in [("admin", 1), ("editor", 3)] {
+        let user = models::User {
+            id: 0,
+            mail: Some(format!("{name}@example.org")),
+            username: name.to_string(),
+            password: "secret".to_string(),
+            role_id: Some(role_id),
+            channel_ids: Some(vec![1]),
+            token: None,
+        };
+
+        handles::insert_user(&pool, user).await.unwrap();
+    }
+
+    let update = UserUpdate {
+        role: Some("channel_admin".to_string()),
+        channels: Some(vec![]),
+        ..Default::default()
+    };
+    change_user(&pool, "editor", update).await.unwrap();
+
+    let editor = handles::select_login(&pool, "editor").await.unwrap();
+    assert_eq!(editor.role_id, Some(2));
+    assert_eq!(editor.mail, Some("editor@example.org".to_string()));
+    assert!(!editor.channel_ids.unwrap_or_default().contains(&1));
+
+    let update = UserUpdate {
+        role: Some("superuser".to_string()),
+        ..Default::default()
+    };
+    assert!(change_user(&pool, "editor", update).await.is_err());
+    assert!(change_user(&pool, "nobody", UserUpdate::default())
+        .await
+        .is_err());
+
+    // the last global admin stays
+    let update = UserUpdate {
+        role: Some("user".to_string()),
+        ..Default::default()
+    };
+    assert!(change_user(&pool, "admin", update).await.is_err());
+    assert!(remove_user(&pool, "admin").await.is_err());
+
+    assert_eq!(list_users(&pool).await.unwrap().len(), 2);
+    remove_user(&pool, "editor").await.unwrap();
+    assert_eq!(list_users(&pool).await.unwrap().len(), 1);
+}
@@ -7,6 +7,8 @@ use ffplayout::db::handles;
 use ffplayout::player::{controller::ChannelManager, utils::*};
 use ffplayout::utils::{
     config::{PlayoutConfig, ProcessMode::Playlist},
+    expand_env, expand_path,
+    logging::fmt_cmd,
     time_machine::{set_mock_time, time_now},
 };
 
@@ -89,3 +91,48 @@ async fn test_delta() {
 
     assert!(delta < 2.0);
 }
+
+#[test]
+fn expand_env_vars() {
+    std::env::set_var("FFPLAYOUT_TEST_KEY", "abc123");
+
+    assert_eq!(
+        expand_env("rtmp://example.org/live/${FFPLAYOUT_TEST_KEY}"),
+        "rtmp://example.org/live/abc123"
+    );
+    assert_eq!(
+        expand_env("-i ${FFPLAYOUT_TEST_MISSING}"),
+        "-i ${FFPLAYOUT_TEST_MISSING}"
+    );
+
+    // values from parameters are masked in the command log, paths not
+    std::env::set_var("FFPLAYOUT_TEST_MEDIA", "/mnt/media");
+
+    let cmd = vec![
+        "-f".to_string(),
+        "flv".to_string(),
+        expand_env("rtmp://example.org/live/${FFPLAYOUT_TEST_KEY}"),
+    ];
+
+    assert_eq!(fmt_cmd(&cmd), "-f flv \"rtmp://example.org/live/********\"");
+    assert_eq!(
+        expand_path("${FFPLAYOUT_TEST_MEDIA}/filler.mp4"),
+        std::path::PathBuf::from("/mnt/media/filler.mp4")
+    );
+    assert_eq!(
+        fmt_cmd(&["-i".to_string(), "/mnt/media/a.mp4".to_string()]),
+        "-i \"/mnt/media/a.mp4\""
+    );
+}
+
+#[test]
+fn dst_change_on_playlist_day() {
+    let timezone = Some("Europe/Berlin".parse().unwrap());
+
+    // playlist starts at 06:00, so the change at night belongs to the day before
+    assert_eq!(dst_change("2024-03-30", 21600.0, &timezone), 3600.0);
+    assert_eq!(dst_change("2024-03-31", 21600.0, &timezone), 0.0);
+    assert_eq!(dst_change("2024-10-26", 21600.0, &timezone), -3600.0);
+    assert_eq!(dst_change("2024-03-31", 0.0, &timezone), 3600.0);
+    assert_eq!(dst_change("2024-05-20", 0.0, &timezone), 0.0);
+}